@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("HTTP Error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// `code` is the node's stable error code, when it sent one
+    #[error("API Error ({status}): {message}")]
+    Api {
+        status: u16,
+        code: Option<String>,
+        message: String,
+    },
+
+    #[error("WebSocket Error: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+
+    #[error("Invalid URL: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("Serialization Error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+}
@@ -0,0 +1,15 @@
+//! Typed async client for the Flow node REST and WebSocket APIs.
+//!
+//! ```ignore
+//! let client = FlowClient::new("http://localhost:8080")?;
+//! let challenge = client.start_registration().await?;
+//! ```
+
+pub mod error;
+pub mod rest;
+pub mod types;
+pub mod ws;
+
+pub use error::ClientError;
+pub use rest::FlowClient;
+pub use ws::FlowSocket;
@@ -0,0 +1,201 @@
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use url::Url;
+use webauthn_rs_proto::{PublicKeyCredential, RegisterPublicKeyCredential};
+
+use crate::error::ClientError;
+use crate::types::{
+    AuthenticationChallenge, AuthenticationResult, Health, ManifestSummary, ManifestVerification,
+    RegistrationChallenge, RegistrationResult, Session, SignedManifest, SummaryComparison,
+};
+
+/// Client for a node's REST API
+#[derive(Debug, Clone)]
+pub struct FlowClient {
+    http: reqwest::Client,
+    base_url: Url,
+}
+
+impl FlowClient {
+    /// Create a client for the node at `base_url`, e.g. `http://localhost:8080`
+    pub fn new(base_url: &str) -> Result<Self, ClientError> {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Create a client reusing an existing `reqwest::Client`
+    pub fn with_http_client(base_url: &str, http: reqwest::Client) -> Result<Self, ClientError> {
+        Ok(Self {
+            http,
+            base_url: Url::parse(base_url)?,
+        })
+    }
+
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    pub async fn health(&self) -> Result<Health, ClientError> {
+        self.send(self.http.get(self.url("/api/v1/health")?)).await
+    }
+
+    pub async fn start_registration(&self) -> Result<RegistrationChallenge, ClientError> {
+        self.send(
+            self.http
+                .get(self.url("/api/v1/webauthn/start_registration")?),
+        )
+        .await
+    }
+
+    pub async fn finish_registration(
+        &self,
+        challenge_id: &str,
+        credential: &RegisterPublicKeyCredential,
+    ) -> Result<RegistrationResult, ClientError> {
+        let body = json!({ "challenge_id": challenge_id, "credential": credential });
+        self.send(
+            self.http
+                .post(self.url("/api/v1/webauthn/finish_registration")?)
+                .json(&body),
+        )
+        .await
+    }
+
+    pub async fn start_authentication(&self) -> Result<AuthenticationChallenge, ClientError> {
+        self.send(
+            self.http
+                .post(self.url("/api/v1/webauthn/start_authentication")?),
+        )
+        .await
+    }
+
+    pub async fn finish_authentication(
+        &self,
+        challenge_id: &str,
+        credential: &PublicKeyCredential,
+    ) -> Result<AuthenticationResult, ClientError> {
+        let body = json!({ "challenge_id": challenge_id, "credential": credential });
+        self.send(
+            self.http
+                .post(self.url("/api/v1/webauthn/finish_authentication")?)
+                .json(&body),
+        )
+        .await
+    }
+
+    /// The session a token from [`Self::finish_authentication`] belongs to
+    pub async fn session(&self, token: &str) -> Result<Session, ClientError> {
+        self.send(
+            self.http
+                .get(self.url("/api/v1/auth/session")?)
+                .bearer_auth(token),
+        )
+        .await
+    }
+
+    /// Sign out, revoking the session token
+    pub async fn logout(&self, token: &str) -> Result<(), ClientError> {
+        let _: serde_json::Value = self
+            .send(
+                self.http
+                    .post(self.url("/api/v1/auth/logout")?)
+                    .bearer_auth(token),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Register a directory on the node as a space, signed in with a token
+    /// from [`Self::finish_authentication`]
+    pub async fn create_space(&self, token: &str, dir: &str) -> Result<(), ClientError> {
+        let _: serde_json::Value = self
+            .send(
+                self.http
+                    .post(self.url("/api/v1/spaces")?)
+                    .bearer_auth(token)
+                    .json(&json!({ "dir": dir })),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Fetch the signed checksum manifest of a folder in a space
+    pub async fn space_manifest(
+        &self,
+        key: &str,
+        path: Option<&str>,
+    ) -> Result<SignedManifest, ClientError> {
+        let mut url = self.url(&format!("/api/v1/spaces/{}/manifest", key))?;
+        if let Some(path) = path {
+            url.query_pairs_mut().append_pair("path", path);
+        }
+        self.send(self.http.get(url)).await
+    }
+
+    /// Check a manifest against the current contents of a space
+    pub async fn verify_space_manifest(
+        &self,
+        key: &str,
+        manifest: &SignedManifest,
+    ) -> Result<ManifestVerification, ClientError> {
+        self.send(
+            self.http
+                .post(self.url(&format!("/api/v1/spaces/{}/manifest/verify", key))?)
+                .json(manifest),
+        )
+        .await
+    }
+
+    /// Fetch the summary of a folder in a space used to negotiate a sync
+    pub async fn space_manifest_summary(
+        &self,
+        key: &str,
+        path: Option<&str>,
+    ) -> Result<ManifestSummary, ClientError> {
+        let mut url = self.url(&format!("/api/v1/spaces/{}/manifest/summary", key))?;
+        if let Some(path) = path {
+            url.query_pairs_mut().append_pair("path", path);
+        }
+        self.send(self.http.get(url)).await
+    }
+
+    /// Find which subtrees of a space differ from another copy's summary
+    pub async fn compare_space_manifest(
+        &self,
+        key: &str,
+        summary: &ManifestSummary,
+    ) -> Result<SummaryComparison, ClientError> {
+        self.send(
+            self.http
+                .post(self.url(&format!("/api/v1/spaces/{}/manifest/compare", key))?)
+                .json(summary),
+        )
+        .await
+    }
+
+    fn url(&self, path: &str) -> Result<Url, ClientError> {
+        Ok(self.base_url.join(path)?)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ClientError> {
+        let response = request.send().await?;
+        Self::parse(response).await
+    }
+
+    async fn parse<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+        let status = response.status();
+        if !status.is_success() {
+            // Errors are `{"code", "message", "detail"}`; the detail is the
+            // specific reason, the message a localized summary
+            let text = response.text().await.unwrap_or_default();
+            let body: Value = serde_json::from_str(&text).unwrap_or_default();
+            return Err(ClientError::Api {
+                status: status.as_u16(),
+                code: body["code"].as_str().map(str::to_string),
+                message: body["detail"].as_str().map_or(text, str::to_string),
+            });
+        }
+
+        Ok(response.json().await?)
+    }
+}
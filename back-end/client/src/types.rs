@@ -0,0 +1,135 @@
+//! Request and response bodies of the node API.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use webauthn_rs_proto::{CreationChallengeResponse, RequestChallengeResponse};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Health {
+    pub status: String,
+    pub timestamp: String,
+}
+
+/// Response of `GET /api/v1/webauthn/start_registration`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationChallenge {
+    pub challenge: CreationChallengeResponse,
+    pub challenge_id: String,
+}
+
+/// Response of `POST /api/v1/webauthn/finish_registration`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationResult {
+    pub verified: bool,
+    pub message: String,
+    pub did: String,
+    #[serde(rename = "didDocument")]
+    pub did_document: Value,
+}
+
+/// Response of `POST /api/v1/webauthn/start_authentication`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthenticationChallenge {
+    pub challenge: RequestChallengeResponse,
+    pub challenge_id: String,
+}
+
+/// Response of `POST /api/v1/webauthn/finish_authentication`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthenticationResult {
+    pub verified: bool,
+    pub message: String,
+    pub counter: u32,
+    pub backup_state: bool,
+    pub backup_eligible: bool,
+    pub needs_update: bool,
+    /// Bearer token for requests made as the signed-in user
+    #[serde(default)]
+    pub session: Option<SessionGrant>,
+}
+
+/// Session handed out by a successful authentication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionGrant {
+    pub token: String,
+    pub expires_at: String,
+}
+
+/// Response of `GET /api/v1/auth/session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub id: String,
+    pub user_id: i32,
+    pub issued_at: String,
+    pub expires_at: String,
+}
+
+/// A single file in a checksum manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub blake3: String,
+    pub size: u64,
+}
+
+/// Signed checksum manifest of a space folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedManifest {
+    pub schema: String,
+    pub space_key: String,
+    pub path: String,
+    pub generated_at: String,
+    pub signer: String,
+    pub public_key_multibase: String,
+    pub entries: Vec<ManifestEntry>,
+    pub signature: String,
+}
+
+/// Hash of the files under one top-level entry of a summarised folder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubtreeSummary {
+    pub path: String,
+    pub entries: u64,
+    pub hash: String,
+}
+
+/// Bloom filter of a summary's entries, as the node encodes it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SummaryFilter {
+    pub bits: u64,
+    pub hashes: u32,
+    pub data: String,
+}
+
+/// Compact summary of a space folder for sync negotiation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestSummary {
+    pub schema: String,
+    pub space_key: String,
+    pub path: String,
+    pub generated_at: String,
+    pub entries: u64,
+    pub hash: String,
+    pub subtrees: Vec<SubtreeSummary>,
+    pub filter: SummaryFilter,
+}
+
+/// Response of `POST /api/v1/spaces/{key}/manifest/compare`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryComparison {
+    pub in_sync: bool,
+    pub subtrees: Vec<String>,
+    pub missing_on_peer: Vec<String>,
+}
+
+/// Response of `POST /api/v1/spaces/{key}/manifest/verify`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestVerification {
+    pub valid: bool,
+    pub signature_valid: bool,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    pub unexpected: Vec<String>,
+}
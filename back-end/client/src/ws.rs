@@ -0,0 +1,67 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::error::ClientError;
+
+/// Connection to a node's WebSocket API
+pub struct FlowSocket {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl FlowSocket {
+    /// Connect to a WebSocket endpoint, e.g. `ws://localhost:8081/ws`
+    pub async fn connect(url: &str) -> Result<Self, ClientError> {
+        let (stream, _) = tokio_tungstenite::connect_async(url).await?;
+        Ok(Self { stream })
+    }
+
+    /// Send an action and wait for the node's reply.
+    ///
+    /// Replies with `"status": "error"` are returned as `ClientError::Api`.
+    pub async fn request(&mut self, payload: Value) -> Result<Value, ClientError> {
+        self.stream
+            .send(Message::Text(payload.to_string().into()))
+            .await?;
+
+        loop {
+            let message = self.stream.next().await.ok_or_else(|| {
+                ClientError::UnexpectedResponse("Connection closed before reply".to_string())
+            })??;
+
+            match message {
+                Message::Text(text) => {
+                    let reply: Value = serde_json::from_str(&text)?;
+                    if reply["status"] == "error" {
+                        return Err(ClientError::Api {
+                            status: 400,
+                            code: None,
+                            message: reply["message"].as_str().unwrap_or_default().to_string(),
+                        });
+                    }
+                    return Ok(reply);
+                }
+                Message::Close(_) => {
+                    return Err(ClientError::UnexpectedResponse(
+                        "Connection closed before reply".to_string(),
+                    ));
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Register a directory on the node as a space
+    pub async fn create_space(&mut self, dir: &str) -> Result<(), ClientError> {
+        self.request(json!({ "action": "create_space", "dir": dir }))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn close(mut self) -> Result<(), ClientError> {
+        self.stream.close(None).await?;
+        Ok(())
+    }
+}
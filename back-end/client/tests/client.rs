@@ -0,0 +1,148 @@
+use flow_client::{ClientError, FlowClient, FlowSocket};
+use flow_testkit::TestNode;
+use flow_testkit::node::TEST_ORIGIN;
+use url::Url;
+use webauthn_authenticator_rs::AuthenticatorBackend;
+use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+
+async fn setup() -> (TestNode, FlowClient) {
+    let node = TestNode::start("client").await.unwrap();
+    let client = FlowClient::new(&node.url("")).unwrap();
+    (node, client)
+}
+
+#[tokio::test]
+async fn test_health() {
+    let (_node, client) = setup().await;
+
+    let health = client.health().await.unwrap();
+    assert_eq!(health.status, "healthy");
+}
+
+#[tokio::test]
+async fn test_webauthn_ceremonies() {
+    let (_node, client) = setup().await;
+    let origin = Url::parse(TEST_ORIGIN).unwrap();
+    let mut authenticator = SoftPasskey::new(true);
+
+    let challenge = client.start_registration().await.unwrap();
+    let credential = authenticator
+        .perform_register(origin.clone(), challenge.challenge.public_key, 60000)
+        .unwrap();
+    let registration = client
+        .finish_registration(&challenge.challenge_id, &credential)
+        .await
+        .unwrap();
+    assert!(registration.verified);
+    assert!(registration.did.starts_with("did:key:"));
+
+    let challenge = client.start_authentication().await.unwrap();
+    let credential = authenticator
+        .perform_auth(origin, challenge.challenge.public_key, 60000)
+        .unwrap();
+    let authentication = client
+        .finish_authentication(&challenge.challenge_id, &credential)
+        .await
+        .unwrap();
+    assert!(authentication.verified);
+    assert_eq!(authentication.counter, 1);
+
+    let token = authentication.session.unwrap().token;
+    let session = client.session(&token).await.unwrap();
+    assert!(!session.id.is_empty());
+
+    client.logout(&token).await.unwrap();
+    let err = client.session(&token).await.unwrap_err();
+    assert!(
+        matches!(err, ClientError::Api { status: 401, .. }),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_space_manifest_round_trip() {
+    let (node, client) = setup().await;
+    let (key, dir) = node.create_space("docs").await.unwrap();
+    std::fs::write(dir.join("a.txt"), b"hello").unwrap();
+
+    let manifest = client.space_manifest(&key, None).await.unwrap();
+    assert_eq!(manifest.entries.len(), 1);
+    assert_eq!(manifest.entries[0].path, "a.txt");
+
+    let verification = client.verify_space_manifest(&key, &manifest).await.unwrap();
+    assert!(verification.valid);
+
+    std::fs::write(dir.join("a.txt"), b"changed").unwrap();
+    let verification = client.verify_space_manifest(&key, &manifest).await.unwrap();
+    assert!(!verification.valid);
+    assert_eq!(verification.modified, vec!["a.txt".to_string()]);
+}
+
+#[tokio::test]
+async fn test_space_manifest_summary_round_trip() {
+    let (node, client) = setup().await;
+    let (key, dir) = node.create_space("docs").await.unwrap();
+    std::fs::write(dir.join("a.txt"), b"hello").unwrap();
+
+    let summary = client.space_manifest_summary(&key, None).await.unwrap();
+    assert_eq!(summary.entries, 1);
+    assert_eq!(summary.subtrees[0].path, "a.txt");
+
+    let comparison = client.compare_space_manifest(&key, &summary).await.unwrap();
+    assert!(comparison.in_sync);
+
+    std::fs::write(dir.join("b.txt"), b"new").unwrap();
+    let comparison = client.compare_space_manifest(&key, &summary).await.unwrap();
+    assert!(!comparison.in_sync);
+    assert_eq!(comparison.subtrees, vec!["b.txt".to_string()]);
+    assert_eq!(comparison.missing_on_peer, vec!["b.txt".to_string()]);
+}
+
+#[tokio::test]
+async fn test_api_errors_carry_status() {
+    let (_node, client) = setup().await;
+
+    let err = client
+        .space_manifest(&"de".repeat(32), None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ClientError::Api { status: 404, .. }),
+        "Unexpected error: {}",
+        err
+    );
+
+    let err = client.space_manifest("deadbeef", None).await.unwrap_err();
+    assert!(
+        matches!(err, ClientError::Api { status: 422, .. }),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_websocket_create_space() {
+    let (node, client) = setup().await;
+    let dir = tempfile::TempDir::new().unwrap();
+
+    let mut socket = FlowSocket::connect(&node.ws_url()).await.unwrap();
+    socket
+        .create_space(&dir.path().to_string_lossy())
+        .await
+        .unwrap();
+
+    let err = socket
+        .request(serde_json::json!({ "action": "unknown" }))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::Api { .. }));
+
+    socket.close().await.unwrap();
+
+    let (token, _) = node.node.user_sessions().open(1).await.unwrap();
+    client
+        .create_space(&token, &dir.path().to_string_lossy())
+        .await
+        .unwrap();
+}
@@ -0,0 +1,13 @@
+//! Hook for encrypting sensitive columns at rest. The node supplies the
+//! implementation; entities only route values through it.
+
+pub type CipherError = Box<dyn std::error::Error + Send + Sync>;
+
+pub trait ColumnCipher: Send + Sync {
+    /// Encrypt a value of `column` belonging to user `owner`
+    fn encrypt(&self, column: &str, owner: i32, plaintext: &str) -> Result<String, CipherError>;
+
+    /// Decrypt a stored value. Values that were never encrypted (rows written
+    /// before encryption was enabled) are returned unchanged.
+    fn decrypt(&self, column: &str, owner: i32, stored: &str) -> Result<String, CipherError>;
+}
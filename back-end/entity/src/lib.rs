@@ -1,5 +1,9 @@
 pub mod prelude;
 
+pub mod cipher;
+pub mod entity_history;
+pub mod outbox;
 pub mod pass_key;
+pub mod session;
 pub mod space;
 pub mod user;
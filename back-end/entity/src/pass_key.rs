@@ -3,6 +3,11 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::cipher::{CipherError, ColumnCipher};
+
+/// Name `json_data` is encrypted under
+pub const JSON_DATA_COLUMN: &str = "pass_key.json_data";
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "pass_key")]
 pub struct Model {
@@ -22,6 +27,17 @@ pub struct Model {
     #[sea_orm(column_type = "Text")]
     pub json_data: String,
     pub time_created: DateTimeWithTimeZone,
+    /// User verification policy the passkey was registered under
+    pub user_verification: String,
+    pub authenticator_attachment: Option<String>,
+    /// The credential may be synced to other devices
+    pub backup_eligible: bool,
+    /// The credential is currently backed up (synced)
+    pub backup_state: bool,
+    /// Bumped by every edit, so a client editing from a stale copy is refused
+    pub version: i32,
+    /// When it was deleted; it can be restored for a while, then is purged
+    pub deleted_at: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -43,3 +59,19 @@ impl Related<super::user::Entity> for Entity {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Decrypted `json_data`
+    pub fn passkey_json(&self, cipher: &dyn ColumnCipher) -> Result<String, CipherError> {
+        cipher.decrypt(JSON_DATA_COLUMN, self.user_id, &self.json_data)
+    }
+}
+
+/// Encrypt a serialized passkey for storage in `json_data`
+pub fn encrypt_json_data(
+    cipher: &dyn ColumnCipher,
+    user_id: i32,
+    json: &str,
+) -> Result<String, CipherError> {
+    cipher.encrypt(JSON_DATA_COLUMN, user_id, json)
+}
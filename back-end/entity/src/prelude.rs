@@ -1,5 +1,8 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+pub use super::entity_history::Entity as EntityHistory;
+pub use super::outbox::Entity as Outbox;
 pub use super::pass_key::Entity as PassKey;
+pub use super::session::Entity as Session;
 pub use super::space::Entity as Space;
 pub use super::user::Entity as User;
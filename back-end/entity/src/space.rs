@@ -11,6 +11,18 @@ pub struct Model {
     pub key: String,
     pub location: String,
     pub time_created: DateTimeWithTimeZone,
+    /// Owning user in multi-user mode; `None` for node-wide spaces
+    pub user_id: Option<i32>,
+    /// Hex root of the space's Merkle tree; `None` until it is first built
+    pub merkle_root: Option<String>,
+    /// Files written into the space are encrypted at rest
+    pub encrypted: bool,
+    /// Files are encrypted by clients, with keys the node never holds
+    pub client_keys: bool,
+    /// Bumped by every edit, so a client editing from a stale copy is refused
+    pub version: i32,
+    /// When it was deleted; it can be restored for a while, then is purged
+    pub deleted_at: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
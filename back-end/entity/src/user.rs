@@ -12,11 +12,17 @@ pub struct Model {
     pub did: String,
     pub username: String,
     pub display_name: String,
+    /// Picture shown for the user; `None` for none
+    pub avatar_url: Option<String>,
     pub device_ids: String,
     #[sea_orm(column_type = "Text")]
     pub public_key_jwk: String,
     pub time_created: DateTimeWithTimeZone,
     pub last_login: DateTimeWithTimeZone,
+    /// Bumped by every edit, so a client editing from a stale copy is refused
+    pub version: i32,
+    /// When it was deleted; it can be restored for a while, then is purged
+    pub deleted_at: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -5,6 +5,15 @@ pub enum AppError {
     #[error("Authentication Error: {0}")]
     Auth(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Too many requests: {0}")]
+    RateLimited(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
     #[error("Configuration Error: {0}")]
     Config(String),
 
@@ -22,4 +31,19 @@ pub enum AppError {
 
     #[error("Migration failed: {0}")]
     Migration(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Not Found: {0}")]
+    NotFound(String),
+
+    #[error("Validation Error: {0}")]
+    Validation(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Replayed request: {0}")]
+    Replayed(String),
+
+    #[error("In maintenance: {0}")]
+    Maintenance(String),
 }
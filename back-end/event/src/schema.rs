@@ -0,0 +1,370 @@
+//! Versioned payloads of the events the node emits.
+//!
+//! Each [`EventType`] has a payload type here, which publishers build the
+//! event from, and an entry in [`SCHEMAS`] describing it to clients. Every
+//! payload carries the `schema_version` it was built to.
+//!
+//! Compatibility: within a version, a payload only gains optional fields,
+//! and clients ignore fields they don't know. Removing or renaming a field,
+//! changing its type or making it required takes a new version.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::types::{Event, EventType};
+
+/// The compatibility policy, as told to clients
+pub const COMPATIBILITY: &str = "Within a version, payloads only gain optional fields; \
+    clients must ignore fields they don't know. Removing or renaming a field, changing its \
+    type or making it required takes a new version.";
+
+/// Property every payload carries its schema version in
+pub const VERSION_FIELD: &str = "schema_version";
+
+/// Property events dispatched from the outbox carry their outbox ID in.
+/// Such events are delivered at least once; a repeat has the same ID.
+pub const ID_FIELD: &str = "event_id";
+
+/// The payload of one or more event types
+pub trait EventPayload: Serialize + DeserializeOwned {
+    /// Event types carrying this payload
+    const TYPES: &'static [EventType];
+}
+
+/// A file in a space was found created, modified or deleted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChanged {
+    pub space_key: String,
+    /// Path within the space, `/`-separated
+    pub path: String,
+    /// Owner of the space; absent for the node's own spaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i32>,
+}
+
+impl EventPayload for FileChanged {
+    const TYPES: &'static [EventType] = &[
+        EventType::FileCreated,
+        EventType::FileModified,
+        EventType::FileDeleted,
+    ];
+}
+
+/// A peer sent a message no handler claimed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageReceived {
+    /// DID of the sending peer
+    pub peer: String,
+    pub kind: String,
+    #[serde(default)]
+    pub body: Value,
+}
+
+impl EventPayload for MessageReceived {
+    const TYPES: &'static [EventType] = &[EventType::MessageReceived];
+}
+
+/// A user registered their first passkey
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserCreated {
+    pub user_id: i32,
+    pub did: String,
+    pub username: String,
+}
+
+impl EventPayload for UserCreated {
+    const TYPES: &'static [EventType] = &[EventType::UserCreated];
+}
+
+/// A passkey signed in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CredentialVerified {
+    /// Base64url credential ID
+    pub credential_id: String,
+    pub counter: u32,
+}
+
+impl EventPayload for CredentialVerified {
+    const TYPES: &'static [EventType] = &[EventType::CredentialVerified];
+}
+
+/// Failed sign-ins locked out a credential or client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthenticationLocked {
+    /// What is locked out, `credential:<id>` or `network:<prefix>`
+    pub key: String,
+    /// RFC 3339
+    pub locked_until: String,
+}
+
+impl EventPayload for AuthenticationLocked {
+    const TYPES: &'static [EventType] = &[EventType::AuthenticationLocked];
+}
+
+/// A passkey's backup state changed, e.g. it was synced to a new device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CredentialBackupChanged {
+    /// Base64url credential ID
+    pub credential_id: String,
+    pub backup_eligible: bool,
+    pub backup_state: bool,
+    pub previous_backup_state: bool,
+}
+
+impl EventPayload for CredentialBackupChanged {
+    const TYPES: &'static [EventType] = &[EventType::CredentialBackupChanged];
+}
+
+/// A scheduled task's run failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledTaskFailed {
+    pub task_id: String,
+    pub name: String,
+    pub error: String,
+}
+
+impl EventPayload for ScheduledTaskFailed {
+    const TYPES: &'static [EventType] = &[EventType::ScheduledTaskFailed];
+}
+
+/// A key was used in an unusual way
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyUsageAnomaly {
+    /// `passkey` or `session`
+    pub kind: String,
+    pub key_id: String,
+    /// What was unusual; its `type` names the anomaly
+    pub anomaly: Value,
+    /// The key's user; absent for keys of the node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i32>,
+}
+
+impl EventPayload for KeyUsageAnomaly {
+    const TYPES: &'static [EventType] = &[EventType::KeyUsageAnomaly];
+}
+
+/// The node's identity key was rotated, changing its DID
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdentityKeyRotated {
+    pub previous_did: String,
+    pub did: String,
+    /// Version of the new key; the node's first key is version 1
+    pub version: u32,
+}
+
+impl EventPayload for IdentityKeyRotated {
+    const TYPES: &'static [EventType] = &[EventType::IdentityKeyRotated];
+}
+
+/// JSON type of a payload field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    String,
+    Integer,
+    Boolean,
+    Object,
+    /// Any JSON value
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub kind: FieldKind,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+/// The payload of an event type, as clients see it
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EventSchema {
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+    pub version: u32,
+    pub description: &'static str,
+    pub fields: &'static [Field],
+}
+
+const fn field(name: &'static str, kind: FieldKind, description: &'static str) -> Field {
+    Field {
+        name,
+        kind,
+        required: true,
+        description,
+    }
+}
+
+const fn optional(name: &'static str, kind: FieldKind, description: &'static str) -> Field {
+    Field {
+        name,
+        kind,
+        required: false,
+        description,
+    }
+}
+
+const FILE_CHANGED: &[Field] = &[
+    field("space_key", FieldKind::String, "Key of the space"),
+    field(
+        "path",
+        FieldKind::String,
+        "Path within the space, /-separated",
+    ),
+    optional("user_id", FieldKind::Integer, "Owner of the space"),
+];
+
+/// Schemas of every event type, in [`EventType::ALL`] order
+pub const SCHEMAS: [EventSchema; EventType::ALL.len()] = [
+    EventSchema {
+        event_type: EventType::FileCreated,
+        version: 1,
+        description: "A file in a space was found created",
+        fields: FILE_CHANGED,
+    },
+    EventSchema {
+        event_type: EventType::FileModified,
+        version: 1,
+        description: "A file in a space was found modified",
+        fields: FILE_CHANGED,
+    },
+    EventSchema {
+        event_type: EventType::FileDeleted,
+        version: 1,
+        description: "A file in a space was found deleted",
+        fields: FILE_CHANGED,
+    },
+    EventSchema {
+        event_type: EventType::MessageReceived,
+        version: 1,
+        description: "A peer sent a message no handler claimed",
+        fields: &[
+            field("peer", FieldKind::String, "DID of the sending peer"),
+            field("kind", FieldKind::String, "What the message is"),
+            field("body", FieldKind::Any, "Message body"),
+        ],
+    },
+    EventSchema {
+        event_type: EventType::UserCreated,
+        version: 1,
+        description: "A user registered their first passkey",
+        fields: &[
+            field("user_id", FieldKind::Integer, "ID of the user"),
+            field("did", FieldKind::String, "DID of the user"),
+            field("username", FieldKind::String, "Name of the user"),
+        ],
+    },
+    EventSchema {
+        event_type: EventType::CredentialVerified,
+        version: 1,
+        description: "A passkey signed in",
+        fields: &[
+            field(
+                "credential_id",
+                FieldKind::String,
+                "Base64url credential ID",
+            ),
+            field("counter", FieldKind::Integer, "Signature counter"),
+        ],
+    },
+    EventSchema {
+        event_type: EventType::AuthenticationLocked,
+        version: 1,
+        description: "Failed sign-ins locked out a credential or client",
+        fields: &[
+            field("key", FieldKind::String, "What is locked out"),
+            field(
+                "locked_until",
+                FieldKind::String,
+                "RFC 3339 end of the lockout",
+            ),
+        ],
+    },
+    EventSchema {
+        event_type: EventType::CredentialBackupChanged,
+        version: 1,
+        description: "A passkey's backup state changed",
+        fields: &[
+            field(
+                "credential_id",
+                FieldKind::String,
+                "Base64url credential ID",
+            ),
+            field(
+                "backup_eligible",
+                FieldKind::Boolean,
+                "Passkey may be backed up",
+            ),
+            field("backup_state", FieldKind::Boolean, "Passkey is backed up"),
+            field(
+                "previous_backup_state",
+                FieldKind::Boolean,
+                "Backup state before the change",
+            ),
+        ],
+    },
+    EventSchema {
+        event_type: EventType::ScheduledTaskFailed,
+        version: 1,
+        description: "A scheduled task's run failed",
+        fields: &[
+            field("task_id", FieldKind::String, "ID of the task"),
+            field("name", FieldKind::String, "Name of the task"),
+            field("error", FieldKind::String, "Why the run failed"),
+        ],
+    },
+    EventSchema {
+        event_type: EventType::KeyUsageAnomaly,
+        version: 1,
+        description: "A key was used in an unusual way",
+        fields: &[
+            field("kind", FieldKind::String, "passkey or session"),
+            field("key_id", FieldKind::String, "ID of the key"),
+            field("anomaly", FieldKind::Object, "What was unusual"),
+            optional("user_id", FieldKind::Integer, "The key's user"),
+        ],
+    },
+    EventSchema {
+        event_type: EventType::IdentityKeyRotated,
+        version: 1,
+        description: "The node's identity key was rotated, changing its DID",
+        fields: &[
+            field("previous_did", FieldKind::String, "DID before the rotation"),
+            field("did", FieldKind::String, "DID of the new key"),
+            field("version", FieldKind::Integer, "Version of the new key"),
+        ],
+    },
+];
+
+/// Schema of `event_type`
+pub fn schema(event_type: EventType) -> &'static EventSchema {
+    SCHEMAS
+        .iter()
+        .find(|schema| schema.event_type == event_type)
+        .expect("every event type has a schema")
+}
+
+impl Event {
+    /// Event of `event_type` carrying `payload`, stamped with its schema
+    /// version
+    pub fn from_payload<P: EventPayload>(event_type: EventType, payload: &P) -> Self {
+        debug_assert!(P::TYPES.contains(&event_type));
+        let Ok(Value::Object(properties)) = serde_json::to_value(payload) else {
+            panic!("event payloads serialize to JSON objects");
+        };
+        Event::new(event_type)
+            .with_properties(properties)
+            .with(VERSION_FIELD, schema(event_type).version)
+    }
+
+    /// The payload of this event, if it is one of `P`'s types
+    pub fn payload<P: EventPayload>(&self) -> Option<Result<P, serde_json::Error>> {
+        P::TYPES.contains(&self.event_type).then(|| {
+            let properties = self.properties.clone().into_iter().collect();
+            serde_json::from_value(Value::Object(properties))
+        })
+    }
+}
@@ -2,7 +2,7 @@ use std::error::Error;
 
 use crate::types::Event;
 
-pub trait EventListener {
+pub trait EventListener: Send + Sync {
     fn handle(&self, event: &Event) -> Result<(), Box<dyn Error>>;
 }
 
@@ -1,13 +1,100 @@
-use serde_json::Value;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum EventType {
     FileCreated,
     FileModified,
     FileDeleted,
+    MessageReceived,
+    UserCreated,
+    CredentialVerified,
+    AuthenticationLocked,
+    CredentialBackupChanged,
+    ScheduledTaskFailed,
+    KeyUsageAnomaly,
+    IdentityKeyRotated,
 }
 
+impl EventType {
+    pub const ALL: [EventType; 11] = [
+        EventType::FileCreated,
+        EventType::FileModified,
+        EventType::FileDeleted,
+        EventType::MessageReceived,
+        EventType::UserCreated,
+        EventType::CredentialVerified,
+        EventType::AuthenticationLocked,
+        EventType::CredentialBackupChanged,
+        EventType::ScheduledTaskFailed,
+        EventType::KeyUsageAnomaly,
+        EventType::IdentityKeyRotated,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::FileCreated => "file_created",
+            EventType::FileModified => "file_modified",
+            EventType::FileDeleted => "file_deleted",
+            EventType::MessageReceived => "message_received",
+            EventType::UserCreated => "user_created",
+            EventType::CredentialVerified => "credential_verified",
+            EventType::AuthenticationLocked => "authentication_locked",
+            EventType::CredentialBackupChanged => "credential_backup_changed",
+            EventType::ScheduledTaskFailed => "scheduled_task_failed",
+            EventType::KeyUsageAnomaly => "key_usage_anomaly",
+            EventType::IdentityKeyRotated => "identity_key_rotated",
+        }
+    }
+}
+
+impl FromStr for EventType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.as_str() == s)
+            .ok_or_else(|| format!("Unknown event type: {}", s))
+    }
+}
+
+impl Serialize for EventType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Event {
     pub event_type: EventType,
     pub properties: HashMap<String, Value>,
 }
+
+impl Event {
+    pub fn new(event_type: EventType) -> Self {
+        Self {
+            event_type,
+            properties: HashMap::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.properties.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn with_properties(mut self, properties: Map<String, Value>) -> Self {
+        self.properties.extend(properties);
+        self
+    }
+}
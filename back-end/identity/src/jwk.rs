@@ -0,0 +1,131 @@
+//! JWKs for the public keys WebAuthn authenticators hand out
+
+use serde_cbor::Value;
+use ssi::jwk::{Algorithm, Base64urlUInt, ECParams, JWK, OctetParams, Params};
+use thiserror::Error;
+
+/// COSE algorithm identifiers (RFC 9053)
+const COSE_ES256: i128 = -7;
+const COSE_EDDSA: i128 = -8;
+
+/// COSE_Key map labels (RFC 9052 section 7.1, RFC 9053 section 7)
+const LABEL_ALG: i128 = 3;
+const LABEL_X: i128 = -2;
+const LABEL_Y: i128 = -3;
+
+#[derive(Debug, Error)]
+pub enum JwkError {
+    #[error("Invalid COSE key: {0}")]
+    InvalidCoseKey(String),
+
+    #[error("Unsupported COSE algorithm: {0}")]
+    UnsupportedAlgorithm(i128),
+}
+
+/// Verification JWK of a P-256 key (ES256)
+pub fn p256_jwk(x: Vec<u8>, y: Vec<u8>) -> JWK {
+    verification_jwk(
+        Params::EC(ECParams {
+            curve: Some("P-256".to_string()),
+            x_coordinate: Some(Base64urlUInt(x)),
+            y_coordinate: Some(Base64urlUInt(y)),
+            ecc_private_key: None,
+        }),
+        Algorithm::ES256,
+    )
+}
+
+/// Verification JWK of an Ed25519 key (EdDSA)
+pub fn ed25519_jwk(public_key: Vec<u8>) -> JWK {
+    verification_jwk(
+        Params::OKP(OctetParams {
+            curve: "Ed25519".to_string(),
+            public_key: Base64urlUInt(public_key),
+            private_key: None,
+        }),
+        Algorithm::EdDSA,
+    )
+}
+
+/// JWK of a CBOR encoded COSE_Key, as found in a WebAuthn attestation's
+/// authenticator data
+pub fn cose_to_jwk(cose_key: &[u8]) -> Result<JWK, JwkError> {
+    let Value::Map(map) =
+        serde_cbor::from_slice(cose_key).map_err(|e| JwkError::InvalidCoseKey(e.to_string()))?
+    else {
+        return Err(JwkError::InvalidCoseKey("not a map".to_string()));
+    };
+    let integer = |label: i128| match map.get(&Value::Integer(label)) {
+        Some(Value::Integer(value)) => Ok(*value),
+        _ => Err(JwkError::InvalidCoseKey(format!("missing label {}", label))),
+    };
+    let bytes = |label: i128| match map.get(&Value::Integer(label)) {
+        Some(Value::Bytes(value)) => Ok(value.clone()),
+        _ => Err(JwkError::InvalidCoseKey(format!("missing label {}", label))),
+    };
+
+    match integer(LABEL_ALG)? {
+        COSE_ES256 => Ok(p256_jwk(bytes(LABEL_X)?, bytes(LABEL_Y)?)),
+        COSE_EDDSA => Ok(ed25519_jwk(bytes(LABEL_X)?)),
+        alg => Err(JwkError::UnsupportedAlgorithm(alg)),
+    }
+}
+
+fn verification_jwk(params: Params, algorithm: Algorithm) -> JWK {
+    JWK {
+        params,
+        public_key_use: Some("sig".to_string()),
+        key_operations: Some(vec!["verify".to_string()]),
+        algorithm: Some(algorithm),
+        key_id: None,
+        x509_url: None,
+        x509_certificate_chain: None,
+        x509_thumbprint_sha1: None,
+        x509_thumbprint_sha256: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn cose_key(entries: Vec<(i128, Value)>) -> Vec<u8> {
+        let map: BTreeMap<Value, Value> = entries
+            .into_iter()
+            .map(|(label, value)| (Value::Integer(label), value))
+            .collect();
+        serde_cbor::to_vec(&Value::Map(map)).unwrap()
+    }
+
+    #[test]
+    fn test_cose_to_jwk_ed25519() {
+        let key = cose_key(vec![
+            (1, Value::Integer(1)),
+            (LABEL_ALG, Value::Integer(COSE_EDDSA)),
+            (-1, Value::Integer(6)),
+            (LABEL_X, Value::Bytes(vec![7u8; 32])),
+        ]);
+
+        let jwk = cose_to_jwk(&key).unwrap();
+
+        assert_eq!(jwk.algorithm, Some(Algorithm::EdDSA));
+        assert!(matches!(jwk.params, Params::OKP(ref okp) if okp.public_key.0 == vec![7u8; 32]));
+    }
+
+    #[test]
+    fn test_cose_to_jwk_rejects_unsupported_keys() {
+        let rs256 = cose_key(vec![(LABEL_ALG, Value::Integer(-257))]);
+        let no_y = cose_key(vec![
+            (LABEL_ALG, Value::Integer(COSE_ES256)),
+            (LABEL_X, Value::Bytes(vec![1u8; 32])),
+        ]);
+
+        assert!(matches!(
+            cose_to_jwk(&rs256),
+            Err(JwkError::UnsupportedAlgorithm(-257))
+        ));
+        assert!(cose_to_jwk(&no_y).is_err());
+        assert!(cose_to_jwk(b"garbage").is_err());
+    }
+}
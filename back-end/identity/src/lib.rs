@@ -0,0 +1,10 @@
+//! Identity primitives shared by the node and its web front-ends: did:peer
+//! generation and resolution, JWKs from WebAuthn keys, and signed space
+//! manifests.
+//!
+//! Nothing here does I/O or needs a runtime, so the crate builds for
+//! `wasm32-unknown-unknown` as well as natively.
+
+pub mod jwk;
+pub mod manifest;
+pub mod peer;
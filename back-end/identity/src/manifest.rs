@@ -0,0 +1,115 @@
+//! Signed checksum manifests of space folders
+//!
+//! The format and its signature. Building a manifest from the files of a
+//! space, and checking one against them, is up to the node.
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use errors::AppError;
+use multibase::Base;
+use serde::{Deserialize, Serialize};
+
+pub const MANIFEST_SCHEMA: &str = "flow-manifest/v1";
+
+/// A single file in a checksum manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the manifest root, always `/`-separated
+    pub path: String,
+    /// Hex encoded BLAKE3 hash of the file contents
+    pub blake3: String,
+    /// File size in bytes
+    pub size: u64,
+}
+
+/// Checksum manifest of a space folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub schema: String,
+    pub space_key: String,
+    /// Folder within the space the manifest covers ("" for the space root)
+    pub path: String,
+    pub generated_at: String,
+    /// DID of the signing node
+    pub signer: String,
+    /// Multibase (base58btc, ed25519-pub multicodec) encoded signing key
+    pub public_key_multibase: String,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Manifest together with the node's Ed25519 signature
+///
+/// The signature covers the canonical JSON form of the manifest
+/// (keys sorted, no whitespace, `signature` field excluded).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedManifest {
+    #[serde(flatten)]
+    pub manifest: Manifest,
+    pub signature: String,
+}
+
+/// Verify the manifest signature against `trusted_key`, the multibase key
+/// of the node expected to have signed it. The key the manifest carries
+/// proves nothing by itself: anyone can re-sign a manifest with their own.
+pub fn verify_signature(signed: &SignedManifest, trusted_key: &str) -> Result<bool, AppError> {
+    if signed.manifest.public_key_multibase != trusted_key {
+        return Ok(false);
+    }
+    verify_with_key(
+        trusted_key,
+        &canonical_bytes(&signed.manifest)?,
+        &signed.signature,
+    )
+}
+
+/// Multibase key a `did:key` signer signs with
+pub fn did_key_public_key(did: &str) -> Result<&str, AppError> {
+    did.strip_prefix("did:key:")
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| AppError::Validation(format!("Expected a did:key signer: {}", did)))
+}
+
+/// Multibase (base58btc, ed25519-pub multicodec) form of a public key
+pub fn encode_public_key(key: &VerifyingKey) -> String {
+    let mut multicodec_key = vec![0xed, 0x01];
+    multicodec_key.extend_from_slice(key.as_bytes());
+    multibase::encode(Base::Base58Btc, &multicodec_key)
+}
+
+/// Multibase (base58btc) form of a signature
+pub fn encode_signature(signature: &Signature) -> String {
+    multibase::encode(Base::Base58Btc, signature.to_bytes())
+}
+
+/// Check a multibase signature over `message` against a multibase
+/// ed25519-pub key
+pub fn verify_with_key(
+    public_key_multibase: &str,
+    message: &[u8],
+    signature: &str,
+) -> Result<bool, AppError> {
+    let (_, key_bytes) = multibase::decode(public_key_multibase)
+        .map_err(|e| AppError::Validation(format!("Invalid public key encoding: {}", e)))?;
+
+    let raw_key: [u8; 32] = key_bytes
+        .strip_prefix(&[0xed, 0x01])
+        .and_then(|k| k.try_into().ok())
+        .ok_or_else(|| AppError::Validation("Expected an Ed25519 public key".to_string()))?;
+    let verifying_key = VerifyingKey::from_bytes(&raw_key)
+        .map_err(|e| AppError::Validation(format!("Invalid public key: {}", e)))?;
+
+    let (_, sig_bytes) = multibase::decode(signature)
+        .map_err(|e| AppError::Validation(format!("Invalid signature encoding: {}", e)))?;
+    let signature = Signature::from_slice(&sig_bytes)
+        .map_err(|e| AppError::Validation(format!("Invalid signature: {}", e)))?;
+
+    Ok(verifying_key.verify(message, &signature).is_ok())
+}
+
+/// Stable JSON encoding of a signed document
+pub fn canonical_bytes<T: Serialize>(document: &T) -> Result<Vec<u8>, AppError> {
+    // serde_json::Value keeps object keys sorted, giving a stable encoding
+    let value = serde_json::to_value(document)
+        .map_err(|e| AppError::Crypto(format!("Failed to serialize document: {}", e)))?;
+    serde_json::to_vec(&value)
+        .map_err(|e| AppError::Crypto(format!("Failed to serialize document: {}", e)))
+}
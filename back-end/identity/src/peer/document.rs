@@ -71,6 +71,12 @@ fn create_verification_method(
     // Encode key appropriately
     if vm_type == "JsonWebKey2020" {
         // For P-256, use JWK format
+        if method.public_key.len() != 64 {
+            return Err(PeerDidError::InvalidEncoding(
+                "P-256 key must be 64 bytes (x || y)".to_string(),
+            ));
+        }
+
         let jwk = serde_json::json!({
             "kty": "EC",
             "crv": "P-256",
@@ -0,0 +1,161 @@
+use super::error::PeerDidError;
+
+/// Generate did:peer from raw public keys
+pub struct PeerDidGenerator;
+
+impl PeerDidGenerator {
+    /// Generate did:peer:0 from Ed25519 public key bytes
+    pub fn from_ed25519_bytes(public_key: &[u8]) -> Result<String, PeerDidError> {
+        if public_key.len() != 32 {
+            return Err(PeerDidError::InvalidEncoding(
+                "Ed25519 key must be 32 bytes".to_string(),
+            ));
+        }
+
+        // Multicodec prefix for Ed25519: 0xed01
+        let mut multicodec_key = vec![0xed, 0x01];
+        multicodec_key.extend_from_slice(public_key);
+
+        // Encode as base58btc
+        let encoded = multibase::encode(multibase::Base::Base58Btc, &multicodec_key);
+
+        Ok(format!("did:peer:0{}", encoded))
+    }
+
+    /// Generate did:peer:0 from X25519 public key bytes
+    pub fn from_x25519_bytes(public_key: &[u8]) -> Result<String, PeerDidError> {
+        if public_key.len() != 32 {
+            return Err(PeerDidError::InvalidEncoding(
+                "X25519 key must be 32 bytes".to_string(),
+            ));
+        }
+
+        // Multicodec prefix for X25519: 0xec01
+        let mut multicodec_key = vec![0xec, 0x01];
+        multicodec_key.extend_from_slice(public_key);
+
+        let encoded = multibase::encode(multibase::Base::Base58Btc, &multicodec_key);
+
+        Ok(format!("did:peer:0{}", encoded))
+    }
+
+    /// Generate did:peer:0 from the x and y coordinates of a P-256 key
+    /// (WebAuthn ES256)
+    pub fn from_p256_coordinates(x: &[u8], y: &[u8]) -> Result<String, PeerDidError> {
+        // For P-256, combine x and y coordinates (32 bytes each)
+        let mut public_key = Vec::with_capacity(64);
+        public_key.extend_from_slice(x);
+        public_key.extend_from_slice(y);
+
+        // Multicodec prefix for P-256: 0x8024
+        let mut multicodec_key = vec![0x80, 0x24];
+        multicodec_key.extend_from_slice(&public_key);
+
+        let encoded = multibase::encode(multibase::Base::Base58Btc, &multicodec_key);
+
+        Ok(format!("did:peer:0{}", encoded))
+    }
+
+    /// Generate did:peer:2 with multiple keys (advanced)
+    ///
+    /// This allows creating a did:peer with separate keys for:
+    /// - Verification/Authentication (signing)
+    /// - Key Agreement (encryption)
+    ///
+    /// Useful for DIDComm where you need both signing and encryption keys.
+    pub fn generate_numalgo2(
+        verification_keys: Vec<Vec<u8>>,
+        encryption_keys: Vec<Vec<u8>>,
+    ) -> Result<String, PeerDidError> {
+        let mut parts = vec!["did:peer:2".to_string()];
+
+        // Add verification keys (transform: E)
+        for key in verification_keys {
+            let encoded = Self::encode_key_with_prefix('E', &key, 0xed)?;
+            parts.push(format!(".{}", encoded));
+        }
+
+        // Add encryption keys (transform: V)
+        for key in encryption_keys {
+            let encoded = Self::encode_key_with_prefix('V', &key, 0xec)?;
+            parts.push(format!(".{}", encoded));
+        }
+
+        Ok(parts.join(""))
+    }
+
+    /// Short form (numalgo 3) of a did:peer:2
+    ///
+    /// The short form is `did:peer:3` followed by the base58btc multibase of
+    /// the sha2-256 multihash of everything after `did:peer:2`.
+    pub fn to_numalgo3(did: &str) -> Result<String, PeerDidError> {
+        use sha2::{Digest, Sha256};
+
+        let encoded = did
+            .strip_prefix("did:peer:2")
+            .filter(|rest| !rest.is_empty())
+            .ok_or(PeerDidError::InvalidFormat)?;
+
+        // Multihash: sha2-256 code, digest length, digest
+        let mut multihash = vec![0x12, 0x20];
+        multihash.extend_from_slice(&Sha256::digest(encoded.as_bytes()));
+
+        let hash = multibase::encode(multibase::Base::Base58Btc, &multihash);
+
+        Ok(format!("did:peer:3{}", hash))
+    }
+
+    /// Helper: Encode key with multicodec and transform prefix
+    fn encode_key_with_prefix(
+        transform: char,
+        public_key: &[u8],
+        multicodec_prefix: u8,
+    ) -> Result<String, PeerDidError> {
+        let mut multicodec_key = vec![multicodec_prefix, 0x01];
+        multicodec_key.extend_from_slice(public_key);
+
+        let encoded = multibase::encode(multibase::Base::Base58Btc, &multicodec_key);
+
+        Ok(format!("{}{}", transform, encoded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_from_ed25519_bytes() {
+        // Test vector: 32-byte Ed25519 public key
+        let public_key = [
+            0x11, 0xa9, 0x80, 0x01, 0x82, 0xb1, 0x0a, 0xb7, 0xd5, 0x4b, 0xfe, 0xd3, 0xc9, 0x64,
+            0x07, 0x3a, 0x0e, 0xe1, 0x72, 0xf3, 0xda, 0xa6, 0x23, 0x25, 0xaf, 0x02, 0x1a, 0x68,
+            0xf7, 0x07, 0x51, 0x1a,
+        ];
+
+        let did = PeerDidGenerator::from_ed25519_bytes(&public_key).unwrap();
+
+        assert!(did.starts_with("did:peer:0z"));
+        assert!(did.len() > 20);
+
+        println!("Generated did:peer: {}", did);
+    }
+
+    #[test]
+    fn test_generate_from_x25519_bytes() {
+        let public_key = [0u8; 32]; // Dummy key for testing
+
+        let did = PeerDidGenerator::from_x25519_bytes(&public_key).unwrap();
+
+        assert!(did.starts_with("did:peer:0z"));
+    }
+
+    #[test]
+    fn test_invalid_key_length() {
+        let short_key = [0u8; 16]; // Too short
+
+        let result = PeerDidGenerator::from_ed25519_bytes(&short_key);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,25 @@
+//! did:peer numalgo 0, 2 and 3
+
+mod document;
+mod error;
+mod generator;
+mod parser;
+
+use document::create_did_document;
+pub use error::PeerDidError;
+pub use generator::PeerDidGenerator;
+use parser::ParsedPeerDid;
+
+/// Parse a did:peer and build its DID document without any I/O
+pub fn peer_did_document(did: &str) -> Result<ssi::dids::Document, PeerDidError> {
+    create_did_document(did, ParsedPeerDid::parse(did)?)
+}
+
+/// DID document of the did:peer:2 `long_form` under the id of its short
+/// form `did`
+pub fn short_form_document(
+    did: &str,
+    long_form: &str,
+) -> Result<ssi::dids::Document, PeerDidError> {
+    create_did_document(did, ParsedPeerDid::parse(long_form)?)
+}
@@ -115,7 +115,7 @@ impl ParsedPeerDid {
             }
 
             let transform = part.chars().next().ok_or(PeerDidError::InvalidFormat)?;
-            let value = &part[1..];
+            let value = &part[transform.len_utf8()..];
 
             match transform {
                 'E' => {
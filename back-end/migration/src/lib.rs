@@ -3,6 +3,18 @@ pub use sea_orm_migration::prelude::*;
 mod m20250811_140008_create_space;
 mod m20251001_170115_create_user;
 mod m20251001_171250_create_passkey;
+mod m20251020_120000_create_session;
+mod m20251022_090000_add_passkey_verification_policy;
+mod m20251023_090000_add_passkey_backup_state;
+mod m20251024_090000_add_space_user_id;
+mod m20251026_090000_add_space_merkle_root;
+mod m20251027_090000_add_space_encrypted;
+mod m20251028_090000_create_outbox;
+mod m20251029_090000_add_row_versions;
+mod m20251030_090000_add_user_avatar_url;
+mod m20251031_090000_add_deleted_at;
+mod m20251101_090000_create_entity_history;
+mod m20251102_090000_add_space_client_keys;
 
 pub struct Migrator;
 
@@ -13,6 +25,18 @@ impl MigratorTrait for Migrator {
             Box::new(m20250811_140008_create_space::Migration),
             Box::new(m20251001_170115_create_user::Migration),
             Box::new(m20251001_171250_create_passkey::Migration),
+            Box::new(m20251020_120000_create_session::Migration),
+            Box::new(m20251022_090000_add_passkey_verification_policy::Migration),
+            Box::new(m20251023_090000_add_passkey_backup_state::Migration),
+            Box::new(m20251024_090000_add_space_user_id::Migration),
+            Box::new(m20251026_090000_add_space_merkle_root::Migration),
+            Box::new(m20251027_090000_add_space_encrypted::Migration),
+            Box::new(m20251028_090000_create_outbox::Migration),
+            Box::new(m20251029_090000_add_row_versions::Migration),
+            Box::new(m20251030_090000_add_user_avatar_url::Migration),
+            Box::new(m20251031_090000_add_deleted_at::Migration),
+            Box::new(m20251101_090000_create_entity_history::Migration),
+            Box::new(m20251102_090000_add_space_client_keys::Migration),
         ]
     }
 }
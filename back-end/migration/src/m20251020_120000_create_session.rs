@@ -0,0 +1,65 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Session::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Session::Id))
+                    .col(string(Session::Kind).not_null())
+                    .col(string(Session::Key).not_null())
+                    .col(text(Session::Data).not_null())
+                    .col(timestamp_with_time_zone(Session::ExpiresAt).not_null())
+                    .col(timestamp_with_time_zone(Session::TimeCreated).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // One live record per (kind, key)
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_session_kind_key")
+                    .table(Session::Table)
+                    .col(Session::Kind)
+                    .col(Session::Key)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Sweeps delete by expiry
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_session_expires_at")
+                    .table(Session::Table)
+                    .col(Session::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Session::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Session {
+    Table,
+    Id,
+    Kind,
+    Key,
+    Data,
+    ExpiresAt,
+    TimeCreated,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing passkeys were all registered with user verification required
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .add_column(
+                        string(PassKey::UserVerification)
+                            .default("required")
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .add_column(string_null(PassKey::AuthenticatorAttachment))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .drop_column(PassKey::AuthenticatorAttachment)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .drop_column(PassKey::UserVerification)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PassKey {
+    Table,
+    UserVerification,
+    AuthenticatorAttachment,
+}
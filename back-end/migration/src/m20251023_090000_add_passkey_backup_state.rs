@@ -0,0 +1,55 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing rows are filled in from the passkey JSON on their next authentication
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .add_column(boolean(PassKey::BackupEligible).default(false))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .add_column(boolean(PassKey::BackupState).default(false))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .drop_column(PassKey::BackupState)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PassKey::Table)
+                    .drop_column(PassKey::BackupEligible)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PassKey {
+    Table,
+    BackupEligible,
+    BackupState,
+}
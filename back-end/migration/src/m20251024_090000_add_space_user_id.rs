@@ -0,0 +1,56 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Spaces created before multi-user mode belong to no user; only the
+        // node itself sees them when multi-user mode is on
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Space::Table)
+                    .add_column(integer_null(Space::UserId))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_space_user_id")
+                    .table(Space::Table)
+                    .col(Space::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_space_user_id")
+                    .table(Space::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Space::Table)
+                    .drop_column(Space::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Space {
+    Table,
+    UserId,
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite alters one column per statement; existing rows start at 1
+        for table in [
+            User::Table.into_iden(),
+            Space::Table.into_iden(),
+            PassKey::Table.into_iden(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .add_column(integer(Version::Version).default(1))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [
+            User::Table.into_iden(),
+            Space::Table.into_iden(),
+            PassKey::Table.into_iden(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .drop_column(Version::Version)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum Space {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum PassKey {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum Version {
+    Version,
+}
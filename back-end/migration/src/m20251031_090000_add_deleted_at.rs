@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite alters one column per statement
+        for table in [
+            User::Table.into_iden(),
+            Space::Table.into_iden(),
+            PassKey::Table.into_iden(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .add_column(timestamp_with_time_zone_null(DeletedAt::DeletedAt))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [
+            User::Table.into_iden(),
+            Space::Table.into_iden(),
+            PassKey::Table.into_iden(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .drop_column(DeletedAt::DeletedAt)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum Space {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum PassKey {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum DeletedAt {
+    DeletedAt,
+}
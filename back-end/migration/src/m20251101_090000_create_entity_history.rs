@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EntityHistory::Table)
+                    .if_not_exists()
+                    .col(pk_auto(EntityHistory::Id))
+                    .col(string(EntityHistory::Entity).not_null())
+                    .col(string(EntityHistory::EntityId).not_null())
+                    .col(integer_null(EntityHistory::OwnerId))
+                    .col(string(EntityHistory::Change).not_null())
+                    .col(text_null(EntityHistory::Before))
+                    .col(text_null(EntityHistory::After))
+                    .col(timestamp_with_time_zone(EntityHistory::TimeCreated).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // History is read one entity at a time
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_entity_history_entity")
+                    .table(EntityHistory::Table)
+                    .col(EntityHistory::Entity)
+                    .col(EntityHistory::EntityId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EntityHistory::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EntityHistory {
+    Table,
+    Id,
+    Entity,
+    EntityId,
+    OwnerId,
+    Change,
+    Before,
+    After,
+    TimeCreated,
+}
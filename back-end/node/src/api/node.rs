@@ -1,67 +1,1088 @@
-use crate::bootstrap::init::NodeData;
-use crate::modules::space;
+use crate::bootstrap::config::{
+    ClockConfig, DidBatchConfig, KeyUsageConfig, MaintenanceConfig, MultiUserConfig,
+    ResourceConfig, RetentionConfig, TelemetryConfig,
+};
+use crate::bootstrap::init::{self, KEYSTORE_DIR, NodeData};
+use crate::modules::account::{
+    self, AccountDeletions, AccountExport, Deletion, Erasure, UserProfile,
+};
+use crate::modules::anchor::{self, AnchorLog, SpaceAnchor};
+use crate::modules::archive::Archive;
+use crate::modules::attestation::{self, NodeAttestation};
+use crate::modules::clock::TimeSource;
+use crate::modules::column_crypto::ColumnKeys;
+use crate::modules::history::{self, EntityChange};
+use crate::modules::key_rotation::SignedRotation;
+use crate::modules::key_usage::{Anomaly, KeyUsage, KeyUse};
+use crate::modules::maintenance::Maintenance;
+use crate::modules::manifest::{self, Manifest, ManifestVerification, SignedManifest};
+use crate::modules::manifest_summary::{self, ManifestSummary, SummaryComparison};
+use crate::modules::merkle::{MerkleIndex, MerkleNode};
+use crate::modules::mtls::PeerIdentity;
+use crate::modules::outbox::{self, Outbox};
+use crate::modules::peer_request::{self, ReplayCache, Target};
+use crate::modules::pin::LocalPin;
+use crate::modules::profile::{self, ProfileUpdate};
+use crate::modules::resources::{self, ResourceUsage, Tasks};
+use crate::modules::retention::{self, PurgeReport};
+use crate::modules::scheduler::{self, Scheduler};
+use crate::modules::secrets::{Secret, SecretVault, SpaceKey};
+use crate::modules::session::SessionStore;
+use crate::modules::settings::SettingsStore;
+use crate::modules::soft_delete::{self, DeletedPurge};
+use crate::modules::space::{self, Encryption, SpaceScan, SpaceWrites};
+use crate::modules::space_crypto::SpaceCipher;
+use crate::modules::ssi::did::DidResolver;
+use crate::modules::ssi::vc::{self, CredentialRequest, IssuedCredential, VerifiableCredential};
 use crate::modules::ssi::webauthn;
+use crate::modules::ssi::webauthn::auth::CeremonyError;
+use crate::modules::ssi::webauthn::fingerprint::ClientFingerprint;
+use crate::modules::ssi::webauthn::lockout::{self, AttemptKey, AuthGuard};
+use crate::modules::ssi::webauthn::options::{CeremonyOptions, CeremonyOverrides};
+use crate::modules::ssi::webauthn::passkeys::{self, PasskeySummary};
 use crate::modules::ssi::webauthn::state::AuthState;
+use crate::modules::storage_stats::{self, StorageStats};
+use crate::modules::telemetry::Telemetry;
+use crate::modules::tenancy::{Actor, ActorKv, UserSession, UserSessions};
+use crate::modules::timestamp::{self, TimestampClient, TimestampProof};
+use crate::modules::updater::{self, UpdateStatus};
+use crate::modules::webhook::WebhookStore;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use errors::AppError;
-use log::info;
-use sea_orm::DatabaseConnection;
+use event::schema::{
+    AuthenticationLocked, CredentialVerified, FileChanged, IdentityKeyRotated, KeyUsageAnomaly,
+};
+use event::source::{EventListener, EventListenerManager};
+use event::types::{Event, EventType};
+use log::{info, warn};
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use sled::Db;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock as StdRwLock};
+use std::time::Duration;
 use webauthn_rs::prelude::CreationChallengeResponse;
 use webauthn_rs::prelude::{
     AuthenticationResult, PublicKeyCredential, RegisterPublicKeyCredential,
     RequestChallengeResponse,
 };
+use zeroize::Zeroizing;
 
 #[derive(Clone)]
 pub struct Node {
     pub node_data: NodeData,
+    /// Config dir holding auth.json and the keystore; nodes without one
+    /// can't rotate their identity key
+    pub home: Option<PathBuf>,
+    /// Scheduled exports and backups are written here, relative to `home`
+    pub export_dir: PathBuf,
     pub db: DatabaseConnection,
     pub kv: Db,
     pub auth_state: AuthState,
+    /// Clock expiry checks go by; set with [`Node::set_time`]
+    pub time: TimeSource,
+    /// How old a signed peer request may be
+    pub peer_replay_window: Duration,
+    pub sessions: SessionStore,
+    /// Application settings, read through to the KV store
+    pub settings: SettingsStore,
+    /// Registered outgoing webhooks; clones share their cache
+    pub webhooks: WebhookStore,
+    /// Keys for columns encrypted at rest
+    pub column_keys: ColumnKeys,
+    /// Listeners notified of node events (e.g. script hooks)
+    pub events: Arc<StdRwLock<EventListenerManager>>,
+    /// Events committed with database changes, waiting to be published
+    pub outbox: Outbox,
+    /// Per-user isolation; off by default
+    pub multi_user: MultiUserConfig,
+    /// Only published resources are open without a session; off by default
+    pub guest_mode: bool,
+    /// Shared so its cache is too
+    pub did_resolver: Arc<DidResolver>,
+    /// Limits of batch resolution; the resolver has the parallelism too
+    pub did_batch: DidBatchConfig,
+    /// Writes in flight to spaces, so manifests see none half done
+    pub space_writes: SpaceWrites,
+    /// Timestamp authority anchors are countersigned by; none by default
+    pub timestamps: Option<TimestampClient>,
+    /// SHA-256 of the TSA certificates timestamps are trusted from
+    pub trusted_timestamps: Vec<[u8; 32]>,
+    pub key_usage: KeyUsageConfig,
+    pub retention: RetentionConfig,
+    /// Soft limits on what the node uses
+    pub resources: ResourceConfig,
+    /// Opt-in usage telemetry; off by default
+    pub telemetry: TelemetryConfig,
+    /// Writes are refused while on; clones share it
+    pub maintenance: Maintenance,
+    /// Background tasks running, per subsystem
+    pub tasks: Tasks,
 }
 
 impl Node {
     pub fn new(node_data: NodeData, db: DatabaseConnection, kv: Db, auth_state: AuthState) -> Self {
         Node {
+            column_keys: ColumnKeys::from_identity(&node_data.private_key),
             node_data,
+            home: None,
+            export_dir: PathBuf::from(scheduler::EXPORT_DIR),
+            time: TimeSource::system(),
+            peer_replay_window: ClockConfig::default().replay_window,
+            sessions: SessionStore::new(db.clone()),
+            outbox: Outbox::new(db.clone()),
+            settings: SettingsStore::new(kv.clone()),
+            webhooks: WebhookStore::new(kv.clone()),
             db,
             kv,
             auth_state,
+            events: Arc::new(StdRwLock::new(EventListenerManager::new())),
+            multi_user: MultiUserConfig::default(),
+            guest_mode: false,
+            did_resolver: Arc::new(DidResolver::new()),
+            did_batch: DidBatchConfig::default(),
+            space_writes: SpaceWrites::new(),
+            timestamps: None,
+            trusted_timestamps: Vec::new(),
+            key_usage: KeyUsageConfig::default(),
+            retention: RetentionConfig::default(),
+            resources: ResourceConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            maintenance: Maintenance::new(&MaintenanceConfig::default()),
+            tasks: Tasks::new(),
+        }
+    }
+
+    /// Read the time from `time`, in the session store too
+    pub fn set_time(&mut self, time: TimeSource) {
+        self.sessions = SessionStore::with_time(self.db.clone(), time.clone());
+        self.time = time;
+    }
+
+    pub fn subscribe(&self, listener: Box<dyn EventListener>) {
+        self.events
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .subscribe(listener);
+    }
+
+    /// Notify listeners; a failing listener is logged, never propagated
+    pub fn publish(&self, event: &Event) {
+        let events = self.events.read().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = events.publish(event) {
+            warn!("Event listener failed on {}: {}", event.event_type, e);
         }
     }
 
     pub async fn create_space(&self, dir: &str) -> Result<(), AppError> {
+        self.create_space_as(Actor::Node, dir).await
+    }
+
+    /// Create a space owned by `actor`, within their space quota
+    pub async fn create_space_as(&self, actor: Actor, dir: &str) -> Result<(), AppError> {
+        self.create_space_with(actor, dir, Encryption::None).await
+    }
+
+    /// Create a space owned by `actor`, its files encrypted as `encryption`
+    /// says
+    pub async fn create_space_with(
+        &self,
+        actor: Actor,
+        dir: &str,
+        encryption: Encryption,
+    ) -> Result<(), AppError> {
         info!("Setting up space in Directory: {}", dir);
-        space::new_space(&self.db, dir).await?;
+        if let (Actor::User(_), Some(max)) = (actor, self.multi_user.max_spaces)
+            && space::list_spaces_for(&self.db, actor).await?.len() as u64 >= max
+        {
+            return Err(AppError::Forbidden(format!(
+                "Space quota of {} reached",
+                max
+            )));
+        }
+        let space = space::new_space(
+            &self.db,
+            dir,
+            actor,
+            encryption,
+            self.multi_user.spaces_root.as_deref(),
+        )
+        .await?;
+        if space.encrypted && self.secrets().get::<SpaceKey>(&space.key)?.is_none() {
+            let key = SpaceCipher::generate_key();
+            self.secrets().put(
+                &space.key,
+                &SpaceKey(Secret::new(URL_SAFE_NO_PAD.encode(key.as_ref()))),
+            )?;
+        }
+        if let Some(cipher) = self
+            .space_cipher(&space)?
+            .filter(|_| encryption == Encryption::Node)
+        {
+            // Files put there before encryption was turned on; asking again
+            // finishes a pass that was cut short
+            let _write = self.space_writes.begin(&space.key);
+            let root = PathBuf::from(&space.location);
+            let count = tokio::task::spawn_blocking(move || cipher.encrypt_existing(&root))
+                .await
+                .map_err(|e| AppError::IO(std::io::Error::other(e)))??;
+            if count > 0 {
+                info!("Encrypted {} existing files of space {}", count, space.key);
+            }
+        }
         Ok(())
     }
 
+    /// Cipher for the files of `space`; `None` unless it is encrypted
+    pub fn space_cipher(
+        &self,
+        space: &entity::space::Model,
+    ) -> Result<Option<SpaceCipher>, AppError> {
+        if !space.encrypted {
+            return Ok(None);
+        }
+        let missing =
+            || AppError::Crypto(format!("Key of encrypted space {} is missing", space.key));
+        let encoded = self
+            .secrets()
+            .get::<SpaceKey>(&space.key)?
+            .ok_or_else(missing)?;
+        let key: Zeroizing<[u8; 32]> = Zeroizing::new(
+            URL_SAFE_NO_PAD
+                .decode(encoded.0.expose())
+                .ok()
+                .and_then(|bytes| Zeroizing::new(bytes).as_slice().try_into().ok())
+                .ok_or_else(missing)?,
+        );
+        Ok(Some(SpaceCipher::new(&space.key, &key)))
+    }
+
+    /// Contents of a file in a space `actor` may see, decrypted if the space
+    /// is encrypted
+    pub async fn read_space_file(
+        &self,
+        actor: Actor,
+        key: &str,
+        path: &str,
+    ) -> Result<Vec<u8>, AppError> {
+        let space = self.find_space_as(actor, key).await?;
+        let file = space::resolve_file(std::path::Path::new(&space.location), path)?;
+        if !file.is_file() {
+            return Err(AppError::NotFound(format!(
+                "No file at {} in space {}",
+                path, key
+            )));
+        }
+        let cipher = self.space_cipher(&space)?;
+        let path = path.to_string();
+        tokio::task::spawn_blocking(move || match cipher {
+            Some(cipher) => cipher.read(&path, &file),
+            None => Ok(std::fs::read(&file)?),
+        })
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+    }
+
+    /// A folder of a space `actor` may see, listed for a tar archive. Its
+    /// files are read as the archive is streamed.
+    pub async fn space_archive(
+        &self,
+        actor: Actor,
+        key: &str,
+        path: &str,
+    ) -> Result<Archive, AppError> {
+        let space = self.find_space_as(actor, key).await?;
+        let root = PathBuf::from(&space.location);
+        let folder = manifest::resolve_folder(&root, path)?;
+        if !space::resolves_within(&root.canonicalize()?, &folder) {
+            return Err(AppError::Forbidden(format!(
+                "Folder leads outside the space: {}",
+                path
+            )));
+        }
+        let cipher = self.space_cipher(&space)?;
+        let sub_path = path.to_string();
+        tokio::task::spawn_blocking(move || {
+            Archive::of_folder(&space.key, &folder, &sub_path, cipher)
+        })
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+    }
+
+    /// A space `actor` may see
+    pub async fn find_space_as(
+        &self,
+        actor: Actor,
+        key: &str,
+    ) -> Result<entity::space::Model, AppError> {
+        space::find_space_for(&self.db, key, actor).await
+    }
+
+    /// Build and sign a checksum manifest for a folder in a space
+    pub async fn space_manifest(&self, key: &str, path: &str) -> Result<SignedManifest, AppError> {
+        let unsigned = self.build_space_manifest(key, path).await?;
+        manifest::sign_manifest(unsigned, &self.node_data)
+    }
+
+    /// Summary of a folder in a space for sync negotiation
+    pub async fn space_manifest_summary(
+        &self,
+        key: &str,
+        path: &str,
+    ) -> Result<ManifestSummary, AppError> {
+        let manifest = self.build_space_manifest(key, path).await?;
+        Ok(manifest_summary::summarize(&manifest))
+    }
+
+    /// Find which parts of a space differ from a peer's summary of it
+    pub async fn compare_space_summary(
+        &self,
+        key: &str,
+        summary: &ManifestSummary,
+    ) -> Result<SummaryComparison, AppError> {
+        if summary.space_key != key {
+            return Err(AppError::Validation(
+                "Summary was generated for a different space".to_string(),
+            ));
+        }
+        let manifest = self.build_space_manifest(key, &summary.path).await?;
+        manifest_summary::compare(&manifest, summary)
+    }
+
+    /// A space's Merkle tree
+    pub fn merkle_index(&self, key: &str) -> Result<MerkleIndex, AppError> {
+        MerkleIndex::open(&self.kv, key)
+    }
+
+    /// Rebuild a space's Merkle tree from its files and record the new root
+    pub async fn rebuild_space_merkle(&self, key: &str) -> Result<String, AppError> {
+        let manifest = self.build_space_manifest(key, "").await?;
+        let index = self.merkle_index(key)?;
+        let root = tokio::task::spawn_blocking(move || index.rebuild(&manifest.entries))
+            .await
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))??;
+
+        space::set_merkle_root(&self.db, key, &root).await?;
+        info!("Rebuilt Merkle tree of space {}: {}", key, root);
+        Ok(root)
+    }
+
+    /// A node of a space's Merkle tree, which is built on first use
+    pub async fn space_merkle_node(&self, key: &str, prefix: &str) -> Result<MerkleNode, AppError> {
+        if space::find_space(&self.db, key)
+            .await?
+            .merkle_root
+            .is_none()
+        {
+            self.rebuild_space_merkle(key).await?;
+        }
+        self.merkle_index(key)?.node(prefix)
+    }
+
+    /// Update a space's Merkle tree, if it has one, after a file was written
+    pub async fn record_space_file(
+        &self,
+        key: &str,
+        path: &str,
+        file: &std::path::Path,
+    ) -> Result<(), AppError> {
+        let space = space::find_space(&self.db, key).await?;
+        if space.merkle_root.is_none() {
+            return Ok(());
+        }
+
+        let cipher = self.space_cipher(&space)?;
+        let (path, file) = (path.to_string(), file.to_path_buf());
+        let entry = tokio::task::spawn_blocking(move || {
+            manifest::file_entry(&path, &file, cipher.as_ref())
+        })
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))??;
+        let root = self.merkle_index(key)?.upsert(&entry)?;
+        space::set_merkle_root(&self.db, key, &root).await
+    }
+
+    async fn build_space_manifest(&self, key: &str, path: &str) -> Result<Manifest, AppError> {
+        let space = space::find_space(&self.db, key).await?;
+        let folder = manifest::resolve_folder(&PathBuf::from(&space.location), path)?;
+        let cipher = self.space_cipher(&space)?;
+
+        let space_key = space.key.clone();
+        let sub_path = path.to_string();
+        let signer = self.node_data.id.clone();
+        let writes = self.space_writes.clone();
+        tokio::task::spawn_blocking(move || {
+            writes.read_consistent(&space_key, || {
+                manifest::build_manifest(&space_key, &folder, &sub_path, &signer, cipher.as_ref())
+            })
+        })
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+    }
+
+    /// Check a signed manifest against the current contents of a space
+    pub async fn verify_space_manifest(
+        &self,
+        key: &str,
+        signed: SignedManifest,
+    ) -> Result<ManifestVerification, AppError> {
+        let space = space::find_space(&self.db, key).await?;
+        if signed.manifest.space_key != space.key {
+            return Err(AppError::Validation(
+                "Manifest was generated for a different space".to_string(),
+            ));
+        }
+
+        let folder =
+            manifest::resolve_folder(&PathBuf::from(&space.location), &signed.manifest.path)?;
+
+        // Only manifests this node signed verify
+        let trusted_key =
+            manifest::encode_public_key(&manifest::signing_key(&self.node_data)?.verifying_key());
+        let cipher = self.space_cipher(&space)?;
+        let writes = self.space_writes.clone();
+        tokio::task::spawn_blocking(move || {
+            writes.read_consistent(&space.key, || {
+                manifest::verify_manifest(&signed, &folder, &trusted_key, cipher.as_ref())
+            })
+        })
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+    }
+
+    /// Issue a Verifiable Credential about `subject_did`, signed by the node
+    pub fn issue_credential(
+        &self,
+        subject_did: &str,
+        claims: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<IssuedCredential, AppError> {
+        self.issue_credential_with(CredentialRequest {
+            subject: subject_did.to_string(),
+            claims,
+            ..Default::default()
+        })
+    }
+
+    /// Issue a credential with more than claims: extra types, an expiry
+    pub fn issue_credential_with(
+        &self,
+        request: CredentialRequest,
+    ) -> Result<IssuedCredential, AppError> {
+        vc::issue(&self.node_data, request)
+    }
+
+    /// Check a JSON-LD credential's proof and validity period, allowing for
+    /// the configured clock skew
+    pub fn verify_credential(&self, credential: &VerifiableCredential) -> Result<(), AppError> {
+        vc::verify_credential_at(credential, &self.time)
+    }
+
+    /// Check a JWT credential's signature and validity period, allowing for
+    /// the configured clock skew
+    pub fn verify_credential_jwt(&self, token: &str) -> Result<VerifiableCredential, AppError> {
+        vc::verify_jwt_at(token, &self.time)
+    }
+
+    /// Sign a request to another node with the node identity key, for the
+    /// [`peer_request::SIGNATURE_HEADER`]
+    pub fn sign_peer_request(&self, target: &Target<'_>) -> Result<String, AppError> {
+        peer_request::sign(&self.node_data, target, self.time.now())
+    }
+
+    /// Check a request from another node was signed for `target` by the DID
+    /// it names, and isn't a replay
+    pub async fn verify_peer_request(
+        &self,
+        signature: &str,
+        target: &Target<'_>,
+    ) -> Result<PeerIdentity, AppError> {
+        peer_request::verify(signature, target, &self.did_resolver, &self.peer_requests()).await
+    }
+
+    /// Nonces of signed peer requests still inside the replay window
+    pub fn peer_requests(&self) -> ReplayCache {
+        ReplayCache::new(self.kv.clone(), self.peer_replay_window, self.time.clone())
+    }
+
+    /// Attest to a space's current Merkle root in a credential the node
+    /// issues about itself, and keep it with the space's earlier anchors
+    pub async fn anchor_space(&self, key: &str) -> Result<SpaceAnchor, AppError> {
+        let merkle_root = self.rebuild_space_merkle(key).await?;
+        let credential = self.issue_credential_with(CredentialRequest {
+            subject: self.node_data.id.clone(),
+            claims: anchor::claims(key, &merkle_root),
+            types: vec![anchor::ANCHOR_TYPE.to_string()],
+            ..Default::default()
+        })?;
+
+        // A timestamp makes the anchor more than the node's word, but an
+        // unreachable authority shouldn't stop the node anchoring
+        let timestamp = match &self.timestamps {
+            Some(client) => {
+                let digest = Sha256::digest(credential.jwt.as_bytes()).into();
+                client
+                    .timestamp(&digest)
+                    .await
+                    .inspect_err(|e| warn!("Failed to timestamp anchor of space {}: {}", key, e))
+                    .ok()
+            }
+            None => None,
+        };
+
+        let anchor = SpaceAnchor {
+            space_key: key.to_string(),
+            merkle_root,
+            anchored_at: chrono::Utc::now(),
+            credential,
+            timestamp,
+        };
+        AnchorLog::open(&self.kv, key)?.record(&anchor)?;
+        Ok(anchor)
+    }
+
+    /// A space's anchors, newest first
+    pub async fn space_anchors(&self, key: &str) -> Result<Vec<SpaceAnchor>, AppError> {
+        space::find_space(&self.db, key).await?;
+        AnchorLog::open(&self.kv, key)?.list()
+    }
+
+    /// Have the timestamp authority countersign a SHA-256 digest, e.g. of an
+    /// issued credential's JWT, and keep the proof
+    pub async fn timestamp(&self, digest: &[u8; 32]) -> Result<TimestampProof, AppError> {
+        let client = self
+            .timestamps
+            .as_ref()
+            .ok_or_else(|| AppError::Config("No timestamp authority is configured".to_string()))?;
+        let proof = client.timestamp(digest).await?;
+        timestamp::save_proof(&self.kv, &proof)?;
+        Ok(proof)
+    }
+
+    /// A kept timestamp of a hex SHA-256 digest
+    pub fn timestamp_proof(&self, digest: &str) -> Result<TimestampProof, AppError> {
+        timestamp::load_proof(&self.kv, digest)?
+            .ok_or_else(|| AppError::NotFound(format!("No timestamp of {}", digest)))
+    }
+
+    /// Prove possession of the node identity key to a verifier
+    pub fn attest(
+        &self,
+        challenge: &str,
+        audience: Option<&str>,
+    ) -> Result<NodeAttestation, AppError> {
+        let attestation = attestation::attest(&self.node_data, challenge, audience)?;
+        info!(
+            "Attested node identity for {}",
+            audience.unwrap_or("unspecified audience")
+        );
+        Ok(attestation)
+    }
+
+    /// All spaces on this node
+    pub async fn list_spaces(&self) -> Result<Vec<entity::space::Model>, AppError> {
+        space::list_spaces(&self.db).await
+    }
+
+    /// Spaces `actor` may see
+    pub async fn list_spaces_as(
+        &self,
+        actor: Actor,
+    ) -> Result<Vec<entity::space::Model>, AppError> {
+        space::list_spaces_for(&self.db, actor).await
+    }
+
+    /// Delete a space `actor` may see. It can be restored within the
+    /// restore window, after which the purge drops it with its Merkle tree.
+    /// The files stay, and so does an encrypted space's key: creating the
+    /// space again at the same directory reads them as before. Refused if
+    /// the space has moved on from `version`, when one is given.
+    pub async fn delete_space_as(
+        &self,
+        actor: Actor,
+        key: &str,
+        version: Option<i32>,
+    ) -> Result<entity::space::Model, AppError> {
+        space::delete_space_for(&self.db, key, actor, version).await
+    }
+
+    /// Bring back a space `actor` deleted within the restore window, if it
+    /// is still at `version` when one is given
+    pub async fn restore_space_as(
+        &self,
+        actor: Actor,
+        key: &str,
+        version: Option<i32>,
+    ) -> Result<entity::space::Model, AppError> {
+        let since =
+            soft_delete::restorable_since(chrono::Utc::now(), self.retention.restore_window)?;
+        space::restore_space_for(&self.db, key, actor, since, version).await
+    }
+
+    /// Re-index a space `actor` may see from its files on disk, picking up
+    /// changes made outside the node. Each file found created, modified or
+    /// deleted since the last index is published as an event, through the
+    /// outbox in the transaction recording the new root; on a space never
+    /// indexed, every file counts as created.
+    pub async fn scan_space_as(&self, actor: Actor, key: &str) -> Result<SpaceScan, AppError> {
+        let found = self.find_space_as(actor, key).await?;
+        let index = self.merkle_index(key)?;
+        let before = match found.merkle_root {
+            Some(_) => index.entries()?,
+            None => Vec::new(),
+        };
+        let manifest = self.build_space_manifest(key, "").await?;
+        let (batch, root, after) = tokio::task::spawn_blocking(move || {
+            let (batch, root) = index.plan_rebuild(&manifest.entries)?;
+            Ok::<_, AppError>((batch, root, manifest.entries))
+        })
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))??;
+        let scan = SpaceScan::between(&before, &after, root);
+
+        // Root and events commit together; the tree follows. A crash before
+        // it does leaves the tree stale, so the next scan reports the same
+        // changes again rather than losing them.
+        let txn = self
+            .db
+            .begin()
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        space::set_merkle_root(&txn, key, &scan.merkle_root).await?;
+        let changes = [
+            (EventType::FileCreated, &scan.created),
+            (EventType::FileModified, &scan.modified),
+            (EventType::FileDeleted, &scan.deleted),
+        ];
+        for (event_type, paths) in changes {
+            for path in paths {
+                let event = Event::from_payload(
+                    event_type,
+                    &FileChanged {
+                        space_key: key.to_string(),
+                        path: path.clone(),
+                        user_id: found.user_id,
+                    },
+                );
+                outbox::enqueue(&txn, &event).await?;
+            }
+        }
+        txn.commit()
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        self.merkle_index(key)?.apply(batch)?;
+        info!("Scanned space {}: {}", key, scan.merkle_root);
+        if let Err(e) = self.dispatch_outbox().await {
+            warn!("Outbox dispatch failed: {}", e);
+        }
+        Ok(scan)
+    }
+
+    /// Publish events waiting in the outbox, e.g. right after committing
+    /// some; on failure they wait for the dispatcher task
+    pub async fn dispatch_outbox(&self) -> Result<usize, AppError> {
+        self.outbox.dispatch(|event| self.publish(event)).await
+    }
+
+    /// Passkeys `actor` may manage
+    pub async fn list_passkeys_as(&self, actor: Actor) -> Result<Vec<PasskeySummary>, AppError> {
+        passkeys::list(&self.db, actor).await
+    }
+
+    /// Rename a passkey `actor` may manage, if it is still at `version`
+    /// when one is given
+    pub async fn rename_passkey_as(
+        &self,
+        actor: Actor,
+        id: i32,
+        name: &str,
+        version: Option<i32>,
+    ) -> Result<PasskeySummary, AppError> {
+        passkeys::rename(&self.db, actor, id, name, version).await
+    }
+
+    /// Delete a passkey `actor` may manage, unless it is its user's last
+    pub async fn delete_passkey_as(
+        &self,
+        actor: Actor,
+        id: i32,
+    ) -> Result<PasskeySummary, AppError> {
+        passkeys::delete(&self.db, actor, id).await
+    }
+
+    /// Bring back a passkey `actor` deleted within the restore window
+    pub async fn restore_passkey_as(
+        &self,
+        actor: Actor,
+        id: i32,
+    ) -> Result<PasskeySummary, AppError> {
+        let since =
+            soft_delete::restorable_since(chrono::Utc::now(), self.retention.restore_window)?;
+        passkeys::restore(&self.db, actor, id, since).await
+    }
+
+    /// Sessions opened by passkey authentication
+    pub fn user_sessions(&self) -> UserSessions {
+        UserSessions::new(self.sessions.clone(), self.node_data.clone())
+    }
+
+    /// Open a session for the user whose passkey just authenticated
+    pub async fn open_user_session(
+        &self,
+        result: &AuthenticationResult,
+    ) -> Result<(String, UserSession), AppError> {
+        let user_id = webauthn::auth::credential_owner(self, result.cred_id().as_ref())
+            .await?
+            .ok_or_else(|| AppError::NotFound("Authenticated credential is gone".to_string()))?;
+        self.user_sessions().open(user_id).await
+    }
+
+    /// The open session behind a token
+    pub async fn user_session(&self, token: &str) -> Result<Option<UserSession>, AppError> {
+        self.user_sessions().find(token).await
+    }
+
+    /// Sign out: revoke the session behind a token
+    pub async fn close_user_session(&self, token: &str) -> Result<(), AppError> {
+        match self.user_sessions().close(token).await? {
+            true => Ok(()),
+            false => Err(AppError::Auth("Invalid or expired session".to_string())),
+        }
+    }
+
+    /// Whether requests must present the sessions authentication hands out
+    pub fn issues_sessions(&self) -> bool {
+        self.multi_user.enabled || self.guest_mode
+    }
+
+    /// Who a session token acts for: its user in multi-user mode, otherwise
+    /// the node. `None` if the session is unknown or expired.
+    pub async fn session_actor(&self, token: &str) -> Result<Option<Actor>, AppError> {
+        let actor = self.user_sessions().actor(token).await?;
+        Ok(match self.multi_user.enabled {
+            true => actor,
+            false => actor.map(|_| Actor::Node),
+        })
+    }
+
+    /// `actor`'s own KV namespace
+    pub fn kv_for(&self, actor: Actor) -> Result<ActorKv, AppError> {
+        ActorKv::open(&self.kv, actor, &self.multi_user)
+    }
+
+    /// Failed-authentication tracking and lockouts
+    pub fn auth_guard(&self) -> AuthGuard {
+        AuthGuard::new(self.sessions.clone())
+    }
+
+    /// The local PIN fallback
+    pub fn local_pin(&self) -> LocalPin {
+        LocalPin::new(
+            self.kv.clone(),
+            self.sessions.clone(),
+            self.settings.clone(),
+        )
+    }
+
+    /// Where scratch files go: under the config dir, or the system's temp
+    /// dir for nodes without one
+    pub fn scratch_dir(&self) -> PathBuf {
+        match &self.home {
+            Some(home) => home.join(init::SCRATCH_DIR),
+            None => std::env::temp_dir(),
+        }
+    }
+
+    /// User-defined scheduled tasks
+    pub fn scheduler(&self) -> Scheduler {
+        Scheduler::new(self.clone())
+    }
+
+    /// Account deletions waiting out their grace period
+    pub fn account_deletions(&self) -> AccountDeletions {
+        AccountDeletions::new(self.kv.clone())
+    }
+
+    /// Ask for a user's account to be deleted, after the deletion grace
+    pub async fn delete_account(&self, user_id: i32) -> Result<Deletion, AppError> {
+        account::request_deletion(self, user_id, chrono::Utc::now()).await
+    }
+
+    /// The profile of user `user_id`
+    pub async fn user_profile(&self, user_id: i32) -> Result<UserProfile, AppError> {
+        profile::get(&self.db, user_id).await
+    }
+
+    /// Edit the profile of user `user_id`
+    pub async fn update_user_profile(
+        &self,
+        user_id: i32,
+        update: ProfileUpdate,
+    ) -> Result<UserProfile, AppError> {
+        profile::update(&self.db, user_id, update).await
+    }
+
+    /// Everything the node holds about a user
+    pub async fn export_account(&self, user_id: i32) -> Result<AccountExport, AppError> {
+        account::export(self, user_id).await
+    }
+
+    /// Withdraw `user_id`'s pending account deletion. False if there was
+    /// none.
+    pub async fn cancel_account_deletion(&self, user_id: i32) -> Result<bool, AppError> {
+        account::cancel_deletion(self, user_id).await
+    }
+
+    /// Remove spaces and passkeys deleted longer ago than the restore window
+    pub async fn purge_deleted(&self) -> Result<DeletedPurge, AppError> {
+        soft_delete::purge(self, chrono::Utc::now()).await
+    }
+
+    /// Recorded changes to a user (`users`, by ID) or space (`spaces`, by
+    /// key) that `actor` may see, newest first
+    pub async fn entity_history(
+        &self,
+        actor: Actor,
+        entity: &str,
+        id: &str,
+    ) -> Result<Vec<EntityChange>, AppError> {
+        history::list(&self.db, actor, entity, id).await
+    }
+
+    /// Remove recorded changes older than their retention
+    pub async fn purge_history(&self) -> Result<u64, AppError> {
+        match retention::cutoff(chrono::Utc::now(), self.retention.history)? {
+            Some(before) => history::purge(&self.db, before).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Erase the accounts whose deletion grace has passed
+    pub async fn erase_deleted_accounts(&self) -> Result<Vec<Erasure>, AppError> {
+        account::erase_due(self, chrono::Utc::now()).await
+    }
+
+    /// Purge records past their retention; with `dry_run` only count them
+    pub fn purge_expired(&self, dry_run: bool) -> Result<PurgeReport, AppError> {
+        retention::purge(self, chrono::Utc::now(), dry_run)
+    }
+
+    /// What the node is using, per subsystem where it can be told apart
+    pub async fn resource_usage(&self) -> Result<ResourceUsage, AppError> {
+        resources::usage(&self.db, &self.kv, &self.tasks).await
+    }
+
+    /// What the KV store and database report about themselves
+    pub async fn storage_stats(&self) -> Result<StorageStats, AppError> {
+        storage_stats::collect(&self.db, &self.kv).await
+    }
+
+    /// Replace the identity key, and with it the node's DID. The old key is
+    /// archived and signs the hand-over to the new one. Sessions signed by
+    /// the old key end, and servers already running (P2P) keep presenting it
+    /// until restarted.
+    pub fn rotate_identity_key(&mut self) -> Result<SignedRotation, AppError> {
+        let home = self.home.clone().ok_or_else(|| {
+            AppError::Config("Node has no config directory to rotate keys in".to_string())
+        })?;
+        let keystore_dir = home.join(KEYSTORE_DIR);
+
+        // Column key k0 comes from the identity key; keep it for existing rows
+        self.column_keys.pin_identity_key(&keystore_dir)?;
+        let (node_data, rotation) = init::rotate_identity_key(&home.to_string_lossy())?;
+        self.column_keys = ColumnKeys::load(&keystore_dir, &node_data.private_key)?;
+        self.node_data = node_data;
+
+        info!(
+            "Rotated identity key: {} is now {}",
+            rotation.statement.previous_did, rotation.statement.next_did
+        );
+        self.publish(&Event::from_payload(
+            EventType::IdentityKeyRotated,
+            &IdentityKeyRotated {
+                previous_did: rotation.statement.previous_did.clone(),
+                did: rotation.statement.next_did.clone(),
+                version: rotation.statement.version,
+            },
+        ));
+        Ok(rotation)
+    }
+
+    /// Third-party secrets, sealed with the column keys
+    pub fn secrets(&self) -> SecretVault {
+        SecretVault::new(self.kv.clone(), self.column_keys.clone())
+    }
+
+    pub fn key_usage(&self) -> KeyUsage {
+        KeyUsage::new(self.kv.clone(), self.key_usage.clone())
+    }
+
+    /// Usage counts for telemetry, kept only while it is on
+    pub fn telemetry(&self) -> Telemetry {
+        Telemetry::new(self.kv.clone(), self.telemetry.clone())
+    }
+
+    /// Record a use of a key and raise its anomalies
+    pub fn record_key_use(&self, key_use: &KeyUse) -> Result<Vec<Anomaly>, AppError> {
+        let anomalies = self.key_usage().record(key_use)?;
+        for anomaly in &anomalies {
+            warn!(
+                target: "audit",
+                "Unusual use of {} {}: {}",
+                key_use.kind, key_use.key_id, anomaly
+            );
+            self.publish(&Event::from_payload(
+                EventType::KeyUsageAnomaly,
+                &KeyUsageAnomaly {
+                    kind: key_use.kind.as_str().to_string(),
+                    key_id: key_use.key_id.clone(),
+                    anomaly: json!(anomaly),
+                    user_id: key_use.user_id,
+                },
+            ));
+        }
+        Ok(anomalies)
+    }
+
+    /// Record a request made with a session token. With step-up on, a
+    /// session whose use looks anomalous is closed, so its user has to sign
+    /// in again.
+    pub async fn record_session_use(&self, token: &str, key_use: &KeyUse) -> Result<(), AppError> {
+        let anomalies = self.record_key_use(key_use)?;
+        if !anomalies.is_empty() && self.key_usage.step_up {
+            self.close_user_session(token).await?;
+            return Err(AppError::Auth(
+                "Unusual activity on this session; sign in again".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Outcome of the last update check, if the updater has run
+    pub fn update_status(&self) -> Result<Option<UpdateStatus>, AppError> {
+        updater::load_status(&self.kv)
+    }
+
     pub async fn start_webauthn_registration(
         &self,
     ) -> Result<(CreationChallengeResponse, String), AppError> {
+        self.start_webauthn_registration_for(None).await
+    }
+
+    /// Start registration bound to the requesting client
+    pub async fn start_webauthn_registration_for(
+        &self,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<(CreationChallengeResponse, String), AppError> {
+        let (ccr, challenge_id, _) = self
+            .start_webauthn_registration_with(client, &CeremonyOverrides::default())
+            .await?;
+        Ok((ccr, challenge_id))
+    }
+
+    /// Start registration with client-requested options, returning the
+    /// options in effect
+    pub async fn start_webauthn_registration_with(
+        &self,
+        client: Option<&ClientFingerprint>,
+        overrides: &CeremonyOverrides,
+    ) -> Result<(CreationChallengeResponse, String, CeremonyOptions), AppError> {
         info!("Starting WebAuthn Registration..");
-        webauthn::auth::start_registration(self)
+        let options = self.auth_state.limits.registration(overrides)?;
+        let (ccr, challenge_id) = webauthn::auth::start_registration(self, client, &options)
             .await
-            .map_err(|e| AppError::Auth(format!("WebAuthn registration failed: {}", e)))
+            .map_err(|e| AppError::Auth(format!("WebAuthn registration failed: {}", e)))?;
+        Ok((ccr, challenge_id, options))
     }
 
     pub async fn finish_webauthn_registration(
         &self,
         challenge_id: &str,
         reg: RegisterPublicKeyCredential,
+    ) -> Result<(String, String), AppError> {
+        self.finish_webauthn_registration_for(challenge_id, reg, None)
+            .await
+    }
+
+    pub async fn finish_webauthn_registration_for(
+        &self,
+        challenge_id: &str,
+        reg: RegisterPublicKeyCredential,
+        client: Option<&ClientFingerprint>,
     ) -> Result<(String, String), AppError> {
         info!("Finishing WebAuthn Registration..");
-        webauthn::auth::finish_registration(self, challenge_id, reg)
+        webauthn::auth::finish_registration(self, challenge_id, reg, client)
             .await
-            .map_err(|e| AppError::Auth(format!("WebAuthn registration failed: {}", e)))
+            .map_err(|e| ceremony_error("WebAuthn registration failed", e))
     }
 
     pub async fn start_webauthn_authentication(
         &self,
     ) -> Result<(RequestChallengeResponse, String), AppError> {
-        webauthn::auth::start_authentication(self)
+        self.start_webauthn_authentication_for(None).await
+    }
+
+    /// Start authentication bound to the requesting client
+    pub async fn start_webauthn_authentication_for(
+        &self,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<(RequestChallengeResponse, String), AppError> {
+        let (rcr, challenge_id, _) = self
+            .start_webauthn_authentication_with(client, &CeremonyOverrides::default())
+            .await?;
+        Ok((rcr, challenge_id))
+    }
+
+    /// Start authentication with client-requested options, returning the
+    /// options in effect
+    pub async fn start_webauthn_authentication_with(
+        &self,
+        client: Option<&ClientFingerprint>,
+        overrides: &CeremonyOverrides,
+    ) -> Result<(RequestChallengeResponse, String, CeremonyOptions), AppError> {
+        // A locked-out network can't even get a challenge
+        if let Some(network) = client.and_then(|c| c.network.clone()) {
+            self.auth_guard()
+                .check(&[AttemptKey::Network(network)])
+                .await?;
+        }
+
+        let options = self.auth_state.limits.authentication(overrides)?;
+        let (rcr, challenge_id) = webauthn::auth::start_authentication(self, client, &options)
+            .await
+            .map_err(|e| AppError::Auth(format!("WebAuthn authentication start failed: {}", e)))?;
+        Ok((rcr, challenge_id, options))
+    }
+
+    /// Start authentication for passkey autofill; finish it with
+    /// [`Node::finish_webauthn_authentication`] as usual
+    pub async fn start_webauthn_conditional_authentication(
+        &self,
+    ) -> Result<(RequestChallengeResponse, String), AppError> {
+        self.start_webauthn_conditional_authentication_for(None)
             .await
-            .map_err(|e| AppError::Auth(format!("WebAuthn authentication start failed: {}", e)))
+    }
+
+    pub async fn start_webauthn_conditional_authentication_for(
+        &self,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<(RequestChallengeResponse, String), AppError> {
+        if let Some(network) = client.and_then(|c| c.network.clone()) {
+            self.auth_guard()
+                .check(&[AttemptKey::Network(network)])
+                .await?;
+        }
+
+        webauthn::auth::start_conditional_authentication(self, client)
+            .await
+            .map_err(|e| {
+                AppError::Auth(format!(
+                    "WebAuthn conditional authentication start failed: {}",
+                    e
+                ))
+            })
     }
 
     pub async fn finish_webauthn_authentication(
@@ -69,9 +1090,55 @@ impl Node {
         challenge_id: &str,
         auth: PublicKeyCredential,
     ) -> Result<AuthenticationResult, AppError> {
-        info!("Finishing WebAuthn Authentication..");
-        webauthn::auth::finish_authentication(self, challenge_id, auth)
+        self.finish_webauthn_authentication_for(challenge_id, auth, None)
             .await
-            .map_err(|e| AppError::Auth(format!("WebAuthn authentication failed: {}", e)))
+    }
+
+    pub async fn finish_webauthn_authentication_for(
+        &self,
+        challenge_id: &str,
+        auth: PublicKeyCredential,
+        client: Option<&ClientFingerprint>,
+    ) -> Result<AuthenticationResult, AppError> {
+        info!("Finishing WebAuthn Authentication..");
+        let guard = self.auth_guard();
+        let credential_id = auth.id.clone();
+        let attempt_keys = lockout::attempt_keys(&credential_id, client);
+        guard.check(&attempt_keys).await?;
+
+        let result =
+            match webauthn::auth::finish_authentication(self, challenge_id, auth, client).await {
+                Ok(result) => result,
+                Err(e) => {
+                    for (key, until) in guard.record_failure(&attempt_keys).await? {
+                        self.publish(&Event::from_payload(
+                            EventType::AuthenticationLocked,
+                            &AuthenticationLocked {
+                                key: key.to_string(),
+                                locked_until: until.to_rfc3339(),
+                            },
+                        ));
+                    }
+                    return Err(ceremony_error("WebAuthn authentication failed", e));
+                }
+            };
+        guard.record_success(&credential_id).await?;
+
+        self.publish(&Event::from_payload(
+            EventType::CredentialVerified,
+            &CredentialVerified {
+                credential_id: URL_SAFE_NO_PAD.encode(result.cred_id().as_ref()),
+                counter: result.counter(),
+            },
+        ));
+
+        Ok(result)
+    }
+}
+
+fn ceremony_error(context: &str, e: CeremonyError) -> AppError {
+    match e {
+        CeremonyError::ClientMismatch => AppError::Forbidden(format!("{}: {}", context, e)),
+        CeremonyError::Webauthn(e) => AppError::Auth(format!("{}: {}", context, e)),
     }
 }
@@ -0,0 +1,103 @@
+//! Guest access for public nodes.
+//!
+//! With guest mode on, unauthenticated requests may only read published
+//! resources; every other request must present a session token
+//! (`Authorization: Bearer`) from passkey authentication. The check runs here,
+//! in front of all REST routes (plugin routes included), so handlers don't
+//! repeat it.
+
+use axum::{
+    Extension,
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::api::servers::app_state::AppState;
+use crate::api::servers::i18n::MessageCode;
+use crate::api::servers::rest::{bearer_token, error_response};
+
+/// Readable without a session. Entries ending in `/` cover everything below;
+/// `*` stands for one path segment.
+pub const PUBLISHED: &[&str] = &[
+    "/api/v1/health",
+    "/api/v1/health/live",
+    "/api/v1/health/ready",
+    "/api/v1/node",
+    "/api/v1/node/did.json",
+    // DID documents, which peers fetch before they have a session
+    "/api/v1/dids/*/document",
+    "/api/v1/dids/resolve/",
+];
+
+/// Actions open to guests: signing in, and attestation and signed requests
+/// for federation peers
+pub const PUBLIC_ACTIONS: &[&str] = &[
+    "/api/v1/webauthn/start_authentication",
+    "/api/v1/webauthn/finish_authentication",
+    "/api/v1/pin/unlock",
+    "/api/v1/node/attest",
+    "/api/v1/peer/messages",
+];
+
+/// Whether `path` is a published resource
+pub fn is_published(path: &str) -> bool {
+    PUBLISHED
+        .iter()
+        .any(|published| match published.ends_with('/') {
+            true => path.starts_with(published),
+            false => {
+                let mut segments = path.split('/');
+                published.split('/').all(|expected| {
+                    segments.next().is_some_and(|segment| {
+                        (expected == "*" && !segment.is_empty()) || expected == segment
+                    })
+                }) && segments.next().is_none()
+            }
+        })
+}
+
+/// Whether a guest may make the request
+pub fn guest_allowed(method: &Method, path: &str) -> bool {
+    let read = method == Method::GET || method == Method::HEAD;
+    (read && is_published(path)) || (method == Method::POST && PUBLIC_ACTIONS.contains(&path))
+}
+
+/// Middleware enforcing guest mode
+pub async fn require_session(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    {
+        let node = app_state.node.read().await;
+        if node.guest_mode && !guest_allowed(request.method(), request.uri().path()) {
+            let Some(token) = bearer_token(request.headers()) else {
+                return (StatusCode::UNAUTHORIZED, "Sign in required").into_response();
+            };
+
+            let session = match node.session_actor(token).await {
+                Ok(actor) => actor.is_some(),
+                Err(e) => return error_response(e).into_response(),
+            };
+            // PIN unlock grants are read-only
+            let grant = !session
+                && request.method() == Method::GET
+                && match node.local_pin().grant(token).await {
+                    Ok(grant) => grant.is_some(),
+                    Err(e) => return error_response(e).into_response(),
+                };
+            if !session && !grant {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    Extension(MessageCode("session_expired")),
+                    "Invalid or expired session",
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    next.run(request).await
+}
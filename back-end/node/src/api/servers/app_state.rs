@@ -1,16 +1,108 @@
 use crate::api::node::Node;
+use crate::api::servers::compression::CompressionStats;
+use crate::bootstrap::config::{
+    BodyLimits, CompressionConfig, ProxyConfig, RequestLimitsConfig, SecurityHeadersConfig,
+    ServerConfig,
+};
+use crate::lifecycle::Shutdown;
+use crate::plugins::PluginHost;
+use event::source::EventListener;
+use event::types::Event;
+use std::error::Error;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, broadcast};
+
+/// Events held for WebSocket subscribers that fall behind
+const EVENT_BUFFER: usize = 256;
 
 #[derive(Clone)]
 pub struct AppState {
     pub node: Arc<RwLock<Node>>,
+    pub plugins: Arc<PluginHost>,
+    /// Listener settings, when serving; advertised by node info
+    pub server: Option<ServerConfig>,
+    pub limits: BodyLimits,
+    pub request_limits: RequestLimitsConfig,
+    pub compression: CompressionConfig,
+    pub compression_stats: CompressionStats,
+    pub security_headers: SecurityHeadersConfig,
+    /// Serve HTTPS with this config instead of plain HTTP
+    pub tls: Option<Arc<rustls::ServerConfig>>,
+    pub proxy: ProxyConfig,
+    /// Node events, for WebSocket subscribers
+    pub events: broadcast::Sender<Event>,
+    /// The node's shutdown, which stops the servers
+    pub shutdown: Shutdown,
 }
 
 impl AppState {
     pub fn new(node: Node) -> Self {
+        Self::with_plugins(node, PluginHost::default())
+    }
+
+    pub fn with_plugins(node: Node, plugins: PluginHost) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        node.subscribe(Box::new(EventBroadcast(events.clone())));
+        let shutdown = node.tasks.shutdown().clone();
         Self {
             node: Arc::new(RwLock::new(node)),
+            plugins: Arc::new(plugins),
+            server: None,
+            limits: BodyLimits::default(),
+            request_limits: RequestLimitsConfig::default(),
+            compression: CompressionConfig::default(),
+            compression_stats: CompressionStats::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            tls: None,
+            proxy: ProxyConfig::default(),
+            events,
+            shutdown,
         }
     }
+
+    pub fn with_server(mut self, server: ServerConfig) -> Self {
+        self.server = Some(server);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: BodyLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_request_limits(mut self, request_limits: RequestLimitsConfig) -> Self {
+        self.request_limits = request_limits;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_security_headers(mut self, security_headers: SecurityHeadersConfig) -> Self {
+        self.security_headers = security_headers;
+        self
+    }
+
+    pub fn with_tls(mut self, tls: Arc<rustls::ServerConfig>) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = proxy;
+        self
+    }
+}
+
+/// Forwards node events to whoever is subscribed to [`AppState::events`]
+struct EventBroadcast(broadcast::Sender<Event>);
+
+impl EventListener for EventBroadcast {
+    fn handle(&self, event: &Event) -> Result<(), Box<dyn Error>> {
+        // Sending only fails when nobody is listening
+        let _ = self.0.send(event.clone());
+        Ok(())
+    }
 }
@@ -0,0 +1,128 @@
+//! Streaming request bodies.
+//!
+//! Extractors like `Json` buffer the whole body, capped by the router's
+//! [`BodyLimits::max_body_bytes`]. Uploads instead stream through [`spool`]:
+//! small bodies stay in memory, anything past the spill threshold continues
+//! into a temp file in the node's scratch dir, and the upload limit is
+//! enforced while reading so an oversized body is cut off early rather than
+//! buffered first.
+
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use axum::body::{Body, HttpBody};
+use errors::AppError;
+use futures_util::StreamExt;
+use tempfile::{NamedTempFile, TempPath};
+use tokio::io::AsyncWriteExt;
+
+use crate::bootstrap::config::BodyLimits;
+
+/// A fully received body
+pub enum SpooledBody {
+    Memory(Vec<u8>),
+    File(NamedTempFile),
+}
+
+impl SpooledBody {
+    /// Bytes received
+    pub fn size(&self) -> Result<u64, AppError> {
+        match self {
+            SpooledBody::Memory(bytes) => Ok(bytes.len() as u64),
+            SpooledBody::File(file) => Ok(file.as_file().metadata()?.len()),
+        }
+    }
+
+    /// Move the body to `dest`, replacing any file there. A spilled body is
+    /// renamed into place; spooled on another filesystem, it is copied next
+    /// to `dest` first, so `dest` never holds part of it. Blocks.
+    pub fn persist(self, dest: &Path) -> Result<(), AppError> {
+        match self {
+            SpooledBody::Memory(bytes) => fs::write(dest, bytes)?,
+            SpooledBody::File(file) => match file.persist(dest) {
+                Ok(_) => {}
+                Err(e) if e.error.kind() == io::ErrorKind::CrossesDevices => {
+                    let mut file = e.file;
+                    file.seek(SeekFrom::Start(0))?;
+                    let mut copy = NamedTempFile::new_in(dest.parent().unwrap_or(Path::new(".")))?;
+                    io::copy(&mut file, &mut copy)?;
+                    copy.persist(dest).map_err(|e| AppError::IO(e.error))?;
+                }
+                Err(e) => return Err(AppError::IO(e.error)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Read the body back from the start, for writing it out transformed
+    pub fn into_reader(self) -> Result<Box<dyn Read + Send>, AppError> {
+        Ok(match self {
+            SpooledBody::Memory(bytes) => Box::new(io::Cursor::new(bytes)),
+            // The temp file is deleted once the reader drops
+            SpooledBody::File(mut file) => {
+                file.seek(SeekFrom::Start(0))?;
+                Box::new(io::BufReader::new(file))
+            }
+        })
+    }
+}
+
+/// Read `body` up to `limits.max_upload_bytes`, spilling into a temp file in
+/// `spill_dir` past `limits.spill_threshold_bytes`
+pub async fn spool(
+    body: Body,
+    limits: &BodyLimits,
+    spill_dir: &Path,
+) -> Result<SpooledBody, AppError> {
+    let too_large =
+        || AppError::PayloadTooLarge(format!("Upload exceeds {} bytes", limits.max_upload_bytes));
+    // Refuse a declared length up front
+    if body.size_hint().lower() > limits.max_upload_bytes {
+        return Err(too_large());
+    }
+
+    let mut received = 0u64;
+    let mut spooled = Spool::Memory(Vec::new());
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk
+            .map_err(|e| AppError::Validation(format!("Failed to read request body: {}", e)))?;
+        received += chunk.len() as u64;
+        if received > limits.max_upload_bytes {
+            return Err(too_large());
+        }
+
+        match &mut spooled {
+            Spool::Memory(buffer) if buffer.len() + chunk.len() > limits.spill_threshold_bytes => {
+                tokio::fs::create_dir_all(spill_dir).await?;
+                let spill_dir = spill_dir.to_path_buf();
+                let (file, path) =
+                    tokio::task::spawn_blocking(move || NamedTempFile::new_in(spill_dir))
+                        .await
+                        .map_err(|e| AppError::IO(io::Error::other(e)))??
+                        .into_parts();
+                let mut file = tokio::fs::File::from_std(file);
+                file.write_all(buffer).await?;
+                file.write_all(&chunk).await?;
+                spooled = Spool::File(file, path);
+            }
+            Spool::Memory(buffer) => buffer.extend_from_slice(&chunk),
+            Spool::File(file, _) => file.write_all(&chunk).await?,
+        }
+    }
+
+    Ok(match spooled {
+        Spool::Memory(bytes) => SpooledBody::Memory(bytes),
+        Spool::File(mut file, path) => {
+            file.flush().await?;
+            SpooledBody::File(NamedTempFile::from_parts(file.into_std().await, path))
+        }
+    })
+}
+
+/// A body being received; the temp file is deleted with its path
+enum Spool {
+    Memory(Vec<u8>),
+    File(tokio::fs::File, TempPath),
+}
@@ -0,0 +1,74 @@
+//! HTTP caching for deterministic resources.
+//!
+//! Documents that only change when their content does (DID documents above
+//! all) carry a strong `ETag` derived from a hash of the body, and a
+//! `Cache-Control` lifetime from the resolver's suggested TTL. Without a TTL
+//! the content is immutable: did:key, did:jwk and did:peer documents are
+//! derived from the identifier itself. A request whose `If-None-Match` still
+//! matches gets `304 Not Modified` and no body.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::Response,
+};
+use errors::AppError;
+use serde::Serialize;
+
+/// Lifetime of immutable content: a year, the longest caches honour
+pub const IMMUTABLE_MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+/// Strong entity tag for `bytes`
+pub fn etag(bytes: &[u8]) -> String {
+    let hash = blake3::hash(bytes);
+    format!("\"{}\"", &hash.to_hex()[..32])
+}
+
+/// `Cache-Control` for content that stays valid `ttl` seconds, or forever
+pub fn cache_control(ttl: Option<u64>) -> String {
+    match ttl {
+        Some(ttl) => format!("public, max-age={}", ttl),
+        None => format!("public, max-age={}, immutable", IMMUTABLE_MAX_AGE),
+    }
+}
+
+/// Whether the request's `If-None-Match` names `etag`. Weak validators match
+/// too, as they should for a GET.
+pub fn not_modified(request: &HeaderMap, etag: &str) -> bool {
+    request
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Serialize `value` as a cacheable response of `content_type`
+pub fn cached_json<T: Serialize>(
+    request: &HeaderMap,
+    value: &T,
+    content_type: &'static str,
+    ttl: Option<u64>,
+) -> Result<Response, AppError> {
+    let body = serde_json::to_vec(value).map_err(|e| AppError::Storage(Box::new(e)))?;
+    let etag = etag(&body);
+
+    let (status, body) = match not_modified(request, &etag) {
+        true => (StatusCode::NOT_MODIFIED, Body::empty()),
+        false => (StatusCode::OK, Body::from(body)),
+    };
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&cache_control(ttl)).expect("valid header value"),
+    );
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("valid header value"),
+    );
+    Ok(response)
+}
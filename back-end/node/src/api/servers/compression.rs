@@ -0,0 +1,175 @@
+//! Response compression.
+//!
+//! JSON and text responses are compressed with gzip, brotli or zstd,
+//! whichever the client prefers in `Accept-Encoding`. Everything else (images,
+//! archives, uploaded blobs) is usually compressed already and goes out as is.
+//! [`CompressionStats`] counts what compression saved.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{Response, header},
+    middleware::Next,
+    response::IntoResponse,
+};
+use http_body_util::BodyExt;
+use serde::Serialize;
+use tower_http::compression::{CompressionLayer, Predicate, predicate::SizeAbove};
+
+use crate::bootstrap::config::CompressionConfig;
+
+/// Whether a content type is worth compressing
+pub fn compressible(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json" | "application/xml" | "application/javascript"
+        )
+}
+
+/// Compress enabled, compressible responses above the size threshold
+#[derive(Debug, Clone, Copy)]
+pub struct ShouldCompress {
+    enabled: bool,
+    size: SizeAbove,
+}
+
+impl ShouldCompress {
+    pub fn new(config: &CompressionConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            size: SizeAbove::new(config.min_size_bytes),
+        }
+    }
+}
+
+impl Predicate for ShouldCompress {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        self.enabled
+            && self.size.should_compress(response)
+            && response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(compressible)
+    }
+}
+
+pub fn layer(config: &CompressionConfig) -> CompressionLayer<ShouldCompress> {
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .zstd(true)
+        .compress_when(ShouldCompress::new(config))
+}
+
+/// Totals over compressed responses
+#[derive(Debug, Clone, Default)]
+pub struct CompressionStats {
+    responses: Arc<AtomicU64>,
+    original_bytes: Arc<AtomicU64>,
+    compressed_bytes: Arc<AtomicU64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionSnapshot {
+    pub responses: u64,
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+    pub saved_bytes: u64,
+}
+
+impl CompressionStats {
+    pub fn snapshot(&self) -> CompressionSnapshot {
+        let original_bytes = self.original_bytes.load(Ordering::Relaxed);
+        let compressed_bytes = self.compressed_bytes.load(Ordering::Relaxed);
+        CompressionSnapshot {
+            responses: self.responses.load(Ordering::Relaxed),
+            original_bytes,
+            compressed_bytes,
+            saved_bytes: original_bytes.saturating_sub(compressed_bytes),
+        }
+    }
+}
+
+/// Bytes a response body has produced so far, before compression
+#[derive(Clone, Default)]
+struct OriginalBytes(Arc<AtomicU64>);
+
+/// Inner middleware (below the compression layer): note the body's size, or
+/// count its bytes as the handler produces them when the size isn't known
+pub async fn count_original(request: Request, next: Next) -> impl IntoResponse {
+    let (mut parts, body) = next.run(request).await.into_parts();
+    // Already encoded by the handler
+    if parts.headers.contains_key(header::CONTENT_ENCODING) {
+        return Response::from_parts(parts, body);
+    }
+
+    let original = OriginalBytes::default();
+    // Wrapping hides the size hint, which the size threshold relies on
+    let body = match body.size_hint().exact() {
+        Some(size) => {
+            original.0.store(size, Ordering::Relaxed);
+            body
+        }
+        None => {
+            let counter = original.0.clone();
+            Body::new(body.map_frame(move |frame| {
+                if let Some(data) = frame.data_ref() {
+                    counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+                }
+                frame
+            }))
+        }
+    };
+    parts.extensions.insert(original);
+    Response::from_parts(parts, body)
+}
+
+/// Outer middleware (above the compression layer): count compressed bytes,
+/// and move the original count into the totals as compressed output flows.
+/// The encoder's last frame comes after all of its input, so by the end of
+/// the body every original byte has been moved.
+pub async fn count_compressed(
+    State(stats): State<CompressionStats>,
+    request: Request,
+    next: Next,
+) -> impl IntoResponse {
+    let response = next.run(request).await;
+    let compressed = response.headers().contains_key(header::CONTENT_ENCODING);
+    let Some(original) = response.extensions().get::<OriginalBytes>().cloned() else {
+        return response;
+    };
+    if !compressed {
+        return response;
+    }
+
+    stats.responses.fetch_add(1, Ordering::Relaxed);
+    let (parts, body) = response.into_parts();
+    let body = body.map_frame(move |frame| {
+        if let Some(data) = frame.data_ref() {
+            stats
+                .compressed_bytes
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        let moved = original.0.swap(0, Ordering::Relaxed);
+        stats.original_bytes.fetch_add(moved, Ordering::Relaxed);
+        frame
+    });
+    Response::from_parts(parts, Body::new(body))
+}
@@ -0,0 +1,68 @@
+//! Cross-origin access to the REST API and the WebSocket server.
+//!
+//! Browser apps on the origins in `CORS_ORIGINS` may call the API and open
+//! WebSocket connections; browsers don't apply CORS to the latter, so the
+//! WebSocket server checks [`allows`] itself. An entry
+//! is an exact origin (`https://app.example.com`), one with `*.` before its
+//! host to allow any subdomain (`https://*.example.com`), or `*` alone for
+//! any origin at all. Without server settings (tests, embedding) only
+//! [`DEFAULT_ORIGINS`] are allowed.
+
+use std::time::Duration;
+
+use axum::http::{HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// Origins allowed when none are configured: the dev front-end
+pub const DEFAULT_ORIGINS: &[&str] = &["http://localhost:3000"];
+
+/// CORS for requests from `origins`
+pub fn layer(origins: &[impl AsRef<str>]) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(allow_origin(origins))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers(Any)
+        // Cache preflight requests for 1 hour
+        .max_age(Duration::from_secs(3600))
+}
+
+fn allow_origin(origins: &[impl AsRef<str>]) -> AllowOrigin {
+    let origins: Vec<String> = origins.iter().map(|o| o.as_ref().to_string()).collect();
+    if origins.iter().any(|pattern| pattern == "*") {
+        return AllowOrigin::any();
+    }
+    AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        origin
+            .to_str()
+            .is_ok_and(|origin| origins.iter().any(|pattern| matches(pattern, origin)))
+    })
+}
+
+/// Whether `origins` let a page on `origin` in
+pub fn allows(origins: &[impl AsRef<str>], origin: &str) -> bool {
+    origins
+        .iter()
+        .any(|pattern| matches(pattern.as_ref(), origin))
+}
+
+/// Whether `origin` is one `pattern` allows
+pub fn matches(pattern: &str, origin: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.split_once("://*.") {
+        Some((scheme, domain)) => origin
+            .strip_prefix(scheme)
+            .and_then(|rest| rest.strip_prefix("://"))
+            .and_then(|host| host.strip_suffix(domain))
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => pattern.eq_ignore_ascii_case(origin),
+    }
+}
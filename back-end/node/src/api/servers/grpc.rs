@@ -0,0 +1,670 @@
+//! gRPC server, for integrators that would rather call typed services than
+//! JSON routes.
+//!
+//! The `flow.v1.Flow` service (see `proto/flow/v1/flow.proto`) covers passkey
+//! registration and sign-in, spaces, DID resolution and health, with the same
+//! rules as REST: a session token travels in the `authorization` metadata, and
+//! multi-user and guest mode require one. It runs only when `GRPC_PORT` is
+//! set, and speaks plaintext HTTP/2 even when REST serves HTTPS, so expose it
+//! through a TLS-terminating proxy or keep it on a private network.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use errors::AppError;
+use log::{error, info};
+use serde::de::DeserializeOwned;
+use tokio::net::TcpListener;
+use tonic::codegen::{Body, BoxFuture, Context, Future, Poll, Service, StdError, http};
+use tonic::server::NamedService;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+use webauthn_rs::prelude::{PublicKeyCredential, RegisterPublicKeyCredential};
+
+use crate::api::node::Node;
+use crate::api::servers::app_state::AppState;
+use crate::api::servers::listen;
+use crate::bootstrap::config::Config;
+use crate::modules::key_usage::{KeyKind, KeyUse, Operation};
+use crate::modules::pin::PinScope;
+use crate::modules::space::Encryption;
+use crate::modules::ssi::did::resolvers::ResolutionError;
+use crate::modules::ssi::did::types::ResolutionOptions;
+use crate::modules::ssi::webauthn::fingerprint::ClientFingerprint;
+use crate::modules::ssi::webauthn::options::CeremonyOverrides;
+use crate::modules::tenancy::Actor;
+use crate::modules::validation;
+
+/// Messages of `flow.v1`, kept by hand in step with
+/// `proto/flow/v1/flow.proto` so building the node doesn't need `protoc`.
+/// `test_grpc_messages_match_the_proto` fails when the two drift apart.
+pub mod proto {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct HealthRequest {}
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct HealthResponse {
+        #[prost(string, tag = "1")]
+        pub status: String,
+        #[prost(string, tag = "2")]
+        pub timestamp: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StartRegistrationRequest {
+        #[prost(string, tag = "1")]
+        pub overrides_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StartRegistrationResponse {
+        #[prost(string, tag = "1")]
+        pub challenge_id: String,
+        #[prost(string, tag = "2")]
+        pub challenge_json: String,
+        #[prost(string, tag = "3")]
+        pub options_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct FinishRegistrationRequest {
+        #[prost(string, tag = "1")]
+        pub challenge_id: String,
+        #[prost(string, tag = "2")]
+        pub credential_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct FinishRegistrationResponse {
+        #[prost(string, tag = "1")]
+        pub did: String,
+        #[prost(string, tag = "2")]
+        pub did_document_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StartAuthenticationRequest {
+        #[prost(string, tag = "1")]
+        pub overrides_json: String,
+        #[prost(string, tag = "2")]
+        pub mediation: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StartAuthenticationResponse {
+        #[prost(string, tag = "1")]
+        pub challenge_id: String,
+        #[prost(string, tag = "2")]
+        pub challenge_json: String,
+        #[prost(string, tag = "3")]
+        pub options_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct FinishAuthenticationRequest {
+        #[prost(string, tag = "1")]
+        pub challenge_id: String,
+        #[prost(string, tag = "2")]
+        pub credential_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Session {
+        #[prost(string, tag = "1")]
+        pub token: String,
+        #[prost(string, tag = "2")]
+        pub expires_at: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct FinishAuthenticationResponse {
+        #[prost(uint32, tag = "1")]
+        pub counter: u32,
+        #[prost(bool, tag = "2")]
+        pub backup_state: bool,
+        #[prost(bool, tag = "3")]
+        pub backup_eligible: bool,
+        #[prost(bool, tag = "4")]
+        pub needs_update: bool,
+        #[prost(message, optional, tag = "5")]
+        pub session: Option<Session>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Space {
+        #[prost(string, tag = "1")]
+        pub key: String,
+        #[prost(string, tag = "2")]
+        pub location: String,
+        #[prost(string, tag = "3")]
+        pub time_created: String,
+        #[prost(bool, tag = "4")]
+        pub client_keys: bool,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ListSpacesRequest {}
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ListSpacesResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub spaces: Vec<Space>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CreateSpaceRequest {
+        #[prost(string, tag = "1")]
+        pub dir: String,
+        #[prost(bool, tag = "2")]
+        pub encrypted: bool,
+        #[prost(bool, tag = "3")]
+        pub client_keys: bool,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CreateSpaceResponse {}
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ResolveDidRequest {
+        #[prost(string, tag = "1")]
+        pub did: String,
+        #[prost(string, tag = "2")]
+        pub accept: String,
+        #[prost(bool, tag = "3")]
+        pub no_cache: bool,
+        #[prost(uint64, tag = "4")]
+        pub timeout_ms: u64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ResolveDidResponse {
+        #[prost(string, tag = "1")]
+        pub result_json: String,
+    }
+}
+
+use proto::*;
+
+pub const SERVICE_NAME: &str = "flow.v1.Flow";
+
+/// Serve gRPC on `GRPC_PORT` on every bind address; never returns when the
+/// port isn't set
+pub async fn start(app_state: &AppState, config: &Config) -> Result<(), AppError> {
+    let Some(port) = config.server.grpc_port else {
+        return std::future::pending().await;
+    };
+    if app_state.tls.is_some() {
+        log::warn!("The gRPC server doesn't serve TLS; expose it through a TLS proxy");
+    }
+
+    let listeners = listen::bind_all(&config.server.listen_addrs(port))?;
+    let servers = listeners
+        .into_iter()
+        .map(|listener| serve(listener, app_state.clone()));
+    futures_util::future::try_join_all(servers).await?;
+    Ok(())
+}
+
+/// Serve the Flow service on a bound listener until shutdown
+pub async fn serve(listener: TcpListener, app_state: AppState) -> Result<(), AppError> {
+    if let Ok(addr) = listener.local_addr() {
+        info!("gRPC Server up on addr: {}", addr);
+    }
+    let stopped = app_state.shutdown.triggered();
+    tonic::transport::Server::builder()
+        .add_service(FlowService::new(app_state))
+        .serve_with_incoming_shutdown(TcpIncoming::from(listener), stopped)
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))
+}
+
+/// The `flow.v1.Flow` service
+#[derive(Clone)]
+pub struct FlowService {
+    app_state: AppState,
+}
+
+impl FlowService {
+    pub fn new(app_state: AppState) -> Self {
+        Self { app_state }
+    }
+
+    async fn health(&self, _: Request<HealthRequest>) -> Result<Response<HealthResponse>, Status> {
+        Ok(Response::new(HealthResponse {
+            status: "healthy".to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+        }))
+    }
+
+    async fn start_registration(
+        &self,
+        request: Request<StartRegistrationRequest>,
+    ) -> Result<Response<StartRegistrationResponse>, Status> {
+        let node = self.app_state.node.read().await;
+        require_guest_session(&node, &request).await?;
+        node.maintenance.check().map_err(status)?;
+        let overrides = overrides(&request.get_ref().overrides_json)?;
+
+        let (challenge, challenge_id, options) = node
+            .start_webauthn_registration_with(Some(&client(&request)), &overrides)
+            .await
+            .map_err(ceremony_failure)?;
+        info!(
+            "WebAuthn registration started over gRPC with challenge_id: {}",
+            challenge_id
+        );
+        Ok(Response::new(StartRegistrationResponse {
+            challenge_id,
+            challenge_json: to_json(&challenge)?,
+            options_json: to_json(&options)?,
+        }))
+    }
+
+    async fn finish_registration(
+        &self,
+        request: Request<FinishRegistrationRequest>,
+    ) -> Result<Response<FinishRegistrationResponse>, Status> {
+        let node = self.app_state.node.read().await;
+        require_guest_session(&node, &request).await?;
+        node.maintenance.check().map_err(status)?;
+        let credential: RegisterPublicKeyCredential =
+            from_json(&request.get_ref().credential_json, "credential")?;
+
+        let challenge_id = &request.get_ref().challenge_id;
+        let (did, did_document_json) = node
+            .finish_webauthn_registration_for(challenge_id, credential, Some(&client(&request)))
+            .await
+            .map_err(|e| {
+                error!(
+                    "WebAuthn registration failed for challenge_id {}: {}",
+                    challenge_id, e
+                );
+                ceremony_failure(e)
+            })?;
+        Ok(Response::new(FinishRegistrationResponse {
+            did,
+            did_document_json,
+        }))
+    }
+
+    async fn start_authentication(
+        &self,
+        request: Request<StartAuthenticationRequest>,
+    ) -> Result<Response<StartAuthenticationResponse>, Status> {
+        let node = self.app_state.node.read().await;
+        let client = client(&request);
+        let request = request.into_inner();
+        let overrides = overrides(&request.overrides_json)?;
+
+        let (challenge, challenge_id, options) = match request.mediation.as_str() {
+            "" => node
+                .start_webauthn_authentication_with(Some(&client), &overrides)
+                .await
+                .map(|(challenge, id, options)| (challenge, id, Some(options))),
+            "conditional" => node
+                .start_webauthn_conditional_authentication_for(Some(&client))
+                .await
+                .map(|(challenge, id)| (challenge, id, None)),
+            other => {
+                return Err(status(AppError::Validation(format!(
+                    "Unsupported mediation '{}'",
+                    other
+                ))));
+            }
+        }
+        .map_err(ceremony_failure)?;
+        Ok(Response::new(StartAuthenticationResponse {
+            challenge_id,
+            challenge_json: to_json(&challenge)?,
+            options_json: options
+                .map(|o| to_json(&o))
+                .transpose()?
+                .unwrap_or_default(),
+        }))
+    }
+
+    async fn finish_authentication(
+        &self,
+        request: Request<FinishAuthenticationRequest>,
+    ) -> Result<Response<FinishAuthenticationResponse>, Status> {
+        let node = self.app_state.node.read().await;
+        let client = client(&request);
+        let ip = request.remote_addr().map(|addr| addr.ip());
+        let request = request.into_inner();
+        let credential: PublicKeyCredential = from_json(&request.credential_json, "credential")?;
+
+        let result = node
+            .finish_webauthn_authentication_for(&request.challenge_id, credential, Some(&client))
+            .await
+            .map_err(|e| {
+                error!(
+                    "WebAuthn authentication failed for challenge_id {}: {}",
+                    request.challenge_id, e
+                );
+                ceremony_failure(e)
+            })?;
+        let (token, session) = node.open_user_session(&result).await.map_err(status)?;
+        node.record_key_use(&KeyUse {
+            kind: KeyKind::Passkey,
+            key_id: URL_SAFE_NO_PAD.encode(result.cred_id().as_ref()),
+            user_id: Some(session.user_id),
+            at: Utc::now(),
+            ip,
+            country: None,
+            operation: Operation::Authenticate,
+        })
+        .map_err(status)?;
+
+        Ok(Response::new(FinishAuthenticationResponse {
+            counter: result.counter(),
+            backup_state: result.backup_state(),
+            backup_eligible: result.backup_eligible(),
+            needs_update: result.needs_update(),
+            session: Some(Session {
+                token,
+                expires_at: session.expires_at.to_rfc3339(),
+            }),
+        }))
+    }
+
+    /// In single-user mode open to a session or a read-only PIN unlock grant,
+    /// as over REST
+    async fn list_spaces(
+        &self,
+        request: Request<ListSpacesRequest>,
+    ) -> Result<Response<ListSpacesResponse>, Status> {
+        let node = self.app_state.node.read().await;
+        let actor = acting(&node, &request, Operation::Read).await?;
+        if actor == Actor::Node {
+            let token = bearer_token(&request)
+                .ok_or_else(|| Status::unauthenticated("Missing unlock token"))?;
+            let unlocked = node.session_actor(token).await.map_err(status)?.is_some()
+                || node
+                    .local_pin()
+                    .grant(token)
+                    .await
+                    .map_err(status)?
+                    .is_some_and(|grant| grant.scope == PinScope::ReadOnly);
+            if !unlocked {
+                return Err(Status::unauthenticated("Invalid or expired unlock token"));
+            }
+        }
+
+        let spaces = node.list_spaces_as(actor).await.map_err(status)?;
+        Ok(Response::new(ListSpacesResponse {
+            spaces: spaces
+                .into_iter()
+                .map(|s| Space {
+                    key: s.key,
+                    location: s.location,
+                    time_created: s.time_created.to_rfc3339(),
+                    client_keys: s.client_keys,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn create_space(
+        &self,
+        request: Request<CreateSpaceRequest>,
+    ) -> Result<Response<CreateSpaceResponse>, Status> {
+        let node = self.app_state.node.read().await;
+        let actor = acting(&node, &request, Operation::Write).await?;
+        let CreateSpaceRequest {
+            dir,
+            encrypted,
+            client_keys,
+        } = request.get_ref();
+        if dir.trim().is_empty() {
+            return Err(Status::invalid_argument("dir is required"));
+        }
+
+        let encryption = Encryption::from_flags(*encrypted, *client_keys).map_err(status)?;
+        node.create_space_with(actor, dir, encryption)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(CreateSpaceResponse {}))
+    }
+
+    async fn resolve_did(
+        &self,
+        request: Request<ResolveDidRequest>,
+    ) -> Result<Response<ResolveDidResponse>, Status> {
+        let resolver = {
+            let node = self.app_state.node.read().await;
+            require_guest_session(&node, &request).await?;
+            node.did_resolver.clone()
+        };
+        let request = request.into_inner();
+        validation::validate_did(&request.did).map_err(status)?;
+
+        let mut options = ResolutionOptions::new();
+        if !request.accept.is_empty() {
+            options = options
+                .with_accept(&request.accept)
+                .map_err(Status::invalid_argument)?;
+        }
+        options.no_cache = request.no_cache.then_some(true);
+        options.timeout_ms = (request.timeout_ms > 0).then_some(request.timeout_ms);
+
+        let result = resolver
+            .resolve_did(&request.did, &options)
+            .await
+            .map_err(resolution_failure)?;
+        Ok(Response::new(ResolveDidResponse {
+            result_json: to_json(&result)?,
+        }))
+    }
+}
+
+impl NamedService for FlowService {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<B> Service<http::Request<B>> for FlowService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let max_message = self.app_state.limits.max_body_bytes;
+        let service = self.clone();
+        macro_rules! route {
+            ($method:ident) => {
+                unary(req, max_message, move |request| {
+                    let service = service.clone();
+                    async move { service.$method(request).await }
+                })
+            };
+        }
+
+        match req.uri().path().strip_prefix("/flow.v1.Flow/") {
+            Some("Health") => route!(health),
+            Some("StartRegistration") => route!(start_registration),
+            Some("FinishRegistration") => route!(finish_registration),
+            Some("StartAuthentication") => route!(start_authentication),
+            Some("FinishAuthentication") => route!(finish_authentication),
+            Some("ListSpaces") => route!(list_spaces),
+            Some("CreateSpace") => route!(create_space),
+            Some("ResolveDid") => route!(resolve_did),
+            _ => Box::pin(async { Ok(Status::unimplemented("Unknown method").into_http()) }),
+        }
+    }
+}
+
+/// A unary method's handler, as tonic expects it
+struct Unary<F>(F);
+
+impl<F, Fut, Req, Res> Service<Request<Req>> for Unary<F>
+where
+    F: FnMut(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Response<Res>, Status>>,
+{
+    type Response = Response<Res>;
+    type Error = Status;
+    type Future = Fut;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        (self.0)(request)
+    }
+}
+
+/// Decode a unary call, run `handler` and encode its reply
+fn unary<B, Req, Res, F, Fut>(
+    req: http::Request<B>,
+    max_message: usize,
+    handler: F,
+) -> BoxFuture<http::Response<tonic::body::Body>, Infallible>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    Req: prost::Message + Default + Send + 'static,
+    Res: prost::Message + Send + 'static,
+    F: FnMut(Request<Req>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Response<Res>, Status>> + Send + 'static,
+{
+    Box::pin(async move {
+        let codec = tonic_prost::ProstCodec::<Res, Req>::default();
+        let mut grpc =
+            tonic::server::Grpc::new(codec).apply_max_message_size_config(Some(max_message), None);
+        Ok(grpc.unary(Unary(handler), req).await)
+    })
+}
+
+fn bearer_token<T>(request: &Request<T>) -> Option<&str> {
+    request
+        .metadata()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// Fingerprint of the calling client, which WebAuthn challenges are bound to
+fn client<T>(request: &Request<T>) -> ClientFingerprint {
+    let user_agent = request
+        .metadata()
+        .get("user-agent")
+        .and_then(|v| v.to_str().ok());
+    ClientFingerprint::new(request.remote_addr().map(|addr| addr.ip()), user_agent)
+}
+
+/// Who the call acts for: the node, or in multi-user mode the user whose
+/// session token is presented. Guest mode requires a session either way.
+async fn acting<T>(
+    node: &Node,
+    request: &Request<T>,
+    operation: Operation,
+) -> Result<Actor, Status> {
+    if operation == Operation::Write {
+        node.maintenance.check().map_err(status)?;
+    }
+    if !node.multi_user.enabled {
+        require_guest_session(node, request).await?;
+        return Ok(Actor::Node);
+    }
+
+    let token = bearer_token(request).ok_or_else(|| Status::unauthenticated("Sign in required"))?;
+    let session = node
+        .user_session(token)
+        .await
+        .map_err(status)?
+        .ok_or_else(|| Status::unauthenticated("Invalid or expired session"))?;
+    node.record_session_use(
+        token,
+        &KeyUse {
+            kind: KeyKind::Session,
+            key_id: session.id.clone(),
+            user_id: Some(session.user_id),
+            at: Utc::now(),
+            ip: request.remote_addr().map(|addr: SocketAddr| addr.ip()),
+            country: None,
+            operation,
+        },
+    )
+    .await
+    .map_err(status)?;
+    Ok(Actor::User(session.user_id))
+}
+
+/// With guest mode on, only sign-in and health are open without a session
+async fn require_guest_session<T>(node: &Node, request: &Request<T>) -> Result<(), Status> {
+    if !node.guest_mode {
+        return Ok(());
+    }
+    let token = bearer_token(request).ok_or_else(|| Status::unauthenticated("Sign in required"))?;
+    match node.session_actor(token).await.map_err(status)? {
+        Some(_) => Ok(()),
+        None => Err(Status::unauthenticated("Invalid or expired session")),
+    }
+}
+
+fn overrides(json: &str) -> Result<CeremonyOverrides, Status> {
+    match json.is_empty() {
+        true => Ok(CeremonyOverrides::default()),
+        false => from_json(json, "overrides"),
+    }
+}
+
+fn from_json<T: DeserializeOwned>(json: &str, what: &str) -> Result<T, Status> {
+    serde_json::from_str(json)
+        .map_err(|e| Status::invalid_argument(format!("Invalid {} format: {}", what, e)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, Status> {
+    serde_json::to_string(value).map_err(|e| Status::internal(e.to_string()))
+}
+
+/// Status for an [`AppError`], matching REST's status codes
+pub fn status(e: AppError) -> Status {
+    match e {
+        AppError::NotFound(msg) => Status::not_found(msg),
+        AppError::Auth(msg) => Status::unauthenticated(msg),
+        AppError::Forbidden(msg) => Status::permission_denied(msg),
+        AppError::RateLimited(msg) | AppError::PayloadTooLarge(msg) => {
+            Status::resource_exhausted(msg)
+        }
+        AppError::Validation(msg) => Status::invalid_argument(msg),
+        AppError::Conflict(msg) | AppError::Replayed(msg) => Status::already_exists(msg),
+        AppError::Maintenance(msg) => Status::unavailable(msg),
+        e => Status::internal(e.to_string()),
+    }
+}
+
+/// Status for a failed ceremony; client mismatches, lockouts and rejected
+/// options are reported as such
+fn ceremony_failure(e: AppError) -> Status {
+    match e {
+        AppError::Forbidden(_) | AppError::RateLimited(_) | AppError::Validation(_) => status(e),
+        e => Status::internal(e.to_string()),
+    }
+}
+
+/// Status for a failed DID resolution
+fn resolution_failure(e: ResolutionError) -> Status {
+    match e {
+        ResolutionError::InvalidDid(_) => Status::invalid_argument(e.to_string()),
+        ResolutionError::NotFound | ResolutionError::Deactivated => {
+            Status::not_found(e.to_string())
+        }
+        ResolutionError::MethodNotSupported(_) | ResolutionError::RepresentationNotSupported(_) => {
+            Status::unimplemented(e.to_string())
+        }
+        ResolutionError::NetworkError(_) => Status::unavailable(e.to_string()),
+        _ => Status::internal(e.to_string()),
+    }
+}
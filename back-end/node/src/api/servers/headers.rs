@@ -0,0 +1,111 @@
+//! Security headers on every HTTP response.
+//!
+//! Responses get `X-Content-Type-Options: nosniff` and a `Referrer-Policy`,
+//! and a `Content-Security-Policy`: published content (shares and sites) may
+//! load what it needs from the node itself, everything else (JSON, mostly)
+//! nothing at all. `Strict-Transport-Security` is only sent over HTTPS, when
+//! the node terminates TLS or a trusted proxy says the client used it;
+//! browsers ignore it over plain HTTP. A header a handler already set is left
+//! alone, so a route can loosen its own policy.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::api::servers::app_state::AppState;
+use crate::api::servers::proxy::RequestOrigin;
+use crate::bootstrap::config::SecurityHeadersConfig;
+
+/// Policy for API responses, which never need to load anything
+pub const API_CSP: &str = "default-src 'none'; frame-ancestors 'none'";
+
+/// Policy for published content: the node's own resources, no plugins, and
+/// never framed elsewhere
+pub const PUBLISHED_CSP: &str = "default-src 'self'; img-src 'self' data:; \
+    style-src 'self' 'unsafe-inline'; object-src 'none'; base-uri 'self'; \
+    form-action 'self'; frame-ancestors 'none'";
+
+/// Where shares and published sites are served from
+pub const PUBLISHED_CONTENT: &str = "/api/v1/public/";
+
+pub const REFERRER_POLICY: &str = "strict-origin-when-cross-origin";
+
+/// What the middleware needs to know about the server
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    pub config: SecurityHeadersConfig,
+    /// The server itself terminates TLS
+    pub tls: bool,
+}
+
+impl SecurityHeaders {
+    pub fn of(app_state: &AppState) -> Arc<Self> {
+        Arc::new(Self {
+            config: app_state.security_headers,
+            tls: app_state.tls.is_some(),
+        })
+    }
+}
+
+/// Middleware adding the security headers to responses
+pub async fn secure(
+    State(security): State<Arc<SecurityHeaders>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = security.config;
+    if !config.enabled {
+        return next.run(request).await;
+    }
+
+    let https = security.tls
+        || request
+            .extensions()
+            .get::<RequestOrigin>()
+            .is_some_and(|RequestOrigin(origin)| origin.starts_with("https://"));
+    let published = request.uri().path().starts_with(PUBLISHED_CONTENT);
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    let mut set_default = |name: HeaderName, value: HeaderValue| {
+        headers.entry(name).or_insert(value);
+    };
+
+    set_default(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    set_default(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static(REFERRER_POLICY),
+    );
+    if config.csp {
+        let policy = match published {
+            true => PUBLISHED_CSP,
+            false => API_CSP,
+        };
+        set_default(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(policy),
+        );
+    }
+    if config.hsts && https {
+        set_default(
+            header::STRICT_TRANSPORT_SECURITY,
+            hsts(config.hsts_max_age_secs),
+        );
+    }
+
+    response
+}
+
+/// `Strict-Transport-Security` value for `max_age` seconds
+pub fn hsts(max_age: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("max-age={}; includeSubDomains", max_age))
+        .expect("digits are a valid header value")
+}
@@ -0,0 +1,301 @@
+//! Error messages in the client's language.
+//!
+//! An error carries a stable `code` for programs to branch on, a `message`
+//! for people in the best of [`LOCALES`] for the client's `Accept-Language`
+//! (English when none fits), and `detail`, the specific reason, which is
+//! left untranslated. REST errors are answered as
+//! `{"code", "message", "detail"}` with a `Content-Language` header;
+//! WebSocket error payloads have the same fields, in the language negotiated
+//! when the connection was opened.
+//!
+//! Codes come from the status, or from a [`MessageCode`] the handler puts
+//! on its response when it knows better. Their texts are in [`CATALOG`].
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::json;
+
+/// Languages messages are available in, the default first
+pub const LOCALES: [&str; 4] = ["en", "de", "es", "fr"];
+
+/// Longest error detail kept; a longer one is dropped
+const MAX_DETAIL_BYTES: usize = 64 * 1024;
+
+/// Text of each code, in the order of [`LOCALES`]
+pub const CATALOG: &[(&str, [&str; 4])] = &[
+    (
+        "bad_request",
+        [
+            "The request is malformed.",
+            "Die Anfrage ist fehlerhaft.",
+            "La solicitud está mal formada.",
+            "La requête est mal formée.",
+        ],
+    ),
+    (
+        "invalid",
+        [
+            "Some of the request's values aren't valid.",
+            "Einige Angaben der Anfrage sind ungültig.",
+            "Algunos valores de la solicitud no son válidos.",
+            "Certaines valeurs de la requête ne sont pas valides.",
+        ],
+    ),
+    (
+        "unauthorized",
+        [
+            "Sign in to continue.",
+            "Melde dich an, um fortzufahren.",
+            "Inicia sesión para continuar.",
+            "Connectez-vous pour continuer.",
+        ],
+    ),
+    (
+        "session_expired",
+        [
+            "Your session has expired; sign in again.",
+            "Deine Sitzung ist abgelaufen; melde dich erneut an.",
+            "Tu sesión ha caducado; vuelve a iniciar sesión.",
+            "Votre session a expiré ; reconnectez-vous.",
+        ],
+    ),
+    (
+        "forbidden",
+        [
+            "You don't have permission to do that.",
+            "Dazu fehlt dir die Berechtigung.",
+            "No tienes permiso para hacer eso.",
+            "Vous n'avez pas l'autorisation de faire cela.",
+        ],
+    ),
+    (
+        "not_found",
+        [
+            "That doesn't exist or is no longer available.",
+            "Das gibt es nicht oder nicht mehr.",
+            "Eso no existe o ya no está disponible.",
+            "Cela n'existe pas ou n'est plus disponible.",
+        ],
+    ),
+    (
+        "conflict",
+        [
+            "That conflicts with the current state; reload and try again.",
+            "Das widerspricht dem aktuellen Stand; lade neu und versuche es erneut.",
+            "Eso entra en conflicto con el estado actual; vuelve a cargar e inténtalo de nuevo.",
+            "Cela entre en conflit avec l'état actuel ; rechargez et réessayez.",
+        ],
+    ),
+    (
+        "replayed",
+        [
+            "That request was already received; sign a new one.",
+            "Diese Anfrage ist bereits eingegangen; signiere eine neue.",
+            "Esa solicitud ya se recibió; firma una nueva.",
+            "Cette requête a déjà été reçue ; signez-en une nouvelle.",
+        ],
+    ),
+    (
+        "too_large",
+        [
+            "The request is too large.",
+            "Die Anfrage ist zu groß.",
+            "La solicitud es demasiado grande.",
+            "La requête est trop volumineuse.",
+        ],
+    ),
+    (
+        "rate_limited",
+        [
+            "Too many attempts; wait a moment and try again.",
+            "Zu viele Versuche; warte kurz und versuche es erneut.",
+            "Demasiados intentos; espera un momento e inténtalo de nuevo.",
+            "Trop de tentatives ; patientez un instant puis réessayez.",
+        ],
+    ),
+    (
+        "timeout",
+        [
+            "The request took too long.",
+            "Die Anfrage hat zu lange gedauert.",
+            "La solicitud tardó demasiado.",
+            "La requête a pris trop de temps.",
+        ],
+    ),
+    (
+        "unavailable",
+        [
+            "The node is busy or unavailable; try again shortly.",
+            "Der Knoten ist ausgelastet oder nicht erreichbar; versuche es gleich noch einmal.",
+            "El nodo está ocupado o no disponible; inténtalo de nuevo en breve.",
+            "Le nœud est occupé ou indisponible ; réessayez sous peu.",
+        ],
+    ),
+    (
+        "maintenance",
+        [
+            "The node is in maintenance and only reads for now; try again later.",
+            "Der Knoten wird gewartet und nimmt vorerst nur Leseanfragen an; versuche es später noch einmal.",
+            "El nodo está en mantenimiento y por ahora solo admite lecturas; inténtalo más tarde.",
+            "Le nœud est en maintenance et n'accepte que des lectures pour l'instant ; réessayez plus tard.",
+        ],
+    ),
+    (
+        "not_supported",
+        [
+            "That isn't supported.",
+            "Das wird nicht unterstützt.",
+            "Eso no es compatible.",
+            "Ce n'est pas pris en charge.",
+        ],
+    ),
+    (
+        "unknown_action",
+        [
+            "That action isn't supported.",
+            "Diese Aktion wird nicht unterstützt.",
+            "Esa acción no es compatible.",
+            "Cette action n'est pas prise en charge.",
+        ],
+    ),
+    (
+        "unsupported_version",
+        [
+            "This protocol version isn't supported.",
+            "Diese Protokollversion wird nicht unterstützt.",
+            "Esta versión del protocolo no es compatible.",
+            "Cette version du protocole n'est pas prise en charge.",
+        ],
+    ),
+    (
+        "internal",
+        [
+            "Something went wrong on the node.",
+            "Auf dem Knoten ist ein Fehler aufgetreten.",
+            "Algo salió mal en el nodo.",
+            "Une erreur s'est produite sur le nœud.",
+        ],
+    ),
+];
+
+/// Response extension giving a more specific code than the status does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageCode(pub &'static str);
+
+/// The best of [`LOCALES`] for an `Accept-Language` header
+pub fn negotiate(accept_language: Option<&str>) -> &'static str {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty())?;
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            (quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so equal weights keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| {
+            let language = tag.split('-').next().unwrap_or(tag);
+            match language {
+                "*" => Some(LOCALES[0]),
+                _ => LOCALES
+                    .into_iter()
+                    .find(|locale| locale.eq_ignore_ascii_case(language)),
+            }
+        })
+        .unwrap_or(LOCALES[0])
+}
+
+/// Locale for a request's headers
+pub fn locale_of(headers: &HeaderMap) -> &'static str {
+    negotiate(
+        headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok()),
+    )
+}
+
+/// Text of `code` in `locale`; that of `internal` for a code not in the
+/// catalog, and English for a locale not in [`LOCALES`]
+pub fn message(code: &str, locale: &str) -> &'static str {
+    let index = LOCALES
+        .iter()
+        .position(|known| *known == locale)
+        .unwrap_or(0);
+    let texts = CATALOG
+        .iter()
+        .find(|(known, _)| *known == code)
+        .or_else(|| CATALOG.iter().find(|(known, _)| *known == "internal"))
+        .map(|(_, texts)| texts)
+        .expect("catalog has internal");
+    texts[index]
+}
+
+/// Code for an error status
+pub fn code_for(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "invalid",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => "timeout",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::PAYLOAD_TOO_LARGE => "too_large",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limited",
+        StatusCode::NOT_IMPLEMENTED => "not_supported",
+        StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+        status if status.is_client_error() => "bad_request",
+        _ => "internal",
+    }
+}
+
+/// Middleware answering plain-text and empty error responses as localized
+/// JSON. Errors with a body of another type (JSON, HTML pages) are left
+/// alone.
+pub async fn localize(request: Request, next: Next) -> Response {
+    let locale = locale_of(request.headers());
+    let response = next.run(request).await;
+
+    let status = response.status();
+    let plain = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_none_or(|value| value.as_bytes().starts_with(b"text/plain"));
+    if !(status.is_client_error() || status.is_server_error()) || !plain {
+        return response;
+    }
+
+    let code = response
+        .extensions()
+        .get::<MessageCode>()
+        .map_or_else(|| code_for(status), |code| code.0);
+    let (mut parts, body) = response.into_parts();
+    let detail = to_bytes(body, MAX_DETAIL_BYTES).await.unwrap_or_default();
+
+    let body = json!({
+        "code": code,
+        "message": message(code, locale),
+        "detail": String::from_utf8_lossy(&detail),
+    });
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    parts
+        .headers
+        .insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale));
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
@@ -0,0 +1,36 @@
+//! Listening sockets for the REST and WebSocket servers.
+//!
+//! Every configured address is bound before either server starts serving, so
+//! a bad address fails startup instead of leaving a partial server up. IPv6
+//! sockets are IPv6-only: `::` and `0.0.0.0` can then be bound side by side
+//! rather than the first claiming both families.
+
+use std::net::SocketAddr;
+
+use errors::AppError;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::TcpListener;
+
+const BACKLOG: i32 = 1024;
+
+/// Bind one listener per address
+pub fn bind_all(addrs: &[SocketAddr]) -> Result<Vec<TcpListener>, AppError> {
+    addrs.iter().map(|addr| bind(*addr)).collect()
+}
+
+fn bind(addr: SocketAddr) -> Result<TcpListener, AppError> {
+    let failed = |e: std::io::Error| AppError::Config(format!("Cannot listen on {}: {}", addr, e));
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))
+        .map_err(failed)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true).map_err(failed)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true).map_err(failed)?;
+    socket.set_nonblocking(true).map_err(failed)?;
+    socket.bind(&addr.into()).map_err(failed)?;
+    socket.listen(BACKLOG).map_err(failed)?;
+
+    TcpListener::from_std(socket.into()).map_err(failed)
+}
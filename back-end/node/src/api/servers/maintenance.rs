@@ -0,0 +1,69 @@
+//! Refusing REST writes during maintenance; see
+//! [`crate::modules::maintenance`].
+//!
+//! Reads (`GET`, `HEAD`, `OPTIONS`) always go through. So do the few writes
+//! an admin needs to end maintenance (signing in, the toggle itself) and
+//! routes that are only `POST`ed for their body, changing nothing.
+
+use axum::{
+    Extension,
+    extract::{Request, State},
+    http::{Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::api::servers::app_state::AppState;
+use crate::api::servers::i18n::MessageCode;
+use crate::api::servers::throttle;
+
+/// Writes allowed during maintenance. Each entry covers itself and
+/// everything below it; `*` stands for one path segment.
+pub const ALLOWED: &[&str] = &[
+    "/api/v1/admin/maintenance",
+    "/api/v1/admin/shutdown",
+    "/api/v1/webauthn/start_authentication",
+    "/api/v1/webauthn/finish_authentication",
+    "/api/v1/pin/unlock",
+    "/api/v1/auth/logout",
+    "/api/v1/spaces/*/manifest/verify",
+    "/api/v1/spaces/*/manifest/compare",
+    "/api/v1/timestamps/verify",
+    "/api/v1/credentials/issue",
+    "/api/v1/node/attest",
+    "/api/v1/dids/resolve_batch",
+];
+
+/// Whether a request may run while writes are refused
+pub fn allowed(method: &Method, path: &str) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || ALLOWED
+            .iter()
+            .any(|pattern| throttle::covers(pattern, path))
+}
+
+/// Middleware answering writes with `503` and `Retry-After` during
+/// maintenance
+pub async fn refuse_writes(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !allowed(request.method(), request.uri().path()) {
+        let maintenance = app_state.node.read().await.maintenance.clone();
+        if let Err(e) = maintenance.check() {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(
+                    header::RETRY_AFTER,
+                    maintenance.retry_after().as_secs().to_string(),
+                )],
+                Extension(MessageCode("maintenance")),
+                e.to_string(),
+            )
+                .into_response();
+        }
+    }
+
+    next.run(request).await
+}
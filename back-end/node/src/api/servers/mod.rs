@@ -1,3 +1,18 @@
+pub mod access;
 pub mod app_state;
+pub mod body;
+pub mod caching;
+pub mod compression;
+pub mod cors;
+pub mod grpc;
+pub mod headers;
+pub mod i18n;
+pub mod listen;
+pub mod maintenance;
+pub mod proxy;
 pub mod rest;
+pub mod telemetry;
+pub mod throttle;
+pub mod tls;
+pub mod validate;
 pub mod websocket;
@@ -0,0 +1,145 @@
+//! Running behind a reverse proxy.
+//!
+//! Requests from a trusted proxy carry the client's address and scheme in
+//! `X-Forwarded-For` and `X-Forwarded-Proto`. The middleware here swaps them
+//! in for the proxy's own, so fingerprints, lockouts and generated links see
+//! the real client. A trusted proxy may also pass on the client's country
+//! (see [`ProxyConfig::country_header`]). Headers from anyone else are
+//! ignored, as a client could set them to anything.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::bootstrap::config::ProxyConfig;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// Origin the client addressed, e.g. `https://flow.example.com`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestOrigin(pub String);
+
+/// Country of the client, an uppercase ISO 3166-1 alpha-2 code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCountry(pub String);
+
+/// What the middleware needs to know about the server
+#[derive(Debug, Clone)]
+pub struct Forwarding {
+    pub proxy: ProxyConfig,
+    /// The server itself terminates TLS
+    pub tls: bool,
+}
+
+/// Middleware resolving the client's address and origin
+pub async fn forwarded(
+    State(forwarding): State<Arc<Forwarding>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let trusted = peer.is_some_and(|peer| forwarding.proxy.trusts(peer.ip()));
+
+    if trusted
+        && let Some(peer) = peer
+        && let Some(client) = forwarded_client(&forwarding.proxy, request.headers())
+    {
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(client, peer.port())));
+    }
+
+    if trusted && let Some(country) = forwarded_country(&forwarding.proxy, request.headers()) {
+        request.extensions_mut().insert(ClientCountry(country));
+    }
+
+    let origin = origin(&forwarding, request.headers(), trusted);
+    if let Some(origin) = origin {
+        request.extensions_mut().insert(RequestOrigin(origin));
+    }
+
+    next.run(request).await
+}
+
+/// The client a trusted proxy forwarded for: the nearest address in
+/// `X-Forwarded-For` that isn't itself a trusted proxy
+pub fn forwarded_client(proxy: &ProxyConfig, headers: &HeaderMap) -> Option<IpAddr> {
+    let hops = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .map(|value| value.to_str().ok())
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .map(parse_hop)
+        .collect::<Option<Vec<_>>>()?;
+
+    hops.iter()
+        .rev()
+        .find(|ip| !proxy.trusts(**ip))
+        // Every hop is a proxy: the first one saw the client
+        .or(hops.first())
+        .copied()
+}
+
+/// The country in the configured header, if it's a plausible country code.
+/// Proxies use `XX` or `T1` for unknown or Tor, which aren't countries.
+fn forwarded_country(proxy: &ProxyConfig, headers: &HeaderMap) -> Option<String> {
+    let value = headers
+        .get(proxy.country_header.as_deref()?)?
+        .to_str()
+        .ok()?
+        .trim()
+        .to_ascii_uppercase();
+    (value.len() == 2 && value.bytes().all(|b| b.is_ascii_uppercase()) && value != "XX")
+        .then_some(value)
+}
+
+/// An `X-Forwarded-For` entry: an address, optionally with a port
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim();
+    hop.parse::<IpAddr>()
+        .ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// The configured external origin, or one built from the request
+fn origin(forwarding: &Forwarding, headers: &HeaderMap, trusted: bool) -> Option<String> {
+    if let Some(origin) = &forwarding.proxy.external_origin {
+        return Some(origin.clone());
+    }
+
+    let forwarded_proto = headers
+        .get(X_FORWARDED_PROTO)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(str::trim)
+        .filter(|proto| trusted && matches!(*proto, "http" | "https"));
+    let scheme = match forwarded_proto {
+        Some(proto) => proto,
+        None if forwarding.tls => "https",
+        None => "http",
+    };
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok())?;
+    Some(format!("{}://{}", scheme, host))
+}
+
+/// Serve `router` under the configured base path
+pub fn nest(router: Router, proxy: &ProxyConfig) -> Router {
+    if proxy.base_path.is_empty() {
+        router
+    } else {
+        Router::new().nest(&proxy.base_path, router)
+    }
+}
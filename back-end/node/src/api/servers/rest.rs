@@ -1,41 +1,92 @@
+use crate::api::node::Node;
+use crate::api::servers::proxy::{self, ClientCountry, Forwarding, RequestOrigin};
+use crate::api::servers::tls::TlsListener;
+use crate::api::servers::validate::ValidPath;
+use crate::api::servers::{
+    access, body, caching, compression, cors, headers, i18n, listen, maintenance, telemetry,
+    throttle,
+};
+use crate::modules::account::{AccountExport, Deletion, UserProfile};
+use crate::modules::anchor::SpaceAnchor;
+use crate::modules::archive;
+use crate::modules::health;
+use crate::modules::key_rotation::SignedRotation;
+use crate::modules::key_usage::{KeyKind, KeyStats, KeyUse, Operation};
+use crate::modules::maintenance::Maintenance;
+use crate::modules::manifest::SignedManifest;
+use crate::modules::manifest_summary::{ManifestSummary, SummaryComparison};
+use crate::modules::merkle::MerkleNode;
+use crate::modules::mtls::PeerIdentity;
+use crate::modules::node_info::NodeInfo;
+use crate::modules::p2p::{Message, MessageHandler, PublishMessages};
+use crate::modules::peer_request::{self, Target};
+use crate::modules::pin::{PinScope, PinUnlock};
+use crate::modules::profile::ProfileUpdate;
+use crate::modules::resources;
+use crate::modules::scheduler::NewTask;
+use crate::modules::space;
+use crate::modules::ssi::did::DidResolver;
+use crate::modules::ssi::did::resolvers::ResolutionError;
+use crate::modules::ssi::did::types::ResolutionOptions;
+use crate::modules::ssi::vc::{CredentialRequest, IssuedCredential};
+use crate::modules::ssi::webauthn::fingerprint::ClientFingerprint;
+use crate::modules::ssi::webauthn::options::CeremonyOverrides;
+use crate::modules::ssi::webauthn::passkeys::PasskeySummary;
+use crate::modules::storage_stats;
+use crate::modules::tenancy::{Actor, UserSession};
+use crate::modules::timestamp::{self, TimestampProof, TimestampVerification};
+use crate::modules::updater;
+use crate::modules::validation::{self, Did, FilePath, KvKey, SpaceKey};
+use crate::modules::webhook::NewWebhook;
 use crate::{api::servers::app_state::AppState, bootstrap::config::Config};
 use axum::{
     Router,
-    extract::State,
-    http::{HeaderValue, Method, StatusCode},
-    response::Json,
-    routing::{get, post},
+    body::Body,
+    body::Bytes,
+    extract::{
+        ConnectInfo, DefaultBodyLimit, Extension, FromRequest, FromRequestParts, OriginalUri, Path,
+        Query, Request, State,
+    },
+    http::{HeaderMap, Method, StatusCode, header, request::Parts},
+    middleware,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, patch, post},
+    serve::ListenerExt,
 };
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
 use errors::AppError;
+use event::schema;
+use futures_util::{FutureExt, StreamExt};
 use log::{error, info};
+use serde::Deserialize;
 use serde_json::{Value, json};
-use tower_http::cors::{Any, CorsLayer};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use webauthn_rs::prelude::{PublicKeyCredential, RegisterPublicKeyCredential};
 
 /// Build the router with all routes configured
 pub fn build_router(app_state: AppState) -> Router {
-    // Configure CORS
-    let cors = CorsLayer::new()
-        // Allow requests from these origins
-        .allow_origin(["http://localhost:3000".parse::<HeaderValue>().unwrap()])
-        // Allow these HTTP methods
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::OPTIONS,
-        ])
-        // Allow headers
-        .allow_headers(Any)
-        // Cache preflight requests for 1 hour
-        .max_age(std::time::Duration::from_secs(3600));
+    let cors = match &app_state.server {
+        Some(server) => cors::layer(&server.allowed_origins),
+        None => cors::layer(cors::DEFAULT_ORIGINS),
+    };
+
+    let plugin_routes = app_state.plugins.router();
+    let security = headers::SecurityHeaders::of(&app_state);
+    let forwarding = Arc::new(Forwarding {
+        proxy: app_state.proxy.clone(),
+        tls: app_state.tls.is_some(),
+    });
+    let proxy = app_state.proxy.clone();
 
     // Configure Router
-    Router::new()
+    let router = Router::new()
         .route(
             "/api/v1/webauthn/start_registration",
-            get(start_webauthn_registration),
+            get(start_webauthn_registration).post(start_webauthn_registration),
         )
         .route(
             "/api/v1/webauthn/finish_registration",
@@ -49,45 +100,459 @@ pub fn build_router(app_state: AppState) -> Router {
             "/api/v1/webauthn/finish_authentication",
             post(finish_webauthn_authentication),
         )
-        .route("/api/v1/spaces", post(create_space))
+        .route("/api/v1/webauthn/passkeys", get(list_passkeys))
+        .route(
+            "/api/v1/webauthn/passkeys/{id}",
+            patch(rename_passkey).delete(delete_passkey),
+        )
+        .route(
+            "/api/v1/webauthn/passkeys/{id}/restore",
+            post(restore_passkey),
+        )
+        .route("/api/v1/auth/session", get(get_auth_session))
+        .route("/api/v1/auth/logout", post(logout))
+        .route(
+            "/api/v1/users/me",
+            get(get_profile)
+                .patch(update_profile)
+                .delete(delete_account),
+        )
+        .route("/api/v1/users/me/export", get(export_account))
+        .route(
+            "/api/v1/users/me/cancel_deletion",
+            post(cancel_account_deletion),
+        )
+        .route("/api/v1/spaces", get(list_spaces).post(create_space))
+        .route("/api/v1/kv", get(list_kv))
+        .route("/api/v1/events/schema", get(event_schema))
+        .route(
+            "/api/v1/settings/{namespace}",
+            get(get_settings).put(put_settings),
+        )
+        .route(
+            "/api/v1/kv/{key}",
+            get(get_kv).put(put_kv).delete(delete_kv),
+        )
+        .route(
+            "/api/v1/spaces/{key}/files/{*path}",
+            get(download_file).put(upload_file),
+        )
+        .route(
+            "/api/v1/spaces/{key}/index",
+            get(get_space_index).put(put_space_index),
+        )
+        .route("/api/v1/spaces/{key}/archive", get(download_archive))
+        .route("/api/v1/spaces/{key}/manifest", get(get_space_manifest))
+        .route(
+            "/api/v1/spaces/{key}/manifest/verify",
+            post(verify_space_manifest),
+        )
+        .route(
+            "/api/v1/spaces/{key}/manifest/summary",
+            get(get_space_manifest_summary),
+        )
+        .route(
+            "/api/v1/spaces/{key}/manifest/compare",
+            post(compare_space_manifest),
+        )
+        .route("/api/v1/spaces/{key}/merkle", get(get_space_merkle))
+        .route(
+            "/api/v1/spaces/{key}/merkle/rebuild",
+            post(rebuild_space_merkle),
+        )
+        .route(
+            "/api/v1/spaces/{key}/anchors",
+            get(list_space_anchors).post(anchor_space),
+        )
+        .route("/api/v1/tasks", get(list_tasks).post(create_task))
+        .route("/api/v1/tasks/{id}", get(get_task).delete(remove_task))
+        .route("/api/v1/tasks/{id}/run", post(run_task))
+        .route("/api/v1/tasks/{id}/runs", get(get_task_runs))
+        .route("/api/v1/pin", post(set_pin).delete(clear_pin))
+        .route("/api/v1/pin/unlock", post(unlock_with_pin))
+        .route("/api/v1/node", get(get_node_info))
+        .route("/api/v1/node/did.json", get(get_node_did_document))
+        .route("/api/v1/dids/resolve/{did}", get(resolve_did))
+        .route("/api/v1/dids/resolve_batch", post(resolve_did_batch))
+        .route("/api/v1/dids/{did}/document", get(get_did_document))
+        .route("/api/v1/node/attest", post(attest_node))
+        .route("/api/v1/peer/messages", post(receive_peer_message))
+        .route("/api/v1/identity/rotate", post(rotate_identity))
+        .route("/api/v1/credentials/issue", post(issue_credential))
+        .route("/api/v1/keys/usage", get(list_key_usage))
+        .route("/api/v1/keys/usage/{kind}/{id}", get(get_key_usage))
+        .route("/api/v1/timestamps", post(create_timestamp))
+        .route("/api/v1/timestamps/verify", post(verify_timestamp))
+        .route("/api/v1/timestamps/{digest}", get(get_timestamp))
         .route("/api/v1/health", get(health_check))
-        .with_state(app_state)
+        .route("/api/v1/health/live", get(health_live))
+        .route("/api/v1/health/ready", get(health_ready))
+        .route("/api/v1/admin/update", get(get_update_status))
+        .route(
+            "/api/v1/admin/lockouts",
+            get(list_lockouts).delete(clear_lockout),
+        )
+        .route(
+            "/api/v1/admin/webhooks",
+            get(list_webhooks).post(register_webhook),
+        )
+        .route("/api/v1/admin/webhooks/{id}", delete(remove_webhook))
+        .route("/api/v1/admin/compression", get(get_compression_stats))
+        .route("/api/v1/admin/stats", get(get_resource_stats))
+        .route("/api/v1/admin/metrics", get(get_metrics))
+        .route("/api/v1/admin/shutdown", post(shutdown_node))
+        .route("/api/v1/admin/telemetry", get(get_telemetry))
+        .route(
+            "/api/v1/admin/maintenance",
+            get(get_maintenance).put(set_maintenance),
+        )
+        .route("/api/v1/{entity}/{id}/history", get(get_entity_history))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            telemetry::count,
+        ))
+        .with_state(app_state.clone())
+        .merge(plugin_routes)
+        .layer(DefaultBodyLimit::max(app_state.limits.max_body_bytes))
+        .layer(middleware::from_fn(compression::count_original))
+        .layer(compression::layer(&app_state.compression))
+        .layer(middleware::from_fn_with_state(
+            app_state.compression_stats.clone(),
+            compression::count_compressed,
+        ))
+        .layer(middleware::from_fn_with_state(
+            throttle::RequestLimits::new(&app_state.request_limits),
+            throttle::limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            maintenance::refuse_writes,
+        ))
+        .layer(middleware::from_fn_with_state(
+            app_state,
+            access::require_session,
+        ))
+        .layer(middleware::from_fn(i18n::localize))
         .layer(cors)
+        .layer(middleware::from_fn_with_state(security, headers::secure))
+        .layer(middleware::from_fn_with_state(forwarding, proxy::forwarded));
+
+    proxy::nest(router, &proxy)
 }
 
+/// Bind every configured address, then serve on all of them
 pub async fn start(app_state: &AppState, config: &Config) -> Result<(), AppError> {
     let app = build_router(app_state.clone());
 
-    let bind_addr = format!("0.0.0.0:{}", config.server.rest_port);
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    axum::serve(listener, app).await?;
+    let addrs = config.server.listen_addrs(config.server.rest_port);
+    let listeners = listen::bind_all(&addrs)?;
+    let servers = listeners
+        .into_iter()
+        .map(|listener| {
+            if let Ok(addr) = listener.local_addr() {
+                info!("Rest Server up on addr: {}", addr);
+            }
+            let app = app
+                .clone()
+                .into_make_service_with_connect_info::<SocketAddr>();
+            // In-flight requests finish once shutdown starts
+            let stopped = app_state.shutdown.triggered();
+            Ok(match &app_state.tls {
+                Some(tls) => {
+                    let listener = TlsListener::new(listener, tls.clone())?.tap_io(|_| {});
+                    axum::serve(listener, app)
+                        .with_graceful_shutdown(stopped)
+                        .into_future()
+                        .boxed()
+                }
+                None => axum::serve(listener, app)
+                    .with_graceful_shutdown(stopped)
+                    .into_future()
+                    .boxed(),
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+    futures_util::future::try_join_all(servers).await?;
+
+    Ok(())
+}
+
+/// Fingerprint of the calling client, which WebAuthn challenges are bound to
+pub(crate) struct Client(pub(crate) ClientFingerprint);
+
+impl Client {
+    pub(crate) fn of(parts: &Parts) -> Self {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok());
+
+        Client(ClientFingerprint::new(ip, user_agent))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Client {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Client::of(parts))
+    }
+}
+
+/// Where the calling client is, as far as key usage cares
+pub(crate) struct ClientPlace {
+    pub(crate) ip: Option<IpAddr>,
+    pub(crate) country: Option<String>,
+}
+
+impl ClientPlace {
+    pub(crate) fn of(parts: &Parts) -> Self {
+        ClientPlace {
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+            country: parts
+                .extensions
+                .get::<ClientCountry>()
+                .map(|ClientCountry(country)| country.clone()),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientPlace {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientPlace::of(parts))
+    }
+}
 
-    info!("Rest Server up on addr: {}", &bind_addr);
+/// Record the use of a session's token; see [`Node::record_session_use`]
+async fn record_session_use(
+    node: &Node,
+    parts: &Parts,
+    token: &str,
+    session: &UserSession,
+) -> Result<(), (StatusCode, String)> {
+    let place = ClientPlace::of(parts);
+    let operation = match parts.method {
+        Method::GET | Method::HEAD | Method::OPTIONS => Operation::Read,
+        _ => Operation::Write,
+    };
+    node.record_session_use(
+        token,
+        &KeyUse {
+            kind: KeyKind::Session,
+            key_id: session.id.clone(),
+            user_id: Some(session.user_id),
+            at: Utc::now(),
+            ip: place.ip,
+            country: place.country,
+            operation,
+        },
+    )
+    .await
+    .map_err(error_response)
+}
 
+/// Refuse `actor` unless it is the node itself; node-wide state (tasks,
+/// webhooks, lockouts, the local PIN) isn't any one user's to manage
+fn node_only(actor: Actor, what: &str) -> Result<(), (StatusCode, String)> {
+    if actor != Actor::Node {
+        return Err(error_response(AppError::Forbidden(format!(
+            "Only the node may {}",
+            what
+        ))));
+    }
     Ok(())
 }
 
+/// Who the request acts for: the node, or in multi-user mode the user whose
+/// session token is presented (`Authorization: Bearer`)
+struct Acting(Actor);
+
+impl FromRequestParts<AppState> for Acting {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let node = state.node.read().await;
+        if !node.multi_user.enabled {
+            return Ok(Acting(Actor::Node));
+        }
+
+        let token = bearer_token(&parts.headers)
+            .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Sign in required").into_response())?;
+        let session = node
+            .user_session(token)
+            .await
+            .map_err(|e| error_response(e).into_response())?
+            .ok_or_else(expired_session)?;
+        record_session_use(&node, parts, token, &session)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        Ok(Acting(Actor::User(session.user_id)))
+    }
+}
+
+/// The session presented with the request (`Authorization: Bearer`),
+/// required in every mode
+struct SignedIn {
+    token: String,
+    session: UserSession,
+}
+
+impl FromRequestParts<AppState> for SignedIn {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers)
+            .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Sign in required").into_response())?;
+        let node = state.node.read().await;
+        let session = node
+            .user_session(token)
+            .await
+            .map_err(|e| error_response(e).into_response())?
+            .ok_or_else(expired_session)?;
+        record_session_use(&node, parts, token, &session)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        Ok(SignedIn {
+            token: token.to_string(),
+            session,
+        })
+    }
+}
+
+impl SignedIn {
+    /// Who the session acts for: its user in multi-user mode, otherwise the
+    /// node
+    fn actor(&self, node: &Node) -> Actor {
+        match node.multi_user.enabled {
+            true => Actor::User(self.session.user_id),
+            false => Actor::Node,
+        }
+    }
+}
+
+/// A request from another node, signed with its identity key (see
+/// [`peer_request`]), and its body
+struct SignedPeer {
+    peer: PeerIdentity,
+    body: Bytes,
+}
+
+impl FromRequest<AppState> for SignedPeer {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        let signature = request
+            .headers()
+            .get(peer_request::SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Peer signature required").into_response())?;
+        let method = request.method().clone();
+        // As the peer sent it, before any base path was taken off
+        let path = request
+            .extensions()
+            .get::<OriginalUri>()
+            .map_or(request.uri(), |OriginalUri(uri)| uri)
+            .path_and_query()
+            .map_or("/", |path| path.as_str())
+            .to_string();
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let node = state.node.read().await;
+        let target = Target {
+            audience: &node.node_data.id,
+            method: method.as_str(),
+            path: &path,
+            body: &body,
+        };
+        match node.verify_peer_request(&signature, &target).await {
+            Ok(peer) => Ok(SignedPeer { peer, body }),
+            Err(e @ AppError::Replayed(_)) => {
+                let (status, message) = error_response(e);
+                Err((status, Extension(i18n::MessageCode("replayed")), message).into_response())
+            }
+            Err(e) => Err(error_response(e).into_response()),
+        }
+    }
+}
+
+/// Answer for a session token that is unknown, expired or closed
+fn expired_session() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Extension(i18n::MessageCode("session_expired")),
+        "Invalid or expired session",
+    )
+        .into_response()
+}
+
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// Status for a failed ceremony; client mismatches, lockouts and rejected
+/// options are reported as such
+fn ceremony_failure(e: AppError) -> (StatusCode, String) {
+    match e {
+        AppError::Forbidden(_) | AppError::RateLimited(_) | AppError::Validation(_) => {
+            error_response(e)
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// `GET` starts with the server defaults; `POST` may carry [`CeremonyOverrides`]
 async fn start_webauthn_registration(
     State(app_state): State<AppState>,
+    Client(client): Client,
+    overrides: Option<Json<CeremonyOverrides>>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
     let node = app_state.node.read().await;
-    match node.start_webauthn_registration().await {
-        Ok((challenge, challenge_key)) => {
+    let overrides = overrides.map(|Json(o)| o).unwrap_or_default();
+    match node
+        .start_webauthn_registration_with(Some(&client), &overrides)
+        .await
+    {
+        Ok((challenge, challenge_key, options)) => {
             info!(
                 "WebAuthn registration started successfully with challenge_id: {}",
                 challenge_key
             );
             Ok(Json(json!({
                 "challenge": challenge,
-                "challenge_id": challenge_key
+                "challenge_id": challenge_key,
+                "options": options
             })))
         }
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err(ceremony_failure(e)),
     }
 }
 
 async fn finish_webauthn_registration(
     State(app_state): State<AppState>,
+    Client(client): Client,
     Json(payload): Json<Value>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
     let challenge_id = payload["challenge_id"].as_str().ok_or_else(|| {
@@ -113,14 +578,14 @@ async fn finish_webauthn_registration(
 
     let node = app_state.node.read().await;
     let (did, did_document) = node
-        .finish_webauthn_registration(challenge_id, reg_credential)
+        .finish_webauthn_registration_for(challenge_id, reg_credential, Some(&client))
         .await
         .map_err(|e| {
             error!(
                 "WebAuthn registration failed for challenge_id {}: {}",
                 challenge_id, e
             );
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            ceremony_failure(e)
         })?;
 
     Ok(Json(json!({
@@ -131,27 +596,60 @@ async fn finish_webauthn_registration(
     })))
 }
 
+#[derive(Debug, Deserialize)]
+struct StartAuthenticationQuery {
+    /// `conditional` for passkey autofill
+    mediation: Option<String>,
+}
+
 async fn start_webauthn_authentication(
     State(app_state): State<AppState>,
+    Client(client): Client,
+    Query(query): Query<StartAuthenticationQuery>,
+    overrides: Option<Json<CeremonyOverrides>>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
     let node = app_state.node.read().await;
-    match node.start_webauthn_authentication().await {
-        Ok((challenge, challenge_id)) => {
+    let overrides = overrides.map(|Json(o)| o).unwrap_or_default();
+    let started = match query.mediation.as_deref() {
+        None => node
+            .start_webauthn_authentication_with(Some(&client), &overrides)
+            .await
+            .map(|(challenge, challenge_id, options)| (challenge, challenge_id, Some(options))),
+        // Autofill ceremonies wait on the page, so they keep their own timeout
+        Some("conditional") => node
+            .start_webauthn_conditional_authentication_for(Some(&client))
+            .await
+            .map(|(challenge, challenge_id)| (challenge, challenge_id, None)),
+        Some(other) => {
+            return Err(error_response(AppError::Validation(format!(
+                "Unsupported mediation '{}'",
+                other
+            ))));
+        }
+    };
+    match started {
+        Ok((challenge, challenge_id, options)) => {
             info!(
                 "WebAuthn authentication started successfully with challenge_id: {}",
                 challenge_id
             );
-            Ok(Json(json!({
+            let mut body = json!({
                 "challenge": challenge,
                 "challenge_id": challenge_id
-            })))
+            });
+            if let Some(options) = options {
+                body["options"] = json!(options);
+            }
+            Ok(Json(body))
         }
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err(ceremony_failure(e)),
     }
 }
 
 async fn finish_webauthn_authentication(
     State(app_state): State<AppState>,
+    Client(client): Client,
+    place: ClientPlace,
     Json(payload): Json<Value>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
     let challenge_id = payload["challenge_id"].as_str().ok_or_else(|| {
@@ -177,39 +675,1513 @@ async fn finish_webauthn_authentication(
 
     let node = app_state.node.read().await;
     let auth_result = node
-        .finish_webauthn_authentication(challenge_id, auth_credential)
+        .finish_webauthn_authentication_for(challenge_id, auth_credential, Some(&client))
         .await
         .map_err(|e| {
             error!(
                 "WebAuthn authentication failed for challenge_id {}: {}",
                 challenge_id, e
             );
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            ceremony_failure(e)
         })?;
 
-    Ok(Json(json!({
+    let mut response = json!({
         "verified": true,
         "message": "Authentication successful",
         "counter": auth_result.counter(),
         "backup_state": auth_result.backup_state(),
         "backup_eligible": auth_result.backup_eligible(),
         "needs_update": auth_result.needs_update()
-    })))
+    });
+
+    // Multi-user and guest mode require the session; otherwise it signs in
+    // to the few routes that ask for one
+    let (token, session) = node
+        .open_user_session(&auth_result)
+        .await
+        .map_err(error_response)?;
+    node.record_key_use(&KeyUse {
+        kind: KeyKind::Passkey,
+        key_id: URL_SAFE_NO_PAD.encode(auth_result.cred_id().as_ref()),
+        user_id: Some(session.user_id),
+        at: Utc::now(),
+        ip: place.ip,
+        country: place.country,
+        operation: Operation::Authenticate,
+    })
+    .map_err(error_response)?;
+    response["session"] = json!({"token": token, "expiresAt": session.expires_at});
+
+    Ok(Json(response))
+}
+
+/// Passkeys of the signed-in user; every user's on a single-user node
+async fn list_passkeys(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let passkeys = node.list_passkeys_as(actor).await.map_err(error_response)?;
+    Ok(Json(json!({ "passkeys": passkeys })))
+}
+
+#[derive(Deserialize)]
+struct RenamePasskey {
+    name: String,
+    /// Version the client read; a stale one is refused with `409 Conflict`
+    #[serde(default)]
+    version: Option<i32>,
+}
+
+async fn rename_passkey(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<i32>,
+    Json(payload): Json<RenamePasskey>,
+) -> Result<Json<PasskeySummary>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let passkey = node
+        .rename_passkey_as(actor, id, &payload.name, payload.version)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(passkey))
+}
+
+/// Refused with `409 Conflict` for a user's last passkey
+async fn delete_passkey(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<i32>,
+) -> Result<Json<PasskeySummary>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let passkey = node
+        .delete_passkey_as(actor, id)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(passkey))
+}
+
+/// `404 Not Found` once the passkey is past its restore window
+async fn restore_passkey(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<i32>,
+) -> Result<Json<PasskeySummary>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let passkey = node
+        .restore_passkey_as(actor, id)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(passkey))
+}
+
+/// Recorded changes to a user (by ID) or space (by key), newest first
+async fn get_entity_history(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path((entity, id)): Path<(String, String)>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let changes = node
+        .entity_history(actor, &entity, &id)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(json!({ "changes": changes })))
+}
+
+async fn get_auth_session(signed_in: SignedIn) -> Json<UserSession> {
+    Json(signed_in.session)
+}
+
+async fn logout(
+    State(app_state): State<AppState>,
+    signed_in: SignedIn,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.close_user_session(&signed_in.token)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(json!({"status": "success"})))
+}
+
+/// The signed-in user's profile
+async fn get_profile(
+    State(app_state): State<AppState>,
+    signed_in: SignedIn,
+) -> Result<Json<UserProfile>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.user_profile(signed_in.session.user_id)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+/// Refused with `409 Conflict` for a stale `version` or a taken username
+async fn update_profile(
+    State(app_state): State<AppState>,
+    signed_in: SignedIn,
+    Json(payload): Json<ProfileUpdate>,
+) -> Result<Json<UserProfile>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.update_user_profile(signed_in.session.user_id, payload)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+/// Schedule the signed-in user's account for erasure after the deletion
+/// grace, pointing them at their export meanwhile
+async fn delete_account(
+    State(app_state): State<AppState>,
+    signed_in: SignedIn,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let deletion = node
+        .delete_account(signed_in.session.user_id)
+        .await
+        .map_err(error_response)?;
+
+    let mut body = json!(deletion);
+    if let Deletion::Scheduled(_) = deletion {
+        body["export"] = json!("/api/v1/users/me/export");
+    }
+    Ok(Json(body))
+}
+
+async fn export_account(
+    State(app_state): State<AppState>,
+    signed_in: SignedIn,
+) -> Result<Json<AccountExport>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.export_account(signed_in.session.user_id)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+async fn cancel_account_deletion(
+    State(app_state): State<AppState>,
+    signed_in: SignedIn,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let cancelled = node
+        .cancel_account_deletion(signed_in.session.user_id)
+        .await
+        .map_err(error_response)?;
+    if !cancelled {
+        return Err(error_response(AppError::NotFound(
+            "No account deletion pending".to_string(),
+        )));
+    }
+    Ok(Json(json!({"status": "success"})))
 }
 
+/// Needs a session in every mode: a space exposes a directory of the host
 async fn create_space(
     State(app_state): State<AppState>,
+    signed_in: SignedIn,
     Json(payload): Json<Value>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
     let node = app_state.node.read().await;
+    let actor = signed_in.actor(&node);
     let dir = payload["dir"].as_str().unwrap_or("/tmp/space");
+    let encryption = space::Encryption::from_flags(
+        payload["encrypted"].as_bool().unwrap_or(false),
+        payload["client_keys"].as_bool().unwrap_or(false),
+    )
+    .map_err(error_response)?;
 
-    match node.create_space(dir).await {
+    match node.create_space_with(actor, dir, encryption).await {
         Ok(_) => Ok(Json(json!({"status": "success"}))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err(error_response(e)),
     }
 }
 
-async fn health_check() -> Json<Value> {
-    Json(json!({"status": "healthy", "timestamp": chrono::Utc::now()}))
+/// Read-only listing. In single-user mode it is open to a session or a PIN
+/// unlock grant (`Authorization: Bearer`); in multi-user mode it lists the
+/// user's spaces.
+async fn list_spaces(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    headers: HeaderMap,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+
+    if actor == Actor::Node {
+        let token = bearer_token(&headers)
+            .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing unlock token".to_string()))?;
+        let signed_in = node
+            .session_actor(token)
+            .await
+            .map_err(error_response)?
+            .is_some();
+        let unlocked = signed_in
+            || node
+                .local_pin()
+                .grant(token)
+                .await
+                .map_err(error_response)?
+                .is_some_and(|grant| grant.scope == PinScope::ReadOnly);
+        if !unlocked {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                "Invalid or expired unlock token".to_string(),
+            ));
+        }
+    }
+
+    let spaces = node.list_spaces_as(actor).await.map_err(error_response)?;
+    Ok(Json(json!({
+        "spaces": spaces
+            .iter()
+            .map(|s| {
+                json!({
+                    "key": s.key,
+                    "location": s.location,
+                    "time_created": s.time_created,
+                    "version": s.version,
+                    "client_keys": s.client_keys,
+                })
+            })
+            .collect::<Vec<_>>()
+    })))
+}
+
+async fn list_kv(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let kv = node.kv_for(actor).map_err(error_response)?;
+    Ok(Json(json!({
+        "keys": kv.keys().map_err(error_response)?,
+        "usageBytes": kv.usage().map_err(error_response)?,
+        "quotaBytes": kv.quota_bytes(),
+    })))
+}
+
+/// Payload schemas of the events the node emits, for clients of the
+/// WebSocket, webhooks and plugins
+async fn event_schema() -> Json<Value> {
+    Json(json!({
+        "compatibility": schema::COMPATIBILITY,
+        "versionField": schema::VERSION_FIELD,
+        "idField": schema::ID_FIELD,
+        "events": schema::SCHEMAS,
+    }))
+}
+
+async fn get_settings(
+    State(app_state): State<AppState>,
+    Path(namespace): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let settings = node.settings.get_json(&namespace).map_err(error_response)?;
+    Ok(Json(settings))
+}
+
+/// Replace a namespace's settings; left-out fields take their defaults
+async fn put_settings(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(namespace): Path<String>,
+    Json(payload): Json<Value>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "change settings")?;
+
+    let node = app_state.node.read().await;
+    let settings = node
+        .settings
+        .put_json(&namespace, payload)
+        .map_err(error_response)?;
+    info!("Updated {} settings", namespace);
+    Ok(Json(settings))
+}
+
+async fn get_kv(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<KvKey>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let kv = node.kv_for(actor).map_err(error_response)?;
+    let bytes = kv
+        .get(&key)
+        .map_err(error_response)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("KV key not found: {}", key)))?;
+    let value: Value = serde_json::from_slice(&bytes).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Stored value is not JSON: {}", e),
+        )
+    })?;
+    Ok(Json(json!({"key": key, "value": value})))
+}
+
+/// Store any JSON value under `key`
+async fn put_kv(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<KvKey>,
+    Json(value): Json<Value>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.kv_for(actor)
+        .and_then(|kv| kv.put(&key, value.to_string().as_bytes()))
+        .map_err(error_response)?;
+    Ok(Json(json!({"status": "success"})))
+}
+
+async fn delete_kv(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<KvKey>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.kv_for(actor)
+        .and_then(|kv| kv.remove(&key))
+        .map_err(error_response)?;
+    Ok(Json(json!({"status": "success"})))
+}
+
+#[derive(Debug, Deserialize)]
+struct SetPinRequest {
+    pin: String,
+    current_pin: Option<String>,
+}
+
+async fn set_pin(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(request): Json<SetPinRequest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "use the local PIN")?;
+    let node = app_state.node.read().await;
+
+    node.local_pin()
+        .set(&request.pin, request.current_pin.as_deref())
+        .await
+        .map_err(error_response)?;
+
+    Ok(Json(json!({"status": "success"})))
+}
+
+#[derive(Debug, Deserialize)]
+struct ClearPinRequest {
+    current_pin: String,
+}
+
+async fn clear_pin(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(request): Json<ClearPinRequest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "use the local PIN")?;
+    let node = app_state.node.read().await;
+
+    node.local_pin()
+        .clear(&request.current_pin)
+        .await
+        .map_err(error_response)?;
+
+    Ok(Json(json!({"status": "success"})))
+}
+
+#[derive(Debug, Deserialize)]
+struct UnlockRequest {
+    pin: String,
+}
+
+async fn unlock_with_pin(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(request): Json<UnlockRequest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "use the local PIN")?;
+    let node = app_state.node.read().await;
+
+    match node
+        .local_pin()
+        .unlock(&request.pin)
+        .await
+        .map_err(error_response)?
+    {
+        PinUnlock::Unlocked { token, grant } => Ok(Json(json!({
+            "token": token,
+            "scope": grant.scope,
+            "expires_at": grant.expires_at,
+        }))),
+        PinUnlock::Rejected { remaining_attempts } => Err((
+            StatusCode::UNAUTHORIZED,
+            format!("Incorrect PIN, {} attempts left", remaining_attempts),
+        )),
+        PinUnlock::LockedOut { until } => Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("PIN is locked until {}", until),
+        )),
+    }
+}
+
+/// Stream a file into a space. Bodies past the spill threshold go to disk
+/// rather than memory; past the upload limit they're refused with 413. In an
+/// encrypted space the file is encrypted on its way into place. A space with
+/// client-held keys takes only blobs.
+async fn upload_file(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath((key, path)): ValidPath<(SpaceKey, FilePath)>,
+    body: Body,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let size = store_file(&app_state, actor, &key, &path, body, |space| {
+        match space.client_keys {
+            true => space::check_blob_name(&path),
+            false => Ok(()),
+        }
+    })
+    .await?;
+
+    info!("{} uploaded {} bytes to {}/{}", actor, size, key, path);
+    Ok(Json(json!({"path": path, "size": size})))
+}
+
+/// The encrypted index of a space with client-held keys, as its clients last
+/// stored it
+async fn get_space_index(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let space = node
+        .find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+    keeps_index(&space).map_err(error_response)?;
+    let index = node
+        .read_space_file(actor, &key, space::INDEX_FILE)
+        .await
+        .map_err(error_response)?;
+
+    let etag = caching::etag(&index);
+    if caching::not_modified(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::ETAG, etag),
+        ],
+        index,
+    )
+        .into_response())
+}
+
+/// Replace the encrypted index of a space with client-held keys. The node
+/// can't read it, so the last write wins.
+async fn put_space_index(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    body: Body,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let size = store_file(
+        &app_state,
+        actor,
+        &key,
+        space::INDEX_FILE,
+        body,
+        keeps_index,
+    )
+    .await?;
+
+    info!("{} stored the {} byte index of space {}", actor, size, key);
+    Ok(Json(json!({"size": size})))
+}
+
+fn keeps_index(space: &entity::space::Model) -> Result<(), AppError> {
+    match space.client_keys {
+        true => Ok(()),
+        false => Err(AppError::Validation(format!(
+            "Only spaces with client-held keys keep an index, and {} isn't one",
+            space.key
+        ))),
+    }
+}
+
+/// Spool `body` and move it into place as the file at `path` of a space
+/// `actor` may write, once `check` accepts the space. Returns its size.
+async fn store_file(
+    app_state: &AppState,
+    actor: Actor,
+    key: &str,
+    path: &str,
+    body: Body,
+    check: impl FnOnce(&entity::space::Model) -> Result<(), AppError>,
+) -> Result<u64, (StatusCode, String)> {
+    let (space, cipher, scratch) = {
+        let node = app_state.node.read().await;
+        let space = node
+            .find_space_as(actor, key)
+            .await
+            .map_err(error_response)?;
+        check(&space).map_err(error_response)?;
+        let cipher = node.space_cipher(&space).map_err(error_response)?;
+        (space, cipher, node.scratch_dir())
+    };
+    let dest =
+        space::resolve_file(std::path::Path::new(&space.location), path).map_err(error_response)?;
+
+    let spooled = body::spool(body, &app_state.limits, &scratch)
+        .await
+        .map_err(error_response)?;
+    // Only now does the file enter the space
+    let _write = app_state.node.read().await.space_writes.begin(key);
+    let size = {
+        let (path, dest) = (path.to_string(), dest.clone());
+        let root = std::path::PathBuf::from(&space.location);
+        tokio::task::spawn_blocking(move || {
+            let size = spooled.size()?;
+            std::fs::create_dir_all(dest.parent().unwrap_or(&root))?;
+            match cipher {
+                Some(cipher) => cipher.encrypt_to(&path, spooled.into_reader()?, &dest)?,
+                None => spooled.persist(&dest)?,
+            }
+            Ok::<_, AppError>(size)
+        })
+        .await
+        .map_err(|e| error_response(AppError::IO(std::io::Error::other(e))))?
+        .map_err(error_response)?
+    };
+    app_state
+        .node
+        .read()
+        .await
+        .record_space_file(key, path, &dest)
+        .await
+        .map_err(error_response)?;
+    Ok(size)
+}
+
+/// A file in a space, decrypted if the space is encrypted
+async fn download_file(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath((key, path)): ValidPath<(SpaceKey, FilePath)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let contents = node
+        .read_space_file(actor, &key, &path)
+        .await
+        .map_err(error_response)?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        contents,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestQuery {
+    path: Option<String>,
+}
+
+/// A folder of a space (`path`, the whole space by default) as a tar
+/// archive, streamed as it is built
+async fn download_archive(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    Query(query): Query<ManifestQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let path = query.path.unwrap_or_default();
+    let archive = {
+        let node = app_state.node.read().await;
+        node.space_archive(actor, &key, &path)
+            .await
+            .map_err(error_response)?
+    };
+
+    let name = path
+        .trim_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&key)
+        .replace('"', "_");
+    info!(
+        "{} downloading {} files of {}/{} as an archive",
+        actor,
+        archive.file_count(),
+        key,
+        path
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.tar\"", name),
+            ),
+            (
+                header::HeaderName::from_static(archive::SIZE_ESTIMATE_HEADER),
+                archive.estimated_size().to_string(),
+            ),
+        ],
+        Body::from_stream(archive.stream()),
+    ))
+}
+
+async fn get_space_manifest(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    Query(query): Query<ManifestQuery>,
+) -> Result<Json<SignedManifest>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let path = query.path.unwrap_or_default();
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    node.space_manifest(&key, &path)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to build manifest for space {}: {}", key, e);
+            error_response(e)
+        })
+}
+
+async fn verify_space_manifest(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    Json(manifest): Json<SignedManifest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    let verification = node
+        .verify_space_manifest(&key, manifest)
+        .await
+        .map_err(error_response)?;
+
+    Ok(Json(json!({
+        "valid": verification.is_valid(),
+        "signature_valid": verification.signature_valid,
+        "missing": verification.missing,
+        "modified": verification.modified,
+        "unexpected": verification.unexpected,
+    })))
+}
+
+async fn get_space_manifest_summary(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    Query(query): Query<ManifestQuery>,
+) -> Result<Json<ManifestSummary>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let path = query.path.unwrap_or_default();
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    node.space_manifest_summary(&key, &path)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to summarise space {}: {}", key, e);
+            error_response(e)
+        })
+}
+
+async fn compare_space_manifest(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    Json(summary): Json<ManifestSummary>,
+) -> Result<Json<SummaryComparison>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    node.compare_space_summary(&key, &summary)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+#[derive(Debug, Deserialize)]
+struct MerkleQuery {
+    prefix: Option<String>,
+}
+
+/// A node of the space's Merkle tree; the root without `prefix`
+async fn get_space_merkle(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+    Query(query): Query<MerkleQuery>,
+) -> Result<Json<MerkleNode>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    node.space_merkle_node(&key, query.prefix.as_deref().unwrap_or_default())
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+/// Rebuild the tree, e.g. after files changed outside the node
+async fn rebuild_space_merkle(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    let root = node
+        .rebuild_space_merkle(&key)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(json!({"root": root})))
+}
+
+async fn list_space_anchors(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+) -> Result<Json<Vec<SpaceAnchor>>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    node.space_anchors(&key)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+/// Anchor the space's current state now, outside any schedule
+async fn anchor_space(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    ValidPath(key): ValidPath<SpaceKey>,
+) -> Result<Json<SpaceAnchor>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.find_space_as(actor, &key)
+        .await
+        .map_err(error_response)?;
+
+    node.anchor_space(&key)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+/// Map an AppError to the HTTP status it should be reported with
+pub(crate) fn error_response(e: AppError) -> (StatusCode, String) {
+    let status = match e {
+        AppError::NotFound(_) => StatusCode::NOT_FOUND,
+        AppError::Auth(_) => StatusCode::UNAUTHORIZED,
+        AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+        AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+        AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        AppError::Validation(_) => StatusCode::BAD_REQUEST,
+        AppError::Conflict(_) | AppError::Replayed(_) => StatusCode::CONFLICT,
+        AppError::Maintenance(_) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string())
+}
+
+async fn get_update_status(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "see update status")?;
+
+    let node = app_state.node.read().await;
+
+    match node.update_status().map_err(error_response)? {
+        Some(status) => Ok(Json(json!(status))),
+        None => Ok(Json(json!({
+            "currentVersion": updater::CURRENT_VERSION,
+            "state": "unchecked",
+        }))),
+    }
+}
+
+async fn list_lockouts(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage lockouts")?;
+
+    let node = app_state.node.read().await;
+    let records = node.auth_guard().records().await.map_err(error_response)?;
+
+    Ok(Json(json!({
+        "lockouts": records
+            .into_iter()
+            .map(|(key, record)| {
+                let mut entry = json!(record);
+                entry["key"] = json!(key.to_string());
+                entry
+            })
+            .collect::<Vec<_>>()
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+struct LockoutQuery {
+    key: String,
+}
+
+async fn clear_lockout(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Query(query): Query<LockoutQuery>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage lockouts")?;
+
+    let node = app_state.node.read().await;
+    let key = query.key.parse().map_err(error_response)?;
+    node.auth_guard()
+        .clear(&key)
+        .await
+        .map_err(error_response)?;
+
+    info!("Cleared authentication lockout for {}", key);
+    Ok(Json(json!({"status": "success"})))
+}
+
+async fn list_tasks(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage scheduled tasks")?;
+    let node = app_state.node.read().await;
+    let tasks = node.scheduler().list().map_err(error_response)?;
+    Ok(Json(json!({"tasks": tasks})))
+}
+
+async fn create_task(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(payload): Json<NewTask>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage scheduled tasks")?;
+    let node = app_state.node.read().await;
+    let task = node
+        .scheduler()
+        .create(payload)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(json!({"status": "success", "task": task})))
+}
+
+async fn get_task(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage scheduled tasks")?;
+    let node = app_state.node.read().await;
+    let task = node.scheduler().get(&id).map_err(error_response)?;
+    Ok(Json(json!({"task": task})))
+}
+
+async fn remove_task(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage scheduled tasks")?;
+    let node = app_state.node.read().await;
+    node.scheduler().remove(&id).map_err(error_response)?;
+    Ok(Json(json!({"status": "success"})))
+}
+
+async fn run_task(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage scheduled tasks")?;
+    let node = app_state.node.read().await;
+    let run = node
+        .scheduler()
+        .run_now(&id)
+        .await
+        .map_err(error_response)?;
+    Ok(Json(json!({"run": run})))
+}
+
+async fn get_task_runs(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage scheduled tasks")?;
+    let node = app_state.node.read().await;
+    let runs = node.scheduler().history(&id).map_err(error_response)?;
+    Ok(Json(json!({"runs": runs})))
+}
+
+async fn list_webhooks(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage webhooks")?;
+    let node = app_state.node.read().await;
+    let webhooks = node.webhooks.list().map_err(error_response)?;
+    Ok(Json(json!({"webhooks": webhooks})))
+}
+
+async fn register_webhook(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(payload): Json<NewWebhook>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage webhooks")?;
+    let node = app_state.node.read().await;
+    let webhook = node.webhooks.register(payload).map_err(error_response)?;
+    Ok(Json(json!({"status": "success", "webhook": webhook})))
+}
+
+async fn remove_webhook(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "manage webhooks")?;
+    let node = app_state.node.read().await;
+    node.webhooks.remove(&id).map_err(error_response)?;
+    Ok(Json(json!({"status": "success"})))
+}
+
+async fn get_node_info(
+    State(app_state): State<AppState>,
+    origin: Option<Extension<RequestOrigin>>,
+) -> Json<NodeInfo> {
+    let node = app_state.node.read().await;
+    Json(
+        NodeInfo::new(
+            &node,
+            app_state.server.as_ref(),
+            app_state.plugins.plugins(),
+        )
+        .behind_proxy(
+            &app_state.proxy.base_path,
+            origin
+                .as_ref()
+                .map(|Extension(RequestOrigin(o))| o.as_str()),
+        ),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct AttestRequest {
+    challenge: String,
+    audience: Option<String>,
+}
+
+async fn attest_node(
+    State(app_state): State<AppState>,
+    Json(payload): Json<AttestRequest>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    let attestation = node
+        .attest(&payload.challenge, payload.audience.as_deref())
+        .map_err(error_response)?;
+    Ok(Json(json!(attestation)))
+}
+
+/// A message from another node over HTTP, handled as if it came over the
+/// P2P transport; answered with the reply, if there is one
+async fn receive_peer_message(
+    State(app_state): State<AppState>,
+    SignedPeer { peer, body }: SignedPeer,
+) -> Result<Response, (StatusCode, String)> {
+    let message: Message = serde_json::from_slice(&body)
+        .map_err(|e| error_response(AppError::Validation(format!("Invalid message: {}", e))))?;
+    let node = app_state.node.read().await;
+    let reply = PublishMessages::new(node.clone())
+        .handle(&peer, message)
+        .await
+        .map_err(error_response)?;
+    Ok(match reply {
+        Some(reply) => Json(reply).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueCredentialRequest {
+    subject: Did,
+    #[serde(default)]
+    claims: serde_json::Map<String, Value>,
+    /// Types besides `VerifiableCredential`
+    #[serde(default, rename = "type")]
+    types: Vec<String>,
+    valid_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Credentials speak for the node, so only the node may have them issued
+async fn issue_credential(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(payload): Json<IssueCredentialRequest>,
+) -> Result<Json<IssuedCredential>, (StatusCode, String)> {
+    node_only(actor, "issue credentials")?;
+
+    let node = app_state.node.read().await;
+    node.issue_credential_with(CredentialRequest {
+        subject: payload.subject.into_inner(),
+        claims: payload.claims,
+        types: payload.types,
+        valid_until: payload.valid_until,
+    })
+    .map(Json)
+    .map_err(error_response)
+}
+
+/// Usage of every key, or in multi-user mode of the caller's own
+async fn list_key_usage(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Vec<KeyStats>>, (StatusCode, String)> {
+    let user_id = match actor {
+        Actor::Node => None,
+        Actor::User(id) => Some(id),
+    };
+    let node = app_state.node.read().await;
+    node.key_usage()
+        .stats(user_id)
+        .map(Json)
+        .map_err(error_response)
+}
+
+async fn get_key_usage(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Path((kind, id)): Path<(String, String)>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let kind: KeyKind = kind.parse().map_err(error_response)?;
+    let node = app_state.node.read().await;
+    let usage = node.key_usage();
+    let stats = usage
+        .key_stats(kind, &id)
+        .map_err(error_response)?
+        .filter(|stats| match actor {
+            Actor::Node => true,
+            Actor::User(user_id) => stats.user_id == Some(user_id),
+        })
+        .ok_or_else(|| {
+            error_response(AppError::NotFound(format!("No usage of {} {}", kind, id)))
+        })?;
+    let history = usage.history(kind, &id).map_err(error_response)?;
+    Ok(Json(json!({"stats": stats, "history": history})))
+}
+
+/// Rotate the node identity key, answering with the signed hand-over
+async fn rotate_identity(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<SignedRotation>, (StatusCode, String)> {
+    node_only(actor, "rotate its identity key")?;
+
+    let mut node = app_state.node.write().await;
+    let rotation = node.rotate_identity_key().map_err(error_response)?;
+    Ok(Json(rotation))
+}
+
+#[derive(Debug, Deserialize)]
+struct TimestampRequest {
+    /// Hex SHA-256 of the data to timestamp
+    digest: String,
+}
+
+/// Have the node's timestamp authority countersign a digest
+async fn create_timestamp(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(payload): Json<TimestampRequest>,
+) -> Result<Json<TimestampProof>, (StatusCode, String)> {
+    node_only(actor, "request timestamps")?;
+
+    let node = app_state.node.read().await;
+    if node.timestamps.is_none() {
+        return Err((
+            StatusCode::NOT_IMPLEMENTED,
+            "No timestamp authority is configured".to_string(),
+        ));
+    }
+    let digest = timestamp::parse_digest(&payload.digest).map_err(error_response)?;
+    node.timestamp(&digest)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+async fn get_timestamp(
+    State(app_state): State<AppState>,
+    Path(digest): Path<String>,
+) -> Result<Json<TimestampProof>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    node.timestamp_proof(&digest)
+        .map(Json)
+        .map_err(error_response)
+}
+
+/// Check any timestamp proof, including ones this node didn't request, as
+/// long as a certificate it trusts signed it
+async fn verify_timestamp(
+    State(app_state): State<AppState>,
+    Json(proof): Json<TimestampProof>,
+) -> Result<Json<TimestampVerification>, (StatusCode, String)> {
+    let node = app_state.node.read().await;
+    if node.trusted_timestamps.is_empty() {
+        return Err((
+            StatusCode::NOT_IMPLEMENTED,
+            "No timestamp authority is trusted".to_string(),
+        ));
+    }
+    timestamp::verify_proof(&proof, &node.trusted_timestamps)
+        .map(Json)
+        .map_err(error_response)
+}
+
+async fn health_check() -> Json<Value> {
+    Json(json!({"status": "healthy", "timestamp": chrono::Utc::now()}))
+}
+
+/// Answers while the process does; restart it when this stops
+async fn health_live() -> Json<Value> {
+    Json(json!({"status": "alive", "timestamp": chrono::Utc::now()}))
+}
+
+/// 503 while any component is down or the node is shutting down, so
+/// traffic goes elsewhere
+async fn health_ready(State(app_state): State<AppState>) -> (StatusCode, Json<Value>) {
+    // Checks can take a while; don't hold writers up meanwhile
+    let node = app_state.node.read().await.clone();
+    let readiness = health::readiness(&node).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({
+            "status": if readiness.ready { "ready" } else { "unavailable" },
+            "timestamp": chrono::Utc::now(),
+            "shuttingDown": readiness.shutting_down,
+            "components": readiness.components,
+        })),
+    )
+}
+
+async fn get_compression_stats(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "see compression stats")?;
+
+    Ok(Json(json!({
+        "enabled": app_state.compression.enabled,
+        "minSizeBytes": app_state.compression.min_size_bytes,
+        "stats": app_state.compression_stats.snapshot(),
+    })))
+}
+
+/// Resource usage per subsystem, with the soft limits it is over
+async fn get_resource_stats(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "see resource usage")?;
+
+    let node = app_state.node.read().await;
+    let usage = node.resource_usage().await.map_err(error_response)?;
+    Ok(Json(json!({
+        "exceeded": usage.exceeded(&node.resources),
+        "usage": usage,
+    })))
+}
+
+/// Resource usage and storage engine internals for Prometheus to scrape
+async fn get_metrics(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Response, (StatusCode, String)> {
+    node_only(actor, "see metrics")?;
+
+    let node = app_state.node.read().await;
+    let usage = node.resource_usage().await.map_err(error_response)?;
+    let storage = node.storage_stats().await.map_err(error_response)?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        resources::prometheus(&usage, &node.resources) + &storage_stats::prometheus(&storage),
+    )
+        .into_response())
+}
+
+/// Whether telemetry is on, where it goes, and the report it would send now
+async fn get_telemetry(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "see telemetry")?;
+
+    let node = app_state.node.read().await;
+    let telemetry = node.telemetry();
+    let report = telemetry.report().map_err(error_response)?;
+    Ok(Json(json!({
+        "enabled": telemetry.enabled(),
+        "endpoint": telemetry.config().endpoint,
+        "submitIntervalSecs": telemetry.config().submit_interval.as_secs(),
+        "epsilon": telemetry.config().epsilon,
+        "report": report,
+    })))
+}
+
+/// Whether writes are refused, since when and why
+async fn get_maintenance(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "see maintenance status")?;
+
+    let maintenance = app_state.node.read().await.maintenance.clone();
+    Ok(Json(maintenance_status(&maintenance)))
+}
+
+#[derive(Debug, Deserialize)]
+struct MaintenanceToggle {
+    enabled: bool,
+    reason: Option<String>,
+}
+
+/// Turn maintenance on or off; users can't in multi-user mode
+async fn set_maintenance(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+    Json(toggle): Json<MaintenanceToggle>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    node_only(actor, "change maintenance mode")?;
+
+    let maintenance = app_state.node.read().await.maintenance.clone();
+    match toggle.enabled {
+        true => maintenance.enable(
+            toggle
+                .reason
+                .as_deref()
+                .filter(|reason| !reason.trim().is_empty())
+                .unwrap_or("requested over the API"),
+        ),
+        false => {
+            maintenance.disable();
+        }
+    }
+    Ok(Json(maintenance_status(&maintenance)))
+}
+
+fn maintenance_status(maintenance: &Maintenance) -> Value {
+    let window = maintenance.window();
+    json!({
+        "enabled": window.is_some(),
+        "reason": window.as_ref().map(|w| &w.reason),
+        "since": window.as_ref().map(|w| w.since),
+        "retryAfterSecs": maintenance.retry_after().as_secs(),
+    })
+}
+
+/// Shut the node down as SIGTERM would; users can't in multi-user mode
+async fn shutdown_node(
+    State(app_state): State<AppState>,
+    Acting(actor): Acting,
+) -> Result<(StatusCode, Json<Value>), (StatusCode, String)> {
+    node_only(actor, "shut the node down")?;
+
+    app_state.shutdown.trigger("requested over the API");
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({"status": "shutting down"})),
+    ))
+}
+
+/// Status for a failed DID resolution
+fn resolution_failure(e: ResolutionError) -> (StatusCode, String) {
+    let status = match e {
+        ResolutionError::InvalidDid(_) => StatusCode::BAD_REQUEST,
+        ResolutionError::NotFound | ResolutionError::Deactivated => StatusCode::NOT_FOUND,
+        ResolutionError::MethodNotSupported(_) => StatusCode::NOT_IMPLEMENTED,
+        ResolutionError::RepresentationNotSupported(_) => StatusCode::NOT_ACCEPTABLE,
+        ResolutionError::NetworkError(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string())
+}
+
+/// Resolve `did` and serve its document with caching headers
+async fn did_document_response(
+    resolver: &DidResolver,
+    did: &str,
+    headers: &HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let result = resolver
+        .resolve_did(did, &ResolutionOptions::new())
+        .await
+        .map_err(resolution_failure)?;
+    let document = result
+        .did_document
+        .ok_or_else(|| resolution_failure(ResolutionError::NotFound))?;
+
+    caching::cached_json(
+        headers,
+        &document,
+        "application/did+json",
+        result.did_resolution_metadata.cache_ttl,
+    )
+    .map_err(error_response)
+}
+
+async fn get_did_document(
+    State(app_state): State<AppState>,
+    ValidPath(did): ValidPath<Did>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let resolver = app_state.node.read().await.did_resolver.clone();
+    did_document_response(&resolver, &did, &headers).await
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolveQuery {
+    accept: Option<String>,
+    no_cache: Option<bool>,
+    timeout_ms: Option<u64>,
+}
+
+/// Options for resolving with `accept`, `no_cache` and `timeout_ms`
+fn resolution_options(
+    accept: Option<&str>,
+    no_cache: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<ResolutionOptions, (StatusCode, String)> {
+    let mut options = ResolutionOptions::new();
+    if let Some(accept) = accept {
+        options = options
+            .with_accept(accept)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    options.no_cache = no_cache;
+    options.timeout_ms = timeout_ms;
+    Ok(options)
+}
+
+/// Full W3C resolution result for `did`, metadata included
+async fn resolve_did(
+    State(app_state): State<AppState>,
+    ValidPath(did): ValidPath<Did>,
+    Query(query): Query<ResolveQuery>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let options = resolution_options(query.accept.as_deref(), query.no_cache, query.timeout_ms)?;
+    let resolver = app_state.node.read().await.did_resolver.clone();
+    let result = resolver
+        .resolve_did(&did, &options)
+        .await
+        .map_err(resolution_failure)?;
+    Ok(Json(json!(result)))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResolveBatch {
+    dids: Vec<String>,
+    accept: Option<String>,
+    no_cache: Option<bool>,
+    timeout_ms: Option<u64>,
+}
+
+/// Resolve several DIDs, streaming one JSON line per DID as soon as it is
+/// resolved: `{"index", "did", "result"}`, or `{"index", "did", "error"}`
+/// with the status a single resolution would have answered
+async fn resolve_did_batch(
+    State(app_state): State<AppState>,
+    Json(batch): Json<ResolveBatch>,
+) -> Result<Response, (StatusCode, String)> {
+    let (resolver, limits) = {
+        let node = app_state.node.read().await;
+        (node.did_resolver.clone(), node.did_batch)
+    };
+    if batch.dids.is_empty() {
+        return Err(error_response(AppError::Validation(
+            "No DIDs to resolve".to_string(),
+        )));
+    }
+    if batch.dids.len() > limits.max_dids {
+        return Err(error_response(AppError::PayloadTooLarge(format!(
+            "At most {} DIDs per batch, got {}",
+            limits.max_dids,
+            batch.dids.len()
+        ))));
+    }
+    for did in &batch.dids {
+        validation::validate_did(did).map_err(error_response)?;
+    }
+    let options = resolution_options(batch.accept.as_deref(), batch.no_cache, batch.timeout_ms)?;
+
+    let lines = resolver
+        .resolve_stream(batch.dids, options)
+        .map(|resolved| {
+            let line = match resolved.result {
+                Ok(result) => json!({
+                    "index": resolved.index,
+                    "did": resolved.did,
+                    "result": result,
+                }),
+                Err(e) => {
+                    let (status, message) = resolution_failure(e);
+                    json!({
+                        "index": resolved.index,
+                        "did": resolved.did,
+                        "error": {"status": status.as_u16(), "message": message},
+                    })
+                }
+            };
+            let mut bytes = line.to_string().into_bytes();
+            bytes.push(b'\n');
+            Ok::<_, Infallible>(Bytes::from(bytes))
+        });
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// The node's own DID document, published for peers
+async fn get_node_did_document(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let (resolver, did) = {
+        let node = app_state.node.read().await;
+        (node.did_resolver.clone(), node.node_data.id.clone())
+    };
+    did_document_response(&resolver, &did, &headers).await
 }
@@ -0,0 +1,47 @@
+//! Counting REST usage for telemetry, when it is on.
+//!
+//! Runs behind routing, so requests are counted by the route they matched
+//! (`GET /api/v1/spaces/{key}`), never by the path as requested. Error
+//! responses are counted by their code, the one [`i18n`] answers with.
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use log::warn;
+
+use crate::api::servers::app_state::AppState;
+use crate::api::servers::i18n::{self, MessageCode};
+use crate::modules::telemetry::Counter;
+
+/// Middleware counting features used and errors answered
+pub async fn count(State(app_state): State<AppState>, request: Request, next: Next) -> Response {
+    let feature = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|route| format!("{} {}", request.method(), route.as_str()));
+    let response = next.run(request).await;
+
+    let telemetry = app_state.node.read().await.telemetry();
+    if !telemetry.enabled() {
+        return response;
+    }
+    let status = response.status();
+    let error = (status.is_client_error() || status.is_server_error()).then(|| {
+        response
+            .extensions()
+            .get::<MessageCode>()
+            .map_or_else(|| i18n::code_for(status), |MessageCode(code)| code)
+    });
+    let counted = feature
+        .map(|feature| telemetry.count(Counter::Feature, &feature))
+        .into_iter()
+        .chain(error.map(|code| telemetry.count(Counter::Error, code)));
+    for result in counted {
+        if let Err(e) = result {
+            warn!("Telemetry count failed: {}", e);
+        }
+    }
+    response
+}
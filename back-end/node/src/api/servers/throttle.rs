@@ -0,0 +1,111 @@
+//! Handler timeouts and concurrency limits.
+//!
+//! Routes come in two classes. Expensive ones scan whole spaces, stream
+//! exports and files, run scheduled tasks on demand, or wait on other
+//! servers (DID resolution, timestamp authorities); everything else, WebAuthn ceremonies included, is cheap.
+//! Each class has its own pool of slots and its own deadline, so a pile of
+//! slow resolutions or large exports queues behind itself and never takes
+//! the slots sign-ins need.
+//!
+//! A request finding no free slot is turned away at once with `503` and
+//! `Retry-After` rather than queued; a handler still running at the deadline
+//! is dropped and the request gets `504`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Semaphore;
+
+use crate::bootstrap::config::RequestLimitsConfig;
+
+/// Expensive routes. Each entry covers itself and everything below it; `*`
+/// stands for one path segment.
+pub const EXPENSIVE: &[&str] = &[
+    "/api/v1/users/me/export",
+    "/api/v1/spaces/*/files",
+    "/api/v1/spaces/*/manifest",
+    "/api/v1/spaces/*/merkle",
+    "/api/v1/spaces/*/anchors",
+    "/api/v1/dids",
+    "/api/v1/timestamps",
+    "/api/v1/tasks/*/run",
+    "/api/v1/admin/stats",
+    "/api/v1/admin/metrics",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteClass {
+    Cheap,
+    Expensive,
+}
+
+impl RouteClass {
+    pub fn of(path: &str) -> Self {
+        match EXPENSIVE.iter().any(|pattern| covers(pattern, path)) {
+            true => RouteClass::Expensive,
+            false => RouteClass::Cheap,
+        }
+    }
+}
+
+/// Whether `pattern` names `path` or one of its ancestors
+pub(crate) fn covers(pattern: &str, path: &str) -> bool {
+    let mut segments = path.split('/');
+    pattern.split('/').all(|expected| match segments.next() {
+        Some(segment) => (expected == "*" && !segment.is_empty()) || expected == segment,
+        None => false,
+    })
+}
+
+/// Slots for each class of route, shared by every request to one server
+#[derive(Clone)]
+pub struct RequestLimits {
+    config: RequestLimitsConfig,
+    cheap: Arc<Semaphore>,
+    expensive: Arc<Semaphore>,
+}
+
+impl RequestLimits {
+    pub fn new(config: &RequestLimitsConfig) -> Self {
+        Self {
+            config: *config,
+            cheap: Arc::new(Semaphore::new(config.max_concurrent)),
+            expensive: Arc::new(Semaphore::new(config.max_concurrent_expensive)),
+        }
+    }
+
+    fn pool(&self, class: RouteClass) -> (&Arc<Semaphore>, Duration) {
+        match class {
+            RouteClass::Cheap => (&self.cheap, self.config.timeout),
+            RouteClass::Expensive => (&self.expensive, self.config.expensive_timeout),
+        }
+    }
+}
+
+/// Middleware holding each request to its class's slots and deadline
+pub async fn limit(State(limits): State<RequestLimits>, request: Request, next: Next) -> Response {
+    let (pool, timeout) = limits.pool(RouteClass::of(request.uri().path()));
+    let Ok(_permit) = pool.clone().try_acquire_owned() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "1")],
+            "Too many requests in progress; try again shortly",
+        )
+            .into_response();
+    };
+
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            format!("Request took longer than {}s", timeout.as_secs()),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,212 @@
+//! HTTPS for the REST and WebSocket servers, and the plain HTTP listener
+//! answering ACME HTTP-01 challenges.
+//!
+//! Certificates come from ACME, from the operator (`FLOW_TLS_CERT` and
+//! `FLOW_TLS_KEY`), or, with `FLOW_TLS_SELF_SIGNED`, from the node itself:
+//! one made on first start and kept in the config directory, for the names
+//! the node is reached by.
+//!
+//! Handshakes run in their own tasks so a slow or stalled client can't hold
+//! up the connections queued behind it.
+
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use errors::AppError;
+use log::{debug, info};
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+use rustls::ServerConfig;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+
+use crate::bootstrap::config::{ServerConfig as ServerSettings, TlsConfig};
+use crate::modules::acme::store;
+use crate::modules::acme::{CertStore, Http01Responses};
+
+/// Directory in the config dir the self-signed certificate is kept in
+pub const SELF_SIGNED_DIR: &str = "tls";
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Completed handshakes waiting to be served
+const ACCEPT_QUEUE: usize = 64;
+
+/// Server config for the certificate `config` names, or the self-signed one
+/// under `config_dir`, made for `server`'s names if there is none yet
+pub fn server_config(
+    config: &TlsConfig,
+    config_dir: &std::path::Path,
+    server: &ServerSettings,
+) -> Result<Arc<ServerConfig>, AppError> {
+    let (cert_pem, key_pem) = match (&config.cert_path, &config.key_path) {
+        (Some(cert), Some(key)) => {
+            info!("Serving HTTPS with the certificate in {}", cert.display());
+            (fs::read_to_string(cert)?, fs::read_to_string(key)?)
+        }
+        _ => {
+            let dir = config_dir.join(SELF_SIGNED_DIR);
+            info!(
+                "Serving HTTPS with the self-signed certificate in {}",
+                dir.display()
+            );
+            self_signed(&dir, &host_names(server))?
+        }
+    };
+    let certificates = Arc::new(CertStore::default());
+    certificates.install(&cert_pem, &key_pem)?;
+    certificates.server_config()
+}
+
+/// The certificate and key saved in `dir`, made for `names` and saved first
+/// if there are none
+pub fn self_signed(dir: &std::path::Path, names: &[String]) -> Result<(String, String), AppError> {
+    if let Some(saved) = store::load(dir)? {
+        return Ok(saved);
+    }
+    let failed =
+        |e: rcgen::Error| AppError::Crypto(format!("Cannot create self-signed certificate: {}", e));
+    let key_pair = KeyPair::generate().map_err(failed)?;
+    let mut params = CertificateParams::new(names.to_vec()).map_err(failed)?;
+    params.distinguished_name = DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "Flow node (self-signed)");
+    let cert = params.self_signed(&key_pair).map_err(failed)?;
+
+    let (cert_pem, key_pem) = (cert.pem(), key_pair.serialize_pem());
+    store::save(dir, &cert_pem, &key_pem)?;
+    info!("Created a self-signed certificate for {}", names.join(", "));
+    Ok((cert_pem, key_pem))
+}
+
+/// Names a node is reached by: localhost, its host, and the hosts of its
+/// advertised addresses, wildcards left out
+fn host_names(server: &ServerSettings) -> Vec<String> {
+    let advertised =
+        server
+            .advertised_addresses
+            .iter()
+            .map(|address| match address.parse::<SocketAddr>() {
+                Ok(address) => address.ip().to_string(),
+                Err(_) => address
+                    .rsplit_once(':')
+                    .map_or(address.as_str(), |(host, _)| host)
+                    .to_string(),
+            });
+    let mut names = Vec::new();
+    for name in ["localhost", "127.0.0.1", "::1"]
+        .map(str::to_string)
+        .into_iter()
+        .chain([server.host.clone()])
+        .chain(advertised)
+    {
+        let wildcard = name.parse::<IpAddr>().is_ok_and(|ip| ip.is_unspecified());
+        if !name.is_empty() && !wildcard && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Listener yielding connections once their TLS handshake has completed
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    accepted: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: Arc<ServerConfig>) -> std::io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (sender, accepted) = mpsc::channel(ACCEPT_QUEUE);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        debug!("Accept failed: {}", e);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        continue;
+                    }
+                };
+                if sender.is_closed() {
+                    break;
+                }
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            accepted,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.accepted.recv().await {
+            Some(accepted) => accepted,
+            // The accept loop only stops once this listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Router answering HTTP-01 challenges and nothing else
+pub fn challenge_router(responses: Http01Responses) -> Router {
+    Router::new()
+        .route(
+            "/.well-known/acme-challenge/{token}",
+            get(challenge_response),
+        )
+        .with_state(responses)
+}
+
+async fn challenge_response(
+    State(responses): State<Http01Responses>,
+    Path(token): Path<String>,
+) -> Result<String, StatusCode> {
+    responses.get(&token).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Answer HTTP-01 challenges on `listeners` until the task is dropped
+pub async fn serve_challenges(
+    listeners: Vec<TcpListener>,
+    responses: Http01Responses,
+) -> Result<(), AppError> {
+    let app = challenge_router(responses);
+    let servers = listeners.into_iter().map(|listener| {
+        if let Ok(addr) = listener.local_addr() {
+            info!("ACME challenge server up on addr: {}", addr);
+        }
+        axum::serve(listener, app.clone()).into_future()
+    });
+    futures_util::future::try_join_all(servers).await?;
+    Ok(())
+}
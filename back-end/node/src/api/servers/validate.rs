@@ -0,0 +1,36 @@
+//! Path parameters validated before the handler runs.
+//!
+//! [`ValidPath`] is [`Path`] reporting parameters that fail to deserialize,
+//! including identifiers rejected by [`crate::modules::validation`], as
+//! `422 Unprocessable Entity`.
+
+use axum::extract::path::ErrorKind;
+use axum::extract::rejection::PathRejection;
+use axum::extract::{FromRequestParts, Path};
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use serde::de::DeserializeOwned;
+
+pub struct ValidPath<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Path::<T>::from_request_parts(parts, state).await {
+            Ok(Path(value)) => Ok(ValidPath(value)),
+            Err(PathRejection::FailedToDeserializePathParams(e)) => {
+                let message = match e.kind() {
+                    ErrorKind::Message(message) => message.clone(),
+                    _ => e.body_text(),
+                };
+                Err((StatusCode::UNPROCESSABLE_ENTITY, message))
+            }
+            Err(e) => Err((e.status(), e.body_text())),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! WebSocket server. Versioned requests are dispatched by action (see
+//! [`protocol`] for the wire format); a connection that subscribes also gets
+//! node events pushed to it.
+
+pub mod protocol;
+mod router;
+
+use crate::api::servers::cors;
+use crate::api::servers::headers::{self, SecurityHeaders};
+use crate::api::servers::i18n;
+use crate::api::servers::rest::{Client, ClientPlace};
+use crate::{
+    api::servers::{app_state::AppState, listen, proxy, tls::TlsListener},
+    bootstrap::config::Config,
+};
+use axum::{
+    Router,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{CloseFrame, Message, WebSocket, close_code},
+    },
+    http::{StatusCode, header::ORIGIN, request::Parts},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+    serve::ListenerExt,
+};
+use errors::AppError;
+use futures_util::{FutureExt, sink::SinkExt, stream::StreamExt};
+use log::{info, warn};
+use protocol::Request;
+use router::{ActionRouter, Call, Connection, actor_for};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::broadcast::error::RecvError;
+
+static ROUTER: LazyLock<ActionRouter> = LazyLock::new(ActionRouter::standard);
+
+/// Build the WebSocket router
+pub fn build_router(app_state: AppState) -> Router {
+    let router = Router::new()
+        .route("/ws", get(websocket_handler))
+        .with_state(app_state.clone())
+        .layer(middleware::from_fn_with_state(
+            SecurityHeaders::of(&app_state),
+            headers::secure,
+        ));
+    proxy::nest(router, &app_state.proxy)
+}
+
+pub async fn start(app_state: &AppState, config: &Config) -> Result<(), AppError> {
+    let app = build_router(app_state.clone());
+
+    let addrs = config.server.listen_addrs(config.server.websocket_port);
+    let listeners = listen::bind_all(&addrs)?;
+    let servers = listeners
+        .into_iter()
+        .map(|listener| {
+            if let Ok(addr) = listener.local_addr() {
+                info!("WebSocket Server up on addr: {}", addr);
+            }
+            let app = app
+                .clone()
+                .into_make_service_with_connect_info::<SocketAddr>();
+            // In-flight requests finish once shutdown starts
+            let stopped = app_state.shutdown.triggered();
+            Ok(match &app_state.tls {
+                Some(tls) => {
+                    let listener = TlsListener::new(listener, tls.clone())?.tap_io(|_| {});
+                    axum::serve(listener, app)
+                        .with_graceful_shutdown(stopped)
+                        .into_future()
+                        .boxed()
+                }
+                None => axum::serve(listener, app)
+                    .with_graceful_shutdown(stopped)
+                    .into_future()
+                    .boxed(),
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+    futures_util::future::try_join_all(servers).await?;
+
+    Ok(())
+}
+
+pub async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    State(app_state): State<AppState>,
+    parts: Parts,
+) -> Response {
+    // Browsers send an Origin with every upgrade; other clients may not
+    if let Some(origin) = parts.headers.get(ORIGIN) {
+        let allowed = origin.to_str().is_ok_and(|origin| match &app_state.server {
+            Some(server) => cors::allows(&server.allowed_origins, origin),
+            None => cors::allows(cors::DEFAULT_ORIGINS, origin),
+        });
+        if !allowed {
+            warn!("Refused WebSocket connection from origin {:?}", origin);
+            return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+        }
+    }
+    let Client(client) = Client::of(&parts);
+    let connection = Arc::new(Connection::new(
+        client,
+        ClientPlace::of(&parts),
+        i18n::locale_of(&parts.headers),
+    ));
+    // Messages are held whole in memory, so they get the JSON body limit
+    ws.max_message_size(app_state.limits.max_body_bytes)
+        .on_upgrade(|socket| websocket_connection(socket, app_state, connection))
+}
+
+async fn websocket_connection(socket: WebSocket, app_state: AppState, connection: Arc<Connection>) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut events = app_state.events.subscribe();
+    let _open = app_state.node.read().await.tasks.track("websocket");
+    let stopped = app_state.shutdown.triggered();
+    tokio::pin!(stopped);
+
+    loop {
+        tokio::select! {
+            _ = &mut stopped => {
+                let _ = sender.send(Message::Close(Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "Node shutting down".into(),
+                }))).await;
+                break;
+            }
+            msg = receiver.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let Ok(message) = serde_json::from_str::<Value>(&text) else {
+                        continue;
+                    };
+                    if Request::is_versioned(&message) {
+                        let response = handle_request(&app_state, &connection, message).await;
+                        let _ = sender.send(Message::Text(json!(response).to_string().into())).await;
+                    } else {
+                        handle_websocket_message(&app_state, &mut sender, message).await;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                _ => {}
+            },
+            event = events.recv() => {
+                let response = match event {
+                    Ok(event) if connection.wants(&event) => {
+                        let properties = json!(event.properties);
+                        protocol::Response::event(event.event_type.as_str(), properties)
+                    }
+                    Ok(_) => continue,
+                    // A slow reader misses events rather than holding them up
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("WebSocket subscriber missed {} events", missed);
+                        protocol::Response::event("events.lagged", json!({"missed": missed}))
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let _ = sender.send(Message::Text(json!(response).to_string().into())).await;
+            }
+        }
+    }
+}
+
+/// Answer a versioned request
+async fn handle_request(
+    app_state: &AppState,
+    connection: &Arc<Connection>,
+    message: Value,
+) -> protocol::Response {
+    let id = message["id"].clone();
+    let action = message["action"].as_str().unwrap_or_default().to_string();
+    let request = match Request::parse(message) {
+        Ok(request) => request,
+        Err(failure) => {
+            return protocol::Response::error(id, &action, failure, connection.locale);
+        }
+    };
+
+    let call = Call {
+        app_state: app_state.clone(),
+        connection: connection.clone(),
+        token: request.token,
+        payload: request.payload,
+    };
+    match ROUTER.dispatch(&request.action, call).await {
+        Ok(payload) => protocol::Response::ok(request.id, &request.action, payload),
+        Err(failure) => {
+            protocol::Response::error(request.id, &request.action, failure, connection.locale)
+        }
+    }
+}
+
+/// Answer a version 0 message
+async fn handle_websocket_message(
+    app_state: &AppState,
+    sender: &mut futures_util::stream::SplitSink<WebSocket, axum::extract::ws::Message>,
+    payload: Value,
+) {
+    let action = payload["action"].as_str().unwrap_or("");
+
+    match action {
+        "create_space" => {
+            let node = app_state.node.read().await;
+            let dir = payload["dir"].as_str().unwrap_or("/tmp/space");
+
+            let created = match actor_for(&node, payload["token"].as_str()).await {
+                Ok(actor) => match node.maintenance.check() {
+                    Ok(()) => node.create_space_as(actor, dir).await,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            match created {
+                Ok(_) => {
+                    let response = json!({
+                        "action": "space_created",
+                        "status": "success"
+                    });
+                    let _ = sender
+                        .send(axum::extract::ws::Message::Text(
+                            response.to_string().into(),
+                        ))
+                        .await;
+                }
+                Err(e) => {
+                    let response = json!({
+                        "action": "error",
+                        "message": e.to_string(),
+                        "status": "error"
+                    });
+                    let _ = sender
+                        .send(axum::extract::ws::Message::Text(
+                            response.to_string().into(),
+                        ))
+                        .await;
+                }
+            }
+        }
+        _ => {
+            // Plugin actions aren't published; guests must sign in first
+            let signed_in = {
+                let node = app_state.node.read().await;
+                !node.guest_mode || actor_for(&node, payload["token"].as_str()).await.is_ok()
+            };
+            let handled = match signed_in {
+                true => {
+                    app_state
+                        .plugins
+                        .handle_ws_action(action, payload.clone())
+                        .await
+                }
+                false => Some(Err(AppError::Auth("Sign in required".to_string()))),
+            };
+            let response = match handled {
+                Some(Ok(result)) => json!({
+                    "action": action,
+                    "status": "success",
+                    "result": result
+                }),
+                Some(Err(e)) => json!({
+                    "action": "error",
+                    "message": e.to_string(),
+                    "status": "error"
+                }),
+                None => json!({
+                    "action": "error",
+                    "message": "Unknown action",
+                    "status": "error"
+                }),
+            };
+            let _ = sender
+                .send(axum::extract::ws::Message::Text(
+                    response.to_string().into(),
+                ))
+                .await;
+        }
+    }
+}
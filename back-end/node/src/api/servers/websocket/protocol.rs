@@ -0,0 +1,174 @@
+//! Wire format of the WebSocket protocol.
+//!
+//! A request is a JSON text message
+//! `{"v": 1, "id": ..., "action": "space.create", "payload": {...}}`, where
+//! `id` is any string or number the client picks, `v` defaults to the
+//! current version, and `token` may carry a session token. Every request gets
+//! exactly one response echoing its `id` and `action`:
+//! `{"v": 1, "id": ..., "status": "ok" | "error", "action": ..., "payload": ...}`,
+//! where an error's payload is `{"code", "message", "detail"}` (see
+//! [`crate::api::servers::i18n`]). Server-initiated
+//! messages have status `event`, a null `id`, and the event type as action.
+//!
+//! Messages without an `id` are version 0, the original
+//! `{"action": "create_space", "dir": ...}` format, and are answered as
+//! before.
+
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::api::servers::i18n;
+use crate::modules::ssi::did::resolvers::ResolutionError;
+
+/// Version this node speaks by default
+pub const VERSION: u64 = 1;
+
+/// Every version this node answers, oldest first
+pub const SUPPORTED_VERSIONS: &[u64] = &[1];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    #[serde(default = "current_version")]
+    pub v: u64,
+    pub id: Value,
+    pub action: String,
+    #[serde(default)]
+    pub payload: Value,
+    /// Session token for this request; otherwise the connection's
+    pub token: Option<String>,
+}
+
+fn current_version() -> u64 {
+    VERSION
+}
+
+impl Request {
+    /// Whether a message is a versioned request rather than a version 0 one
+    pub fn is_versioned(message: &Value) -> bool {
+        message.get("id").is_some()
+    }
+
+    pub fn parse(message: Value) -> Result<Self, Failure> {
+        let request: Request = serde_json::from_value(message)
+            .map_err(|e| Failure::new("bad_request", format!("Malformed request: {}", e)))?;
+        if !SUPPORTED_VERSIONS.contains(&request.v) {
+            return Err(Failure::new(
+                "unsupported_version",
+                format!(
+                    "Protocol version {} isn't supported; supported versions: {:?}",
+                    request.v, SUPPORTED_VERSIONS
+                ),
+            ));
+        }
+        if !(request.id.is_string() || request.id.is_number()) {
+            return Err(Failure::new("bad_request", "id must be a string or number"));
+        }
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Error,
+    Event,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Response {
+    pub v: u64,
+    pub id: Value,
+    pub status: Status,
+    pub action: String,
+    pub payload: Value,
+}
+
+impl Response {
+    pub fn ok(id: Value, action: &str, payload: Value) -> Self {
+        Self::new(id, Status::Ok, action, payload)
+    }
+
+    /// A failure, its message in `locale`
+    pub fn error(id: Value, action: &str, failure: Failure, locale: &str) -> Self {
+        let payload = json!({
+            "code": failure.code,
+            "message": i18n::message(failure.code, locale),
+            "detail": failure.message,
+        });
+        Self::new(id, Status::Error, action, payload)
+    }
+
+    pub fn event(action: &str, payload: Value) -> Self {
+        Self::new(Value::Null, Status::Event, action, payload)
+    }
+
+    fn new(id: Value, status: Status, action: &str, payload: Value) -> Self {
+        Self {
+            v: VERSION,
+            id,
+            status,
+            action: action.to_string(),
+            payload,
+        }
+    }
+}
+
+/// Why a request failed; `code` is stable, `message` is the specific
+/// reason, for people
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Failure {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn unknown_action(action: &str) -> Self {
+        Self::new("unknown_action", format!("Unknown action '{}'", action))
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new("invalid", message)
+    }
+}
+
+/// Codes for an [`AppError`], matching REST's status codes
+impl From<AppError> for Failure {
+    fn from(e: AppError) -> Self {
+        let code = match &e {
+            AppError::NotFound(_) => "not_found",
+            AppError::Auth(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::RateLimited(_) => "rate_limited",
+            AppError::PayloadTooLarge(_) => "too_large",
+            AppError::Validation(_) => "invalid",
+            AppError::Conflict(_) => "conflict",
+            AppError::Replayed(_) => "replayed",
+            AppError::Maintenance(_) => "maintenance",
+            _ => "internal",
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<ResolutionError> for Failure {
+    fn from(e: ResolutionError) -> Self {
+        let code = match &e {
+            ResolutionError::InvalidDid(_) => "invalid",
+            ResolutionError::NotFound | ResolutionError::Deactivated => "not_found",
+            ResolutionError::MethodNotSupported(_)
+            | ResolutionError::RepresentationNotSupported(_) => "not_supported",
+            ResolutionError::NetworkError(_) => "unavailable",
+            _ => "internal",
+        };
+        Self::new(code, e.to_string())
+    }
+}
@@ -0,0 +1,554 @@
+//! Dispatch table for versioned WebSocket requests.
+//!
+//! Each action maps to a handler taking a [`Call`]. Actions not in the table
+//! fall through to the ones plugins register, then fail as unknown. With
+//! guest mode on, only public actions run without a session; in maintenance,
+//! actions that write are refused.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use errors::AppError;
+use event::types::{Event, EventType};
+use futures_util::future::BoxFuture;
+use log::{error, info};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use webauthn_rs::prelude::{PublicKeyCredential, RegisterPublicKeyCredential};
+
+use super::protocol::{Failure, SUPPORTED_VERSIONS, VERSION};
+use crate::api::node::Node;
+use crate::api::servers::app_state::AppState;
+use crate::api::servers::rest::ClientPlace;
+use crate::modules::key_usage::{KeyKind, KeyUse, Operation};
+use crate::modules::space::Encryption;
+use crate::modules::ssi::did::types::ResolutionOptions;
+use crate::modules::ssi::webauthn::fingerprint::ClientFingerprint;
+use crate::modules::ssi::webauthn::options::CeremonyOverrides;
+use crate::modules::tenancy::Actor;
+use crate::modules::validation;
+
+pub type Handler = fn(Call) -> BoxFuture<'static, Result<Value, Failure>>;
+
+struct Route {
+    handler: Handler,
+    /// Runs without a session in guest mode
+    public: bool,
+    /// Changes what the node holds, so is refused in maintenance
+    writes: bool,
+}
+
+/// Actions by name
+#[derive(Default)]
+pub struct ActionRouter {
+    routes: BTreeMap<&'static str, Route>,
+}
+
+impl ActionRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The node's own actions
+    pub fn standard() -> Self {
+        Self::new()
+            .public("protocol.hello", |call| Box::pin(hello(call)))
+            .writes("space.create", |call| Box::pin(create_space(call)))
+            .route("space.list", |call| Box::pin(list_spaces(call)))
+            .writes("space.delete", |call| Box::pin(delete_space(call)))
+            .writes("space.restore", |call| Box::pin(restore_space(call)))
+            .writes("space.scan", |call| Box::pin(scan_space(call)))
+            .route("did.resolve", |call| Box::pin(resolve_did(call)))
+            .writes("webauthn.start_registration", |call| {
+                Box::pin(start_registration(call))
+            })
+            .writes("webauthn.finish_registration", |call| {
+                Box::pin(finish_registration(call))
+            })
+            .public("webauthn.start_authentication", |call| {
+                Box::pin(start_authentication(call))
+            })
+            .public("webauthn.finish_authentication", |call| {
+                Box::pin(finish_authentication(call))
+            })
+            .route("events.subscribe", |call| Box::pin(subscribe(call)))
+            .route("events.unsubscribe", |call| Box::pin(unsubscribe(call)))
+    }
+
+    pub fn route(mut self, action: &'static str, handler: Handler) -> Self {
+        self.routes.insert(
+            action,
+            Route {
+                handler,
+                public: false,
+                writes: false,
+            },
+        );
+        self
+    }
+
+    /// Like [`route`](Self::route), but open to guests
+    pub fn public(mut self, action: &'static str, handler: Handler) -> Self {
+        self.routes.insert(
+            action,
+            Route {
+                handler,
+                public: true,
+                writes: false,
+            },
+        );
+        self
+    }
+
+    /// Like [`route`](Self::route), for an action that writes
+    pub fn writes(mut self, action: &'static str, handler: Handler) -> Self {
+        self.routes.insert(
+            action,
+            Route {
+                handler,
+                public: false,
+                writes: true,
+            },
+        );
+        self
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.routes.keys().copied()
+    }
+
+    pub async fn dispatch(&self, action: &str, call: Call) -> Result<Value, Failure> {
+        let route = self.routes.get(action);
+        if !route.is_some_and(|route| route.public) {
+            let node = call.app_state.node.read().await;
+            if node.guest_mode {
+                actor_for(&node, call.token().as_deref()).await?;
+            }
+        }
+        if route.is_some_and(|route| route.writes) {
+            call.app_state.node.read().await.maintenance.check()?;
+        }
+
+        match route {
+            Some(route) => (route.handler)(call).await,
+            None => match call
+                .app_state
+                .plugins
+                .handle_ws_action(action, call.payload)
+                .await
+            {
+                Some(result) => result.map_err(Failure::from),
+                None => Err(Failure::unknown_action(action)),
+            },
+        }
+    }
+}
+
+/// State of one WebSocket connection
+pub struct Connection {
+    pub client: ClientFingerprint,
+    pub place: ClientPlace,
+    /// Language of error messages, from the upgrade's `Accept-Language`
+    pub locale: &'static str,
+    /// Session opened by signing in over this connection
+    session: Mutex<Option<String>>,
+    subscription: Mutex<Option<Subscription>>,
+}
+
+impl Connection {
+    pub fn new(client: ClientFingerprint, place: ClientPlace, locale: &'static str) -> Self {
+        Self {
+            client,
+            place,
+            locale,
+            session: Mutex::new(None),
+            subscription: Mutex::new(None),
+        }
+    }
+
+    /// Whether the subscriber on this connection should see `event`
+    pub fn wants(&self, event: &Event) -> bool {
+        self.subscription
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .is_some_and(|subscription| subscription.wants(event))
+    }
+}
+
+/// Events a connection asked for, seen as its actor
+struct Subscription {
+    actor: Actor,
+    /// Every type when `None`
+    types: Option<HashSet<EventType>>,
+}
+
+impl Subscription {
+    /// Users only see events about themselves; the node sees every event
+    fn wants(&self, event: &Event) -> bool {
+        let about_actor = match self.actor {
+            Actor::Node => true,
+            Actor::User(id) => {
+                event.properties.get("user_id").and_then(Value::as_i64) == Some(i64::from(id))
+            }
+        };
+        about_actor
+            && self
+                .types
+                .as_ref()
+                .is_none_or(|types| types.contains(&event.event_type))
+    }
+}
+
+/// One request, as a handler sees it
+pub struct Call {
+    pub app_state: AppState,
+    pub connection: std::sync::Arc<Connection>,
+    pub token: Option<String>,
+    pub payload: Value,
+}
+
+impl Call {
+    /// The request's session token, else the one this connection signed in with
+    fn token(&self) -> Option<String> {
+        self.token.clone().or_else(|| {
+            self.connection
+                .session
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone()
+        })
+    }
+
+    fn payload<T: DeserializeOwned>(&self) -> Result<T, Failure> {
+        let payload = match &self.payload {
+            Value::Null => json!({}),
+            payload => payload.clone(),
+        };
+        serde_json::from_value(payload)
+            .map_err(|e| Failure::invalid(format!("Invalid payload: {}", e)))
+    }
+}
+
+/// Who a message acts for. In multi-user and guest mode messages carry the
+/// sender's session `token`.
+pub async fn actor_for(node: &Node, token: Option<&str>) -> Result<Actor, AppError> {
+    if !node.issues_sessions() {
+        return Ok(Actor::Node);
+    }
+
+    let token = token.ok_or_else(|| AppError::Auth("Sign in required".to_string()))?;
+    node.session_actor(token)
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired session".to_string()))
+}
+
+/// Failure for a ceremony; client mismatches, lockouts and rejected options
+/// are reported as such
+fn ceremony_failure(e: AppError) -> Failure {
+    match e {
+        AppError::Forbidden(_) | AppError::RateLimited(_) | AppError::Validation(_) => e.into(),
+        e => Failure::new("internal", e.to_string()),
+    }
+}
+
+async fn hello(_call: Call) -> Result<Value, Failure> {
+    Ok(json!({
+        "version": VERSION,
+        "versions": SUPPORTED_VERSIONS,
+        "actions": super::ROUTER.actions().collect::<Vec<_>>(),
+    }))
+}
+
+#[derive(Deserialize)]
+struct CreateSpace {
+    dir: String,
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    client_keys: bool,
+}
+
+async fn create_space(call: Call) -> Result<Value, Failure> {
+    let request: CreateSpace = call.payload()?;
+    if request.dir.trim().is_empty() {
+        return Err(Failure::invalid("dir is required"));
+    }
+
+    let node = call.app_state.node.read().await;
+    let actor = actor_for(&node, call.token().as_deref()).await?;
+    let encryption = Encryption::from_flags(request.encrypted, request.client_keys)?;
+    node.create_space_with(actor, &request.dir, encryption)
+        .await?;
+    Ok(json!({"dir": request.dir}))
+}
+
+/// Spaces the caller may see, as the REST listing shows them
+async fn list_spaces(call: Call) -> Result<Value, Failure> {
+    let node = call.app_state.node.read().await;
+    let actor = actor_for(&node, call.token().as_deref()).await?;
+    let spaces = node.list_spaces_as(actor).await?;
+    Ok(json!({
+        "spaces": spaces
+            .iter()
+            .map(|s| {
+                json!({
+                    "key": s.key,
+                    "location": s.location,
+                    "time_created": s.time_created,
+                    "version": s.version,
+                    "client_keys": s.client_keys,
+                })
+            })
+            .collect::<Vec<_>>()
+    }))
+}
+
+#[derive(Deserialize)]
+struct SpaceKey {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct SpaceEdit {
+    key: String,
+    /// Version the client read; a stale one is refused with `conflict`
+    #[serde(default)]
+    version: Option<i32>,
+}
+
+/// Delete a space; its files stay on disk, and `space.restore` brings it
+/// back within the restore window
+async fn delete_space(call: Call) -> Result<Value, Failure> {
+    let request: SpaceEdit = call.payload()?;
+    let node = call.app_state.node.read().await;
+    let actor = actor_for(&node, call.token().as_deref()).await?;
+    let space = node
+        .delete_space_as(actor, &request.key, request.version)
+        .await?;
+    Ok(json!({"key": space.key, "location": space.location, "version": space.version}))
+}
+
+async fn restore_space(call: Call) -> Result<Value, Failure> {
+    let request: SpaceEdit = call.payload()?;
+    let node = call.app_state.node.read().await;
+    let actor = actor_for(&node, call.token().as_deref()).await?;
+    let space = node
+        .restore_space_as(actor, &request.key, request.version)
+        .await?;
+    Ok(json!({"key": space.key, "location": space.location, "version": space.version}))
+}
+
+/// Re-index a space. The answer lists what changed; a subscriber on this
+/// connection also gets a file event for each change.
+async fn scan_space(call: Call) -> Result<Value, Failure> {
+    let request: SpaceKey = call.payload()?;
+    let node = call.app_state.node.read().await;
+    let actor = actor_for(&node, call.token().as_deref()).await?;
+    let scan = node.scan_space_as(actor, &request.key).await?;
+    Ok(json!(scan))
+}
+
+#[derive(Deserialize)]
+struct ResolveDid {
+    did: String,
+    accept: Option<String>,
+    no_cache: Option<bool>,
+    timeout_ms: Option<u64>,
+}
+
+async fn resolve_did(call: Call) -> Result<Value, Failure> {
+    let request: ResolveDid = call.payload()?;
+    validation::validate_did(&request.did)?;
+
+    let mut options = ResolutionOptions::new();
+    if let Some(accept) = &request.accept {
+        options = options.with_accept(accept).map_err(Failure::invalid)?;
+    }
+    options.no_cache = request.no_cache;
+    options.timeout_ms = request.timeout_ms;
+
+    let resolver = call.app_state.node.read().await.did_resolver.clone();
+    let result = resolver.resolve_did(&request.did, &options).await?;
+    Ok(json!(result))
+}
+
+#[derive(Deserialize)]
+struct StartCeremony {
+    #[serde(default)]
+    overrides: CeremonyOverrides,
+    /// `conditional` for passkey autofill; sign-in only
+    mediation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FinishCeremony<C> {
+    challenge_id: String,
+    credential: C,
+}
+
+async fn start_registration(call: Call) -> Result<Value, Failure> {
+    let request: StartCeremony = call.payload()?;
+    let node = call.app_state.node.read().await;
+    let (challenge, challenge_id, options) = node
+        .start_webauthn_registration_with(Some(&call.connection.client), &request.overrides)
+        .await
+        .map_err(ceremony_failure)?;
+    info!(
+        "WebAuthn registration started over WebSocket with challenge_id: {}",
+        challenge_id
+    );
+    Ok(json!({
+        "challenge": challenge,
+        "challenge_id": challenge_id,
+        "options": options
+    }))
+}
+
+async fn finish_registration(call: Call) -> Result<Value, Failure> {
+    let request: FinishCeremony<RegisterPublicKeyCredential> = call.payload()?;
+    let node = call.app_state.node.read().await;
+    let (did, did_document) = node
+        .finish_webauthn_registration_for(
+            &request.challenge_id,
+            request.credential,
+            Some(&call.connection.client),
+        )
+        .await
+        .map_err(|e| {
+            error!(
+                "WebAuthn registration failed for challenge_id {}: {}",
+                request.challenge_id, e
+            );
+            ceremony_failure(e)
+        })?;
+    Ok(json!({
+        "did": did,
+        "didDocument": serde_json::from_str::<Value>(&did_document).unwrap_or(json!({}))
+    }))
+}
+
+async fn start_authentication(call: Call) -> Result<Value, Failure> {
+    let request: StartCeremony = call.payload()?;
+    let client = &call.connection.client;
+    let node = call.app_state.node.read().await;
+    let (challenge, challenge_id, options) = match request.mediation.as_deref() {
+        None => node
+            .start_webauthn_authentication_with(Some(client), &request.overrides)
+            .await
+            .map(|(challenge, id, options)| (challenge, id, Some(options))),
+        Some("conditional") => node
+            .start_webauthn_conditional_authentication_for(Some(client))
+            .await
+            .map(|(challenge, id)| (challenge, id, None)),
+        Some(other) => {
+            return Err(Failure::invalid(format!(
+                "Unsupported mediation '{}'",
+                other
+            )));
+        }
+    }
+    .map_err(ceremony_failure)?;
+
+    let mut body = json!({
+        "challenge": challenge,
+        "challenge_id": challenge_id
+    });
+    if let Some(options) = options {
+        body["options"] = json!(options);
+    }
+    Ok(body)
+}
+
+/// Signing in also signs the connection in, so later requests needn't carry
+/// the token
+async fn finish_authentication(call: Call) -> Result<Value, Failure> {
+    let request: FinishCeremony<PublicKeyCredential> = call.payload()?;
+    let node = call.app_state.node.read().await;
+    let result = node
+        .finish_webauthn_authentication_for(
+            &request.challenge_id,
+            request.credential,
+            Some(&call.connection.client),
+        )
+        .await
+        .map_err(|e| {
+            error!(
+                "WebAuthn authentication failed for challenge_id {}: {}",
+                request.challenge_id, e
+            );
+            ceremony_failure(e)
+        })?;
+    let (token, session) = node.open_user_session(&result).await?;
+    node.record_key_use(&KeyUse {
+        kind: KeyKind::Passkey,
+        key_id: URL_SAFE_NO_PAD.encode(result.cred_id().as_ref()),
+        user_id: Some(session.user_id),
+        at: Utc::now(),
+        ip: call.connection.place.ip,
+        country: call.connection.place.country.clone(),
+        operation: Operation::Authenticate,
+    })?;
+    *call
+        .connection
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(token.clone());
+
+    Ok(json!({
+        "counter": result.counter(),
+        "backup_state": result.backup_state(),
+        "backup_eligible": result.backup_eligible(),
+        "needs_update": result.needs_update(),
+        "session": {"token": token, "expiresAt": session.expires_at}
+    }))
+}
+
+#[derive(Deserialize)]
+struct Subscribe {
+    types: Option<Vec<String>>,
+}
+
+/// Push node events to this connection; a later subscribe replaces it
+async fn subscribe(call: Call) -> Result<Value, Failure> {
+    let request: Subscribe = call.payload()?;
+    let types = request
+        .types
+        .map(|types| {
+            types
+                .iter()
+                .map(|t| t.parse::<EventType>())
+                .collect::<Result<HashSet<_>, _>>()
+        })
+        .transpose()
+        .map_err(Failure::invalid)?;
+
+    let actor = {
+        let node = call.app_state.node.read().await;
+        actor_for(&node, call.token().as_deref()).await?
+    };
+    let subscribed = types
+        .as_ref()
+        .map(|types| types.iter().map(EventType::as_str).collect::<Vec<_>>());
+    *call
+        .connection
+        .subscription
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(Subscription { actor, types });
+    Ok(json!({"types": subscribed}))
+}
+
+async fn unsubscribe(call: Call) -> Result<Value, Failure> {
+    let was = call
+        .connection
+        .subscription
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .is_some();
+    Ok(json!({"subscribed": was}))
+}
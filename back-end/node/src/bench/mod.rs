@@ -0,0 +1,129 @@
+//! `node bench` - load tests that drive the node's own APIs against an
+//! ephemeral in-process node and report latency percentiles.
+
+mod report;
+mod scenarios;
+
+pub use report::BenchReport;
+pub use scenarios::{BenchNode, run_scenario};
+
+use errors::AppError;
+use std::fmt;
+use std::str::FromStr;
+
+pub const USAGE: &str =
+    "Usage: node bench <webauthn|did|spaces|ws|broadcast|all> [--concurrency N] [--iterations N]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scenario {
+    /// Registration + authentication ceremonies with a software passkey
+    WebAuthn,
+    /// did:key and did:peer resolution
+    Did,
+    /// Checksum manifest of a seeded space folder
+    Spaces,
+    /// WebSocket connect + `create_space` round trip
+    WebSocket,
+    /// Fan-out of one node event to every `events.subscribe` subscriber
+    Broadcast,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 5] = [
+        Scenario::WebAuthn,
+        Scenario::Did,
+        Scenario::Spaces,
+        Scenario::WebSocket,
+        Scenario::Broadcast,
+    ];
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Scenario::WebAuthn => "webauthn",
+            Scenario::Did => "did",
+            Scenario::Spaces => "spaces",
+            Scenario::WebSocket => "ws",
+            Scenario::Broadcast => "broadcast",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webauthn" => Ok(Scenario::WebAuthn),
+            "did" => Ok(Scenario::Did),
+            "spaces" => Ok(Scenario::Spaces),
+            "ws" => Ok(Scenario::WebSocket),
+            "broadcast" => Ok(Scenario::Broadcast),
+            _ => Err(AppError::Config(format!("Unknown bench scenario: {}", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchArgs {
+    pub scenarios: Vec<Scenario>,
+    pub concurrency: usize,
+    pub iterations: usize,
+}
+
+impl BenchArgs {
+    /// Parse the arguments following `bench`
+    pub fn parse<I, S>(args: I) -> Result<Self, AppError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut scenarios = None;
+        let mut concurrency = 4;
+        let mut iterations = 100;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--concurrency" | "-c" => concurrency = parse_count(&arg, args.next())?,
+                "--iterations" | "-n" => iterations = parse_count(&arg, args.next())?,
+                "all" => scenarios = Some(Scenario::ALL.to_vec()),
+                other => scenarios = Some(vec![other.parse()?]),
+            }
+        }
+
+        Ok(Self {
+            scenarios: scenarios.ok_or_else(|| AppError::Config(USAGE.to_string()))?,
+            concurrency,
+            iterations,
+        })
+    }
+}
+
+fn parse_count<S: AsRef<str>>(flag: &S, value: Option<S>) -> Result<usize, AppError> {
+    value
+        .as_ref()
+        .and_then(|v| v.as_ref().parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .ok_or_else(|| AppError::Config(format!("{} expects a positive number", flag.as_ref())))
+}
+
+/// Entry point for `node bench ...`
+pub async fn run<I, S>(args: I) -> Result<Vec<BenchReport>, AppError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let args = BenchArgs::parse(args)?;
+    let bench_node = BenchNode::start().await?;
+
+    let mut reports = Vec::with_capacity(args.scenarios.len());
+    for scenario in &args.scenarios {
+        reports
+            .push(run_scenario(&bench_node, *scenario, args.concurrency, args.iterations).await?);
+    }
+
+    Ok(reports)
+}
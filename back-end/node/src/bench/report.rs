@@ -0,0 +1,71 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Latency summary for one bench scenario
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub scenario: String,
+    pub concurrency: usize,
+    /// Latencies of successful operations, sorted ascending
+    pub latencies: Vec<Duration>,
+    pub errors: usize,
+    /// Wall clock time for the whole run
+    pub elapsed: Duration,
+}
+
+impl BenchReport {
+    pub fn new(
+        scenario: String,
+        concurrency: usize,
+        mut latencies: Vec<Duration>,
+        errors: usize,
+        elapsed: Duration,
+    ) -> Self {
+        latencies.sort();
+        Self {
+            scenario,
+            concurrency,
+            latencies,
+            errors,
+            elapsed,
+        }
+    }
+
+    /// Latency at percentile `p` (0-100), nearest-rank
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let rank = ((p / 100.0) * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+
+    /// Successful operations per second
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.latencies.len() as f64 / secs
+        }
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<9} ok={:<6} err={:<4} c={:<3} {:>9.1} op/s  p50={:>9.3?} p90={:>9.3?} p99={:>9.3?} max={:>9.3?}",
+            self.scenario,
+            self.latencies.len(),
+            self.errors,
+            self.concurrency,
+            self.throughput(),
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.latencies.last().copied().unwrap_or_default(),
+        )
+    }
+}
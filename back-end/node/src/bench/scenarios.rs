@@ -0,0 +1,405 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use errors::AppError;
+use event::types::{Event, EventType};
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{ColumnTrait, Database, EntityTrait, QueryFilter};
+use serde_json::{Value, json};
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use url::Url;
+use webauthn_authenticator_rs::AuthenticatorBackend;
+use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+
+use super::Scenario;
+use super::report::BenchReport;
+use crate::api::node::Node;
+use crate::api::servers::app_state::AppState;
+use crate::api::servers::websocket;
+use crate::bootstrap::init::initialize_config_dir;
+use crate::modules::ssi::did::DidResolver;
+use crate::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+use crate::modules::ssi::did::types::ResolutionOptions;
+use crate::modules::ssi::webauthn::state::{AuthConfig, AuthState};
+
+const BENCH_ORIGIN: &str = "http://localhost:3000";
+const SEED_FILES: usize = 100;
+const AUTHENTICATOR_TIMEOUT_MS: u32 = 60000;
+/// How long a subscriber may take to see a broadcast event
+const BROADCAST_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Ephemeral node the bench scenarios run against
+pub struct BenchNode {
+    pub node: Node,
+    pub ws_addr: SocketAddr,
+    pub space_key: String,
+    pub temp: TempDir,
+}
+
+impl BenchNode {
+    /// Bootstrap a throwaway node in a temp directory, seed a space and serve
+    /// the WebSocket API on loopback
+    pub async fn start() -> Result<Self, AppError> {
+        let temp = TempDir::new()?;
+
+        let node_data = initialize_config_dir(&temp.path().join("config").to_string_lossy())?;
+
+        let db_url = format!(
+            "sqlite://{}?mode=rwc",
+            temp.path().join("bench.db").display()
+        );
+        let db = Database::connect(&db_url)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Migrator::up(&db, None)
+            .await
+            .map_err(|e| AppError::Migration(Box::new(e)))?;
+
+        let kv = sled::open(temp.path().join("kv")).map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        let auth_state = AuthState::new(AuthConfig {
+            rp_id: "localhost".to_string(),
+            rp_origin: BENCH_ORIGIN.to_string(),
+            rp_name: "Flow Bench".to_string(),
+            policy: Default::default(),
+            limits: Default::default(),
+        })?;
+
+        let node = Node::new(node_data, db, kv, auth_state);
+        let space_key = seed_space(&node, &temp).await?;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let ws_addr = listener.local_addr()?;
+        let app = websocket::build_router(AppState::new(node.clone()));
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        info!(
+            "Bench node {} ready in {}",
+            node.node_data.id,
+            temp.path().display()
+        );
+
+        Ok(Self {
+            node,
+            ws_addr,
+            space_key,
+            temp,
+        })
+    }
+}
+
+async fn seed_space(node: &Node, temp: &TempDir) -> Result<String, AppError> {
+    let dir = temp.path().join("space");
+    for i in 0..SEED_FILES {
+        let sub = dir.join(format!("dir-{}", i % 10));
+        std::fs::create_dir_all(&sub)?;
+        std::fs::write(sub.join(format!("file-{}.txt", i)), vec![b'x'; 4096])?;
+    }
+
+    let location = dir.canonicalize()?.to_string_lossy().into_owned();
+    node.create_space(&location).await?;
+
+    entity::space::Entity::find()
+        .filter(entity::space::Column::Location.eq(location.as_str()))
+        .one(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .map(|space| space.key)
+        .ok_or_else(|| AppError::NotFound(format!("Space not found: {}", location)))
+}
+
+/// Run `iterations` operations of `scenario` spread across `concurrency`
+/// workers. A broadcast has `concurrency` subscribers instead.
+pub async fn run_scenario(
+    bench: &BenchNode,
+    scenario: Scenario,
+    concurrency: usize,
+    iterations: usize,
+) -> Result<BenchReport, AppError> {
+    match scenario {
+        Scenario::WebAuthn => {
+            let node = bench.node.clone();
+            drive(scenario, concurrency, iterations, move |_| {
+                let node = node.clone();
+                async move { webauthn_round(&node).await }
+            })
+            .await
+        }
+        Scenario::Did => {
+            let resolver = Arc::new(DidResolver::new());
+            let did_key = bench.node.node_data.id.clone();
+            let did_peer = PeerDidGenerator::from_ed25519_bytes(&bench.node.node_data.public_key)
+                .map_err(|e| AppError::Crypto(e.to_string()))?;
+
+            drive(scenario, concurrency, iterations, move |i| {
+                let resolver = resolver.clone();
+                let did = if i % 2 == 0 {
+                    did_key.clone()
+                } else {
+                    did_peer.clone()
+                };
+                async move {
+                    resolver
+                        .resolve_did(&did, &ResolutionOptions::default())
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| AppError::Validation(e.to_string()))
+                }
+            })
+            .await
+        }
+        Scenario::Spaces => {
+            let node = bench.node.clone();
+            let key = bench.space_key.clone();
+            drive(scenario, concurrency, iterations, move |_| {
+                let node = node.clone();
+                let key = key.clone();
+                async move { node.space_manifest(&key, "").await.map(|_| ()) }
+            })
+            .await
+        }
+        Scenario::WebSocket => {
+            let url = format!("ws://{}/ws", bench.ws_addr);
+            let root = bench.temp.path().join("ws-spaces");
+            drive(scenario, concurrency, iterations, move |i| {
+                let url = url.clone();
+                let dir = root.join(format!("space-{}", i));
+                async move { websocket_round(&url, &dir.to_string_lossy()).await }
+            })
+            .await
+        }
+        Scenario::Broadcast => broadcast(bench, concurrency, iterations).await,
+    }
+}
+
+async fn drive<F, Fut>(
+    scenario: Scenario,
+    concurrency: usize,
+    iterations: usize,
+    op: F,
+) -> Result<BenchReport, AppError>
+where
+    F: Fn(usize) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), AppError>> + Send,
+{
+    let next = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+
+    let workers: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let next = next.clone();
+            let op = op.clone();
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                let mut errors = 0;
+
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= iterations {
+                        break;
+                    }
+
+                    let start = Instant::now();
+                    match op(i).await {
+                        Ok(()) => latencies.push(start.elapsed()),
+                        Err(e) => {
+                            warn!("Bench {} operation {} failed: {}", scenario, i, e);
+                            errors += 1;
+                        }
+                    }
+                }
+
+                (latencies, errors)
+            })
+        })
+        .collect();
+
+    let mut latencies: Vec<Duration> = Vec::with_capacity(iterations);
+    let mut errors = 0;
+    for worker in workers {
+        let (worker_latencies, worker_errors) = worker
+            .await
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))?;
+        latencies.extend(worker_latencies);
+        errors += worker_errors;
+    }
+
+    Ok(BenchReport::new(
+        scenario.to_string(),
+        concurrency,
+        latencies,
+        errors,
+        started.elapsed(),
+    ))
+}
+
+async fn webauthn_round(node: &Node) -> Result<(), AppError> {
+    let origin = Url::parse(BENCH_ORIGIN).map_err(|e| AppError::Config(e.to_string()))?;
+    let mut authenticator = SoftPasskey::new(true);
+
+    let (challenge, challenge_id) = node.start_webauthn_registration().await?;
+    let credential = authenticator
+        .perform_register(
+            origin.clone(),
+            challenge.public_key,
+            AUTHENTICATOR_TIMEOUT_MS,
+        )
+        .map_err(|e| AppError::Auth(format!("Authenticator registration failed: {:?}", e)))?;
+    node.finish_webauthn_registration(&challenge_id, credential)
+        .await?;
+
+    let (challenge, challenge_id) = node.start_webauthn_authentication().await?;
+    let credential = authenticator
+        .perform_auth(origin, challenge.public_key, AUTHENTICATOR_TIMEOUT_MS)
+        .map_err(|e| AppError::Auth(format!("Authenticator assertion failed: {:?}", e)))?;
+    node.finish_webauthn_authentication(&challenge_id, credential)
+        .await?;
+
+    Ok(())
+}
+
+/// Publish `iterations` events one at a time; an operation lasts until every
+/// subscriber has received its event
+async fn broadcast(
+    bench: &BenchNode,
+    subscribers: usize,
+    iterations: usize,
+) -> Result<BenchReport, AppError> {
+    let url = format!("ws://{}/ws", bench.ws_addr);
+    let mut sockets = Vec::with_capacity(subscribers);
+    for _ in 0..subscribers {
+        sockets.push(subscriber(&url).await?);
+    }
+
+    let mut latencies = Vec::with_capacity(iterations);
+    let mut errors = 0;
+    let started = Instant::now();
+    for i in 0..iterations {
+        let event = Event::new(EventType::FileCreated)
+            .with("path", format!("bench/file-{}.txt", i))
+            .with("bench", i);
+
+        let start = Instant::now();
+        bench.node.publish(&event);
+        let delivered = futures_util::future::try_join_all(
+            sockets.iter_mut().map(|socket| receive_event(socket, i)),
+        );
+        match tokio::time::timeout(BROADCAST_TIMEOUT, delivered).await {
+            Ok(Ok(_)) => latencies.push(start.elapsed()),
+            Ok(Err(e)) => {
+                warn!("Bench broadcast {} failed: {}", i, e);
+                errors += 1;
+            }
+            Err(_) => {
+                warn!("Bench broadcast {} timed out", i);
+                errors += 1;
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+
+    for mut socket in sockets {
+        let _ = socket.close(None).await;
+    }
+
+    Ok(BenchReport::new(
+        Scenario::Broadcast.to_string(),
+        subscribers,
+        latencies,
+        errors,
+        elapsed,
+    ))
+}
+
+/// A connection subscribed to file events
+async fn subscriber(url: &str) -> Result<Socket, AppError> {
+    let mut socket = connect(url).await?;
+
+    let request = json!({
+        "v": 1,
+        "id": "subscribe",
+        "action": "events.subscribe",
+        "payload": { "types": [EventType::FileCreated.as_str()] },
+    });
+    send(&mut socket, &request).await?;
+
+    let reply = receive(&mut socket).await?;
+    if reply["status"] != "ok" {
+        return Err(AppError::Validation(format!(
+            "events.subscribe failed: {}",
+            reply["payload"]["detail"]
+        )));
+    }
+
+    Ok(socket)
+}
+
+/// Read until the event of broadcast `seq`, skipping earlier ones
+async fn receive_event(socket: &mut Socket, seq: usize) -> Result<(), AppError> {
+    loop {
+        let message = receive(socket).await?;
+        if message["status"] == "event" && message["payload"]["bench"] == seq {
+            return Ok(());
+        }
+    }
+}
+
+async fn connect(url: &str) -> Result<Socket, AppError> {
+    let (socket, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))?;
+    Ok(socket)
+}
+
+async fn send(socket: &mut Socket, message: &Value) -> Result<(), AppError> {
+    socket
+        .send(Message::Text(message.to_string().into()))
+        .await
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))
+}
+
+async fn receive(socket: &mut Socket) -> Result<Value, AppError> {
+    let message = socket
+        .next()
+        .await
+        .ok_or_else(|| AppError::IO(std::io::Error::other("WebSocket closed before reply")))?
+        .map_err(|e| AppError::IO(std::io::Error::other(e)))?;
+
+    serde_json::from_str(message.to_text().unwrap_or_default())
+        .map_err(|e| AppError::Validation(format!("Invalid WebSocket reply: {}", e)))
+}
+
+async fn websocket_round(url: &str, dir: &str) -> Result<(), AppError> {
+    let mut socket = connect(url).await?;
+
+    send(
+        &mut socket,
+        &json!({ "action": "create_space", "dir": dir }),
+    )
+    .await?;
+    let reply = receive(&mut socket).await;
+
+    let _ = socket.close(None).await;
+
+    let reply = reply?;
+    if reply["status"] != "success" {
+        return Err(AppError::Validation(format!(
+            "create_space failed: {}",
+            reply["message"]
+        )));
+    }
+
+    Ok(())
+}
@@ -1,13 +1,132 @@
 use dotenvy::dotenv;
 use errors::AppError;
+use ipnet::IpNet;
+
+use super::layout::DataLayout;
+use crate::api::servers::cors;
+use crate::modules::ssi::did::resolvers::adapter::DEFAULT_PARALLELISM;
+use crate::modules::ssi::did::resolvers::cache::DEFAULT_CAPACITY;
+use crate::modules::{scheduler, timestamp};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::{env, time::Duration};
 
+pub const IN_MEMORY_DATABASE_URL: &str = "sqlite::memory:";
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+pub const DEFAULT_NTP_SERVER: &str = "pool.ntp.org:123";
+
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub rest_port: u16,
     pub websocket_port: u16,
+    /// Port of the gRPC server; none runs unless set
+    pub grpc_port: Option<u16>,
+    /// Port other nodes connect to; no peer listener runs unless set
+    pub p2p_port: Option<u16>,
     pub host: String,
+    /// Interfaces both servers listen on; `HOST` alone unless
+    /// `FLOW_BIND_ADDRESSES` lists several
+    pub bind_addresses: Vec<IpAddr>,
+    /// `host:port` addresses peers should use to reach this node, when they
+    /// differ from the bound ones (NAT, proxies)
+    pub advertised_addresses: Vec<String>,
+    /// Origins browser apps may call the REST API and open WebSockets from; see
+    /// [`crate::api::servers::cors`] for wildcards
+    pub allowed_origins: Vec<String>,
+}
+
+impl ServerConfig {
+    /// Socket addresses to listen on for `port`
+    pub fn listen_addrs(&self, port: u16) -> Vec<SocketAddr> {
+        self.bind_addresses
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, port))
+            .collect()
+    }
+
+    /// Reject settings that can't all be bound, before binding any
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.bind_addresses.is_empty() {
+            return Err(AppError::Config("No bind addresses configured".to_string()));
+        }
+        if self.rest_port == self.websocket_port && self.rest_port != 0 {
+            return Err(AppError::Config(format!(
+                "REST and WebSocket servers both use port {}",
+                self.rest_port
+            )));
+        }
+        if let Some(grpc_port) = self.grpc_port
+            && grpc_port != 0
+            && (grpc_port == self.rest_port || grpc_port == self.websocket_port)
+        {
+            return Err(AppError::Config(format!(
+                "The gRPC server shares port {} with another server",
+                grpc_port
+            )));
+        }
+        if let Some(p2p_port) = self.p2p_port
+            && p2p_port != 0
+            && (p2p_port == self.rest_port
+                || p2p_port == self.websocket_port
+                || Some(p2p_port) == self.grpc_port)
+        {
+            return Err(AppError::Config(format!(
+                "The P2P listener shares port {} with another server",
+                p2p_port
+            )));
+        }
+
+        for (i, ip) in self.bind_addresses.iter().enumerate() {
+            for other in &self.bind_addresses[i + 1..] {
+                if ip == other {
+                    return Err(AppError::Config(format!(
+                        "Bind address {} is listed twice",
+                        ip
+                    )));
+                }
+                // A wildcard already covers every address of its family
+                if ip.is_ipv4() == other.is_ipv4()
+                    && (ip.is_unspecified() || other.is_unspecified())
+                {
+                    return Err(AppError::Config(format!(
+                        "Bind addresses {} and {} overlap",
+                        ip, other
+                    )));
+                }
+            }
+        }
+
+        for origin in &self.allowed_origins {
+            // A wildcard subdomain stands in for a host label; anything past
+            // the origin (a path, the default port) would never match
+            let origin_of = origin.replacen("://*.", "://wildcard.", 1);
+            let valid = origin == "*"
+                || url::Url::parse(&origin_of).is_ok_and(|url| {
+                    matches!(url.scheme(), "http" | "https")
+                        && url.origin().ascii_serialization() == origin_of
+                });
+            if !valid {
+                return Err(AppError::Config(format!(
+                    "CORS origin {} is not an http(s) origin",
+                    origin
+                )));
+            }
+        }
+
+        for address in &self.advertised_addresses {
+            let valid = url::Url::parse(&format!("tcp://{}", address)).is_ok_and(|url| {
+                url.host().is_some() && url.port().is_some() && url.path().is_empty()
+            });
+            if !valid {
+                return Err(AppError::Config(format!(
+                    "Advertised address {} is not host:port",
+                    address
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -21,9 +140,627 @@ pub struct DbConfig {
     pub logging_enabled: bool,
 }
 
+impl DbConfig {
+    /// Configuration for a throwaway in-memory SQLite database
+    pub fn in_memory() -> Self {
+        Self {
+            url: IN_MEMORY_DATABASE_URL.to_string(),
+            max_connections: 1,
+            min_connections: 1,
+            connect_timeout: Duration::from_secs(8),
+            idle_timeout: Duration::from_secs(600),
+            max_lifetime: Duration::from_secs(1800),
+            logging_enabled: false,
+        }
+    }
+
+    /// Whether the URL points at an in-memory SQLite database
+    pub fn is_in_memory(&self) -> bool {
+        self.url.contains(":memory:") || self.url.contains("mode=memory")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct KvConfig {
     pub path: String,
+    /// Use a temporary sled instance that is discarded on shutdown
+    pub in_memory: bool,
+    /// Page cache size; sled's default (1 GiB) when `None`
+    pub cache_bytes: Option<u64>,
+    /// Favour a small footprint over write throughput
+    pub low_space: bool,
+}
+
+impl KvConfig {
+    /// Configuration for a temporary KV store
+    pub fn in_memory() -> Self {
+        Self {
+            path: ":memory:".to_string(),
+            in_memory: true,
+            cache_bytes: None,
+            low_space: false,
+        }
+    }
+}
+
+/// Defaults under `FLOW_LOW_RESOURCE`, sized for Raspberry Pi–class devices.
+/// Settings given explicitly still win.
+pub mod low_resource {
+    pub const DB_MAX_CONNECTIONS: u64 = 4;
+    pub const DB_MIN_CONNECTIONS: u64 = 1;
+    pub const KV_CACHE_MB: u64 = 16;
+    pub const DID_CACHE_CAPACITY: u64 = 32;
+    pub const BODY_SPILL_BYTES: u64 = 64 * 1024;
+    pub const SOFT_LIMIT_MEMORY_MB: u64 = 384;
+    pub const MAX_CONCURRENT_REQUESTS: u64 = 64;
+    pub const MAX_CONCURRENT_EXPENSIVE: u64 = 2;
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateConfig {
+    pub enabled: bool,
+    /// URL serving the latest signed release manifest as JSON
+    pub endpoint: Option<String>,
+    /// Multibase Ed25519 key releases must be signed with
+    pub publisher_key: Option<String>,
+    pub check_interval: Duration,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            publisher_key: None,
+            check_interval: Duration::from_secs(60 * 60 * 24),
+        }
+    }
+}
+
+/// Read-only maintenance; see [`crate::modules::maintenance`]
+#[derive(Debug, Clone, Copy)]
+pub struct MaintenanceConfig {
+    /// Start in maintenance, until turned off over the API
+    pub enabled: bool,
+    /// Sent as `Retry-After` with refused writes
+    pub retry_after: Duration,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Opt-in usage telemetry; see [`crate::modules::telemetry`]
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    pub enabled: bool,
+    /// URL reports are POSTed to as JSON
+    pub endpoint: Option<String>,
+    pub submit_interval: Duration,
+    /// Privacy budget of a report; lower adds more noise
+    pub epsilon: f64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            submit_interval: Duration::from_secs(60 * 60 * 24 * 7),
+            epsilon: 1.0,
+        }
+    }
+}
+
+impl TelemetryConfig {
+    /// Reports need somewhere to go
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.enabled && self.endpoint.is_none() {
+            return Err(AppError::Config(
+                "FLOW_TELEMETRY_URL must be set when telemetry is enabled".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Request body limits. Uploads stream to disk once they outgrow
+/// `spill_threshold_bytes`, so a large body never sits in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    /// Largest body for ordinary (JSON) requests
+    pub max_body_bytes: usize,
+    /// Largest file upload
+    pub max_upload_bytes: u64,
+    pub spill_threshold_bytes: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1024 * 1024,
+            max_upload_bytes: 256 * 1024 * 1024,
+            spill_threshold_bytes: 256 * 1024,
+        }
+    }
+}
+
+/// Response compression for JSON and text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// Smaller responses aren't worth the overhead
+    pub min_size_bytes: u16,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size_bytes: 1024,
+        }
+    }
+}
+
+/// How long REST handlers may take and how many run at once, per class of
+/// route; see [`crate::api::servers::throttle`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLimitsConfig {
+    pub timeout: Duration,
+    /// Scans, exports, uploads and remote lookups
+    pub expensive_timeout: Duration,
+    pub max_concurrent: usize,
+    pub max_concurrent_expensive: usize,
+}
+
+impl Default for RequestLimitsConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            expensive_timeout: Duration::from_secs(300),
+            max_concurrent: 512,
+            max_concurrent_expensive: 16,
+        }
+    }
+}
+
+/// Security headers on HTTP responses; see [`crate::api::servers::headers`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecurityHeadersConfig {
+    /// Off leaves responses as handlers made them
+    pub enabled: bool,
+    /// `Strict-Transport-Security`, over HTTPS only
+    pub hsts: bool,
+    pub hsts_max_age_secs: u64,
+    /// `Content-Security-Policy`
+    pub csp: bool,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hsts: true,
+            hsts_max_age_secs: 365 * 24 * 60 * 60,
+            csp: true,
+        }
+    }
+}
+
+/// Where resolved DID documents are cached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DidCacheBackend {
+    /// Least recently used entries are evicted past the capacity
+    #[default]
+    Memory,
+    /// The node's KV store; survives restarts
+    Kv,
+    Off,
+}
+
+impl FromStr for DidCacheBackend {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "memory" => Ok(DidCacheBackend::Memory),
+            "kv" => Ok(DidCacheBackend::Kv),
+            "off" => Ok(DidCacheBackend::Off),
+            other => Err(AppError::Config(format!(
+                "Invalid DID cache '{}', expected memory, kv or off",
+                other
+            ))),
+        }
+    }
+}
+
+/// Where the node's private keys are kept; see [`super::keystore`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStorageBackend {
+    /// Files in the keystore directory, readable only by the node's user
+    #[default]
+    File,
+    /// The OS keychain: Keychain on macOS, the Credential Manager on
+    /// Windows, the Secret Service on Linux desktops. Needs the `keychain`
+    /// build feature.
+    Keychain,
+}
+
+impl FromStr for KeyStorageBackend {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "file" => Ok(KeyStorageBackend::File),
+            "keychain" => Ok(KeyStorageBackend::Keychain),
+            other => Err(AppError::Config(format!(
+                "Invalid key storage '{}', expected file or keychain",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DidCacheConfig {
+    pub backend: DidCacheBackend,
+    /// Entries held by the memory cache
+    pub capacity: usize,
+}
+
+impl Default for DidCacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: DidCacheBackend::default(),
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+/// Limits of `POST /api/v1/dids/resolve_batch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DidBatchConfig {
+    /// DIDs accepted in one request
+    pub max_dids: usize,
+    /// DIDs resolved at once
+    pub parallelism: usize,
+}
+
+impl Default for DidBatchConfig {
+    fn default() -> Self {
+        Self {
+            max_dids: 100,
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+}
+
+/// ACME challenge type used to prove control of the domains
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AcmeChallenge {
+    /// Answered over plain HTTP on `AcmeConfig::http_port`
+    #[default]
+    Http01,
+    /// Answered by the TLS listeners themselves
+    TlsAlpn01,
+}
+
+impl AcmeChallenge {
+    /// Challenge type as named by ACME
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AcmeChallenge::Http01 => "http-01",
+            AcmeChallenge::TlsAlpn01 => "tls-alpn-01",
+        }
+    }
+}
+
+impl FromStr for AcmeChallenge {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "http-01" => Ok(AcmeChallenge::Http01),
+            "tls-alpn-01" => Ok(AcmeChallenge::TlsAlpn01),
+            other => Err(AppError::Config(format!(
+                "Invalid ACME challenge '{}', expected http-01 or tls-alpn-01",
+                other
+            ))),
+        }
+    }
+}
+
+/// Automatic certificates from an ACME CA. Off unless domains are set;
+/// when on, the REST and WebSocket servers serve HTTPS.
+#[derive(Debug, Clone)]
+pub struct AcmeConfig {
+    pub domains: Vec<String>,
+    /// Email address the CA sends expiry notices to
+    pub contact: Option<String>,
+    pub directory_url: String,
+    pub challenge: AcmeChallenge,
+    /// Port HTTP-01 challenges are answered on
+    pub http_port: u16,
+    /// Renew once the certificate expires within this long
+    pub renew_before: Duration,
+}
+
+impl AcmeConfig {
+    pub fn enabled(&self) -> bool {
+        !self.domains.is_empty()
+    }
+}
+
+impl Default for AcmeConfig {
+    fn default() -> Self {
+        Self {
+            domains: Vec::new(),
+            contact: None,
+            directory_url: LETS_ENCRYPT_DIRECTORY.to_string(),
+            challenge: AcmeChallenge::default(),
+            http_port: 80,
+            renew_before: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// HTTPS from the operator's certificate, or a self-signed one the node
+/// makes. Off unless one is configured.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first
+    pub cert_path: Option<PathBuf>,
+    /// PEM private key of the certificate
+    pub key_path: Option<PathBuf>,
+    /// Serve a self-signed certificate kept in the config directory
+    pub self_signed: bool,
+}
+
+impl TlsConfig {
+    pub fn enabled(&self) -> bool {
+        self.cert_path.is_some() || self.self_signed
+    }
+
+    /// Check the settings make sense on their own and next to `acme`
+    pub fn validate(&self, acme: &AcmeConfig) -> Result<(), AppError> {
+        if self.cert_path.is_some() != self.key_path.is_some() {
+            return Err(AppError::Config(
+                "FLOW_TLS_CERT and FLOW_TLS_KEY must be set together".to_string(),
+            ));
+        }
+        if self.cert_path.is_some() && self.self_signed {
+            return Err(AppError::Config(
+                "FLOW_TLS_SELF_SIGNED can't be combined with FLOW_TLS_CERT".to_string(),
+            ));
+        }
+        if self.enabled() && acme.enabled() {
+            return Err(AppError::Config(
+                "Certificates come from ACME or FLOW_TLS_*, not both".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Running behind a reverse proxy
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    /// Peers whose `X-Forwarded-For` and `X-Forwarded-Proto` are believed
+    pub trusted_proxies: Vec<IpNet>,
+    /// Prefix the proxy serves the node under, e.g. `/flow`; empty for none
+    pub base_path: String,
+    /// Origin browsers reach the node at, e.g. `https://flow.example.com`
+    pub external_origin: Option<String>,
+    /// Header a trusted proxy puts the client's country in, e.g.
+    /// `CF-IPCountry`
+    pub country_header: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn trusts(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+
+    /// `path` as clients see it, under the base path
+    pub fn public_path(&self, path: &str) -> String {
+        format!("{}{}", self.base_path, path)
+    }
+
+    /// Normalise a base path to `/prefix`, or empty for the root
+    pub fn parse_base_path(value: &str) -> Result<String, AppError> {
+        let trimmed = value.trim().trim_matches('/');
+        if trimmed.is_empty() {
+            return Ok(String::new());
+        }
+        let valid = trimmed.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+        });
+        if !valid {
+            return Err(AppError::Config(format!("Invalid base path: {}", value)));
+        }
+        Ok(format!("/{}", trimmed))
+    }
+
+    /// An `http(s)://host[:port]` origin, without path
+    pub fn parse_origin(value: &str) -> Result<String, AppError> {
+        let invalid = || AppError::Config(format!("Invalid external origin: {}", value));
+        let url = url::Url::parse(value.trim()).map_err(|_| invalid())?;
+        if !matches!(url.scheme(), "http" | "https")
+            || url.host().is_none()
+            || url.path() != "/"
+            || url.query().is_some()
+        {
+            return Err(invalid());
+        }
+        Ok(url.origin().ascii_serialization())
+    }
+}
+
+/// RFC 3161 timestamps for anchors. Off unless an authority is set.
+#[derive(Debug, Clone)]
+pub struct TimestampConfig {
+    /// URL of the timestamp authority (TSA)
+    pub authority_url: Option<String>,
+    /// SHA-256 of the TSA certificates timestamps are trusted from; needed
+    /// with an authority, and to verify timestamps at all
+    pub trusted_certificates: Vec<[u8; 32]>,
+    pub timeout: Duration,
+}
+
+impl Default for TimestampConfig {
+    fn default() -> Self {
+        Self {
+            authority_url: None,
+            trusted_certificates: Vec::new(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// How much clocks may disagree, and where the node checks its own
+#[derive(Debug, Clone)]
+pub struct ClockConfig {
+    /// Leeway given to expiry and validity times set by another clock
+    pub max_skew: Duration,
+    /// NTP server (`host:port`) asked at startup; `None` skips the check
+    pub ntp_server: Option<String>,
+    /// How old a signed peer request may be; its nonce is kept this long
+    pub replay_window: Duration,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            max_skew: Duration::from_secs(60),
+            ntp_server: Some(DEFAULT_NTP_SERVER.to_string()),
+            replay_window: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// Anomaly rules for key usage
+#[derive(Debug, Clone)]
+pub struct KeyUsageConfig {
+    /// A key unused for this long is dormant
+    pub dormant_after: Duration,
+    /// Uses from two countries closer together than this are impossible
+    /// travel
+    pub travel_window: Duration,
+    /// Close sessions that show an anomaly, so the user signs in again
+    pub step_up: bool,
+}
+
+impl Default for KeyUsageConfig {
+    fn default() -> Self {
+        Self {
+            dormant_after: Duration::from_secs(90 * 24 * 60 * 60),
+            travel_window: Duration::from_secs(2 * 60 * 60),
+            step_up: false,
+        }
+    }
+}
+
+/// How long records that only matter for a while are kept; `None` keeps
+/// them for good
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    /// Runs of scheduled tasks
+    pub task_runs: Option<Duration>,
+    /// Key uses, and the stats of keys unused for as long
+    pub key_usage: Option<Duration>,
+    /// Entries of the KV-backed DID resolution cache
+    pub did_cache: Option<Duration>,
+    /// Recorded changes to users and spaces
+    pub history: Option<Duration>,
+    /// Time between purges
+    pub purge_interval: Duration,
+    /// How long a deleted account waits before it is erased, to be exported
+    /// or restored; zero erases it at once
+    pub account_deletion_grace: Duration,
+    /// How long a deleted space or passkey can be restored before a purge
+    /// removes it for good
+    pub restore_window: Duration,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        const DAY: u64 = 24 * 60 * 60;
+        Self {
+            task_runs: Some(Duration::from_secs(90 * DAY)),
+            key_usage: Some(Duration::from_secs(365 * DAY)),
+            did_cache: Some(Duration::from_secs(30 * DAY)),
+            history: Some(Duration::from_secs(365 * DAY)),
+            purge_interval: Duration::from_secs(DAY),
+            account_deletion_grace: Duration::from_secs(30 * DAY),
+            restore_window: Duration::from_secs(30 * DAY),
+        }
+    }
+}
+
+/// Soft resource limits, checked every `check_interval`; going over one logs
+/// a warning. `None` is unlimited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceConfig {
+    /// Resident memory of the process
+    pub memory_bytes: Option<u64>,
+    pub open_files: Option<u64>,
+    /// KV store size on disk
+    pub kv_bytes: Option<u64>,
+    pub db_bytes: Option<u64>,
+    /// Tasks running across subsystems
+    pub tasks: Option<u64>,
+    pub check_interval: Duration,
+}
+
+impl Default for ResourceConfig {
+    fn default() -> Self {
+        Self {
+            memory_bytes: None,
+            open_files: None,
+            kv_bytes: None,
+            db_bytes: None,
+            tasks: None,
+            check_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ResourceConfig {
+    pub fn any_limit(&self) -> bool {
+        [
+            self.memory_bytes,
+            self.open_files,
+            self.kv_bytes,
+            self.db_bytes,
+            self.tasks,
+        ]
+        .iter()
+        .any(Option::is_some)
+    }
+}
+
+/// Per-user isolation of spaces and KV data
+#[derive(Debug, Clone, Default)]
+pub struct MultiUserConfig {
+    pub enabled: bool,
+    /// Spaces each user may create
+    pub max_spaces: Option<u64>,
+    /// Bytes of KV data (keys and values) each user may store
+    pub kv_quota_bytes: Option<u64>,
+    /// Directory users' spaces go in; a user can make spaces nowhere else.
+    /// `None` leaves users without spaces.
+    pub spaces_root: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -31,10 +768,53 @@ pub struct Config {
     pub db: DbConfig,
     pub kv: KvConfig,
     pub server: ServerConfig,
+    pub update: UpdateConfig,
+    pub telemetry: TelemetryConfig,
+    pub maintenance: MaintenanceConfig,
+    pub multi_user: MultiUserConfig,
+    pub limits: BodyLimits,
+    pub request_limits: RequestLimitsConfig,
+    pub compression: CompressionConfig,
+    pub security_headers: SecurityHeadersConfig,
+    pub did_cache: DidCacheConfig,
+    pub did_batch: DidBatchConfig,
+    pub acme: AcmeConfig,
+    pub tls: TlsConfig,
+    pub proxy: ProxyConfig,
+    pub timestamping: TimestampConfig,
+    pub clock: ClockConfig,
+    pub key_usage: KeyUsageConfig,
+    pub retention: RetentionConfig,
+    pub resources: ResourceConfig,
+    /// Where scheduled exports and backups are written, relative to the
+    /// config dir
+    pub export_dir: PathBuf,
+    pub key_storage: KeyStorageBackend,
+    /// Public node: published resources are open to unauthenticated guests,
+    /// everything else needs a session
+    pub guest_mode: bool,
+    /// Nothing is persisted: in-memory database and KV store, throwaway identity
+    pub ephemeral: bool,
+    /// Smaller caches, pools and buffers; see [`low_resource`]
+    pub low_resource: bool,
 }
 
 impl Config {
+    /// Whether the REST and WebSocket servers serve HTTPS themselves
+    pub fn serves_https(&self) -> bool {
+        self.acme.enabled() || self.tls.enabled()
+    }
+
     pub fn from_env() -> Result<Self, AppError> {
+        Self::load(false)
+    }
+
+    /// Load configuration for `--ephemeral` mode. DATABASE_URL is ignored.
+    pub fn ephemeral_from_env() -> Result<Self, AppError> {
+        Self::load(true)
+    }
+
+    fn load(ephemeral: bool) -> Result<Self, AppError> {
         dotenv().ok();
 
         // Helper to parse a u64
@@ -59,24 +839,376 @@ impl Config {
 
         // --- Parse all variables ---
 
+        let ephemeral = ephemeral || get_env_bool("FLOW_EPHEMERAL", false)?;
+
+        // Stores default to locations under FLOW_DATA_DIR when it is set
+        let data_layout = env::var("FLOW_DATA_DIR")
+            .ok()
+            .map(|dir| DataLayout::under(Path::new(&dir)));
+
         // DbConfig
-        let database_url = env::var("DATABASE_URL")
-            .map_err(|_| AppError::Config("DATABASE_URL must be set".to_string()))?;
+        let database_url = if ephemeral {
+            IN_MEMORY_DATABASE_URL.to_string()
+        } else {
+            env::var("DATABASE_URL")
+                .ok()
+                .or_else(|| data_layout.as_ref().and_then(|l| l.database_url()))
+                .ok_or_else(|| {
+                    AppError::Config("DATABASE_URL or FLOW_DATA_DIR must be set".to_string())
+                })?
+        };
 
-        let max_connections = get_env_u64("DB_MAX_CONNECTIONS", 100)? as u32;
-        let min_connections = get_env_u64("DB_MIN_CONNECTIONS", 5)? as u32;
+        let low_resource_mode = get_env_bool("FLOW_LOW_RESOURCE", false)?;
+        // The default for a setting, or its low-resource default
+        let sized = |default: u64, low: u64| if low_resource_mode { low } else { default };
+
+        let max_connections = get_env_u64(
+            "DB_MAX_CONNECTIONS",
+            sized(100, low_resource::DB_MAX_CONNECTIONS),
+        )? as u32;
+        let min_connections = get_env_u64(
+            "DB_MIN_CONNECTIONS",
+            sized(5, low_resource::DB_MIN_CONNECTIONS),
+        )? as u32;
         let connect_timeout_secs = get_env_u64("DB_CONNECT_TIMEOUT", 8)?;
         let idle_timeout_secs = get_env_u64("DB_IDLE_TIMEOUT", 600)?;
         let max_lifetime_secs = get_env_u64("DB_MAX_LIFETIME", 1800)?;
         let logging_enabled = get_env_bool("DB_LOGGING_ENABLED", false)?; // <-- Parse the new variable
 
         // KvConfig
-        let kv_path = env::var("KV_STORE_PATH").unwrap_or("/tmp/flow-kv".to_string());
+        let kv_path = env::var("KV_STORE_PATH")
+            .ok()
+            .or_else(|| {
+                data_layout
+                    .as_ref()
+                    .and_then(|l| l.kv.as_ref())
+                    .map(|kv| kv.to_string_lossy().into_owned())
+            })
+            .unwrap_or("/tmp/flow-kv".to_string());
+        let kv_in_memory =
+            ephemeral || kv_path == ":memory:" || get_env_bool("KV_IN_MEMORY", false)?;
+        let kv_cache_bytes = env::var("KV_CACHE_MB")
+            .ok()
+            .map(|s| s.parse::<u64>())
+            .transpose()
+            .map_err(|_| AppError::Config("Invalid value for KV_CACHE_MB".to_string()))?
+            .or(low_resource_mode.then_some(low_resource::KV_CACHE_MB))
+            .map(|mb| mb * 1024 * 1024);
 
         // ServerConfig
         let rest_port = get_env_u64("REST_PORT", 8080)? as u16;
         let websocket_port = get_env_u64("WEBSOCKET_PORT", 8081)? as u16;
         let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
+        let bind_addresses = env::var("FLOW_BIND_ADDRESSES")
+            .unwrap_or_else(|_| host.clone())
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.trim_matches(['[', ']'])
+                    .parse::<IpAddr>()
+                    .map_err(|_| AppError::Config(format!("Invalid bind address: {}", s)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let advertised_addresses = env::var("FLOW_ADVERTISED_ADDRESSES")
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let allowed_origins = env::var("CORS_ORIGINS")
+            .map(|s| {
+                s.split(',')
+                    .map(|origin| origin.trim().trim_end_matches('/').to_ascii_lowercase())
+                    .filter(|origin| !origin.is_empty())
+                    .collect()
+            })
+            .unwrap_or_else(|_| {
+                cors::DEFAULT_ORIGINS
+                    .iter()
+                    .map(|o| o.to_string())
+                    .collect()
+            });
+
+        // UpdateConfig (never in ephemeral mode)
+        let update_enabled = !ephemeral && get_env_bool("FLOW_UPDATE_ENABLED", false)?;
+        let update_interval_secs = get_env_u64("FLOW_UPDATE_INTERVAL", 60 * 60 * 24)?;
+
+        let telemetry = TelemetryConfig {
+            enabled: !ephemeral && get_env_bool("FLOW_TELEMETRY_ENABLED", false)?,
+            endpoint: env::var("FLOW_TELEMETRY_URL").ok(),
+            submit_interval: Duration::from_secs(get_env_u64(
+                "FLOW_TELEMETRY_INTERVAL",
+                60 * 60 * 24 * 7,
+            )?),
+            epsilon: match env::var("FLOW_TELEMETRY_EPSILON") {
+                Ok(value) => value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|epsilon| epsilon.is_finite() && *epsilon > 0.0)
+                    .ok_or_else(|| {
+                        AppError::Config("FLOW_TELEMETRY_EPSILON must be above 0".to_string())
+                    })?,
+                Err(_) => TelemetryConfig::default().epsilon,
+            },
+        };
+        telemetry.validate()?;
+
+        let maintenance = MaintenanceConfig {
+            enabled: get_env_bool("FLOW_MAINTENANCE", false)?,
+            retry_after: Duration::from_secs(get_env_u64(
+                "FLOW_MAINTENANCE_RETRY_AFTER",
+                MaintenanceConfig::default().retry_after.as_secs(),
+            )?),
+        };
+
+        // MultiUserConfig
+        let get_env_limit = |key: &str| -> Result<Option<u64>, AppError> {
+            env::var(key)
+                .ok()
+                .map(|s| s.parse::<u64>())
+                .transpose()
+                .map_err(|_| AppError::Config(format!("Invalid value for {}", key)))
+        };
+        // Days to keep a kind of record; 0 keeps it for good
+        let get_env_retention = |key: &str, default: u64| -> Result<Option<Duration>, AppError> {
+            let days = get_env_u64(key, default)?;
+            Ok((days > 0).then(|| Duration::from_secs(days * 24 * 60 * 60)))
+        };
+        // As `openssl x509 -fingerprint -sha256` prints them, or plain hex
+        let trusted_certificates = env::var("FLOW_TSA_CERT_SHA256")
+            .unwrap_or_default()
+            .split(',')
+            .map(|fingerprint| fingerprint.trim().replace(':', ""))
+            .filter(|fingerprint| !fingerprint.is_empty())
+            .map(|fingerprint| {
+                timestamp::parse_digest(&fingerprint).map_err(|_| {
+                    AppError::Config(
+                        "FLOW_TSA_CERT_SHA256 must list SHA-256 certificate fingerprints"
+                            .to_string(),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if env::var("FLOW_TSA_URL").is_ok() && trusted_certificates.is_empty() {
+            return Err(AppError::Config(
+                "FLOW_TSA_CERT_SHA256 must pin the certificate of FLOW_TSA_URL".to_string(),
+            ));
+        }
+
+        let export_dir = PathBuf::from(
+            env::var("FLOW_EXPORT_DIR").unwrap_or_else(|_| scheduler::EXPORT_DIR.to_string()),
+        );
+        if export_dir.as_os_str().is_empty() || !scheduler::is_plain_relative(&export_dir) {
+            return Err(AppError::Config(
+                "FLOW_EXPORT_DIR must be a directory within the config dir".to_string(),
+            ));
+        }
+
+        let retention = RetentionConfig {
+            task_runs: get_env_retention("FLOW_RETAIN_TASK_RUNS_DAYS", 90)?,
+            key_usage: get_env_retention("FLOW_RETAIN_KEY_USAGE_DAYS", 365)?,
+            did_cache: get_env_retention("FLOW_RETAIN_DID_CACHE_DAYS", 30)?,
+            history: get_env_retention("FLOW_RETAIN_HISTORY_DAYS", 365)?,
+            purge_interval: Duration::from_secs(
+                get_env_u64("FLOW_PURGE_INTERVAL_SECS", 24 * 60 * 60)?.max(60),
+            ),
+            account_deletion_grace: Duration::from_secs(
+                get_env_u64("FLOW_ACCOUNT_DELETION_GRACE_DAYS", 30)? * 24 * 60 * 60,
+            ),
+            restore_window: Duration::from_secs(
+                get_env_u64("FLOW_RESTORE_WINDOW_DAYS", 30)? * 24 * 60 * 60,
+            ),
+        };
+
+        const MIB: u64 = 1024 * 1024;
+        let resources = ResourceConfig {
+            memory_bytes: get_env_limit("FLOW_SOFT_LIMIT_MEMORY_MB")?
+                .or(low_resource_mode.then_some(low_resource::SOFT_LIMIT_MEMORY_MB))
+                .map(|mb| mb * MIB),
+            open_files: get_env_limit("FLOW_SOFT_LIMIT_OPEN_FILES")?,
+            kv_bytes: get_env_limit("FLOW_SOFT_LIMIT_KV_MB")?.map(|mb| mb * MIB),
+            db_bytes: get_env_limit("FLOW_SOFT_LIMIT_DB_MB")?.map(|mb| mb * MIB),
+            tasks: get_env_limit("FLOW_SOFT_LIMIT_TASKS")?,
+            check_interval: Duration::from_secs(
+                get_env_u64("FLOW_RESOURCE_CHECK_SECS", 60)?.max(1),
+            ),
+        };
+
+        let multi_user = MultiUserConfig {
+            enabled: get_env_bool("FLOW_MULTI_USER", false)?,
+            max_spaces: get_env_limit("FLOW_USER_MAX_SPACES")?,
+            kv_quota_bytes: get_env_limit("FLOW_USER_KV_QUOTA_BYTES")?,
+            spaces_root: env::var("FLOW_SPACES_DIR")
+                .ok()
+                .map(PathBuf::from)
+                .or_else(|| data_layout.as_ref().and_then(|l| l.spaces.clone())),
+        };
+
+        // BodyLimits
+        let default_limits = BodyLimits::default();
+        let limits = BodyLimits {
+            max_body_bytes: get_env_u64(
+                "FLOW_MAX_BODY_BYTES",
+                default_limits.max_body_bytes as u64,
+            )? as usize,
+            max_upload_bytes: get_env_u64(
+                "FLOW_MAX_UPLOAD_BYTES",
+                default_limits.max_upload_bytes,
+            )?,
+            spill_threshold_bytes: get_env_u64(
+                "FLOW_BODY_SPILL_BYTES",
+                sized(
+                    default_limits.spill_threshold_bytes as u64,
+                    low_resource::BODY_SPILL_BYTES,
+                ),
+            )? as usize,
+        };
+
+        // RequestLimitsConfig
+        let default_request_limits = RequestLimitsConfig::default();
+        let get_env_concurrency = |key: &str, default: u64| -> Result<usize, AppError> {
+            match get_env_u64(key, default)? {
+                0 => Err(AppError::Config(format!("{} must be at least 1", key))),
+                n => Ok(n as usize),
+            }
+        };
+        let request_limits = RequestLimitsConfig {
+            timeout: Duration::from_secs(get_env_u64(
+                "FLOW_REQUEST_TIMEOUT_SECS",
+                default_request_limits.timeout.as_secs(),
+            )?),
+            expensive_timeout: Duration::from_secs(get_env_u64(
+                "FLOW_EXPENSIVE_REQUEST_TIMEOUT_SECS",
+                default_request_limits.expensive_timeout.as_secs(),
+            )?),
+            max_concurrent: get_env_concurrency(
+                "FLOW_MAX_CONCURRENT_REQUESTS",
+                sized(
+                    default_request_limits.max_concurrent as u64,
+                    low_resource::MAX_CONCURRENT_REQUESTS,
+                ),
+            )?,
+            max_concurrent_expensive: get_env_concurrency(
+                "FLOW_MAX_CONCURRENT_EXPENSIVE",
+                sized(
+                    default_request_limits.max_concurrent_expensive as u64,
+                    low_resource::MAX_CONCURRENT_EXPENSIVE,
+                ),
+            )?,
+        };
+
+        // CompressionConfig
+        let min_size_bytes = get_env_u64(
+            "FLOW_COMPRESSION_MIN_BYTES",
+            CompressionConfig::default().min_size_bytes as u64,
+        )?;
+        let compression = CompressionConfig {
+            enabled: get_env_bool("FLOW_COMPRESSION_ENABLED", true)?,
+            min_size_bytes: u16::try_from(min_size_bytes).map_err(|_| {
+                AppError::Config("Invalid value for FLOW_COMPRESSION_MIN_BYTES".to_string())
+            })?,
+        };
+
+        // SecurityHeadersConfig
+        let security_headers = SecurityHeadersConfig {
+            enabled: get_env_bool("FLOW_SECURITY_HEADERS", true)?,
+            hsts: get_env_bool("FLOW_HSTS", true)?,
+            hsts_max_age_secs: get_env_u64(
+                "FLOW_HSTS_MAX_AGE_SECS",
+                SecurityHeadersConfig::default().hsts_max_age_secs,
+            )?,
+            csp: get_env_bool("FLOW_CSP", true)?,
+        };
+
+        // DidCacheConfig
+        let did_cache = DidCacheConfig {
+            backend: match env::var("FLOW_DID_CACHE") {
+                Ok(value) => value.parse()?,
+                Err(_) => DidCacheBackend::default(),
+            },
+            capacity: get_env_u64(
+                "FLOW_DID_CACHE_CAPACITY",
+                sized(DEFAULT_CAPACITY as u64, low_resource::DID_CACHE_CAPACITY),
+            )? as usize,
+        };
+
+        // DidBatchConfig
+        let did_batch = DidBatchConfig {
+            max_dids: get_env_u64(
+                "FLOW_DID_BATCH_MAX",
+                DidBatchConfig::default().max_dids as u64,
+            )? as usize,
+            parallelism: get_env_u64("FLOW_DID_BATCH_PARALLELISM", DEFAULT_PARALLELISM as u64)?
+                as usize,
+        };
+
+        // AcmeConfig
+        let default_acme = AcmeConfig::default();
+        let acme = AcmeConfig {
+            domains: env::var("FLOW_ACME_DOMAINS")
+                .map(|s| {
+                    s.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            contact: env::var("FLOW_ACME_CONTACT").ok(),
+            directory_url: env::var("FLOW_ACME_DIRECTORY").unwrap_or(default_acme.directory_url),
+            challenge: match env::var("FLOW_ACME_CHALLENGE") {
+                Ok(value) => value.parse()?,
+                Err(_) => default_acme.challenge,
+            },
+            http_port: get_env_u64("FLOW_ACME_HTTP_PORT", default_acme.http_port as u64)? as u16,
+            renew_before: Duration::from_secs(
+                get_env_u64("FLOW_ACME_RENEW_DAYS", 30)? * 24 * 60 * 60,
+            ),
+        };
+
+        // TlsConfig
+        let tls_path = |key: &str| {
+            env::var(key)
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(PathBuf::from)
+        };
+        let tls = TlsConfig {
+            cert_path: tls_path("FLOW_TLS_CERT"),
+            key_path: tls_path("FLOW_TLS_KEY"),
+            self_signed: get_env_bool("FLOW_TLS_SELF_SIGNED", false)?,
+        };
+        tls.validate(&acme)?;
+
+        // ProxyConfig
+        let proxy = ProxyConfig {
+            trusted_proxies: env::var("FLOW_TRUSTED_PROXIES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    s.parse::<IpNet>()
+                        .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                        .map_err(|_| AppError::Config(format!("Invalid trusted proxy: {}", s)))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            base_path: ProxyConfig::parse_base_path(
+                &env::var("FLOW_BASE_PATH").unwrap_or_default(),
+            )?,
+            external_origin: env::var("FLOW_EXTERNAL_ORIGIN")
+                .ok()
+                .map(|s| ProxyConfig::parse_origin(&s))
+                .transpose()?,
+            country_header: env::var("FLOW_COUNTRY_HEADER")
+                .ok()
+                .filter(|s| !s.trim().is_empty()),
+        };
 
         Ok(Self {
             db: DbConfig {
@@ -88,12 +1220,84 @@ impl Config {
                 max_lifetime: Duration::from_secs(max_lifetime_secs),
                 logging_enabled,
             },
-            kv: KvConfig { path: kv_path },
+            kv: KvConfig {
+                path: kv_path,
+                in_memory: kv_in_memory,
+                cache_bytes: kv_cache_bytes,
+                low_space: low_resource_mode,
+            },
             server: ServerConfig {
                 rest_port,
                 websocket_port,
+                grpc_port: env::var("GRPC_PORT")
+                    .ok()
+                    .map(|s| s.parse::<u16>())
+                    .transpose()
+                    .map_err(|_| AppError::Config("Invalid value for GRPC_PORT".to_string()))?,
+                p2p_port: env::var("FLOW_P2P_PORT")
+                    .ok()
+                    .map(|s| s.parse::<u16>())
+                    .transpose()
+                    .map_err(|_| AppError::Config("Invalid value for FLOW_P2P_PORT".to_string()))?,
                 host,
+                bind_addresses,
+                advertised_addresses,
+                allowed_origins,
+            },
+            update: UpdateConfig {
+                enabled: update_enabled,
+                endpoint: env::var("FLOW_UPDATE_URL").ok(),
+                publisher_key: env::var("FLOW_UPDATE_PUBLIC_KEY").ok(),
+                check_interval: Duration::from_secs(update_interval_secs),
+            },
+            telemetry,
+            maintenance,
+            multi_user,
+            limits,
+            request_limits,
+            compression,
+            security_headers,
+            did_cache,
+            did_batch,
+            acme,
+            tls,
+            proxy,
+            key_usage: KeyUsageConfig {
+                dormant_after: Duration::from_secs(
+                    get_env_u64("FLOW_KEY_DORMANT_DAYS", 90)? * 24 * 60 * 60,
+                ),
+                travel_window: Duration::from_secs(
+                    get_env_u64("FLOW_KEY_TRAVEL_WINDOW_MINS", 120)? * 60,
+                ),
+                step_up: get_env_bool("FLOW_KEY_STEP_UP", false)?,
+            },
+            retention,
+            resources,
+            export_dir,
+            timestamping: TimestampConfig {
+                authority_url: env::var("FLOW_TSA_URL").ok(),
+                trusted_certificates,
+                timeout: Duration::from_secs(get_env_u64("FLOW_TSA_TIMEOUT_SECS", 30)?),
+            },
+            clock: ClockConfig {
+                max_skew: Duration::from_secs(get_env_u64("FLOW_CLOCK_SKEW_SECS", 60)?),
+                // Empty turns the startup check off
+                ntp_server: match env::var("FLOW_NTP_SERVER") {
+                    Ok(server) => Some(server.trim().to_string()).filter(|s| !s.is_empty()),
+                    Err(_) => Some(DEFAULT_NTP_SERVER.to_string()),
+                },
+                replay_window: Duration::from_secs(get_env_u64(
+                    "FLOW_PEER_REPLAY_WINDOW_SECS",
+                    5 * 60,
+                )?),
+            },
+            key_storage: match env::var("FLOW_KEY_STORAGE") {
+                Ok(value) => value.parse()?,
+                Err(_) => KeyStorageBackend::default(),
             },
+            guest_mode: get_env_bool("FLOW_GUEST_MODE", false)?,
+            ephemeral,
+            low_resource: low_resource_mode,
         })
     }
 }
@@ -0,0 +1,131 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use errors::AppError;
+use log::info;
+
+pub const LOCK_FILE: &str = "node.lock";
+pub const PID_FILE: &str = "node.pid";
+pub const LOG_FILE: &str = "logs/node.log";
+
+/// Exclusive lock on a config directory, held for the life of the process.
+///
+/// Only one node may run per config directory. The PID file is written once
+/// the lock is acquired and removed again on drop.
+pub struct InstanceLock {
+    _file: File,
+    pid_path: PathBuf,
+}
+
+impl InstanceLock {
+    pub fn acquire(config_dir: &Path) -> Result<Self, AppError> {
+        fs::create_dir_all(config_dir)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(config_dir.join(LOCK_FILE))?;
+
+        if !fs4::fs_std::FileExt::try_lock_exclusive(&file)? {
+            let pid = read_pid(config_dir)
+                .map(|pid| format!(" (PID {})", pid))
+                .unwrap_or_default();
+            return Err(AppError::Bootstrap(format!(
+                "Another node is already running with config dir {}{}",
+                config_dir.display(),
+                pid
+            )));
+        }
+
+        let pid_path = config_dir.join(PID_FILE);
+        let mut pid_file = File::create(&pid_path)?;
+        writeln!(pid_file, "{}", std::process::id())?;
+
+        info!(
+            "Acquired instance lock for {} (PID {})",
+            config_dir.display(),
+            std::process::id()
+        );
+
+        Ok(Self {
+            _file: file,
+            pid_path,
+        })
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.pid_path);
+    }
+}
+
+/// Whether a node currently holds the lock for `config_dir`
+pub fn is_running(config_dir: &Path) -> Result<bool, AppError> {
+    let lock_path = config_dir.join(LOCK_FILE);
+    if !lock_path.exists() {
+        return Ok(false);
+    }
+
+    let file = OpenOptions::new().write(true).open(lock_path)?;
+    if fs4::fs_std::FileExt::try_lock_exclusive(&file)? {
+        fs4::fs_std::FileExt::unlock(&file)?;
+        Ok(false)
+    } else {
+        Ok(true)
+    }
+}
+
+/// PID recorded by the running instance, if any
+pub fn read_pid(config_dir: &Path) -> Option<u32> {
+    fs::read_to_string(config_dir.join(PID_FILE))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+}
+
+/// Re-launch the current executable detached from the terminal, with stdout
+/// and stderr appended to the log file in `config_dir`. Returns the child PID.
+pub fn spawn_daemon(config_dir: &Path, args: &[String]) -> Result<u32, AppError> {
+    if is_running(config_dir)? {
+        return Err(AppError::Bootstrap(format!(
+            "Another node is already running with config dir {}",
+            config_dir.display()
+        )));
+    }
+
+    let log_path = config_dir.join(LOG_FILE);
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(args.iter().filter(|a| a.as_str() != "--daemon"))
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // New process group so terminal signals (Ctrl+C, hangup) don't reach it
+        command.process_group(0);
+    }
+
+    let child = command.spawn()?;
+
+    info!(
+        "Node daemon started with PID {}, logging to {}",
+        child.id(),
+        log_path.display()
+    );
+
+    Ok(child.id())
+}
@@ -6,6 +6,7 @@ use errors::AppError;
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use tempfile::NamedTempFile;
+use zeroize::Zeroizing;
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
@@ -13,10 +14,27 @@ use std::os::unix::fs::PermissionsExt;
 use ed25519_dalek::SigningKey;
 use multibase::Base;
 
+use super::config::KeyStorageBackend;
+use super::keystore;
+use crate::modules::key_rotation::{RotationStatement, SignedRotation};
+
+/// Directory under the config dir holding node key material
+pub const KEYSTORE_DIR: &str = "keystore";
+
+/// Directory under the config dir for scratch files, e.g. spilled uploads
+pub const SCRATCH_DIR: &str = "tmp";
+
+/// Name of the identity key in key storage
+pub const IDENTITY_KEY: &str = "ed25519";
+
+/// Name of a rotated-in identity key until it replaces [`IDENTITY_KEY`]
+pub const PENDING_KEY: &str = "ed25519.next";
+
 #[derive(Clone)]
 pub struct NodeData {
     pub id: String,
-    pub private_key: Vec<u8>,
+    /// Ed25519 seed; wiped from memory when dropped
+    pub private_key: Zeroizing<Vec<u8>>,
     pub public_key: Vec<u8>,
 }
 
@@ -26,13 +44,29 @@ pub struct AuthMetadata {
     pub did: String,
     pub created_at: String,
     pub pub_key_multibase: String,
+    /// Identity keys rotated out, oldest first
+    #[serde(default)]
+    pub key_history: Vec<RetiredKey>,
+    /// Where the private keys are kept
+    #[serde(default)]
+    pub key_storage: KeyStorageBackend,
+}
+
+/// An identity key rotated out, archived in the keystore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetiredKey {
+    /// The node's first key is version 1
+    pub version: u32,
+    pub did: String,
+    pub pub_key_multibase: String,
+    /// Hand-over to the key that replaced it
+    pub rotation: SignedRotation,
 }
 
 struct Paths {
     config_dir: PathBuf,
     keystore_dir: PathBuf,
     auth_file: PathBuf,
-    priv_key_file: PathBuf,
     pub_key_file: PathBuf,
 }
 
@@ -57,11 +91,73 @@ pub fn get_flow_config_dir() -> String {
     "flow".to_string()
 }
 
+/// Load the node in config dir `dir`, or create it with its keys in file
+/// storage. An existing node keeps its keys where they are.
 pub fn initialize_config_dir(dir: &str) -> Result<NodeData, AppError> {
+    init(dir, None)
+}
+
+/// Like [`initialize_config_dir`], but with the private keys in `backend`.
+/// An existing node whose keys are elsewhere has them moved to `backend`
+/// and removed from where they were.
+pub fn initialize_with(dir: &str, backend: KeyStorageBackend) -> Result<NodeData, AppError> {
+    init(dir, Some(backend))
+}
+
+fn init(dir: &str, backend: Option<KeyStorageBackend>) -> Result<NodeData, AppError> {
     let p = paths(dir);
     let _created = create_directory(&p.config_dir)
         .map_err(|e| AppError::Bootstrap(format!("Failed to create directory. {}", e)))?;
 
+    let file = lock(&p)?;
+
+    let result = match p.auth_file.exists() {
+        true => finish_rotation(&p)
+            .and_then(|()| move_keys(&p, backend))
+            .map_err(Box::from)
+            .and_then(|()| load_existing(&p))
+            .map_err(|e| {
+                AppError::Bootstrap(format!(
+                    "Error while loading existing configurations. {}",
+                    e
+                ))
+            }),
+        false => bootstrap_new(&p, backend.unwrap_or_default()),
+    };
+    fs4::fs_std::FileExt::unlock(&file)?;
+    result
+}
+
+/// Replace the identity key in config dir `dir` with a fresh one. The old
+/// keypair is archived as `ed25519.v<version>`, the private key in key
+/// storage and the public key in the keystore, and recorded, with the
+/// signed rotation statement, in the key history of auth.json.
+pub fn rotate_identity_key(dir: &str) -> Result<(NodeData, SignedRotation), AppError> {
+    let p = paths(dir);
+    let file = lock(&p)?;
+    let result = rotate(&p);
+    fs4::fs_std::FileExt::unlock(&file)?;
+    result
+}
+
+/// Check the identity key can still be read from the key storage
+/// `auth.json` names
+pub fn check_keystore(dir: &str) -> Result<(), AppError> {
+    let p = paths(dir);
+    let meta = read_metadata(&p)?;
+    keystore::open(meta.key_storage, &p.config_dir)?
+        .load(IDENTITY_KEY)?
+        .map(|_| ())
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Private key missing from {:?} key storage",
+                meta.key_storage
+            ))
+        })
+}
+
+/// Serializes initialization and rotation across processes
+fn lock(p: &Paths) -> Result<fs::File, AppError> {
     let lock_file = p.config_dir.join(".init.lock");
     let file = fs::OpenOptions::new()
         .create(true)
@@ -69,41 +165,27 @@ pub fn initialize_config_dir(dir: &str) -> Result<NodeData, AppError> {
         .open(&lock_file)?;
 
     fs4::fs_std::FileExt::lock_exclusive(&file)?; // Block until we get the lock
-
-    if p.auth_file.exists() {
-        fs4::fs_std::FileExt::unlock(&file)?;
-        return load_existing(&p).map_err(|e| {
-            AppError::Bootstrap(format!(
-                "Error while loading existing configurations. {}",
-                e
-            ))
-        });
-    }
-
-    let result = bootstrap_new(&p);
-    fs4::fs_std::FileExt::unlock(&file)?;
-    result
+    Ok(file)
 }
 
 fn paths(dir: &str) -> Paths {
     let config_dir = PathBuf::from(dir);
-    let keystore_dir = config_dir.join("keystore");
+    let keystore_dir = config_dir.join(KEYSTORE_DIR);
 
     Paths {
         config_dir: config_dir.clone(),
         keystore_dir: keystore_dir.clone(),
         auth_file: config_dir.join("auth.json"),
-        priv_key_file: keystore_dir.join("ed25519.priv"),
         pub_key_file: keystore_dir.join("ed25519.pub"),
     }
 }
 
-fn generate_keys_and_did() -> (Vec<u8>, Vec<u8>, String, String) {
+fn generate_keys_and_did() -> (Zeroizing<Vec<u8>>, Vec<u8>, String, String) {
     let mut rng = OsRng;
     let sk = SigningKey::generate(&mut rng);
     let vk = sk.verifying_key();
 
-    let priv_key_bytes = sk.to_bytes().to_vec(); // 32 bytes
+    let priv_key_bytes = Zeroizing::new(sk.as_bytes().to_vec()); // 32 bytes
     let pub_key_bytes = vk.to_bytes().to_vec(); // 32 bytes
 
     // multicodec prefix for ed25519-pub: 0xED 0x01
@@ -117,6 +199,23 @@ fn generate_keys_and_did() -> (Vec<u8>, Vec<u8>, String, String) {
     (priv_key_bytes, pub_key_bytes, pub_key_multibase, did)
 }
 
+/// Public key and `did:key` of the Ed25519 seed `private_key`
+fn keys_of(private_key: &[u8]) -> Result<(Vec<u8>, String), AppError> {
+    let seed: Zeroizing<[u8; 32]> = Zeroizing::new(
+        private_key
+            .try_into()
+            .map_err(|_| AppError::Bootstrap("Identity key must be 32 bytes".to_string()))?,
+    );
+    let public_key = SigningKey::from_bytes(&seed).verifying_key().to_bytes();
+    let mut multicodec_key = vec![0xED, 0x01];
+    multicodec_key.extend_from_slice(&public_key);
+    let did = format!(
+        "did:key:{}",
+        multibase::encode(Base::Base58Btc, &multicodec_key)
+    );
+    Ok((public_key.to_vec(), did))
+}
+
 fn create_directory<P: AsRef<Path>>(path: P) -> Result<bool, Box<dyn Error>> {
     let path = path.as_ref();
 
@@ -135,9 +234,29 @@ fn create_directory<P: AsRef<Path>>(path: P) -> Result<bool, Box<dyn Error>> {
     Ok(true)
 }
 
+fn read_metadata(p: &Paths) -> Result<AuthMetadata, AppError> {
+    let json = fs::read(&p.auth_file)?;
+    serde_json::from_slice(&json)
+        .map_err(|e| AppError::Bootstrap(format!("Failed to read auth metadata: {}", e)))
+}
+
+fn write_metadata(p: &Paths, meta: &AuthMetadata) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(meta)
+        .map_err(|e| AppError::Bootstrap(format!("Failed to serialize auth metadata: {}", e)))?;
+    write_atomic_with_mode(&p.auth_file, json.as_bytes(), 0o644)
+        .map_err(|e| AppError::Bootstrap(format!("Failed to write auth file: {}", e)))
+}
+
 fn load_existing(p: &Paths) -> Result<NodeData, Box<dyn Error>> {
-    let meta: AuthMetadata = serde_json::from_slice(&fs::read(&p.auth_file)?)?;
-    let priv_key_bytes = fs::read(&p.priv_key_file)?;
+    let meta = read_metadata(p)?;
+    let priv_key_bytes = keystore::open(meta.key_storage, &p.config_dir)?
+        .load(IDENTITY_KEY)?
+        .ok_or_else(|| {
+            format!(
+                "Private key missing from {:?} key storage",
+                meta.key_storage
+            )
+        })?;
     let pub_key_bytes = fs::read(&p.pub_key_file)?;
 
     Ok(NodeData {
@@ -147,14 +266,56 @@ fn load_existing(p: &Paths) -> Result<NodeData, Box<dyn Error>> {
     })
 }
 
-fn bootstrap_new(p: &Paths) -> Result<NodeData, AppError> {
+/// Move the private keys of an existing node to `backend`. Keys are copied
+/// and recorded in auth.json before the originals go, so an interrupted move
+/// leaves a node that still starts.
+fn move_keys(p: &Paths, backend: Option<KeyStorageBackend>) -> Result<(), AppError> {
+    let mut meta = read_metadata(p)?;
+    let Some(backend) = backend.filter(|backend| *backend != meta.key_storage) else {
+        return Ok(());
+    };
+    let from = keystore::open(meta.key_storage, &p.config_dir)?;
+    let to = keystore::open(backend, &p.config_dir)?;
+
+    let names: Vec<String> = std::iter::once(IDENTITY_KEY.to_string())
+        .chain(
+            meta.key_history
+                .iter()
+                .map(|retired| archived_key(retired.version)),
+        )
+        .collect();
+    for name in &names {
+        if let Some(key) = from.load(name)? {
+            to.store(name, &key)?;
+        }
+    }
+    let previous = std::mem::replace(&mut meta.key_storage, backend);
+    write_metadata(p, &meta)?;
+    for name in &names {
+        from.delete(name)?;
+    }
+
+    log::info!(
+        "Moved node keys from {:?} to {:?} key storage",
+        previous,
+        backend
+    );
+    Ok(())
+}
+
+/// Name of the identity key rotated out at `version`
+fn archived_key(version: u32) -> String {
+    format!("{}.v{}", IDENTITY_KEY, version)
+}
+
+fn bootstrap_new(p: &Paths, backend: KeyStorageBackend) -> Result<NodeData, AppError> {
     ensure_keystore_dir(p)
         .map_err(|e| AppError::Bootstrap(format!("Failed to setup Keystore directories. {}", e)))?;
+    let storage = keystore::open(backend, &p.config_dir)?;
 
     let (priv_key_bytes, pub_key_bytes, pub_key_multibase, did) = generate_keys_and_did();
 
-    write_atomic_with_mode(&p.priv_key_file, &priv_key_bytes, 0o600)
-        .map_err(|e| AppError::Bootstrap(format!("Failed to write private key: {}", e)))?;
+    storage.store(IDENTITY_KEY, &priv_key_bytes)?;
 
     write_atomic_with_mode(&p.pub_key_file, &pub_key_bytes, 0o644)
         .map_err(|e| AppError::Bootstrap(format!("Failed to write public key: {}", e)))?;
@@ -164,13 +325,10 @@ fn bootstrap_new(p: &Paths) -> Result<NodeData, AppError> {
         did: did.clone(),
         created_at: chrono::Utc::now().to_rfc3339(),
         pub_key_multibase: pub_key_multibase,
+        key_history: Vec::new(),
+        key_storage: backend,
     };
-
-    let json = serde_json::to_string_pretty(&meta)
-        .map_err(|e| AppError::Bootstrap(format!("Failed to serialize auth metadata: {}", e)))?;
-
-    write_atomic_with_mode(&p.auth_file, json.as_bytes(), 0o644)
-        .map_err(|e| AppError::Bootstrap(format!("Failed to write auth file: {}", e)))?;
+    write_metadata(p, &meta)?;
 
     Ok(NodeData {
         id: did,
@@ -179,6 +337,66 @@ fn bootstrap_new(p: &Paths) -> Result<NodeData, AppError> {
     })
 }
 
+fn rotate(p: &Paths) -> Result<(NodeData, SignedRotation), AppError> {
+    let mut meta = read_metadata(p)?;
+    let storage = keystore::open(meta.key_storage, &p.config_dir)?;
+    let previous = load_existing(p)
+        .map_err(|e| AppError::Bootstrap(format!("Failed to load identity key: {}", e)))?;
+    let version = meta.key_history.len() as u32 + 1;
+
+    let (priv_key_bytes, pub_key_bytes, pub_key_multibase, did) = generate_keys_and_did();
+    let next = NodeData {
+        id: did.clone(),
+        private_key: priv_key_bytes,
+        public_key: pub_key_bytes,
+    };
+    let rotation = RotationStatement::new(&previous, &next, version + 1)?.sign(&previous)?;
+
+    // Archived before anything is replaced, so the old key is never lost
+    let archived = archived_key(version);
+    storage.store(&archived, &previous.private_key)?;
+    write_atomic_with_mode(
+        &p.keystore_dir.join(format!("{}.pub", archived)),
+        &previous.public_key,
+        0o644,
+    )
+    .map_err(|e| AppError::Bootstrap(format!("Failed to archive public key: {}", e)))?;
+
+    // Staged until auth.json names it: a crash in between leaves the old
+    // key in place, matching auth.json, and the staged one is dropped
+    storage.store(PENDING_KEY, &next.private_key)?;
+    meta.key_history.push(RetiredKey {
+        version,
+        did: std::mem::replace(&mut meta.did, did),
+        pub_key_multibase: std::mem::replace(&mut meta.pub_key_multibase, pub_key_multibase),
+        rotation: rotation.clone(),
+    });
+    write_metadata(p, &meta)?;
+    finish_rotation(p)?;
+
+    Ok((next, rotation))
+}
+
+/// Put a staged identity key in place if auth.json names it, or drop it if
+/// the rotation never got as far as recording it
+fn finish_rotation(p: &Paths) -> Result<(), AppError> {
+    let meta = read_metadata(p)?;
+    let storage = keystore::open(meta.key_storage, &p.config_dir)?;
+    let Some(pending) = storage.load(PENDING_KEY)? else {
+        return Ok(());
+    };
+
+    let (public_key, did) = keys_of(&pending)?;
+    if did == meta.did {
+        storage.store(IDENTITY_KEY, &pending)?;
+        write_atomic_with_mode(&p.pub_key_file, &public_key, 0o644)
+            .map_err(|e| AppError::Bootstrap(format!("Failed to write public key: {}", e)))?;
+    } else {
+        log::warn!("Dropping identity key {} from an interrupted rotation", did);
+    }
+    storage.delete(PENDING_KEY)
+}
+
 fn ensure_keystore_dir(p: &Paths) -> Result<(), Box<dyn Error>> {
     fs::create_dir_all(&p.keystore_dir)?;
 
@@ -193,7 +411,11 @@ fn ensure_keystore_dir(p: &Paths) -> Result<(), Box<dyn Error>> {
 }
 
 // Atomic write with permissions
-fn write_atomic_with_mode(path: &Path, data: &[u8], mode: u32) -> Result<(), Box<dyn Error>> {
+pub(super) fn write_atomic_with_mode(
+    path: &Path,
+    data: &[u8],
+    mode: u32,
+) -> Result<(), Box<dyn Error>> {
     let parent = path.parent().ok_or("No parent directory")?;
     let mut tmp = NamedTempFile::new_in(parent)?;
     tmp.write_all(data)?;
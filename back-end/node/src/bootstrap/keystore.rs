@@ -0,0 +1,140 @@
+//! Where the node's private keys live.
+//!
+//! By default they are files in the keystore directory, readable only by the
+//! node's user. On desktops the OS keychain is the stronger home: secrets sit
+//! encrypted under the user's login and other programs need the user's
+//! consent to read them. Public keys and `auth.json` stay files either way;
+//! `auth.json` records which storage holds the private keys, so a node keeps
+//! finding them whatever it is later configured with until it moves them
+//! (see [`super::init::initialize_with`]).
+//!
+//! Keys are stored by name: `ed25519` for the identity key, `ed25519.v<n>`
+//! for identity keys rotated out.
+
+use std::path::{Path, PathBuf};
+
+use errors::AppError;
+use zeroize::Zeroizing;
+
+use super::config::KeyStorageBackend;
+use super::init::write_atomic_with_mode;
+
+/// Service the node's entries are filed under in the OS keychain
+pub const KEYCHAIN_SERVICE: &str = "flow-node";
+
+pub trait KeyStorage {
+    /// The key called `name`, `None` if there is none
+    fn load(&self, name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, AppError>;
+
+    /// Store `key` as `name`, replacing any key of that name
+    fn store(&self, name: &str, key: &[u8]) -> Result<(), AppError>;
+
+    /// Forget the key called `name`; forgetting a missing key is not an error
+    fn delete(&self, name: &str) -> Result<(), AppError>;
+}
+
+/// The storage `backend` for the node whose config dir is `config_dir`
+pub fn open(
+    backend: KeyStorageBackend,
+    config_dir: &Path,
+) -> Result<Box<dyn KeyStorage>, AppError> {
+    match backend {
+        KeyStorageBackend::File => Ok(Box::new(FileKeyStorage::new(
+            config_dir.join(super::init::KEYSTORE_DIR),
+        ))),
+        #[cfg(feature = "keychain")]
+        KeyStorageBackend::Keychain => Ok(Box::new(KeychainStorage::new(config_dir))),
+        #[cfg(not(feature = "keychain"))]
+        KeyStorageBackend::Keychain => Err(AppError::Config(
+            "Key storage 'keychain' needs a node built with the keychain feature".to_string(),
+        )),
+    }
+}
+
+/// Keys as `<name>.priv` files, mode 0600, in a directory
+pub struct FileKeyStorage {
+    dir: PathBuf,
+}
+
+impl FileKeyStorage {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.priv", name))
+    }
+}
+
+impl KeyStorage for FileKeyStorage {
+    fn load(&self, name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, AppError> {
+        match std::fs::read(self.path(name)) {
+            Ok(key) => Ok(Some(Zeroizing::new(key))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn store(&self, name: &str, key: &[u8]) -> Result<(), AppError> {
+        write_atomic_with_mode(&self.path(name), key, 0o600)
+            .map_err(|e| AppError::Bootstrap(format!("Failed to write key {}: {}", name, e)))
+    }
+
+    fn delete(&self, name: &str) -> Result<(), AppError> {
+        match std::fs::remove_file(self.path(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keys in the OS keychain: Keychain on macOS, the Credential Manager on
+/// Windows, the Secret Service on Linux desktops. Entries are named after
+/// the key and the config dir, so several nodes of one user don't collide.
+#[cfg(feature = "keychain")]
+pub struct KeychainStorage {
+    config_dir: String,
+}
+
+#[cfg(feature = "keychain")]
+impl KeychainStorage {
+    pub fn new(config_dir: &Path) -> Self {
+        Self {
+            config_dir: config_dir.to_string_lossy().into_owned(),
+        }
+    }
+
+    fn entry(&self, name: &str) -> Result<keyring::Entry, AppError> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, &format!("{}@{}", name, self.config_dir))
+            .map_err(|e| keychain_error(name, e))
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl KeyStorage for KeychainStorage {
+    fn load(&self, name: &str) -> Result<Option<Zeroizing<Vec<u8>>>, AppError> {
+        match self.entry(name)?.get_secret() {
+            Ok(key) => Ok(Some(Zeroizing::new(key))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keychain_error(name, e)),
+        }
+    }
+
+    fn store(&self, name: &str, key: &[u8]) -> Result<(), AppError> {
+        self.entry(name)?
+            .set_secret(key)
+            .map_err(|e| keychain_error(name, e))
+    }
+
+    fn delete(&self, name: &str) -> Result<(), AppError> {
+        match self.entry(name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keychain_error(name, e)),
+        }
+    }
+}
+
+#[cfg(feature = "keychain")]
+fn keychain_error(name: &str, e: keyring::Error) -> AppError {
+    AppError::Bootstrap(format!("Keychain entry for key {}: {}", name, e))
+}
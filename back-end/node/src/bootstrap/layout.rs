@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use errors::AppError;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::config::Config;
+
+/// Where the node last recorded its stores, in the config directory
+pub const LAYOUT_FILE: &str = "layout.json";
+
+/// SQLite sidecar files that travel with the database
+const SQLITE_SIDECARS: [&str; 2] = ["-wal", "-shm"];
+
+/// On-disk locations of the node's stores. `None` means the store is not
+/// file-backed (in-memory, or a non-SQLite database).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataLayout {
+    pub db: Option<PathBuf>,
+    pub kv: Option<PathBuf>,
+    /// Where users' spaces go, each user's in a directory of their own (see
+    /// [`user_spaces`]). Not a store: `relocate` leaves it where it is.
+    #[serde(default)]
+    pub spaces: Option<PathBuf>,
+}
+
+impl DataLayout {
+    /// Layout the configuration points at
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            db: sqlite_path(&config.db.url),
+            kv: (!config.kv.in_memory).then(|| PathBuf::from(&config.kv.path)),
+            spaces: config.multi_user.spaces_root.clone(),
+        }
+    }
+
+    /// Default layout under a single data directory (`FLOW_DATA_DIR`)
+    pub fn under(root: &Path) -> Self {
+        Self {
+            db: Some(root.join("db").join("flow.sqlite")),
+            kv: Some(root.join("kv")),
+            spaces: Some(root.join("spaces")),
+        }
+    }
+
+    pub fn database_url(&self) -> Option<String> {
+        self.db
+            .as_ref()
+            .map(|db| format!("sqlite://{}?mode=rwc", db.display()))
+    }
+
+    fn stores(&self) -> [(&'static str, Option<&PathBuf>); 2] {
+        [
+            ("database", self.db.as_ref()),
+            ("KV store", self.kv.as_ref()),
+        ]
+    }
+}
+
+/// Directory of `user_id`'s spaces under the users' spaces root
+pub fn user_spaces(spaces_root: &Path, user_id: i32) -> PathBuf {
+    spaces_root.join(user_id.to_string())
+}
+
+/// File path of a SQLite database URL, if it names one
+pub fn sqlite_path(url: &str) -> Option<PathBuf> {
+    let rest = url
+        .strip_prefix("sqlite://")
+        .or_else(|| url.strip_prefix("sqlite:"))?;
+    let path = rest.split('?').next().unwrap_or_default();
+
+    if path.is_empty() || path.contains(":memory:") || url.contains("mode=memory") {
+        None
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+pub fn load_recorded(config_dir: &Path) -> Result<Option<DataLayout>, AppError> {
+    match fs::read(config_dir.join(LAYOUT_FILE)) {
+        Ok(json) => serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(|e| AppError::Bootstrap(format!("Invalid {}: {}", LAYOUT_FILE, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn record(config_dir: &Path, layout: &DataLayout) -> Result<(), AppError> {
+    fs::create_dir_all(config_dir)?;
+    let json = serde_json::to_vec_pretty(layout)
+        .map_err(|e| AppError::Bootstrap(format!("Failed to serialize layout: {}", e)))?;
+    fs::write(config_dir.join(LAYOUT_FILE), json)?;
+    Ok(())
+}
+
+/// Startup check: refuse to start against an empty location when the data
+/// recorded for that store still exists elsewhere, then record the layout.
+pub fn check(config_dir: &Path, layout: &DataLayout) -> Result<(), AppError> {
+    if let Some(recorded) = load_recorded(config_dir)? {
+        for ((store, current), (_, previous)) in layout.stores().into_iter().zip(recorded.stores())
+        {
+            let (Some(current), Some(previous)) = (current, previous) else {
+                continue;
+            };
+            if current == previous {
+                continue;
+            }
+
+            if !current.exists() && previous.exists() {
+                return Err(AppError::Bootstrap(format!(
+                    "Data layout mismatch: the {} is configured at {} but its data is at {}. \
+                     Point the configuration at the existing data (see FLOW_DATA_DIR) or move it \
+                     with `node relocate-data <dir>`.",
+                    store,
+                    current.display(),
+                    previous.display()
+                )));
+            }
+            warn!(
+                "The {} moved from {} to {}",
+                store,
+                previous.display(),
+                current.display()
+            );
+        }
+    }
+
+    record(config_dir, layout)
+}
+
+/// Move the file-backed stores of `from` under `new_root`, verifying every
+/// copied file before the originals are removed. The node must not be running.
+pub fn relocate(
+    config_dir: &Path,
+    from: &DataLayout,
+    new_root: &Path,
+) -> Result<DataLayout, AppError> {
+    let to = DataLayout::under(new_root);
+
+    let mut moves = Vec::new();
+    for ((store, source), (_, target)) in from.stores().into_iter().zip(to.stores()) {
+        let (Some(source), Some(target)) = (source, target) else {
+            continue;
+        };
+        if !source.exists() {
+            info!("No {} at {}, skipping", store, source.display());
+            continue;
+        }
+        if target.exists() {
+            return Err(AppError::Validation(format!(
+                "Refusing to overwrite existing {} at {}",
+                store,
+                target.display()
+            )));
+        }
+        moves.push((source.clone(), target.clone()));
+        for suffix in SQLITE_SIDECARS {
+            let sidecar = with_suffix(source, suffix);
+            if store == "database" && sidecar.exists() {
+                moves.push((sidecar, with_suffix(target, suffix)));
+            }
+        }
+    }
+
+    for (source, target) in &moves {
+        copy_recursive(source, target)?;
+        if digest_tree(source)? != digest_tree(target)? {
+            return Err(AppError::Storage(
+                format!(
+                    "Verification failed copying {} to {}; originals left in place",
+                    source.display(),
+                    target.display()
+                )
+                .into(),
+            ));
+        }
+        info!("Copied {} to {}", source.display(), target.display());
+    }
+
+    record(config_dir, &to)?;
+
+    for (source, _) in &moves {
+        if source.is_dir() {
+            fs::remove_dir_all(source)?;
+        } else {
+            fs::remove_file(source)?;
+        }
+    }
+
+    Ok(to)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn copy_recursive(source: &Path, target: &Path) -> Result<(), AppError> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if source.is_dir() {
+        fs::create_dir_all(target)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, target)?;
+    }
+    Ok(())
+}
+
+/// blake3 digest of every file under `root`, keyed by relative path
+fn digest_tree(root: &Path) -> Result<BTreeMap<PathBuf, String>, AppError> {
+    fn walk(root: &Path, dir: &Path, out: &mut BTreeMap<PathBuf, String>) -> Result<(), AppError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(root, &path, out)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                out.insert(
+                    relative,
+                    blake3::hash(&fs::read(&path)?).to_hex().to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    let mut out = BTreeMap::new();
+    if root.is_dir() {
+        walk(root, root, &mut out)?;
+    } else {
+        out.insert(
+            PathBuf::new(),
+            blake3::hash(&fs::read(root)?).to_hex().to_string(),
+        );
+    }
+    Ok(out)
+}
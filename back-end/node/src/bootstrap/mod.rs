@@ -1,2 +1,7 @@
 pub mod config;
+pub mod daemon;
 pub mod init;
+pub mod keystore;
+pub mod layout;
+pub mod profile;
+pub mod service;
@@ -0,0 +1,288 @@
+//! Signed configuration profiles.
+//!
+//! A profile is the node's non-secret settings (ports, pool sizes, WebAuthn
+//! relying party, update channel...) as a versioned bundle signed with the
+//! node identity key. Exporting one from a reference node and importing it on
+//! others provisions a fleet consistently; the imported profile is kept as a
+//! baseline so drift from it can be reported later.
+//!
+//! Only the keys in [`PROFILE_KEYS`] are captured. Database URLs, data paths
+//! and key material are node-specific or secret and never leave the node.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use ed25519_dalek::Signer;
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+
+use crate::bootstrap::init::NodeData;
+use crate::modules::manifest;
+
+pub const PROFILE_SCHEMA: &str = "flow-config-profile/v1";
+/// Baseline kept in the config directory after an import
+pub const BASELINE_FILE: &str = "profile.json";
+
+/// Settings a profile carries
+pub const PROFILE_KEYS: &[&str] = &[
+    "HOST",
+    "REST_PORT",
+    "WEBSOCKET_PORT",
+    "FLOW_BASE_PATH",
+    "FLOW_EXTERNAL_ORIGIN",
+    "DB_MAX_CONNECTIONS",
+    "DB_MIN_CONNECTIONS",
+    "DB_CONNECT_TIMEOUT",
+    "DB_IDLE_TIMEOUT",
+    "DB_MAX_LIFETIME",
+    "DB_LOGGING_ENABLED",
+    "KV_IN_MEMORY",
+    "KV_CACHE_MB",
+    "FLOW_LOW_RESOURCE",
+    "FLOW_UPDATE_ENABLED",
+    "FLOW_UPDATE_INTERVAL",
+    "FLOW_UPDATE_URL",
+    "FLOW_UPDATE_PUBLIC_KEY",
+    "WEBAUTHN_RP_ID",
+    "WEBAUTHN_RP_ORIGIN",
+    "WEBAUTHN_RP_NAME",
+    "WEBAUTHN_REGISTRATION_UV",
+    "WEBAUTHN_AUTHENTICATION_UV",
+    "WEBAUTHN_AUTHENTICATOR_ATTACHMENT",
+    "WEBAUTHN_RESIDENT_KEY",
+    "WEBAUTHN_TIMEOUT_MS",
+    "WEBAUTHN_MIN_TIMEOUT_MS",
+    "WEBAUTHN_MAX_TIMEOUT_MS",
+    "WEBAUTHN_ALLOWED_ATTESTATION",
+    "FLOW_MULTI_USER",
+    "FLOW_USER_MAX_SPACES",
+    "FLOW_USER_KV_QUOTA_BYTES",
+    "FLOW_GUEST_MODE",
+    "FLOW_KEY_STORAGE",
+    "FLOW_MAX_BODY_BYTES",
+    "FLOW_MAX_UPLOAD_BYTES",
+    "FLOW_BODY_SPILL_BYTES",
+    "FLOW_REQUEST_TIMEOUT_SECS",
+    "FLOW_EXPENSIVE_REQUEST_TIMEOUT_SECS",
+    "FLOW_MAX_CONCURRENT_REQUESTS",
+    "FLOW_MAX_CONCURRENT_EXPENSIVE",
+    "FLOW_COMPRESSION_ENABLED",
+    "FLOW_COMPRESSION_MIN_BYTES",
+    "FLOW_SECURITY_HEADERS",
+    "FLOW_CSP",
+    "FLOW_HSTS",
+    "FLOW_HSTS_MAX_AGE_SECS",
+    "FLOW_DID_CACHE",
+    "FLOW_DID_CACHE_CAPACITY",
+    "FLOW_ACME_DIRECTORY",
+    "FLOW_ACME_CHALLENGE",
+    "FLOW_ACME_RENEW_DAYS",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigProfile {
+    pub schema: String,
+    /// Increases with each published revision of the profile
+    pub version: u32,
+    /// Setting name to value; unset settings are absent
+    pub settings: BTreeMap<String, String>,
+    /// DID of the node that signed the profile
+    pub issuer: String,
+    pub public_key_multibase: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedProfile {
+    pub profile: ConfigProfile,
+    /// Multibase (base58btc) Ed25519 signature over the canonical profile
+    pub signature: String,
+}
+
+/// A setting whose current value differs from the baseline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Drift {
+    pub key: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+/// Profile settings from the current environment
+pub fn current_settings() -> BTreeMap<String, String> {
+    PROFILE_KEYS
+        .iter()
+        .filter_map(|key| {
+            std::env::var(key)
+                .ok()
+                .map(|value| (key.to_string(), value))
+        })
+        .collect()
+}
+
+/// Sign `settings` as revision `version` of a profile
+pub fn export(
+    node_data: &NodeData,
+    settings: BTreeMap<String, String>,
+    version: u32,
+) -> Result<SignedProfile, AppError> {
+    check_settings(&settings)?;
+
+    let signing_key = manifest::signing_key(node_data)?;
+    let profile = ConfigProfile {
+        schema: PROFILE_SCHEMA.to_string(),
+        version,
+        settings,
+        issuer: node_data.id.clone(),
+        public_key_multibase: manifest::encode_public_key(&signing_key.verifying_key()),
+        created_at: Utc::now().to_rfc3339(),
+    };
+    let signature = signing_key.sign(&manifest::canonical_bytes(&profile)?);
+
+    Ok(SignedProfile {
+        profile,
+        signature: manifest::encode_signature(&signature),
+    })
+}
+
+/// Check the profile's signature and that its key is the issuer's. With
+/// `trusted_key`, the profile must also be signed by that key.
+pub fn verify(signed: &SignedProfile, trusted_key: Option<&str>) -> Result<(), AppError> {
+    let profile = &signed.profile;
+    if profile.schema != PROFILE_SCHEMA {
+        return Err(AppError::Validation(format!(
+            "Unsupported profile schema: {}",
+            profile.schema
+        )));
+    }
+    if profile.issuer != format!("did:key:{}", profile.public_key_multibase) {
+        return Err(AppError::Auth(
+            "Profile key does not belong to its issuer".to_string(),
+        ));
+    }
+    if trusted_key.is_some_and(|key| key != profile.public_key_multibase) {
+        return Err(AppError::Auth(format!(
+            "Profile is signed by untrusted issuer {}",
+            profile.issuer
+        )));
+    }
+    check_settings(&profile.settings)?;
+
+    let valid = manifest::verify_with_key(
+        &profile.public_key_multibase,
+        &manifest::canonical_bytes(profile)?,
+        &signed.signature,
+    )?;
+    if !valid {
+        return Err(AppError::Auth("Invalid profile signature".to_string()));
+    }
+    Ok(())
+}
+
+/// Verify a profile, write its settings into `env_file` and keep it as the
+/// baseline in `config_dir`. Settings the profile doesn't carry are left
+/// alone, as are other lines of the file.
+pub fn import(
+    signed: &SignedProfile,
+    trusted_key: Option<&str>,
+    env_file: &Path,
+    config_dir: &Path,
+) -> Result<(), AppError> {
+    verify(signed, trusted_key)?;
+
+    if let Some(baseline) = load_baseline(config_dir)?
+        && baseline.profile.issuer == signed.profile.issuer
+        && baseline.profile.version > signed.profile.version
+    {
+        return Err(AppError::Validation(format!(
+            "Profile version {} is older than the current baseline ({})",
+            signed.profile.version, baseline.profile.version
+        )));
+    }
+
+    let existing = match fs::read_to_string(env_file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    fs::write(env_file, merge_env(&existing, &signed.profile.settings))?;
+
+    let json = serde_json::to_vec_pretty(signed)
+        .map_err(|e| AppError::Config(format!("Failed to encode profile: {}", e)))?;
+    fs::write(config_dir.join(BASELINE_FILE), json)?;
+    Ok(())
+}
+
+/// The profile last imported into `config_dir`, if any
+pub fn load_baseline(config_dir: &Path) -> Result<Option<SignedProfile>, AppError> {
+    match fs::read(config_dir.join(BASELINE_FILE)) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| AppError::Config(format!("Invalid baseline profile: {}", e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Profile settings whose current value differs from the baseline
+pub fn drift(baseline: &ConfigProfile, current: &BTreeMap<String, String>) -> Vec<Drift> {
+    PROFILE_KEYS
+        .iter()
+        .filter_map(|key| {
+            let expected = baseline.settings.get(*key);
+            let actual = current.get(*key);
+            (expected != actual).then(|| Drift {
+                key: key.to_string(),
+                expected: expected.cloned(),
+                actual: actual.cloned(),
+            })
+        })
+        .collect()
+}
+
+/// Only profile keys, with values that fit on one `.env` line
+fn check_settings(settings: &BTreeMap<String, String>) -> Result<(), AppError> {
+    for (key, value) in settings {
+        if !PROFILE_KEYS.contains(&key.as_str()) {
+            return Err(AppError::Validation(format!(
+                "{} cannot be part of a profile",
+                key
+            )));
+        }
+        if value.contains(['\n', '\r']) {
+            return Err(AppError::Validation(format!(
+                "Value of {} spans multiple lines",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Replace or append `KEY=value` lines for each setting
+fn merge_env(existing: &str, settings: &BTreeMap<String, String>) -> String {
+    let mut remaining = settings.clone();
+    let mut lines: Vec<String> = existing
+        .lines()
+        .map(|line| {
+            let key = line.split_once('=').map(|(k, _)| k.trim());
+            match key.and_then(|k| remaining.remove_entry(k)) {
+                Some((key, value)) => format!("{}={}", key, value),
+                None => line.to_string(),
+            }
+        })
+        .collect();
+    lines.extend(
+        remaining
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value)),
+    );
+
+    let mut merged = lines.join("\n");
+    merged.push('\n');
+    merged
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use directories::BaseDirs;
+use errors::AppError;
+use log::info;
+
+use super::daemon::LOG_FILE;
+
+pub const SERVICE_NAME: &str = "flow-node";
+pub const LAUNCHD_LABEL: &str = "io.flow.node";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceManager {
+    Systemd,
+    Launchd,
+}
+
+impl ServiceManager {
+    pub fn for_platform() -> Self {
+        if cfg!(target_os = "macos") {
+            ServiceManager::Launchd
+        } else {
+            ServiceManager::Systemd
+        }
+    }
+
+    /// Per-user unit location the service manager picks up
+    pub fn default_path(&self) -> Result<PathBuf, AppError> {
+        let dirs = BaseDirs::new()
+            .ok_or_else(|| AppError::Config("Could not determine home directory".to_string()))?;
+
+        Ok(match self {
+            ServiceManager::Systemd => dirs
+                .config_dir()
+                .join("systemd/user")
+                .join(format!("{}.service", SERVICE_NAME)),
+            ServiceManager::Launchd => dirs
+                .home_dir()
+                .join("Library/LaunchAgents")
+                .join(format!("{}.plist", LAUNCHD_LABEL)),
+        })
+    }
+
+    /// Command to run after installing the unit
+    pub fn enable_hint(&self, path: &Path) -> String {
+        match self {
+            ServiceManager::Systemd => format!(
+                "systemctl --user daemon-reload && systemctl --user enable --now {}",
+                SERVICE_NAME
+            ),
+            ServiceManager::Launchd => format!("launchctl load -w {}", path.display()),
+        }
+    }
+}
+
+/// What the generated unit runs
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    pub executable: PathBuf,
+    /// Also the directory the node starts in, so a `.env` there is loaded
+    /// on startup
+    pub config_dir: PathBuf,
+}
+
+impl ServiceSpec {
+    /// Spec for the running executable and config dir
+    pub fn current(config_dir: &Path) -> Result<Self, AppError> {
+        Ok(Self {
+            executable: std::env::current_exe()?,
+            config_dir: config_dir.to_path_buf(),
+        })
+    }
+}
+
+/// The unit file for `manager`. Fails for paths a systemd unit can't hold.
+pub fn render(manager: ServiceManager, spec: &ServiceSpec) -> Result<String, AppError> {
+    Ok(match manager {
+        ServiceManager::Systemd => format!(
+            "[Unit]
+Description=Flow node
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=simple
+ExecStart={exe}
+WorkingDirectory={workdir}
+Environment={env}
+Restart=on-failure
+RestartSec=5
+
+[Install]
+WantedBy=default.target
+",
+            exe = systemd_quote(&systemd_path(&spec.executable)?),
+            workdir = systemd_path(&spec.config_dir)?,
+            env = systemd_quote(&format!(
+                "FLOW_CONFIG_HOME={}",
+                systemd_path(&spec.config_dir)?
+            )),
+        ),
+        ServiceManager::Launchd => format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+    </array>
+    <key>WorkingDirectory</key>
+    <string>{config}</string>
+    <key>EnvironmentVariables</key>
+    <dict>
+        <key>FLOW_CONFIG_HOME</key>
+        <string>{config}</string>
+    </dict>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+            label = LAUNCHD_LABEL,
+            exe = xml_escape(&spec.executable.to_string_lossy()),
+            config = xml_escape(&spec.config_dir.to_string_lossy()),
+            log = xml_escape(&spec.config_dir.join(LOG_FILE).to_string_lossy()),
+        ),
+    })
+}
+
+/// Write the unit file for `manager` to `path`
+pub fn install(manager: ServiceManager, spec: &ServiceSpec, path: &Path) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, render(manager, spec)?)?;
+
+    info!("Wrote {:?} service unit to {}", manager, path.display());
+    Ok(())
+}
+
+/// `path` with its `%` specifiers escaped. Unit files are line based, so
+/// a path with a line break or other control character can't be written.
+fn systemd_path(path: &Path) -> Result<String, AppError> {
+    let path = path.to_string_lossy();
+    if path.chars().any(char::is_control) {
+        return Err(AppError::Config(format!(
+            "Path can't be used in a systemd unit: {:?}",
+            path
+        )));
+    }
+    Ok(path.replace('%', "%%"))
+}
+
+/// `value` as one double-quoted word of `ExecStart=` or `Environment=`
+fn systemd_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -1,4 +1,11 @@
 pub mod api;
+pub mod bench;
 pub mod bootstrap;
+pub mod lifecycle;
 pub mod modules;
+pub mod plugins;
 pub mod runner;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "seed")]
+pub mod seed;
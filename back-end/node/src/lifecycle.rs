@@ -0,0 +1,94 @@
+//! Stopping the node cleanly.
+//!
+//! A [`Shutdown`] is triggered by SIGINT or SIGTERM, by
+//! `POST /api/v1/admin/shutdown`, or by a server failing. The REST, WebSocket
+//! and gRPC servers then stop accepting connections and finish the requests
+//! in flight, WebSocket clients are sent a close frame, and background tasks
+//! are stopped. Last, [`close`] flushes the KV store and closes the database
+//! connections.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use errors::AppError;
+use log::info;
+use tokio::sync::watch;
+
+use crate::api::node::Node;
+
+/// How long servers get to finish requests in flight once shutdown starts
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// Signal to stop, shared by everything that has to. Clones trigger and see
+/// the same shutdown.
+#[derive(Debug, Clone)]
+pub struct Shutdown(Arc<watch::Sender<bool>>);
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self(Arc::new(watch::Sender::new(false)))
+    }
+
+    /// Start shutting down; later triggers do nothing more
+    pub fn trigger(&self, reason: &str) {
+        if !self.0.send_replace(true) {
+            info!("Shutting down: {}", reason);
+        }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once shutdown is triggered, at once if it already was
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.0.subscribe();
+        async move {
+            let _ = receiver.wait_for(|stopping| *stopping).await;
+        }
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolves with the signal's name once SIGINT (Ctrl+C) or SIGTERM arrives
+pub async fn signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = terminate.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                log::warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
+
+/// Flush the KV store and close the database connections
+pub async fn close(node: &Node) -> Result<(), AppError> {
+    node.kv
+        .flush_async()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    node.db
+        .clone()
+        .close()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(())
+}
@@ -1,11 +1,363 @@
 use log::error;
+use node::bootstrap::{
+    config::Config,
+    daemon,
+    init::{self, get_flow_config_dir},
+    layout, profile, service,
+};
+use node::modules::column_crypto::{self, ColumnKeys};
+use node::modules::storage_stats;
+use std::path::{Path, PathBuf};
+
+const INSTALL_SERVICE_USAGE: &str =
+    "Usage: node install-service [--systemd|--launchd] [--output PATH]";
+
+const RELOCATE_DATA_USAGE: &str = "Usage: node relocate-data <new-dir>";
+
+const ROTATE_COLUMN_KEY_USAGE: &str = "Usage: node rotate-column-key";
+
+const DOCTOR_USAGE: &str = "Usage: node doctor";
+
+const CONFIG_USAGE: &str = "Usage: node config export [--version N] [--output PATH]
+       node config import <PATH> [--env-file PATH] [--trust KEY]
+       node config drift";
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
 
-    if let Err(e) = node::runner::run().await {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("bench") => {
+            match node::bench::run(&args[1..]).await {
+                Ok(reports) => {
+                    for report in reports {
+                        println!("{}", report);
+                    }
+                }
+                Err(e) => {
+                    error!("Bench failed: {}", e);
+                    eprintln!("{}\n{}", e, node::bench::USAGE);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        #[cfg(feature = "seed")]
+        Some("seed") => {
+            match node::seed::run(&args[1..]).await {
+                Ok(report) => print!("{}", report),
+                Err(e) => {
+                    error!("Seeding failed: {}", e);
+                    eprintln!("{}\n{}", e, node::seed::USAGE);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        #[cfg(not(feature = "seed"))]
+        Some("seed") => {
+            eprintln!("This build has no seed data; rebuild with `--features seed`");
+            std::process::exit(1);
+        }
+        Some("install-service") => {
+            if let Err(e) = install_service(&args[1..]) {
+                error!("Service install failed: {}", e);
+                eprintln!("{}\n{}", e, INSTALL_SERVICE_USAGE);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("relocate-data") => {
+            if let Err(e) = relocate_data(&args[1..]) {
+                error!("Relocation failed: {}", e);
+                eprintln!("{}\n{}", e, RELOCATE_DATA_USAGE);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("config") => {
+            if let Err(e) = config_profile(&args[1..]) {
+                error!("Config command failed: {}", e);
+                eprintln!("{}\n{}", e, CONFIG_USAGE);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("rotate-column-key") => {
+            if let Err(e) = rotate_column_key().await {
+                error!("Column key rotation failed: {}", e);
+                eprintln!("{}\n{}", e, ROTATE_COLUMN_KEY_USAGE);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("doctor") => {
+            if let Err(e) = doctor().await {
+                error!("Doctor failed: {}", e);
+                eprintln!("{}\n{}", e, DOCTOR_USAGE);
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    if args.iter().any(|a| a == "--daemon") {
+        let config_dir = get_flow_config_dir();
+        match daemon::spawn_daemon(Path::new(&config_dir), &args) {
+            Ok(pid) => println!(
+                "Flow node started in the background (PID {}), logging to {}",
+                pid,
+                Path::new(&config_dir).join(daemon::LOG_FILE).display()
+            ),
+            Err(e) => {
+                error!("Failed to start daemon: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let result = if args.iter().any(|a| a == "--ephemeral") {
+        node::runner::run_ephemeral().await
+    } else {
+        node::runner::run().await
+    };
+
+    if let Err(e) = result {
         error!("Application failed to start: {}", e);
         std::process::exit(1);
     }
 }
+
+fn install_service(args: &[String]) -> Result<(), errors::AppError> {
+    let mut manager = service::ServiceManager::for_platform();
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--systemd" => manager = service::ServiceManager::Systemd,
+            "--launchd" => manager = service::ServiceManager::Launchd,
+            "--output" | "-o" => {
+                output = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    errors::AppError::Config("--output expects a path".to_string())
+                })?))
+            }
+            other => {
+                return Err(errors::AppError::Config(format!(
+                    "Unknown argument: {}",
+                    other
+                )));
+            }
+        }
+    }
+
+    let path = match output {
+        Some(path) => path,
+        None => manager.default_path()?,
+    };
+    let spec = service::ServiceSpec::current(Path::new(&get_flow_config_dir()))?;
+    service::install(manager, &spec, &path)?;
+
+    println!("Wrote {}", path.display());
+    println!("Enable it with: {}", manager.enable_hint(&path));
+    Ok(())
+}
+
+fn relocate_data(args: &[String]) -> Result<(), errors::AppError> {
+    let [new_dir] = args else {
+        return Err(errors::AppError::Config(
+            "Expected exactly one target directory".to_string(),
+        ));
+    };
+
+    let config = Config::from_env()?;
+    let config_dir = PathBuf::from(get_flow_config_dir());
+    // Holding the instance lock guarantees no node is using the stores
+    let _lock = daemon::InstanceLock::acquire(&config_dir)?;
+
+    let from = layout::DataLayout::from_config(&config);
+    let to = layout::relocate(&config_dir, &from, Path::new(new_dir))?;
+
+    println!("Data moved to {}", new_dir);
+    if let Some(url) = to.database_url() {
+        println!("  database: {}", url);
+    }
+    if let Some(kv) = &to.kv {
+        println!("  KV store: {}", kv.display());
+    }
+    println!(
+        "Set FLOW_DATA_DIR={} (and unset DATABASE_URL / KV_STORE_PATH) before starting the node",
+        new_dir
+    );
+    Ok(())
+}
+
+/// Switch column encryption to a fresh key and re-encrypt existing rows.
+/// Vault secrets are re-sealed when the node next starts.
+async fn rotate_column_key() -> Result<(), errors::AppError> {
+    let config = Config::from_env()?;
+    let config_dir = PathBuf::from(get_flow_config_dir());
+    let _lock = daemon::InstanceLock::acquire(&config_dir)?;
+
+    let node_data = init::initialize()?;
+    let db = node::runner::setup_database(&config.db).await?;
+    let keystore_dir = config_dir.join(init::KEYSTORE_DIR);
+
+    let keys = ColumnKeys::load(&keystore_dir, &node_data.private_key)?.rotate(&keystore_dir)?;
+    let rewritten = column_crypto::reencrypt_pass_keys(&db, &keys).await?;
+
+    println!(
+        "Active column key is now {}; re-encrypted {} rows",
+        keys.active_key_id(),
+        rewritten
+    );
+    Ok(())
+}
+
+/// Report storage engine internals of the stopped node, and what in them
+/// looks wrong
+async fn doctor() -> Result<(), errors::AppError> {
+    let config = Config::from_env()?;
+    let config_dir = PathBuf::from(get_flow_config_dir());
+    let _lock = daemon::InstanceLock::acquire(&config_dir)?;
+
+    let db = node::runner::setup_database(&config.db).await?;
+    let kv = node::runner::setup_kv_store(&config.kv).await?;
+    let stats = storage_stats::collect(&db, &kv).await?;
+
+    print!("{}", stats);
+    let warnings = stats.warnings();
+    if warnings.is_empty() {
+        println!("No storage issues found");
+    }
+    for warning in warnings {
+        println!("warning: {}", warning);
+    }
+    Ok(())
+}
+
+/// Export, import or check drift against a signed configuration profile
+fn config_profile(args: &[String]) -> Result<(), errors::AppError> {
+    let config_dir = PathBuf::from(get_flow_config_dir());
+    // Profiles are made from the same settings the node loads
+    dotenvy::dotenv().ok();
+
+    match args.first().map(String::as_str) {
+        Some("export") => {
+            let mut version = 1;
+            let mut output = None;
+            let mut args = args[1..].iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--version" => {
+                        version = args.next().and_then(|v| v.parse().ok()).ok_or_else(|| {
+                            errors::AppError::Config("--version expects a number".to_string())
+                        })?
+                    }
+                    "--output" | "-o" => {
+                        output = Some(PathBuf::from(args.next().ok_or_else(|| {
+                            errors::AppError::Config("--output expects a path".to_string())
+                        })?))
+                    }
+                    other => {
+                        return Err(errors::AppError::Config(format!(
+                            "Unknown argument: {}",
+                            other
+                        )));
+                    }
+                }
+            }
+
+            let node_data = init::initialize()?;
+            let signed = profile::export(&node_data, profile::current_settings(), version)?;
+            let json = serde_json::to_string_pretty(&signed)
+                .map_err(|e| errors::AppError::Config(e.to_string()))?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    println!(
+                        "Wrote profile version {} with {} settings to {}",
+                        version,
+                        signed.profile.settings.len(),
+                        path.display()
+                    );
+                }
+                None => println!("{}", json),
+            }
+            Ok(())
+        }
+        Some("import") => {
+            let Some(source) = args.get(1) else {
+                return Err(errors::AppError::Config(
+                    "Expected a profile to import".to_string(),
+                ));
+            };
+            let mut env_file = PathBuf::from(".env");
+            let mut trusted_key = None;
+            let mut args = args[2..].iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--env-file" => {
+                        env_file = PathBuf::from(args.next().ok_or_else(|| {
+                            errors::AppError::Config("--env-file expects a path".to_string())
+                        })?)
+                    }
+                    "--trust" => {
+                        trusted_key = Some(args.next().cloned().ok_or_else(|| {
+                            errors::AppError::Config("--trust expects a key".to_string())
+                        })?)
+                    }
+                    other => {
+                        return Err(errors::AppError::Config(format!(
+                            "Unknown argument: {}",
+                            other
+                        )));
+                    }
+                }
+            }
+
+            let signed: profile::SignedProfile = serde_json::from_slice(&std::fs::read(source)?)
+                .map_err(|e| errors::AppError::Config(format!("Invalid profile: {}", e)))?;
+            profile::import(&signed, trusted_key.as_deref(), &env_file, &config_dir)?;
+            println!(
+                "Imported profile version {} from {} into {}",
+                signed.profile.version,
+                signed.profile.issuer,
+                env_file.display()
+            );
+            println!("Restart the node to apply it");
+            Ok(())
+        }
+        Some("drift") => {
+            let Some(baseline) = profile::load_baseline(&config_dir)? else {
+                return Err(errors::AppError::NotFound(
+                    "No profile has been imported".to_string(),
+                ));
+            };
+            let drift = profile::drift(&baseline.profile, &profile::current_settings());
+            if drift.is_empty() {
+                println!(
+                    "Configuration matches profile version {}",
+                    baseline.profile.version
+                );
+                return Ok(());
+            }
+            for d in &drift {
+                println!(
+                    "{}: expected {}, found {}",
+                    d.key,
+                    d.expected.as_deref().unwrap_or("<unset>"),
+                    d.actual.as_deref().unwrap_or("<unset>")
+                );
+            }
+            std::process::exit(2);
+        }
+        _ => Err(errors::AppError::Config(
+            "Expected export, import or drift".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,408 @@
+//! Account deletion, as the GDPR's right to erasure asks.
+//!
+//! A user asks for deletion with `DELETE /api/v1/users/me`. The account is
+//! erased once [`RetentionConfig::account_deletion_grace`] has passed, which
+//! leaves time to take an [`AccountExport`] of its data or to cancel; with no
+//! grace it goes straight away. The runner erases accounts that are due along
+//! with its retention purge.
+//!
+//! Erasure removes the user's passkeys, DID and profile, sessions, spaces
+//! (records and Merkle indexes), KV namespace, key usage, change history,
+//! and cached resolutions of the DID. What survives:
+//!
+//! - files in space directories, which need not be the node's to delete; the
+//!   [`Erasure`] names them so the operator can,
+//! - signed public artifacts: space anchors, timestamp proofs and credentials
+//!   the node issued, which others may hold and rely on,
+//! - log lines, audit entries included, which are the log collector's.
+//!
+//! [`RetentionConfig::account_deletion_grace`]: crate::bootstrap::config::RetentionConfig::account_deletion_grace
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use entity::{pass_key, space, user};
+use errors::AppError;
+use log::{info, warn};
+use sea_orm::{ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use sled::Db;
+
+use crate::api::node::Node;
+use crate::modules::history;
+use crate::modules::key_usage::KeyStats;
+use crate::modules::merkle::MerkleIndex;
+use crate::modules::space as spaces;
+use crate::modules::ssi::did::resolvers::cache::KvDidCache;
+use crate::modules::tenancy::Actor;
+use crate::modules::versioning::update_versioned;
+
+pub const DELETION_TREE: &str = "account_deletions";
+
+const AUDIT: &str = "audit";
+
+/// A requested deletion waiting out its grace period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingDeletion {
+    pub user_id: i32,
+    pub requested_at: DateTime<Utc>,
+    pub erase_after: DateTime<Utc>,
+}
+
+/// What erasing an account removed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Erasure {
+    pub user_id: i32,
+    pub did: Option<String>,
+    pub passkeys: u64,
+    pub sessions: u64,
+    pub spaces: u64,
+    pub kv_keys: u64,
+    pub key_usage: u64,
+    pub did_cache: u64,
+    pub history: u64,
+    /// Directories of the erased spaces, left on disk
+    pub space_locations: Vec<String>,
+}
+
+/// Outcome of a deletion request
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum Deletion {
+    Scheduled(PendingDeletion),
+    Erased(Erasure),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserProfile {
+    pub id: i32,
+    pub did: String,
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub time_created: String,
+    pub last_login: String,
+    /// Send back with an edit, to refuse it if the profile changed since
+    pub version: i32,
+}
+
+impl From<user::Model> for UserProfile {
+    fn from(user: user::Model) -> Self {
+        Self {
+            id: user.id,
+            did: user.did,
+            username: user.username,
+            display_name: user.display_name,
+            avatar_url: user.avatar_url,
+            time_created: user.time_created.to_rfc3339(),
+            last_login: user.last_login.to_rfc3339(),
+            version: user.version,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyInfo {
+    /// Base64url credential ID
+    pub credential_id: String,
+    pub name: String,
+    pub time_created: String,
+    pub last_authenticated: String,
+    pub authentication_count: i32,
+    pub authenticator_attachment: Option<String>,
+    pub backup_eligible: bool,
+    pub backup_state: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpaceInfo {
+    pub key: String,
+    pub location: String,
+    pub time_created: String,
+}
+
+/// Everything the node holds about a user
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: UserProfile,
+    pub passkeys: Vec<PasskeyInfo>,
+    pub spaces: Vec<SpaceInfo>,
+    /// KV entries, values base64url
+    pub kv: BTreeMap<String, String>,
+    pub key_usage: Vec<KeyStats>,
+    pub pending_deletion: Option<PendingDeletion>,
+}
+
+/// Deletions waiting out their grace period
+pub struct AccountDeletions {
+    kv: Db,
+}
+
+impl AccountDeletions {
+    pub fn new(kv: Db) -> Self {
+        Self { kv }
+    }
+
+    /// Schedule `user_id`'s account for erasure after `grace`. Asking again
+    /// keeps the first request.
+    pub fn schedule(
+        &self,
+        user_id: i32,
+        now: DateTime<Utc>,
+        grace: Duration,
+    ) -> Result<PendingDeletion, AppError> {
+        if let Some(pending) = self.pending(user_id)? {
+            return Ok(pending);
+        }
+        let grace = chrono::Duration::from_std(grace)
+            .map_err(|e| AppError::Config(format!("Invalid deletion grace: {}", e)))?;
+        let pending = PendingDeletion {
+            user_id,
+            requested_at: now,
+            erase_after: now + grace,
+        };
+        let json = serde_json::to_vec(&pending).map_err(|e| AppError::Storage(Box::new(e)))?;
+        self.tree()?
+            .insert(user_id.to_string().as_bytes(), json)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        info!(target: AUDIT, "{} asked for account deletion, due {}", Actor::User(user_id), pending.erase_after);
+        Ok(pending)
+    }
+
+    pub fn pending(&self, user_id: i32) -> Result<Option<PendingDeletion>, AppError> {
+        self.tree()?
+            .get(user_id.to_string().as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .map(|bytes| serde_json::from_slice(&bytes).map_err(|e| AppError::Storage(Box::new(e))))
+            .transpose()
+    }
+
+    /// Withdraw a pending deletion. False if there was none.
+    pub fn cancel(&self, user_id: i32) -> Result<bool, AppError> {
+        let removed = self
+            .tree()?
+            .remove(user_id.to_string().as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if removed.is_some() {
+            info!(target: AUDIT, "{} cancelled account deletion", Actor::User(user_id));
+        }
+        Ok(removed.is_some())
+    }
+
+    /// Deletions whose grace has passed by `now`
+    pub fn due(&self, now: DateTime<Utc>) -> Result<Vec<PendingDeletion>, AppError> {
+        self.tree()?
+            .iter()
+            .values()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice::<PendingDeletion>(&value)
+                    .map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .filter(|pending| !matches!(pending, Ok(p) if p.erase_after > now))
+            .collect()
+    }
+
+    fn tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(DELETION_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+/// Ask for `user_id`'s account to be deleted; it is erased at once when the
+/// node allows no grace
+pub async fn request_deletion(
+    node: &Node,
+    user_id: i32,
+    now: DateTime<Utc>,
+) -> Result<Deletion, AppError> {
+    let pending =
+        node.account_deletions()
+            .schedule(user_id, now, node.retention.account_deletion_grace)?;
+    stamp_deleted(node, user_id, Some(pending.requested_at)).await?;
+    match pending.erase_after <= now {
+        true => Ok(Deletion::Erased(erase(node, user_id).await?)),
+        false => Ok(Deletion::Scheduled(pending)),
+    }
+}
+
+/// Withdraw `user_id`'s pending deletion. False if there was none.
+pub async fn cancel_deletion(node: &Node, user_id: i32) -> Result<bool, AppError> {
+    let cancelled = node.account_deletions().cancel(user_id)?;
+    if cancelled {
+        stamp_deleted(node, user_id, None).await?;
+    }
+    Ok(cancelled)
+}
+
+/// Record on the user's row when their deletion was asked for, if it is
+async fn stamp_deleted(
+    node: &Node,
+    user_id: i32,
+    deleted_at: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let changes = user::ActiveModel {
+        deleted_at: Set(deleted_at.map(Into::into)),
+        ..Default::default()
+    };
+    update_versioned::<user::Entity>(&node.db, user_id, None, changes).await?;
+    Ok(())
+}
+
+/// Erase the accounts whose grace has passed by `now`
+pub async fn erase_due(node: &Node, now: DateTime<Utc>) -> Result<Vec<Erasure>, AppError> {
+    let mut erased = Vec::new();
+    for pending in node.account_deletions().due(now)? {
+        erased.push(erase(node, pending.user_id).await?);
+    }
+    Ok(erased)
+}
+
+/// Everything the node holds about `user_id`
+pub async fn export(node: &Node, user_id: i32) -> Result<AccountExport, AppError> {
+    let user = find_user(node, user_id).await?;
+    let passkeys = user_passkeys(node, user_id).await?;
+    let spaces = spaces::list_spaces_for(&node.db, Actor::User(user_id)).await?;
+
+    let namespace = node.kv_for(Actor::User(user_id))?;
+    let mut kv = BTreeMap::new();
+    for key in namespace.keys()? {
+        if let Some(value) = namespace.get(&key)? {
+            kv.insert(key, URL_SAFE_NO_PAD.encode(value));
+        }
+    }
+
+    info!(target: AUDIT, "{} exported their account data", Actor::User(user_id));
+    Ok(AccountExport {
+        exported_at: Utc::now(),
+        user: user.into(),
+        passkeys: passkeys
+            .into_iter()
+            .map(|p| PasskeyInfo {
+                credential_id: URL_SAFE_NO_PAD.encode(&p.credential_id),
+                name: p.name,
+                time_created: p.time_created.to_rfc3339(),
+                last_authenticated: p.last_authenticated.to_rfc3339(),
+                authentication_count: p.authentication_count,
+                authenticator_attachment: p.authenticator_attachment,
+                backup_eligible: p.backup_eligible,
+                backup_state: p.backup_state,
+            })
+            .collect(),
+        spaces: spaces
+            .into_iter()
+            .map(|s| SpaceInfo {
+                key: s.key,
+                location: s.location,
+                time_created: s.time_created.to_rfc3339(),
+            })
+            .collect(),
+        kv,
+        key_usage: node.key_usage().stats(Some(user_id))?,
+        pending_deletion: node.account_deletions().pending(user_id)?,
+    })
+}
+
+/// Erase `user_id`'s account now. Sessions go first, so nothing more is done
+/// in the user's name while the rest is removed.
+pub async fn erase(node: &Node, user_id: i32) -> Result<Erasure, AppError> {
+    let actor = Actor::User(user_id);
+    let mut erasure = Erasure {
+        user_id,
+        ..Default::default()
+    };
+
+    erasure.sessions = node.user_sessions().close_all(user_id).await? as u64;
+
+    // Deleted spaces too, still waiting for the purge
+    let user_spaces = space::Entity::find()
+        .filter(space::Column::UserId.eq(user_id))
+        .all(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    for space in user_spaces {
+        MerkleIndex::drop_index(&node.kv, &space.key)?;
+        space::Entity::delete_by_id(space.id)
+            .exec(&node.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        erasure.space_locations.push(space.location);
+        erasure.spaces += 1;
+    }
+    erasure.kv_keys = node.kv_for(actor)?.erase(&node.kv)? as u64;
+    erasure.history = history::forget_owner(&node.db, user_id).await?;
+    erasure.key_usage = node.key_usage().forget_user(user_id)?;
+
+    erasure.passkeys = pass_key::Entity::delete_many()
+        .filter(pass_key::Column::UserId.eq(user_id))
+        .exec(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .rows_affected;
+
+    if let Some(user) = user::Entity::find_by_id(user_id)
+        .one(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+    {
+        erasure.did_cache = KvDidCache::open(&node.kv)
+            .and_then(|cache| cache.forget(&user.did))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        user::Entity::delete_by_id(user_id)
+            .exec(&node.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        erasure.did = Some(user.did);
+    }
+
+    node.account_deletions().cancel(user_id)?;
+
+    info!(
+        target: AUDIT,
+        "{} erased: {} passkeys, {} sessions, {} spaces, {} KV keys, {} key usage records, {} history entries",
+        actor,
+        erasure.passkeys,
+        erasure.sessions,
+        erasure.spaces,
+        erasure.kv_keys,
+        erasure.key_usage,
+        erasure.history
+    );
+    if !erasure.space_locations.is_empty() {
+        warn!(
+            target: AUDIT,
+            "Files of {}'s erased spaces are left in {}",
+            actor,
+            erasure.space_locations.join(", ")
+        );
+    }
+    Ok(erasure)
+}
+
+async fn find_user(node: &Node, user_id: i32) -> Result<user::Model, AppError> {
+    user::Entity::find_by_id(user_id)
+        .one(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .ok_or_else(|| AppError::NotFound(format!("User {} not found", user_id)))
+}
+
+async fn user_passkeys(node: &Node, user_id: i32) -> Result<Vec<pass_key::Model>, AppError> {
+    pass_key::Entity::find()
+        .filter(pass_key::Column::UserId.eq(user_id))
+        .all(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))
+}
@@ -0,0 +1,312 @@
+//! Minimal ACME (RFC 8555) client: account registration, orders,
+//! authorizations, finalization and certificate download. Requests are JWS
+//! signed with an ECDSA P-256 account key.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use errors::AppError;
+use reqwest::header::{CONTENT_TYPE, LOCATION};
+use ring::rand::SystemRandom;
+use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+const REPLAY_NONCE: &str = "replay-nonce";
+const BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+
+/// The ACME account key
+pub struct AccountKey {
+    pkcs8: Vec<u8>,
+    key_pair: EcdsaKeyPair,
+}
+
+impl AccountKey {
+    pub fn generate() -> Result<Self, AppError> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+                .map_err(|_| AppError::Crypto("Cannot generate ACME account key".to_string()))?;
+        Self::from_pkcs8(pkcs8.as_ref())
+    }
+
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<Self, AppError> {
+        let key_pair = EcdsaKeyPair::from_pkcs8(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            pkcs8,
+            &SystemRandom::new(),
+        )
+        .map_err(|e| AppError::Crypto(format!("Invalid ACME account key: {}", e)))?;
+        Ok(Self {
+            pkcs8: pkcs8.to_vec(),
+            key_pair,
+        })
+    }
+
+    pub fn pkcs8(&self) -> &[u8] {
+        &self.pkcs8
+    }
+
+    /// Public key as a JWK, members in the RFC 7638 thumbprint order
+    pub fn jwk(&self) -> Value {
+        // Uncompressed point: 0x04 || x || y
+        let point = self.key_pair.public_key().as_ref();
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        })
+    }
+
+    /// RFC 7638 thumbprint of the public key
+    pub fn thumbprint(&self) -> String {
+        // serde_json keeps keys sorted and adds no whitespace, as RFC 7638 requires
+        let canonical = self.jwk().to_string();
+        URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+    }
+
+    /// Flattened JWS over `payload`, an empty payload being a POST-as-GET
+    pub fn sign(&self, protected: &Value, payload: Option<&Value>) -> Result<Value, AppError> {
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let payload = payload
+            .map(|payload| URL_SAFE_NO_PAD.encode(payload.to_string()))
+            .unwrap_or_default();
+        let signature = self
+            .key_pair
+            .sign(
+                &SystemRandom::new(),
+                format!("{}.{}", protected, payload).as_bytes(),
+            )
+            .map_err(|_| AppError::Crypto("Cannot sign ACME request".to_string()))?;
+
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        }))
+    }
+
+    /// What a challenge for `token` is answered with
+    pub fn key_authorization(&self, token: &str) -> String {
+        format!("{}.{}", token, self.thumbprint())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Order {
+    pub status: String,
+    #[serde(default)]
+    pub authorizations: Vec<String>,
+    pub finalize: String,
+    pub certificate: Option<String>,
+    pub error: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Authorization {
+    pub status: String,
+    pub identifier: Identifier,
+    #[serde(default)]
+    pub challenges: Vec<Challenge>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Identifier {
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Challenge {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub url: String,
+    pub token: String,
+    pub status: String,
+}
+
+pub struct AcmeClient {
+    http: reqwest::Client,
+    directory: Directory,
+    key: AccountKey,
+    /// Account URL, once registered
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+impl AcmeClient {
+    pub async fn connect(directory_url: &str, key: AccountKey) -> Result<Self, AppError> {
+        let http = reqwest::Client::new();
+        let directory = http
+            .get(directory_url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(network_error)?
+            .json()
+            .await
+            .map_err(|e| AppError::Validation(format!("Invalid ACME directory: {}", e)))?;
+
+        Ok(Self {
+            http,
+            directory,
+            key,
+            kid: None,
+            nonce: None,
+        })
+    }
+
+    pub fn key(&self) -> &AccountKey {
+        &self.key
+    }
+
+    /// Register the account, or look up the existing one for this key
+    pub async fn register(&mut self, contact: Option<&str>) -> Result<(), AppError> {
+        let mut payload = json!({ "termsOfServiceAgreed": true });
+        if let Some(contact) = contact {
+            payload["contact"] = json!([format!("mailto:{}", contact)]);
+        }
+        let url = self.directory.new_account.clone();
+        let response = self.post(&url, Some(&payload)).await?;
+        self.kid = Some(location(&response)?);
+        Ok(())
+    }
+
+    /// Place an order for `domains`, returning its URL with it
+    pub async fn new_order(&mut self, domains: &[String]) -> Result<(String, Order), AppError> {
+        let identifiers: Vec<Value> = domains
+            .iter()
+            .map(|domain| json!({ "type": "dns", "value": domain }))
+            .collect();
+        let url = self.directory.new_order.clone();
+        let response = self
+            .post(&url, Some(&json!({ "identifiers": identifiers })))
+            .await?;
+        let order_url = location(&response)?;
+        Ok((order_url, parse(response).await?))
+    }
+
+    pub async fn order(&mut self, url: &str) -> Result<Order, AppError> {
+        parse(self.post(url, None).await?).await
+    }
+
+    pub async fn authorization(&mut self, url: &str) -> Result<Authorization, AppError> {
+        parse(self.post(url, None).await?).await
+    }
+
+    /// Tell the server a challenge is ready to be validated
+    pub async fn respond(&mut self, challenge_url: &str) -> Result<(), AppError> {
+        self.post(challenge_url, Some(&json!({}))).await?;
+        Ok(())
+    }
+
+    pub async fn finalize(&mut self, order: &Order, csr_der: &[u8]) -> Result<Order, AppError> {
+        let payload = json!({ "csr": URL_SAFE_NO_PAD.encode(csr_der) });
+        parse(self.post(&order.finalize, Some(&payload)).await?).await
+    }
+
+    /// PEM certificate chain of a valid order
+    pub async fn certificate(&mut self, url: &str) -> Result<String, AppError> {
+        self.post(url, None)
+            .await?
+            .text()
+            .await
+            .map_err(network_error)
+    }
+
+    async fn fresh_nonce(&mut self) -> Result<String, AppError> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = self
+            .http
+            .head(&self.directory.new_nonce)
+            .send()
+            .await
+            .map_err(network_error)?;
+        response
+            .headers()
+            .get(REPLAY_NONCE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| network_error("ACME server sent no nonce"))
+    }
+
+    /// Signed POST, retried once if the server rejects the nonce
+    async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<reqwest::Response, AppError> {
+        let mut retried = false;
+        loop {
+            let mut protected = json!({
+                "alg": "ES256",
+                "nonce": self.fresh_nonce().await?,
+                "url": url,
+            });
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.key.jwk(),
+            }
+            let body = self.key.sign(&protected, payload)?;
+
+            let response = self
+                .http
+                .post(url)
+                .header(CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(network_error)?;
+            self.nonce = response
+                .headers()
+                .get(REPLAY_NONCE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+
+            if response.status().is_success() {
+                return Ok(response);
+            }
+            let status = response.status();
+            let problem: Value = response.json().await.unwrap_or(Value::Null);
+            if problem["type"] == BAD_NONCE && !retried {
+                retried = true;
+                continue;
+            }
+            return Err(network_error(format!(
+                "ACME request to {} failed ({}): {}",
+                url,
+                status,
+                problem["detail"].as_str().unwrap_or("no detail")
+            )));
+        }
+    }
+}
+
+fn location(response: &reqwest::Response) -> Result<String, AppError> {
+    response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| network_error("ACME response has no Location"))
+}
+
+async fn parse<T: for<'de> Deserialize<'de>>(response: reqwest::Response) -> Result<T, AppError> {
+    response
+        .json()
+        .await
+        .map_err(|e| AppError::Validation(format!("Invalid ACME response: {}", e)))
+}
+
+fn network_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> AppError {
+    AppError::IO(std::io::Error::other(e))
+}
@@ -0,0 +1,267 @@
+//! Automatic TLS certificates from an ACME CA (Let's Encrypt by default) for
+//! nodes on public domains.
+//!
+//! The certificate and its key are kept under `acme/` in the config dir and
+//! served from a [`CertStore`]; renewal swaps the new certificate in without
+//! restarting the listeners. Domains are proven with HTTP-01 (answered on a
+//! plain HTTP port) or TLS-ALPN-01 (answered by the TLS listeners).
+
+pub mod client;
+pub mod store;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use log::{info, warn};
+use rcgen::{CertificateParams, KeyPair};
+
+use crate::bootstrap::config::{AcmeChallenge, AcmeConfig};
+use client::{AccountKey, AcmeClient, Authorization, Order};
+pub use store::CertStore;
+
+pub const ACME_DIR: &str = "acme";
+const ACCOUNT_KEY_FILE: &str = "account.pk8";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_POLLS: u32 = 60;
+
+/// Key authorizations for pending HTTP-01 challenges, by token
+#[derive(Debug, Clone, Default)]
+pub struct Http01Responses(Arc<RwLock<HashMap<String, String>>>);
+
+impl Http01Responses {
+    pub fn get(&self, token: &str) -> Option<String> {
+        self.0
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(token)
+            .cloned()
+    }
+
+    fn insert(&self, token: &str, key_authorization: String) {
+        self.0
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(token.to_string(), key_authorization);
+    }
+
+    fn remove(&self, token: &str) {
+        self.0
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(token);
+    }
+}
+
+pub struct AcmeManager {
+    config: AcmeConfig,
+    dir: PathBuf,
+    store: Arc<CertStore>,
+    http01: Http01Responses,
+}
+
+impl AcmeManager {
+    /// Manager for `config`, serving the certificate saved by a previous run
+    /// if there is one
+    pub fn new(config: AcmeConfig, config_dir: &Path) -> Result<Self, AppError> {
+        if !config.enabled() {
+            return Err(AppError::Config(
+                "FLOW_ACME_DOMAINS must be set".to_string(),
+            ));
+        }
+        let dir = config_dir.join(ACME_DIR);
+        let store = Arc::new(CertStore::default());
+        if let Some((cert_pem, key_pem)) = store::load(&dir)? {
+            match store.install(&cert_pem, &key_pem) {
+                Ok(()) => info!("Loaded TLS certificate for {}", config.domains.join(", ")),
+                Err(e) => warn!("Ignoring saved TLS certificate: {}", e),
+            }
+        }
+
+        Ok(Self {
+            config,
+            dir,
+            store,
+            http01: Http01Responses::default(),
+        })
+    }
+
+    pub fn store(&self) -> Arc<CertStore> {
+        self.store.clone()
+    }
+
+    pub fn http01(&self) -> Http01Responses {
+        self.http01.clone()
+    }
+
+    /// Whether there is no certificate, or it expires within `renew_before`
+    pub fn renewal_due(&self, now: DateTime<Utc>) -> bool {
+        let renew_before =
+            chrono::Duration::from_std(self.config.renew_before).unwrap_or(chrono::Duration::MAX);
+        self.store
+            .not_after()
+            .is_none_or(|not_after| not_after - renew_before <= now)
+    }
+
+    /// Obtain a certificate for the configured domains, save it and start
+    /// serving it
+    pub async fn issue(&self) -> Result<(), AppError> {
+        info!(
+            "Requesting TLS certificate for {}",
+            self.config.domains.join(", ")
+        );
+        let mut client =
+            AcmeClient::connect(&self.config.directory_url, self.account_key()?).await?;
+        client.register(self.config.contact.as_deref()).await?;
+
+        let (order_url, order) = client.new_order(&self.config.domains).await?;
+        for url in &order.authorizations {
+            self.authorize(&mut client, url).await?;
+        }
+        let order = poll_order(&mut client, &order_url, &["ready"]).await?;
+
+        let failed = |e: rcgen::Error| AppError::Crypto(format!("Cannot create CSR: {}", e));
+        let key_pair = KeyPair::generate().map_err(failed)?;
+        let csr = CertificateParams::new(self.config.domains.clone())
+            .map_err(failed)?
+            .serialize_request(&key_pair)
+            .map_err(failed)?;
+        client.finalize(&order, csr.der()).await?;
+        let order = poll_order(&mut client, &order_url, &["valid"]).await?;
+        let certificate_url = order.certificate.ok_or_else(|| {
+            AppError::Validation("ACME order is valid but has no certificate".to_string())
+        })?;
+
+        let cert_pem = client.certificate(&certificate_url).await?;
+        let key_pem = key_pair.serialize_pem();
+        self.store.install(&cert_pem, &key_pem)?;
+        store::save(&self.dir, &cert_pem, &key_pem)?;
+        info!(
+            "TLS certificate installed, valid until {}",
+            self.store
+                .not_after()
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default()
+        );
+        Ok(())
+    }
+
+    /// Complete one authorization with the configured challenge type
+    async fn authorize(&self, client: &mut AcmeClient, url: &str) -> Result<(), AppError> {
+        let authorization = client.authorization(url).await?;
+        if authorization.status == "valid" {
+            return Ok(());
+        }
+        let kind = self.config.challenge.as_str();
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|c| c.kind == kind)
+            .ok_or_else(|| {
+                AppError::Validation(format!(
+                    "ACME server offers no {} challenge for {}",
+                    kind, authorization.identifier.value
+                ))
+            })?;
+
+        let domain = &authorization.identifier.value;
+        let key_authorization = client.key().key_authorization(&challenge.token);
+        match self.config.challenge {
+            AcmeChallenge::Http01 => self.http01.insert(&challenge.token, key_authorization),
+            AcmeChallenge::TlsAlpn01 => self.store.add_challenge(domain, &key_authorization)?,
+        }
+
+        let result = async {
+            client.respond(&challenge.url).await?;
+            poll_authorization(client, url).await
+        }
+        .await;
+
+        match self.config.challenge {
+            AcmeChallenge::Http01 => self.http01.remove(&challenge.token),
+            AcmeChallenge::TlsAlpn01 => self.store.remove_challenge(domain),
+        }
+        result
+    }
+
+    /// The saved account key, or a new one saved for next time
+    fn account_key(&self) -> Result<AccountKey, AppError> {
+        let path = self.dir.join(ACCOUNT_KEY_FILE);
+        if path.exists() {
+            return AccountKey::from_pkcs8(&std::fs::read(&path)?);
+        }
+        let key = AccountKey::generate()?;
+        std::fs::create_dir_all(&self.dir)?;
+        store::write_private(&path, key.pkcs8())?;
+        Ok(key)
+    }
+
+    /// Keep the certificate renewed until the task is dropped
+    pub async fn run(self) {
+        loop {
+            let wait = if self.renewal_due(Utc::now()) {
+                match self.issue().await {
+                    Ok(()) => CHECK_INTERVAL,
+                    Err(e) => {
+                        warn!("TLS certificate request failed: {}", e);
+                        RETRY_INTERVAL
+                    }
+                }
+            } else {
+                CHECK_INTERVAL
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+async fn poll_authorization(client: &mut AcmeClient, url: &str) -> Result<(), AppError> {
+    for _ in 0..MAX_POLLS {
+        let Authorization {
+            status, identifier, ..
+        } = client.authorization(url).await?;
+        match status.as_str() {
+            "valid" => return Ok(()),
+            "pending" | "processing" => tokio::time::sleep(POLL_INTERVAL).await,
+            _ => {
+                return Err(AppError::Auth(format!(
+                    "ACME validation of {} failed: {}",
+                    identifier.value, status
+                )));
+            }
+        }
+    }
+    Err(AppError::Auth(
+        "ACME validation did not complete in time".to_string(),
+    ))
+}
+
+/// Poll an order until it reaches one of `wanted`
+async fn poll_order(
+    client: &mut AcmeClient,
+    url: &str,
+    wanted: &[&str],
+) -> Result<Order, AppError> {
+    for _ in 0..MAX_POLLS {
+        let order = client.order(url).await?;
+        if wanted.contains(&order.status.as_str()) {
+            return Ok(order);
+        }
+        if order.status == "invalid" {
+            return Err(AppError::Auth(format!(
+                "ACME order failed: {}",
+                order.error.map(|e| e.to_string()).unwrap_or_default()
+            )));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Err(AppError::Auth(
+        "ACME order did not complete in time".to_string(),
+    ))
+}
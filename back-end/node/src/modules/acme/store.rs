@@ -0,0 +1,168 @@
+//! Certificates served by the TLS listeners. A renewed certificate replaces
+//! the current one in place; connections already open keep the old one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use rcgen::{CertificateParams, CustomExtension, KeyPair};
+use rustls::ServerConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use sha2::{Digest, Sha256};
+
+/// ALPN protocol TLS-ALPN-01 validation connects with
+pub const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+pub const CERT_FILE: &str = "cert.pem";
+pub const KEY_FILE: &str = "key.pem";
+
+/// Issued certificate plus any TLS-ALPN-01 challenge certificates
+#[derive(Debug, Default)]
+pub struct CertStore {
+    current: RwLock<Option<(Arc<CertifiedKey>, DateTime<Utc>)>>,
+    /// Challenge certificate by domain
+    challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl CertStore {
+    /// Serve `cert_pem` (leaf first) with `key_pem` from now on
+    pub fn install(&self, cert_pem: &str, key_pem: &str) -> Result<(), AppError> {
+        let invalid = |e: String| AppError::Validation(format!("Invalid certificate: {}", e));
+
+        let chain = CertificateDer::pem_slice_iter(cert_pem.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| invalid(e.to_string()))?;
+        let leaf = chain
+            .first()
+            .ok_or_else(|| invalid("no certificate in PEM".to_string()))?;
+        let (_, parsed) =
+            x509_parser::parse_x509_certificate(leaf).map_err(|e| invalid(e.to_string()))?;
+        let not_after = DateTime::from_timestamp(parsed.validity().not_after.timestamp(), 0)
+            .ok_or_else(|| invalid("expiry out of range".to_string()))?;
+
+        let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes())
+            .map_err(|e| invalid(e.to_string()))?;
+        let certified = certified_key(chain, &key)?;
+
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) =
+            Some((Arc::new(certified), not_after));
+        Ok(())
+    }
+
+    /// Expiry of the certificate being served
+    pub fn not_after(&self) -> Option<DateTime<Utc>> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|(_, not_after)| *not_after)
+    }
+
+    /// Answer TLS-ALPN-01 validation for `domain` with `key_authorization`
+    pub fn add_challenge(&self, domain: &str, key_authorization: &str) -> Result<(), AppError> {
+        let failed = |e: rcgen::Error| {
+            AppError::Crypto(format!("Cannot create challenge certificate: {}", e))
+        };
+
+        let key_pair = KeyPair::generate().map_err(failed)?;
+        let mut params = CertificateParams::new(vec![domain.to_string()]).map_err(failed)?;
+        params.custom_extensions = vec![CustomExtension::new_acme_identifier(&Sha256::digest(
+            key_authorization.as_bytes(),
+        ))];
+        let cert = params.self_signed(&key_pair).map_err(failed)?;
+
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+        let certified = certified_key(vec![cert.der().clone()], &key)?;
+        self.challenges
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(domain.to_string(), Arc::new(certified));
+        Ok(())
+    }
+
+    pub fn remove_challenge(&self, domain: &str) {
+        self.challenges
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(domain);
+    }
+
+    /// Server config resolving certificates from this store
+    pub fn server_config(self: &Arc<Self>) -> Result<Arc<ServerConfig>, AppError> {
+        let mut config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .map_err(|e| AppError::Crypto(format!("TLS configuration failed: {}", e)))?
+                .with_no_client_auth()
+                .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"http/1.1".to_vec(), ACME_TLS_ALPN.to_vec()];
+        Ok(Arc::new(config))
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let validating = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|p| p == ACME_TLS_ALPN));
+        if validating {
+            let domain = client_hello.server_name()?;
+            return self
+                .challenges
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(domain)
+                .cloned();
+        }
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|(certified, _)| certified.clone())
+    }
+}
+
+fn certified_key(
+    chain: Vec<CertificateDer<'static>>,
+    key: &PrivateKeyDer<'_>,
+) -> Result<CertifiedKey, AppError> {
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(key)
+        .map_err(|e| AppError::Crypto(format!("Unsupported certificate key: {}", e)))?;
+    Ok(CertifiedKey::new(chain, signing_key))
+}
+
+/// Certificate and key saved by a previous run, if any
+pub fn load(dir: &Path) -> Result<Option<(String, String)>, AppError> {
+    let cert = dir.join(CERT_FILE);
+    let key = dir.join(KEY_FILE);
+    if !cert.exists() || !key.exists() {
+        return Ok(None);
+    }
+    Ok(Some((fs::read_to_string(cert)?, fs::read_to_string(key)?)))
+}
+
+/// Persist a certificate and its key, the key readable by the owner only
+pub fn save(dir: &Path, cert_pem: &str, key_pem: &str) -> Result<(), AppError> {
+    fs::create_dir_all(dir)?;
+    write_private(&dir.join(KEY_FILE), key_pem.as_bytes())?;
+    fs::write(dir.join(CERT_FILE), cert_pem)?;
+    Ok(())
+}
+
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> Result<(), AppError> {
+    let mut tmp = tempfile::NamedTempFile::new_in(path.parent().unwrap_or(Path::new(".")))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(tmp.path(), fs::Permissions::from_mode(0o600))?;
+    }
+    tmp.write_all(contents)?;
+    tmp.persist(path).map_err(|e| AppError::IO(e.error))?;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Anchors of space states in self-issued credentials.
+//!
+//! Anchoring a space rebuilds its Merkle tree and has the node issue a
+//! credential about itself stating the space's root at that moment. Anyone
+//! holding the credential can check the node's signature on it, and a file's
+//! leaf and the hashes on its path to the root then show the file was in the
+//! space, unchanged, when the credential was issued.
+//!
+//! With a timestamp authority configured, each anchor is also timestamped
+//! (RFC 3161), so its time doesn't rest on the node's clock alone.
+//!
+//! Anchors are kept in sled, one tree per space, oldest first. Scheduling
+//! [`TaskAction::AnchorSpace`](crate::modules::scheduler::TaskAction) anchors
+//! a space periodically.
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use sled::Db;
+
+use crate::modules::ssi::vc::IssuedCredential;
+use crate::modules::timestamp::TimestampProof;
+
+/// Credential type of an anchor, besides `VerifiableCredential`
+pub const ANCHOR_TYPE: &str = "SpaceStateCredential";
+/// Anchors kept per space; older ones are dropped
+pub const MAX_ANCHORS: usize = 1000;
+
+/// A space's Merkle root, attested by the node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpaceAnchor {
+    pub space_key: String,
+    pub merkle_root: String,
+    pub anchored_at: DateTime<Utc>,
+    pub credential: IssuedCredential,
+    /// Timestamp of the SHA-256 of the credential's JWT, when the node has a
+    /// timestamp authority
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<TimestampProof>,
+}
+
+/// Claims of an anchor credential, made about the node itself
+pub fn claims(space_key: &str, merkle_root: &str) -> Map<String, Value> {
+    let mut claims = Map::new();
+    claims.insert(
+        "space".to_string(),
+        json!({ "key": space_key, "merkleRoot": merkle_root }),
+    );
+    claims
+}
+
+/// A space's anchors
+pub struct AnchorLog {
+    tree: sled::Tree,
+}
+
+impl AnchorLog {
+    pub fn open(kv: &Db, space_key: &str) -> Result<Self, AppError> {
+        let tree = kv
+            .open_tree(format!("anchors:{}", space_key))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(Self { tree })
+    }
+
+    pub fn record(&self, anchor: &SpaceAnchor) -> Result<(), AppError> {
+        let key = format!("{:020}", anchor.anchored_at.timestamp_micros());
+        let json = serde_json::to_vec(anchor).map_err(|e| AppError::Storage(Box::new(e)))?;
+        self.tree
+            .insert(key.as_bytes(), json)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        let excess = self.tree.len().saturating_sub(MAX_ANCHORS);
+        for key in self.tree.iter().keys().take(excess) {
+            let key = key.map_err(|e| AppError::Storage(Box::new(e)))?;
+            self.tree
+                .remove(key)
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Anchors, newest first
+    pub fn list(&self) -> Result<Vec<SpaceAnchor>, AppError> {
+        self.tree
+            .iter()
+            .values()
+            .rev()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .collect()
+    }
+
+    pub fn latest(&self) -> Result<Option<SpaceAnchor>, AppError> {
+        match self
+            .tree
+            .last()
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+        {
+            Some((_, value)) => serde_json::from_slice(&value)
+                .map(Some)
+                .map_err(|e| AppError::Storage(Box::new(e))),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Tar archives of a folder in a space, written as they are downloaded.
+//!
+//! The files are listed up front, which gives the size estimate, and then
+//! read one after another into the response: the archive is never staged on
+//! disk or held in memory whole. A client that goes away closes the stream,
+//! and the writer stops at its next chunk. Files are taken the way the
+//! manifest takes them: regular files only, symlinks skipped, and decrypted
+//! in encrypted spaces.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use errors::AppError;
+use futures_util::Stream;
+use log::{info, warn};
+use tokio::sync::mpsc;
+
+use crate::modules::space_crypto::{self, SpaceCipher};
+
+/// Response header carrying [`Archive::estimated_size`]; the body is
+/// streamed, so it has no length up front
+pub const SIZE_ESTIMATE_HEADER: &str = "x-flow-archive-size";
+
+const BLOCK: u64 = 512;
+/// Bytes sent to the client at a time
+const CHUNK_BYTES: usize = 64 * 1024;
+/// Chunks written ahead of the client
+const CHUNKS_AHEAD: usize = 4;
+/// Longest name that fits a tar header; longer ones take an extra entry
+const HEADER_NAME_BYTES: usize = 100;
+
+/// A folder of a space, ready to be archived
+pub struct Archive {
+    space_key: String,
+    files: Vec<ArchiveFile>,
+    cipher: Option<SpaceCipher>,
+}
+
+struct ArchiveFile {
+    /// Where it goes in the archive, relative to the folder
+    name: String,
+    /// Where it sits in the space, which an encrypted file is bound to
+    in_space: String,
+    file: PathBuf,
+    size: u64,
+    modified: u64,
+}
+
+impl Archive {
+    /// List the files under `folder`, which sits at `sub_path` in the space.
+    /// With the space's `cipher`, files are archived decrypted.
+    pub fn of_folder(
+        space_key: &str,
+        folder: &Path,
+        sub_path: &str,
+        cipher: Option<SpaceCipher>,
+    ) -> Result<Self, AppError> {
+        let mut files = Vec::new();
+        collect_files(folder, folder, sub_path, cipher.is_some(), &mut files)?;
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self {
+            space_key: space_key.to_string(),
+            files,
+            cipher,
+        })
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Bytes the archive comes to, unless files change while it is written
+    pub fn estimated_size(&self) -> u64 {
+        let entries: u64 = self
+            .files
+            .iter()
+            .map(|file| {
+                let long_name = match file.name.len() {
+                    len if len > HEADER_NAME_BYTES => BLOCK + padded(len as u64 + 1),
+                    _ => 0,
+                };
+                long_name + BLOCK + padded(file.size)
+            })
+            .sum();
+        // Two zero blocks end the archive
+        entries + 2 * BLOCK
+    }
+
+    /// Write the whole archive into `out`
+    pub fn write(&self, out: impl Write) -> Result<(), AppError> {
+        let mut builder = tar::Builder::new(out);
+        self.append_files(&mut builder)?;
+        builder.into_inner()?.flush()?;
+        Ok(())
+    }
+
+    /// The archive as a stream of chunks, written on a blocking thread as
+    /// the stream is read. Dropping the stream stops the writer.
+    pub fn stream(self) -> impl Stream<Item = io::Result<Vec<u8>>> + Send + 'static {
+        let (sender, receiver) = mpsc::channel(CHUNKS_AHEAD);
+        tokio::task::spawn_blocking(move || {
+            let out = io::BufWriter::with_capacity(CHUNK_BYTES, ChunkSender(sender.clone()));
+            let mut builder = tar::Builder::new(out);
+            let written = match self.append_files(&mut builder) {
+                Ok(()) => builder
+                    .into_inner()
+                    .and_then(|mut out| out.flush())
+                    .map_err(AppError::from),
+                Err(e) => Err(e),
+            };
+            match written {
+                Ok(()) => info!(
+                    "Sent archive of {} files from space {}",
+                    self.files.len(),
+                    self.space_key
+                ),
+                Err(_) if sender.is_closed() => {
+                    info!("Archive download from space {} cancelled", self.space_key)
+                }
+                // Fail the response, so a cut-short archive isn't taken as whole
+                Err(e) => {
+                    warn!("Archive of space {} failed: {}", self.space_key, e);
+                    let _ = sender.blocking_send(Err(io::Error::other(e.to_string())));
+                }
+            }
+        });
+
+        futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        })
+    }
+
+    fn append_files<W: Write>(&self, builder: &mut tar::Builder<W>) -> Result<(), AppError> {
+        for file in &self.files {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(0o644);
+            header.set_mtime(file.modified);
+            header.set_size(file.size);
+
+            let appended = match &self.cipher {
+                Some(cipher) => {
+                    let plaintext = cipher.open_file(&file.in_space, &file.file)?;
+                    builder.append_data(&mut header, &file.name, Exactly::new(plaintext, file))
+                }
+                None => {
+                    let contents = io::BufReader::new(fs::File::open(&file.file)?);
+                    builder.append_data(&mut header, &file.name, Exactly::new(contents, file))
+                }
+            };
+            appended.map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidData => AppError::Crypto(e.to_string()),
+                _ => AppError::IO(e),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Files under `dir`, named relative to `root`, which sits at `sub_path` in
+/// the space
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    sub_path: &str,
+    encrypted: bool,
+    files: &mut Vec<ArchiveFile>,
+) -> Result<(), AppError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            collect_files(root, &path, sub_path, encrypted, files)?;
+        } else if file_type.is_file() {
+            let name = path
+                .strip_prefix(root)
+                .map_err(|e| AppError::IO(io::Error::other(e)))?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            let in_space = match sub_path.trim_matches('/') {
+                "" => name.clone(),
+                folder => format!("{}/{}", folder, name),
+            };
+
+            let metadata = entry.metadata()?;
+            let size = match encrypted {
+                true => space_crypto::plaintext_len(metadata.len()).ok_or_else(|| {
+                    AppError::Crypto(format!("{} is corrupt or not from this space", in_space))
+                })?,
+                false => metadata.len(),
+            };
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+
+            files.push(ArchiveFile {
+                name,
+                in_space,
+                file: path,
+                size,
+                modified,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn padded(size: u64) -> u64 {
+    size.div_ceil(BLOCK) * BLOCK
+}
+
+/// Reads exactly the size a file was listed with, failing if it has since
+/// shrunk; a tar entry's size is written before its contents
+struct Exactly<'a, R> {
+    inner: io::Take<R>,
+    file: &'a ArchiveFile,
+}
+
+impl<'a, R: Read> Exactly<'a, R> {
+    fn new(inner: R, file: &'a ArchiveFile) -> Self {
+        Self {
+            inner: inner.take(file.size),
+            file,
+        }
+    }
+}
+
+impl<R: Read> Read for Exactly<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if len == 0 && !buf.is_empty() && self.inner.limit() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} changed while it was archived", self.file.in_space),
+            ));
+        }
+        Ok(len)
+    }
+}
+
+/// Hands what the archive writer writes to the stream
+struct ChunkSender(mpsc::Sender<io::Result<Vec<u8>>>);
+
+impl Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(buf.to_vec()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Archive download went away"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! Node credential: proof that a node holds its identity key.
+//!
+//! A peer or relying service sends the node a fresh challenge. The node signs
+//! a statement binding the challenge, the audience it is answering and its
+//! DID with its Ed25519 identity key. The verifier checks the signature, that
+//! the key is the one the `did:key` names, and that the challenge is the one
+//! it issued.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Signer;
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+
+use crate::bootstrap::init::NodeData;
+use crate::modules::updater::CURRENT_VERSION;
+use crate::modules::{auth_crypto, manifest};
+
+pub const ATTESTATION_SCHEMA: &str = "flow-node-attestation/v1";
+pub const MIN_CHALLENGE_LENGTH: usize = 16;
+pub const MAX_CHALLENGE_LENGTH: usize = 512;
+
+/// What the node signs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationStatement {
+    pub schema: String,
+    /// Challenge issued by the verifier, as given
+    pub challenge: String,
+    /// Who the statement is meant for, so it can't be replayed to another verifier
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    /// DID of the attesting node
+    pub node: String,
+    /// Multibase (base58btc, ed25519-pub multicodec) encoded identity key
+    pub public_key_multibase: String,
+    pub software_version: String,
+    pub issued_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeAttestation {
+    pub statement: AttestationStatement,
+    /// Multibase (base58btc) Ed25519 signature over the canonical statement
+    pub signature: String,
+}
+
+/// Sign a verifier's challenge with the node identity key
+pub fn attest(
+    node_data: &NodeData,
+    challenge: &str,
+    audience: Option<&str>,
+) -> Result<NodeAttestation, AppError> {
+    if !(MIN_CHALLENGE_LENGTH..=MAX_CHALLENGE_LENGTH).contains(&challenge.len()) {
+        return Err(AppError::Validation(format!(
+            "Challenge must be between {} and {} characters",
+            MIN_CHALLENGE_LENGTH, MAX_CHALLENGE_LENGTH
+        )));
+    }
+
+    let signing_key = manifest::signing_key(node_data)?;
+    let statement = AttestationStatement {
+        schema: ATTESTATION_SCHEMA.to_string(),
+        challenge: challenge.to_string(),
+        audience: audience.map(str::to_string),
+        node: node_data.id.clone(),
+        public_key_multibase: manifest::encode_public_key(&signing_key.verifying_key()),
+        software_version: CURRENT_VERSION.to_string(),
+        issued_at: Utc::now().to_rfc3339(),
+    };
+    let signature = signing_key.sign(&manifest::canonical_bytes(&statement)?);
+
+    Ok(NodeAttestation {
+        statement,
+        signature: manifest::encode_signature(&signature),
+    })
+}
+
+/// Check an attestation answers `challenge` (for `audience`, if given) and
+/// was signed by the key its `did:key` names no more than `max_age` ago.
+/// Returns the attesting node's DID.
+pub fn verify_attestation(
+    attestation: &NodeAttestation,
+    challenge: &str,
+    audience: Option<&str>,
+    max_age: Duration,
+) -> Result<String, AppError> {
+    let statement = &attestation.statement;
+    if statement.schema != ATTESTATION_SCHEMA {
+        return Err(AppError::Validation(format!(
+            "Unsupported attestation schema: {}",
+            statement.schema
+        )));
+    }
+    if !auth_crypto::eq(&statement.challenge, challenge) {
+        return Err(AppError::Auth(
+            "Attestation answers a different challenge".to_string(),
+        ));
+    }
+    if statement.audience.as_deref() != audience {
+        return Err(AppError::Auth(
+            "Attestation was made for a different audience".to_string(),
+        ));
+    }
+    if statement.node != format!("did:key:{}", statement.public_key_multibase) {
+        return Err(AppError::Auth(
+            "Attestation key does not belong to the node DID".to_string(),
+        ));
+    }
+
+    let issued_at = DateTime::parse_from_rfc3339(&statement.issued_at)
+        .map_err(|e| AppError::Validation(format!("Invalid issuedAt: {}", e)))?;
+    let age = Utc::now().signed_duration_since(issued_at);
+    if age > chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX) {
+        return Err(AppError::Auth("Attestation has expired".to_string()));
+    }
+
+    let valid = manifest::verify_with_key(
+        &statement.public_key_multibase,
+        &manifest::canonical_bytes(statement)?,
+        &attestation.signature,
+    )?;
+    if !valid {
+        return Err(AppError::Auth("Invalid attestation signature".to_string()));
+    }
+
+    Ok(statement.node.clone())
+}
@@ -0,0 +1,25 @@
+//! Comparing and looking up secrets without leaking them through timing.
+//!
+//! Session tokens, unlock tokens and challenge identifiers are bearer
+//! secrets: compare them with [`eq`], never `==`, which returns at the first
+//! differing byte. So do digests checked against what was signed. Where a
+//! secret is looked up in a store, look it up by [`lookup_key`] instead, so
+//! what an index comparison can leak is a prefix of a digest rather than of
+//! the secret.
+
+use subtle::ConstantTimeEq;
+
+const LOOKUP_CONTEXT: &str = "flow auth lookup key v1";
+
+/// Whether `a` and `b` are equal, taking the same time wherever they differ.
+/// Only their lengths can be told apart.
+pub fn eq(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    a.as_ref().ct_eq(b.as_ref()).into()
+}
+
+/// Digest of `secret` to store and look it up by
+pub fn lookup_key(secret: &str) -> String {
+    blake3::Hash::from(blake3::derive_key(LOOKUP_CONTEXT, secret.as_bytes()))
+        .to_hex()
+        .to_string()
+}
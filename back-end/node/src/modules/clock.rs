@@ -0,0 +1,179 @@
+//! Where the node gets the time from.
+//!
+//! Code that checks expiry reads the time through a [`TimeSource`]: a
+//! [`Clock`], the [`SystemClock`] unless a test sets a [`TestClock`], and how
+//! far clocks may be off from each other (`FLOW_CLOCK_SKEW_SECS`). Session
+//! token expiry, WebAuthn challenge TTLs and credential validity periods
+//! allow for that skew, so a client or issuer whose clock is a little off
+//! isn't turned away.
+//!
+//! At startup the node asks an NTP server (`FLOW_NTP_SERVER`) for the time
+//! and warns if its own clock is off by more than the allowed skew.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use log::{info, warn};
+use tokio::net::UdpSocket;
+
+/// A source of the current time
+pub trait Clock: Send + Sync + Debug {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The operating system's clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the time.
+#[derive(Debug, Clone)]
+pub struct TestClock(Arc<Mutex<DateTime<Utc>>>);
+
+impl TestClock {
+    pub fn at(time: DateTime<Utc>) -> Self {
+        Self(Arc::new(Mutex::new(time)))
+    }
+
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = time;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut time = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *time = chrono::Duration::from_std(by)
+            .ok()
+            .and_then(|by| time.checked_add_signed(by))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// A clock, and how far other clocks may be off from it
+#[derive(Debug, Clone)]
+pub struct TimeSource {
+    pub clock: SharedClock,
+    pub max_skew: Duration,
+}
+
+impl TimeSource {
+    pub fn new(clock: SharedClock, max_skew: Duration) -> Self {
+        Self { clock, max_skew }
+    }
+
+    /// The system clock, allowing no skew
+    pub fn system() -> Self {
+        Self::new(Arc::new(SystemClock), Duration::ZERO)
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Whether `deadline` has passed, even if the clock that set it was off
+    /// by the allowed skew
+    pub fn has_passed(&self, deadline: DateTime<Utc>) -> bool {
+        deadline
+            .checked_add_signed(self.skew())
+            .is_some_and(|end| end <= self.now())
+    }
+
+    /// Whether `start` is still to come, even if the clock that set it was
+    /// off by the allowed skew
+    pub fn is_ahead(&self, start: DateTime<Utc>) -> bool {
+        self.now()
+            .checked_add_signed(self.skew())
+            .is_some_and(|latest| start > latest)
+    }
+
+    fn skew(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.max_skew).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+impl Default for TimeSource {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch
+const NTP_EPOCH_OFFSET: i64 = 2_208_988_800;
+
+/// How far `clock` is behind the time an SNTP (RFC 4330) `server` gives;
+/// negative when it is ahead
+pub async fn ntp_offset(
+    server: &str,
+    clock: &dyn Clock,
+    timeout: Duration,
+) -> Result<chrono::Duration, AppError> {
+    let failed = |e: String| {
+        AppError::IO(std::io::Error::other(format!(
+            "NTP query to {}: {}",
+            server, e
+        )))
+    };
+    let io = |e: std::io::Error| failed(e.to_string());
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(io)?;
+    socket.connect(server).await.map_err(io)?;
+
+    // Client request, version 3
+    let mut packet = [0u8; 48];
+    packet[0] = 0b00_011_011;
+    let sent = clock.now();
+    socket.send(&packet).await.map_err(io)?;
+    let received = tokio::time::timeout(timeout, socket.recv(&mut packet))
+        .await
+        .map_err(|_| failed("timed out".to_string()))?
+        .map_err(io)?;
+    let arrived = clock.now();
+    if received < 48 || packet[0] & 0b111 != 4 {
+        return Err(failed("invalid response".to_string()));
+    }
+
+    let timestamp = |at: usize| {
+        let seconds = u32::from_be_bytes(packet[at..at + 4].try_into().unwrap_or_default());
+        let fraction = u32::from_be_bytes(packet[at + 4..at + 8].try_into().unwrap_or_default());
+        let nanos = (u64::from(fraction) * 1_000_000_000) >> 32;
+        DateTime::from_timestamp(i64::from(seconds) - NTP_EPOCH_OFFSET, nanos as u32)
+            .ok_or_else(|| failed("invalid time".to_string()))
+    };
+    let (server_received, server_sent) = (timestamp(32)?, timestamp(40)?);
+    Ok(((server_received - sent) + (server_sent - arrived)) / 2)
+}
+
+/// Compare `time`'s clock with `server`'s, warning if it is off by more than
+/// the allowed skew. A server that can't be reached is only logged.
+pub async fn check_drift(server: &str, time: &TimeSource) {
+    match ntp_offset(server, time.clock.as_ref(), Duration::from_secs(5)).await {
+        Ok(offset) if offset.abs().to_std().unwrap_or(Duration::MAX) > time.max_skew => {
+            warn!(
+                "Clock is off by {}ms from {}, more than the {}s allowed; expiry checks may fail",
+                offset.num_milliseconds(),
+                server,
+                time.max_skew.as_secs()
+            );
+        }
+        Ok(offset) => info!(
+            "Clock is within {}ms of {}",
+            offset.num_milliseconds().abs(),
+            server
+        ),
+        Err(e) => info!("Clock drift not checked: {}", e),
+    }
+}
@@ -0,0 +1,314 @@
+//! At-rest encryption of sensitive DB columns (currently `pass_key.json_data`).
+//!
+//! Each value is sealed with XChaCha20-Poly1305 under a key derived per
+//! column and per user from a master key. Master key `k0` is derived from the
+//! node identity key; rotation adds random master keys to
+//! `keystore/column_keys.json` and re-encrypts rows under the newest one.
+//! Stored values look like `enc:v1:<key id>:<base64 nonce||ciphertext>`.
+//! Rotating the identity key first pins `k0` in that file, since it is
+//! derived from the key being retired.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use entity::cipher::{CipherError, ColumnCipher};
+use entity::pass_key;
+use errors::AppError;
+use log::info;
+use rand::RngCore;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseConnection, EntityTrait};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+pub const KEYS_FILE: &str = "column_keys.json";
+const IDENTITY_KEY_ID: &str = "k0";
+const NONCE_LEN: usize = 24;
+
+#[derive(Serialize, Deserialize, Default)]
+struct KeysFile {
+    active: Option<String>,
+    /// Key ID to base64url master key
+    keys: BTreeMap<String, String>,
+}
+
+impl Drop for KeysFile {
+    fn drop(&mut self) {
+        self.keys.values_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+#[derive(Clone)]
+pub struct ColumnKeys {
+    active: String,
+    /// Master keys, wiped from memory when the last clone drops
+    keys: Arc<BTreeMap<String, Zeroizing<[u8; 32]>>>,
+}
+
+impl ColumnKeys {
+    /// Keyring holding only the key derived from the node identity
+    pub fn from_identity(private_key: &[u8]) -> Self {
+        let mut keys = BTreeMap::new();
+        keys.insert(IDENTITY_KEY_ID.to_string(), identity_key(private_key));
+        Self {
+            active: IDENTITY_KEY_ID.to_string(),
+            keys: Arc::new(keys),
+        }
+    }
+
+    /// Identity key plus any rotated keys recorded in `keystore_dir`
+    pub fn load(keystore_dir: &Path, private_key: &[u8]) -> Result<Self, AppError> {
+        let file = read_keys_file(keystore_dir)?;
+
+        let mut keys = BTreeMap::new();
+        keys.insert(IDENTITY_KEY_ID.to_string(), identity_key(private_key));
+        for (id, encoded) in &file.keys {
+            let decoded = Zeroizing::new(URL_SAFE_NO_PAD.decode(encoded).unwrap_or_default());
+            let key: [u8; 32] = decoded
+                .as_slice()
+                .try_into()
+                .map_err(|_| AppError::Crypto(format!("Invalid column key {}", id)))?;
+            keys.insert(id.clone(), Zeroizing::new(key));
+        }
+
+        let active = file
+            .active
+            .clone()
+            .unwrap_or_else(|| IDENTITY_KEY_ID.to_string());
+        if !keys.contains_key(&active) {
+            return Err(AppError::Crypto(format!(
+                "Active column key {} is missing from {}",
+                active, KEYS_FILE
+            )));
+        }
+
+        Ok(Self {
+            active,
+            keys: Arc::new(keys),
+        })
+    }
+
+    /// Generate a new master key, persist it and make it active. Existing
+    /// rows stay readable until re-encrypted.
+    pub fn rotate(&self, keystore_dir: &Path) -> Result<Self, AppError> {
+        let mut file = read_keys_file(keystore_dir)?;
+
+        let id = format!("k{}", self.keys.len());
+        let mut key = Zeroizing::new([0u8; 32]);
+        rand::rngs::OsRng.fill_bytes(key.as_mut());
+
+        file.keys
+            .insert(id.clone(), URL_SAFE_NO_PAD.encode(key.as_ref()));
+        file.active = Some(id.clone());
+        write_keys_file(keystore_dir, &file)?;
+
+        let mut keys = (*self.keys).clone();
+        keys.insert(id.clone(), key);
+        info!("Rotated column encryption key to {}", id);
+
+        Ok(Self {
+            active: id,
+            keys: Arc::new(keys),
+        })
+    }
+
+    /// Record the key derived from the identity key in `keystore_dir`, so
+    /// rows sealed under it stay readable once the identity key is rotated
+    pub fn pin_identity_key(&self, keystore_dir: &Path) -> Result<(), AppError> {
+        let mut file = read_keys_file(keystore_dir)?;
+        if file.keys.contains_key(IDENTITY_KEY_ID) {
+            return Ok(());
+        }
+        let key = self
+            .keys
+            .get(IDENTITY_KEY_ID)
+            .ok_or_else(|| AppError::Crypto("Identity column key is missing".to_string()))?;
+        file.keys.insert(
+            IDENTITY_KEY_ID.to_string(),
+            URL_SAFE_NO_PAD.encode(key.as_ref()),
+        );
+        file.active.get_or_insert_with(|| self.active.clone());
+        write_keys_file(keystore_dir, &file)
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.active
+    }
+
+    /// Whether `stored` is sealed under the active key
+    pub fn is_current(&self, stored: &str) -> bool {
+        key_id_of(stored) == Some(self.active.as_str())
+    }
+
+    fn cipher(
+        &self,
+        key_id: &str,
+        column: &str,
+        owner: i32,
+    ) -> Result<XChaCha20Poly1305, CipherError> {
+        let master = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| format!("Unknown column key {}", key_id))?;
+
+        let mut material = Zeroizing::new(Vec::with_capacity(32 + column.len() + 4));
+        material.extend_from_slice(master.as_ref());
+        material.extend_from_slice(column.as_bytes());
+        material.extend_from_slice(&owner.to_le_bytes());
+        let key = Zeroizing::new(blake3::derive_key(
+            "flow column encryption v1 user key",
+            &material,
+        ));
+
+        Ok(XChaCha20Poly1305::new(key.as_ref().into()))
+    }
+}
+
+impl ColumnCipher for ColumnKeys {
+    fn encrypt(&self, column: &str, owner: i32, plaintext: &str) -> Result<String, CipherError> {
+        let cipher = self.cipher(&self.active, column, owner)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = associated_data(column, owner);
+
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| "Column encryption failed")?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!(
+            "{}{}:{}",
+            ENCRYPTED_PREFIX,
+            self.active,
+            URL_SAFE_NO_PAD.encode(sealed)
+        ))
+    }
+
+    fn decrypt(&self, column: &str, owner: i32, stored: &str) -> Result<String, CipherError> {
+        let Some(rest) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let (key_id, sealed) = rest.split_once(':').ok_or("Malformed encrypted column")?;
+        let sealed = URL_SAFE_NO_PAD.decode(sealed)?;
+        if sealed.len() < NONCE_LEN {
+            return Err("Malformed encrypted column".into());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        let plaintext = self
+            .cipher(key_id, column, owner)?
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &associated_data(column, owner),
+                },
+            )
+            .map_err(|_| "Column decryption failed")?;
+
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+/// Key ID of an encrypted value, `None` for plaintext
+pub fn key_id_of(stored: &str) -> Option<&str> {
+    stored
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .map(|(id, _)| id)
+}
+
+/// Encrypt plaintext rows and re-encrypt rows sealed under an older key.
+/// Returns the number of rows rewritten.
+pub async fn reencrypt_pass_keys(
+    db: &DatabaseConnection,
+    keys: &ColumnKeys,
+) -> Result<u64, AppError> {
+    let mut rewritten = 0;
+
+    for model in pass_key::Entity::find()
+        .all(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+    {
+        if keys.is_current(&model.json_data) {
+            continue;
+        }
+
+        let json = model
+            .passkey_json(keys)
+            .map_err(|e| AppError::Crypto(format!("Passkey {}: {}", model.id, e)))?;
+        let sealed = pass_key::encrypt_json_data(keys, model.user_id, &json)
+            .map_err(|e| AppError::Crypto(e.to_string()))?;
+
+        let mut active: pass_key::ActiveModel = model.into();
+        active.json_data = Set(sealed);
+        active
+            .update(db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        rewritten += 1;
+    }
+
+    if rewritten > 0 {
+        info!(
+            "Encrypted {} passkey rows under column key {}",
+            rewritten,
+            keys.active_key_id()
+        );
+    }
+    Ok(rewritten)
+}
+
+fn identity_key(private_key: &[u8]) -> Zeroizing<[u8; 32]> {
+    Zeroizing::new(blake3::derive_key(
+        "flow column encryption v1 identity master",
+        private_key,
+    ))
+}
+
+fn associated_data(column: &str, owner: i32) -> Vec<u8> {
+    let mut aad = column.as_bytes().to_vec();
+    aad.extend_from_slice(&owner.to_le_bytes());
+    aad
+}
+
+fn read_keys_file(keystore_dir: &Path) -> Result<KeysFile, AppError> {
+    match fs::read(keystore_dir.join(KEYS_FILE)) {
+        Ok(json) => serde_json::from_slice(&Zeroizing::new(json))
+            .map_err(|e| AppError::Crypto(format!("Invalid {}: {}", KEYS_FILE, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(KeysFile::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_keys_file(keystore_dir: &Path, file: &KeysFile) -> Result<(), AppError> {
+    fs::create_dir_all(keystore_dir)?;
+    let path = keystore_dir.join(KEYS_FILE);
+    let json = Zeroizing::new(
+        serde_json::to_vec_pretty(file)
+            .map_err(|e| AppError::Crypto(format!("Failed to serialize column keys: {}", e)))?,
+    );
+
+    let tmp = keystore_dir.join(format!("{}.tmp", KEYS_FILE));
+    fs::write(&tmp, json.as_slice())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+    }
+    fs::rename(tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Liveness and readiness.
+//!
+//! A node is live while it answers at all. It is ready while what requests
+//! depend on works: the database answers, the KV store reads, no migration is
+//! pending, and the identity key can be read from key storage. [`readiness`]
+//! checks each of those, each bounded by [`CHECK_TIMEOUT`], and reports them
+//! per component so an orchestrator (or whoever reads its logs) can tell
+//! which one held the node back. Once shutdown starts the node is not ready,
+//! so traffic drains before it stops.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use errors::AppError;
+use migration::{Migrator, MigratorTrait};
+use serde::Serialize;
+
+use crate::api::node::Node;
+use crate::bootstrap::init;
+
+/// Longest any one check may take before its component counts as down
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Up,
+    Down,
+}
+
+/// Outcome of one check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    pub status: ComponentStatus,
+    /// Why it is down, or anything worth knowing while up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+    pub ready: bool,
+    pub shutting_down: bool,
+    /// `database`, `kv`, `migrations` and `keystore`
+    pub components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+/// Check everything requests depend on
+pub async fn readiness(node: &Node) -> Readiness {
+    let (database, kv, migrations, keystore) = tokio::join!(
+        check(database(node)),
+        check(kv(node)),
+        check(migrations(node)),
+        check(keystore(node)),
+    );
+    let components = BTreeMap::from([
+        ("database", database),
+        ("kv", kv),
+        ("migrations", migrations),
+        ("keystore", keystore),
+    ]);
+    let shutting_down = node.tasks.shutdown().is_triggered();
+    Readiness {
+        ready: !shutting_down && components.values().all(|c| c.status == ComponentStatus::Up),
+        shutting_down,
+        components,
+    }
+}
+
+/// Run `probe` under the timeout; `Ok` carries an optional detail
+async fn check(probe: impl Future<Output = Result<Option<String>, AppError>>) -> ComponentHealth {
+    let started = Instant::now();
+    let (status, detail) = match tokio::time::timeout(CHECK_TIMEOUT, probe).await {
+        Ok(Ok(detail)) => (ComponentStatus::Up, detail),
+        Ok(Err(e)) => (ComponentStatus::Down, Some(e.to_string())),
+        Err(_) => (
+            ComponentStatus::Down,
+            Some(format!("No answer within {:?}", CHECK_TIMEOUT)),
+        ),
+    };
+    ComponentHealth {
+        status,
+        detail,
+        latency_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+async fn database(node: &Node) -> Result<Option<String>, AppError> {
+    node.db
+        .ping()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(None)
+}
+
+async fn kv(node: &Node) -> Result<Option<String>, AppError> {
+    node.kv
+        .get(b"health")
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(None)
+}
+
+async fn migrations(node: &Node) -> Result<Option<String>, AppError> {
+    let pending = Migrator::get_pending_migrations(&node.db)
+        .await
+        .map_err(|e| AppError::Migration(Box::new(e)))?;
+    if pending.is_empty() {
+        return Ok(None);
+    }
+    let names: Vec<&str> = pending.iter().map(|m| m.name()).collect();
+    Err(AppError::Migration(
+        format!("Pending migrations: {}", names.join(", ")).into(),
+    ))
+}
+
+async fn keystore(node: &Node) -> Result<Option<String>, AppError> {
+    match &node.home {
+        Some(home) => {
+            let home = home.to_string_lossy().to_string();
+            tokio::task::spawn_blocking(move || init::check_keystore(&home))
+                .await
+                .map_err(|e| AppError::IO(std::io::Error::other(e)))??;
+            Ok(None)
+        }
+        None => Ok(Some("Identity key held in memory".to_string())),
+    }
+}
@@ -0,0 +1,180 @@
+//! Change history of users and spaces, for troubleshooting and audit.
+//!
+//! Edits to these rows go through
+//! [`update_versioned`](crate::modules::versioning::update_versioned), which
+//! records each one in the `entity_history` table, with the row as it was
+//! before and after, in the same transaction as the edit. Creating a row is
+//! recorded with nothing before it, and the purge removing one with nothing
+//! after. Bookkeeping the node does itself (sign-ins, Merkle roots) leaves
+//! no history, as it leaves the version alone.
+//!
+//! History is read at `GET /api/v1/users/{id}/history` and
+//! `GET /api/v1/spaces/{key}/history`, newest first. Users see their own and
+//! their spaces'; erasing an account erases its history, and the rest is
+//! kept for [`RetentionConfig::history`].
+//!
+//! [`RetentionConfig::history`]: crate::bootstrap::config::RetentionConfig::history
+
+use chrono::{DateTime, Utc};
+use entity::{entity_history, space, user};
+use errors::AppError;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ActiveValue::Set, ColumnTrait, ConnectionTrait,
+    DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::modules::tenancy::Actor;
+
+/// What happened to a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Created,
+    Updated,
+    Removed,
+}
+
+impl Change {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Change::Created => "created",
+            Change::Updated => "updated",
+            Change::Removed => "removed",
+        }
+    }
+}
+
+/// The row a history entry is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subject {
+    /// Kind of row, as in the history URL: `users` or `spaces`
+    pub entity: &'static str,
+    /// A user's ID or a space's key
+    pub id: String,
+    /// User the row belongs to; `None` for the node's spaces
+    pub owner: Option<i32>,
+}
+
+impl From<&user::Model> for Subject {
+    fn from(user: &user::Model) -> Self {
+        Subject {
+            entity: "users",
+            id: user.id.to_string(),
+            owner: Some(user.id),
+        }
+    }
+}
+
+impl From<&space::Model> for Subject {
+    fn from(space: &space::Model) -> Self {
+        Subject {
+            entity: "spaces",
+            id: space.key.clone(),
+            owner: space.user_id,
+        }
+    }
+}
+
+/// One recorded change
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityChange {
+    pub id: i32,
+    pub change: String,
+    /// The row before the change; `None` for a creation
+    pub before: Option<Value>,
+    /// The row after the change; `None` for a removal
+    pub after: Option<Value>,
+    pub time: DateTime<Utc>,
+}
+
+/// Record a change to `subject` through `conn`, the transaction making it
+pub async fn record<M: Serialize>(
+    conn: &impl ConnectionTrait,
+    subject: &Subject,
+    change: Change,
+    before: Option<&M>,
+    after: Option<&M>,
+) -> Result<(), AppError> {
+    let snapshot = |row: Option<&M>| {
+        row.map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    };
+    entity_history::ActiveModel {
+        id: NotSet,
+        entity: Set(subject.entity.to_string()),
+        entity_id: Set(subject.id.clone()),
+        owner_id: Set(subject.owner),
+        change: Set(change.as_str().to_string()),
+        before: Set(snapshot(before)?),
+        after: Set(snapshot(after)?),
+        time_created: Set(Utc::now().into()),
+    }
+    .insert(conn)
+    .await
+    .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(())
+}
+
+/// Changes to the `entity` row `id` that `actor` may see, newest first
+pub async fn list(
+    db: &DatabaseConnection,
+    actor: Actor,
+    entity: &str,
+    id: &str,
+) -> Result<Vec<EntityChange>, AppError> {
+    if !matches!(entity, "users" | "spaces") {
+        return Err(AppError::NotFound(format!(
+            "No history is kept for {}",
+            entity
+        )));
+    }
+    let rows = entity_history::Entity::find()
+        .filter(entity_history::Column::Entity.eq(entity))
+        .filter(entity_history::Column::EntityId.eq(id))
+        .order_by_desc(entity_history::Column::Id)
+        .all(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+    let parse = |snapshot: Option<String>| {
+        snapshot
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    };
+    rows.into_iter()
+        .filter(|row| actor.can_access(row.owner_id))
+        .map(|row| {
+            Ok(EntityChange {
+                id: row.id,
+                change: row.change,
+                before: parse(row.before)?,
+                after: parse(row.after)?,
+                time: row.time_created.with_timezone(&Utc),
+            })
+        })
+        .collect()
+}
+
+/// Remove the history of user `user_id` and their spaces
+pub async fn forget_owner(conn: &impl ConnectionTrait, user_id: i32) -> Result<u64, AppError> {
+    Ok(entity_history::Entity::delete_many()
+        .filter(entity_history::Column::OwnerId.eq(user_id))
+        .exec(conn)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .rows_affected)
+}
+
+/// Remove changes recorded before `before`
+pub async fn purge(db: &DatabaseConnection, before: DateTime<Utc>) -> Result<u64, AppError> {
+    Ok(entity_history::Entity::delete_many()
+        .filter(entity_history::Column::TimeCreated.lt(before))
+        .exec(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .rows_affected)
+}
@@ -0,0 +1,98 @@
+//! Rotation of the node identity key.
+//!
+//! The node's DID is a did:key, so a new key is a new DID. To let peers
+//! follow the node from one to the next, each rotation produces a statement
+//! naming both, signed by the retiring key: whoever trusted the old DID can
+//! check that its holder handed over to the new one. Statements are kept in
+//! the key history in `auth.json`, and the retired keys in the keystore.
+
+use ed25519_dalek::Signer;
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+
+use crate::bootstrap::init::NodeData;
+use crate::modules::manifest::{self, canonical_bytes, encode_public_key, encode_signature};
+
+pub const STATEMENT_TYPE: &str = "flow-key-rotation/v1";
+
+/// The retiring key handing over to its successor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationStatement {
+    #[serde(rename = "type")]
+    pub statement_type: String,
+    pub previous_did: String,
+    pub previous_key_multibase: String,
+    pub next_did: String,
+    pub next_key_multibase: String,
+    /// Version of the next key; the node's first key is version 1
+    pub version: u32,
+    pub rotated_at: String,
+}
+
+/// The signature covers the canonical JSON form of the statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedRotation {
+    #[serde(flatten)]
+    pub statement: RotationStatement,
+    pub signature: String,
+}
+
+impl RotationStatement {
+    /// Statement handing over from `previous` to `next` at `version`
+    pub fn new(previous: &NodeData, next: &NodeData, version: u32) -> Result<Self, AppError> {
+        Ok(Self {
+            statement_type: STATEMENT_TYPE.to_string(),
+            previous_did: previous.id.clone(),
+            previous_key_multibase: public_key_multibase(previous)?,
+            next_did: next.id.clone(),
+            next_key_multibase: public_key_multibase(next)?,
+            version,
+            rotated_at: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Sign with the retiring key
+    pub fn sign(self, previous: &NodeData) -> Result<SignedRotation, AppError> {
+        let signing_key = manifest::signing_key(previous)?;
+        if encode_public_key(&signing_key.verifying_key()) != self.previous_key_multibase {
+            return Err(AppError::Crypto(
+                "Rotation must be signed by the retiring key".to_string(),
+            ));
+        }
+        let signature = signing_key.sign(&canonical_bytes(&self)?);
+        Ok(SignedRotation {
+            statement: self,
+            signature: encode_signature(&signature),
+        })
+    }
+}
+
+impl SignedRotation {
+    /// Whether the retiring key signed this statement, and the DIDs are the
+    /// did:keys of the keys it names
+    pub fn verify(&self) -> Result<bool, AppError> {
+        let statement = &self.statement;
+        if statement.statement_type != STATEMENT_TYPE
+            || statement.previous_did != did_key(&statement.previous_key_multibase)
+            || statement.next_did != did_key(&statement.next_key_multibase)
+        {
+            return Ok(false);
+        }
+        manifest::verify_with_key(
+            &statement.previous_key_multibase,
+            &canonical_bytes(statement)?,
+            &self.signature,
+        )
+    }
+}
+
+fn public_key_multibase(node_data: &NodeData) -> Result<String, AppError> {
+    Ok(encode_public_key(
+        &manifest::signing_key(node_data)?.verifying_key(),
+    ))
+}
+
+fn did_key(public_key_multibase: &str) -> String {
+    format!("did:key:{}", public_key_multibase)
+}
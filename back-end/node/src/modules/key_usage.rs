@@ -0,0 +1,377 @@
+//! Usage of the keys users sign in with: passkeys and session tokens.
+//!
+//! Every use is recorded with its time, client address and country, and the
+//! class of operation, and folded into per-key stats. Each use is also held
+//! against the key's past:
+//!
+//! - a country the key hasn't been used from before,
+//! - a key idle for [`KeyUsageConfig::dormant_after`] suddenly used again,
+//! - two countries within [`KeyUsageConfig::travel_window`] (impossible
+//!   travel, as far as countries can tell).
+//!
+//! The node publishes each anomaly as an [`EventType::KeyUsageAnomaly`]
+//! event, which webhooks and scripts can pick up, and with step-up on closes
+//! a session that shows one, so its user has to sign in with a passkey again.
+//!
+//! Countries come from a header set by a trusted proxy (see
+//! [`ProxyConfig::country_header`]); without one only dormancy is checked.
+//!
+//! [`EventType::KeyUsageAnomaly`]: event::types::EventType::KeyUsageAnomaly
+//! [`ProxyConfig::country_header`]: crate::bootstrap::config::ProxyConfig::country_header
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+
+use crate::bootstrap::config::KeyUsageConfig;
+
+pub const STATS_TREE: &str = "key_usage:stats";
+pub const USE_TREE: &str = "key_usage:uses";
+/// Uses kept per key; older ones are dropped, the stats keep counting
+pub const MAX_USES_PER_KEY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    Passkey,
+    Session,
+}
+
+impl KeyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyKind::Passkey => "passkey",
+            KeyKind::Session => "session",
+        }
+    }
+}
+
+impl fmt::Display for KeyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for KeyKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "passkey" => Ok(KeyKind::Passkey),
+            "session" => Ok(KeyKind::Session),
+            _ => Err(AppError::Validation(format!("Unknown key kind: {}", s))),
+        }
+    }
+}
+
+/// What a key was used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Signing in
+    Authenticate,
+    Read,
+    Write,
+}
+
+/// One use of a key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyUse {
+    pub kind: KeyKind,
+    /// Base64url credential ID of a passkey, or a session's ID
+    pub key_id: String,
+    pub user_id: Option<i32>,
+    pub at: DateTime<Utc>,
+    pub ip: Option<IpAddr>,
+    /// ISO 3166-1 alpha-2 code
+    pub country: Option<String>,
+    pub operation: Operation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyStats {
+    pub kind: KeyKind,
+    pub key_id: String,
+    pub user_id: Option<i32>,
+    pub uses: u64,
+    pub first_used: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+    pub last_ip: Option<IpAddr>,
+    pub last_country: Option<String>,
+    /// Every country the key was used from
+    pub countries: Vec<String>,
+    pub operations: BTreeMap<Operation, u64>,
+}
+
+impl KeyStats {
+    fn new(key_use: &KeyUse) -> Self {
+        Self {
+            kind: key_use.kind,
+            key_id: key_use.key_id.clone(),
+            user_id: key_use.user_id,
+            uses: 0,
+            first_used: key_use.at,
+            last_used: key_use.at,
+            last_ip: None,
+            last_country: None,
+            countries: Vec::new(),
+            operations: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, key_use: &KeyUse) {
+        self.uses += 1;
+        self.last_used = self.last_used.max(key_use.at);
+        self.last_ip = key_use.ip;
+        if let Some(country) = &key_use.country {
+            self.last_country = Some(country.clone());
+            if !self.countries.contains(country) {
+                self.countries.push(country.clone());
+            }
+        }
+        *self.operations.entry(key_use.operation).or_default() += 1;
+    }
+}
+
+/// A use that doesn't fit the key's past
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "rule",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum Anomaly {
+    NewCountry {
+        country: String,
+    },
+    DormantKeyActive {
+        idle_days: i64,
+    },
+    ImpossibleTravel {
+        from: String,
+        to: String,
+        minutes: i64,
+    },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::NewCountry { country } => write!(f, "first use from {}", country),
+            Anomaly::DormantKeyActive { idle_days } => {
+                write!(f, "used after {} days idle", idle_days)
+            }
+            Anomaly::ImpossibleTravel { from, to, minutes } => {
+                write!(
+                    f,
+                    "used from {} then {} within {} minutes",
+                    from, to, minutes
+                )
+            }
+        }
+    }
+}
+
+/// Anomalies of `key_use` given the key's stats before it
+pub fn check(
+    previous: Option<&KeyStats>,
+    key_use: &KeyUse,
+    config: &KeyUsageConfig,
+) -> Vec<Anomaly> {
+    let Some(previous) = previous else {
+        return Vec::new();
+    };
+    let mut anomalies = Vec::new();
+
+    let idle = key_use.at - previous.last_used;
+    if idle.to_std().is_ok_and(|idle| idle >= config.dormant_after) {
+        anomalies.push(Anomaly::DormantKeyActive {
+            idle_days: idle.num_days(),
+        });
+    }
+
+    if let Some(country) = &key_use.country {
+        if !previous.countries.is_empty() && !previous.countries.contains(country) {
+            anomalies.push(Anomaly::NewCountry {
+                country: country.clone(),
+            });
+        }
+        if let Some(last) = &previous.last_country
+            && last != country
+            && idle.to_std().is_ok_and(|idle| idle < config.travel_window)
+        {
+            anomalies.push(Anomaly::ImpossibleTravel {
+                from: last.clone(),
+                to: country.clone(),
+                minutes: idle.num_minutes(),
+            });
+        }
+    }
+    anomalies
+}
+
+/// Recorded key usage
+pub struct KeyUsage {
+    kv: Db,
+    config: KeyUsageConfig,
+}
+
+impl KeyUsage {
+    pub fn new(kv: Db, config: KeyUsageConfig) -> Self {
+        Self { kv, config }
+    }
+
+    /// Record a use, returning the anomalies it shows
+    pub fn record(&self, key_use: &KeyUse) -> Result<Vec<Anomaly>, AppError> {
+        let key = stats_key(key_use.kind, &key_use.key_id);
+        let previous = self
+            .stats_tree()?
+            .fetch_and_update(key.as_bytes(), |old| {
+                let mut stats = old
+                    .and_then(|bytes| serde_json::from_slice::<KeyStats>(bytes).ok())
+                    .unwrap_or_else(|| KeyStats::new(key_use));
+                stats.add(key_use);
+                serde_json::to_vec(&stats).ok()
+            })
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .map(|bytes| serde_json::from_slice::<KeyStats>(&bytes))
+            .transpose()
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        let uses = self.use_tree()?;
+        let prefix = use_prefix(key_use.kind, &key_use.key_id);
+        let json = serde_json::to_vec(key_use).map_err(|e| AppError::Storage(Box::new(e)))?;
+        uses.insert(
+            format!("{}{:020}", prefix, key_use.at.timestamp_micros()).as_bytes(),
+            json,
+        )
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+        let keys = uses
+            .scan_prefix(&prefix)
+            .keys()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        for key in keys
+            .iter()
+            .take(keys.len().saturating_sub(MAX_USES_PER_KEY))
+        {
+            uses.remove(key)
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+        }
+
+        Ok(check(previous.as_ref(), key_use, &self.config))
+    }
+
+    /// Stats of every key, of `user_id`'s only if given
+    pub fn stats(&self, user_id: Option<i32>) -> Result<Vec<KeyStats>, AppError> {
+        let mut stats = self
+            .stats_tree()?
+            .iter()
+            .values()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice::<KeyStats>(&value)
+                    .map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .filter(|stats| user_id.is_none() || stats.as_ref().is_ok_and(|s| s.user_id == user_id))
+            .collect::<Result<Vec<_>, _>>()?;
+        stats.sort_by_key(|s| std::cmp::Reverse(s.last_used));
+        Ok(stats)
+    }
+
+    pub fn key_stats(&self, kind: KeyKind, key_id: &str) -> Result<Option<KeyStats>, AppError> {
+        self.stats_tree()?
+            .get(stats_key(kind, key_id).as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .map(|bytes| serde_json::from_slice(&bytes).map_err(|e| AppError::Storage(Box::new(e))))
+            .transpose()
+    }
+
+    /// Recent uses of a key, newest first
+    pub fn history(&self, kind: KeyKind, key_id: &str) -> Result<Vec<KeyUse>, AppError> {
+        let mut uses = self
+            .use_tree()?
+            .scan_prefix(use_prefix(kind, key_id))
+            .values()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .collect::<Result<Vec<KeyUse>, AppError>>()?;
+        uses.reverse();
+        Ok(uses)
+    }
+
+    /// Remove uses from before `before`, and the stats of keys last used
+    /// before then, returning how many records. With `dry_run` they are only
+    /// counted.
+    pub fn purge(&self, before: DateTime<Utc>, dry_run: bool) -> Result<u64, AppError> {
+        let uses = purge_tree(&self.use_tree()?, dry_run, |u: KeyUse| u.at < before)?;
+        let stats = purge_tree(&self.stats_tree()?, dry_run, |s: KeyStats| {
+            s.last_used < before
+        })?;
+        Ok(uses + stats)
+    }
+
+    /// Remove every use and stat of `user_id`'s keys, returning how many
+    /// records
+    pub fn forget_user(&self, user_id: i32) -> Result<u64, AppError> {
+        let uses = purge_tree(&self.use_tree()?, false, |u: KeyUse| {
+            u.user_id == Some(user_id)
+        })?;
+        let stats = purge_tree(&self.stats_tree()?, false, |s: KeyStats| {
+            s.user_id == Some(user_id)
+        })?;
+        Ok(uses + stats)
+    }
+
+    fn stats_tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(STATS_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    fn use_tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(USE_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+/// Remove the records of `tree` that are `stale`, returning how many
+fn purge_tree<T: DeserializeOwned>(
+    tree: &sled::Tree,
+    dry_run: bool,
+    stale: impl Fn(T) -> bool,
+) -> Result<u64, AppError> {
+    let mut purged = 0;
+    for item in tree.iter() {
+        let (key, value) = item.map_err(|e| AppError::Storage(Box::new(e)))?;
+        let record = serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))?;
+        if !stale(record) {
+            continue;
+        }
+        if !dry_run {
+            tree.remove(key)
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+        }
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+fn stats_key(kind: KeyKind, key_id: &str) -> String {
+    format!("{}/{}", kind, key_id)
+}
+
+fn use_prefix(kind: KeyKind, key_id: &str) -> String {
+    format!("{}/{}/", kind, key_id)
+}
@@ -0,0 +1,126 @@
+//! Read-only maintenance mode.
+//!
+//! While on, requests that would change what the node holds are refused
+//! with [`AppError::Maintenance`] (REST answers `503` with `Retry-After`),
+//! and reads, health checks and WebSocket event delivery carry on.
+//! Operations that need the data to hold still (backups, restores,
+//! migrations) keep it on for as long as they run with [`Maintenance::begin`];
+//! admins toggle it with `PUT /api/v1/admin/maintenance`, or start the node
+//! in it with `FLOW_MAINTENANCE`.
+//!
+//! The state is kept in memory only, and shared by every clone: a restart
+//! goes back to what the configuration says.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use log::info;
+use serde::Serialize;
+
+use crate::bootstrap::config::MaintenanceConfig;
+
+/// Why and since when the node is in maintenance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceWindow {
+    pub reason: String,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Maintenance {
+    window: Arc<RwLock<Option<MaintenanceWindow>>>,
+    retry_after: Duration,
+}
+
+impl Maintenance {
+    pub fn new(config: &MaintenanceConfig) -> Self {
+        let maintenance = Self {
+            window: Arc::default(),
+            retry_after: config.retry_after,
+        };
+        if config.enabled {
+            maintenance.enable("configured at startup");
+        }
+        maintenance
+    }
+
+    /// How long refused clients are told to wait
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+
+    /// The current window; `None` while writes are allowed
+    pub fn window(&self) -> Option<MaintenanceWindow> {
+        self.read().clone()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.read().is_some()
+    }
+
+    /// Refuse writes, for `reason`. Already on, the first reason stays.
+    pub fn enable(&self, reason: &str) {
+        let mut window = self.window.write().unwrap_or_else(|e| e.into_inner());
+        if window.is_none() {
+            info!("Maintenance mode on: {}", reason);
+            *window = Some(MaintenanceWindow {
+                reason: reason.to_string(),
+                since: Utc::now(),
+            });
+        }
+    }
+
+    /// Allow writes again; whether maintenance was on
+    pub fn disable(&self) -> bool {
+        let ended = self
+            .window
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(window) = &ended {
+            info!("Maintenance mode off, on since {}", window.since);
+        }
+        ended.is_some()
+    }
+
+    /// Hold the node in maintenance until the guard drops. Fails if it
+    /// already is, so two operations don't end each other's window.
+    pub fn begin(&self, reason: &str) -> Result<MaintenanceGuard, AppError> {
+        self.check()?;
+        self.enable(reason);
+        Ok(MaintenanceGuard {
+            maintenance: self.clone(),
+        })
+    }
+
+    /// Fails while writes are refused
+    pub fn check(&self) -> Result<(), AppError> {
+        match self.read().as_ref() {
+            Some(window) => Err(AppError::Maintenance(format!(
+                "Writes are paused ({}); retry in {}s",
+                window.reason,
+                self.retry_after.as_secs()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Option<MaintenanceWindow>> {
+        self.window.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Ends maintenance when dropped
+#[must_use = "maintenance ends when the guard is dropped"]
+pub struct MaintenanceGuard {
+    maintenance: Maintenance,
+}
+
+impl Drop for MaintenanceGuard {
+    fn drop(&mut self) {
+        self.maintenance.disable();
+    }
+}
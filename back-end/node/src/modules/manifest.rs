@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use ed25519_dalek::{Signer, SigningKey};
+use errors::AppError;
+use log::info;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::bootstrap::init::NodeData;
+use crate::modules::space_crypto::SpaceCipher;
+
+pub use identity::manifest::{
+    MANIFEST_SCHEMA, Manifest, ManifestEntry, SignedManifest, did_key_public_key, verify_signature,
+};
+pub(crate) use identity::manifest::{
+    canonical_bytes, encode_public_key, encode_signature, verify_with_key,
+};
+
+/// Result of checking a manifest against the files on disk
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestVerification {
+    pub signature_valid: bool,
+    /// Files listed in the manifest that are absent on disk
+    pub missing: Vec<String>,
+    /// Files whose hash or size differ from the manifest
+    pub modified: Vec<String>,
+    /// Files on disk that are not listed in the manifest
+    pub unexpected: Vec<String>,
+}
+
+impl ManifestVerification {
+    pub fn is_valid(&self) -> bool {
+        self.signature_valid
+            && self.missing.is_empty()
+            && self.modified.is_empty()
+            && self.unexpected.is_empty()
+    }
+}
+
+/// Resolve a folder inside a space, rejecting anything that could escape it
+pub fn resolve_folder(space_root: &Path, sub_path: &str) -> Result<PathBuf, AppError> {
+    let relative = Path::new(sub_path.trim_start_matches('/'));
+
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(AppError::Validation(format!(
+            "Invalid path within space: {}",
+            sub_path
+        )));
+    }
+
+    let folder = space_root.join(relative);
+    if !folder.is_dir() {
+        return Err(AppError::NotFound(format!(
+            "Folder not found: {}",
+            sub_path
+        )));
+    }
+
+    Ok(folder)
+}
+
+/// Walk a folder and hash every regular file in it. Symlinks are skipped.
+/// In an encrypted space, given its `cipher`, files are hashed as they
+/// download: decrypted.
+pub fn build_manifest(
+    space_key: &str,
+    folder: &Path,
+    sub_path: &str,
+    signer: &str,
+    cipher: Option<&SpaceCipher>,
+) -> Result<Manifest, AppError> {
+    let mut entries = Vec::new();
+    collect_entries(folder, folder, sub_path, cipher, &mut entries)?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    info!(
+        "Built manifest for space {} ({} files under '{}')",
+        space_key,
+        entries.len(),
+        sub_path
+    );
+
+    Ok(Manifest {
+        schema: MANIFEST_SCHEMA.to_string(),
+        space_key: space_key.to_string(),
+        path: sub_path.to_string(),
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        signer: signer.to_string(),
+        public_key_multibase: String::new(),
+        entries,
+    })
+}
+
+/// Entries for the files under `dir`, with paths relative to `root`, which
+/// sits at `sub_path` in the space
+fn collect_entries(
+    root: &Path,
+    dir: &Path,
+    sub_path: &str,
+    cipher: Option<&SpaceCipher>,
+    entries: &mut Vec<ManifestEntry>,
+) -> Result<(), AppError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            collect_entries(root, &path, sub_path, cipher, entries)?;
+        } else if file_type.is_file() {
+            let relative = path
+                .strip_prefix(root)
+                .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let in_space = match sub_path.trim_matches('/') {
+                "" => relative.clone(),
+                folder => format!("{}/{}", folder, relative),
+            };
+            let (hash, size) = hash_file(&in_space, &path, cipher)?;
+            entries.push(ManifestEntry {
+                path: relative,
+                blake3: hash,
+                size,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Manifest entry for one file, `path` being where it sits in the space
+pub fn file_entry(
+    path: &str,
+    file: &Path,
+    cipher: Option<&SpaceCipher>,
+) -> Result<ManifestEntry, AppError> {
+    let (blake3, size) = hash_file(path, file, cipher)?;
+    Ok(ManifestEntry {
+        path: path.trim_start_matches('/').to_string(),
+        blake3,
+        size,
+    })
+}
+
+/// Hash and size of the file at `file`, which is at `path` in the space,
+/// decrypted first if the space has a `cipher`
+fn hash_file(
+    path: &str,
+    file: &Path,
+    cipher: Option<&SpaceCipher>,
+) -> Result<(String, u64), AppError> {
+    let mut hasher = blake3::Hasher::new();
+    match cipher {
+        Some(cipher) => cipher.decrypt_file(path, file, &mut hasher)?,
+        None => {
+            std::io::copy(&mut fs::File::open(file)?, &mut hasher)?;
+        }
+    }
+    Ok((hasher.finalize().to_hex().to_string(), hasher.count()))
+}
+
+/// Sign a manifest with the node's Ed25519 identity key
+pub fn sign_manifest(
+    mut manifest: Manifest,
+    node_data: &NodeData,
+) -> Result<SignedManifest, AppError> {
+    let signing_key = signing_key(node_data)?;
+
+    manifest.public_key_multibase = encode_public_key(&signing_key.verifying_key());
+
+    let signature = signing_key.sign(&canonical_bytes(&manifest)?);
+
+    Ok(SignedManifest {
+        manifest,
+        signature: encode_signature(&signature),
+    })
+}
+
+/// Check a signed manifest, signed by `trusted_key`, against the current
+/// contents of a folder, decrypted with `cipher` in an encrypted space
+pub fn verify_manifest(
+    signed: &SignedManifest,
+    folder: &Path,
+    trusted_key: &str,
+    cipher: Option<&SpaceCipher>,
+) -> Result<ManifestVerification, AppError> {
+    let signature_valid = verify_signature(signed, trusted_key)?;
+
+    let mut current = Vec::new();
+    collect_entries(folder, folder, &signed.manifest.path, cipher, &mut current)?;
+
+    let mut verification = ManifestVerification {
+        signature_valid,
+        ..Default::default()
+    };
+
+    let current: HashMap<&str, &ManifestEntry> =
+        current.iter().map(|e| (e.path.as_str(), e)).collect();
+    let expected: HashMap<&str, &ManifestEntry> = signed
+        .manifest
+        .entries
+        .iter()
+        .map(|e| (e.path.as_str(), e))
+        .collect();
+
+    for (path, entry) in &expected {
+        match current.get(path) {
+            None => verification.missing.push(path.to_string()),
+            Some(actual) if actual != entry => verification.modified.push(path.to_string()),
+            Some(_) => {}
+        }
+    }
+
+    for path in current.keys() {
+        if !expected.contains_key(path) {
+            verification.unexpected.push(path.to_string());
+        }
+    }
+
+    verification.missing.sort();
+    verification.modified.sort();
+    verification.unexpected.sort();
+
+    Ok(verification)
+}
+
+/// The node's Ed25519 identity key
+pub(crate) fn signing_key(node_data: &NodeData) -> Result<SigningKey, AppError> {
+    let key_bytes: Zeroizing<[u8; 32]> = Zeroizing::new(
+        node_data
+            .private_key
+            .as_slice()
+            .try_into()
+            .map_err(|_| AppError::Crypto("Node private key must be 32 bytes".to_string()))?,
+    );
+    Ok(SigningKey::from_bytes(&key_bytes))
+}
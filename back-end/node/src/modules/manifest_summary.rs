@@ -0,0 +1,275 @@
+//! Compact manifest summaries for sync negotiation.
+//!
+//! Sending the full manifest of a space with millions of files is slow. A
+//! summary instead carries one hash per top-level subtree and a Bloom filter
+//! of the entries, so peers first find which subtrees differ and then fetch
+//! manifests for just those.
+
+use std::collections::BTreeMap;
+
+use errors::AppError;
+use multibase::Base;
+use serde::{Deserialize, Serialize};
+
+use crate::modules::manifest::{Manifest, ManifestEntry};
+
+pub const SUMMARY_SCHEMA: &str = "flow-manifest-summary/v1";
+/// False positive rate the entry filter is sized for
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+/// Largest filter built or accepted, in bits (2 MiB)
+pub const MAX_FILTER_BITS: u64 = 16 * 1024 * 1024;
+const MAX_FILTER_HASHES: u32 = 32;
+
+/// Hash of the files under one top-level entry of the summarised folder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubtreeSummary {
+    /// Top-level folder, or the file name for files directly in the folder
+    pub path: String,
+    pub entries: u64,
+    /// Hex encoded BLAKE3 over the subtree's sorted entries
+    pub hash: String,
+}
+
+/// Summary of a manifest: enough to find what differs, not what to fetch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestSummary {
+    pub schema: String,
+    pub space_key: String,
+    /// Folder within the space the summary covers ("" for the space root)
+    pub path: String,
+    pub generated_at: String,
+    pub entries: u64,
+    /// Hex encoded BLAKE3 over all subtree hashes
+    pub hash: String,
+    pub subtrees: Vec<SubtreeSummary>,
+    /// Every entry's path, hash and size
+    pub filter: BloomFilter,
+}
+
+/// Where a local manifest and a peer's summary disagree
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SummaryComparison {
+    pub in_sync: bool,
+    /// Subtrees that differ, including ones only one side has; fetch the
+    /// manifests for these
+    pub subtrees: Vec<String>,
+    /// Local files the peer's filter shows it definitely lacks
+    pub missing_on_peer: Vec<String>,
+}
+
+/// Summarise a manifest
+pub fn summarize(manifest: &Manifest) -> ManifestSummary {
+    let mut filter = BloomFilter::with_capacity(manifest.entries.len());
+    let mut subtrees: BTreeMap<&str, (u64, blake3::Hasher)> = BTreeMap::new();
+
+    // Entries are sorted by path, so each subtree hashes them in order
+    for entry in &manifest.entries {
+        let (count, hasher) = subtrees.entry(subtree_of(&entry.path)).or_default();
+        *count += 1;
+        hasher.update(&entry_bytes(entry));
+        filter.insert(&entry_bytes(entry));
+    }
+
+    let mut root = blake3::Hasher::new();
+    let subtrees: Vec<SubtreeSummary> = subtrees
+        .into_iter()
+        .map(|(path, (entries, hasher))| {
+            let hash = hasher.finalize();
+            root.update(path.as_bytes())
+                .update(&[0])
+                .update(hash.as_bytes());
+            SubtreeSummary {
+                path: path.to_string(),
+                entries,
+                hash: hash.to_hex().to_string(),
+            }
+        })
+        .collect();
+
+    ManifestSummary {
+        schema: SUMMARY_SCHEMA.to_string(),
+        space_key: manifest.space_key.clone(),
+        path: manifest.path.clone(),
+        generated_at: manifest.generated_at.clone(),
+        entries: manifest.entries.len() as u64,
+        hash: root.finalize().to_hex().to_string(),
+        subtrees,
+        filter,
+    }
+}
+
+/// Compare a local manifest with a peer's summary of the same folder
+pub fn compare(local: &Manifest, remote: &ManifestSummary) -> Result<SummaryComparison, AppError> {
+    if remote.schema != SUMMARY_SCHEMA {
+        return Err(AppError::Validation(format!(
+            "Unsupported summary schema: {}",
+            remote.schema
+        )));
+    }
+    if remote.path.trim_matches('/') != local.path.trim_matches('/') {
+        return Err(AppError::Validation(format!(
+            "Summary covers '{}', not '{}'",
+            remote.path, local.path
+        )));
+    }
+
+    let summary = summarize(local);
+    if summary.hash == remote.hash {
+        return Ok(SummaryComparison {
+            in_sync: true,
+            ..Default::default()
+        });
+    }
+
+    let theirs: BTreeMap<&str, &str> = remote
+        .subtrees
+        .iter()
+        .map(|s| (s.path.as_str(), s.hash.as_str()))
+        .collect();
+    let ours: BTreeMap<&str, &str> = summary
+        .subtrees
+        .iter()
+        .map(|s| (s.path.as_str(), s.hash.as_str()))
+        .collect();
+
+    let mut subtrees: Vec<String> = ours
+        .iter()
+        .filter(|(path, hash)| theirs.get(*path) != Some(*hash))
+        .map(|(path, _)| path.to_string())
+        .collect();
+    subtrees.extend(
+        theirs
+            .keys()
+            .filter(|path| !ours.contains_key(*path))
+            .map(|path| path.to_string()),
+    );
+    subtrees.sort();
+
+    let missing_on_peer = local
+        .entries
+        .iter()
+        .filter(|entry| {
+            subtrees
+                .binary_search_by(|s| s.as_str().cmp(subtree_of(&entry.path)))
+                .is_ok()
+                && !remote.filter.contains(&entry_bytes(entry))
+        })
+        .map(|entry| entry.path.clone())
+        .collect();
+
+    Ok(SummaryComparison {
+        in_sync: false,
+        subtrees,
+        missing_on_peer,
+    })
+}
+
+/// First segment of a manifest path
+fn subtree_of(path: &str) -> &str {
+    path.split('/').next().unwrap_or(path)
+}
+
+fn entry_bytes(entry: &ManifestEntry) -> Vec<u8> {
+    format!("{}\0{}\0{}\n", entry.path, entry.blake3, entry.size).into_bytes()
+}
+
+/// Bloom filter over byte strings
+///
+/// Bit positions come from double hashing the item's BLAKE3 digest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "EncodedFilter", into = "EncodedFilter")]
+pub struct BloomFilter {
+    num_bits: u64,
+    hashes: u32,
+    bits: Vec<u8>,
+}
+
+/// Wire form: sizes plus the bit array as base64url multibase
+#[derive(Serialize, Deserialize)]
+struct EncodedFilter {
+    bits: u64,
+    hashes: u32,
+    data: String,
+}
+
+impl BloomFilter {
+    /// Filter sized for `items` at [`FALSE_POSITIVE_RATE`], up to
+    /// [`MAX_FILTER_BITS`]
+    pub fn with_capacity(items: usize) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-items * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_bits = num_bits.clamp(64, MAX_FILTER_BITS);
+        let hashes = ((num_bits as f64 / items) * ln2).round() as u32;
+
+        BloomFilter {
+            num_bits,
+            hashes: hashes.clamp(1, MAX_FILTER_HASHES),
+            bits: vec![0; num_bits.div_ceil(8) as usize],
+        }
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        for bit in self.positions(item) {
+            self.bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+    }
+
+    /// False means `item` was never inserted; true means it probably was
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.positions(item)
+            .all(|bit| self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+    }
+
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = u64> + use<> {
+        let (num_bits, hashes) = (self.num_bits, self.hashes);
+        let digest = blake3::hash(item);
+        let bytes = digest.as_bytes();
+        let h1 = u64::from_le_bytes(bytes[..8].try_into().unwrap_or_default());
+        // Odd, so successive positions never repeat early
+        let h2 = u64::from_le_bytes(bytes[8..16].try_into().unwrap_or_default()) | 1;
+        (0..hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+}
+
+impl TryFrom<EncodedFilter> for BloomFilter {
+    type Error = AppError;
+
+    fn try_from(encoded: EncodedFilter) -> Result<Self, Self::Error> {
+        if encoded.bits == 0 || encoded.bits > MAX_FILTER_BITS {
+            return Err(AppError::Validation(format!(
+                "Filter must be 1 to {} bits",
+                MAX_FILTER_BITS
+            )));
+        }
+        if encoded.hashes == 0 || encoded.hashes > MAX_FILTER_HASHES {
+            return Err(AppError::Validation(format!(
+                "Filter must use 1 to {} hashes",
+                MAX_FILTER_HASHES
+            )));
+        }
+        let (_, bits) = multibase::decode(&encoded.data)
+            .map_err(|e| AppError::Validation(format!("Invalid filter encoding: {}", e)))?;
+        if bits.len() as u64 != encoded.bits.div_ceil(8) {
+            return Err(AppError::Validation(
+                "Filter data doesn't match its size".to_string(),
+            ));
+        }
+
+        Ok(BloomFilter {
+            num_bits: encoded.bits,
+            hashes: encoded.hashes,
+            bits,
+        })
+    }
+}
+
+impl From<BloomFilter> for EncodedFilter {
+    fn from(filter: BloomFilter) -> Self {
+        EncodedFilter {
+            bits: filter.num_bits,
+            hashes: filter.hashes,
+            data: multibase::encode(Base::Base64Url, &filter.bits),
+        }
+    }
+}
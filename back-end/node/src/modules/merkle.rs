@@ -0,0 +1,330 @@
+//! Merkle tree over the files of a space.
+//!
+//! A file's leaf is keyed by the BLAKE3 hash of its path, and leaves are
+//! grouped into a 16-ary tree on the hex digits of that key, [`DEPTH`]
+//! levels deep. The buckets at the bottom hash their leaves in key order and
+//! every node above hashes its 16 children, so changing a file rehashes one
+//! bucket and its ancestors. Peers diff two trees from the root down,
+//! descending only into children whose hashes differ.
+//!
+//! The tree lives in sled, one tree per space. The root is copied to the
+//! space's database row, where it can be shared or anchored as a compact
+//! claim about the space's contents. Files changed on disk outside the node
+//! leave the tree stale until it is rebuilt or the space is scanned.
+
+use std::collections::BTreeMap;
+
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+use sled::transaction::{ConflictableTransactionError, TransactionError, TransactionalTree};
+use sled::{Batch, Db};
+
+use crate::modules::manifest::ManifestEntry;
+
+/// Levels below the root; buckets sit at this depth
+pub const DEPTH: usize = 4;
+const FANOUT: usize = 16;
+/// Hash of a subtree without files
+const EMPTY: [u8; 32] = [0; 32];
+
+const BUCKET: u8 = b'b';
+const NODE: u8 = b'n';
+
+/// A node of the tree as peers see it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleNode {
+    /// Hex digits of the leaf keys below this node; "" for the root
+    pub prefix: String,
+    pub hash: String,
+    /// Hashes of the 16 children, above the buckets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<String>,
+    /// Files in the bucket, at the bottom
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub leaves: Vec<ManifestEntry>,
+}
+
+/// Children of two versions of the same node whose hashes differ, as the
+/// prefixes to descend into next
+pub fn differing_children(ours: &MerkleNode, theirs: &MerkleNode) -> Vec<String> {
+    ours.children
+        .iter()
+        .zip(&theirs.children)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(digit, _)| format!("{}{:x}", ours.prefix, digit))
+        .collect()
+}
+
+/// A space's Merkle tree
+pub struct MerkleIndex {
+    tree: sled::Tree,
+}
+
+impl MerkleIndex {
+    pub fn open(kv: &Db, space_key: &str) -> Result<Self, AppError> {
+        let tree = kv
+            .open_tree(format!("merkle:{}", space_key))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(Self { tree })
+    }
+
+    /// Drop a space's index
+    pub fn drop_index(kv: &Db, space_key: &str) -> Result<(), AppError> {
+        kv.drop_tree(format!("merkle:{}", space_key))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(())
+    }
+
+    /// Hex root hash
+    pub fn root(&self) -> Result<String, AppError> {
+        Ok(hex(&self.hash(&node_key(""))?))
+    }
+
+    /// Replace the whole tree with `entries`, returning the new root
+    pub fn rebuild(&self, entries: &[ManifestEntry]) -> Result<String, AppError> {
+        let (batch, root) = self.plan_rebuild(entries)?;
+        self.apply(batch)?;
+        Ok(root)
+    }
+
+    /// The writes replacing the whole tree with `entries`, and the root they
+    /// lead to; nothing changes until they are applied
+    pub fn plan_rebuild(&self, entries: &[ManifestEntry]) -> Result<(Batch, String), AppError> {
+        let mut buckets: BTreeMap<String, Vec<ManifestEntry>> = BTreeMap::new();
+        for entry in entries {
+            let key = leaf_key(&entry.path);
+            buckets
+                .entry(key[..DEPTH].to_string())
+                .or_default()
+                .push(entry.clone());
+        }
+
+        let mut batch = Batch::default();
+        for key in self.tree.iter().keys() {
+            batch.remove(key.map_err(|e| AppError::Storage(Box::new(e)))?);
+        }
+
+        // Hash the buckets, then each level above from the one below
+        let mut level: BTreeMap<String, [u8; 32]> = BTreeMap::new();
+        let mut root = EMPTY;
+        for (prefix, mut leaves) in buckets {
+            leaves.sort_by_key(|leaf| leaf_key(&leaf.path));
+            batch.insert(bucket_key(&prefix), encode_leaves(&leaves)?);
+            level.insert(prefix, bucket_hash(&leaves));
+        }
+        for depth in (0..=DEPTH).rev() {
+            if depth == 0 {
+                root = level.get("").copied().unwrap_or(EMPTY);
+            }
+            let mut parents: BTreeMap<String, [[u8; 32]; FANOUT]> = BTreeMap::new();
+            for (prefix, hash) in &level {
+                batch.insert(node_key(prefix), hash.to_vec());
+                if depth > 0 {
+                    let digit = usize::from_str_radix(&prefix[depth - 1..], 16).unwrap_or(0);
+                    parents
+                        .entry(prefix[..depth - 1].to_string())
+                        .or_insert([EMPTY; FANOUT])[digit] = *hash;
+                }
+            }
+            level = parents
+                .into_iter()
+                .map(|(prefix, children)| (prefix, node_hash(&children)))
+                .collect();
+        }
+
+        Ok((batch, hex(&root)))
+    }
+
+    /// Apply writes planned by [`Self::plan_rebuild`]
+    pub fn apply(&self, batch: Batch) -> Result<(), AppError> {
+        self.tree
+            .apply_batch(batch)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    /// Add or update a file's leaf, returning the new root
+    pub fn upsert(&self, entry: &ManifestEntry) -> Result<String, AppError> {
+        self.update(&entry.path, |leaves| {
+            leaves.retain(|leaf| leaf.path != entry.path);
+            leaves.push(entry.clone());
+        })
+    }
+
+    /// Drop a file's leaf, returning the new root
+    pub fn remove(&self, path: &str) -> Result<String, AppError> {
+        self.update(path, |leaves| leaves.retain(|leaf| leaf.path != path))
+    }
+
+    /// Every file's leaf, in key order
+    pub fn entries(&self) -> Result<Vec<ManifestEntry>, AppError> {
+        let mut entries = Vec::new();
+        for bucket in self.tree.scan_prefix([BUCKET]).values() {
+            entries.extend(decode_leaves(
+                &bucket.map_err(|e| AppError::Storage(Box::new(e)))?,
+            )?);
+        }
+        Ok(entries)
+    }
+
+    /// The node at `prefix`, up to [`DEPTH`] lowercase hex digits
+    pub fn node(&self, prefix: &str) -> Result<MerkleNode, AppError> {
+        if prefix.len() > DEPTH
+            || !prefix
+                .bytes()
+                .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return Err(AppError::Validation(format!(
+                "Invalid tree prefix: expected up to {} lowercase hex digits",
+                DEPTH
+            )));
+        }
+
+        let mut node = MerkleNode {
+            prefix: prefix.to_string(),
+            hash: hex(&self.hash(&node_key(prefix))?),
+            children: Vec::new(),
+            leaves: Vec::new(),
+        };
+        if prefix.len() == DEPTH {
+            node.leaves = match self.get(&bucket_key(prefix))? {
+                Some(bytes) => decode_leaves(&bytes)?,
+                None => Vec::new(),
+            };
+        } else {
+            for digit in 0..FANOUT {
+                let child = format!("{}{:x}", prefix, digit);
+                node.children.push(hex(&self.hash(&node_key(&child))?));
+            }
+        }
+        Ok(node)
+    }
+
+    /// Change the leaves of `path`'s bucket, then rehash up to the root
+    fn update(
+        &self,
+        path: &str,
+        change: impl Fn(&mut Vec<ManifestEntry>),
+    ) -> Result<String, AppError> {
+        let key = leaf_key(path);
+        let bucket = &key[..DEPTH];
+
+        self.tree
+            .transaction(|tx| {
+                let mut leaves = match tx.get(bucket_key(bucket))? {
+                    Some(bytes) => {
+                        decode_leaves(&bytes).map_err(ConflictableTransactionError::Abort)?
+                    }
+                    None => Vec::new(),
+                };
+                change(&mut leaves);
+                leaves.sort_by_key(|leaf| leaf_key(&leaf.path));
+
+                if leaves.is_empty() {
+                    tx.remove(bucket_key(bucket))?;
+                } else {
+                    let encoded =
+                        encode_leaves(&leaves).map_err(ConflictableTransactionError::Abort)?;
+                    tx.insert(bucket_key(bucket), encoded)?;
+                }
+                set_hash(tx, bucket, bucket_hash(&leaves))?;
+
+                for depth in (0..DEPTH).rev() {
+                    let prefix = &bucket[..depth];
+                    let mut children = [EMPTY; FANOUT];
+                    for (digit, child) in children.iter_mut().enumerate() {
+                        if let Some(hash) = tx.get(node_key(&format!("{}{:x}", prefix, digit)))? {
+                            child.copy_from_slice(&hash);
+                        }
+                    }
+                    set_hash(tx, prefix, node_hash(&children))?;
+                }
+                Ok(())
+            })
+            .map_err(|e| match e {
+                TransactionError::Abort(e) => e,
+                TransactionError::Storage(e) => AppError::Storage(Box::new(e)),
+            })?;
+
+        self.root()
+    }
+
+    fn hash(&self, key: &[u8]) -> Result<[u8; 32], AppError> {
+        let mut hash = EMPTY;
+        if let Some(bytes) = self.get(key)? {
+            hash.copy_from_slice(&bytes);
+        }
+        Ok(hash)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<sled::IVec>, AppError> {
+        self.tree
+            .get(key)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+/// Store a node's hash; empty subtrees aren't stored
+fn set_hash(
+    tx: &TransactionalTree,
+    prefix: &str,
+    hash: [u8; 32],
+) -> Result<(), ConflictableTransactionError<AppError>> {
+    if hash == EMPTY {
+        tx.remove(node_key(prefix))?;
+    } else {
+        tx.insert(node_key(prefix), hash.to_vec())?;
+    }
+    Ok(())
+}
+
+/// Hex BLAKE3 of the path
+fn leaf_key(path: &str) -> String {
+    blake3::hash(path.as_bytes()).to_hex().to_string()
+}
+
+fn leaf_hash(entry: &ManifestEntry) -> blake3::Hash {
+    blake3::hash(format!("{}\0{}\0{}", entry.path, entry.blake3, entry.size).as_bytes())
+}
+
+fn bucket_hash(leaves: &[ManifestEntry]) -> [u8; 32] {
+    if leaves.is_empty() {
+        return EMPTY;
+    }
+    let mut hasher = blake3::Hasher::new();
+    for leaf in leaves {
+        hasher.update(leaf_hash(leaf).as_bytes());
+    }
+    *hasher.finalize().as_bytes()
+}
+
+fn node_hash(children: &[[u8; 32]; FANOUT]) -> [u8; 32] {
+    if children.iter().all(|child| *child == EMPTY) {
+        return EMPTY;
+    }
+    let mut hasher = blake3::Hasher::new();
+    for child in children {
+        hasher.update(child);
+    }
+    *hasher.finalize().as_bytes()
+}
+
+fn node_key(prefix: &str) -> Vec<u8> {
+    [&[NODE], prefix.as_bytes()].concat()
+}
+
+fn bucket_key(prefix: &str) -> Vec<u8> {
+    [&[BUCKET], prefix.as_bytes()].concat()
+}
+
+fn encode_leaves(leaves: &[ManifestEntry]) -> Result<Vec<u8>, AppError> {
+    serde_json::to_vec(leaves).map_err(|e| AppError::Storage(Box::new(e)))
+}
+
+fn decode_leaves(bytes: &[u8]) -> Result<Vec<ManifestEntry>, AppError> {
+    serde_json::from_slice(bytes).map_err(|e| AppError::Storage(Box::new(e)))
+}
+
+fn hex(hash: &[u8; 32]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -1,2 +1,43 @@
+pub mod account;
+pub mod acme;
+pub mod anchor;
+pub mod archive;
+pub mod attestation;
+pub mod auth_crypto;
+pub mod clock;
+pub mod column_crypto;
+pub mod health;
+pub mod history;
+pub mod jws;
+pub mod key_rotation;
+pub mod key_usage;
+pub mod maintenance;
+pub mod manifest;
+pub mod manifest_summary;
+pub mod merkle;
+pub mod mtls;
+pub mod node_info;
+pub mod outbox;
+pub mod p2p;
+pub mod peer_request;
+pub mod pin;
+pub mod profile;
+pub mod resources;
+pub mod retention;
+pub mod scheduler;
+pub mod secrets;
+pub mod session;
+pub mod session_token;
+pub mod settings;
+pub mod soft_delete;
 pub mod space;
+pub mod space_crypto;
 pub mod ssi;
+pub mod storage_stats;
+pub mod telemetry;
+pub mod tenancy;
+pub mod timestamp;
+pub mod updater;
+pub mod validation;
+pub mod versioning;
+pub mod webhook;
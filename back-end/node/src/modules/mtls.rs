@@ -0,0 +1,396 @@
+//! Mutual TLS between federated nodes.
+//!
+//! A node's TLS certificate is self-signed with its Ed25519 identity key and
+//! names the node DID as a URI subject alternative name. There is no CA: a
+//! peer is trusted for the DID its certificate names once the certificate key
+//! is shown to belong to that DID. The handshake itself proves the peer holds
+//! the certificate key, so the certificate signature adds nothing and is not
+//! checked.
+//!
+//! `did:key` binds the key into the DID and is checked during the handshake.
+//! Other methods need their document resolved, which
+//! [`authenticate_peer`] does once the handshake has completed.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use errors::AppError;
+use rcgen::{CertificateParams, KeyPair, PKCS_ED25519, SanType};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, DistinguishedName, ServerConfig, SignatureScheme,
+};
+use serde_json::Value;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use x509_parser::oid_registry::OID_SIG_ED25519;
+use zeroize::Zeroizing;
+
+use crate::bootstrap::init::NodeData;
+use crate::modules::manifest;
+use crate::modules::ssi::did::DidResolver;
+use crate::modules::ssi::did::types::ResolutionOptions;
+
+/// How long an issued node certificate is valid for
+pub const DEFAULT_VALIDITY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// PKCS#8 v1 header of an Ed25519 private key; the 32-byte seed follows
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// A node's TLS certificate and the identity key it was issued for
+pub struct NodeCertificate {
+    did: String,
+    cert: CertificateDer<'static>,
+    key: Zeroizing<PrivatePkcs8KeyDer<'static>>,
+    cert_pem: String,
+}
+
+impl NodeCertificate {
+    /// Certificate for the node's own DID and identity key
+    pub fn issue(node_data: &NodeData, validity: Duration) -> Result<Self, AppError> {
+        Self::issue_for(&manifest::signing_key(node_data)?, &node_data.id, validity)
+    }
+
+    /// Certificate naming `did`, signed with `signing_key`
+    pub fn issue_for(
+        signing_key: &SigningKey,
+        did: &str,
+        validity: Duration,
+    ) -> Result<Self, AppError> {
+        let failed = |e: rcgen::Error| AppError::Crypto(format!("Cannot issue certificate: {}", e));
+
+        let mut pkcs8 = ED25519_PKCS8_PREFIX.to_vec();
+        pkcs8.extend_from_slice(signing_key.as_bytes());
+        let key = Zeroizing::new(PrivatePkcs8KeyDer::from(pkcs8));
+        let key_pair =
+            KeyPair::from_pkcs8_der_and_sign_algo(&key, &PKCS_ED25519).map_err(failed)?;
+
+        let mut params = CertificateParams::default();
+        params.subject_alt_names = vec![SanType::URI(did.try_into().map_err(failed)?)];
+        params.not_before = time::OffsetDateTime::now_utc() - time::Duration::minutes(5);
+        params.not_after = time::OffsetDateTime::now_utc()
+            + time::Duration::try_from(validity)
+                .map_err(|e| AppError::Validation(format!("Invalid validity: {}", e)))?;
+        let cert = params.self_signed(&key_pair).map_err(failed)?;
+
+        Ok(Self {
+            did: did.to_string(),
+            cert_pem: cert.pem(),
+            cert: cert.der().clone(),
+            key,
+        })
+    }
+
+    pub fn did(&self) -> &str {
+        &self.did
+    }
+
+    pub fn cert_der(&self) -> &[u8] {
+        &self.cert
+    }
+
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    /// TLS 1.3 server config requiring a DID-bound client certificate
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>, AppError> {
+        let provider = provider();
+        let verifier = Arc::new(DidCertVerifier::new(None, &provider));
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(tls_error)?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(vec![self.cert.clone()], self.private_key())
+            .map_err(tls_error)?;
+        Ok(Arc::new(config))
+    }
+
+    /// TLS 1.3 client config presenting this certificate and accepting only
+    /// a server certificate for `peer_did`
+    pub fn client_config(&self, peer_did: &str) -> Result<Arc<ClientConfig>, AppError> {
+        let provider = provider();
+        let verifier = Arc::new(DidCertVerifier::new(Some(peer_did), &provider));
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(tls_error)?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier)
+            .with_client_auth_cert(vec![self.cert.clone()], self.private_key())
+            .map_err(tls_error)?;
+        Ok(Arc::new(config))
+    }
+
+    pub fn acceptor(&self) -> Result<TlsAcceptor, AppError> {
+        Ok(TlsAcceptor::from(self.server_config()?))
+    }
+
+    pub fn connector(&self, peer_did: &str) -> Result<TlsConnector, AppError> {
+        Ok(TlsConnector::from(self.client_config(peer_did)?))
+    }
+
+    fn private_key(&self) -> PrivateKeyDer<'static> {
+        PrivateKeyDer::Pkcs8(self.key.clone_key())
+    }
+}
+
+/// Who a certificate says its holder is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerIdentity {
+    pub did: String,
+    pub public_key: VerifyingKey,
+}
+
+/// DID and Ed25519 key named by a node certificate
+pub fn peer_identity(cert_der: &[u8]) -> Result<PeerIdentity, AppError> {
+    let invalid = |reason: String| AppError::Auth(format!("Invalid node certificate: {}", reason));
+
+    let (_, cert) =
+        x509_parser::parse_x509_certificate(cert_der).map_err(|e| invalid(e.to_string()))?;
+
+    let spki = cert.public_key();
+    if spki.algorithm.algorithm != OID_SIG_ED25519 {
+        return Err(invalid("key is not Ed25519".to_string()));
+    }
+    let key_bytes: [u8; 32] = spki
+        .subject_public_key
+        .data
+        .as_ref()
+        .try_into()
+        .map_err(|_| invalid("Ed25519 key must be 32 bytes".to_string()))?;
+    let public_key = VerifyingKey::from_bytes(&key_bytes).map_err(|e| invalid(e.to_string()))?;
+
+    let did = cert
+        .subject_alternative_name()
+        .map_err(|e| invalid(e.to_string()))?
+        .and_then(|san| {
+            san.value.general_names.iter().find_map(|name| match name {
+                x509_parser::extensions::GeneralName::URI(uri) if uri.starts_with("did:") => {
+                    Some(uri.to_string())
+                }
+                _ => None,
+            })
+        })
+        .ok_or_else(|| invalid("no DID subject alternative name".to_string()))?;
+
+    Ok(PeerIdentity { did, public_key })
+}
+
+/// Whether `identity`'s key is bound to its DID without resolving it
+fn bound_locally(identity: &PeerIdentity) -> Option<bool> {
+    identity.did.starts_with("did:key:").then(|| {
+        identity.did
+            == format!(
+                "did:key:{}",
+                manifest::encode_public_key(&identity.public_key)
+            )
+    })
+}
+
+/// Check the certificate key is a verification method of the DID
+pub async fn verify_binding(
+    resolver: &DidResolver,
+    identity: &PeerIdentity,
+) -> Result<(), AppError> {
+    let bound = match bound_locally(identity) {
+        Some(bound) => bound,
+        None => {
+            let result = resolver
+                .resolve_did(&identity.did, &ResolutionOptions::new())
+                .await
+                .map_err(|e| AppError::Auth(format!("Cannot resolve {}: {}", identity.did, e)))?;
+            let document = result
+                .did_document
+                .and_then(|document| serde_json::to_value(document).ok())
+                .ok_or_else(|| AppError::Auth(format!("{} has no DID document", identity.did)))?;
+            document_has_key(&document, &identity.public_key)
+        }
+    };
+
+    if bound {
+        Ok(())
+    } else {
+        Err(AppError::Auth(format!(
+            "Certificate key does not belong to {}",
+            identity.did
+        )))
+    }
+}
+
+/// Whether a verification method of `document` is `key`, as multibase or JWK
+fn document_has_key(document: &Value, key: &VerifyingKey) -> bool {
+    let multibase = manifest::encode_public_key(key);
+    let x = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(key.as_bytes());
+
+    document["verificationMethod"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|method| {
+            method["publicKeyMultibase"] == multibase.as_str()
+                || (method["publicKeyJwk"]["crv"] == "Ed25519"
+                    && method["publicKeyJwk"]["x"] == x.as_str())
+        })
+}
+
+/// Identity of the peer at the other end of a completed handshake, with its
+/// certificate key checked against its DID
+pub async fn authenticate_peer(
+    resolver: &DidResolver,
+    certificates: Option<&[CertificateDer<'_>]>,
+) -> Result<PeerIdentity, AppError> {
+    let cert = certificates
+        .and_then(|certs| certs.first())
+        .ok_or_else(|| AppError::Auth("Peer presented no certificate".to_string()))?;
+    let identity = peer_identity(cert)?;
+    verify_binding(resolver, &identity).await?;
+    Ok(identity)
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn tls_error(e: rustls::Error) -> AppError {
+    AppError::Crypto(format!("TLS configuration failed: {}", e))
+}
+
+/// Accepts certificates naming a DID and an Ed25519 key, optionally only for
+/// one expected DID. Host names are not checked: peers are identified by DID.
+struct DidCertVerifier {
+    expected: Option<String>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl DidCertVerifier {
+    fn new(expected: Option<&str>, provider: &CryptoProvider) -> Self {
+        Self {
+            expected: expected.map(str::to_string),
+            algorithms: provider.signature_verification_algorithms,
+        }
+    }
+
+    fn check(&self, end_entity: &CertificateDer<'_>, now: UnixTime) -> Result<(), rustls::Error> {
+        let rejected = |reason: String| rustls::Error::General(reason);
+
+        let identity = peer_identity(end_entity).map_err(|e| rejected(e.to_string()))?;
+        if let Some(expected) = &self.expected
+            && identity.did != *expected
+        {
+            return Err(rejected(format!(
+                "Expected a certificate for {}, got {}",
+                expected, identity.did
+            )));
+        }
+        if bound_locally(&identity) == Some(false) {
+            return Err(rejected(format!(
+                "Certificate key does not belong to {}",
+                identity.did
+            )));
+        }
+
+        let (_, cert) =
+            x509_parser::parse_x509_certificate(end_entity).map_err(|e| rejected(e.to_string()))?;
+        let now = now.as_secs() as i64;
+        let validity = cert.validity();
+        if now < validity.not_before.timestamp() || now > validity.not_after.timestamp() {
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::Expired,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for DidCertVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DidCertVerifier")
+            .field("expected", &self.expected)
+            .finish()
+    }
+}
+
+impl ServerCertVerifier for DidCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.check(end_entity, now)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::General(
+            "TLS 1.2 is not used between nodes".to_string(),
+        ))
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
+
+impl ClientCertVerifier for DidCertVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.check(end_entity, now)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Err(rustls::Error::General(
+            "TLS 1.2 is not used between nodes".to_string(),
+        ))
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ED25519]
+    }
+}
@@ -0,0 +1,141 @@
+//! Node info and capability discovery, for clients and federation peers to
+//! negotiate features with.
+
+use serde::Serialize;
+
+use crate::api::node::Node;
+use crate::bootstrap::config::ServerConfig;
+use crate::modules::attestation::ATTESTATION_SCHEMA;
+use crate::modules::manifest::MANIFEST_SCHEMA;
+use crate::modules::manifest_summary::SUMMARY_SCHEMA;
+use crate::modules::ssi::did::resolvers::adapter::DidResolver;
+use crate::modules::updater::CURRENT_VERSION;
+
+pub const API_VERSION: &str = "v1";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfo {
+    pub did: String,
+    pub software_version: String,
+    /// DID methods this node can resolve
+    pub did_methods: Vec<String>,
+    pub subsystems: Subsystems,
+    pub protocols: Protocols,
+    pub endpoints: Endpoints,
+    pub plugins: Vec<PluginInfo>,
+}
+
+/// Which optional parts of the node are available
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subsystems {
+    pub sync: bool,
+    pub messaging: bool,
+    pub verifiable_credentials: bool,
+    pub webauthn: bool,
+    pub local_pin: bool,
+    pub manifests: bool,
+    pub attestation: bool,
+    pub scripting: bool,
+}
+
+/// Versions of the wire formats this node speaks
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Protocols {
+    pub api: String,
+    pub manifest: String,
+    pub manifest_summary: String,
+    pub attestation: String,
+}
+
+/// Where the public interfaces live. Paths are relative to the REST server's
+/// origin.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoints {
+    /// Absolute URL clients reach the REST server at, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub rest: String,
+    pub attest: String,
+    pub health: String,
+    pub websocket: String,
+    /// The WebSocket server listens on its own port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub websocket_port: Option<u16>,
+    /// `host:port` addresses peers should connect to
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub advertised: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub name: String,
+    pub version: String,
+}
+
+impl NodeInfo {
+    pub fn new(node: &Node, server: Option<&ServerConfig>, plugins: &[(String, String)]) -> Self {
+        Self {
+            did: node.node_data.id.clone(),
+            software_version: CURRENT_VERSION.to_string(),
+            did_methods: DidResolver::new()
+                .supported_methods()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            subsystems: Subsystems {
+                sync: false,
+                messaging: false,
+                verifiable_credentials: true,
+                webauthn: true,
+                local_pin: true,
+                manifests: true,
+                attestation: true,
+                scripting: cfg!(feature = "scripting"),
+            },
+            protocols: Protocols {
+                api: API_VERSION.to_string(),
+                manifest: MANIFEST_SCHEMA.to_string(),
+                manifest_summary: SUMMARY_SCHEMA.to_string(),
+                attestation: ATTESTATION_SCHEMA.to_string(),
+            },
+            endpoints: Endpoints {
+                url: None,
+                rest: format!("/api/{}", API_VERSION),
+                attest: format!("/api/{}/node/attest", API_VERSION),
+                health: format!("/api/{}/health", API_VERSION),
+                websocket: "/ws".to_string(),
+                websocket_port: server.map(|s| s.websocket_port),
+                advertised: server
+                    .map(|s| s.advertised_addresses.clone())
+                    .unwrap_or_default(),
+            },
+            plugins: plugins
+                .iter()
+                .map(|(name, version)| PluginInfo {
+                    name: name.clone(),
+                    version: version.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Links as seen through a reverse proxy serving the node under
+    /// `base_path`, at `origin`
+    pub fn behind_proxy(mut self, base_path: &str, origin: Option<&str>) -> Self {
+        let endpoints = &mut self.endpoints;
+        for path in [
+            &mut endpoints.rest,
+            &mut endpoints.attest,
+            &mut endpoints.health,
+            &mut endpoints.websocket,
+        ] {
+            path.insert_str(0, base_path);
+        }
+        endpoints.url = origin.map(|origin| format!("{}{}", origin, base_path));
+        self
+    }
+}
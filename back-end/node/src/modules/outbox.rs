@@ -0,0 +1,135 @@
+//! Transactional outbox for events born of database changes.
+//!
+//! Such an event (a user created, a space's files indexed) is written to the
+//! `outbox` table with [`enqueue`] inside the transaction making the change,
+//! rather than published directly. If the transaction rolls back, the event
+//! is gone with it; once it commits, the event outlives a crash. After the
+//! commit, [`Node::dispatch_outbox`] publishes rows oldest first to the
+//! node's listeners (WebSocket, webhooks, scripts) and deletes them; the
+//! dispatcher task ([`Outbox::run`]) picks up whatever a crash left behind.
+//!
+//! A crash between publishing and deleting a row publishes it again, so
+//! delivery is at least once. Each event carries its row's ID in
+//! [`ID_FIELD`] for consumers to drop repeats by.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use entity::outbox;
+use errors::AppError;
+use event::schema::ID_FIELD;
+use event::types::{Event, EventType};
+use log::{error, warn};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ActiveValue::Set, ColumnTrait, ConnectionTrait,
+    DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde_json::{Map, Value};
+use tokio::sync::Mutex;
+
+use crate::api::node::Node;
+
+/// Rows published per batch
+const BATCH: u64 = 100;
+/// How often the dispatcher task looks for rows left behind
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Write `event` to the outbox through `conn`, usually a transaction; it is
+/// published once that commits and the outbox is next dispatched
+pub async fn enqueue(conn: &impl ConnectionTrait, event: &Event) -> Result<(), AppError> {
+    let properties: Map<String, Value> = event.properties.clone().into_iter().collect();
+    outbox::ActiveModel {
+        id: NotSet,
+        event_type: Set(event.event_type.as_str().to_string()),
+        properties: Set(Value::Object(properties).to_string()),
+        time_created: Set(Utc::now().into()),
+    }
+    .insert(conn)
+    .await
+    .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct Outbox {
+    db: DatabaseConnection,
+    /// Held while dispatching, so two dispatches don't publish a row twice
+    dispatching: Arc<Mutex<()>>,
+}
+
+impl Outbox {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            db,
+            dispatching: Arc::default(),
+        }
+    }
+
+    /// Events waiting to be published
+    pub async fn pending(&self) -> Result<u64, AppError> {
+        outbox::Entity::find()
+            .count(&self.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    /// Publish every waiting event through `publish`, oldest first, deleting
+    /// each after; returns how many were published
+    pub async fn dispatch(&self, publish: impl Fn(&Event)) -> Result<usize, AppError> {
+        let _dispatching = self.dispatching.lock().await;
+        let mut published = 0;
+        loop {
+            let rows = outbox::Entity::find()
+                .order_by_asc(outbox::Column::Id)
+                .limit(BATCH)
+                .all(&self.db)
+                .await
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+            let Some(last) = rows.last().map(|row| row.id) else {
+                return Ok(published);
+            };
+
+            for row in rows {
+                match decode(&row) {
+                    Some(event) => {
+                        publish(&event);
+                        published += 1;
+                    }
+                    // Dropped with the batch rather than retried forever
+                    None => error!("Dropping undecodable outbox event {}", row.id),
+                }
+            }
+            outbox::Entity::delete_many()
+                .filter(outbox::Column::Id.lte(last))
+                .exec(&self.db)
+                .await
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+        }
+    }
+
+    /// Dispatch to `node`'s listeners every [`POLL_INTERVAL`], starting
+    /// now; never returns
+    pub async fn run(self, node: Node) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.dispatch(|event| node.publish(event)).await {
+                warn!("Outbox dispatch failed: {}", e);
+            }
+        }
+    }
+}
+
+fn decode(row: &outbox::Model) -> Option<Event> {
+    let event_type = EventType::from_str(&row.event_type).ok()?;
+    let Ok(Value::Object(properties)) = serde_json::from_str(&row.properties) else {
+        return None;
+    };
+    Some(
+        Event::new(event_type)
+            .with_properties(properties)
+            .with(ID_FIELD, row.id),
+    )
+}
@@ -0,0 +1,121 @@
+//! The multiaddrs peers are dialed at, in their text form:
+//! `/ip4/192.0.2.7/tcp/4001/p2p/did:key:z6Mk...`. The `/p2p/` component names
+//! the peer by DID, where libp2p would use a peer id; dialing needs it, since
+//! the handshake is pinned to that DID.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use errors::AppError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Host {
+    Ip(IpAddr),
+    /// `/dns/`, `/dns4/` or `/dns6/`; resolved when dialed
+    Dns(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Multiaddr {
+    pub host: Host,
+    pub port: u16,
+    /// DID of the node listening there
+    pub peer: Option<String>,
+}
+
+impl Multiaddr {
+    pub fn new(host: Host, port: u16) -> Self {
+        Self {
+            host,
+            port,
+            peer: None,
+        }
+    }
+
+    pub fn with_peer(mut self, did: impl Into<String>) -> Self {
+        self.peer = Some(did.into());
+        self
+    }
+
+    /// `host:port`, for connecting
+    pub fn socket_addr(&self) -> String {
+        match &self.host {
+            Host::Ip(IpAddr::V6(ip)) => format!("[{}]:{}", ip, self.port),
+            Host::Ip(ip) => format!("{}:{}", ip, self.port),
+            Host::Dns(name) => format!("{}:{}", name, self.port),
+        }
+    }
+}
+
+impl FromStr for Multiaddr {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |reason: &str| AppError::Validation(format!("Invalid multiaddr '{}': {}", s, reason));
+        let mut parts = s
+            .strip_prefix('/')
+            .ok_or_else(|| invalid("must start with '/'"))?
+            .split('/');
+        let mut next = |what: &str| {
+            parts
+                .next()
+                .filter(|part| !part.is_empty())
+                .ok_or_else(|| invalid(&format!("missing {}", what)))
+        };
+
+        let host = match next("protocol")? {
+            "ip4" => Host::Ip(IpAddr::V4(
+                next("address")?
+                    .parse::<Ipv4Addr>()
+                    .map_err(|_| invalid("bad IPv4 address"))?,
+            )),
+            "ip6" => Host::Ip(IpAddr::V6(
+                next("address")?
+                    .parse::<Ipv6Addr>()
+                    .map_err(|_| invalid("bad IPv6 address"))?,
+            )),
+            "dns" | "dns4" | "dns6" => Host::Dns(next("host name")?.to_string()),
+            other => return Err(invalid(&format!("unsupported protocol '{}'", other))),
+        };
+        if next("transport")? != "tcp" {
+            return Err(invalid("only tcp is supported"));
+        }
+        let port = next("port")?
+            .parse::<u16>()
+            .map_err(|_| invalid("bad port"))?;
+
+        let peer = match parts.next() {
+            None => None,
+            Some("p2p") => {
+                let did = parts.next().filter(|did| did.starts_with("did:"));
+                Some(
+                    did.ok_or_else(|| invalid("/p2p/ must name a DID"))?
+                        .to_string(),
+                )
+            }
+            Some(other) => return Err(invalid(&format!("unexpected '{}'", other))),
+        };
+        if parts.next().is_some() {
+            return Err(invalid("trailing components"));
+        }
+
+        Ok(Self { host, port, peer })
+    }
+}
+
+impl fmt::Display for Multiaddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.host {
+            Host::Ip(IpAddr::V4(ip)) => write!(f, "/ip4/{}", ip)?,
+            Host::Ip(IpAddr::V6(ip)) => write!(f, "/ip6/{}", ip)?,
+            Host::Dns(name) => write!(f, "/dns/{}", name)?,
+        }
+        write!(f, "/tcp/{}", self.port)?;
+        if let Some(peer) = &self.peer {
+            write!(f, "/p2p/{}", peer)?;
+        }
+        Ok(())
+    }
+}
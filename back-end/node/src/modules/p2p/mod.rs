@@ -0,0 +1,251 @@
+//! Node-to-node transport.
+//!
+//! Nodes talk over TCP wrapped in mutual TLS 1.3 (see [`mtls`]): each side
+//! presents a certificate issued with its Ed25519 identity key and naming its
+//! DID, so once the handshake completes both know which node they are talking
+//! to and every message on the connection is from it. Peers are dialed by
+//! [`Multiaddr`], which names the DID the handshake must reach.
+//!
+//! Connections carry [`Message`]s both ways. Inbound ones go to a
+//! [`MessageHandler`], which may answer each. This is the layer space sync is
+//! to be built on; the node's own handler only publishes what arrives as
+//! `message_received` events.
+
+pub mod addr;
+pub mod wire;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use errors::AppError;
+use event::schema::MessageReceived;
+use event::types::{Event, EventType};
+use log::{info, warn};
+use rustls::pki_types::ServerName;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
+
+use crate::api::node::Node;
+use crate::api::servers::listen;
+use crate::bootstrap::config::Config;
+use crate::modules::mtls::{self, NodeCertificate, PeerIdentity};
+use crate::modules::ssi::did::DidResolver;
+
+pub use addr::{Host, Multiaddr};
+pub use wire::Message;
+
+/// ALPN protocol of this transport
+pub const ALPN: &[u8] = b"flow-p2p/1";
+
+/// Largest message either side accepts
+pub const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+
+/// How long connecting and the handshake may take
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handles messages from peers
+#[async_trait]
+pub trait MessageHandler: Send + Sync {
+    /// Handle `message` from `peer`, answering it with the returned message
+    async fn handle(
+        &self,
+        peer: &PeerIdentity,
+        message: Message,
+    ) -> Result<Option<Message>, AppError>;
+}
+
+/// This node's end of the transport
+#[derive(Clone)]
+pub struct P2p {
+    certificate: Arc<NodeCertificate>,
+    resolver: Arc<DidResolver>,
+}
+
+impl P2p {
+    pub fn new(certificate: NodeCertificate, resolver: Arc<DidResolver>) -> Self {
+        Self {
+            certificate: Arc::new(certificate),
+            resolver,
+        }
+    }
+
+    /// DID this node is known to peers by
+    pub fn did(&self) -> &str {
+        self.certificate.did()
+    }
+
+    /// Connect to the node at `addr`, which must name its DID
+    pub async fn dial(&self, addr: &Multiaddr) -> Result<PeerConnection, AppError> {
+        let peer_did = addr.peer.as_deref().ok_or_else(|| {
+            AppError::Validation(format!("{} doesn't name a peer (/p2p/<did>)", addr))
+        })?;
+        let mut config = (*self.certificate.client_config(peer_did)?).clone();
+        config.alpn_protocols = vec![ALPN.to_vec()];
+        let server_name = match &addr.host {
+            Host::Ip(ip) => ServerName::IpAddress((*ip).into()),
+            Host::Dns(name) => ServerName::try_from(name.clone())
+                .map_err(|_| AppError::Validation(format!("Invalid host name '{}'", name)))?,
+        };
+
+        let stream = handshake(async {
+            let tcp = TcpStream::connect(addr.socket_addr()).await?;
+            let tls = TlsConnector::from(Arc::new(config))
+                .connect(server_name, tcp)
+                .await?;
+            Ok(TlsStream::from(tls))
+        })
+        .await?;
+        PeerConnection::established(stream, &self.resolver).await
+    }
+
+    /// Complete the handshake with a node that connected to us
+    pub async fn accept(&self, tcp: TcpStream) -> Result<PeerConnection, AppError> {
+        let mut config = (*self.certificate.server_config()?).clone();
+        config.alpn_protocols = vec![ALPN.to_vec()];
+        let stream = handshake(async {
+            let tls = TlsAcceptor::from(Arc::new(config)).accept(tcp).await?;
+            Ok(TlsStream::from(tls))
+        })
+        .await?;
+        PeerConnection::established(stream, &self.resolver).await
+    }
+
+    /// Accept peers on `listener`, handing what they send to `handler`
+    pub async fn serve(
+        &self,
+        listener: TcpListener,
+        handler: Arc<dyn MessageHandler>,
+    ) -> Result<(), AppError> {
+        if let Ok(addr) = listener.local_addr() {
+            info!("P2P listening on addr: {} as {}", addr, self.did());
+        }
+        loop {
+            let (tcp, remote) = listener.accept().await?;
+            let p2p = self.clone();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let result = match p2p.accept(tcp).await {
+                    Ok(connection) => connection.handle_with(handler.as_ref()).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    warn!("P2P connection from {} failed: {}", remote, e);
+                }
+            });
+        }
+    }
+}
+
+/// Accept peers on `FLOW_P2P_PORT` on every bind address, publishing what
+/// they send; never returns when the port isn't set
+pub async fn start(node: &Node, config: &Config) -> Result<(), AppError> {
+    let Some(port) = config.server.p2p_port else {
+        return std::future::pending().await;
+    };
+
+    let certificate = NodeCertificate::issue(&node.node_data, mtls::DEFAULT_VALIDITY)?;
+    let p2p = P2p::new(certificate, node.did_resolver.clone());
+    let handler: Arc<dyn MessageHandler> = Arc::new(PublishMessages::new(node.clone()));
+    let listeners = listen::bind_all(&config.server.listen_addrs(port))?;
+    let servers = listeners
+        .into_iter()
+        .map(|listener| p2p.serve(listener, handler.clone()));
+    futures_util::future::try_join_all(servers).await?;
+    Ok(())
+}
+
+async fn handshake(
+    connecting: impl Future<Output = Result<TlsStream<TcpStream>, std::io::Error>>,
+) -> Result<TlsStream<TcpStream>, AppError> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, connecting)
+        .await
+        .map_err(|_| AppError::Auth("P2P handshake timed out".to_string()))?
+        .map_err(|e| AppError::Auth(format!("P2P handshake failed: {}", e)))
+}
+
+/// An authenticated connection to another node
+pub struct PeerConnection {
+    peer: PeerIdentity,
+    stream: TlsStream<TcpStream>,
+}
+
+impl PeerConnection {
+    /// Identify the peer by its certificate, checking the key belongs to the
+    /// DID it names
+    async fn established(
+        stream: TlsStream<TcpStream>,
+        resolver: &DidResolver,
+    ) -> Result<Self, AppError> {
+        let certificates = match &stream {
+            TlsStream::Client(tls) => tls.get_ref().1.peer_certificates(),
+            TlsStream::Server(tls) => tls.get_ref().1.peer_certificates(),
+        };
+        let peer = mtls::authenticate_peer(resolver, certificates).await?;
+        Ok(Self { peer, stream })
+    }
+
+    pub fn peer(&self) -> &PeerIdentity {
+        &self.peer
+    }
+
+    pub async fn send(&mut self, message: &Message) -> Result<(), AppError> {
+        wire::write(&mut self.stream, message, MAX_MESSAGE_BYTES).await
+    }
+
+    /// The next message, or `None` once the peer hangs up
+    pub async fn recv(&mut self) -> Result<Option<Message>, AppError> {
+        wire::read(&mut self.stream, MAX_MESSAGE_BYTES).await
+    }
+
+    /// Send `message` and wait for the answer
+    pub async fn request(&mut self, message: &Message) -> Result<Message, AppError> {
+        self.send(message).await?;
+        self.recv().await?.ok_or_else(|| {
+            AppError::IO(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("{} hung up without answering", self.peer.did),
+            ))
+        })
+    }
+
+    /// Hand every message from the peer to `handler` until it hangs up
+    pub async fn handle_with(mut self, handler: &dyn MessageHandler) -> Result<(), AppError> {
+        while let Some(message) = self.recv().await? {
+            if let Some(answer) = handler.handle(&self.peer, message).await? {
+                self.send(&answer).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Publishes what peers send as `message_received` events
+pub struct PublishMessages {
+    node: Node,
+}
+
+impl PublishMessages {
+    pub fn new(node: Node) -> Self {
+        Self { node }
+    }
+}
+
+#[async_trait]
+impl MessageHandler for PublishMessages {
+    async fn handle(
+        &self,
+        peer: &PeerIdentity,
+        message: Message,
+    ) -> Result<Option<Message>, AppError> {
+        self.node.publish(&Event::from_payload(
+            EventType::MessageReceived,
+            &MessageReceived {
+                peer: peer.did.clone(),
+                kind: message.kind,
+                body: message.body,
+            },
+        ));
+        Ok(None)
+    }
+}
@@ -0,0 +1,68 @@
+//! Messages on a peer connection: each is a big-endian `u32` length followed
+//! by that many bytes of JSON.
+
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// One message between nodes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// What the message is, e.g. `sync.offer`; receivers dispatch on it
+    pub kind: String,
+    #[serde(default)]
+    pub body: Value,
+}
+
+impl Message {
+    pub fn new(kind: impl Into<String>, body: Value) -> Self {
+        Self {
+            kind: kind.into(),
+            body,
+        }
+    }
+}
+
+pub async fn write<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+    max_bytes: usize,
+) -> Result<(), AppError> {
+    let bytes = serde_json::to_vec(message)
+        .map_err(|e| AppError::Validation(format!("Unencodable message: {}", e)))?;
+    if bytes.len() > max_bytes {
+        return Err(AppError::PayloadTooLarge(format!(
+            "Message of {} bytes is over the {} byte limit",
+            bytes.len(),
+            max_bytes
+        )));
+    }
+    writer.write_u32(bytes.len() as u32).await?;
+    writer.write_all(&bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// The next message, or `None` once the peer has closed the connection
+pub async fn read<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_bytes: usize,
+) -> Result<Option<Message>, AppError> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > max_bytes {
+        return Err(AppError::PayloadTooLarge(format!(
+            "Peer sent a {} byte message; the limit is {}",
+            len, max_bytes
+        )));
+    }
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes).await?;
+    serde_json::from_slice(&bytes)
+        .map(Some)
+        .map_err(|e| AppError::Validation(format!("Malformed message: {}", e)))
+}
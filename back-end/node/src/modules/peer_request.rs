@@ -0,0 +1,246 @@
+//! DID-signed HTTP requests between federated nodes.
+//!
+//! Peers calling each other over HTTP, rather than over the mutual TLS
+//! transport, sign each request with their identity key. The
+//! [`SIGNATURE_HEADER`] holds a compact JWS whose claims name the signer's
+//! DID and key, the node addressed, the method, path and a digest of the
+//! body, a nonce and when it was signed. As with [`mtls`] certificates, the
+//! key must be a verification method of the signer's DID.
+//!
+//! A signature is good once. Requests signed further than the replay window
+//! (`FLOW_PEER_REPLAY_WINDOW_SECS`, give or take the allowed clock skew) from
+//! now are turned away, and the nonces of the rest are kept in a
+//! [`ReplayCache`] until they fall out of the window, so a captured request
+//! can't be sent again. A request sent again is an [`AppError::Replayed`].
+//!
+//! [`mtls`]: crate::modules::mtls
+
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use errors::AppError;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sled::Db;
+
+use crate::bootstrap::init::NodeData;
+use crate::modules::clock::TimeSource;
+use crate::modules::mtls::{self, PeerIdentity};
+use crate::modules::ssi::did::DidResolver;
+use crate::modules::{auth_crypto, jws, manifest};
+
+pub const SIGNATURE_HEADER: &str = "x-flow-signature";
+pub const NONCE_TREE: &str = "peer_requests:nonces";
+pub const MIN_NONCE_LENGTH: usize = 16;
+pub const MAX_NONCE_LENGTH: usize = 128;
+
+const TOKEN_TYPE: &str = "flow-peer-request";
+
+/// What a signed request is for; a signature covers all of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target<'a> {
+    /// DID of the node addressed
+    pub audience: &'a str,
+    pub method: &'a str,
+    /// Path and query, as the sender requested them
+    pub path: &'a str,
+    pub body: &'a [u8],
+}
+
+/// What a request signature asserts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerRequestClaims {
+    /// DID of the signing node
+    pub iss: String,
+    /// Multibase (base58btc, ed25519-pub multicodec) key it signed with
+    pub key: String,
+    pub aud: String,
+    pub method: String,
+    pub path: String,
+    /// Base64url SHA-256 of the body
+    pub body: String,
+    pub nonce: String,
+    /// Signing time, in seconds since the Unix epoch
+    pub iat: i64,
+}
+
+impl PeerRequestClaims {
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.iat, 0).unwrap_or_default()
+    }
+
+    fn covers(&self, target: &Target<'_>) -> bool {
+        self.aud == target.audience
+            && self.method.eq_ignore_ascii_case(target.method)
+            && self.path == target.path
+            && auth_crypto::eq(&self.body, body_digest(target.body))
+    }
+}
+
+/// Sign a request with the node identity key, for the [`SIGNATURE_HEADER`]
+pub fn sign(
+    node_data: &NodeData,
+    target: &Target<'_>,
+    at: DateTime<Utc>,
+) -> Result<String, AppError> {
+    sign_with(
+        &manifest::signing_key(node_data)?,
+        &node_data.id,
+        target,
+        at,
+    )
+}
+
+/// Sign a request as `did` with `key`, under a fresh nonce
+pub fn sign_with(
+    key: &SigningKey,
+    did: &str,
+    target: &Target<'_>,
+    at: DateTime<Utc>,
+) -> Result<String, AppError> {
+    let mut nonce = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let claims = PeerRequestClaims {
+        iss: did.to_string(),
+        key: manifest::encode_public_key(&key.verifying_key()),
+        aud: target.audience.to_string(),
+        method: target.method.to_ascii_uppercase(),
+        path: target.path.to_string(),
+        body: body_digest(target.body),
+        nonce: URL_SAFE_NO_PAD.encode(nonce),
+        iat: at.timestamp(),
+    };
+    jws::sign(&jws::Header::new(TOKEN_TYPE), &claims, key)
+}
+
+/// The peer that signed a request `signature` for `target`, if its key
+/// belongs to the DID it names and the request hasn't been seen before
+pub async fn verify(
+    signature: &str,
+    target: &Target<'_>,
+    resolver: &DidResolver,
+    replays: &ReplayCache,
+) -> Result<PeerIdentity, AppError> {
+    let invalid = || AppError::Auth("Invalid peer request signature".to_string());
+
+    let (_, claims) = jws::decode::<PeerRequestClaims>(signature).ok_or_else(invalid)?;
+    let public_key = decode_public_key(&claims.key).ok_or_else(invalid)?;
+    let (header, claims) =
+        jws::verify::<PeerRequestClaims>(signature, &public_key).ok_or_else(invalid)?;
+    if header.typ != TOKEN_TYPE {
+        return Err(invalid());
+    }
+    if !claims.covers(target) {
+        return Err(AppError::Auth(
+            "Peer request signature is for another request".to_string(),
+        ));
+    }
+
+    // The key before the nonce, so strangers can't fill the cache
+    let peer = PeerIdentity {
+        did: claims.iss.clone(),
+        public_key,
+    };
+    mtls::verify_binding(resolver, &peer).await?;
+    replays.check(&peer.did, &claims.nonce, claims.issued_at())?;
+    Ok(peer)
+}
+
+fn body_digest(body: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(body))
+}
+
+fn decode_public_key(multibase: &str) -> Option<VerifyingKey> {
+    let (_, bytes) = multibase::decode(multibase).ok()?;
+    let raw: [u8; 32] = bytes.strip_prefix(&[0xed, 0x01])?.try_into().ok()?;
+    VerifyingKey::from_bytes(&raw).ok()
+}
+
+/// Nonces of signed requests still inside the replay window, in the KV store
+#[derive(Debug, Clone)]
+pub struct ReplayCache {
+    kv: Db,
+    window: Duration,
+    time: TimeSource,
+}
+
+impl ReplayCache {
+    pub fn new(kv: Db, window: Duration, time: TimeSource) -> Self {
+        Self { kv, window, time }
+    }
+
+    /// Record `nonce` from `did`, refusing it if the request is outside the
+    /// window or the nonce was used already
+    pub fn check(&self, did: &str, nonce: &str, issued_at: DateTime<Utc>) -> Result<(), AppError> {
+        if !(MIN_NONCE_LENGTH..=MAX_NONCE_LENGTH).contains(&nonce.len()) {
+            return Err(AppError::Validation(format!(
+                "Nonce must be between {} and {} characters",
+                MIN_NONCE_LENGTH, MAX_NONCE_LENGTH
+            )));
+        }
+        if self.time.is_ahead(issued_at) {
+            return Err(AppError::Auth(
+                "Peer request is signed in the future".to_string(),
+            ));
+        }
+        let expires = match self.expiry(issued_at) {
+            Some(expires) if !self.time.has_passed(expires) => expires,
+            _ => {
+                return Err(AppError::Auth(
+                    "Peer request signature is too old".to_string(),
+                ));
+            }
+        };
+
+        let key = format!("{}\n{}", did, nonce);
+        match self
+            .tree()?
+            .compare_and_swap(
+                key.as_bytes(),
+                None::<&[u8]>,
+                Some(&expires.timestamp().to_be_bytes()[..]),
+            )
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+        {
+            Ok(()) => Ok(()),
+            Err(_) => Err(AppError::Replayed(format!(
+                "Nonce {} from {} was already used",
+                nonce, did
+            ))),
+        }
+    }
+
+    /// Drop nonces that have fallen out of the window, returning how many
+    pub fn sweep(&self) -> Result<usize, AppError> {
+        let tree = self.tree()?;
+        let mut swept = 0;
+        for entry in tree.iter() {
+            let (key, value) = entry.map_err(|e| AppError::Storage(Box::new(e)))?;
+            let expires = value
+                .as_ref()
+                .try_into()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(i64::from_be_bytes(secs), 0));
+            if expires.is_none_or(|expires| self.time.has_passed(expires)) {
+                tree.remove(key)
+                    .map_err(|e| AppError::Storage(Box::new(e)))?;
+                swept += 1;
+            }
+        }
+        Ok(swept)
+    }
+
+    fn expiry(&self, issued_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        issued_at.checked_add_signed(chrono::Duration::from_std(self.window).ok()?)
+    }
+
+    fn tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(NONCE_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
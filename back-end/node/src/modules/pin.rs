@@ -0,0 +1,367 @@
+//! Optional local PIN, a fallback for when no WebAuthn authenticator is
+//! available. A correct PIN unlocks a short-lived read-only grant (browsing
+//! spaces); it never grants what a passkey does.
+//!
+//! The argon2id hash lives in the KV store. Failed attempts and unlock grants
+//! are expiring state and live in the [`SessionStore`]. Every set, unlock,
+//! failure and lockout is written to the `audit` log target. Attempt limits
+//! and lifetimes are the `pin` [`PinSettings`].
+
+use std::time::Duration;
+
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use log::{info, warn};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+
+use crate::modules::session::{SessionKind, SessionStore};
+use crate::modules::settings::{Settings, SettingsStore};
+
+pub const PIN_TREE: &str = "auth:pin";
+pub const MIN_PIN_LENGTH: usize = 4;
+pub const MAX_PIN_LENGTH: usize = 64;
+/// Failed attempts allowed before the PIN is locked, by default
+pub const MAX_FAILED_ATTEMPTS: u32 = 5;
+/// Most failed attempts settings may allow before a lockout
+pub const MAX_FAILED_ATTEMPTS_LIMIT: u32 = 20;
+pub const LOCKOUT_DURATION: Duration = Duration::from_secs(15 * 60);
+pub const UNLOCK_TTL: Duration = Duration::from_secs(15 * 60);
+/// Longest lockout or unlock grant settings may ask for
+const MAX_SETTING_SECS: u64 = 24 * 60 * 60;
+
+const AUDIT: &str = "audit";
+const HASH_KEY: &[u8] = b"hash";
+/// There is a single local PIN per node
+const ATTEMPTS_KEY: &str = "local";
+
+/// What an unlock grant allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinScope {
+    ReadOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinGrant {
+    pub scope: PinScope,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// `pin` settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct PinSettings {
+    /// Failed attempts allowed before the PIN is locked
+    pub max_failed_attempts: u32,
+    pub lockout_secs: u64,
+    /// Lifetime of an unlock grant
+    pub unlock_ttl_secs: u64,
+}
+
+impl Default for PinSettings {
+    fn default() -> Self {
+        Self {
+            max_failed_attempts: MAX_FAILED_ATTEMPTS,
+            lockout_secs: LOCKOUT_DURATION.as_secs(),
+            unlock_ttl_secs: UNLOCK_TTL.as_secs(),
+        }
+    }
+}
+
+impl Settings for PinSettings {
+    const NAMESPACE: &'static str = "pin";
+
+    fn validate(&self) -> Result<(), AppError> {
+        if !(1..=MAX_FAILED_ATTEMPTS_LIMIT).contains(&self.max_failed_attempts) {
+            return Err(AppError::Validation(format!(
+                "maxFailedAttempts must be between 1 and {}",
+                MAX_FAILED_ATTEMPTS_LIMIT
+            )));
+        }
+        for (name, secs) in [
+            ("lockoutSecs", self.lockout_secs),
+            ("unlockTtlSecs", self.unlock_ttl_secs),
+        ] {
+            if !(1..=MAX_SETTING_SECS).contains(&secs) {
+                return Err(AppError::Validation(format!(
+                    "{} must be between 1 and {}",
+                    name, MAX_SETTING_SECS
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PinSettings {
+    fn lockout(&self) -> Duration {
+        Duration::from_secs(self.lockout_secs)
+    }
+
+    fn unlock_ttl(&self) -> Duration {
+        Duration::from_secs(self.unlock_ttl_secs)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PinUnlock {
+    Unlocked { token: String, grant: PinGrant },
+    Rejected { remaining_attempts: u32 },
+    LockedOut { until: DateTime<Utc> },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Attempts {
+    failures: u32,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct LocalPin {
+    kv: Db,
+    sessions: SessionStore,
+    settings: SettingsStore,
+}
+
+impl LocalPin {
+    pub fn new(kv: Db, sessions: SessionStore, settings: SettingsStore) -> Self {
+        Self {
+            kv,
+            sessions,
+            settings,
+        }
+    }
+
+    pub fn is_set(&self) -> Result<bool, AppError> {
+        Ok(self.stored_hash()?.is_some())
+    }
+
+    /// Set or change the PIN. Changing an existing PIN requires the current
+    /// one, and wrong guesses count towards the lockout.
+    pub async fn set(&self, pin: &str, current: Option<&str>) -> Result<(), AppError> {
+        validate(pin)?;
+
+        if self.is_set()? {
+            let current =
+                current.ok_or_else(|| AppError::Auth("Current PIN is required".to_string()))?;
+            match self.check(current).await? {
+                PinCheck::Valid => {}
+                PinCheck::Invalid { .. } => {
+                    return Err(AppError::Auth("Current PIN is incorrect".to_string()));
+                }
+                PinCheck::Locked(until) => {
+                    return Err(AppError::Auth(format!("PIN is locked until {}", until)));
+                }
+            }
+        }
+
+        let hash = hash_pin(pin.to_string()).await?;
+
+        self.tree()?
+            .insert(HASH_KEY, hash.as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        info!(target: AUDIT, "Local PIN set");
+        Ok(())
+    }
+
+    /// Remove the PIN after checking it
+    pub async fn clear(&self, current: &str) -> Result<(), AppError> {
+        match self.check(current).await? {
+            PinCheck::Valid => {}
+            PinCheck::Invalid { .. } => {
+                return Err(AppError::Auth("Current PIN is incorrect".to_string()));
+            }
+            PinCheck::Locked(until) => {
+                return Err(AppError::Auth(format!("PIN is locked until {}", until)));
+            }
+        }
+
+        self.tree()?
+            .remove(HASH_KEY)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        info!(target: AUDIT, "Local PIN removed");
+        Ok(())
+    }
+
+    /// Exchange the PIN for a read-only grant token
+    pub async fn unlock(&self, pin: &str) -> Result<PinUnlock, AppError> {
+        match self.check(pin).await? {
+            PinCheck::Valid => {}
+            PinCheck::Invalid { remaining_attempts } => {
+                return Ok(PinUnlock::Rejected { remaining_attempts });
+            }
+            PinCheck::Locked(until) => return Ok(PinUnlock::LockedOut { until }),
+        }
+
+        let mut token = [0u8; 32];
+        OsRng.fill_bytes(&mut token);
+        let token = URL_SAFE_NO_PAD.encode(token);
+
+        let ttl = self.settings.get::<PinSettings>()?.unlock_ttl();
+        let grant = PinGrant {
+            scope: PinScope::ReadOnly,
+            expires_at: Utc::now()
+                + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::zero()),
+        };
+        self.sessions
+            .put(SessionKind::PinUnlock, &token, &grant, ttl)
+            .await?;
+
+        info!(target: AUDIT, "Local PIN unlocked read-only access until {}", grant.expires_at);
+        Ok(PinUnlock::Unlocked { token, grant })
+    }
+
+    /// The grant behind an unlock token, if it is still valid
+    pub async fn grant(&self, token: &str) -> Result<Option<PinGrant>, AppError> {
+        self.sessions.get(SessionKind::PinUnlock, token).await
+    }
+
+    async fn check(&self, pin: &str) -> Result<PinCheck, AppError> {
+        let Some(hash) = self.stored_hash()? else {
+            return Err(AppError::NotFound("No local PIN is set".to_string()));
+        };
+
+        let attempts: Attempts = self
+            .sessions
+            .get(SessionKind::PinAttempts, ATTEMPTS_KEY)
+            .await?
+            .unwrap_or_default();
+        if let Some(until) = attempts.locked_until.filter(|until| *until > Utc::now()) {
+            warn!(target: AUDIT, "Local PIN attempt while locked out until {}", until);
+            return Ok(PinCheck::Locked(until));
+        }
+
+        if verify_pin(pin.to_string(), hash).await? {
+            self.sessions
+                .delete(SessionKind::PinAttempts, ATTEMPTS_KEY)
+                .await?;
+            return Ok(PinCheck::Valid);
+        }
+
+        // Counted in one step, so concurrent guesses can't overwrite each
+        // other's failures. Failures are forgotten once a lockout window
+        // passes without another.
+        let settings = self.settings.get::<PinSettings>()?;
+        let attempts = self
+            .sessions
+            .update(
+                SessionKind::PinAttempts,
+                ATTEMPTS_KEY,
+                |attempts: Option<Attempts>| {
+                    (
+                        record_failure(attempts.unwrap_or_default(), &settings),
+                        settings.lockout(),
+                    )
+                },
+            )
+            .await?;
+
+        Ok(match attempts.locked_until {
+            Some(until) => PinCheck::Locked(until),
+            None => PinCheck::Invalid {
+                remaining_attempts: settings
+                    .max_failed_attempts
+                    .saturating_sub(attempts.failures),
+            },
+        })
+    }
+
+    fn stored_hash(&self) -> Result<Option<String>, AppError> {
+        let hash = self
+            .tree()?
+            .get(HASH_KEY)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(hash.map(|h| String::from_utf8_lossy(&h).into_owned()))
+    }
+
+    fn tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(PIN_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+enum PinCheck {
+    Valid,
+    Invalid { remaining_attempts: u32 },
+    Locked(DateTime<Utc>),
+}
+
+/// `attempts` after one more failure, locked once they run out
+fn record_failure(attempts: Attempts, settings: &PinSettings) -> Attempts {
+    let now = Utc::now();
+    if let Some(until) = attempts.locked_until.filter(|until| *until > now) {
+        // Locked by a concurrent guess
+        warn!(target: AUDIT, "Local PIN attempt while locked out until {}", until);
+        return attempts;
+    }
+
+    let failures = attempts.failures + 1;
+    let remaining_attempts = settings.max_failed_attempts.saturating_sub(failures);
+    if remaining_attempts > 0 {
+        warn!(
+            target: AUDIT,
+            "Local PIN attempt failed ({} attempts left)", remaining_attempts
+        );
+        return Attempts {
+            failures,
+            locked_until: None,
+        };
+    }
+
+    let until =
+        now + chrono::Duration::from_std(settings.lockout()).unwrap_or(chrono::Duration::zero());
+    warn!(
+        target: AUDIT,
+        "Local PIN locked until {} after {} failed attempts", until, failures
+    );
+    Attempts {
+        failures: 0,
+        locked_until: Some(until),
+    }
+}
+
+/// argon2id hash of `pin`, off the async runtime
+async fn hash_pin(pin: String) -> Result<String, AppError> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(pin.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| AppError::Crypto(format!("Failed to hash PIN: {}", e)))
+    })
+    .await
+    .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+}
+
+/// Whether `pin` matches the stored `hash`, checked off the async runtime
+async fn verify_pin(pin: String, hash: String) -> Result<bool, AppError> {
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&hash)
+            .map_err(|e| AppError::Crypto(format!("Stored PIN hash is invalid: {}", e)))?;
+        Ok(Argon2::default()
+            .verify_password(pin.as_bytes(), &parsed)
+            .is_ok())
+    })
+    .await
+    .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+}
+
+fn validate(pin: &str) -> Result<(), AppError> {
+    let length = pin.chars().count();
+    if !(MIN_PIN_LENGTH..=MAX_PIN_LENGTH).contains(&length) {
+        return Err(AppError::Validation(format!(
+            "PIN must be between {} and {} characters",
+            MIN_PIN_LENGTH, MAX_PIN_LENGTH
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Users' own profiles: username, display name and avatar.
+//!
+//! Signed-in users read theirs at `GET /api/v1/users/me` and edit it with
+//! `PATCH`, sending only what changes. Edits carry the profile's `version`
+//! to be refused if another client changed it first (see
+//! [`crate::modules::versioning`]).
+
+use entity::user;
+use errors::AppError;
+use log::info;
+use sea_orm::{ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Deserialize;
+
+use crate::modules::account::UserProfile;
+use crate::modules::tenancy::Actor;
+use crate::modules::validation::validate_username;
+use crate::modules::versioning::update_versioned;
+
+const AUDIT: &str = "audit";
+
+/// Longest display name, in characters
+pub const MAX_DISPLAY_NAME_CHARS: usize = 64;
+/// Longest avatar URL, in bytes
+pub const MAX_AVATAR_URL_LEN: usize = 2048;
+
+/// Changes to a profile; fields left out stay as they are
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileUpdate {
+    pub username: Option<String>,
+    pub display_name: Option<String>,
+    /// An http(s) URL; empty to remove the avatar
+    pub avatar_url: Option<String>,
+    /// Version the client read; a stale one is refused
+    pub version: Option<i32>,
+}
+
+/// The profile of user `user_id`
+pub async fn get(db: &DatabaseConnection, user_id: i32) -> Result<UserProfile, AppError> {
+    Ok(find(db, user_id).await?.into())
+}
+
+/// Apply `update` to the profile of user `user_id`
+pub async fn update(
+    db: &DatabaseConnection,
+    user_id: i32,
+    update: ProfileUpdate,
+) -> Result<UserProfile, AppError> {
+    let mut changes = user::ActiveModel::default();
+
+    if let Some(username) = update.username {
+        validate_username(&username)?;
+        let taken = user::Entity::find()
+            .filter(user::Column::Username.eq(&username))
+            .filter(user::Column::Id.ne(user_id))
+            .one(db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if taken.is_some() {
+            return Err(AppError::Conflict(format!(
+                "Username '{}' is taken",
+                username
+            )));
+        }
+        changes.username = Set(username);
+    }
+    if let Some(display_name) = update.display_name {
+        let display_name = display_name.trim();
+        if display_name.is_empty()
+            || display_name.chars().count() > MAX_DISPLAY_NAME_CHARS
+            || display_name.chars().any(char::is_control)
+        {
+            return Err(AppError::Validation(format!(
+                "Display name must be 1 to {} printable characters",
+                MAX_DISPLAY_NAME_CHARS
+            )));
+        }
+        changes.display_name = Set(display_name.to_string());
+    }
+    if let Some(avatar_url) = update.avatar_url {
+        changes.avatar_url = Set(match avatar_url.trim() {
+            "" => None,
+            url => Some(validate_avatar_url(url)?),
+        });
+    }
+
+    let user = update_versioned::<user::Entity>(db, user_id, update.version, changes).await?;
+    info!(target: AUDIT, "{} updated their profile", Actor::User(user_id));
+    Ok(user.into())
+}
+
+fn validate_avatar_url(url: &str) -> Result<String, AppError> {
+    if url.len() > MAX_AVATAR_URL_LEN {
+        return Err(AppError::Validation(format!(
+            "Avatar URL must be at most {} bytes",
+            MAX_AVATAR_URL_LEN
+        )));
+    }
+    let parsed = url::Url::parse(url)
+        .map_err(|e| AppError::Validation(format!("Invalid avatar URL: {}", e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(AppError::Validation(
+            "Avatar URL must be http or https".to_string(),
+        ));
+    }
+    Ok(parsed.into())
+}
+
+async fn find(db: &DatabaseConnection, user_id: i32) -> Result<user::Model, AppError> {
+    user::Entity::find_by_id(user_id)
+        .one(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .ok_or_else(|| AppError::NotFound(format!("User {} not found", user_id)))
+}
@@ -0,0 +1,354 @@
+//! Resource usage accounting.
+//!
+//! [`usage`] takes a snapshot of what the node holds: process memory and open
+//! files, the KV store (on disk and per tree), the database, and the tasks
+//! each subsystem has running. Soft limits in [`ResourceConfig`] only warn;
+//! they exist so constrained devices get told before they run out, not to
+//! refuse work.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use errors::AppError;
+use log::warn;
+use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement};
+use serde::Serialize;
+use sled::Db;
+use tokio::task::JoinHandle;
+
+use crate::bootstrap::config::ResourceConfig;
+use crate::lifecycle::Shutdown;
+
+/// Running tasks per subsystem. Clones share the counts.
+#[derive(Debug, Clone, Default)]
+pub struct Tasks {
+    running: Arc<Mutex<BTreeMap<&'static str, u64>>>,
+    /// Spawned tasks are dropped once this is triggered
+    shutdown: Shutdown,
+}
+
+impl Tasks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shutdown of the node these tasks run in
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
+    /// Count `subsystem` as running a task until the guard drops
+    pub fn track(&self, subsystem: &'static str) -> TaskGuard {
+        *self.lock().entry(subsystem).or_default() += 1;
+        TaskGuard {
+            tasks: self.clone(),
+            subsystem,
+        }
+    }
+
+    /// Spawn `task` on the runtime, counted against `subsystem`. It is
+    /// dropped at shutdown, and then yields `None`.
+    pub fn spawn<F>(&self, subsystem: &'static str, task: F) -> JoinHandle<Option<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let guard = self.track(subsystem);
+        let stopped = self.shutdown.triggered();
+        tokio::spawn(async move {
+            let _guard = guard;
+            tokio::select! {
+                output = task => Some(output),
+                _ = stopped => None,
+            }
+        })
+    }
+
+    /// Subsystems with tasks running, and how many
+    pub fn running(&self) -> BTreeMap<String, u64> {
+        self.lock()
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(subsystem, count)| (subsystem.to_string(), *count))
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<&'static str, u64>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Keeps a task counted while held
+pub struct TaskGuard {
+    tasks: Tasks,
+    subsystem: &'static str,
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if let Some(count) = self.tasks.lock().get_mut(self.subsystem) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    /// Resident memory; `None` where the platform doesn't say
+    pub memory_bytes: Option<u64>,
+    pub open_files: Option<u64>,
+    /// KV store size on disk
+    pub kv_bytes: u64,
+    /// Bytes of keys and values per KV tree, each tree being one subsystem's
+    pub kv_trees: BTreeMap<String, u64>,
+    /// `None` for databases other than SQLite
+    pub db_bytes: Option<u64>,
+    pub tasks: BTreeMap<String, u64>,
+}
+
+/// A soft limit that usage went over
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LimitExceeded {
+    pub resource: &'static str,
+    pub used: u64,
+    pub limit: u64,
+}
+
+impl ResourceUsage {
+    pub fn task_count(&self) -> u64 {
+        self.tasks.values().sum()
+    }
+
+    /// Soft limits this usage is over
+    pub fn exceeded(&self, limits: &ResourceConfig) -> Vec<LimitExceeded> {
+        [
+            ("memoryBytes", self.memory_bytes, limits.memory_bytes),
+            ("openFiles", self.open_files, limits.open_files),
+            ("kvBytes", Some(self.kv_bytes), limits.kv_bytes),
+            ("dbBytes", self.db_bytes, limits.db_bytes),
+            ("tasks", Some(self.task_count()), limits.tasks),
+        ]
+        .into_iter()
+        .filter_map(|(resource, used, limit)| match (used, limit) {
+            (Some(used), Some(limit)) if used > limit => Some(LimitExceeded {
+                resource,
+                used,
+                limit,
+            }),
+            _ => None,
+        })
+        .collect()
+    }
+}
+
+/// What the node is using right now. Sizing KV trees reads every entry, so
+/// this isn't for hot paths.
+pub async fn usage(
+    db: &DatabaseConnection,
+    kv: &Db,
+    tasks: &Tasks,
+) -> Result<ResourceUsage, AppError> {
+    let mut usage = totals(db, kv, tasks).await?;
+    usage.kv_trees = kv_trees(kv)?;
+    Ok(usage)
+}
+
+/// Like [`usage`] without the per-tree sizes, cheap enough to poll
+pub async fn totals(
+    db: &DatabaseConnection,
+    kv: &Db,
+    tasks: &Tasks,
+) -> Result<ResourceUsage, AppError> {
+    Ok(ResourceUsage {
+        memory_bytes: resident_memory(),
+        open_files: open_files(),
+        kv_bytes: kv
+            .size_on_disk()
+            .map_err(|e| AppError::Storage(Box::new(e)))?,
+        kv_trees: BTreeMap::new(),
+        db_bytes: db_bytes(db).await?,
+        tasks: tasks.running(),
+    })
+}
+
+/// Warn about every soft limit the node is over, every `check_interval`
+pub async fn watch(db: DatabaseConnection, kv: Db, tasks: Tasks, limits: ResourceConfig) {
+    let mut interval = tokio::time::interval(limits.check_interval);
+    loop {
+        interval.tick().await;
+        match totals(&db, &kv, &tasks).await {
+            Ok(usage) => {
+                for over in usage.exceeded(&limits) {
+                    warn!(
+                        "{} is over its soft limit: {} > {}",
+                        over.resource, over.used, over.limit
+                    );
+                }
+            }
+            Err(e) => warn!("Resource check failed: {}", e),
+        }
+    }
+}
+
+fn kv_trees(kv: &Db) -> Result<BTreeMap<String, u64>, AppError> {
+    let mut sizes = BTreeMap::new();
+    for name in kv.tree_names() {
+        let tree = kv
+            .open_tree(&name)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        let mut bytes = 0;
+        for entry in tree.iter() {
+            let (key, value) = entry.map_err(|e| AppError::Storage(Box::new(e)))?;
+            bytes += (key.len() + value.len()) as u64;
+        }
+        sizes.insert(String::from_utf8_lossy(&name).into_owned(), bytes);
+    }
+    Ok(sizes)
+}
+
+async fn db_bytes(db: &DatabaseConnection) -> Result<Option<u64>, AppError> {
+    if db.get_database_backend() != DatabaseBackend::Sqlite {
+        return Ok(None);
+    }
+    let row = db
+        .query_one(Statement::from_string(
+            DatabaseBackend::Sqlite,
+            "SELECT page_count * page_size AS size FROM pragma_page_count(), pragma_page_size()",
+        ))
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(row
+        .map(|row| row.try_get::<i64>("", "size"))
+        .transpose()
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .map(|size| size as u64))
+}
+
+#[cfg(target_os = "linux")]
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory() -> Option<u64> {
+    None
+}
+
+fn open_files() -> Option<u64> {
+    let dir = if cfg!(target_os = "linux") {
+        "/proc/self/fd"
+    } else {
+        "/dev/fd"
+    };
+    // Listing the directory opens one more
+    Some(std::fs::read_dir(dir).ok()?.count().saturating_sub(1) as u64)
+}
+
+/// `usage` in the Prometheus text exposition format, soft limits included
+pub fn prometheus(usage: &ResourceUsage, limits: &ResourceConfig) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(Option<(&str, &str)>, u64)>| {
+        write_gauge(&mut out, name, help, samples)
+    };
+
+    gauge(
+        "flow_memory_resident_bytes",
+        "Resident memory of the node process",
+        usage.memory_bytes.map(|v| (None, v)).into_iter().collect(),
+    );
+    gauge(
+        "flow_open_files",
+        "Files and sockets the node process has open",
+        usage.open_files.map(|v| (None, v)).into_iter().collect(),
+    );
+    gauge(
+        "flow_kv_disk_bytes",
+        "KV store size on disk",
+        vec![(None, usage.kv_bytes)],
+    );
+    gauge(
+        "flow_kv_tree_bytes",
+        "Bytes of keys and values in a KV tree",
+        usage
+            .kv_trees
+            .iter()
+            .map(|(tree, bytes)| (Some(("tree", tree.as_str())), *bytes))
+            .collect(),
+    );
+    gauge(
+        "flow_db_bytes",
+        "Database size",
+        usage.db_bytes.map(|v| (None, v)).into_iter().collect(),
+    );
+    gauge(
+        "flow_tasks",
+        "Tasks running in a subsystem",
+        usage
+            .tasks
+            .iter()
+            .map(|(subsystem, count)| (Some(("subsystem", subsystem.as_str())), *count))
+            .collect(),
+    );
+    gauge(
+        "flow_soft_limit",
+        "Soft limit on a resource",
+        [
+            ("memoryBytes", limits.memory_bytes),
+            ("openFiles", limits.open_files),
+            ("kvBytes", limits.kv_bytes),
+            ("dbBytes", limits.db_bytes),
+            ("tasks", limits.tasks),
+        ]
+        .into_iter()
+        .filter_map(|(resource, limit)| Some((Some(("resource", resource)), limit?)))
+        .collect(),
+    );
+    out
+}
+
+/// Append gauge `name` to `out`, one sample per optional `(label, value)`
+/// pair; nothing at all without samples
+pub(crate) fn write_gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    samples: Vec<(Option<(&str, &str)>, u64)>,
+) {
+    if samples.is_empty() {
+        return;
+    }
+    out.push_str(&format!(
+        "# HELP {} {}\n# TYPE {} gauge\n",
+        name, help, name
+    ));
+    for (label, value) in samples {
+        match label {
+            Some((key, label)) => out.push_str(&format!(
+                "{}{{{}=\"{}\"}} {}\n",
+                name,
+                key,
+                escape_label(label),
+                value
+            )),
+            None => out.push_str(&format!("{} {}\n", name, value)),
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
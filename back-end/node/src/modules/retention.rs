@@ -0,0 +1,85 @@
+//! Retention of records that only matter for a while.
+//!
+//! Scheduled task runs, key usage and the KV-backed DID resolution cache are
+//! purged once older than their [`RetentionConfig`] window. The runner purges
+//! every [`RetentionConfig::purge_interval`]; a `purge_data` task can do the
+//! same on its own schedule, or with `dryRun` only report what would go.
+//!
+//! Logs (audit lines included) are left to the log collector. Signed
+//! artifacts such as anchors and timestamp proofs are evidence and are never
+//! purged.
+//!
+//! [`RetentionConfig`]: crate::bootstrap::config::RetentionConfig
+//! [`RetentionConfig::purge_interval`]: crate::bootstrap::config::RetentionConfig::purge_interval
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use log::info;
+use serde::Serialize;
+
+use crate::api::node::Node;
+use crate::modules::ssi::did::resolvers::cache::KvDidCache;
+
+/// Records purged, or that would be on a dry run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeReport {
+    pub dry_run: bool,
+    pub task_runs: u64,
+    pub key_usage: u64,
+    pub did_cache: u64,
+}
+
+impl PurgeReport {
+    pub fn total(&self) -> u64 {
+        self.task_runs + self.key_usage + self.did_cache
+    }
+}
+
+/// Purge the node's records past their retention
+pub fn purge(node: &Node, now: DateTime<Utc>, dry_run: bool) -> Result<PurgeReport, AppError> {
+    let retention = &node.retention;
+    let mut report = PurgeReport {
+        dry_run,
+        ..Default::default()
+    };
+
+    if let Some(before) = cutoff(now, retention.task_runs)? {
+        report.task_runs = node.scheduler().purge_runs(before, dry_run)?;
+    }
+    if let Some(before) = cutoff(now, retention.key_usage)? {
+        report.key_usage = node.key_usage().purge(before, dry_run)?;
+    }
+    if let Some(before) = cutoff(now, retention.did_cache)? {
+        report.did_cache = KvDidCache::open(&node.kv)
+            .and_then(|cache| cache.purge(before, now, dry_run))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+    }
+
+    if report.total() > 0 {
+        info!(
+            "{} {} expired records ({} task runs, {} key usage, {} DID cache)",
+            if dry_run { "Would purge" } else { "Purged" },
+            report.total(),
+            report.task_runs,
+            report.key_usage,
+            report.did_cache
+        );
+    }
+    Ok(report)
+}
+
+/// Records from before this are past keeping for `keep`; `None` keeps them
+pub fn cutoff(
+    now: DateTime<Utc>,
+    keep: Option<Duration>,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    keep.map(|keep| {
+        chrono::Duration::from_std(keep)
+            .map(|keep| now - keep)
+            .map_err(|e| AppError::Config(format!("Invalid retention: {}", e)))
+    })
+    .transpose()
+}
@@ -0,0 +1,529 @@
+//! User-defined scheduled tasks.
+//!
+//! A task pairs a cron expression with one of a fixed set of built-in
+//! actions. Expressions take the usual five fields (minute to weekday), an
+//! optional leading seconds field, or shorthands such as `@daily`; times are
+//! UTC.
+//!
+//! Tasks are the node's: only it may manage them, and their actions reach
+//! every space, whoever owns it.
+//!
+//! Exports and backups are only written within the node's export dir, under
+//! its config dir ([`EXPORT_DIR`] unless configured otherwise); a task names
+//! a destination relative to it.
+//!
+//! Tasks and their run history live in the KV store; how much history is
+//! kept is the `scheduler` [`SchedulerSettings`]. [`Scheduler::run`]
+//! checks for due tasks on a short tick; a failed run is kept in the history
+//! and published as a [`EventType::ScheduledTaskFailed`] event, which
+//! webhooks and scripts can pick up.
+
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use errors::AppError;
+use event::schema::ScheduledTaskFailed;
+use event::types::{Event, EventType};
+use log::{info, warn};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sea_orm::{ConnectionTrait, DatabaseBackend};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::api::node::Node;
+use crate::modules::settings::Settings;
+use crate::modules::space;
+
+pub const TASK_TREE: &str = "scheduler:tasks";
+pub const RUN_TREE: &str = "scheduler:runs";
+/// Runs kept per task by default; older ones are dropped
+pub const MAX_RUN_HISTORY: usize = 50;
+/// Most runs settings may keep per task
+const RUN_HISTORY_LIMIT: usize = 10_000;
+pub const TICK_INTERVAL: Duration = Duration::from_secs(15);
+/// Directory under the config dir exports and backups go to by default
+pub const EXPORT_DIR: &str = "exports";
+
+const URI_RESERVED: &AsciiSet = &CONTROLS.add(b'%').add(b'?').add(b'#');
+
+/// `scheduler` settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct SchedulerSettings {
+    /// Runs kept per task; older ones are dropped
+    pub max_run_history: usize,
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        Self {
+            max_run_history: MAX_RUN_HISTORY,
+        }
+    }
+}
+
+impl Settings for SchedulerSettings {
+    const NAMESPACE: &'static str = "scheduler";
+
+    fn validate(&self) -> Result<(), AppError> {
+        if !(1..=RUN_HISTORY_LIMIT).contains(&self.max_run_history) {
+            return Err(AppError::Validation(format!(
+                "maxRunHistory must be between 1 and {}",
+                RUN_HISTORY_LIMIT
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// What a task does when it fires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum TaskAction {
+    /// Walk a space and report its file count and size
+    ScanSpace { space_key: String },
+    /// Write a tar archive of a space into `destination`, relative to the
+    /// export dir
+    ExportArchive {
+        space_key: String,
+        #[serde(default)]
+        destination: PathBuf,
+    },
+    /// Write a copy of the node database into `destination`, relative to the
+    /// export dir
+    RunBackup {
+        #[serde(default)]
+        destination: PathBuf,
+    },
+    /// Attest to a space's Merkle root in a credential
+    AnchorSpace { space_key: String },
+    /// Purge records past their retention, or only report them
+    PurgeData {
+        #[serde(default)]
+        dry_run: bool,
+    },
+}
+
+/// A task creation request
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTask {
+    pub name: String,
+    pub schedule: String,
+    pub action: TaskAction,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledTask {
+    pub id: String,
+    pub name: String,
+    pub schedule: String,
+    pub action: TaskAction,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskRun {
+    pub task_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Scheduler {
+    node: Node,
+}
+
+impl Scheduler {
+    pub fn new(node: Node) -> Self {
+        Self { node }
+    }
+
+    pub async fn create(&self, new: NewTask) -> Result<ScheduledTask, AppError> {
+        if new.name.trim().is_empty() {
+            return Err(AppError::Validation("Task name is required".to_string()));
+        }
+        let schedule = parse_schedule(&new.schedule)?;
+        self.validate_action(&new.action).await?;
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let task = ScheduledTask {
+            id: URL_SAFE_NO_PAD.encode(id),
+            name: new.name,
+            schedule: new.schedule,
+            action: new.action,
+            enabled: new.enabled,
+            created_at: Utc::now(),
+            next_run: schedule.after(&Utc::now()).next(),
+        };
+        self.save(&task)?;
+
+        info!(
+            "Scheduled task {} ({}) on '{}'",
+            task.name, task.id, task.schedule
+        );
+        Ok(task)
+    }
+
+    pub fn list(&self) -> Result<Vec<ScheduledTask>, AppError> {
+        self.tasks()?
+            .iter()
+            .values()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Result<ScheduledTask, AppError> {
+        let value = self
+            .tasks()?
+            .get(id.as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .ok_or_else(|| AppError::NotFound(format!("Task {} not found", id)))?;
+        serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    /// Delete a task and its run history
+    pub fn remove(&self, id: &str) -> Result<(), AppError> {
+        let removed = self
+            .tasks()?
+            .remove(id.as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if removed.is_none() {
+            return Err(AppError::NotFound(format!("Task {} not found", id)));
+        }
+
+        let runs = self.runs()?;
+        for key in runs.scan_prefix(run_prefix(id)).keys() {
+            let key = key.map_err(|e| AppError::Storage(Box::new(e)))?;
+            runs.remove(key)
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+        }
+
+        info!("Removed scheduled task {}", id);
+        Ok(())
+    }
+
+    /// Runs of a task, newest first
+    pub fn history(&self, id: &str) -> Result<Vec<TaskRun>, AppError> {
+        self.get(id)?;
+        let mut runs = self
+            .runs()?
+            .scan_prefix(run_prefix(id))
+            .values()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .collect::<Result<Vec<TaskRun>, AppError>>()?;
+        runs.reverse();
+        Ok(runs)
+    }
+
+    /// Remove runs that finished before `before`, returning how many. With
+    /// `dry_run` they are only counted.
+    pub fn purge_runs(&self, before: DateTime<Utc>, dry_run: bool) -> Result<u64, AppError> {
+        let runs = self.runs()?;
+        let mut purged = 0;
+        for item in runs.iter() {
+            let (key, value) = item.map_err(|e| AppError::Storage(Box::new(e)))?;
+            let run: TaskRun =
+                serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))?;
+            if run.finished_at >= before {
+                continue;
+            }
+            if !dry_run {
+                runs.remove(key)
+                    .map_err(|e| AppError::Storage(Box::new(e)))?;
+            }
+            purged += 1;
+        }
+        Ok(purged)
+    }
+
+    /// Run a task immediately, outside its schedule
+    pub async fn run_now(&self, id: &str) -> Result<TaskRun, AppError> {
+        let task = self.get(id)?;
+        self.execute(&task).await
+    }
+
+    /// Run every enabled task due at `now` and move it to its next slot
+    pub async fn run_due(&self, now: DateTime<Utc>) -> Result<Vec<TaskRun>, AppError> {
+        let mut runs = Vec::new();
+        for mut task in self.list()? {
+            if !task.enabled || task.next_run.is_none_or(|next| next > now) {
+                continue;
+            }
+
+            runs.push(self.execute(&task).await?);
+            task.next_run = parse_schedule(&task.schedule)?.after(&now).next();
+            self.save(&task)?;
+        }
+        Ok(runs)
+    }
+
+    /// Check for due tasks until the task is dropped
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.run_due(Utc::now()).await {
+                warn!("Scheduler tick failed: {}", e);
+            }
+        }
+    }
+
+    async fn execute(&self, task: &ScheduledTask) -> Result<TaskRun, AppError> {
+        let started_at = Utc::now();
+        let result = run_action(&self.node, &task.action).await;
+        let finished_at = Utc::now();
+
+        let run = match result {
+            Ok(output) => {
+                info!("Scheduled task {} succeeded", task.name);
+                TaskRun {
+                    task_id: task.id.clone(),
+                    started_at,
+                    finished_at,
+                    status: RunStatus::Succeeded,
+                    output: Some(output),
+                    error: None,
+                }
+            }
+            Err(e) => {
+                warn!("Scheduled task {} failed: {}", task.name, e);
+                self.node.publish(&Event::from_payload(
+                    EventType::ScheduledTaskFailed,
+                    &ScheduledTaskFailed {
+                        task_id: task.id.clone(),
+                        name: task.name.clone(),
+                        error: e.to_string(),
+                    },
+                ));
+                TaskRun {
+                    task_id: task.id.clone(),
+                    started_at,
+                    finished_at,
+                    status: RunStatus::Failed,
+                    output: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        self.record(&run)?;
+        Ok(run)
+    }
+
+    async fn validate_action(&self, action: &TaskAction) -> Result<(), AppError> {
+        match action {
+            TaskAction::ScanSpace { space_key } | TaskAction::AnchorSpace { space_key } => {
+                space::find_space(&self.node.db, space_key).await?;
+            }
+            TaskAction::ExportArchive {
+                space_key,
+                destination,
+            } => {
+                space::find_space(&self.node.db, space_key).await?;
+                export_path(&self.node, destination)?;
+            }
+            TaskAction::RunBackup { destination } => {
+                export_path(&self.node, destination)?;
+                if self.node.db.get_database_backend() != DatabaseBackend::Sqlite {
+                    return Err(AppError::Validation(
+                        "Backups are only supported for SQLite databases".to_string(),
+                    ));
+                }
+            }
+            TaskAction::PurgeData { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn record(&self, run: &TaskRun) -> Result<(), AppError> {
+        let runs = self.runs()?;
+        let key = format!(
+            "{}{:020}",
+            run_prefix(&run.task_id),
+            run.started_at.timestamp_micros()
+        );
+        let json = serde_json::to_vec(run).map_err(|e| AppError::Storage(Box::new(e)))?;
+        runs.insert(key.as_bytes(), json)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        let keys = runs
+            .scan_prefix(run_prefix(&run.task_id))
+            .keys()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        let kept = self
+            .node
+            .settings
+            .get::<SchedulerSettings>()?
+            .max_run_history;
+        for key in keys.iter().take(keys.len().saturating_sub(kept)) {
+            runs.remove(key)
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, task: &ScheduledTask) -> Result<(), AppError> {
+        let json = serde_json::to_vec(task).map_err(|e| AppError::Storage(Box::new(e)))?;
+        self.tasks()?
+            .insert(task.id.as_bytes(), json)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(())
+    }
+
+    fn tasks(&self) -> Result<sled::Tree, AppError> {
+        self.node
+            .kv
+            .open_tree(TASK_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    fn runs(&self) -> Result<sled::Tree, AppError> {
+        self.node
+            .kv
+            .open_tree(RUN_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+/// Parse a cron expression, accepting the five-field form without seconds
+pub fn parse_schedule(expression: &str) -> Result<Schedule, AppError> {
+    let expression = expression.trim();
+    let normalized = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    Schedule::from_str(&normalized)
+        .map_err(|e| AppError::Validation(format!("Invalid cron expression: {}", e)))
+}
+
+async fn run_action(node: &Node, action: &TaskAction) -> Result<Value, AppError> {
+    match action {
+        TaskAction::ScanSpace { space_key } => {
+            let manifest = node.space_manifest(space_key, "").await?.manifest;
+            Ok(json!({
+                "files": manifest.entries.len(),
+                "bytes": manifest.entries.iter().map(|e| e.size).sum::<u64>(),
+            }))
+        }
+        TaskAction::ExportArchive {
+            space_key,
+            destination,
+        } => {
+            let space = space::find_space(&node.db, space_key).await?;
+            let archive =
+                export_path(node, destination)?.join(format!("{}-{}.tar", space.key, timestamp()));
+            let path = archive.clone();
+            tokio::task::spawn_blocking(move || write_archive(Path::new(&space.location), &path))
+                .await
+                .map_err(|e| AppError::IO(std::io::Error::other(e)))??;
+            Ok(json!({"archive": archive}))
+        }
+        TaskAction::RunBackup { destination } => {
+            let destination = export_path(node, destination)?;
+            tokio::fs::create_dir_all(&destination).await?;
+            let backup = destination.join(format!("flow-{}.db", timestamp()));
+            let path = backup.to_str().ok_or_else(|| {
+                AppError::Config("Backup path contains invalid UTF-8".to_string())
+            })?;
+            // A URI with an explicit mode, so the copy of an in-memory
+            // database is still written to disk
+            let target = utf8_percent_encode(path, URI_RESERVED)
+                .to_string()
+                .replace('\'', "''");
+            node.db
+                .execute_unprepared(&format!("VACUUM INTO 'file:{}?mode=rwc'", target))
+                .await
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+            Ok(json!({"backup": backup}))
+        }
+        TaskAction::PurgeData { dry_run } => Ok(json!(node.purge_expired(*dry_run)?)),
+        TaskAction::AnchorSpace { space_key } => {
+            let anchor = node.anchor_space(space_key).await?;
+            Ok(json!({
+                "merkleRoot": anchor.merkle_root,
+                "credential": anchor.credential.credential.id,
+            }))
+        }
+    }
+}
+
+fn write_archive(source: &Path, archive: &Path) -> Result<(), AppError> {
+    if let Some(parent) = archive.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut builder = tar::Builder::new(std::fs::File::create(archive)?);
+    builder.append_dir_all(".", source)?;
+    builder.finish()?;
+    Ok(())
+}
+
+/// Where `destination` is in the node's export dir; paths that would leave
+/// it are refused
+fn export_path(node: &Node, destination: &Path) -> Result<PathBuf, AppError> {
+    if !is_plain_relative(destination) {
+        return Err(AppError::Validation(
+            "Destination must be a path relative to the export dir, without '..'".to_string(),
+        ));
+    }
+    let home = node
+        .home
+        .as_ref()
+        .ok_or_else(|| AppError::Config("Exports need a config dir".to_string()))?;
+    Ok(home.join(&node.export_dir).join(destination))
+}
+
+/// Whether `path` stays within whatever directory it's joined to
+pub fn is_plain_relative(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+fn run_prefix(task_id: &str) -> String {
+    format!("{}/", task_id)
+}
+
+fn timestamp() -> String {
+    Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
@@ -0,0 +1,460 @@
+//! Vault for third-party secrets (OAuth tokens, webhook signing secrets and
+//! TURN shared secrets) and the keys of encrypted spaces.
+//!
+//! Entries live in the `secrets` sled tree. Each value is sealed with the
+//! node's column keys ([`ColumnKeys`]) under a context naming the entry, so a
+//! sealed value copied to another entry won't open. Entries sealed under an
+//! older master key are re-sealed when the node starts after
+//! `node rotate-column-key`.
+//!
+//! Rotating a secret keeps the previous value for a grace period, for
+//! consumers that have to accept both while the other side catches up.
+//!
+//! Values are held in [`Secret`]s, which print and serialize as
+//! `[redacted]`; only [`SecretValue::expose`] hands out the plaintext, and
+//! listing the vault reports metadata alone.
+
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use entity::cipher::ColumnCipher;
+use errors::AppError;
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Value, json};
+use sled::Db;
+use zeroize::Zeroize;
+
+use crate::modules::column_crypto::{self, ColumnKeys};
+
+pub const SECRETS_TREE: &str = "secrets";
+pub const REDACTED: &str = "[redacted]";
+const MAX_NAME_LEN: usize = 128;
+const AUDIT: &str = "audit";
+
+/// A secret string that stays out of logs and serialized output
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretKind {
+    OauthToken,
+    WebhookSecret,
+    TurnSecret,
+    SpaceKey,
+}
+
+impl SecretKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecretKind::OauthToken => "oauth_token",
+            SecretKind::WebhookSecret => "webhook_secret",
+            SecretKind::TurnSecret => "turn_secret",
+            SecretKind::SpaceKey => "space_key",
+        }
+    }
+}
+
+impl fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for SecretKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "oauth_token" => Ok(SecretKind::OauthToken),
+            "webhook_secret" => Ok(SecretKind::WebhookSecret),
+            "turn_secret" => Ok(SecretKind::TurnSecret),
+            "space_key" => Ok(SecretKind::SpaceKey),
+            _ => Err(AppError::Validation(format!("Unknown secret kind: {}", s))),
+        }
+    }
+}
+
+/// A value the vault keeps, of a fixed kind
+pub trait SecretValue: DeserializeOwned {
+    const KIND: SecretKind;
+
+    /// The value with its secrets in plaintext, as it is sealed
+    fn expose(&self) -> Value;
+}
+
+/// Tokens granted by an OAuth authorization server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthToken {
+    pub access_token: Secret,
+    #[serde(default)]
+    pub refresh_token: Option<Secret>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+impl SecretValue for OAuthToken {
+    const KIND: SecretKind = SecretKind::OauthToken;
+
+    fn expose(&self) -> Value {
+        json!({
+            "accessToken": self.access_token.expose(),
+            "refreshToken": self.refresh_token.as_ref().map(Secret::expose),
+            "expiresAt": self.expires_at,
+            "scope": self.scope,
+        })
+    }
+}
+
+/// Key outgoing webhook deliveries are signed with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WebhookSecret(pub Secret);
+
+impl SecretValue for WebhookSecret {
+    const KIND: SecretKind = SecretKind::WebhookSecret;
+
+    fn expose(&self) -> Value {
+        Value::String(self.0.expose().to_string())
+    }
+}
+
+/// Shared secret for TURN REST API credentials
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TurnSecret(pub Secret);
+
+impl SecretValue for TurnSecret {
+    const KIND: SecretKind = SecretKind::TurnSecret;
+
+    fn expose(&self) -> Value {
+        Value::String(self.0.expose().to_string())
+    }
+}
+
+/// Key the files of an encrypted space are sealed with, base64url encoded;
+/// named by the space key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SpaceKey(pub Secret);
+
+impl SecretValue for SpaceKey {
+    const KIND: SecretKind = SecretKind::SpaceKey;
+
+    fn expose(&self) -> Value {
+        Value::String(self.0.expose().to_string())
+    }
+}
+
+/// What the vault tells about an entry, without its value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretInfo {
+    pub kind: SecretKind,
+    pub name: String,
+    /// Starts at 1 and increases with each replacement or rotation
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Until when the value before the last rotation is still handed out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    info: SecretInfo,
+    sealed: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous: Option<String>,
+}
+
+/// Encrypted third-party secrets
+pub struct SecretVault {
+    kv: Db,
+    keys: ColumnKeys,
+}
+
+impl SecretVault {
+    pub fn new(kv: Db, keys: ColumnKeys) -> Self {
+        Self { kv, keys }
+    }
+
+    /// Store a secret, replacing any previous value outright
+    pub fn put<T: SecretValue>(&self, name: &str, value: &T) -> Result<SecretInfo, AppError> {
+        self.write(name, value, None)
+    }
+
+    /// Replace a secret, keeping its previous value readable for `grace`
+    pub fn rotate<T: SecretValue>(
+        &self,
+        name: &str,
+        value: &T,
+        grace: Duration,
+    ) -> Result<SecretInfo, AppError> {
+        if self.load(T::KIND, name)?.is_none() {
+            return Err(AppError::NotFound(format!("No {} named {}", T::KIND, name)));
+        }
+        self.write(name, value, Some(grace))
+    }
+
+    pub fn get<T: SecretValue>(&self, name: &str) -> Result<Option<T>, AppError> {
+        self.load(T::KIND, name)?
+            .map(|entry| self.open(T::KIND, name, &entry.sealed))
+            .transpose()
+    }
+
+    /// The value before the last rotation, while its grace period lasts
+    pub fn previous<T: SecretValue>(&self, name: &str) -> Result<Option<T>, AppError> {
+        let Some(entry) = self.load(T::KIND, name)? else {
+            return Ok(None);
+        };
+        match (&entry.previous, entry.info.previous_until) {
+            (Some(sealed), Some(until)) if until > Utc::now() => {
+                self.open(T::KIND, name, sealed).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn info(&self, kind: SecretKind, name: &str) -> Result<Option<SecretInfo>, AppError> {
+        Ok(self.load(kind, name)?.map(|entry| entry.info))
+    }
+
+    /// Every entry, by kind and name
+    pub fn list(&self) -> Result<Vec<SecretInfo>, AppError> {
+        self.tree()?
+            .iter()
+            .values()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice::<Entry>(&value)
+                    .map(|entry| entry.info)
+                    .map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .collect()
+    }
+
+    /// Remove a secret. False if there was none.
+    pub fn delete(&self, kind: SecretKind, name: &str) -> Result<bool, AppError> {
+        let removed = self
+            .tree()?
+            .remove(entry_key(kind, name))
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .is_some();
+        if removed {
+            info!(target: AUDIT, "Deleted {} {}", kind, name);
+        }
+        Ok(removed)
+    }
+
+    /// Re-seal entries sealed under an older column key, returning how many
+    /// were rewritten
+    pub fn reseal(&self) -> Result<u64, AppError> {
+        let tree = self.tree()?;
+        let mut rewritten = 0;
+        for item in tree.iter() {
+            let (key, value) = item.map_err(|e| AppError::Storage(Box::new(e)))?;
+            let mut entry: Entry =
+                serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))?;
+            let current = self.keys.is_current(&entry.sealed)
+                && entry
+                    .previous
+                    .as_ref()
+                    .is_none_or(|sealed| self.keys.is_current(sealed));
+            if current {
+                continue;
+            }
+
+            let (kind, name) = (entry.info.kind, entry.info.name.clone());
+            entry.sealed = self.reseal_value(kind, &name, &entry.sealed)?;
+            if let Some(previous) = &entry.previous {
+                entry.previous = Some(self.reseal_value(kind, &name, previous)?);
+            }
+            tree.insert(key, encode(&entry)?)
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+            rewritten += 1;
+        }
+
+        if rewritten > 0 {
+            info!(
+                "Re-sealed {} secrets under column key {}",
+                rewritten,
+                self.keys.active_key_id()
+            );
+        }
+        Ok(rewritten)
+    }
+
+    fn write<T: SecretValue>(
+        &self,
+        name: &str,
+        value: &T,
+        grace: Option<Duration>,
+    ) -> Result<SecretInfo, AppError> {
+        validate_name(name)?;
+        let now = Utc::now();
+        let sealed = self.seal(T::KIND, name, &value.expose())?;
+        let previous = self.load(T::KIND, name)?;
+
+        let entry = match previous {
+            None => Entry {
+                info: SecretInfo {
+                    kind: T::KIND,
+                    name: name.to_string(),
+                    version: 1,
+                    created_at: now,
+                    updated_at: now,
+                    previous_until: None,
+                },
+                sealed,
+                previous: None,
+            },
+            Some(old) => {
+                let previous_until = grace
+                    .map(|grace| {
+                        chrono::Duration::from_std(grace)
+                            .map(|grace| now + grace)
+                            .map_err(|e| AppError::Validation(format!("Invalid grace: {}", e)))
+                    })
+                    .transpose()?;
+                Entry {
+                    info: SecretInfo {
+                        version: old.info.version + 1,
+                        updated_at: now,
+                        previous_until,
+                        ..old.info
+                    },
+                    sealed,
+                    previous: previous_until.map(|_| old.sealed),
+                }
+            }
+        };
+
+        self.tree()?
+            .insert(entry_key(T::KIND, name), encode(&entry)?)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        info!(
+            target: AUDIT,
+            "Stored {} {} (version {})", T::KIND, name, entry.info.version
+        );
+        Ok(entry.info)
+    }
+
+    fn load(&self, kind: SecretKind, name: &str) -> Result<Option<Entry>, AppError> {
+        self.tree()?
+            .get(entry_key(kind, name))
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .map(|bytes| serde_json::from_slice(&bytes).map_err(|e| AppError::Storage(Box::new(e))))
+            .transpose()
+    }
+
+    fn seal(&self, kind: SecretKind, name: &str, value: &Value) -> Result<String, AppError> {
+        self.keys
+            .encrypt(&context(kind, name), 0, &value.to_string())
+            .map_err(|e| AppError::Crypto(format!("Sealing {} {}: {}", kind, name, e)))
+    }
+
+    fn open<T: SecretValue>(
+        &self,
+        kind: SecretKind,
+        name: &str,
+        sealed: &str,
+    ) -> Result<T, AppError> {
+        // Never take a value that wasn't sealed at all
+        if column_crypto::key_id_of(sealed).is_none() {
+            return Err(AppError::Crypto(format!("{} {} is not sealed", kind, name)));
+        }
+        let plaintext = self
+            .keys
+            .decrypt(&context(kind, name), 0, sealed)
+            .map_err(|e| AppError::Crypto(format!("Opening {} {}: {}", kind, name, e)))?;
+        serde_json::from_str(&plaintext).map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    fn reseal_value(&self, kind: SecretKind, name: &str, sealed: &str) -> Result<String, AppError> {
+        let plaintext = self
+            .keys
+            .decrypt(&context(kind, name), 0, sealed)
+            .map_err(|e| AppError::Crypto(format!("Opening {} {}: {}", kind, name, e)))?;
+        self.keys
+            .encrypt(&context(kind, name), 0, &plaintext)
+            .map_err(|e| AppError::Crypto(format!("Sealing {} {}: {}", kind, name, e)))
+    }
+
+    fn tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(SECRETS_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'));
+    if !valid {
+        return Err(AppError::Validation(format!(
+            "Secret names are 1 to {} letters, digits, '.', '_' or '-'",
+            MAX_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn entry_key(kind: SecretKind, name: &str) -> String {
+    format!("{}/{}", kind, name)
+}
+
+/// What a value is sealed for, bound into its ciphertext
+fn context(kind: SecretKind, name: &str) -> String {
+    format!("secret:{}/{}", kind, name)
+}
+
+fn encode(entry: &Entry) -> Result<Vec<u8>, AppError> {
+    serde_json::to_vec(entry).map_err(|e| AppError::Storage(Box::new(e)))
+}
@@ -0,0 +1,321 @@
+//! Short-lived, expiring server-side state, kept in the `session` table.
+//!
+//! Where node state lives:
+//!
+//! | State                                   | Store                          |
+//! |-----------------------------------------|--------------------------------|
+//! | Users, passkeys, spaces                 | Database (entities)            |
+//! | Pending WebAuthn ceremonies and other   | Database, via [`SessionStore`] |
+//! | expiring auth state                     |                                |
+//! | Node identity keys, data layout         | Config directory               |
+//! | Update status, local PIN hash, plugin   | sled KV store                  |
+//! | and script data                         |                                |
+//!
+//! New expiring auth state (rate-limit buckets, resume tokens, ...) belongs in
+//! the `SessionStore` under its own [`SessionKind`], not in statics or sled.
+//! Pending ceremonies were previously held in process-global maps and never
+//! persisted, so there is no existing data to carry over.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use entity::session;
+use errors::AppError;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::modules::auth_crypto;
+use crate::modules::clock::TimeSource;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionKind {
+    /// In-flight passkey registration, keyed by challenge
+    Registration,
+    /// In-flight passkey authentication, keyed by challenge ID
+    Authentication,
+    /// Failed local PIN attempts and lockout
+    PinAttempts,
+    /// Read-only grant from a PIN unlock, keyed by token
+    PinUnlock,
+    /// Failed authentications per credential or client network
+    AuthFailures,
+    /// Signed-in user in multi-user mode, keyed by token
+    UserSession,
+}
+
+impl SessionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionKind::Registration => "webauthn_registration",
+            SessionKind::Authentication => "webauthn_authentication",
+            SessionKind::PinAttempts => "pin_attempts",
+            SessionKind::PinUnlock => "pin_unlock",
+            SessionKind::AuthFailures => "auth_failures",
+            SessionKind::UserSession => "user_session",
+        }
+    }
+
+    /// Whether keys of this kind are bearer secrets. Those records are stored
+    /// under [`auth_crypto::lookup_key`] of the key, never the key itself.
+    pub fn is_secret(&self) -> bool {
+        matches!(
+            self,
+            SessionKind::Registration | SessionKind::Authentication | SessionKind::PinUnlock
+        )
+    }
+
+    /// Whether records of this kind stay valid for the allowed clock skew
+    /// past their expiry: ceremonies a client may finish late by its own
+    /// clock, and sessions whose tokens carry their expiry
+    pub fn tolerates_skew(&self) -> bool {
+        matches!(
+            self,
+            SessionKind::Registration | SessionKind::Authentication | SessionKind::UserSession
+        )
+    }
+
+    fn stored_key(&self, key: &str) -> String {
+        if self.is_secret() {
+            auth_crypto::lookup_key(key)
+        } else {
+            key.to_string()
+        }
+    }
+}
+
+/// Outcome of consuming a session record
+#[derive(Debug, PartialEq, Eq)]
+pub enum Taken<T> {
+    Found(T),
+    Expired,
+    Missing,
+}
+
+#[derive(Clone)]
+pub struct SessionStore {
+    db: DatabaseConnection,
+    time: TimeSource,
+}
+
+impl SessionStore {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self::with_time(db, TimeSource::system())
+    }
+
+    /// A store reading the time from `time`
+    pub fn with_time(db: DatabaseConnection, time: TimeSource) -> Self {
+        Self { db, time }
+    }
+
+    pub fn time(&self) -> &TimeSource {
+        &self.time
+    }
+
+    /// Store `value` under `(kind, key)` for `ttl`, replacing any existing record
+    pub async fn put<T: Serialize>(
+        &self,
+        kind: SessionKind,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<(), AppError> {
+        self.put_on(&self.db, kind, key, value, ttl).await
+    }
+
+    /// Replace the record under `(kind, key)` with what `update` makes of
+    /// the current one (`None` when missing or expired) and store it for
+    /// the returned TTL. Concurrent updates of a record apply one after
+    /// the other, so none is lost.
+    pub async fn update<T, F>(&self, kind: SessionKind, key: &str, update: F) -> Result<T, AppError>
+    where
+        T: Serialize + DeserializeOwned + Send,
+        F: FnOnce(Option<T>) -> (T, Duration) + Send,
+    {
+        let txn = self
+            .db
+            .begin()
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        // Writing before reading takes SQLite's write lock, so a concurrent
+        // update waits for this one to commit before it reads
+        session::Entity::update_many()
+            .col_expr(
+                session::Column::Data,
+                Expr::col(session::Column::Data).into(),
+            )
+            .filter(session::Column::Kind.eq(kind.as_str()))
+            .filter(session::Column::Key.eq(kind.stored_key(key)))
+            .exec(&txn)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        let current = match find_on(&txn, kind, key).await? {
+            Some(record) if !self.has_expired(kind, &record) => Some(decode(&record)?),
+            _ => None,
+        };
+        let (value, ttl) = update(current);
+        self.put_on(&txn, kind, key, &value, ttl).await?;
+
+        txn.commit()
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(value)
+    }
+
+    async fn put_on<T: Serialize>(
+        &self,
+        db: &impl ConnectionTrait,
+        kind: SessionKind,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<(), AppError> {
+        let data = serde_json::to_string(value).map_err(|e| AppError::Storage(Box::new(e)))?;
+        let now = self.time.now();
+        let ttl = chrono::Duration::from_std(ttl)
+            .map_err(|e| AppError::Validation(format!("Invalid session TTL: {}", e)))?;
+
+        let record = session::ActiveModel {
+            id: NotSet,
+            kind: Set(kind.as_str().to_string()),
+            key: Set(kind.stored_key(key)),
+            data: Set(data),
+            expires_at: Set((now + ttl).into()),
+            time_created: Set(now.into()),
+        };
+
+        session::Entity::insert(record)
+            .on_conflict(
+                OnConflict::columns([session::Column::Kind, session::Column::Key])
+                    .update_columns([
+                        session::Column::Data,
+                        session::Column::ExpiresAt,
+                        session::Column::TimeCreated,
+                    ])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    /// Read a record without consuming it. Expired records read as `None`.
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        kind: SessionKind,
+        key: &str,
+    ) -> Result<Option<T>, AppError> {
+        match find_on(&self.db, kind, key).await? {
+            Some(record) if !self.has_expired(kind, &record) => decode(&record).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Remove and return a record, so it can be used at most once
+    pub async fn take<T: DeserializeOwned>(
+        &self,
+        kind: SessionKind,
+        key: &str,
+    ) -> Result<Taken<T>, AppError> {
+        let Some(record) = find_on(&self.db, kind, key).await? else {
+            return Ok(Taken::Missing);
+        };
+
+        let deleted = session::Entity::delete_by_id(record.id)
+            .exec(&self.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if deleted.rows_affected == 0 {
+            // Consumed concurrently
+            return Ok(Taken::Missing);
+        }
+
+        if self.has_expired(kind, &record) {
+            return Ok(Taken::Expired);
+        }
+        decode(&record).map(Taken::Found)
+    }
+
+    /// All unexpired records of a kind, keyed by their key (its lookup key, for
+    /// secret kinds)
+    pub async fn list<T: DeserializeOwned>(
+        &self,
+        kind: SessionKind,
+    ) -> Result<Vec<(String, T)>, AppError> {
+        session::Entity::find()
+            .filter(session::Column::Kind.eq(kind.as_str()))
+            .filter(session::Column::ExpiresAt.gt(self.expired_before(kind)))
+            .all(&self.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .into_iter()
+            .map(|record| decode(&record).map(|value| (record.key, value)))
+            .collect()
+    }
+
+    pub async fn delete(&self, kind: SessionKind, key: &str) -> Result<(), AppError> {
+        session::Entity::delete_many()
+            .filter(session::Column::Kind.eq(kind.as_str()))
+            .filter(session::Column::Key.eq(kind.stored_key(key)))
+            .exec(&self.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(())
+    }
+
+    /// Delete every expired record, returning how many were removed. Records
+    /// are kept for the allowed skew past their expiry, in case their kind
+    /// tolerates it.
+    pub async fn sweep_expired(&self) -> Result<u64, AppError> {
+        let result = session::Entity::delete_many()
+            .filter(session::Column::ExpiresAt.lte(self.now_less_skew()))
+            .exec(&self.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(result.rows_affected)
+    }
+
+    fn has_expired(&self, kind: SessionKind, record: &session::Model) -> bool {
+        record.expires_at <= self.expired_before(kind)
+    }
+
+    /// Records of `kind` that expired at or before this time are gone
+    fn expired_before(&self, kind: SessionKind) -> DateTime<Utc> {
+        if kind.tolerates_skew() {
+            self.now_less_skew()
+        } else {
+            self.time.now()
+        }
+    }
+
+    fn now_less_skew(&self) -> DateTime<Utc> {
+        chrono::Duration::from_std(self.time.max_skew)
+            .ok()
+            .and_then(|skew| self.time.now().checked_sub_signed(skew))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+async fn find_on(
+    db: &impl ConnectionTrait,
+    kind: SessionKind,
+    key: &str,
+) -> Result<Option<session::Model>, AppError> {
+    session::Entity::find()
+        .filter(session::Column::Kind.eq(kind.as_str()))
+        .filter(session::Column::Key.eq(kind.stored_key(key)))
+        .one(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))
+}
+
+fn decode<T: DeserializeOwned>(record: &session::Model) -> Result<T, AppError> {
+    serde_json::from_str(&record.data).map_err(|e| AppError::Storage(Box::new(e)))
+}
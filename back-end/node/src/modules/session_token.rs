@@ -0,0 +1,66 @@
+//! Signed session tokens.
+//!
+//! A session token is a JWT (RFC 7519) signed with the node's Ed25519
+//! identity key (`alg: EdDSA`, RFC 8037). Forged and expired tokens are
+//! turned away on the signature and `exp` alone; the session store keeps a
+//! record per session as well, so logging out revokes a token before it
+//! expires.
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+
+use crate::modules::clock::TimeSource;
+use crate::modules::jws;
+
+const TOKEN_TYPE: &str = "JWT";
+
+/// What a session token asserts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionClaims {
+    /// DID of the issuing node
+    pub iss: String,
+    /// ID of the signed-in user
+    pub sub: String,
+    /// Session ID, the key of the session's record
+    pub sid: String,
+    /// Issue and expiry times, in seconds since the Unix epoch
+    pub iat: i64,
+    pub exp: i64,
+}
+
+impl SessionClaims {
+    pub fn user_id(&self) -> Option<i32> {
+        self.sub.parse().ok()
+    }
+
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.iat, 0).unwrap_or_default()
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.exp, 0).unwrap_or_default()
+    }
+}
+
+/// Encode and sign `claims`
+pub fn sign(claims: &SessionClaims, key: &SigningKey) -> Result<String, AppError> {
+    jws::sign(&jws::Header::new(TOKEN_TYPE), claims, key)
+}
+
+/// Claims of a token `key` signed for `issuer`, if it hasn't expired
+pub fn verify(token: &str, key: &VerifyingKey, issuer: &str) -> Option<SessionClaims> {
+    verify_at(token, key, issuer, &TimeSource::system())
+}
+
+/// [`verify`], by `time` and allowing for its clock skew
+pub fn verify_at(
+    token: &str,
+    key: &VerifyingKey,
+    issuer: &str,
+    time: &TimeSource,
+) -> Option<SessionClaims> {
+    let (_, claims): (_, SessionClaims) = jws::verify(token, key)?;
+    (claims.iss == issuer && !time.has_passed(claims.expires_at())).then_some(claims)
+}
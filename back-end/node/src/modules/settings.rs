@@ -0,0 +1,156 @@
+//! Application settings kept in the KV store.
+//!
+//! Settings come in namespaces, each a serde type implementing [`Settings`]
+//! owned by the module it tunes (e.g. [`PinSettings`] in `pin`). Reads go
+//! through to the KV store, so a change applies to the next use without a
+//! restart; a namespace never written reads as its defaults. Writes are
+//! validated by the namespace first, and anyone holding a [`watch`] receiver
+//! from [`SettingsStore::watch`] sees the new value.
+//!
+//! The REST API reaches namespaces by name, as JSON, through
+//! [`SettingsStore::get_json`] and [`SettingsStore::put_json`]; a namespace
+//! must be listed in [`NAMESPACES`] to be reachable there.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use errors::AppError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sled::Db;
+use tokio::sync::watch;
+
+use crate::modules::pin::PinSettings;
+use crate::modules::scheduler::SchedulerSettings;
+
+pub const SETTINGS_TREE: &str = "settings";
+
+/// A namespace of settings
+pub trait Settings: Serialize + DeserializeOwned + Default + Clone + Send + Sync + 'static {
+    const NAMESPACE: &'static str;
+
+    /// Reject values that parse but make no sense
+    fn validate(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+/// A namespace reachable by name
+pub struct Namespace {
+    pub name: &'static str,
+    get: fn(&SettingsStore) -> Result<Value, AppError>,
+    put: fn(&SettingsStore, Value) -> Result<Value, AppError>,
+}
+
+impl Namespace {
+    const fn of<T: Settings>() -> Self {
+        Self {
+            name: T::NAMESPACE,
+            get: |store| to_json(&store.get::<T>()?),
+            put: |store, value| {
+                let settings: T = serde_json::from_value(value).map_err(|e| {
+                    AppError::Validation(format!("Invalid {} settings: {}", T::NAMESPACE, e))
+                })?;
+                store.put(&settings)?;
+                to_json(&settings)
+            },
+        }
+    }
+}
+
+pub const NAMESPACES: &[Namespace] = &[
+    Namespace::of::<PinSettings>(),
+    Namespace::of::<SchedulerSettings>(),
+];
+
+#[derive(Clone)]
+pub struct SettingsStore {
+    kv: Db,
+    /// The `watch::Sender<T>` of each watched namespace
+    watchers: Arc<Mutex<HashMap<&'static str, Box<dyn Any + Send + Sync>>>>,
+}
+
+impl SettingsStore {
+    pub fn new(kv: Db) -> Self {
+        Self {
+            kv,
+            watchers: Arc::default(),
+        }
+    }
+
+    /// Current settings of a namespace, its defaults if never written
+    pub fn get<T: Settings>(&self) -> Result<T, AppError> {
+        let stored = self
+            .tree()?
+            .get(T::NAMESPACE)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        match stored {
+            Some(json) => serde_json::from_slice(&json).map_err(|e| AppError::Storage(Box::new(e))),
+            None => Ok(T::default()),
+        }
+    }
+
+    /// Validate and store `settings`, notifying watchers
+    pub fn put<T: Settings>(&self, settings: &T) -> Result<(), AppError> {
+        settings.validate()?;
+        let json = serde_json::to_vec(settings).map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        // Held across the write, so watchers see writes in order
+        let watchers = self.watchers.lock().unwrap_or_else(|e| e.into_inner());
+        self.tree()?
+            .insert(T::NAMESPACE, json)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if let Some(sender) = watchers
+            .get(T::NAMESPACE)
+            .and_then(|sender| sender.downcast_ref::<watch::Sender<T>>())
+        {
+            sender.send_replace(settings.clone());
+        }
+        Ok(())
+    }
+
+    /// Receiver holding a namespace's current settings, updated on every
+    /// write through this store or its clones
+    pub fn watch<T: Settings>(&self) -> Result<watch::Receiver<T>, AppError> {
+        let mut watchers = self.watchers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = watchers
+            .get(T::NAMESPACE)
+            .and_then(|sender| sender.downcast_ref::<watch::Sender<T>>())
+        {
+            return Ok(sender.subscribe());
+        }
+        let (sender, receiver) = watch::channel(self.get::<T>()?);
+        watchers.insert(T::NAMESPACE, Box::new(sender));
+        Ok(receiver)
+    }
+
+    /// Settings of the namespace called `name`, as JSON
+    pub fn get_json(&self, name: &str) -> Result<Value, AppError> {
+        (namespace(name)?.get)(self)
+    }
+
+    /// Replace the settings of the namespace called `name`; fields left out
+    /// take their defaults. Answers with what was stored.
+    pub fn put_json(&self, name: &str, value: Value) -> Result<Value, AppError> {
+        (namespace(name)?.put)(self, value)
+    }
+
+    fn tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(SETTINGS_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+fn namespace(name: &str) -> Result<&'static Namespace, AppError> {
+    NAMESPACES
+        .iter()
+        .find(|namespace| namespace.name == name)
+        .ok_or_else(|| AppError::NotFound(format!("No settings namespace '{}'", name)))
+}
+
+fn to_json<T: Serialize>(settings: &T) -> Result<Value, AppError> {
+    serde_json::to_value(settings).map_err(|e| AppError::Storage(Box::new(e)))
+}
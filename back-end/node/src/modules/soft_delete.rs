@@ -0,0 +1,123 @@
+//! Soft deletion of users, spaces and passkeys.
+//!
+//! Deleting one of these stamps its `deleted_at` instead of removing the
+//! row. Lookups go through [`live`], which skips stamped rows, so the row
+//! reads as gone; for [`RetentionConfig::restore_window`] it can be brought
+//! back, and after that the runner's purge ([`purge`]) removes it for good
+//! along with what hangs off it, such as a space's Merkle index. Request
+//! handlers never hard-delete.
+//!
+//! Users follow their account deletion (see [`crate::modules::account`]):
+//! stamped when it is asked for, cleared when it is cancelled and erased
+//! when its grace is over. A user pending deletion still signs in, to export
+//! their data or cancel.
+//!
+//! [`RetentionConfig::restore_window`]: crate::bootstrap::config::RetentionConfig::restore_window
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use entity::{pass_key, space, user};
+use errors::AppError;
+use log::info;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Select, TransactionTrait};
+use serde::Serialize;
+
+use crate::api::node::Node;
+use crate::modules::history::{self, Change};
+use crate::modules::merkle::MerkleIndex;
+
+/// An entity whose rows are deleted by stamping `deleted_at`
+pub trait SoftDelete: EntityTrait {
+    fn deleted_at_column() -> Self::Column;
+}
+
+impl SoftDelete for user::Entity {
+    fn deleted_at_column() -> Self::Column {
+        user::Column::DeletedAt
+    }
+}
+
+impl SoftDelete for space::Entity {
+    fn deleted_at_column() -> Self::Column {
+        space::Column::DeletedAt
+    }
+}
+
+impl SoftDelete for pass_key::Entity {
+    fn deleted_at_column() -> Self::Column {
+        pass_key::Column::DeletedAt
+    }
+}
+
+/// Rows not deleted
+pub fn live<E: SoftDelete>() -> Select<E> {
+    E::find().filter(E::deleted_at_column().is_null())
+}
+
+/// Rows deleted, restorable or not
+pub fn deleted<E: SoftDelete>() -> Select<E> {
+    E::find().filter(E::deleted_at_column().is_not_null())
+}
+
+/// Deletions before this are past restoring
+pub fn restorable_since(now: DateTime<Utc>, window: Duration) -> Result<DateTime<Utc>, AppError> {
+    chrono::Duration::from_std(window)
+        .map(|window| now - window)
+        .map_err(|e| AppError::Config(format!("Invalid restore window: {}", e)))
+}
+
+/// What a purge removed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedPurge {
+    pub spaces: u64,
+    pub passkeys: u64,
+    /// Directories of the purged spaces, left on disk
+    pub space_locations: Vec<String>,
+}
+
+/// Remove spaces and passkeys deleted longer ago than the restore window
+pub async fn purge(node: &Node, now: DateTime<Utc>) -> Result<DeletedPurge, AppError> {
+    let before = restorable_since(now, node.retention.restore_window)?;
+    let mut purged = DeletedPurge::default();
+
+    let spaces = deleted::<space::Entity>()
+        .filter(space::Column::DeletedAt.lt(before))
+        .all(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    for space in spaces {
+        MerkleIndex::drop_index(&node.kv, &space.key)?;
+        let txn = node
+            .db
+            .begin()
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        space::Entity::delete_by_id(space.id)
+            .exec(&txn)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        history::record(&txn, &(&space).into(), Change::Removed, Some(&space), None).await?;
+        txn.commit()
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        purged.space_locations.push(space.location);
+        purged.spaces += 1;
+    }
+
+    purged.passkeys = pass_key::Entity::delete_many()
+        .filter(pass_key::Column::DeletedAt.lt(before))
+        .exec(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .rows_affected;
+
+    if purged.spaces + purged.passkeys > 0 {
+        info!(
+            "Purged {} deleted spaces and {} deleted passkeys",
+            purged.spaces, purged.passkeys
+        );
+    }
+    Ok(purged)
+}
@@ -1,25 +1,99 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use errors::AppError;
 use log::{info, warn};
 use sea_orm::{
-    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    EntityTrait, QueryFilter, QueryOrder, TransactionTrait,
 };
+use serde::Serialize;
 
 use entity::space;
 use sha2::{Digest, Sha256};
 use space::Entity as Space;
 
-pub async fn new_space(db: &DatabaseConnection, dir: &str) -> Result<(), AppError> {
+use crate::bootstrap::layout;
+use crate::modules::history::{self, Change};
+use crate::modules::manifest::ManifestEntry;
+use crate::modules::scheduler::is_plain_relative;
+use crate::modules::soft_delete::{deleted, live};
+use crate::modules::tenancy::Actor;
+use crate::modules::versioning::update_versioned;
+
+const AUDIT: &str = "audit";
+
+/// Where a space with client-held keys keeps its encrypted index. Blob names
+/// can't start with a dot, so no blob takes its place.
+pub const INDEX_FILE: &str = ".flow-index";
+/// Longest blob name in a space with client-held keys
+const MAX_BLOB_NAME: usize = 128;
+
+/// Who encrypts the files of a space
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encryption {
+    /// Files are stored as written
+    #[default]
+    None,
+    /// The node encrypts files at rest, with a key of its own
+    Node,
+    /// Clients encrypt files before uploading them; the node holds no key and
+    /// only ever stores opaque blobs and an encrypted index
+    Client,
+}
+
+impl Encryption {
+    /// From the `encrypted` and `client_keys` flags of a create request
+    pub fn from_flags(encrypted: bool, client_keys: bool) -> Result<Self, AppError> {
+        match (encrypted, client_keys) {
+            (false, false) => Ok(Encryption::None),
+            (true, false) => Ok(Encryption::Node),
+            (false, true) => Ok(Encryption::Client),
+            (true, true) => Err(AppError::Validation(
+                "A space is encrypted by the node or by its clients, not both".to_string(),
+            )),
+        }
+    }
+
+    pub fn of(space: &space::Model) -> Self {
+        match (space.encrypted, space.client_keys) {
+            (_, true) => Encryption::Client,
+            (true, false) => Encryption::Node,
+            (false, false) => Encryption::None,
+        }
+    }
+}
+
+/// Create a space for `actor`, or find the one already at `dir`, restoring
+/// it if it was deleted. A user's spaces are theirs alone, and only made in
+/// their directory under `spaces_root`; the node's have no owner and go
+/// anywhere. Asking for node `encryption` turns it on for an existing space
+/// too. A space with client-held keys must start out empty, since the node
+/// would otherwise hold plaintext, and an existing space can't become one.
+pub async fn new_space(
+    db: &DatabaseConnection,
+    dir: &str,
+    actor: Actor,
+    encryption: Encryption,
+    spaces_root: Option<&Path>,
+) -> Result<space::Model, AppError> {
     info!("Setting up space in directory: {}", dir);
 
-    let path = Path::new(dir);
+    let path = match actor {
+        Actor::Node => PathBuf::from(dir),
+        Actor::User(id) => user_space_dir(spaces_root, id, dir)?,
+    };
+    let dir = path
+        .to_str()
+        .ok_or_else(|| AppError::Config("Directory path contains invalid UTF-8".to_owned()))?;
 
     if !path.exists() {
         info!("Creating directory: {}", dir);
-        fs::create_dir_all(path).map_err(|e| AppError::IO(e))?;
+        fs::create_dir_all(&path).map_err(|e| AppError::IO(e))?;
     }
 
     let space_key = generate_space_key(dir)?;
@@ -30,12 +104,46 @@ pub async fn new_space(db: &DatabaseConnection, dir: &str) -> Result<(), AppErro
         .one(db)
         .await
     {
-        Ok(Some(_existing_space)) => {
+        Ok(Some(existing_space)) => {
+            if !actor.can_access(existing_space.user_id) {
+                return Err(AppError::Forbidden(
+                    "Directory is already another user's space".to_string(),
+                ));
+            }
             info!(
                 "Space already exists at directory: {} (key: {})",
                 dir, space_key
             );
-            return Ok(());
+            match (encryption, Encryption::of(&existing_space)) {
+                (Encryption::Client, Encryption::Client) | (Encryption::None, _) => {}
+                (Encryption::Client, _) => {
+                    return Err(AppError::Validation(format!(
+                        "Space {} already holds files the node can read, so it can't take client-held keys",
+                        existing_space.key
+                    )));
+                }
+                (Encryption::Node, Encryption::Client) => {
+                    return Err(AppError::Validation(format!(
+                        "Space {} is encrypted by its clients",
+                        existing_space.key
+                    )));
+                }
+                (Encryption::Node, _) => {}
+            }
+            let mut changes: space::ActiveModel = Default::default();
+            if existing_space.deleted_at.is_some() {
+                changes.deleted_at = Set(None);
+                info!(target: AUDIT, "{} restored space {}", actor, existing_space.key);
+            }
+            if encryption == Encryption::Node && !existing_space.encrypted {
+                changes.encrypted = Set(true);
+                info!(target: AUDIT, "{} turned on encryption for space {}", actor, existing_space.key);
+            }
+            if !changes.is_changed() {
+                return Ok(existing_space);
+            }
+            // Creating is idempotent, so there is no version to hold it to
+            return update_versioned::<Space>(db, existing_space.id, None, changes).await;
         }
         Ok(None) => {
             warn!(
@@ -48,6 +156,13 @@ pub async fn new_space(db: &DatabaseConnection, dir: &str) -> Result<(), AppErro
         }
     }
 
+    if encryption == Encryption::Client && fs::read_dir(&path)?.next().is_some() {
+        return Err(AppError::Validation(format!(
+            "A space with client-held keys must start empty: {}",
+            dir
+        )));
+    }
+
     let canonical_location = path
         .canonicalize()
         .map_err(|e| AppError::IO(e))?
@@ -59,21 +174,274 @@ pub async fn new_space(db: &DatabaseConnection, dir: &str) -> Result<(), AppErro
         key: Set(space_key.clone()),
         location: Set(canonical_location.clone()),
         time_created: Set(Utc::now().into()),
+        user_id: Set(actor.user_id()),
+        encrypted: Set(encryption == Encryption::Node),
+        client_keys: Set(encryption == Encryption::Client),
         ..Default::default()
     };
 
-    match new_space.insert(db).await {
-        Ok(space_model) => {
-            info!(
-                "Successfully created space with ID: {}, Key: {}, Location: {}",
-                space_model.id, space_model.key, space_model.location
-            );
-            Ok(())
+    // The space and its first history entry commit together
+    let txn = db
+        .begin()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    let space_model = new_space
+        .insert(&txn)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    history::record(
+        &txn,
+        &(&space_model).into(),
+        Change::Created,
+        None,
+        Some(&space_model),
+    )
+    .await?;
+    txn.commit()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+    info!(
+        "Successfully created space with ID: {}, Key: {}, Location: {}",
+        space_model.id, space_model.key, space_model.location
+    );
+    info!(target: AUDIT, "{} created space {}", actor, space_model.key);
+    Ok(space_model)
+}
+
+/// Files of a space with client-held keys are opaque blobs the client names:
+/// one path segment of letters, digits, `-` and `_`
+pub fn check_blob_name(path: &str) -> Result<(), AppError> {
+    let valid = (1..=MAX_BLOB_NAME).contains(&path.len())
+        && path
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if !valid {
+        return Err(AppError::Validation(format!(
+            "Files of a space with client-held keys are blobs named by up to {} letters, digits, '-' or '_': {}",
+            MAX_BLOB_NAME, path
+        )));
+    }
+    Ok(())
+}
+
+/// Look up a space by its key
+pub async fn find_space(db: &impl ConnectionTrait, key: &str) -> Result<space::Model, AppError> {
+    live::<Space>()
+        .filter(space::Column::Key.eq(key))
+        .one(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .ok_or_else(|| AppError::NotFound(format!("Space not found: {}", key)))
+}
+
+/// Record the root of a space's Merkle tree
+pub async fn set_merkle_root(
+    db: &impl ConnectionTrait,
+    key: &str,
+    root: &str,
+) -> Result<(), AppError> {
+    let mut active: space::ActiveModel = find_space(db, key).await?.into();
+    active.merkle_root = Set(Some(root.to_string()));
+    active
+        .update(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(())
+}
+
+/// Look up a space `actor` may see; other users' spaces read as missing
+pub async fn find_space_for(
+    db: &DatabaseConnection,
+    key: &str,
+    actor: Actor,
+) -> Result<space::Model, AppError> {
+    let space = find_space(db, key).await?;
+    if !actor.can_access(space.user_id) {
+        return Err(AppError::NotFound(format!("Space not found: {}", key)));
+    }
+    Ok(space)
+}
+
+/// Delete a space `actor` may see, if it is still at `version` when one is
+/// given. Its files stay where they are, and it can be restored until the
+/// restore window is over.
+pub async fn delete_space_for(
+    db: &DatabaseConnection,
+    key: &str,
+    actor: Actor,
+    version: Option<i32>,
+) -> Result<space::Model, AppError> {
+    let space = find_space_for(db, key, actor).await?;
+    let changes = space::ActiveModel {
+        deleted_at: Set(Some(Utc::now().into())),
+        ..Default::default()
+    };
+    let space = update_versioned::<Space>(db, space.id, version, changes).await?;
+    info!(target: AUDIT, "{} deleted space {}", actor, space.key);
+    Ok(space)
+}
+
+/// Bring back a space `actor` deleted no earlier than `since`, if it is
+/// still at `version` when one is given
+pub async fn restore_space_for(
+    db: &DatabaseConnection,
+    key: &str,
+    actor: Actor,
+    since: DateTime<Utc>,
+    version: Option<i32>,
+) -> Result<space::Model, AppError> {
+    let space = deleted::<Space>()
+        .filter(space::Column::Key.eq(key))
+        .filter(space::Column::DeletedAt.gte(since))
+        .one(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .filter(|space| actor.can_access(space.user_id))
+        .ok_or_else(|| AppError::NotFound(format!("No restorable space {}", key)))?;
+    let changes = space::ActiveModel {
+        deleted_at: Set(None),
+        ..Default::default()
+    };
+    let space = update_versioned::<Space>(db, space.id, version, changes).await?;
+    info!(target: AUDIT, "{} restored space {}", actor, space.key);
+    Ok(space)
+}
+
+/// Files a scan found changed since a space was last indexed, by path
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SpaceScan {
+    pub created: Vec<String>,
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+    pub merkle_root: String,
+}
+
+impl SpaceScan {
+    /// Changes from the files `before` to the files `after`
+    pub fn between(before: &[ManifestEntry], after: &[ManifestEntry], merkle_root: String) -> Self {
+        let known: HashMap<&str, &str> = before
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.blake3.as_str()))
+            .collect();
+        let mut scan = SpaceScan {
+            merkle_root,
+            ..Default::default()
+        };
+        for entry in after {
+            match known.get(entry.path.as_str()) {
+                None => scan.created.push(entry.path.clone()),
+                Some(hash) if *hash != entry.blake3 => scan.modified.push(entry.path.clone()),
+                Some(_) => {}
+            }
         }
-        Err(e) => Err(AppError::Storage(Box::new(e))),
+        let found: HashSet<&str> = after.iter().map(|entry| entry.path.as_str()).collect();
+        scan.deleted = before
+            .iter()
+            .filter(|entry| !found.contains(entry.path.as_str()))
+            .map(|entry| entry.path.clone())
+            .collect();
+        for paths in [&mut scan.created, &mut scan.modified, &mut scan.deleted] {
+            paths.sort();
+        }
+        scan
     }
 }
 
+/// Where user `user_id` asked for a space at `dir`: a path relative to their
+/// spaces directory, or an absolute one within it. Anywhere else, symlinks
+/// followed, is refused.
+fn user_space_dir(
+    spaces_root: Option<&Path>,
+    user_id: i32,
+    dir: &str,
+) -> Result<PathBuf, AppError> {
+    let spaces_root = spaces_root.ok_or_else(|| {
+        AppError::Forbidden("No spaces directory is set up for users".to_string())
+    })?;
+    let user_root = layout::user_spaces(spaces_root, user_id);
+    fs::create_dir_all(&user_root)?;
+    let user_root = user_root.canonicalize()?;
+
+    let requested = Path::new(dir);
+    let relative = requested.strip_prefix(&user_root).unwrap_or(requested);
+    let path = user_root.join(relative);
+    if relative.as_os_str().is_empty()
+        || !is_plain_relative(relative)
+        || !resolves_within(&user_root, &path)
+    {
+        return Err(AppError::Forbidden(format!(
+            "Spaces must be within your spaces directory: {}",
+            dir
+        )));
+    }
+    Ok(path)
+}
+
+/// Whether `path`, symlinks followed, is within the canonical `root`. Of a
+/// path not there yet, its nearest existing ancestor must be.
+pub(crate) fn resolves_within(root: &Path, path: &Path) -> bool {
+    let mut existing = path;
+    while existing.symlink_metadata().is_err() {
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return false,
+        }
+    }
+    existing
+        .canonicalize()
+        .is_ok_and(|existing| existing.starts_with(root))
+}
+
+/// Path of a file inside a space. `sub_path` must stay within the space and
+/// name a file, not the space root, and may not lead out of it through a
+/// symlink.
+pub fn resolve_file(space_root: &Path, sub_path: &str) -> Result<PathBuf, AppError> {
+    let relative = Path::new(sub_path.trim_start_matches('/'));
+    if !is_plain_relative(relative) || relative.file_name().is_none() {
+        return Err(AppError::Validation(format!(
+            "Invalid file path within space: {}",
+            sub_path
+        )));
+    }
+
+    let file = space_root.join(relative);
+    if !resolves_within(&space_root.canonicalize()?, &file) {
+        return Err(AppError::Forbidden(format!(
+            "File path leads outside the space: {}",
+            sub_path
+        )));
+    }
+    if file.is_dir() {
+        return Err(AppError::Validation(format!(
+            "Path is a directory: {}",
+            sub_path
+        )));
+    }
+    Ok(file)
+}
+
+/// All spaces, oldest first
+pub async fn list_spaces(db: &DatabaseConnection) -> Result<Vec<space::Model>, AppError> {
+    list_spaces_for(db, Actor::Node).await
+}
+
+/// Spaces `actor` may see, oldest first
+pub async fn list_spaces_for(
+    db: &DatabaseConnection,
+    actor: Actor,
+) -> Result<Vec<space::Model>, AppError> {
+    let mut query = live::<Space>();
+    if let Actor::User(id) = actor {
+        query = query.filter(space::Column::UserId.eq(id));
+    }
+    query
+        .order_by_asc(space::Column::Id)
+        .all(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))
+}
+
 fn generate_space_key(dir: &str) -> Result<String, AppError> {
     let path = Path::new(dir).canonicalize().map_err(|e| AppError::IO(e))?;
 
@@ -89,6 +457,98 @@ fn generate_space_key(dir: &str) -> Result<String, AppError> {
     Ok(format!("{:x}", hash))
 }
 
+/// Times a read is retried before giving up on a consistent snapshot
+const SNAPSHOT_ATTEMPTS: u32 = 10;
+const SNAPSHOT_BACKOFF: Duration = Duration::from_millis(25);
+
+/// Writes in flight to each space, and how many have finished.
+///
+/// Walking a space takes a while, so a write landing midway leaves the walk
+/// with part old and part new contents. Readers note the generation before
+/// walking and retry if a write was running or finished in the meantime.
+#[derive(Debug, Clone, Default)]
+pub struct SpaceWrites {
+    spaces: Arc<Mutex<HashMap<String, WriteState>>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct WriteState {
+    active: u32,
+    generation: u64,
+}
+
+/// A write to a space; it ends when dropped
+#[derive(Debug)]
+pub struct SpaceWrite {
+    writes: SpaceWrites,
+    key: String,
+}
+
+impl SpaceWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark a write to `key` as running until the returned guard drops
+    pub fn begin(&self, key: &str) -> SpaceWrite {
+        self.update(key, |state| state.active += 1);
+        SpaceWrite {
+            writes: self.clone(),
+            key: key.to_string(),
+        }
+    }
+
+    /// Number of writes to `key` that have finished
+    pub fn generation(&self, key: &str) -> u64 {
+        self.state(key).generation
+    }
+
+    /// Run `read` until no write to `key` overlaps it, or fail with
+    /// [`AppError::Conflict`] if the space keeps changing
+    pub fn read_consistent<T>(
+        &self,
+        key: &str,
+        mut read: impl FnMut() -> Result<T, AppError>,
+    ) -> Result<T, AppError> {
+        for attempt in 1..=SNAPSHOT_ATTEMPTS {
+            let before = self.state(key);
+            if before.active == 0 {
+                let value = read()?;
+                if self.state(key) == before {
+                    return Ok(value);
+                }
+            }
+            std::thread::sleep(SNAPSHOT_BACKOFF * attempt);
+        }
+        warn!("Space {} kept changing while it was being read", key);
+        Err(AppError::Conflict(format!(
+            "Space {} is being written to, try again shortly",
+            key
+        )))
+    }
+
+    fn state(&self, key: &str) -> WriteState {
+        self.lock().get(key).copied().unwrap_or_default()
+    }
+
+    fn update(&self, key: &str, f: impl FnOnce(&mut WriteState)) {
+        f(self.lock().entry(key.to_string()).or_default());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, WriteState>> {
+        self.spaces.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for SpaceWrite {
+    fn drop(&mut self) {
+        self.writes.update(&self.key, |state| {
+            state.active -= 1;
+            state.generation += 1;
+        });
+    }
+}
+
 //////////////////////////////////////////////////////////////////////////
 //////////////////////////////////////////////////////////////////////////
 
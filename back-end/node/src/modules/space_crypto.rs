@@ -0,0 +1,339 @@
+//! Encryption at rest for files in encrypted spaces.
+//!
+//! Each encrypted space has its own random 256-bit key, kept in the secrets
+//! vault under the space key, so it is sealed by the node's column keys and
+//! ultimately by the node identity. Files are written as
+//!
+//! ```text
+//! FLOWENC1 || nonce prefix (19 bytes) || chunk || chunk || ...
+//! ```
+//!
+//! where every chunk is up to [`CHUNK_BYTES`] of plaintext sealed with
+//! XChaCha20-Poly1305 (the STREAM construction: the nonce is the prefix, the
+//! chunk's index and a last-chunk flag). The associated data names the space
+//! and the file's path in it, so a file moved or copied elsewhere won't open,
+//! and the last-chunk flag catches truncation. Files stream through in
+//! chunks, so large uploads are never held in memory whole.
+//!
+//! Turning encryption on encrypts the files already in the space, so every
+//! file of an encrypted space carries the header. One without it was put
+//! there behind the node's back and is refused, never read as plaintext.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use errors::AppError;
+use rand::RngCore;
+use tempfile::NamedTempFile;
+use zeroize::Zeroizing;
+
+/// Leads every encrypted file
+pub const MAGIC: &[u8; 8] = b"FLOWENC1";
+/// Plaintext bytes per chunk
+pub const CHUNK_BYTES: usize = 64 * 1024;
+const NONCE_PREFIX_LEN: usize = 19;
+const TAG_LEN: usize = 16;
+
+/// Encrypts and decrypts the files of one space
+pub struct SpaceCipher {
+    space_key: String,
+    cipher: XChaCha20Poly1305,
+}
+
+impl SpaceCipher {
+    pub fn new(space_key: &str, key: &[u8; 32]) -> Self {
+        Self {
+            space_key: space_key.to_string(),
+            cipher: XChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    /// A fresh random space key
+    pub fn generate_key() -> Zeroizing<[u8; 32]> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut());
+        key
+    }
+
+    /// Encrypt everything `plaintext` yields into `out`, as the file at
+    /// `path` in the space
+    pub fn encrypt(
+        &self,
+        path: &str,
+        mut plaintext: impl Read,
+        mut out: impl Write,
+    ) -> Result<(), AppError> {
+        let mut prefix = [0u8; NONCE_PREFIX_LEN];
+        OsRng.fill_bytes(&mut prefix);
+        out.write_all(MAGIC)?;
+        out.write_all(&prefix)?;
+
+        let aad = self.associated_data(path);
+        let mut chunk = Zeroizing::new(vec![0u8; CHUNK_BYTES]);
+        let mut index = 0u32;
+        loop {
+            let len = read_full(&mut plaintext, &mut chunk)?;
+            // A final chunk is always shorter than a full one, empty if need be
+            let last = len < CHUNK_BYTES;
+            let sealed = self
+                .cipher
+                .encrypt(
+                    &nonce(&prefix, index, last),
+                    Payload {
+                        msg: &chunk[..len],
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| AppError::Crypto("Failed to encrypt file".to_string()))?;
+            out.write_all(&sealed)?;
+            if last {
+                return Ok(());
+            }
+            index = next_index(index)?;
+        }
+    }
+
+    /// Decrypt `ciphertext`, written by [`SpaceCipher::encrypt`] for `path`,
+    /// into `out`
+    pub fn decrypt(
+        &self,
+        path: &str,
+        ciphertext: impl Read,
+        mut out: impl Write,
+    ) -> Result<(), AppError> {
+        let mut plaintext = self.decrypting(path, ciphertext)?;
+        io::copy(&mut plaintext, &mut out).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => AppError::Crypto(e.to_string()),
+            _ => AppError::IO(e),
+        })?;
+        Ok(())
+    }
+
+    /// Reader of the plaintext of `ciphertext`, written by
+    /// [`SpaceCipher::encrypt`] for `path`. A chunk that won't open fails the
+    /// read with [`io::ErrorKind::InvalidData`].
+    pub fn decrypting<R: Read>(
+        &self,
+        path: &str,
+        mut ciphertext: R,
+    ) -> Result<Decrypting<'_, R>, AppError> {
+        let mut header = [0u8; MAGIC.len() + NONCE_PREFIX_LEN];
+        if read_full(&mut ciphertext, &mut header)? < header.len() || &header[..8] != MAGIC {
+            return Err(AppError::Crypto(corrupt(path)));
+        }
+        let prefix = header[8..]
+            .try_into()
+            .map_err(|_| AppError::Crypto(corrupt(path)))?;
+
+        Ok(Decrypting {
+            cipher: self,
+            ciphertext,
+            path: path.to_string(),
+            aad: self.associated_data(path),
+            prefix,
+            index: 0,
+            chunk: vec![0u8; CHUNK_BYTES + TAG_LEN],
+            opened: Zeroizing::new(Vec::new()),
+            taken: 0,
+            finished: false,
+        })
+    }
+
+    /// Encrypt `plaintext` into `dest`, replacing it only once complete
+    pub fn encrypt_to(
+        &self,
+        path: &str,
+        plaintext: impl Read,
+        dest: &Path,
+    ) -> Result<(), AppError> {
+        let dir = dest.parent().unwrap_or(Path::new("."));
+        let mut file = NamedTempFile::new_in(dir)?;
+        let mut writer = io::BufWriter::new(file.as_file_mut());
+        self.encrypt(path, plaintext, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        file.as_file().sync_all()?;
+        file.persist(dest).map_err(|e| AppError::IO(e.error))?;
+        Ok(())
+    }
+
+    /// Decrypt the file at `file`, which is at `path` in the space, into
+    /// `out`. A file that isn't encrypted is refused.
+    pub fn decrypt_file(&self, path: &str, file: &Path, out: impl Write) -> Result<(), AppError> {
+        if !is_encrypted(file)? {
+            return Err(AppError::Crypto(format!(
+                "{} is not encrypted, so it wasn't written by the node",
+                path
+            )));
+        }
+        self.decrypt(path, io::BufReader::new(fs::File::open(file)?), out)
+    }
+
+    /// Reader of the plaintext of the file at `file`, which is at `path` in
+    /// the space. A file that isn't encrypted is refused.
+    pub fn open_file(
+        &self,
+        path: &str,
+        file: &Path,
+    ) -> Result<Decrypting<'_, io::BufReader<fs::File>>, AppError> {
+        if !is_encrypted(file)? {
+            return Err(AppError::Crypto(format!(
+                "{} is not encrypted, so it wasn't written by the node",
+                path
+            )));
+        }
+        self.decrypting(path, io::BufReader::new(fs::File::open(file)?))
+    }
+
+    /// Decrypted contents of the file at `file`, which is at `path` in the
+    /// space
+    pub fn read(&self, path: &str, file: &Path) -> Result<Vec<u8>, AppError> {
+        let mut plaintext = Vec::new();
+        self.decrypt_file(path, file, &mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    /// Encrypt in place every file under `space_root` that isn't yet, as
+    /// when encryption is turned on. Symlinks are skipped. Returns how many
+    /// files were encrypted.
+    pub fn encrypt_existing(&self, space_root: &Path) -> Result<u64, AppError> {
+        self.encrypt_dir(space_root, space_root)
+    }
+
+    fn encrypt_dir(&self, root: &Path, dir: &Path) -> Result<u64, AppError> {
+        let mut encrypted = 0;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                encrypted += self.encrypt_dir(root, &file)?;
+            } else if file_type.is_file() && !is_encrypted(&file)? {
+                let path = file
+                    .strip_prefix(root)
+                    .map_err(|e| AppError::IO(io::Error::other(e)))?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                self.encrypt_to(&path, io::BufReader::new(fs::File::open(&file)?), &file)?;
+                encrypted += 1;
+            }
+        }
+        Ok(encrypted)
+    }
+
+    fn associated_data(&self, path: &str) -> Vec<u8> {
+        let path = path.trim_start_matches('/');
+        let mut aad = Vec::with_capacity(self.space_key.len() + 1 + path.len());
+        aad.extend_from_slice(self.space_key.as_bytes());
+        aad.push(0);
+        aad.extend_from_slice(path.as_bytes());
+        aad
+    }
+}
+
+/// Plaintext of an encrypted file, opened a chunk at a time
+pub struct Decrypting<'a, R> {
+    cipher: &'a SpaceCipher,
+    ciphertext: R,
+    path: String,
+    aad: Vec<u8>,
+    prefix: [u8; NONCE_PREFIX_LEN],
+    index: u32,
+    chunk: Vec<u8>,
+    /// The chunk being read out, and how much of it has been
+    opened: Zeroizing<Vec<u8>>,
+    taken: usize,
+    finished: bool,
+}
+
+impl<R: Read> Decrypting<'_, R> {
+    fn open_next(&mut self) -> io::Result<()> {
+        let len = read_full(&mut self.ciphertext, &mut self.chunk)?;
+        let last = len < self.chunk.len();
+        let opened = self.cipher.cipher.decrypt(
+            &nonce(&self.prefix, self.index, last),
+            Payload {
+                msg: &self.chunk[..len],
+                aad: &self.aad,
+            },
+        );
+        self.opened = Zeroizing::new(
+            opened.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, corrupt(&self.path)))?,
+        );
+        self.taken = 0;
+        if last {
+            self.finished = true;
+        } else {
+            self.index = next_index(self.index).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decrypting<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.taken == self.opened.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.open_next()?;
+        }
+        let len = buf.len().min(self.opened.len() - self.taken);
+        buf[..len].copy_from_slice(&self.opened[self.taken..self.taken + len]);
+        self.taken += len;
+        Ok(len)
+    }
+}
+
+/// Plaintext length of an encrypted file `ciphertext_len` bytes long, or
+/// `None` if that is too short to be one
+pub fn plaintext_len(ciphertext_len: u64) -> Option<u64> {
+    let sealed = ciphertext_len.checked_sub((MAGIC.len() + NONCE_PREFIX_LEN + TAG_LEN) as u64)?;
+    // Every chunk but the last is full, and the last is shorter than a full one
+    let full_chunks = sealed / (CHUNK_BYTES + TAG_LEN) as u64;
+    Some(sealed - full_chunks * TAG_LEN as u64)
+}
+
+fn corrupt(path: &str) -> String {
+    format!("{} is corrupt or not from this space", path)
+}
+
+/// Whether the file at `file` starts with the encrypted file header
+pub fn is_encrypted(file: &Path) -> Result<bool, AppError> {
+    let mut magic = [0u8; MAGIC.len()];
+    let len = read_full(&mut fs::File::open(file)?, &mut magic)?;
+    Ok(len == magic.len() && &magic == MAGIC)
+}
+
+fn nonce(prefix: &[u8; NONCE_PREFIX_LEN], index: u32, last: bool) -> XNonce {
+    let mut nonce = [0u8; 24];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..23].copy_from_slice(&index.to_be_bytes());
+    nonce[23] = last as u8;
+    nonce.into()
+}
+
+fn next_index(index: u32) -> Result<u32, AppError> {
+    index
+        .checked_add(1)
+        .ok_or_else(|| AppError::PayloadTooLarge("File is too large to encrypt".to_string()))
+}
+
+/// Fill `buf` as far as `reader` allows, returning how much was read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
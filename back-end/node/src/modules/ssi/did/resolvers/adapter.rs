@@ -1,9 +1,15 @@
 use async_trait::async_trait;
 use chrono::Utc;
+use futures_util::stream::{self, Stream, StreamExt};
 use ssi::dids::{AnyDidMethod as SsiResolver, DID, DIDResolver as SsiDIDResolver};
+use std::sync::Arc;
 use std::time::Instant;
 
+use crate::modules::ssi::did::resolvers::cache::{
+    self, CachedResolution, DidCache, MemoryDidCache,
+};
 use crate::modules::ssi::did::resolvers::peer;
+use crate::modules::ssi::did::resolvers::peer::store::{MemoryPeerDidStore, PeerDidStore};
 
 use super::super::types::{
     DocumentMetadata, RegistryProof, ResolutionMetadata, ResolutionOptions, VdrInfo,
@@ -25,16 +31,33 @@ pub enum DidWebError {
     Url(#[from] url::ParseError),
 }
 
+/// DIDs [`DidResolver::resolve_many`] resolves at once unless told otherwise
+pub const DEFAULT_PARALLELISM: usize = 8;
+
+/// Outcome for one DID of a batch
+#[derive(Debug, Clone)]
+pub struct BatchResolution {
+    /// Position of the DID in the batch
+    pub index: usize,
+    pub did: String,
+    pub result: Result<ResolutionResult, ResolutionError>,
+}
+
 /// DID resolver adapter
 ///
 /// Wraps SSI's `AnyDidMethod` resolver with extended features:
 /// - Verifiable Data Registry tracking
 /// - Performance metrics
-/// - Caching metadata
+/// - Result caching (in memory by default)
+/// - did:peer:3 short forms of previously seen did:peer:2 DIDs
 /// - Cryptographic proof collection
 pub struct DidResolver {
     /// SSI's universal DID resolver (supports key, jwk, web, pkh, ethr, ion, tz)
     inner: SsiResolver,
+    cache: Option<Arc<dyn DidCache>>,
+    peer_store: Arc<dyn PeerDidStore>,
+    /// DIDs of a batch resolved at once
+    parallelism: usize,
 }
 
 #[async_trait]
@@ -49,14 +72,45 @@ pub trait DidResolverTrait {
 impl DidResolver {
     /// Create a new resolver with default SSI resolver
     pub fn new() -> Self {
-        Self {
-            inner: SsiResolver::default(),
-        }
+        Self::with_resolver(SsiResolver::default())
     }
 
     /// Create with custom SSI resolver configuration
     pub fn with_resolver(resolver: SsiResolver) -> Self {
-        Self { inner: resolver }
+        Self {
+            inner: resolver,
+            cache: Some(Arc::new(MemoryDidCache::default())),
+            peer_store: Arc::new(MemoryPeerDidStore::default()),
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+
+    /// Cache results in `cache` instead of memory
+    pub fn with_cache(mut self, cache: Arc<dyn DidCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Resolve every DID afresh
+    pub fn without_cache(mut self) -> Self {
+        self.cache = None;
+        self
+    }
+
+    /// Record did:peer:2 short forms in `store` instead of memory
+    pub fn with_peer_store(mut self, store: Arc<dyn PeerDidStore>) -> Self {
+        self.peer_store = store;
+        self
+    }
+
+    /// Resolve at most `parallelism` DIDs of a batch at once
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    pub fn parallelism(&self) -> usize {
+        self.parallelism
     }
 
     /// Convert our options to SSI options
@@ -70,7 +124,6 @@ impl DidResolver {
         did: &str,
         ssi_output: ssi::dids::resolution::Output,
         duration_ms: u64,
-        options: &ResolutionOptions,
     ) -> ResolutionResult {
         let method = did.split(':').nth(1).unwrap_or("unknown");
 
@@ -83,7 +136,7 @@ impl DidResolver {
             error: None,
             verifiable_data_registry: Self::build_vdr_info(method, did),
             duration: Some(duration_ms),
-            from_cache: options.no_cache.map(|nc| !nc),
+            from_cache: Some(false),
             cache_ttl: Self::suggested_cache_ttl(method),
             resolved_at: Some(Utc::now()),
             did_method: Some(method.to_string()),
@@ -244,16 +297,84 @@ impl DidResolver {
         }
     }
 
-    /// Resolve a DID using SSI's resolver with enhancements
+    /// Resolve a DID using SSI's resolver with enhancements. A fresh cached
+    /// result is returned without resolving, unless `options.no_cache` is set.
     pub async fn resolve_did(
         &self,
         did: &str,
         options: &ResolutionOptions,
+    ) -> Result<ResolutionResult, ResolutionError> {
+        let key = cache::cache_key(did, options);
+        if let Some(cache) = &self.cache
+            && options.no_cache != Some(true)
+            && let Some(entry) = cache.get(&key)
+        {
+            if entry.is_fresh(Utc::now()) {
+                return Ok(entry.into_result());
+            }
+            cache.remove(&key);
+        }
+
+        let result = self.resolve_uncached(did, options).await?;
+        if let Some(cache) = &self.cache
+            && let Some(entry) = CachedResolution::from_result(&result, Utc::now())
+        {
+            cache.put(&key, entry);
+        }
+        Ok(result)
+    }
+
+    /// Resolve each of `dids`, [`Self::parallelism`] at a time. One DID
+    /// failing doesn't stop the others; results come in the order of `dids`.
+    pub async fn resolve_many(
+        &self,
+        dids: &[&str],
+        options: &ResolutionOptions,
+    ) -> Vec<BatchResolution> {
+        stream::iter(dids.iter().enumerate())
+            .map(|(index, did)| async move {
+                BatchResolution {
+                    index,
+                    did: did.to_string(),
+                    result: self.resolve_did(did, options).await,
+                }
+            })
+            .buffered(self.parallelism)
+            .collect()
+            .await
+    }
+
+    /// Like [`Self::resolve_many`], but yielding each result as soon as it
+    /// is ready, so in no particular order
+    pub fn resolve_stream(
+        self: &Arc<Self>,
+        dids: Vec<String>,
+        options: ResolutionOptions,
+    ) -> impl Stream<Item = BatchResolution> + Send + 'static {
+        let resolver = self.clone();
+        let options = Arc::new(options);
+        stream::iter(dids.into_iter().enumerate())
+            .map(move |(index, did)| {
+                let resolver = resolver.clone();
+                let options = options.clone();
+                async move {
+                    let result = resolver.resolve_did(&did, &options).await;
+                    BatchResolution { index, did, result }
+                }
+            })
+            .buffer_unordered(self.parallelism)
+    }
+
+    async fn resolve_uncached(
+        &self,
+        did: &str,
+        options: &ResolutionOptions,
     ) -> Result<ResolutionResult, ResolutionError> {
         let future = async {
             // If a did:peer - handle locally
             if did.starts_with("did:peer:") {
-                return peer::resolve_peer_did(did, options).await;
+                return peer::resolve_peer_did_with(did, options, Some(self.peer_store.as_ref()))
+                    .await;
             } else {
                 let start = Instant::now();
 
@@ -265,7 +386,7 @@ impl DidResolver {
 
                 let duration_ms = start.elapsed().as_millis() as u64;
 
-                Ok(Self::enrich_result(did, ssi_output, duration_ms, options))
+                Ok(Self::enrich_result(did, ssi_output, duration_ms))
             }
         };
 
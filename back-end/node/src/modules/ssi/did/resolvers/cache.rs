@@ -0,0 +1,262 @@
+//! Caching of DID resolution results.
+//!
+//! Entries are keyed by DID and requested media type and expire after the
+//! method's suggested TTL; methods without one (key, jwk, peer) are
+//! deterministic and never expire. Only successful resolutions are cached.
+//! The cache is best-effort: a storage failure is logged and treated as a
+//! miss.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use ssi::dids::Document as DIDDocument;
+
+use super::super::types::{DocumentMetadata, ResolutionMetadata, ResolutionOptions};
+use super::types::ResolutionResult;
+
+/// Entries the in-memory cache holds before evicting the least recently used
+pub const DEFAULT_CAPACITY: usize = 256;
+/// Tree of the KV-backed cache
+pub const CACHE_TREE: &str = "did_cache";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedResolution {
+    pub document: DIDDocument,
+    pub resolution_metadata: ResolutionMetadata,
+    pub document_metadata: DocumentMetadata,
+    /// `None` for deterministic documents
+    pub expires_at: Option<DateTime<Utc>>,
+    /// `None` for entries cached before this was recorded
+    #[serde(default)]
+    pub cached_at: Option<DateTime<Utc>>,
+}
+
+impl CachedResolution {
+    /// Cache entry for a successful result, expiring after its `cache_ttl`
+    pub fn from_result(result: &ResolutionResult, now: DateTime<Utc>) -> Option<Self> {
+        let document = result.did_document.clone()?;
+        if !result.is_success() {
+            return None;
+        }
+        let expires_at = result
+            .did_resolution_metadata
+            .cache_ttl
+            .map(|ttl| now + Duration::seconds(ttl as i64));
+
+        Some(Self {
+            document,
+            resolution_metadata: result.did_resolution_metadata.clone(),
+            document_metadata: result.did_document_metadata.clone(),
+            expires_at,
+            cached_at: Some(now),
+        })
+    }
+
+    pub fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    /// The cached result, marked as served from cache
+    pub fn into_result(self) -> ResolutionResult {
+        let mut resolution_metadata = self.resolution_metadata;
+        resolution_metadata.from_cache = Some(true);
+        ResolutionResult {
+            did_document: Some(self.document),
+            did_resolution_metadata: resolution_metadata,
+            did_document_metadata: self.document_metadata,
+        }
+    }
+}
+
+/// Key for `did` resolved with `options`: the same DID in another
+/// representation is a separate entry
+pub fn cache_key(did: &str, options: &ResolutionOptions) -> String {
+    match &options.standard.accept {
+        Some(accept) => format!("{}#{}", did, accept),
+        None => did.to_string(),
+    }
+}
+
+/// Storage for cached resolutions
+pub trait DidCache: Send + Sync {
+    fn get(&self, key: &str) -> Option<CachedResolution>;
+
+    fn put(&self, key: &str, entry: CachedResolution);
+
+    fn remove(&self, key: &str);
+}
+
+/// In-memory cache evicting the least recently used entry when full
+pub struct MemoryDidCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Default)]
+struct LruState {
+    /// Entry and the tick it was last used at
+    entries: HashMap<String, (CachedResolution, u64)>,
+    tick: u64,
+}
+
+impl MemoryDidCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(LruState::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for MemoryDidCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl DidCache for MemoryDidCache {
+    fn get(&self, key: &str) -> Option<CachedResolution> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.get_mut(key).map(|(entry, used)| {
+            *used = tick;
+            entry.clone()
+        })
+    }
+
+    fn put(&self, key: &str, entry: CachedResolution) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+
+        if !state.entries.contains_key(key) && state.entries.len() >= self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(key.to_string(), (entry, tick));
+    }
+
+    fn remove(&self, key: &str) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .remove(key);
+    }
+}
+
+/// Cache in the node's KV store, surviving restarts
+pub struct KvDidCache {
+    tree: sled::Tree,
+}
+
+impl KvDidCache {
+    pub fn open(kv: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            tree: kv.open_tree(CACHE_TREE)?,
+        })
+    }
+}
+
+impl KvDidCache {
+    /// Remove entries that have expired or were cached before `before`,
+    /// returning how many. With `dry_run` they are only counted.
+    pub fn purge(
+        &self,
+        before: DateTime<Utc>,
+        now: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<u64, sled::Error> {
+        let mut purged = 0;
+        for item in self.tree.iter() {
+            let (key, value) = item?;
+            let stale = match serde_json::from_slice::<CachedResolution>(&value) {
+                Ok(entry) => !entry.is_fresh(now) || entry.cached_at.is_none_or(|at| at < before),
+                Err(_) => true,
+            };
+            if stale {
+                if !dry_run {
+                    self.tree.remove(key)?;
+                }
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Remove every entry for `did`, in any representation, returning how
+    /// many
+    pub fn forget(&self, did: &str) -> Result<u64, sled::Error> {
+        let mut forgotten = 0;
+        for key in self.tree.scan_prefix(did.as_bytes()).keys() {
+            let key = key?;
+            if key.len() == did.len() || key.get(did.len()) == Some(&b'#') {
+                self.tree.remove(key)?;
+                forgotten += 1;
+            }
+        }
+        Ok(forgotten)
+    }
+}
+
+impl DidCache for KvDidCache {
+    fn get(&self, key: &str) -> Option<CachedResolution> {
+        let bytes = match self.tree.get(key.as_bytes()) {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                warn!("DID cache read failed for {}: {}", key, e);
+                return None;
+            }
+        };
+        match serde_json::from_slice(&bytes) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Dropping unreadable DID cache entry for {}: {}", key, e);
+                self.remove(key);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, entry: CachedResolution) {
+        let stored = serde_json::to_vec(&entry)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                self.tree
+                    .insert(key.as_bytes(), bytes)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = stored {
+            warn!("DID cache write failed for {}: {}", key, e);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        if let Err(e) = self.tree.remove(key.as_bytes()) {
+            warn!("DID cache removal failed for {}: {}", key, e);
+        }
+    }
+}
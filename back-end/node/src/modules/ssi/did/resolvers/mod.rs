@@ -1,6 +1,7 @@
 pub mod adapter;
+pub mod cache;
 pub mod peer;
 pub mod types;
 
-pub use adapter::DidResolver;
+pub use adapter::{BatchResolution, DidResolver};
 pub use types::{ResolutionError, ResolutionResult};
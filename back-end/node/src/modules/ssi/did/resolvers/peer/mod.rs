@@ -1,11 +1,14 @@
-mod document;
 mod error;
 pub mod generator;
-mod parser;
+pub mod store;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategies;
 
-use document::create_did_document;
 pub use error::PeerDidError;
-use parser::ParsedPeerDid;
+use generator::PeerDidGenerator;
+pub use identity::peer::peer_did_document;
+use identity::peer::short_form_document;
+use store::PeerDidStore;
 
 use crate::modules::ssi::did::resolvers::types::{ResolutionError, ResolutionResult};
 use crate::modules::ssi::did::types::{
@@ -14,17 +17,44 @@ use crate::modules::ssi::did::types::{
 use chrono::Utc;
 
 /// Resolve a did:peer DID
+///
+/// A did:peer:3 needs the did:peer:2 it hashes, so resolves only through
+/// [`resolve_peer_did_with`].
 pub async fn resolve_peer_did(
+    did: &str,
+    options: &ResolutionOptions,
+) -> Result<ResolutionResult, ResolutionError> {
+    resolve_peer_did_with(did, options, None).await
+}
+
+/// Resolve a did:peer DID, looking up short forms in `store` and recording
+/// the short form of every did:peer:2 resolved
+pub async fn resolve_peer_did_with(
     did: &str,
     _options: &ResolutionOptions,
+    store: Option<&dyn PeerDidStore>,
 ) -> Result<ResolutionResult, ResolutionError> {
     let start = std::time::Instant::now();
 
-    // Parse the did:peer
-    let parsed = ParsedPeerDid::parse(did)?;
-
-    // Create DID Document
-    let document = create_did_document(did, parsed)?;
+    let mut did_document_metadata = DocumentMetadata::default();
+    let (document, registry_version) = if did.starts_with("did:peer:3") {
+        // The document of the long form, under the short form's id
+        let long_form = store
+            .and_then(|store| store.get(did))
+            .ok_or_else(|| PeerDidError::UnknownShortForm(did.to_string()))?;
+        let document = short_form_document(did, &long_form)?;
+        did_document_metadata.equivalent_id = Some(vec![long_form]);
+        (document, "did:peer:3")
+    } else {
+        // Parse the did:peer and create its DID Document
+        let document = peer_did_document(did)?;
+        if let Some(store) = store
+            && did.starts_with("did:peer:2")
+        {
+            store.put(&PeerDidGenerator::to_numalgo3(did)?, did);
+        }
+        (document, "did:peer:2")
+    };
 
     let duration_ms = start.elapsed().as_millis() as u64;
 
@@ -42,7 +72,7 @@ pub async fn resolve_peer_did(
                 public_key_id: did.to_string(),
                 signed_data: did.to_string(),
             }),
-            registry_version: Some("did:peer:2".to_string()),
+            registry_version: Some(registry_version.to_string()),
         }),
         duration: Some(duration_ms),
         from_cache: Some(false),
@@ -55,6 +85,6 @@ pub async fn resolve_peer_did(
     Ok(ResolutionResult {
         did_document: Some(document),
         did_resolution_metadata,
-        did_document_metadata: DocumentMetadata::default(),
+        did_document_metadata,
     })
 }
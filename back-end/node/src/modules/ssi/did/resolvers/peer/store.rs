@@ -0,0 +1,79 @@
+//! Long-form DIDs behind did:peer:3 short forms.
+//!
+//! A numalgo 3 DID is only a hash, so it resolves only if the numalgo 2 DID
+//! it was derived from has been seen before. Every numalgo 2 DID resolved is
+//! recorded here under its short form. Like the resolution cache, the store
+//! is best-effort: a storage failure is logged and treated as a miss.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use log::warn;
+
+/// Tree of the KV-backed store
+pub const PEER_DID_TREE: &str = "peer_dids";
+
+/// Storage mapping did:peer:3 DIDs to the did:peer:2 DIDs they hash
+pub trait PeerDidStore: Send + Sync {
+    fn get(&self, short_form: &str) -> Option<String>;
+
+    fn put(&self, short_form: &str, long_form: &str);
+}
+
+/// In-memory store, forgotten on restart
+#[derive(Default)]
+pub struct MemoryPeerDidStore {
+    entries: RwLock<HashMap<String, String>>,
+}
+
+impl PeerDidStore for MemoryPeerDidStore {
+    fn get(&self, short_form: &str) -> Option<String> {
+        self.entries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(short_form)
+            .cloned()
+    }
+
+    fn put(&self, short_form: &str, long_form: &str) {
+        self.entries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(short_form.to_string(), long_form.to_string());
+    }
+}
+
+/// Store in the node's KV store, surviving restarts
+pub struct KvPeerDidStore {
+    tree: sled::Tree,
+}
+
+impl KvPeerDidStore {
+    pub fn open(kv: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            tree: kv.open_tree(PEER_DID_TREE)?,
+        })
+    }
+}
+
+impl PeerDidStore for KvPeerDidStore {
+    fn get(&self, short_form: &str) -> Option<String> {
+        let bytes = match self.tree.get(short_form.as_bytes()) {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                warn!("Peer DID store read failed for {}: {}", short_form, e);
+                return None;
+            }
+        };
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
+    fn put(&self, short_form: &str, long_form: &str) {
+        if let Err(e) = self
+            .tree
+            .insert(short_form.as_bytes(), long_form.as_bytes())
+        {
+            warn!("Peer DID store write failed for {}: {}", short_form, e);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Proptest strategies for did:peer identifiers.
+//!
+//! Available to downstream crates through the `proptest` feature.
+
+use base64::Engine;
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use super::generator::PeerDidGenerator;
+
+/// A service entry to embed in a did:peer:2
+#[derive(Debug, Clone)]
+pub struct PeerServiceSpec {
+    pub service_type: String,
+    pub endpoint: String,
+}
+
+/// Keys and services making up a did:peer:2
+#[derive(Debug, Clone)]
+pub struct PeerDidSpec {
+    /// Ed25519 keys (`E` transform)
+    pub verification_keys: Vec<[u8; 32]>,
+    /// X25519 keys (`V` transform)
+    pub encryption_keys: Vec<[u8; 32]>,
+    pub services: Vec<PeerServiceSpec>,
+}
+
+impl PeerDidSpec {
+    /// Encode the spec as a did:peer:2 string
+    pub fn to_did(&self) -> String {
+        let mut did = PeerDidGenerator::generate_numalgo2(
+            self.verification_keys.iter().map(|k| k.to_vec()).collect(),
+            self.encryption_keys.iter().map(|k| k.to_vec()).collect(),
+        )
+        .expect("Generating did:peer:2 from 32 byte keys cannot fail");
+
+        for service in &self.services {
+            let json = serde_json::json!({
+                "t": service.service_type,
+                "s": service.endpoint,
+            });
+            let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json.to_string());
+            did.push_str(&format!(".S{}", encoded));
+        }
+
+        did
+    }
+}
+
+/// Raw 32 byte public key
+pub fn key_bytes() -> impl Strategy<Value = [u8; 32]> {
+    any::<[u8; 32]>()
+}
+
+/// Service with a plain HTTPS endpoint
+pub fn service_spec() -> impl Strategy<Value = PeerServiceSpec> {
+    (
+        "[A-Z][A-Za-z]{0,15}",
+        "https://[a-z]{1,12}\\.example/[a-z0-9]{0,8}",
+    )
+        .prop_map(|(service_type, endpoint)| PeerServiceSpec {
+            service_type,
+            endpoint,
+        })
+}
+
+/// A valid did:peer:2 spec with at least one verification key
+pub fn peer_did_spec() -> impl Strategy<Value = PeerDidSpec> {
+    (
+        vec(key_bytes(), 1..4),
+        vec(key_bytes(), 0..3),
+        vec(service_spec(), 0..3),
+    )
+        .prop_map(
+            |(verification_keys, encryption_keys, services)| PeerDidSpec {
+                verification_keys,
+                encryption_keys,
+                services,
+            },
+        )
+}
+
+/// An Ed25519 key together with its did:peer:0
+pub fn numalgo0_ed25519() -> impl Strategy<Value = ([u8; 32], String)> {
+    key_bytes().prop_map(|key| {
+        let did = PeerDidGenerator::from_ed25519_bytes(&key)
+            .expect("Generating did:peer:0 from a 32 byte key cannot fail");
+        (key, did)
+    })
+}
+
+/// Strings that look more or less like did:peer identifiers, for fuzzing
+pub fn arbitrary_peer_did() -> impl Strategy<Value = String> {
+    let segment = ("[EVASX\u{e9}]", any::<String>()).prop_map(|(t, v)| format!(".{}{}", t, v));
+
+    prop_oneof![
+        any::<String>(),
+        any::<String>().prop_map(|s| format!("did:peer:{}", s)),
+        any::<String>().prop_map(|s| format!("did:peer:0{}", s)),
+        vec(segment, 0..4).prop_map(|parts| format!("did:peer:2{}", parts.concat())),
+        vec(any::<u8>(), 0..80).prop_map(|bytes| format!(
+            "did:peer:0{}",
+            multibase::encode(multibase::Base::Base58Btc, bytes)
+        )),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::peer_did_document;
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn multibase_key(prefix: u8, key: &[u8]) -> String {
+        let mut multicodec_key = vec![prefix, 0x01];
+        multicodec_key.extend_from_slice(key);
+        multibase::encode(multibase::Base::Base58Btc, &multicodec_key)
+    }
+
+    fn resolve(did: &str) -> Value {
+        let document = peer_did_document(did).expect("Generated DID should resolve");
+        serde_json::to_value(document).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn numalgo2_round_trip(spec in peer_did_spec()) {
+            let did = spec.to_did();
+            let document = resolve(&did);
+
+            let expected_methods: Vec<Value> = spec
+                .verification_keys
+                .iter()
+                .map(|k| ("Ed25519VerificationKey2020", multibase_key(0xed, k)))
+                .chain(
+                    spec.encryption_keys
+                        .iter()
+                        .map(|k| ("X25519KeyAgreementKey2020", multibase_key(0xec, k))),
+                )
+                .enumerate()
+                .map(|(idx, (vm_type, key))| {
+                    json!({
+                        "id": format!("{}#key-{}", did, idx + 1),
+                        "type": vm_type,
+                        "controller": did,
+                        "publicKeyMultibase": key,
+                    })
+                })
+                .collect();
+
+            prop_assert_eq!(&document["id"], &json!(did));
+            prop_assert_eq!(&document["verificationMethod"], &json!(expected_methods));
+            prop_assert_eq!(
+                document["authentication"].as_array().map(Vec::len),
+                Some(spec.verification_keys.len())
+            );
+            prop_assert_eq!(
+                document["keyAgreement"].as_array().map(Vec::len).unwrap_or(0),
+                spec.encryption_keys.len()
+            );
+
+            let services = document["service"].as_array().cloned().unwrap_or_default();
+            prop_assert_eq!(services.len(), spec.services.len());
+            for (service, expected) in services.iter().zip(&spec.services) {
+                prop_assert_eq!(&service["type"], &json!(expected.service_type));
+                prop_assert_eq!(&service["serviceEndpoint"], &json!(expected.endpoint));
+            }
+        }
+
+        #[test]
+        fn numalgo0_round_trip((key, did) in numalgo0_ed25519()) {
+            let document = resolve(&did);
+
+            prop_assert_eq!(
+                &document["verificationMethod"][0]["publicKeyMultibase"],
+                &json!(multibase_key(0xed, &key))
+            );
+        }
+
+        #[test]
+        fn parse_never_panics(did in arbitrary_peer_did()) {
+            let _ = peer_did_document(&did);
+        }
+    }
+}
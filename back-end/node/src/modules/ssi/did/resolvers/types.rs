@@ -1,3 +1,4 @@
+use serde::Serialize;
 use ssi::dids::Document as DIDDocument;
 
 use super::super::types::{DocumentMetadata, ResolutionMetadata};
@@ -5,7 +6,8 @@ use super::super::types::{DocumentMetadata, ResolutionMetadata};
 /// W3C DID Resolution result
 /// Spec: resolve(did, resolutionOptions) → « didResolutionMetadata, didDocument, didDocumentMetadata »
 /// See: https://www.w3.org/TR/did-core/#did-resolution
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ResolutionResult {
     /// The DID Document (if resolution was successful)
     pub did_document: Option<DIDDocument>,
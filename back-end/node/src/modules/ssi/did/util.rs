@@ -1,7 +1,8 @@
+use identity::jwk;
 use log::{error, info};
 use ssi::dids::{DIDKey, Document as DIDDocument};
-use ssi::jwk::{JWK, Params as JWKParams};
-use webauthn_rs::prelude::{COSEKey, Passkey};
+use ssi::jwk::JWK;
+use webauthn_rs::prelude::{COSEAlgorithm, COSEKey, Passkey};
 
 use crate::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
 
@@ -32,7 +33,17 @@ pub fn generate_dids_from_passkey(
 pub fn generate_did_peer_from_passkey(
     passkey: &Passkey,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let did = PeerDidGenerator::from_passkey(passkey)?;
+    let cose_key = passkey.get_public_key();
+    let did = match cose_key.type_ {
+        COSEAlgorithm::ES256 => {
+            let (x, y) = extract_ec_coordinates(cose_key)?;
+            PeerDidGenerator::from_p256_coordinates(&x, &y)?
+        }
+        COSEAlgorithm::EDDSA => {
+            PeerDidGenerator::from_ed25519_bytes(&extract_eddsa_public_key(cose_key)?)?
+        }
+        _ => return Err("Unsupported COSE algorithm".into()),
+    };
 
     info!("Generated did:peer: {}", did);
     Ok(did)
@@ -56,58 +67,20 @@ pub fn generate_did_key_from_passkey(
 
 /// Convert COSE key to JWK format
 pub fn cose_to_jwk(cose_key: &COSEKey) -> Result<JWK, Box<dyn std::error::Error>> {
-    use ssi::jwk::{ECParams, OctetParams};
-
     // Extract key type and algorithm from COSE key
     let alg = &cose_key.type_;
 
     // WebAuthn typically uses ES256 (ECDSA with P-256 and SHA-256)
     // COSE algorithm -7 = ES256
     match alg {
-        webauthn_rs::prelude::COSEAlgorithm::ES256 => {
+        COSEAlgorithm::ES256 => {
             // Extract x and y coordinates from COSE key
             let (x, y) = extract_ec_coordinates(cose_key)?;
-
-            let ec_params = ECParams {
-                curve: Some("P-256".to_string()),
-                x_coordinate: Some(ssi::jwk::Base64urlUInt(x)),
-                y_coordinate: Some(ssi::jwk::Base64urlUInt(y)),
-                ecc_private_key: None,
-            };
-
-            Ok(JWK {
-                params: JWKParams::EC(ec_params),
-                public_key_use: Some("sig".to_string()),
-                key_operations: Some(vec!["verify".to_string()]),
-                algorithm: Some(ssi::jwk::Algorithm::ES256),
-                key_id: None,
-                x509_url: None,
-                x509_certificate_chain: None,
-                x509_thumbprint_sha1: None,
-                x509_thumbprint_sha256: None,
-            })
+            Ok(jwk::p256_jwk(x, y))
         }
-        webauthn_rs::prelude::COSEAlgorithm::EDDSA => {
+        COSEAlgorithm::EDDSA => {
             // Extract public key bytes for EdDSA
-            let public_key_bytes = extract_eddsa_public_key(cose_key)?;
-
-            let octet_params = OctetParams {
-                curve: "Ed25519".to_string(),
-                public_key: ssi::jwk::Base64urlUInt(public_key_bytes),
-                private_key: None,
-            };
-
-            Ok(JWK {
-                params: JWKParams::OKP(octet_params),
-                public_key_use: Some("sig".to_string()),
-                key_operations: Some(vec!["verify".to_string()]),
-                algorithm: Some(ssi::jwk::Algorithm::EdDSA),
-                key_id: None,
-                x509_url: None,
-                x509_certificate_chain: None,
-                x509_thumbprint_sha1: None,
-                x509_thumbprint_sha256: None,
-            })
+            Ok(jwk::ed25519_jwk(extract_eddsa_public_key(cose_key)?))
         }
         _ => {
             error!("Unsupported COSE algorithm: {:?}", alg);
@@ -1,2 +1,3 @@
 pub mod did;
+pub mod vc;
 pub mod webauthn;
@@ -0,0 +1,267 @@
+//! W3C Verifiable Credentials (VC Data Model 2.0) issued by the node.
+//!
+//! The node is the issuer: credentials name its `did:key` and are signed with
+//! its Ed25519 identity key. Each credential is handed out twice, secured two
+//! ways: as JSON-LD with a Data Integrity proof ([`proof`]), and as a
+//! `vc+jwt` whose payload is the same credential.
+
+pub mod proof;
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+use errors::AppError;
+use log::info;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::bootstrap::init::NodeData;
+use crate::modules::clock::TimeSource;
+use crate::modules::ssi::vc::proof::DataIntegrityProof;
+use crate::modules::validation;
+use crate::modules::{jws, manifest};
+
+pub const CREDENTIALS_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+pub const VERIFIABLE_CREDENTIAL: &str = "VerifiableCredential";
+/// JOSE `typ` of a JWT-secured credential
+pub const JWT_TYPE: &str = "vc+jwt";
+
+const AUDIT: &str = "audit";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiableCredential {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    pub issuer: String,
+    pub valid_from: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<String>,
+    /// The subject's `id` and the claims made about it
+    pub credential_subject: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<DataIntegrityProof>,
+}
+
+impl VerifiableCredential {
+    pub fn subject(&self) -> Option<&str> {
+        self.credential_subject.get("id").and_then(Value::as_str)
+    }
+}
+
+/// What to issue a credential about
+#[derive(Debug, Clone, Default)]
+pub struct CredentialRequest {
+    /// DID of the subject
+    pub subject: String,
+    pub claims: Map<String, Value>,
+    /// Types besides `VerifiableCredential`
+    pub types: Vec<String>,
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+/// The same credential, secured both ways
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuedCredential {
+    pub credential: VerifiableCredential,
+    pub jwt: String,
+}
+
+/// Issue and sign a credential as the node
+pub fn issue(
+    node_data: &NodeData,
+    request: CredentialRequest,
+) -> Result<IssuedCredential, AppError> {
+    validation::validate_did(&request.subject)?;
+    if request.claims.contains_key("id") {
+        return Err(AppError::Validation(
+            "Claims can't override the subject id".to_string(),
+        ));
+    }
+    if request.types.iter().any(|t| t.trim().is_empty()) {
+        return Err(AppError::Validation(
+            "Credential types can't be empty".to_string(),
+        ));
+    }
+    let now = Utc::now();
+    if request.valid_until.is_some_and(|until| until <= now) {
+        return Err(AppError::Validation(
+            "validUntil must be in the future".to_string(),
+        ));
+    }
+
+    let signing_key = manifest::signing_key(node_data)?;
+    let verification_method = verification_method(&node_data.id)?;
+
+    let mut subject = Map::new();
+    subject.insert("id".to_string(), Value::String(request.subject));
+    subject.extend(request.claims);
+
+    let mut credential = VerifiableCredential {
+        context: vec![CREDENTIALS_CONTEXT.to_string()],
+        id: credential_id(),
+        types: std::iter::once(VERIFIABLE_CREDENTIAL.to_string())
+            .chain(
+                request
+                    .types
+                    .into_iter()
+                    .filter(|t| t != VERIFIABLE_CREDENTIAL),
+            )
+            .collect(),
+        issuer: node_data.id.clone(),
+        valid_from: now.to_rfc3339(),
+        valid_until: request.valid_until.map(|until| until.to_rfc3339()),
+        credential_subject: subject,
+        proof: None,
+    };
+
+    let jwt = jws::sign(
+        &jws::Header {
+            kid: Some(verification_method.clone()),
+            ..jws::Header::new(JWT_TYPE)
+        },
+        &credential,
+        &signing_key,
+    )?;
+
+    let options = DataIntegrityProof {
+        proof_type: proof::PROOF_TYPE.to_string(),
+        cryptosuite: proof::CRYPTOSUITE.to_string(),
+        created: credential.valid_from.clone(),
+        verification_method,
+        proof_purpose: proof::ASSERTION_METHOD.to_string(),
+        proof_value: None,
+        context: None,
+    };
+    credential.proof = Some(proof::create(
+        &credential,
+        &credential.context,
+        options,
+        &signing_key,
+    )?);
+
+    info!(target: AUDIT, "Issued credential {} about {}", credential.id, credential.subject().unwrap_or_default());
+    Ok(IssuedCredential { credential, jwt })
+}
+
+/// Check a JSON-LD credential's proof against its `did:key` issuer and its
+/// validity period
+pub fn verify_credential(credential: &VerifiableCredential) -> Result<(), AppError> {
+    verify_credential_at(credential, &TimeSource::system())
+}
+
+/// [`verify_credential`], by `time` and allowing for its clock skew
+pub fn verify_credential_at(
+    credential: &VerifiableCredential,
+    time: &TimeSource,
+) -> Result<(), AppError> {
+    let proof = credential
+        .proof
+        .as_ref()
+        .ok_or_else(|| AppError::Validation("Credential has no proof".to_string()))?;
+    if proof.verification_method != verification_method(&credential.issuer)? {
+        return Err(AppError::Auth(
+            "Proof was not made with the issuer's key".to_string(),
+        ));
+    }
+
+    let unsecured = VerifiableCredential {
+        proof: None,
+        ..credential.clone()
+    };
+    if !proof::verify(
+        &unsecured,
+        &credential.context,
+        proof,
+        &issuer_key(&credential.issuer)?,
+    )? {
+        return Err(AppError::Auth("Invalid credential proof".to_string()));
+    }
+    check_validity(credential, time)
+}
+
+/// Check a JWT credential's signature against its `did:key` issuer and its
+/// validity period, returning the credential
+pub fn verify_jwt(token: &str) -> Result<VerifiableCredential, AppError> {
+    verify_jwt_at(token, &TimeSource::system())
+}
+
+/// [`verify_jwt`], by `time` and allowing for its clock skew
+pub fn verify_jwt_at(token: &str, time: &TimeSource) -> Result<VerifiableCredential, AppError> {
+    let (_, unverified): (_, VerifiableCredential) = jws::decode(token)
+        .ok_or_else(|| AppError::Validation("Malformed credential JWT".to_string()))?;
+
+    let (header, credential): (_, VerifiableCredential) =
+        jws::verify(token, &issuer_key(&unverified.issuer)?)
+            .ok_or_else(|| AppError::Auth("Invalid credential signature".to_string()))?;
+    if header.typ != JWT_TYPE {
+        return Err(AppError::Validation(format!(
+            "Expected a {} token, got {}",
+            JWT_TYPE, header.typ
+        )));
+    }
+    check_validity(&credential, time)?;
+    Ok(credential)
+}
+
+fn check_validity(credential: &VerifiableCredential, time: &TimeSource) -> Result<(), AppError> {
+    let parse = |at: &str| {
+        DateTime::parse_from_rfc3339(at)
+            .map(|at| at.with_timezone(&Utc))
+            .map_err(|e| AppError::Validation(format!("Invalid credential time: {}", e)))
+    };
+    if time.is_ahead(parse(&credential.valid_from)?) {
+        return Err(AppError::Auth("Credential is not valid yet".to_string()));
+    }
+    if let Some(until) = &credential.valid_until
+        && time.has_passed(parse(until)?)
+    {
+        return Err(AppError::Auth("Credential has expired".to_string()));
+    }
+    Ok(())
+}
+
+/// `did:key:z…#z…`, the key's verification method in the DID document
+fn verification_method(did: &str) -> Result<String, AppError> {
+    let key = did_key_multibase(did)?;
+    Ok(format!("{}#{}", did, key))
+}
+
+fn issuer_key(did: &str) -> Result<VerifyingKey, AppError> {
+    let (_, bytes) = multibase::decode(did_key_multibase(did)?)
+        .map_err(|e| AppError::Validation(format!("Invalid issuer key: {}", e)))?;
+    let raw: [u8; 32] = bytes
+        .strip_prefix(&[0xed, 0x01])
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| AppError::Validation("Issuer must be an Ed25519 did:key".to_string()))?;
+    VerifyingKey::from_bytes(&raw)
+        .map_err(|e| AppError::Validation(format!("Invalid issuer key: {}", e)))
+}
+
+fn did_key_multibase(did: &str) -> Result<&str, AppError> {
+    did.strip_prefix("did:key:")
+        .filter(|key| key.starts_with('z'))
+        .ok_or_else(|| AppError::Validation(format!("Issuer must be a did:key: {}", did)))
+}
+
+/// `urn:uuid:` with a random (version 4) UUID
+fn credential_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
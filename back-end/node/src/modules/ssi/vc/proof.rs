@@ -0,0 +1,91 @@
+//! Data Integrity proofs with the `eddsa-jcs-2022` cryptosuite.
+//!
+//! The signature covers the SHA-256 of the JCS-canonical proof options
+//! followed by the SHA-256 of the JCS-canonical credential without its
+//! proof.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use errors::AppError;
+use multibase::Base;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::modules::manifest;
+
+pub const PROOF_TYPE: &str = "DataIntegrityProof";
+pub const CRYPTOSUITE: &str = "eddsa-jcs-2022";
+pub const ASSERTION_METHOD: &str = "assertionMethod";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataIntegrityProof {
+    #[serde(rename = "type")]
+    pub proof_type: String,
+    pub cryptosuite: String,
+    pub created: String,
+    pub verification_method: String,
+    pub proof_purpose: String,
+    /// Multibase (base58btc) signature; absent from the signed proof options
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof_value: Option<String>,
+    /// The credential's context, only while signing the proof options
+    #[serde(rename = "@context", default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<Vec<String>>,
+}
+
+/// Sign `document` (without a proof), filling in `options.proof_value`
+pub fn create<T: Serialize>(
+    document: &T,
+    context: &[String],
+    mut options: DataIntegrityProof,
+    key: &SigningKey,
+) -> Result<DataIntegrityProof, AppError> {
+    options.proof_value = None;
+    let signature = key.sign(&hash_data(document, context, &options)?);
+    options.proof_value = Some(multibase::encode(Base::Base58Btc, signature.to_bytes()));
+    Ok(options)
+}
+
+/// Check `proof` over `document` (without its proof) against `key`
+pub fn verify<T: Serialize>(
+    document: &T,
+    context: &[String],
+    proof: &DataIntegrityProof,
+    key: &VerifyingKey,
+) -> Result<bool, AppError> {
+    if proof.proof_type != PROOF_TYPE || proof.cryptosuite != CRYPTOSUITE {
+        return Err(AppError::Validation(format!(
+            "Unsupported proof: {} / {}",
+            proof.proof_type, proof.cryptosuite
+        )));
+    }
+    let Some(proof_value) = &proof.proof_value else {
+        return Ok(false);
+    };
+    let (_, signature) = multibase::decode(proof_value)
+        .map_err(|e| AppError::Validation(format!("Invalid proof value: {}", e)))?;
+    let signature = Signature::from_slice(&signature)
+        .map_err(|e| AppError::Validation(format!("Invalid proof value: {}", e)))?;
+
+    let options = DataIntegrityProof {
+        proof_value: None,
+        ..proof.clone()
+    };
+    Ok(key
+        .verify(&hash_data(document, context, &options)?, &signature)
+        .is_ok())
+}
+
+fn hash_data<T: Serialize>(
+    document: &T,
+    context: &[String],
+    options: &DataIntegrityProof,
+) -> Result<Vec<u8>, AppError> {
+    let options = DataIntegrityProof {
+        context: Some(context.to_vec()),
+        ..options.clone()
+    };
+    let mut data = Sha256::digest(manifest::canonical_bytes(&options)?).to_vec();
+    data.extend_from_slice(&Sha256::digest(manifest::canonical_bytes(document)?));
+    Ok(data)
+}
@@ -1,48 +1,78 @@
 use crate::api::node::Node;
+use crate::modules::history::{self, Change};
+use crate::modules::outbox;
+use crate::modules::session::{SessionKind, Taken};
+use crate::modules::soft_delete::live;
 use crate::modules::ssi::did::util::{
     cose_to_jwk, create_did_document, did_document_to_json, generate_did_key_from_passkey,
 };
+use crate::modules::ssi::webauthn::fingerprint::{self, ClientFingerprint};
+use crate::modules::ssi::webauthn::options::CeremonyOptions;
+use crate::modules::ssi::webauthn::policy::{CeremonyPolicy, UserVerification};
 use base64::prelude::*;
+use entity::cipher::ColumnCipher;
 use entity::pass_key;
 use entity::user;
-use log::{error, info};
-use once_cell::sync::Lazy;
+use event::schema::{CredentialBackupChanged, UserCreated};
+use event::types::{Event, EventType};
+use log::{error, info, warn};
 use sea_orm::{
     ActiveModelTrait,
     ActiveValue::{NotSet, Set},
-    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait,
 };
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use webauthn_rs::prelude::{
-    AuthenticationResult, CreationChallengeResponse, CredentialID, Passkey, PasskeyAuthentication,
-    PasskeyRegistration, PublicKeyCredential, RegisterPublicKeyCredential,
-    RequestChallengeResponse, Uuid, WebauthnError,
+    AuthenticationResult, CreationChallengeResponse, CredentialID, DiscoverableAuthentication,
+    DiscoverableKey, Passkey, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential,
+    RegisterPublicKeyCredential, RequestChallengeResponse, Uuid, WebauthnError,
 };
+use webauthn_rs_proto::PublicKeyCredentialHints;
 
+/// How long a started conditional ceremony may be finished; other ceremonies
+/// last for their requested timeout
+const CHALLENGE_TTL: Duration = Duration::from_secs(300);
+
+const AUDIT: &str = "audit";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CeremonyError {
+    #[error(transparent)]
+    Webauthn(#[from] WebauthnError),
+    /// The ceremony is being finished by a different client than started it
+    #[error("Challenge was issued to a different client")]
+    ClientMismatch,
+}
+
+#[derive(Serialize, Deserialize)]
 struct RegistrationSession {
-    uuid: Uuid,
     device_id: String,
     reg_state: PasskeyRegistration,
-    created_at: Instant,
+    /// Policy in effect when the ceremony started, recorded with the passkey
+    policy: CeremonyPolicy,
+    client: Option<ClientFingerprint>,
 }
 
+#[derive(Serialize, Deserialize)]
 struct AuthenticationSession {
-    uuid: Uuid,
     device_id: String,
-    auth_state: PasskeyAuthentication,
-    created_at: Instant,
+    auth_state: AuthenticationCeremony,
+    client: Option<ClientFingerprint>,
 }
 
-static REG_CACHE: Lazy<Arc<Mutex<HashMap<String, RegistrationSession>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
-
-static AUTH_CACHE: Lazy<Arc<Mutex<HashMap<String, AuthenticationSession>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+#[derive(Serialize, Deserialize)]
+enum AuthenticationCeremony {
+    /// The client was told which credentials to use
+    Passkey(PasskeyAuthentication),
+    /// Conditional mediation; the browser picks a discoverable credential
+    Discoverable(DiscoverableAuthentication),
+}
 
 pub async fn start_registration(
     node: &Node,
+    client: Option<&ClientFingerprint>,
+    options: &CeremonyOptions,
 ) -> Result<(CreationChallengeResponse, String), WebauthnError> {
     info!("Starting registration.");
 
@@ -50,7 +80,7 @@ pub async fn start_registration(
     let device_id = node.node_data.id.clone();
 
     // Query existing credentials for this device_id and exclude them
-    let existing_creds = get_existing_credentials(&node.db, &device_id)
+    let existing_creds = get_existing_credentials(&node.db, &node.column_keys, &device_id)
         .await
         .map_err(|e| {
             error!("Failed to query existing credentials: {}", e);
@@ -69,23 +99,36 @@ pub async fn start_registration(
         device_id.as_str(),
         exclude_creds,
     ) {
-        Ok((ccr, reg_state)) => {
+        Ok((mut ccr, reg_state)) => {
+            let policy = node.auth_state.policy;
+            let reg_state = policy
+                .apply_to_registration(&mut ccr, reg_state)
+                .map_err(|e| {
+                    error!("Failed to apply registration policy: {}", e);
+                    WebauthnError::Configuration
+                })?;
+            options.apply_to_registration(&mut ccr);
+
             let challenge_key = BASE64_STANDARD.encode(&ccr.public_key.challenge);
             let session = RegistrationSession {
-                uuid,
                 device_id,
                 reg_state,
-                created_at: Instant::now(),
+                policy,
+                client: client.cloned(),
             };
 
-            let mut cache = REG_CACHE.lock().map_err(|e| {
-                error!("Failed to acquire lock on REG_CACHE: {}", e);
-                WebauthnError::CredentialPersistenceError
-            })?;
-
-            // Clean up expired entries before inserting so the expired ones don't pile up
-            cache.retain(|_, v| v.created_at.elapsed() < Duration::from_secs(300));
-            cache.insert(challenge_key.clone(), session);
+            node.sessions
+                .put(
+                    SessionKind::Registration,
+                    &challenge_key,
+                    &session,
+                    options.timeout(),
+                )
+                .await
+                .map_err(|e| {
+                    error!("Failed to store registration session: {}", e);
+                    WebauthnError::CredentialPersistenceError
+                })?;
 
             info!(
                 "Started Registration process with challenge: {}",
@@ -106,26 +149,24 @@ pub async fn finish_registration(
     node: &Node,
     challenge_key: &str,
     reg: RegisterPublicKeyCredential,
-) -> Result<(String, String), WebauthnError> {
+    client: Option<&ClientFingerprint>,
+) -> Result<(String, String), CeremonyError> {
     info!("Finishing registration for challenge_id: {}", challenge_key);
 
-    let (_uuid, device_id, reg_state) = {
-        let mut cache = REG_CACHE.lock().map_err(|e| {
-            error!("Failed to acquire lock on REG_CACHE: {}", e);
+    let session: RegistrationSession = match node
+        .sessions
+        .take(SessionKind::Registration, challenge_key)
+        .await
+        .map_err(|e| {
+            error!("Failed to load registration session: {}", e);
             WebauthnError::CredentialRetrievalError
-        })?;
-
-        let session = cache
-            .remove(challenge_key)
-            .ok_or_else(|| WebauthnError::MismatchedChallenge)?;
-
-        // Check if expired
-        if session.created_at.elapsed() > Duration::from_secs(300) {
-            return Err(WebauthnError::ChallengeNotFound);
-        }
-
-        (session.uuid, session.device_id, session.reg_state)
+        })? {
+        Taken::Found(session) => session,
+        Taken::Expired => return Err(WebauthnError::ChallengeNotFound.into()),
+        Taken::Missing => return Err(WebauthnError::MismatchedChallenge.into()),
     };
+    check_client(challenge_key, session.client.as_ref(), client)?;
+    let (device_id, reg_state, policy) = (session.device_id, session.reg_state, session.policy);
 
     // Complete the registration
     let passkey = node
@@ -171,13 +212,23 @@ pub async fn finish_registration(
         error!("Failed to create/get user: {}", e);
         WebauthnError::CredentialPersistenceError
     })?;
+    if let Err(e) = node.dispatch_outbox().await {
+        warn!("Outbox dispatch failed: {}", e);
+    }
 
-    store_passkey(&node.db, user.id, &device_id, &passkey)
-        .await
-        .map_err(|e| {
-            error!("Failed to store Passkey: {}", e.to_string());
-            WebauthnError::CredentialPersistenceError
-        })?;
+    store_passkey(
+        &node.db,
+        &node.column_keys,
+        user.id,
+        &device_id,
+        &passkey,
+        &policy,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to store Passkey: {}", e.to_string());
+        WebauthnError::CredentialPersistenceError
+    })?;
 
     info!(
         "Passkey stored successfully for user: {} (DID: {})",
@@ -189,17 +240,20 @@ pub async fn finish_registration(
 
 pub async fn store_passkey(
     db: &DatabaseConnection,
+    cipher: &dyn ColumnCipher,
     user_id: i32,
     device_id: &str,
     passkey: &Passkey,
+    policy: &CeremonyPolicy,
 ) -> Result<pass_key::ActiveModel, Box<dyn std::error::Error>> {
     info!(
         "Storing passkey for user {} with device_id {}",
         user_id, device_id
     );
 
-    // Serialize the entire Passkey object to JSON for storage
-    let json_data = serde_json::to_string(&passkey)?;
+    // Serialize the entire Passkey object to JSON and encrypt it for storage
+    let json_data = pass_key::encrypt_json_data(cipher, user_id, &serde_json::to_string(&passkey)?)
+        .map_err(|e| e.to_string())?;
 
     // Extract credential_id as bytes
     let credential_id: Vec<u8> = passkey.cred_id().as_ref().to_vec();
@@ -208,6 +262,8 @@ pub async fn store_passkey(
     // Note: The public key in Passkey is stored as COSEKey, we need to serialize it
     let public_key = serde_json::to_vec(&passkey.get_public_key())?;
 
+    let (backup_eligible, backup_state) = backup_flags(passkey)?;
+
     let attestation = "None".to_string();
     let name = format!(
         "Passkey-{}-{}",
@@ -228,6 +284,12 @@ pub async fn store_passkey(
         json_data: Set(json_data),
         time_created: Set(chrono::Utc::now().into()),
         last_authenticated: Set(chrono::Utc::now().into()),
+        user_verification: Set(policy.registration.to_string()),
+        authenticator_attachment: Set(policy.attachment.map(|a| a.as_str().to_string())),
+        backup_eligible: Set(backup_eligible),
+        backup_state: Set(backup_state),
+        version: Set(1),
+        deleted_at: Set(None),
     };
 
     match new_passkey.insert(db).await {
@@ -245,12 +307,14 @@ pub async fn store_passkey(
 /// Start the authentication process
 pub async fn start_authentication(
     node: &Node,
+    client: Option<&ClientFingerprint>,
+    options: &CeremonyOptions,
 ) -> Result<(RequestChallengeResponse, String), WebauthnError> {
     let device_id = node.node_data.id.as_str();
     info!("Starting authentication for device: {}", device_id);
 
     // Get all passkeys for this device
-    let passkeys = get_passkeys_for_device(&node.db, device_id)
+    let passkeys = get_passkeys_for_device(&node.db, &node.column_keys, device_id)
         .await
         .map_err(|_| WebauthnError::CredentialRetrievalError)?;
 
@@ -258,44 +322,112 @@ pub async fn start_authentication(
         return Err(WebauthnError::CredentialNotFound);
     }
 
-    let uuid = Uuid::new_v4();
-    let challenge_key = Uuid::new_v4().to_string();
+    let policy = authentication_policy(node, device_id).await?;
 
-    let res = match node
+    let (mut rcr, auth_state) = node
         .auth_state
         .webauthn
         .start_passkey_authentication(&passkeys)
-    {
-        Ok((rcr, auth_state)) => {
-            let session = AuthenticationSession {
-                uuid,
-                device_id: device_id.to_string(),
-                auth_state,
-                created_at: Instant::now(),
-            };
+        .inspect_err(|e| info!("Authentication start error -> {:?}", e))?;
+    let auth_state = CeremonyPolicy::apply_to_authentication(policy, &mut rcr, auth_state)
+        .map_err(|e| {
+            error!("Failed to apply authentication policy: {}", e);
+            WebauthnError::Configuration
+        })?;
+    options.apply_to_authentication(&mut rcr);
 
-            let mut cache = AUTH_CACHE.lock().map_err(|e| {
-                error!("Failed to acquire lock on AUTH_CACHE: {}", e);
-                WebauthnError::CredentialPersistenceError
-            })?;
+    let challenge_key = store_authentication_session(
+        node,
+        AuthenticationCeremony::Passkey(auth_state),
+        client,
+        options.timeout(),
+    )
+    .await?;
+    Ok((rcr, challenge_key))
+}
 
-            // Clean up expired entries before inserting so the expired ones don't pile up
-            cache.retain(|_, v| v.created_at.elapsed() < Duration::from_secs(300));
-            cache.insert(challenge_key.clone(), session);
+/// Start an authentication for passkey autofill (`mediation: "conditional"`).
+/// No credentials are listed; the browser offers the discoverable passkeys it
+/// holds for this relying party.
+pub async fn start_conditional_authentication(
+    node: &Node,
+    client: Option<&ClientFingerprint>,
+) -> Result<(RequestChallengeResponse, String), WebauthnError> {
+    let device_id = node.node_data.id.as_str();
+    info!(
+        "Starting conditional authentication for device: {}",
+        device_id
+    );
 
-            info!(
-                "Started authentication process with challenge: {}",
-                challenge_key
-            );
-            (rcr, challenge_key)
-        }
+    let policy = authentication_policy(node, device_id).await?;
+
+    let (mut rcr, auth_state) = node
+        .auth_state
+        .webauthn
+        .start_discoverable_authentication()
+        .inspect_err(|e| info!("Conditional authentication start error -> {:?}", e))?;
+    rcr.public_key.hints = Some(vec![
+        PublicKeyCredentialHints::ClientDevice,
+        PublicKeyCredentialHints::Hybrid,
+    ]);
+    let auth_state = CeremonyPolicy::apply_to_authentication(policy, &mut rcr, auth_state)
+        .map_err(|e| {
+            error!("Failed to apply authentication policy: {}", e);
+            WebauthnError::Configuration
+        })?;
+
+    let challenge_key = store_authentication_session(
+        node,
+        AuthenticationCeremony::Discoverable(auth_state),
+        client,
+        CHALLENGE_TTL,
+    )
+    .await?;
+    Ok((rcr, challenge_key))
+}
+
+/// Configured authentication policy; credentials registered under a
+/// stricter policy keep it
+async fn authentication_policy(
+    node: &Node,
+    device_id: &str,
+) -> Result<UserVerification, WebauthnError> {
+    match registered_policy(&node.db, device_id).await {
+        Ok(Some(registered)) => Ok(node.auth_state.policy.authentication.strictest(registered)),
+        Ok(None) => Ok(node.auth_state.policy.authentication),
         Err(e) => {
-            info!("Authentication start error -> {:?}", e);
-            return Err(e);
+            error!("Failed to load passkey policies: {}", e);
+            Err(WebauthnError::CredentialRetrievalError)
         }
+    }
+}
+
+async fn store_authentication_session(
+    node: &Node,
+    auth_state: AuthenticationCeremony,
+    client: Option<&ClientFingerprint>,
+    ttl: Duration,
+) -> Result<String, WebauthnError> {
+    let challenge_key = Uuid::new_v4().to_string();
+    let session = AuthenticationSession {
+        device_id: node.node_data.id.clone(),
+        auth_state,
+        client: client.cloned(),
     };
 
-    Ok(res)
+    node.sessions
+        .put(SessionKind::Authentication, &challenge_key, &session, ttl)
+        .await
+        .map_err(|e| {
+            error!("Failed to store authentication session: {}", e);
+            WebauthnError::CredentialPersistenceError
+        })?;
+
+    info!(
+        "Started authentication process with challenge: {}",
+        challenge_key
+    );
+    Ok(challenge_key)
 }
 
 /// Complete the authentication process
@@ -303,35 +435,56 @@ pub async fn finish_authentication(
     node: &Node,
     challenge_key: &str,
     auth: PublicKeyCredential,
-) -> Result<AuthenticationResult, WebauthnError> {
-    let (_uuid, device_id, auth_state) = {
-        let mut cache = AUTH_CACHE.lock().map_err(|e| {
-            error!("Failed to acquire lock on AUTH_CACHE: {}", e);
+    client: Option<&ClientFingerprint>,
+) -> Result<AuthenticationResult, CeremonyError> {
+    let session: AuthenticationSession = match node
+        .sessions
+        .take(SessionKind::Authentication, challenge_key)
+        .await
+        .map_err(|e| {
+            error!("Failed to load authentication session: {}", e);
             WebauthnError::CredentialRetrievalError
-        })?;
-
-        let session = cache
-            .remove(challenge_key)
-            .ok_or_else(|| WebauthnError::ChallengeNotFound)?;
-
-        // Check if expired
-        if session.created_at.elapsed() > Duration::from_secs(300) {
-            return Err(WebauthnError::ChallengeNotFound);
-        }
-
-        (session.uuid, session.device_id, session.auth_state)
+        })? {
+        Taken::Found(session) => session,
+        Taken::Expired | Taken::Missing => return Err(WebauthnError::ChallengeNotFound.into()),
     };
+    check_client(challenge_key, session.client.as_ref(), client)?;
+    let (device_id, auth_state) = (session.device_id, session.auth_state);
 
     // Complete the authentication
-    let auth_result = node
-        .auth_state
-        .webauthn
-        .finish_passkey_authentication(&auth, &auth_state)?;
+    let webauthn = &node.auth_state.webauthn;
+    let auth_result = match auth_state {
+        AuthenticationCeremony::Passkey(state) => {
+            webauthn.finish_passkey_authentication(&auth, &state)?
+        }
+        AuthenticationCeremony::Discoverable(state) => {
+            let passkey = find_passkey(
+                &node.db,
+                &node.column_keys,
+                &device_id,
+                auth.get_credential_id(),
+            )
+            .await
+            .map_err(|e| {
+                error!("Failed to load discovered passkey: {}", e);
+                WebauthnError::CredentialRetrievalError
+            })?
+            .ok_or(WebauthnError::CredentialNotFound)?;
+            webauthn.finish_discoverable_authentication(
+                &auth,
+                state,
+                &[DiscoverableKey::from(&passkey)],
+            )?
+        }
+    };
 
-    // Update passkey counter
-    update_passkey_counter(&node.db, &device_id, auth_result.counter())
+    // Update the credential that was actually used
+    record_authentication(node, &device_id, &auth_result)
         .await
-        .map_err(|_| WebauthnError::CredentialCounterUpdateFailure)?;
+        .map_err(|e| {
+            error!("Failed to record authentication: {}", e);
+            WebauthnError::CredentialCounterUpdateFailure
+        })?;
 
     info!(
         "Authentication successful for device: {} with counter: {}",
@@ -342,12 +495,34 @@ pub async fn finish_authentication(
     Ok(auth_result)
 }
 
+/// Reject a ceremony finished by a different client than the one it was issued to
+fn check_client(
+    challenge_key: &str,
+    issued: Option<&ClientFingerprint>,
+    presented: Option<&ClientFingerprint>,
+) -> Result<(), CeremonyError> {
+    if fingerprint::matches(issued, presented) {
+        return Ok(());
+    }
+
+    warn!(
+        "Challenge {} was issued to [{}] but finished by [{}]",
+        challenge_key,
+        issued.map(ToString::to_string).unwrap_or_default(),
+        presented
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "unknown client".to_string())
+    );
+    Err(CeremonyError::ClientMismatch)
+}
+
 /// Get all passkeys for a specific device
 pub async fn get_passkeys_for_device(
     db: &DatabaseConnection,
+    cipher: &dyn ColumnCipher,
     device_id: &str,
 ) -> Result<Vec<Passkey>, Box<dyn std::error::Error>> {
-    let passkeys = pass_key::Entity::find()
+    let passkeys = live::<pass_key::Entity>()
         .filter(pass_key::Column::DeviceId.eq(device_id))
         .all(db)
         .await?;
@@ -355,33 +530,141 @@ pub async fn get_passkeys_for_device(
     let mut result = Vec::new();
     for passkey_model in passkeys {
         // Deserialize the JSON data back to Passkey
-        let passkey: Passkey = serde_json::from_str(&passkey_model.json_data)?;
+        let passkey: Passkey = serde_json::from_str(
+            &passkey_model
+                .passkey_json(cipher)
+                .map_err(|e| e.to_string())?,
+        )?;
         result.push(passkey);
     }
 
     Ok(result)
 }
 
-/// Update passkey counter after successful authentication
-async fn update_passkey_counter(
+/// A device's passkey by credential ID, for credentials the client discovered
+async fn find_passkey(
     db: &DatabaseConnection,
+    cipher: &dyn ColumnCipher,
     device_id: &str,
-    new_counter: u32,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let passkey = pass_key::Entity::find()
+    credential_id: &[u8],
+) -> Result<Option<Passkey>, Box<dyn std::error::Error>> {
+    let Some(model) = live::<pass_key::Entity>()
         .filter(pass_key::Column::DeviceId.eq(device_id))
+        .filter(pass_key::Column::CredentialId.eq(credential_id.to_vec()))
         .one(db)
         .await?
-        .ok_or("Passkey not found")?;
+    else {
+        return Ok(None);
+    };
 
-    let mut active_model: pass_key::ActiveModel = passkey.into();
-    active_model.sign_count = Set(new_counter as i32);
-    active_model.update(db).await?;
+    let passkey = serde_json::from_str(&model.passkey_json(cipher).map_err(|e| e.to_string())?)?;
+    Ok(Some(passkey))
+}
 
-    info!(
-        "Updated passkey counter for device: {} to {}",
-        device_id, new_counter
-    );
+/// User that owns one of this node's credentials
+pub async fn credential_owner(
+    node: &Node,
+    credential_id: &[u8],
+) -> Result<Option<i32>, errors::AppError> {
+    Ok(live::<pass_key::Entity>()
+        .filter(pass_key::Column::DeviceId.eq(node.node_data.id.as_str()))
+        .filter(pass_key::Column::CredentialId.eq(credential_id.to_vec()))
+        .one(&node.db)
+        .await
+        .map_err(|e| errors::AppError::Storage(Box::new(e)))?
+        .map(|model| model.user_id))
+}
+
+/// Strictest user verification policy among a device's passkeys
+async fn registered_policy(
+    db: &DatabaseConnection,
+    device_id: &str,
+) -> Result<Option<UserVerification>, Box<dyn std::error::Error>> {
+    let passkeys = live::<pass_key::Entity>()
+        .filter(pass_key::Column::DeviceId.eq(device_id))
+        .all(db)
+        .await?;
+
+    let mut strictest: Option<UserVerification> = None;
+    for passkey in passkeys {
+        let policy: UserVerification = passkey.user_verification.parse()?;
+        strictest = Some(strictest.map_or(policy, |s| s.strictest(policy)));
+    }
+    Ok(strictest)
+}
+
+/// Backup eligible and backup state flags of a passkey. webauthn-rs only
+/// exposes them through the serialized credential.
+fn backup_flags(passkey: &Passkey) -> Result<(bool, bool), serde_json::Error> {
+    let value = serde_json::to_value(passkey)?;
+    let flag = |name: &str| value["cred"][name].as_bool().unwrap_or(false);
+    Ok((flag("backup_eligible"), flag("backup_state")))
+}
+
+/// Record a successful authentication against the credential that was used:
+/// the updated passkey (counter and backup flags), its columns and the
+/// authentication count. Backup state transitions are announced.
+async fn record_authentication(
+    node: &Node,
+    device_id: &str,
+    result: &AuthenticationResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let model = live::<pass_key::Entity>()
+        .filter(pass_key::Column::DeviceId.eq(device_id))
+        .filter(pass_key::Column::CredentialId.eq(result.cred_id().as_ref().to_vec()))
+        .one(&node.db)
+        .await?
+        .ok_or("Passkey not found")?;
+
+    let mut passkey: Passkey = serde_json::from_str(
+        &model
+            .passkey_json(&node.column_keys)
+            .map_err(|e| e.to_string())?,
+    )?;
+    let changed = passkey
+        .update_credential(result)
+        .ok_or("Authentication result is for a different credential")?;
+    let (backup_eligible, backup_state) = backup_flags(&passkey)?;
+
+    let (id, user_id) = (model.id, model.user_id);
+    let (previous_state, authentication_count) = (model.backup_state, model.authentication_count);
+    let sign_count = (model.sign_count as u32).max(result.counter());
+
+    let mut active_model: pass_key::ActiveModel = model.into();
+    if changed {
+        active_model.json_data = Set(pass_key::encrypt_json_data(
+            &node.column_keys,
+            user_id,
+            &serde_json::to_string(&passkey)?,
+        )
+        .map_err(|e| e.to_string())?);
+    }
+    active_model.sign_count = Set(sign_count as i32);
+    active_model.authentication_count = Set(authentication_count + 1);
+    active_model.last_authenticated = Set(chrono::Utc::now().into());
+    active_model.backup_eligible = Set(backup_eligible);
+    active_model.backup_state = Set(backup_state);
+    active_model.update(&node.db).await?;
+
+    info!("Updated passkey {} counter to {}", id, sign_count);
+
+    if backup_state != previous_state {
+        let credential_id = BASE64_URL_SAFE_NO_PAD.encode(result.cred_id().as_ref());
+        info!(
+            target: AUDIT,
+            "Passkey {} ({}) backup state changed: {} -> {}",
+            id, credential_id, previous_state, backup_state
+        );
+        node.publish(&Event::from_payload(
+            EventType::CredentialBackupChanged,
+            &CredentialBackupChanged {
+                credential_id,
+                backup_eligible,
+                backup_state,
+                previous_backup_state: previous_state,
+            },
+        ));
+    }
     Ok(())
 }
 
@@ -389,6 +672,7 @@ async fn update_passkey_counter(
 /// Used during registration to populate exclude_credentials
 async fn get_passkeys_by_device_id(
     db: &DatabaseConnection,
+    cipher: &dyn ColumnCipher,
     device_id: &str,
 ) -> Result<Vec<Passkey>, Box<dyn std::error::Error>> {
     use entity::pass_key::Column;
@@ -396,14 +680,21 @@ async fn get_passkeys_by_device_id(
 
     info!("Retrieving passkeys for device_id: {}", device_id);
 
-    let passkey_models = PassKeyEntity::find()
+    let passkey_models = live::<PassKeyEntity>()
         .filter(Column::DeviceId.eq(device_id))
         .all(db)
         .await?;
 
     let mut passkeys = Vec::new();
     for model in passkey_models {
-        match serde_json::from_str::<Passkey>(&model.json_data) {
+        let json = match model.passkey_json(cipher) {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to decrypt passkey {}: {}", model.id, e);
+                continue;
+            }
+        };
+        match serde_json::from_str::<Passkey>(&json) {
             Ok(passkey) => {
                 info!(
                     "Loaded passkey with credential_id: {:x?}",
@@ -425,6 +716,7 @@ async fn get_passkeys_by_device_id(
 /// Used during authentication to find the passkey for verification
 async fn _get_passkey_by_credential_id(
     db: &DatabaseConnection,
+    cipher: &dyn ColumnCipher,
     credential_id: &[u8],
 ) -> Result<Option<(pass_key::Model, Passkey)>, Box<dyn std::error::Error>> {
     use entity::pass_key::Column;
@@ -432,14 +724,16 @@ async fn _get_passkey_by_credential_id(
 
     info!("Looking up passkey by credential_id: {:x?}", credential_id);
 
-    let passkey_model = PassKeyEntity::find()
+    let passkey_model = live::<PassKeyEntity>()
         .filter(Column::CredentialId.eq(credential_id))
         .one(db)
         .await?;
 
     match passkey_model {
         Some(model) => {
-            let passkey = serde_json::from_str::<Passkey>(&model.json_data)?;
+            let passkey = serde_json::from_str::<Passkey>(
+                &model.passkey_json(cipher).map_err(|e| e.to_string())?,
+            )?;
             info!("Found passkey with ID: {}", model.id);
             Ok(Some((model, passkey)))
         }
@@ -454,9 +748,10 @@ async fn _get_passkey_by_credential_id(
 /// Returns a list of CredentialID objects that WebAuthn can use
 async fn get_existing_credentials(
     db: &DatabaseConnection,
+    cipher: &dyn ColumnCipher,
     device_id: &str,
 ) -> Result<Vec<CredentialID>, Box<dyn std::error::Error>> {
-    let passkeys = get_passkeys_by_device_id(db, device_id).await?;
+    let passkeys = get_passkeys_by_device_id(db, cipher, device_id).await?;
 
     let credential_ids: Vec<CredentialID> =
         passkeys.iter().map(|pk| pk.cred_id().clone()).collect();
@@ -473,6 +768,7 @@ async fn get_existing_credentials(
 /// Update the sign_count for a passkey after successful authentication
 async fn _update_sign_count(
     db: &DatabaseConnection,
+    cipher: &dyn ColumnCipher,
     passkey_id: i32,
     new_count: u32,
     updated_passkey: &Passkey,
@@ -486,15 +782,21 @@ async fn _update_sign_count(
         new_count
     );
 
-    // Serialize the updated passkey
-    let json_data = serde_json::to_string(updated_passkey)?;
-
-    // Update both sign_count and json_data
-    let mut passkey: pass_key::ActiveModel = PassKeyEntity::find_by_id(passkey_id)
+    let model = PassKeyEntity::find_by_id(passkey_id)
         .one(db)
         .await?
-        .ok_or("Passkey not found")?
-        .into();
+        .ok_or("Passkey not found")?;
+
+    // Serialize and encrypt the updated passkey
+    let json_data = pass_key::encrypt_json_data(
+        cipher,
+        model.user_id,
+        &serde_json::to_string(updated_passkey)?,
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Update both sign_count and json_data
+    let mut passkey: pass_key::ActiveModel = model.into();
 
     passkey.sign_count = Set(new_count as i32);
     passkey.json_data = Set(json_data);
@@ -534,12 +836,28 @@ async fn get_or_create_user(
         device_ids: Set(serde_json::to_string(&device_ids)?),
         username: Set(username.to_string()),
         display_name: Set(username.to_string()),
+        avatar_url: Set(None),
         public_key_jwk: Set(public_key_jwk.unwrap_or_default()),
         time_created: Set(chrono::Utc::now().into()),
         last_login: Set(chrono::Utc::now().into()),
+        version: Set(1),
+        deleted_at: Set(None),
     };
 
-    let user = new_user.insert(db).await?;
+    // The user, their first history entry and their event commit together
+    let txn = db.begin().await?;
+    let user = new_user.insert(&txn).await?;
+    history::record(&txn, &(&user).into(), Change::Created, None, Some(&user)).await?;
+    let event = Event::from_payload(
+        EventType::UserCreated,
+        &UserCreated {
+            user_id: user.id,
+            did: user.did.clone(),
+            username: user.username.clone(),
+        },
+    );
+    outbox::enqueue(&txn, &event).await?;
+    txn.commit().await?;
     info!("Created user with ID: {} and DID: {}", user.id, did);
 
     Ok(user)
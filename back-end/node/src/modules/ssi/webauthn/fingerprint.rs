@@ -0,0 +1,60 @@
+//! Lightweight client fingerprint that WebAuthn challenges are bound to, so a
+//! challenge issued to one client can't be relayed to and finished by another.
+//!
+//! The network is coarse on purpose (IPv4 /24, IPv6 /64) to tolerate address
+//! churn within a network; the user agent is kept only as a hash.
+
+use std::fmt;
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientFingerprint {
+    /// Client network prefix, e.g. `192.168.1.0/24`
+    pub network: Option<String>,
+    /// Truncated blake3 hash of the User-Agent header
+    pub user_agent: Option<String>,
+}
+
+impl ClientFingerprint {
+    pub fn new(ip: Option<IpAddr>, user_agent: Option<&str>) -> Self {
+        Self {
+            network: ip.map(network_prefix),
+            user_agent: user_agent.map(|ua| blake3::hash(ua.as_bytes()).to_hex()[..16].to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ClientFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "network={} ua={}",
+            self.network.as_deref().unwrap_or("-"),
+            self.user_agent.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+fn network_prefix(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            format!("{:x}:{:x}:{:x}:{:x}::/64", s[0], s[1], s[2], s[3])
+        }
+    }
+}
+
+/// Whether a ceremony bound to `issued` may be finished by `presented`.
+/// Ceremonies started without a client (e.g. in-process callers) are unbound.
+pub fn matches(issued: Option<&ClientFingerprint>, presented: Option<&ClientFingerprint>) -> bool {
+    match issued {
+        None => true,
+        Some(issued) => presented == Some(issued),
+    }
+}
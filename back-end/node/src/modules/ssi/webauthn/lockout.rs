@@ -0,0 +1,226 @@
+//! Brute-force protection for passkey authentication.
+//!
+//! Failed finishes are counted per credential and per client network. After
+//! a few free attempts each further failure delays the next attempt
+//! (doubling, capped), and crossing the threshold locks the key out. Repeat
+//! lockouts within the failure window last longer each time. Counters live
+//! in the [`SessionStore`] and expire on their own.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::fingerprint::ClientFingerprint;
+use crate::modules::session::{SessionKind, SessionStore};
+
+/// Failures allowed before delays start
+pub const FREE_ATTEMPTS: u32 = 3;
+pub const MAX_DELAY: Duration = Duration::from_secs(60);
+pub const CREDENTIAL_LOCKOUT_THRESHOLD: u32 = 10;
+/// Networks can hold many legitimate clients, so they get more room
+pub const NETWORK_LOCKOUT_THRESHOLD: u32 = 25;
+pub const BASE_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+pub const MAX_LOCKOUT: Duration = Duration::from_secs(24 * 60 * 60);
+/// Failures are forgotten after this long without another
+pub const FAILURE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AttemptKey {
+    /// Base64url credential ID
+    Credential(String),
+    /// Client network prefix
+    Network(String),
+}
+
+impl AttemptKey {
+    fn threshold(&self) -> u32 {
+        match self {
+            AttemptKey::Credential(_) => CREDENTIAL_LOCKOUT_THRESHOLD,
+            AttemptKey::Network(_) => NETWORK_LOCKOUT_THRESHOLD,
+        }
+    }
+}
+
+impl fmt::Display for AttemptKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttemptKey::Credential(id) => write!(f, "credential:{}", id),
+            AttemptKey::Network(network) => write!(f, "network:{}", network),
+        }
+    }
+}
+
+impl FromStr for AttemptKey {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("credential", id)) => Ok(AttemptKey::Credential(id.to_string())),
+            Some(("network", network)) => Ok(AttemptKey::Network(network.to_string())),
+            _ => Err(AppError::Validation(format!("Invalid lockout key: {}", s))),
+        }
+    }
+}
+
+/// Keys an authentication attempt counts against
+pub fn attempt_keys(credential_id: &str, client: Option<&ClientFingerprint>) -> Vec<AttemptKey> {
+    let mut keys = vec![AttemptKey::Credential(credential_id.to_string())];
+    if let Some(network) = client.and_then(|c| c.network.clone()) {
+        keys.push(AttemptKey::Network(network));
+    }
+    keys
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailureRecord {
+    /// Failures since the last lockout
+    pub failures: u32,
+    /// Lockouts within the failure window
+    pub lockouts: u32,
+    pub retry_after: Option<DateTime<Utc>>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl FailureRecord {
+    fn blocked_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        [self.locked_until, self.retry_after]
+            .into_iter()
+            .flatten()
+            .filter(|until| *until > now)
+            .max()
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthGuard {
+    sessions: SessionStore,
+}
+
+impl AuthGuard {
+    pub fn new(sessions: SessionStore) -> Self {
+        Self { sessions }
+    }
+
+    /// Fail with `RateLimited` if any key is delayed or locked out
+    pub async fn check(&self, keys: &[AttemptKey]) -> Result<(), AppError> {
+        let now = Utc::now();
+        for key in keys {
+            if let Some(until) = self.record(key).await?.and_then(|r| r.blocked_until(now)) {
+                warn!("Authentication attempt for {} blocked until {}", key, until);
+                return Err(AppError::RateLimited(format!(
+                    "Too many failed authentications, retry after {}",
+                    until.to_rfc3339()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a failed attempt against every key. Returns the keys this
+    /// failure locked out, with the end of their lockout.
+    pub async fn record_failure(
+        &self,
+        keys: &[AttemptKey],
+    ) -> Result<Vec<(AttemptKey, DateTime<Utc>)>, AppError> {
+        let now = Utc::now();
+        let mut locked = Vec::new();
+
+        for key in keys {
+            // Counted in one step, so concurrent failures can't overwrite
+            // each other's counts
+            let record = self
+                .sessions
+                .update(
+                    SessionKind::AuthFailures,
+                    &key.to_string(),
+                    |record: Option<FailureRecord>| {
+                        count_failure(record.unwrap_or_default(), key.threshold(), now)
+                    },
+                )
+                .await?;
+
+            if let Some(until) = record.locked_until {
+                warn!(
+                    "{} locked out until {} after repeated failed authentications",
+                    key, until
+                );
+                locked.push((key.clone(), until));
+            }
+        }
+
+        Ok(locked)
+    }
+
+    /// A successful authentication clears the credential's failures. Network
+    /// counters are left to expire so one good login can't mask an attack.
+    pub async fn record_success(&self, credential_id: &str) -> Result<(), AppError> {
+        self.clear(&AttemptKey::Credential(credential_id.to_string()))
+            .await
+    }
+
+    pub async fn clear(&self, key: &AttemptKey) -> Result<(), AppError> {
+        self.sessions
+            .delete(SessionKind::AuthFailures, &key.to_string())
+            .await
+    }
+
+    /// Keys with recorded failures
+    pub async fn records(&self) -> Result<Vec<(AttemptKey, FailureRecord)>, AppError> {
+        self.sessions
+            .list::<FailureRecord>(SessionKind::AuthFailures)
+            .await?
+            .into_iter()
+            .map(|(key, record)| Ok((key.parse()?, record)))
+            .collect()
+    }
+
+    async fn record(&self, key: &AttemptKey) -> Result<Option<FailureRecord>, AppError> {
+        self.sessions
+            .get(SessionKind::AuthFailures, &key.to_string())
+            .await
+    }
+}
+
+/// `record` after one more failure, with how long to keep it. Only a
+/// failure that crosses `threshold` leaves `locked_until` set.
+fn count_failure(
+    mut record: FailureRecord,
+    threshold: u32,
+    now: DateTime<Utc>,
+) -> (FailureRecord, Duration) {
+    record.failures += 1;
+    record.retry_after = None;
+    record.locked_until = None;
+
+    let mut ttl = FAILURE_WINDOW;
+    if record.failures >= threshold {
+        let lockout = escalate(BASE_LOCKOUT, record.lockouts, MAX_LOCKOUT);
+        record.failures = 0;
+        record.lockouts += 1;
+        record.locked_until = Some(now + to_chrono(lockout));
+        ttl += lockout;
+    } else if record.failures > FREE_ATTEMPTS {
+        let delay = escalate(
+            Duration::from_secs(1),
+            record.failures - FREE_ATTEMPTS - 1,
+            MAX_DELAY,
+        );
+        record.retry_after = Some(now + to_chrono(delay));
+    }
+    (record, ttl)
+}
+
+/// `base * 2^steps`, capped at `max`
+fn escalate(base: Duration, steps: u32, max: Duration) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(steps)).min(max)
+}
+
+fn to_chrono(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::zero())
+}
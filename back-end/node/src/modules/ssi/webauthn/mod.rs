@@ -1,2 +1,7 @@
 pub mod auth;
+pub mod fingerprint;
+pub mod lockout;
+pub mod options;
+pub mod passkeys;
+pub mod policy;
 pub mod state;
@@ -0,0 +1,193 @@
+//! Per-request ceremony options.
+//!
+//! Clients may ask for a ceremony timeout, an attestation preference and
+//! authenticator hints when starting a ceremony. Requests are checked against
+//! the server's [`CeremonyLimits`]; the resolved [`CeremonyOptions`] are
+//! applied to the generated options and echoed back to the client.
+
+use std::time::Duration;
+
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::{CreationChallengeResponse, RequestChallengeResponse};
+use webauthn_rs_proto::{AttestationConveyancePreference, PublicKeyCredentialHints};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+pub const MIN_TIMEOUT: Duration = Duration::from_secs(30);
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Attestation conveyance preference for registration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Attestation {
+    #[default]
+    None,
+    Indirect,
+    Direct,
+}
+
+impl Attestation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Attestation::None => "none",
+            Attestation::Indirect => "indirect",
+            Attestation::Direct => "direct",
+        }
+    }
+
+    /// Parse a comma separated config list
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, AppError> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| match v.to_ascii_lowercase().as_str() {
+                "none" => Ok(Attestation::None),
+                "indirect" => Ok(Attestation::Indirect),
+                "direct" => Ok(Attestation::Direct),
+                other => Err(AppError::Config(format!(
+                    "Invalid attestation preference '{}', expected none, indirect or direct",
+                    other
+                ))),
+            })
+            .collect()
+    }
+
+    fn to_webauthn(self) -> AttestationConveyancePreference {
+        match self {
+            Attestation::None => AttestationConveyancePreference::None,
+            Attestation::Indirect => AttestationConveyancePreference::Indirect,
+            Attestation::Direct => AttestationConveyancePreference::Direct,
+        }
+    }
+}
+
+/// Class of authenticator the client should offer first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hint {
+    SecurityKey,
+    ClientDevice,
+    Hybrid,
+}
+
+impl Hint {
+    fn to_webauthn(self) -> PublicKeyCredentialHints {
+        match self {
+            Hint::SecurityKey => PublicKeyCredentialHints::SecurityKey,
+            Hint::ClientDevice => PublicKeyCredentialHints::ClientDevice,
+            Hint::Hybrid => PublicKeyCredentialHints::Hybrid,
+        }
+    }
+}
+
+/// What the server lets clients choose
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CeremonyLimits {
+    pub default_timeout: Duration,
+    pub min_timeout: Duration,
+    pub max_timeout: Duration,
+    /// Attestation preferences clients may request
+    pub attestation: Vec<Attestation>,
+}
+
+impl Default for CeremonyLimits {
+    fn default() -> Self {
+        Self {
+            default_timeout: DEFAULT_TIMEOUT,
+            min_timeout: MIN_TIMEOUT,
+            max_timeout: MAX_TIMEOUT,
+            attestation: vec![Attestation::None],
+        }
+    }
+}
+
+/// Options a client asked for when starting a ceremony
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CeremonyOverrides {
+    /// Milliseconds
+    pub timeout: Option<u64>,
+    pub attestation: Option<Attestation>,
+    pub hints: Option<Vec<Hint>>,
+}
+
+/// Options in effect for a ceremony
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CeremonyOptions {
+    /// Milliseconds
+    pub timeout: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+    pub hints: Vec<Hint>,
+}
+
+impl CeremonyOptions {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout)
+    }
+
+    pub fn apply_to_registration(&self, ccr: &mut CreationChallengeResponse) {
+        ccr.public_key.timeout = Some(self.timeout as u32);
+        ccr.public_key.attestation = self.attestation.map(Attestation::to_webauthn);
+        if !self.hints.is_empty() {
+            ccr.public_key.hints = Some(self.hints.iter().map(|h| h.to_webauthn()).collect());
+        }
+    }
+
+    pub fn apply_to_authentication(&self, rcr: &mut RequestChallengeResponse) {
+        rcr.public_key.timeout = Some(self.timeout as u32);
+        if !self.hints.is_empty() {
+            rcr.public_key.hints = Some(self.hints.iter().map(|h| h.to_webauthn()).collect());
+        }
+    }
+}
+
+impl CeremonyLimits {
+    /// Options for a registration, rejecting requests outside the limits.
+    /// Without a requested attestation the first allowed one is used.
+    pub fn registration(&self, overrides: &CeremonyOverrides) -> Result<CeremonyOptions, AppError> {
+        let attestation = match overrides.attestation {
+            Some(requested) if !self.attestation.contains(&requested) => {
+                return Err(AppError::Validation(format!(
+                    "Attestation '{}' is not allowed",
+                    requested.as_str()
+                )));
+            }
+            Some(requested) => requested,
+            None => self.attestation.first().copied().unwrap_or_default(),
+        };
+
+        Ok(CeremonyOptions {
+            attestation: Some(attestation),
+            ..self.authentication(overrides)?
+        })
+    }
+
+    /// Options for an authentication; attestation doesn't apply
+    pub fn authentication(
+        &self,
+        overrides: &CeremonyOverrides,
+    ) -> Result<CeremonyOptions, AppError> {
+        let timeout = match overrides.timeout {
+            Some(ms) => {
+                let requested = Duration::from_millis(ms);
+                if requested < self.min_timeout || requested > self.max_timeout {
+                    return Err(AppError::Validation(format!(
+                        "Timeout must be between {} and {} ms",
+                        self.min_timeout.as_millis(),
+                        self.max_timeout.as_millis()
+                    )));
+                }
+                requested
+            }
+            None => self.default_timeout,
+        };
+
+        Ok(CeremonyOptions {
+            timeout: timeout.as_millis() as u64,
+            attestation: None,
+            hints: overrides.hints.clone().unwrap_or_default(),
+        })
+    }
+}
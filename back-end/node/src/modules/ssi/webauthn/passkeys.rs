@@ -0,0 +1,187 @@
+//! Managing registered passkeys: listing, renaming and deleting them.
+//!
+//! Users manage their own passkeys and the node manages everyone's; a
+//! passkey of another user reads as missing. A user's last passkey can't be
+//! deleted, since they could never sign in again; deleting the account is
+//! the way to drop it. A deleted passkey no longer signs in, and can be
+//! restored within the restore window (see [`crate::modules::soft_delete`]).
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use entity::pass_key;
+use errors::AppError;
+use log::info;
+use sea_orm::sea_query::{Expr, ExprTrait, Query};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+};
+use serde::Serialize;
+
+use crate::modules::soft_delete::{deleted, live};
+use crate::modules::tenancy::Actor;
+use crate::modules::versioning::update_versioned;
+
+const AUDIT: &str = "audit";
+
+/// Longest passkey name, in characters
+pub const MAX_NAME_CHARS: usize = 64;
+
+/// A passkey as its owner sees it; no key material
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeySummary {
+    pub id: i32,
+    pub user_id: i32,
+    /// Base64url credential ID
+    pub credential_id: String,
+    pub name: String,
+    pub time_created: String,
+    pub last_authenticated: String,
+    pub authentication_count: i32,
+    pub authenticator_attachment: Option<String>,
+    pub backup_eligible: bool,
+    pub backup_state: bool,
+    /// Send back with a rename, to refuse it if the passkey changed since
+    pub version: i32,
+}
+
+impl From<pass_key::Model> for PasskeySummary {
+    fn from(passkey: pass_key::Model) -> Self {
+        Self {
+            id: passkey.id,
+            user_id: passkey.user_id,
+            credential_id: URL_SAFE_NO_PAD.encode(&passkey.credential_id),
+            name: passkey.name,
+            time_created: passkey.time_created.to_rfc3339(),
+            last_authenticated: passkey.last_authenticated.to_rfc3339(),
+            authentication_count: passkey.authentication_count,
+            authenticator_attachment: passkey.authenticator_attachment,
+            backup_eligible: passkey.backup_eligible,
+            backup_state: passkey.backup_state,
+            version: passkey.version,
+        }
+    }
+}
+
+/// Passkeys `actor` may manage, oldest first
+pub async fn list(db: &DatabaseConnection, actor: Actor) -> Result<Vec<PasskeySummary>, AppError> {
+    let mut query = live::<pass_key::Entity>();
+    if let Actor::User(id) = actor {
+        query = query.filter(pass_key::Column::UserId.eq(id));
+    }
+    let passkeys = query
+        .order_by_asc(pass_key::Column::Id)
+        .all(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(passkeys.into_iter().map(PasskeySummary::from).collect())
+}
+
+/// Give a passkey `actor` may manage a new name, if it is still at
+/// `version` when one is given
+pub async fn rename(
+    db: &DatabaseConnection,
+    actor: Actor,
+    id: i32,
+    name: &str,
+    version: Option<i32>,
+) -> Result<PasskeySummary, AppError> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME_CHARS
+        || name.chars().any(char::is_control)
+    {
+        return Err(AppError::Validation(format!(
+            "Passkey name must be 1 to {} printable characters",
+            MAX_NAME_CHARS
+        )));
+    }
+
+    find(db, actor, id).await?;
+    let changes = pass_key::ActiveModel {
+        name: Set(name.to_string()),
+        ..Default::default()
+    };
+    let passkey = update_versioned::<pass_key::Entity>(db, id, version, changes).await?;
+    info!(target: AUDIT, "{} renamed passkey {} to {:?}", actor, id, name);
+    Ok(passkey.into())
+}
+
+/// Delete a passkey `actor` may manage, unless it is its user's last live
+/// one
+pub async fn delete(
+    db: &DatabaseConnection,
+    actor: Actor,
+    id: i32,
+) -> Result<PasskeySummary, AppError> {
+    let passkey = find(db, actor, id).await?;
+
+    // One statement, so two deletes can't each leave the other the last
+    let users_with_others = Query::select()
+        .column(pass_key::Column::UserId)
+        .from(pass_key::Entity)
+        .and_where(pass_key::Column::DeletedAt.is_null())
+        .group_by_col(pass_key::Column::UserId)
+        .and_having(Expr::col(pass_key::Column::Id).count().gt(1))
+        .to_owned();
+    let deleted = pass_key::Entity::update_many()
+        .col_expr(pass_key::Column::DeletedAt, Expr::value(Utc::now()))
+        .col_expr(
+            pass_key::Column::Version,
+            Expr::col(pass_key::Column::Version).add(1),
+        )
+        .filter(pass_key::Column::Id.eq(id))
+        .filter(pass_key::Column::DeletedAt.is_null())
+        .filter(pass_key::Column::UserId.in_subquery(users_with_others))
+        .exec(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .rows_affected;
+    if deleted == 0 {
+        return Err(AppError::Conflict(
+            "Can't delete the last passkey; register another first".to_string(),
+        ));
+    }
+
+    info!(
+        target: AUDIT,
+        "{} deleted passkey {} of user {}", actor, id, passkey.user_id
+    );
+    Ok(passkey.into())
+}
+
+/// Bring back a passkey `actor` may manage, deleted no earlier than `since`
+pub async fn restore(
+    db: &DatabaseConnection,
+    actor: Actor,
+    id: i32,
+    since: DateTime<Utc>,
+) -> Result<PasskeySummary, AppError> {
+    deleted::<pass_key::Entity>()
+        .filter(pass_key::Column::Id.eq(id))
+        .filter(pass_key::Column::DeletedAt.gte(since))
+        .one(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .filter(|passkey| actor.can_access(Some(passkey.user_id)))
+        .ok_or_else(|| AppError::NotFound(format!("No restorable passkey {}", id)))?;
+
+    let changes = pass_key::ActiveModel {
+        deleted_at: Set(None),
+        ..Default::default()
+    };
+    let passkey = update_versioned::<pass_key::Entity>(db, id, None, changes).await?;
+    info!(target: AUDIT, "{} restored passkey {}", actor, id);
+    Ok(passkey.into())
+}
+
+async fn find(db: &DatabaseConnection, actor: Actor, id: i32) -> Result<pass_key::Model, AppError> {
+    live::<pass_key::Entity>()
+        .filter(pass_key::Column::Id.eq(id))
+        .one(db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .filter(|passkey| actor.can_access(Some(passkey.user_id)))
+        .ok_or_else(|| AppError::NotFound(format!("Passkey not found: {}", id)))
+}
@@ -0,0 +1,219 @@
+//! User verification, authenticator attachment and resident key policy for
+//! passkey ceremonies.
+//!
+//! webauthn-rs always asks passkeys for user verification, sets no
+//! attachment hint and discourages resident keys. The configured policy is applied on top of the options it
+//! generates, both in the options sent to the client and in the ceremony
+//! state the response is verified against.
+
+use std::fmt;
+use std::str::FromStr;
+
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use webauthn_rs::prelude::{
+    AuthenticatorAttachment, CreationChallengeResponse, PasskeyRegistration,
+    RequestChallengeResponse,
+};
+use webauthn_rs_proto::{ResidentKeyRequirement, UserVerificationPolicy};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UserVerification {
+    #[default]
+    Required,
+    Preferred,
+    Discouraged,
+}
+
+impl UserVerification {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserVerification::Required => "required",
+            UserVerification::Preferred => "preferred",
+            UserVerification::Discouraged => "discouraged",
+        }
+    }
+
+    /// The stricter of two policies
+    pub fn strictest(self, other: Self) -> Self {
+        self.min(other)
+    }
+
+    fn to_webauthn(self) -> UserVerificationPolicy {
+        match self {
+            UserVerification::Required => UserVerificationPolicy::Required,
+            UserVerification::Preferred => UserVerificationPolicy::Preferred,
+            UserVerification::Discouraged => UserVerificationPolicy::Discouraged_DO_NOT_USE,
+        }
+    }
+}
+
+impl fmt::Display for UserVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for UserVerification {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "required" => Ok(UserVerification::Required),
+            "preferred" => Ok(UserVerification::Preferred),
+            "discouraged" => Ok(UserVerification::Discouraged),
+            other => Err(AppError::Config(format!(
+                "Invalid user verification policy '{}', expected required, preferred or discouraged",
+                other
+            ))),
+        }
+    }
+}
+
+/// Authenticator attachment hint; `None` lets the client offer any authenticator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Attachment {
+    Platform,
+    CrossPlatform,
+}
+
+impl Attachment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Attachment::Platform => "platform",
+            Attachment::CrossPlatform => "cross-platform",
+        }
+    }
+
+    /// Parse a config value, where `any` means no preference
+    pub fn parse(s: &str) -> Result<Option<Self>, AppError> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "" | "any" => Ok(None),
+            "platform" => Ok(Some(Attachment::Platform)),
+            "cross-platform" => Ok(Some(Attachment::CrossPlatform)),
+            other => Err(AppError::Config(format!(
+                "Invalid authenticator attachment '{}', expected any, platform or cross-platform",
+                other
+            ))),
+        }
+    }
+
+    fn to_webauthn(self) -> AuthenticatorAttachment {
+        match self {
+            Attachment::Platform => AuthenticatorAttachment::Platform,
+            Attachment::CrossPlatform => AuthenticatorAttachment::CrossPlatform,
+        }
+    }
+}
+
+/// Whether registration asks for a discoverable (resident) credential.
+/// Passkey autofill can only offer discoverable credentials.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResidentKey {
+    #[default]
+    Discouraged,
+    Preferred,
+    Required,
+}
+
+impl ResidentKey {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResidentKey::Discouraged => "discouraged",
+            ResidentKey::Preferred => "preferred",
+            ResidentKey::Required => "required",
+        }
+    }
+
+    fn to_webauthn(self) -> ResidentKeyRequirement {
+        match self {
+            ResidentKey::Discouraged => ResidentKeyRequirement::Discouraged,
+            ResidentKey::Preferred => ResidentKeyRequirement::Preferred,
+            ResidentKey::Required => ResidentKeyRequirement::Required,
+        }
+    }
+}
+
+impl FromStr for ResidentKey {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "discouraged" => Ok(ResidentKey::Discouraged),
+            "preferred" => Ok(ResidentKey::Preferred),
+            "required" => Ok(ResidentKey::Required),
+            other => Err(AppError::Config(format!(
+                "Invalid resident key requirement '{}', expected discouraged, preferred or required",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CeremonyPolicy {
+    pub registration: UserVerification,
+    pub authentication: UserVerification,
+    pub attachment: Option<Attachment>,
+    #[serde(default)]
+    pub resident_key: ResidentKey,
+}
+
+impl CeremonyPolicy {
+    /// Apply the registration policy to freshly generated options and state
+    pub fn apply_to_registration(
+        &self,
+        ccr: &mut CreationChallengeResponse,
+        state: PasskeyRegistration,
+    ) -> Result<PasskeyRegistration, AppError> {
+        if let Some(selection) = ccr.public_key.authenticator_selection.as_mut() {
+            selection.user_verification = self.registration.to_webauthn();
+            selection.authenticator_attachment = self.attachment.map(Attachment::to_webauthn);
+            selection.resident_key = Some(self.resident_key.to_webauthn());
+            selection.require_resident_key = self.resident_key == ResidentKey::Required;
+        }
+
+        rewrite_state(state, "rs", |rs| {
+            rs["policy"] = serde_json::json!(self.registration.as_str());
+            rs["authenticator_attachment"] = serde_json::json!(self.attachment.map(|a| a.as_str()));
+            rs["require_resident_key"] =
+                serde_json::json!(self.resident_key == ResidentKey::Required);
+        })
+    }
+
+    /// Apply `policy` to freshly generated authentication options and state
+    pub fn apply_to_authentication<T>(
+        policy: UserVerification,
+        rcr: &mut RequestChallengeResponse,
+        state: T,
+    ) -> Result<T, AppError>
+    where
+        T: Serialize + serde::de::DeserializeOwned,
+    {
+        rcr.public_key.user_verification = policy.to_webauthn();
+
+        rewrite_state(state, "ast", |ast| {
+            ast["policy"] = serde_json::json!(policy.as_str());
+        })
+    }
+}
+
+/// Edit a field of a ceremony state through its serialized form, the only
+/// access webauthn-rs gives to it
+fn rewrite_state<T>(state: T, field: &str, edit: impl FnOnce(&mut Value)) -> Result<T, AppError>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let mut value = serde_json::to_value(&state)
+        .map_err(|e| AppError::Auth(format!("Failed to serialize ceremony state: {}", e)))?;
+    let inner = value
+        .get_mut(field)
+        .ok_or_else(|| AppError::Auth(format!("Ceremony state has no '{}'", field)))?;
+    edit(inner);
+    serde_json::from_value(value)
+        .map_err(|e| AppError::Auth(format!("Failed to rebuild ceremony state: {}", e)))
+}
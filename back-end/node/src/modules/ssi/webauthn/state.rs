@@ -1,11 +1,17 @@
+use super::options::{Attestation, CeremonyLimits};
+use super::policy::{Attachment, CeremonyPolicy};
+use crate::bootstrap::config::ProxyConfig;
 use errors::AppError;
 use log::info;
 use std::sync::Arc;
+use std::time::Duration;
 use webauthn_rs::prelude::*;
 
 #[derive(Clone)]
 pub struct AuthState {
     pub webauthn: Arc<Webauthn>,
+    pub policy: CeremonyPolicy,
+    pub limits: CeremonyLimits,
 }
 
 /// Configuration for WebAuthn authentication
@@ -17,24 +23,98 @@ pub struct AuthConfig {
     pub rp_origin: String,
     /// Relying Party display name
     pub rp_name: String,
+    /// User verification, attachment and resident key preferences
+    pub policy: CeremonyPolicy,
+    /// Bounds for options clients may request per ceremony
+    pub limits: CeremonyLimits,
 }
 
 impl AuthConfig {
     /// Load authentication configuration from environment variables
     pub fn from_env() -> Result<Self, AppError> {
+        Self::from_env_serving(false)
+    }
+
+    /// Load authentication configuration for a node serving HTTPS itself if
+    /// `https`: the default origin is `https://` then, and an `http://`
+    /// WEBAUTHN_RP_ORIGIN is refused, as browsers would never match it
+    pub fn from_env_serving(https: bool) -> Result<Self, AppError> {
         use std::env;
 
-        let rp_id = env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string());
+        // Behind a reverse proxy, browsers see the proxy's origin
+        let external_origin = env::var("FLOW_EXTERNAL_ORIGIN")
+            .ok()
+            .map(|value| ProxyConfig::parse_origin(&value))
+            .transpose()?;
+
+        let rp_id = env::var("WEBAUTHN_RP_ID")
+            .ok()
+            .or_else(|| {
+                let origin = Url::parse(external_origin.as_ref()?).ok()?;
+                origin.host_str().map(str::to_string)
+            })
+            .unwrap_or_else(|| "localhost".to_string());
 
-        let rp_origin =
-            env::var("WEBAUTHN_RP_ORIGIN").unwrap_or_else(|_| "http://localhost:8080".to_string());
+        let explicit_origin = env::var("WEBAUTHN_RP_ORIGIN").ok();
+        if https
+            && explicit_origin
+                .as_deref()
+                .is_some_and(|origin| origin.starts_with("http://"))
+        {
+            return Err(AppError::Config(
+                "WEBAUTHN_RP_ORIGIN must be https:// when the node serves HTTPS".to_string(),
+            ));
+        }
+        let scheme = if https { "https" } else { "http" };
+        let rp_origin = explicit_origin
+            .or(external_origin)
+            .unwrap_or_else(|| format!("{}://localhost:8080", scheme));
 
         let rp_name = env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| "Flow WebAuthn".to_string());
 
+        let policy = CeremonyPolicy {
+            registration: match env::var("WEBAUTHN_REGISTRATION_UV") {
+                Ok(value) => value.parse()?,
+                Err(_) => Default::default(),
+            },
+            authentication: match env::var("WEBAUTHN_AUTHENTICATION_UV") {
+                Ok(value) => value.parse()?,
+                Err(_) => Default::default(),
+            },
+            attachment: match env::var("WEBAUTHN_AUTHENTICATOR_ATTACHMENT") {
+                Ok(value) => Attachment::parse(&value)?,
+                Err(_) => None,
+            },
+            resident_key: match env::var("WEBAUTHN_RESIDENT_KEY") {
+                Ok(value) => value.parse()?,
+                Err(_) => Default::default(),
+            },
+        };
+
+        let defaults = CeremonyLimits::default();
+        let limits = CeremonyLimits {
+            default_timeout: millis_var("WEBAUTHN_TIMEOUT_MS")?.unwrap_or(defaults.default_timeout),
+            min_timeout: millis_var("WEBAUTHN_MIN_TIMEOUT_MS")?.unwrap_or(defaults.min_timeout),
+            max_timeout: millis_var("WEBAUTHN_MAX_TIMEOUT_MS")?.unwrap_or(defaults.max_timeout),
+            attestation: match env::var("WEBAUTHN_ALLOWED_ATTESTATION") {
+                Ok(value) => Attestation::parse_list(&value)?,
+                Err(_) => defaults.attestation,
+            },
+        };
+        if !(limits.min_timeout <= limits.default_timeout
+            && limits.default_timeout <= limits.max_timeout)
+        {
+            return Err(AppError::Config(
+                "WebAuthn timeouts must satisfy min <= default <= max".to_string(),
+            ));
+        }
+
         Ok(Self {
             rp_id,
             rp_origin,
             rp_name,
+            policy,
+            limits,
         })
     }
 }
@@ -58,7 +138,11 @@ impl AuthState {
                 .map_err(|e| AppError::Config(format!("Failed to build WebAuthn: {}", e)))?,
         );
 
-        Ok(AuthState { webauthn })
+        Ok(AuthState {
+            webauthn,
+            policy: config.policy,
+            limits: config.limits,
+        })
     }
 
     /// Create a new AuthState from environment variables
@@ -66,4 +150,20 @@ impl AuthState {
         let config = AuthConfig::from_env()?;
         Self::new(config)
     }
+
+    /// [`AuthState::from_env`] for a node serving HTTPS itself if `https`
+    pub fn from_env_serving(https: bool) -> Result<Self, AppError> {
+        Self::new(AuthConfig::from_env_serving(https)?)
+    }
+}
+
+fn millis_var(name: &str) -> Result<Option<Duration>, AppError> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(|ms| Some(Duration::from_millis(ms)))
+            .map_err(|e| AppError::Config(format!("Invalid {}: {}", name, e))),
+        Err(_) => Ok(None),
+    }
 }
@@ -0,0 +1,333 @@
+//! Storage engine internals.
+//!
+//! What sled and SQLite say about themselves, so capacity trouble shows
+//! before it stalls writes: how big each KV tree has grown and how long a
+//! flush takes; SQLite's pages, free pages, page cache, write-ahead log and
+//! connection pool. [`StorageStats::warnings`] points out what looks wrong,
+//! and `node doctor` prints both; `/api/v1/admin/metrics` exports them.
+//!
+//! Neither engine reports page cache hits where we can read them: sled only
+//! counts them in its `metrics` build, and SQLite per connection, behind its
+//! C API. The configured cache sizes are reported instead.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use errors::AppError;
+use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement};
+use serde::Serialize;
+use sled::Db;
+
+use crate::modules::resources::write_gauge;
+
+/// A flush slower than this means writes are waiting on the disk
+pub const SLOW_FLUSH: Duration = Duration::from_secs(1);
+
+/// A write-ahead log this big isn't being checkpointed
+pub const LARGE_WAL_BYTES: u64 = 64 * 1024 * 1024;
+
+/// KV store on disk over this many times what it holds is mostly garbage
+pub const KV_AMPLIFICATION: u64 = 4;
+
+/// Below this, space amplification is not worth a warning
+const KV_AMPLIFICATION_MIN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Share of free pages above which a `VACUUM` is worth it
+const FREE_PAGES_PERCENT: u64 = 25;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeStats {
+    pub entries: u64,
+    /// Bytes of keys and values
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KvStats {
+    pub disk_bytes: u64,
+    pub trees: BTreeMap<String, TreeStats>,
+    /// How long flushing dirty pages to disk took
+    pub flush_micros: u64,
+}
+
+impl KvStats {
+    /// Bytes of keys and values across all trees
+    pub fn stored_bytes(&self) -> u64 {
+        self.trees.values().map(|tree| tree.bytes).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbStats {
+    pub page_size: u64,
+    pub pages: u64,
+    /// Pages no longer in use, given back by `VACUUM`
+    pub free_pages: u64,
+    /// Page cache each connection may hold
+    pub cache_bytes: u64,
+    pub journal_mode: String,
+    /// `None` when not in WAL mode or in memory
+    pub wal_bytes: Option<u64>,
+    pub pool_connections: u64,
+    pub pool_idle: u64,
+    /// Connections the pool may open
+    pub pool_max: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageStats {
+    pub kv: KvStats,
+    /// `None` for databases other than SQLite
+    pub db: Option<DbStats>,
+}
+
+impl StorageStats {
+    /// What in these stats is worth acting on
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let flush = Duration::from_micros(self.kv.flush_micros);
+        if flush > SLOW_FLUSH {
+            warnings.push(format!(
+                "KV store flush took {:?}; the disk is not keeping up with writes",
+                flush
+            ));
+        }
+        let stored = self.kv.stored_bytes();
+        if self.kv.disk_bytes > KV_AMPLIFICATION_MIN_BYTES
+            && self.kv.disk_bytes > stored.saturating_mul(KV_AMPLIFICATION)
+        {
+            warnings.push(format!(
+                "KV store takes {} bytes on disk for {} bytes of data",
+                self.kv.disk_bytes, stored
+            ));
+        }
+        if let Some(db) = &self.db {
+            if let Some(wal) = db.wal_bytes.filter(|wal| *wal > LARGE_WAL_BYTES) {
+                warnings.push(format!(
+                    "Database write-ahead log is {} bytes; checkpoints are falling behind",
+                    wal
+                ));
+            }
+            if db.pages > 0 && db.free_pages * 100 / db.pages > FREE_PAGES_PERCENT {
+                warnings.push(format!(
+                    "{} of {} database pages are free; VACUUM would reclaim them",
+                    db.free_pages, db.pages
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+impl fmt::Display for StorageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "KV store (sled)")?;
+        writeln!(f, "  on disk        {} bytes", self.kv.disk_bytes)?;
+        writeln!(f, "  flush          {} us", self.kv.flush_micros)?;
+        for (name, tree) in &self.kv.trees {
+            writeln!(
+                f,
+                "  tree {:<24} {} entries, {} bytes",
+                name, tree.entries, tree.bytes
+            )?;
+        }
+        match &self.db {
+            Some(db) => {
+                writeln!(f, "Database (SQLite)")?;
+                writeln!(
+                    f,
+                    "  pages          {} of {} bytes, {} free",
+                    db.pages, db.page_size, db.free_pages
+                )?;
+                writeln!(f, "  page cache     {} bytes", db.cache_bytes)?;
+                writeln!(f, "  journal        {}", db.journal_mode)?;
+                if let Some(wal) = db.wal_bytes {
+                    writeln!(f, "  WAL            {} bytes", wal)?;
+                }
+                writeln!(
+                    f,
+                    "  connections    {} open of {}, {} idle",
+                    db.pool_connections, db.pool_max, db.pool_idle
+                )?;
+            }
+            None => writeln!(f, "Database: not SQLite, no internals reported")?,
+        }
+        Ok(())
+    }
+}
+
+/// Read the stats of both stores. Counts every KV entry and flushes the KV
+/// store, so this isn't for hot paths.
+pub async fn collect(db: &DatabaseConnection, kv: &Db) -> Result<StorageStats, AppError> {
+    Ok(StorageStats {
+        kv: kv_stats(kv).await?,
+        db: db_stats(db).await?,
+    })
+}
+
+async fn kv_stats(kv: &Db) -> Result<KvStats, AppError> {
+    let mut trees = BTreeMap::new();
+    for name in kv.tree_names() {
+        let tree = kv
+            .open_tree(&name)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        let mut stats = TreeStats::default();
+        for entry in tree.iter() {
+            let (key, value) = entry.map_err(|e| AppError::Storage(Box::new(e)))?;
+            stats.entries += 1;
+            stats.bytes += (key.len() + value.len()) as u64;
+        }
+        trees.insert(String::from_utf8_lossy(&name).into_owned(), stats);
+    }
+
+    let started = Instant::now();
+    kv.flush_async()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    let flush_micros = started.elapsed().as_micros() as u64;
+
+    Ok(KvStats {
+        disk_bytes: kv
+            .size_on_disk()
+            .map_err(|e| AppError::Storage(Box::new(e)))?,
+        trees,
+        flush_micros,
+    })
+}
+
+async fn db_stats(db: &DatabaseConnection) -> Result<Option<DbStats>, AppError> {
+    if db.get_database_backend() != DatabaseBackend::Sqlite {
+        return Ok(None);
+    }
+    // Before the query below takes a connection of its own. Connections
+    // return to the pool in the background, so this is a sample, too
+    // rough to warn on.
+    let pool = db.get_sqlite_connection_pool();
+    let (pool_connections, pool_idle) = (pool.size() as u64, pool.num_idle() as u64);
+    let pool_max = pool.options().get_max_connections() as u64;
+
+    let row = db
+        .query_one(Statement::from_string(
+            DatabaseBackend::Sqlite,
+            "SELECT page_size, page_count, freelist_count, cache_size, journal_mode, \
+             (SELECT file FROM pragma_database_list WHERE name = 'main') AS file \
+             FROM pragma_page_size(), pragma_page_count(), pragma_freelist_count(), \
+             pragma_cache_size(), pragma_journal_mode()",
+        ))
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .ok_or_else(|| AppError::NotFound("SQLite reported no pragmas".to_string()))?;
+    let get = |column: &str| {
+        row.try_get::<i64>("", column)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    };
+    let page_size = get("page_size")?.max(0) as u64;
+    // Positive in pages, negative in KiB
+    let cache_size = get("cache_size")?;
+    let cache_bytes = if cache_size < 0 {
+        cache_size.unsigned_abs() * 1024
+    } else {
+        cache_size as u64 * page_size
+    };
+    let journal_mode: String = row
+        .try_get("", "journal_mode")
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    let file: Option<String> = row
+        .try_get("", "file")
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    let wal_bytes = match file.filter(|file| !file.is_empty()) {
+        Some(file) if journal_mode.eq_ignore_ascii_case("wal") => {
+            let mut wal = PathBuf::from(file).into_os_string();
+            wal.push("-wal");
+            // Not there until the first write after a checkpoint
+            Some(std::fs::metadata(&wal).map(|m| m.len()).unwrap_or(0))
+        }
+        _ => None,
+    };
+
+    Ok(Some(DbStats {
+        page_size,
+        pages: get("page_count")?.max(0) as u64,
+        free_pages: get("freelist_count")?.max(0) as u64,
+        cache_bytes,
+        journal_mode,
+        wal_bytes,
+        pool_connections,
+        pool_idle,
+        pool_max,
+    }))
+}
+
+/// `stats` in the Prometheus text exposition format
+pub fn prometheus(stats: &StorageStats) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(Option<(&str, &str)>, u64)>| {
+        write_gauge(&mut out, name, help, samples)
+    };
+
+    gauge(
+        "flow_kv_tree_entries",
+        "Entries in a KV tree",
+        stats
+            .kv
+            .trees
+            .iter()
+            .map(|(tree, stats)| (Some(("tree", tree.as_str())), stats.entries))
+            .collect(),
+    );
+    gauge(
+        "flow_kv_flush_microseconds",
+        "Time the last KV store flush took",
+        vec![(None, stats.kv.flush_micros)],
+    );
+    if let Some(db) = &stats.db {
+        gauge(
+            "flow_db_page_size_bytes",
+            "SQLite page size",
+            vec![(None, db.page_size)],
+        );
+        gauge(
+            "flow_db_pages",
+            "SQLite pages in the database file",
+            vec![(None, db.pages)],
+        );
+        gauge(
+            "flow_db_free_pages",
+            "SQLite pages no longer in use",
+            vec![(None, db.free_pages)],
+        );
+        gauge(
+            "flow_db_cache_bytes",
+            "SQLite page cache per connection",
+            vec![(None, db.cache_bytes)],
+        );
+        gauge(
+            "flow_db_wal_bytes",
+            "SQLite write-ahead log size",
+            db.wal_bytes.map(|v| (None, v)).into_iter().collect(),
+        );
+        gauge(
+            "flow_db_connections",
+            "Database connections by state",
+            vec![
+                (
+                    Some(("state", "busy")),
+                    db.pool_connections.saturating_sub(db.pool_idle),
+                ),
+                (Some(("state", "idle")), db.pool_idle),
+            ],
+        );
+        gauge(
+            "flow_db_connections_max",
+            "Database connections the pool may open",
+            vec![(None, db.pool_max)],
+        );
+    }
+    out
+}
@@ -0,0 +1,270 @@
+//! Opt-in usage telemetry.
+//!
+//! With `FLOW_TELEMETRY_ENABLED` on, the node counts which REST features are
+//! used (method and route, e.g. `GET /api/v1/spaces/{key}`) and the classes
+//! of error it answers with (their codes, e.g. `not_found`). Nothing else is
+//! kept: no paths as requested, no content, no DIDs, user IDs or addresses.
+//!
+//! Counts are noised before they leave the node. Each gets Laplace noise of
+//! scale 1/ε (`FLOW_TELEMETRY_EPSILON`) and is rounded, and counts that come
+//! out below one are left out, so a report doesn't tell whether any one
+//! request was made. The noise is drawn from a seed kept with the counts,
+//! which makes [`Telemetry::report`] (`GET /api/v1/admin/telemetry`) exactly
+//! what would be sent next. Every `FLOW_TELEMETRY_INTERVAL` the report is
+//! POSTed to `FLOW_TELEMETRY_URL`, and the counts it covers are dropped.
+
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, Utc};
+use errors::AppError;
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sled::{Db, IVec};
+
+use crate::bootstrap::config::TelemetryConfig;
+use crate::modules::updater::CURRENT_VERSION;
+
+pub const COUNTER_TREE: &str = "telemetry:counters";
+pub const STATE_TREE: &str = "telemetry:state";
+pub const REPORT_SCHEMA: &str = "flow-telemetry/v1";
+
+const SEED_KEY: &str = "seed";
+const SINCE_KEY: &str = "since";
+
+/// What is counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    /// A REST route, as `METHOD /route/{param}`
+    Feature,
+    /// An error code, as in the i18n catalog
+    Error,
+}
+
+impl Counter {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Counter::Feature => "feature:",
+            Counter::Error => "error:",
+        }
+    }
+}
+
+/// What a submission sends
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryReport {
+    pub schema: String,
+    pub software_version: String,
+    /// Day counting started, no finer
+    pub since: Option<NaiveDate>,
+    /// Noised uses per feature
+    pub features: BTreeMap<String, u64>,
+    /// Noised responses per error code
+    pub errors: BTreeMap<String, u64>,
+}
+
+/// Usage counts kept in the KV store
+#[derive(Debug, Clone)]
+pub struct Telemetry {
+    kv: Db,
+    config: TelemetryConfig,
+}
+
+impl Telemetry {
+    pub fn new(kv: Db, config: TelemetryConfig) -> Self {
+        Self { kv, config }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn config(&self) -> &TelemetryConfig {
+        &self.config
+    }
+
+    /// Count one use of `name`; nothing is kept while telemetry is off
+    pub fn count(&self, counter: Counter, name: &str) -> Result<(), AppError> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let key = format!("{}{}", counter.prefix(), name);
+        self.counters()?
+            .update_and_fetch(key.as_bytes(), |count| {
+                Some((decode_count(count) + 1).to_be_bytes().to_vec())
+            })
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        let today = Utc::now().date_naive().to_string();
+        self.state()?
+            .compare_and_swap(SINCE_KEY, None::<&[u8]>, Some(today.as_bytes()))
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .ok();
+        Ok(())
+    }
+
+    /// The report that would be sent now
+    pub fn report(&self) -> Result<TelemetryReport, AppError> {
+        self.report_of(&self.snapshot()?)
+    }
+
+    /// Send the report, and drop the counts it covers. Nothing is sent while
+    /// nothing has been counted.
+    pub async fn submit(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<Option<TelemetryReport>, AppError> {
+        let endpoint = self
+            .config
+            .endpoint
+            .as_deref()
+            .ok_or_else(|| AppError::Config("FLOW_TELEMETRY_URL must be set".to_string()))?;
+        let counts = self.snapshot()?;
+        if counts.is_empty() {
+            return Ok(None);
+        }
+        let report = self.report_of(&counts)?;
+
+        client
+            .post(endpoint)
+            .json(&report)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))?;
+
+        self.forget(&counts)?;
+        Ok(Some(report))
+    }
+
+    /// Submit on the configured interval until the task is dropped
+    pub async fn run(self) {
+        let client = reqwest::Client::new();
+        let mut interval = tokio::time::interval(self.config.submit_interval);
+        // The first tick is immediate, before anything is counted
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match self.submit(&client).await {
+                Ok(Some(report)) => info!(
+                    "Submitted telemetry: {} features, {} error classes",
+                    report.features.len(),
+                    report.errors.len()
+                ),
+                Ok(None) => {}
+                Err(e) => warn!("Telemetry submission failed: {}", e),
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Result<Vec<(IVec, u64)>, AppError> {
+        self.counters()?
+            .iter()
+            .map(|entry| {
+                let (key, count) = entry.map_err(|e| AppError::Storage(Box::new(e)))?;
+                Ok((key, decode_count(Some(&count))))
+            })
+            .collect()
+    }
+
+    fn report_of(&self, counts: &[(IVec, u64)]) -> Result<TelemetryReport, AppError> {
+        let scale = 1.0 / self.config.epsilon;
+        let mut report = TelemetryReport {
+            schema: REPORT_SCHEMA.to_string(),
+            software_version: CURRENT_VERSION.to_string(),
+            since: self
+                .state()?
+                .get(SINCE_KEY)
+                .map_err(|e| AppError::Storage(Box::new(e)))?
+                .and_then(|since| std::str::from_utf8(&since).ok()?.parse().ok()),
+            features: BTreeMap::new(),
+            errors: BTreeMap::new(),
+        };
+        if counts.is_empty() {
+            return Ok(report);
+        }
+
+        let seed = self.seed()?;
+        for (key, count) in counts {
+            // Each count's noise depends on its key only, so it stays put
+            // while other counts come and go
+            let mut rng = StdRng::from_seed(*blake3::keyed_hash(&seed, key).as_bytes());
+            let noised = (*count as f64 + laplace(&mut rng, scale)).round();
+            if noised < 1.0 {
+                continue;
+            }
+            let key = String::from_utf8_lossy(key);
+            if let Some(feature) = key.strip_prefix(Counter::Feature.prefix()) {
+                report.features.insert(feature.to_string(), noised as u64);
+            } else if let Some(class) = key.strip_prefix(Counter::Error.prefix()) {
+                report.errors.insert(class.to_string(), noised as u64);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Take sent counts off, and start over with a new seed
+    fn forget(&self, counts: &[(IVec, u64)]) -> Result<(), AppError> {
+        let counters = self.counters()?;
+        for (key, sent) in counts {
+            counters
+                .update_and_fetch(key, |count| {
+                    let left = decode_count(count).saturating_sub(*sent);
+                    (left > 0).then(|| left.to_be_bytes().to_vec())
+                })
+                .map_err(|e| AppError::Storage(Box::new(e)))?;
+        }
+
+        let state = self.state()?;
+        state
+            .remove(SEED_KEY)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        state
+            .insert(SINCE_KEY, Utc::now().date_naive().to_string().as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Ok(())
+    }
+
+    /// Seed of the noise in the next report, made on first use
+    fn seed(&self) -> Result<[u8; 32], AppError> {
+        let mut fresh = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut fresh);
+        let seed = match self
+            .state()?
+            .compare_and_swap(SEED_KEY, None::<&[u8]>, Some(&fresh[..]))
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+        {
+            Ok(()) => return Ok(fresh),
+            Err(existing) => existing.current,
+        };
+        seed.and_then(|seed| seed.as_ref().try_into().ok())
+            .ok_or_else(|| AppError::Storage("Invalid telemetry seed".into()))
+    }
+
+    fn counters(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(COUNTER_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    fn state(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(STATE_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+fn decode_count(count: Option<&[u8]>) -> u64 {
+    count
+        .and_then(|count| count.try_into().ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or(0)
+}
+
+/// A draw from the Laplace distribution around 0 with `scale`
+fn laplace(rng: &mut impl Rng, scale: f64) -> f64 {
+    let u: f64 = rng.gen_range(-0.5..0.5);
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
@@ -0,0 +1,289 @@
+//! Multi-user mode.
+//!
+//! By default a node serves one person and every request acts for the node
+//! itself. With `FLOW_MULTI_USER` on, a passkey authentication opens a user
+//! session, and requests act for that user: they see only the spaces the
+//! user created and a KV namespace of their own, both subject to the
+//! per-user quotas in [`MultiUserConfig`]. Audit entries name the acting
+//! user.
+//!
+//! Spaces created before multi-user mode was turned on have no owner and are
+//! only visible to the node.
+
+use std::fmt;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use log::info;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+
+use crate::bootstrap::config::MultiUserConfig;
+use crate::bootstrap::init::NodeData;
+use crate::modules::manifest;
+use crate::modules::session::{SessionKind, SessionStore};
+use crate::modules::session_token::{self, SessionClaims};
+
+pub const USER_SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+const AUDIT: &str = "audit";
+
+/// Who an operation acts for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Actor {
+    /// The node itself: single-user mode, background tasks, admin tooling
+    Node,
+    User(i32),
+}
+
+impl Actor {
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            Actor::Node => None,
+            Actor::User(id) => Some(*id),
+        }
+    }
+
+    /// Whether the actor may see a resource owned by `owner`
+    pub fn can_access(&self, owner: Option<i32>) -> bool {
+        match self {
+            Actor::Node => true,
+            Actor::User(id) => owner == Some(*id),
+        }
+    }
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Actor::Node => f.write_str("node"),
+            Actor::User(id) => write!(f, "user:{}", id),
+        }
+    }
+}
+
+/// A signed-in user's session, as recorded in the session store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSession {
+    pub id: String,
+    pub user_id: i32,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Sessions opened by passkey authentication. Tokens are signed by the node
+/// (see [`session_token`]).
+#[derive(Clone)]
+pub struct UserSessions {
+    sessions: SessionStore,
+    node_data: NodeData,
+}
+
+impl UserSessions {
+    pub fn new(sessions: SessionStore, node_data: NodeData) -> Self {
+        Self {
+            sessions,
+            node_data,
+        }
+    }
+
+    /// Open a session for `user_id`, returning its bearer token
+    pub async fn open(&self, user_id: i32) -> Result<(String, UserSession), AppError> {
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+
+        // Whole seconds, as the token carries them
+        let issued_at =
+            DateTime::from_timestamp(self.sessions.time().now().timestamp(), 0).unwrap_or_default();
+        let session = UserSession {
+            id: URL_SAFE_NO_PAD.encode(id),
+            user_id,
+            issued_at,
+            expires_at: issued_at
+                + chrono::Duration::from_std(USER_SESSION_TTL).unwrap_or(chrono::Duration::zero()),
+        };
+        let token = session_token::sign(
+            &SessionClaims {
+                iss: self.node_data.id.clone(),
+                sub: user_id.to_string(),
+                sid: session.id.clone(),
+                iat: session.issued_at.timestamp(),
+                exp: session.expires_at.timestamp(),
+            },
+            &manifest::signing_key(&self.node_data)?,
+        )?;
+        self.sessions
+            .put(
+                SessionKind::UserSession,
+                &session.id,
+                &session,
+                USER_SESSION_TTL,
+            )
+            .await?;
+
+        info!(target: AUDIT, "{} opened a session until {}", Actor::User(user_id), session.expires_at);
+        Ok((token, session))
+    }
+
+    /// The session behind a token, if the token is genuine and the session
+    /// is still open
+    pub async fn find(&self, token: &str) -> Result<Option<UserSession>, AppError> {
+        let Some(claims) = self.verify(token)? else {
+            return Ok(None);
+        };
+        let session: Option<UserSession> = self
+            .sessions
+            .get(SessionKind::UserSession, &claims.sid)
+            .await?;
+        Ok(session.filter(|s| Some(s.user_id) == claims.user_id()))
+    }
+
+    /// The user behind a session token, if it is still valid
+    pub async fn actor(&self, token: &str) -> Result<Option<Actor>, AppError> {
+        Ok(self.find(token).await?.map(|s| Actor::User(s.user_id)))
+    }
+
+    /// Revoke the session behind a token. False if it wasn't open.
+    pub async fn close(&self, token: &str) -> Result<bool, AppError> {
+        let Some(session) = self.find(token).await? else {
+            return Ok(false);
+        };
+        self.sessions
+            .delete(SessionKind::UserSession, &session.id)
+            .await?;
+
+        info!(target: AUDIT, "{} closed session {}", Actor::User(session.user_id), session.id);
+        Ok(true)
+    }
+
+    /// Revoke every session of `user_id`, returning how many were open
+    pub async fn close_all(&self, user_id: i32) -> Result<usize, AppError> {
+        let sessions: Vec<(String, UserSession)> =
+            self.sessions.list(SessionKind::UserSession).await?;
+        let mut closed = 0;
+        for (id, session) in sessions {
+            if session.user_id == user_id {
+                self.sessions.delete(SessionKind::UserSession, &id).await?;
+                closed += 1;
+            }
+        }
+
+        info!(target: AUDIT, "{} sessions of {} closed", closed, Actor::User(user_id));
+        Ok(closed)
+    }
+
+    fn verify(&self, token: &str) -> Result<Option<SessionClaims>, AppError> {
+        let key = manifest::signing_key(&self.node_data)?.verifying_key();
+        Ok(session_token::verify_at(
+            token,
+            &key,
+            &self.node_data.id,
+            self.sessions.time(),
+        ))
+    }
+}
+
+/// An actor's own KV namespace
+pub struct ActorKv {
+    actor: Actor,
+    tree: sled::Tree,
+    quota_bytes: Option<u64>,
+}
+
+impl ActorKv {
+    /// The node's namespace is unlimited; users get the configured quota
+    pub fn open(kv: &Db, actor: Actor, config: &MultiUserConfig) -> Result<Self, AppError> {
+        let tree = kv
+            .open_tree(format!("kv:{}", actor))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        let quota_bytes = match actor {
+            Actor::Node => None,
+            Actor::User(_) => config.kv_quota_bytes,
+        };
+        Ok(Self {
+            actor,
+            tree,
+            quota_bytes,
+        })
+    }
+
+    /// Drop the whole namespace, returning how many keys it held
+    pub fn erase(self, kv: &Db) -> Result<usize, AppError> {
+        let keys = self.tree.len();
+        kv.drop_tree(format!("kv:{}", self.actor))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        info!(target: AUDIT, "{} KV namespace erased ({} keys)", self.actor, keys);
+        Ok(keys)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        Ok(self
+            .tree
+            .get(key.as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .map(|v| v.to_vec()))
+    }
+
+    pub fn put(&self, key: &str, value: &[u8]) -> Result<(), AppError> {
+        if let Some(quota) = self.quota_bytes {
+            let replaced = self
+                .get(key)?
+                .map(|old| (key.len() + old.len()) as u64)
+                .unwrap_or(0);
+            let usage = self.usage()? - replaced + (key.len() + value.len()) as u64;
+            if usage > quota {
+                return Err(AppError::Forbidden(format!(
+                    "KV quota of {} bytes exceeded",
+                    quota
+                )));
+            }
+        }
+
+        self.tree
+            .insert(key.as_bytes(), value)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        info!(target: AUDIT, "{} wrote KV key {}", self.actor, key);
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) -> Result<(), AppError> {
+        let removed = self
+            .tree
+            .remove(key.as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if removed.is_none() {
+            return Err(AppError::NotFound(format!("KV key not found: {}", key)));
+        }
+        info!(target: AUDIT, "{} removed KV key {}", self.actor, key);
+        Ok(())
+    }
+
+    pub fn keys(&self) -> Result<Vec<String>, AppError> {
+        self.tree
+            .iter()
+            .keys()
+            .map(|key| {
+                let key = key.map_err(|e| AppError::Storage(Box::new(e)))?;
+                Ok(String::from_utf8_lossy(&key).into_owned())
+            })
+            .collect()
+    }
+
+    /// Bytes stored, keys included
+    pub fn usage(&self) -> Result<u64, AppError> {
+        self.tree.iter().try_fold(0u64, |total, entry| {
+            let (key, value) = entry.map_err(|e| AppError::Storage(Box::new(e)))?;
+            Ok(total + (key.len() + value.len()) as u64)
+        })
+    }
+
+    pub fn quota_bytes(&self) -> Option<u64> {
+        self.quota_bytes
+    }
+}
@@ -0,0 +1,620 @@
+//! Trusted timestamps (RFC 3161) from an external timestamp authority.
+//!
+//! A self-issued anchor only shows what the node claims and when it says it
+//! made the claim. A timestamp authority (TSA) countersigns the SHA-256 of
+//! the data with its own time, so the data provably existed no later than
+//! that, whatever the node's clock said.
+//!
+//! Requests ask for the TSA's certificate and carry a random nonce. A proof
+//! keeps the TSA's token as returned; verifying it checks that the token
+//! covers the digest and carries a valid signature by the timestamping
+//! certificate embedded in it, found by issuer and serial number. That
+//! certificate must be one the node trusts: its SHA-256 is pinned in the
+//! configuration, as TSAs are few and their certificates long-lived.
+//!
+//! OpenTimestamps isn't supported: its proofs are only complete once a
+//! Bitcoin block commits to them, and checking that needs a block source.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use errors::AppError;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use sled::Db;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::bootstrap::config::TimestampConfig;
+use crate::modules::auth_crypto;
+
+/// Proofs requested through the API, by digest
+pub const PROOF_TREE: &str = "timestamps";
+
+pub const QUERY_CONTENT_TYPE: &str = "application/timestamp-query";
+pub const REPLY_CONTENT_TYPE: &str = "application/timestamp-reply";
+
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_TST_INFO: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04,
+];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const BOOLEAN: u8 = 0x01;
+const NULL: u8 = 0x05;
+const GENERALIZED_TIME: u8 = 0x18;
+const CONTEXT_0: u8 = 0xa0;
+
+/// A TSA's countersignature over a SHA-256 digest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimestampProof {
+    pub authority_url: String,
+    /// Hex SHA-256 of the timestamped data
+    pub digest: String,
+    /// Base64 DER of the RFC 3161 token (a CMS SignedData)
+    pub token: String,
+    pub gen_time: DateTime<Utc>,
+}
+
+/// What a valid token says
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimestampVerification {
+    pub gen_time: DateTime<Utc>,
+    /// Hex serial number the TSA gave the token
+    pub serial_number: String,
+    /// Dotted OID of the TSA policy
+    pub policy: String,
+    /// Subject of the certificate that signed the token
+    pub authority: String,
+    /// Hex SHA-256 of that certificate
+    pub certificate_sha256: String,
+}
+
+/// Client for one timestamp authority
+#[derive(Clone)]
+pub struct TimestampClient {
+    authority_url: String,
+    /// SHA-256 of the certificates its tokens may be signed with
+    trusted: Vec<[u8; 32]>,
+    client: reqwest::Client,
+}
+
+impl TimestampClient {
+    pub fn new(config: &TimestampConfig) -> Result<Self, AppError> {
+        let authority_url = config
+            .authority_url
+            .clone()
+            .ok_or_else(|| AppError::Config("No timestamp authority configured".to_string()))?;
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| AppError::Config(format!("Failed to build TSA client: {}", e)))?;
+        if config.trusted_certificates.is_empty() {
+            return Err(AppError::Config(
+                "The timestamp authority's certificate must be pinned".to_string(),
+            ));
+        }
+        Ok(Self {
+            authority_url,
+            trusted: config.trusted_certificates.clone(),
+            client,
+        })
+    }
+
+    /// Have the authority timestamp `digest`
+    pub async fn timestamp(&self, digest: &[u8; 32]) -> Result<TimestampProof, AppError> {
+        let nonce = OsRng.next_u64() >> 1;
+        let reply = self
+            .client
+            .post(&self.authority_url)
+            .header(reqwest::header::CONTENT_TYPE, QUERY_CONTENT_TYPE)
+            .header(reqwest::header::ACCEPT, REPLY_CONTENT_TYPE)
+            .body(request(digest, nonce))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+            .bytes()
+            .await
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))?;
+
+        let token = parse_reply(&reply)?;
+        let tst_info = read_token(&token)?.tst_info;
+        if !tst_info
+            .nonce
+            .is_some_and(|sent| auth_crypto::eq(sent, encode_unsigned(nonce)))
+        {
+            return Err(AppError::Crypto(
+                "Timestamp reply does not answer this request".to_string(),
+            ));
+        }
+        let verification = verify(&token, digest, &self.trusted)?;
+
+        Ok(TimestampProof {
+            authority_url: self.authority_url.clone(),
+            digest: hex(digest),
+            token: STANDARD.encode(&token),
+            gen_time: verification.gen_time,
+        })
+    }
+}
+
+/// Check a proof against its own digest, trusting tokens signed with one of
+/// the `trusted` certificates
+pub fn verify_proof(
+    proof: &TimestampProof,
+    trusted: &[[u8; 32]],
+) -> Result<TimestampVerification, AppError> {
+    let digest = parse_digest(&proof.digest)?;
+    let token = STANDARD
+        .decode(&proof.token)
+        .map_err(|e| AppError::Validation(format!("Invalid timestamp token: {}", e)))?;
+    verify(&token, &digest, trusted)
+}
+
+/// A hex SHA-256, e.g. a certificate fingerprint
+pub fn parse_digest(digest: &str) -> Result<[u8; 32], AppError> {
+    decode_hex(digest)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| AppError::Validation("Digest must be 64 hex digits (SHA-256)".to_string()))
+}
+
+pub fn save_proof(kv: &Db, proof: &TimestampProof) -> Result<(), AppError> {
+    let json = serde_json::to_vec(proof).map_err(|e| AppError::Storage(Box::new(e)))?;
+    kv.open_tree(PROOF_TREE)
+        .and_then(|tree| tree.insert(proof.digest.as_bytes(), json))
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(())
+}
+
+pub fn load_proof(kv: &Db, digest: &str) -> Result<Option<TimestampProof>, AppError> {
+    let value = kv
+        .open_tree(PROOF_TREE)
+        .and_then(|tree| tree.get(digest.to_ascii_lowercase().as_bytes()))
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    value
+        .map(|bytes| serde_json::from_slice(&bytes).map_err(|e| AppError::Storage(Box::new(e))))
+        .transpose()
+}
+
+/// DER `TimeStampReq` for a SHA-256 digest, asking for the TSA certificate
+pub fn request(digest: &[u8; 32], nonce: u64) -> Vec<u8> {
+    let algorithm = tlv(SEQUENCE, &[tlv(OID, OID_SHA256), tlv(NULL, &[])].concat());
+    let imprint = tlv(SEQUENCE, &[algorithm, tlv(OCTET_STRING, digest)].concat());
+    tlv(
+        SEQUENCE,
+        &[
+            tlv(INTEGER, &[1]),
+            imprint,
+            tlv(INTEGER, &encode_unsigned(nonce)),
+            tlv(BOOLEAN, &[0xff]),
+        ]
+        .concat(),
+    )
+}
+
+/// The token of a DER `TimeStampResp`, if the TSA granted the request
+pub fn parse_reply(reply: &[u8]) -> Result<Vec<u8>, AppError> {
+    let resp = children(expect(reply, SEQUENCE)?)?;
+    let status_info = children(expect_tlv(resp.first(), SEQUENCE)?)?;
+    let status = expect_tlv(status_info.first(), INTEGER)?;
+    // 0 granted, 1 granted with modifications
+    if !matches!(status, [0] | [1]) {
+        let reason = status_info
+            .get(1)
+            .filter(|t| t.tag == SEQUENCE)
+            .and_then(|t| children(t.content).ok())
+            .and_then(|texts| {
+                texts
+                    .first()
+                    .map(|t| String::from_utf8_lossy(t.content).into())
+            })
+            .unwrap_or_else(|| format!("status {:?}", status));
+        return Err(AppError::Crypto(format!(
+            "Timestamp authority refused the request: {}",
+            reason
+        )));
+    }
+    resp.get(1)
+        .map(|token| token.raw.to_vec())
+        .ok_or_else(|| AppError::Crypto("Timestamp reply has no token".to_string()))
+}
+
+/// Check that a DER token covers `digest` and its signature holds, by one of
+/// the `trusted` certificates (their SHA-256)
+pub fn verify(
+    token: &[u8],
+    digest: &[u8; 32],
+    trusted: &[[u8; 32]],
+) -> Result<TimestampVerification, AppError> {
+    let token = read_token(token)?;
+    let tst_info = &token.tst_info;
+    if tst_info.imprint_algorithm != OID_SHA256 || !auth_crypto::eq(tst_info.imprint, digest) {
+        return Err(AppError::Validation(
+            "Timestamp token is for different data".to_string(),
+        ));
+    }
+
+    let signer = &token.signer;
+    let message_digest = signer
+        .message_digest
+        .as_deref()
+        .ok_or_else(|| malformed("no message digest"))?;
+    if !auth_crypto::eq(
+        message_digest,
+        hash(&signer.digest_algorithm, token.e_content)?,
+    ) {
+        return Err(AppError::Validation(
+            "Timestamp token content was altered".to_string(),
+        ));
+    }
+
+    let certificate = token
+        .certificates
+        .iter()
+        .filter_map(|der| Some((*der, X509Certificate::from_der(der).ok()?.1)))
+        .find(|(_, cert)| {
+            cert.issuer().as_raw() == signer.issuer && cert.raw_serial() == signer.serial
+        })
+        .ok_or_else(|| {
+            AppError::Validation("Timestamp token does not include its certificate".to_string())
+        })?;
+    let (certificate_der, certificate) = certificate;
+    let certificate_sha256: [u8; 32] = Sha256::digest(certificate_der).into();
+    if !trusted.contains(&certificate_sha256) {
+        return Err(AppError::Validation(format!(
+            "Timestamp token was signed by an untrusted certificate ({})",
+            certificate.subject()
+        )));
+    }
+    if !certificate
+        .extended_key_usage()
+        .ok()
+        .flatten()
+        .is_some_and(|eku| eku.value.time_stamping)
+    {
+        return Err(AppError::Validation(
+            "Timestamp token was not signed by a timestamping certificate".to_string(),
+        ));
+    }
+
+    let spki = certificate.public_key();
+    let key_algorithm = spki.algorithm.algorithm.as_bytes();
+    let algorithm = signature_algorithm(
+        &signer.digest_algorithm,
+        key_algorithm,
+        spki.subject_public_key.data.len(),
+    )?;
+    UnparsedPublicKey::new(algorithm, &spki.subject_public_key.data)
+        .verify(&signer.signed_attrs, signer.signature)
+        .map_err(|_| AppError::Validation("Invalid timestamp token signature".to_string()))?;
+
+    Ok(TimestampVerification {
+        gen_time: tst_info.gen_time,
+        serial_number: hex(tst_info.serial),
+        policy: dotted_oid(tst_info.policy),
+        authority: certificate.subject().to_string(),
+        certificate_sha256: hex(&certificate_sha256),
+    })
+}
+
+struct Token<'a> {
+    e_content: &'a [u8],
+    tst_info: TstInfo<'a>,
+    certificates: Vec<&'a [u8]>,
+    signer: Signer<'a>,
+}
+
+struct TstInfo<'a> {
+    policy: &'a [u8],
+    imprint_algorithm: &'a [u8],
+    imprint: &'a [u8],
+    serial: &'a [u8],
+    gen_time: DateTime<Utc>,
+    nonce: Option<Vec<u8>>,
+}
+
+struct Signer<'a> {
+    /// DER of the certificate issuer's name
+    issuer: &'a [u8],
+    serial: &'a [u8],
+    digest_algorithm: Vec<u8>,
+    /// DER of the signed attributes as signed, i.e. tagged as a SET
+    signed_attrs: Vec<u8>,
+    message_digest: Option<Vec<u8>>,
+    signature: &'a [u8],
+}
+
+fn read_token(der: &[u8]) -> Result<Token<'_>, AppError> {
+    let content_info = children(expect(der, SEQUENCE)?)?;
+    if expect_tlv(content_info.first(), OID)? != OID_SIGNED_DATA {
+        return Err(malformed("not a SignedData"));
+    }
+    let signed_data = children(expect_tlv(content_info.get(1), CONTEXT_0)?)?;
+    let signed_data = children(expect_tlv(signed_data.first(), SEQUENCE)?)?;
+
+    let encap = children(expect_tlv(signed_data.get(2), SEQUENCE)?)?;
+    if expect_tlv(encap.first(), OID)? != OID_TST_INFO {
+        return Err(malformed("content is not a TSTInfo"));
+    }
+    let e_content = children(expect_tlv(encap.get(1), CONTEXT_0)?)?;
+    let e_content = expect_tlv(e_content.first(), OCTET_STRING)?;
+
+    let certificates = signed_data
+        .iter()
+        .skip(3)
+        .find(|t| t.tag == CONTEXT_0)
+        .map(|t| children(t.content))
+        .transpose()?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|cert| cert.raw)
+        .collect();
+    let signer_infos = signed_data
+        .last()
+        .filter(|t| t.tag == SET)
+        .ok_or_else(|| malformed("no signer infos"))?;
+    let signer_info = children(signer_infos.content)?;
+
+    Ok(Token {
+        e_content,
+        tst_info: read_tst_info(e_content)?,
+        certificates,
+        signer: read_signer(expect_tlv(signer_info.first(), SEQUENCE)?)?,
+    })
+}
+
+fn read_tst_info(der: &[u8]) -> Result<TstInfo<'_>, AppError> {
+    let fields = children(expect(der, SEQUENCE)?)?;
+    let imprint = children(expect_tlv(fields.get(2), SEQUENCE)?)?;
+    let imprint_algorithm = children(expect_tlv(imprint.first(), SEQUENCE)?)?;
+    let gen_time = std::str::from_utf8(expect_tlv(fields.get(4), GENERALIZED_TIME)?)
+        .ok()
+        .and_then(parse_generalized_time)
+        .ok_or_else(|| malformed("invalid genTime"))?;
+    // After genTime: accuracy, ordering, then the nonce, all optional
+    let nonce = fields
+        .iter()
+        .skip(5)
+        .find(|t| t.tag == INTEGER)
+        .map(|t| t.content.to_vec());
+
+    Ok(TstInfo {
+        policy: expect_tlv(fields.get(1), OID)?,
+        imprint_algorithm: expect_tlv(imprint_algorithm.first(), OID)?,
+        imprint: expect_tlv(imprint.get(1), OCTET_STRING)?,
+        serial: expect_tlv(fields.get(3), INTEGER)?,
+        gen_time,
+        nonce,
+    })
+}
+
+fn read_signer(der: &[u8]) -> Result<Signer<'_>, AppError> {
+    let fields = children(der)?;
+    let sid = fields
+        .get(1)
+        .filter(|t| t.tag == SEQUENCE)
+        .ok_or_else(|| malformed("signer is not identified by issuer and serial"))?;
+    let sid = children(sid.content)?;
+    let digest_algorithm = children(expect_tlv(fields.get(2), SEQUENCE)?)?;
+    let signed_attrs = fields
+        .get(3)
+        .filter(|t| t.tag == CONTEXT_0)
+        .ok_or_else(|| malformed("no signed attributes"))?;
+
+    let message_digest = children(signed_attrs.content)?
+        .into_iter()
+        .filter_map(|attr| children(attr.content).ok())
+        .find(|attr| {
+            attr.first()
+                .is_some_and(|t| t.content == OID_MESSAGE_DIGEST)
+        })
+        .and_then(|attr| children(attr.get(1)?.content).ok())
+        .and_then(|values| Some(values.first()?.content.to_vec()));
+
+    Ok(Signer {
+        issuer: sid
+            .first()
+            .filter(|t| t.tag == SEQUENCE)
+            .map(|t| t.raw)
+            .ok_or_else(|| malformed("signer has no issuer"))?,
+        serial: expect_tlv(sid.get(1), INTEGER)?,
+        digest_algorithm: expect_tlv(digest_algorithm.first(), OID)?.to_vec(),
+        signed_attrs: tlv(SET, signed_attrs.content),
+        message_digest,
+        signature: expect_tlv(fields.get(5), OCTET_STRING)?,
+    })
+}
+
+fn hash(algorithm: &[u8], data: &[u8]) -> Result<Vec<u8>, AppError> {
+    match algorithm {
+        OID_SHA256 => Ok(Sha256::digest(data).to_vec()),
+        OID_SHA384 => Ok(Sha384::digest(data).to_vec()),
+        OID_SHA512 => Ok(Sha512::digest(data).to_vec()),
+        _ => Err(AppError::Validation(
+            "Unsupported timestamp digest algorithm".to_string(),
+        )),
+    }
+}
+
+/// The signature scheme for a signer's digest and certificate key
+fn signature_algorithm(
+    digest: &[u8],
+    key_algorithm: &[u8],
+    key_len: usize,
+) -> Result<&'static dyn VerificationAlgorithm, AppError> {
+    const RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+    const EC: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+
+    Ok(match (key_algorithm, digest, key_len) {
+        (RSA, OID_SHA256, _) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (RSA, OID_SHA384, _) => &signature::RSA_PKCS1_2048_8192_SHA384,
+        (RSA, OID_SHA512, _) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        // Uncompressed P-256 and P-384 points
+        (EC, OID_SHA256, 65) => &signature::ECDSA_P256_SHA256_ASN1,
+        (EC, OID_SHA384, 65) => &signature::ECDSA_P256_SHA384_ASN1,
+        (EC, OID_SHA256, 97) => &signature::ECDSA_P384_SHA256_ASN1,
+        (EC, OID_SHA384, 97) => &signature::ECDSA_P384_SHA384_ASN1,
+        (OID_ED25519, _, _) => &signature::ED25519,
+        _ => {
+            return Err(AppError::Validation(
+                "Unsupported timestamp signature algorithm".to_string(),
+            ));
+        }
+    })
+}
+
+fn parse_generalized_time(time: &str) -> Option<DateTime<Utc>> {
+    let time = time.strip_suffix('Z')?;
+    let (whole, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut parsed = NaiveDateTime::parse_from_str(whole, "%Y%m%d%H%M%S").ok()?;
+    if !fraction.is_empty() {
+        let nanos: u32 = format!("{:0<9}", fraction).get(..9)?.parse().ok()?;
+        parsed += chrono::Duration::nanoseconds(nanos.into());
+    }
+    Some(parsed.and_utc())
+}
+
+/// Dotted form of a DER OID
+fn dotted_oid(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut value: u64 = 0;
+    for byte in oid {
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (value / 40).min(2);
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        }
+    }
+    arcs.iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// Just enough DER for the structures above
+
+struct Tlv<'a> {
+    tag: u8,
+    content: &'a [u8],
+    raw: &'a [u8],
+}
+
+fn read(input: &[u8]) -> Option<(Tlv<'_>, &[u8])> {
+    let tag = *input.first()?;
+    let first = *input.get(1)?;
+    let (len, header) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = input
+            .get(2..2 + count)?
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | usize::from(*b));
+        (len, 2 + count)
+    };
+    let end = header.checked_add(len)?;
+    let raw = input.get(..end)?;
+    Some((
+        Tlv {
+            tag,
+            content: &raw[header..],
+            raw,
+        },
+        &input[end..],
+    ))
+}
+
+fn children(mut content: &[u8]) -> Result<Vec<Tlv<'_>>, AppError> {
+    let mut items = Vec::new();
+    while !content.is_empty() {
+        let (item, rest) = read(content).ok_or_else(|| malformed("truncated"))?;
+        items.push(item);
+        content = rest;
+    }
+    Ok(items)
+}
+
+fn expect(der: &[u8], tag: u8) -> Result<&[u8], AppError> {
+    match read(der) {
+        Some((tlv, [])) if tlv.tag == tag => Ok(tlv.content),
+        _ => Err(malformed("unexpected structure")),
+    }
+}
+
+fn expect_tlv<'a>(tlv: Option<&Tlv<'a>>, tag: u8) -> Result<&'a [u8], AppError> {
+    tlv.filter(|t| t.tag == tag)
+        .map(|t| t.content)
+        .ok_or_else(|| malformed("unexpected structure"))
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Content octets of a non-negative DER INTEGER
+fn encode_unsigned(value: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|b| *b == 0)
+        .collect();
+    if bytes.first().is_none_or(|b| b & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    bytes
+}
+
+fn malformed(what: &str) -> AppError {
+    AppError::Validation(format!("Malformed timestamp token: {}", what))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,315 @@
+//! Opt-in self-updater. Polls a release endpoint for a signed release
+//! manifest, verifies it against a pinned publisher key, downloads the binary
+//! and stages it. The staged binary replaces the running one on next start.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use errors::AppError;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sled::Db;
+
+use crate::bootstrap::config::UpdateConfig;
+use crate::modules::auth_crypto;
+
+pub const UPDATE_STATUS_KEY: &str = "updater:status";
+pub const UPDATES_DIR: &str = "updates";
+const STAGED_FILE: &str = "staged.json";
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub url: String,
+    /// blake3 hex digest of the binary
+    pub blake3: String,
+    pub size: u64,
+    pub published_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRelease {
+    pub release: ReleaseManifest,
+    /// Multibase (base58btc) Ed25519 signature over the canonical release JSON
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum UpdateState {
+    UpToDate,
+    Staged { version: String },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateStatus {
+    pub current_version: String,
+    pub latest_version: Option<String>,
+    #[serde(flatten)]
+    pub state: UpdateState,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StagedUpdate {
+    version: String,
+    path: PathBuf,
+    blake3: String,
+}
+
+/// Decode a multibase Ed25519 public key (`z6Mk...`)
+pub fn decode_publisher_key(key: &str) -> Result<VerifyingKey, AppError> {
+    let (_, bytes) = multibase::decode(key)
+        .map_err(|e| AppError::Config(format!("Invalid publisher key encoding: {}", e)))?;
+    let raw: [u8; 32] = bytes
+        .strip_prefix(&[0xed, 0x01])
+        .unwrap_or(&bytes)
+        .try_into()
+        .map_err(|_| AppError::Config("Publisher key must be an Ed25519 key".to_string()))?;
+    VerifyingKey::from_bytes(&raw)
+        .map_err(|e| AppError::Config(format!("Invalid publisher key: {}", e)))
+}
+
+/// Verify a release was signed by the pinned publisher key
+pub fn verify_release(signed: &SignedRelease, publisher: &VerifyingKey) -> Result<(), AppError> {
+    let (_, sig_bytes) = multibase::decode(&signed.signature)
+        .map_err(|e| AppError::Validation(format!("Invalid signature encoding: {}", e)))?;
+    let signature = Signature::from_slice(&sig_bytes)
+        .map_err(|e| AppError::Validation(format!("Invalid signature: {}", e)))?;
+    let message = serde_json::to_vec(&signed.release)
+        .map_err(|e| AppError::Validation(format!("Invalid release manifest: {}", e)))?;
+
+    publisher
+        .verify(&message, &signature)
+        .map_err(|_| AppError::Crypto("Release signature does not match publisher key".to_string()))
+}
+
+/// Whether `candidate` is a strictly newer semantic version than `current`
+pub fn is_newer(candidate: &str, current: &str) -> Result<bool, AppError> {
+    let parse = |v: &str| {
+        semver::Version::parse(v.trim_start_matches('v'))
+            .map_err(|e| AppError::Validation(format!("Invalid version {}: {}", v, e)))
+    };
+    Ok(parse(candidate)? > parse(current)?)
+}
+
+pub struct Updater {
+    config: UpdateConfig,
+    publisher: VerifyingKey,
+    endpoint: String,
+    updates_dir: PathBuf,
+    kv: Db,
+    client: reqwest::Client,
+}
+
+impl Updater {
+    pub fn new(config: UpdateConfig, config_dir: &Path, kv: Db) -> Result<Self, AppError> {
+        let endpoint = config
+            .endpoint
+            .clone()
+            .ok_or_else(|| AppError::Config("FLOW_UPDATE_URL must be set".to_string()))?;
+        let publisher =
+            decode_publisher_key(config.publisher_key.as_deref().ok_or_else(|| {
+                AppError::Config("FLOW_UPDATE_PUBLIC_KEY must be set".to_string())
+            })?)?;
+
+        Ok(Self {
+            config,
+            publisher,
+            endpoint,
+            updates_dir: config_dir.join(UPDATES_DIR),
+            kv,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Check for an update now, staging it if one is available, and record
+    /// the outcome
+    pub async fn check(&self) -> UpdateStatus {
+        let (latest_version, state) = match self.check_inner().await {
+            Ok((latest, state)) => (Some(latest), state),
+            Err(e) => {
+                warn!("Update check failed: {}", e);
+                (
+                    None,
+                    UpdateState::Failed {
+                        error: e.to_string(),
+                    },
+                )
+            }
+        };
+
+        let status = UpdateStatus {
+            current_version: CURRENT_VERSION.to_string(),
+            latest_version,
+            state,
+            checked_at: Utc::now(),
+        };
+        if let Err(e) = save_status(&self.kv, &status) {
+            error!("Failed to record update status: {}", e);
+        }
+        status
+    }
+
+    async fn check_inner(&self) -> Result<(String, UpdateState), AppError> {
+        let signed: SignedRelease = self
+            .client
+            .get(&self.endpoint)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::Validation(format!("Invalid release manifest: {}", e)))?;
+
+        verify_release(&signed, &self.publisher)?;
+        let release = signed.release;
+
+        if !is_newer(&release.version, CURRENT_VERSION)? {
+            return Ok((release.version, UpdateState::UpToDate));
+        }
+        if staged_version(&self.updates_dir).as_deref() == Some(release.version.as_str()) {
+            let version = release.version.clone();
+            return Ok((release.version, UpdateState::Staged { version }));
+        }
+
+        info!(
+            "Downloading update {} from {}",
+            release.version, release.url
+        );
+        let binary = self
+            .client
+            .get(&release.url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))?
+            .bytes()
+            .await
+            .map_err(|e| AppError::IO(std::io::Error::other(e)))?;
+
+        stage(&self.updates_dir, &release, &binary)?;
+        let version = release.version.clone();
+        Ok((release.version, UpdateState::Staged { version }))
+    }
+
+    /// Check on the configured interval until the task is dropped
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.config.check_interval);
+        loop {
+            interval.tick().await;
+            self.check().await;
+        }
+    }
+}
+
+/// Verify a downloaded binary against its release manifest and stage it
+pub fn stage(
+    updates_dir: &Path,
+    release: &ReleaseManifest,
+    binary: &[u8],
+) -> Result<PathBuf, AppError> {
+    if binary.len() as u64 != release.size {
+        return Err(AppError::Validation(format!(
+            "Downloaded binary is {} bytes, expected {}",
+            binary.len(),
+            release.size
+        )));
+    }
+    let digest = blake3::hash(binary).to_hex().to_string();
+    if !digest.eq_ignore_ascii_case(&release.blake3) {
+        return Err(AppError::Crypto(
+            "Downloaded binary does not match release digest".to_string(),
+        ));
+    }
+
+    fs::create_dir_all(updates_dir)?;
+    let path = updates_dir.join(format!("node-{}", release.version));
+    fs::write(&path, binary)?;
+
+    let staged = StagedUpdate {
+        version: release.version.clone(),
+        path: path.clone(),
+        blake3: digest,
+    };
+    let json = serde_json::to_vec_pretty(&staged).map_err(|e| AppError::Storage(Box::new(e)))?;
+    fs::write(updates_dir.join(STAGED_FILE), json)?;
+
+    info!("Staged update {} at {}", release.version, path.display());
+    Ok(path)
+}
+
+fn read_staged(updates_dir: &Path) -> Option<StagedUpdate> {
+    let json = fs::read(updates_dir.join(STAGED_FILE)).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Version currently waiting to be applied, if any
+pub fn staged_version(updates_dir: &Path) -> Option<String> {
+    read_staged(updates_dir).map(|s| s.version)
+}
+
+/// Replace `executable` with the staged binary, keeping the old one alongside
+/// as `<name>.previous`. Returns the version applied.
+pub fn apply_staged(updates_dir: &Path, executable: &Path) -> Result<Option<String>, AppError> {
+    let Some(staged) = read_staged(updates_dir) else {
+        return Ok(None);
+    };
+
+    let binary = fs::read(&staged.path)?;
+    if !auth_crypto::eq(blake3::hash(&binary).to_hex().as_str(), &staged.blake3) {
+        fs::remove_file(updates_dir.join(STAGED_FILE))?;
+        return Err(AppError::Crypto(format!(
+            "Staged update {} was modified on disk, discarding it",
+            staged.version
+        )));
+    }
+
+    let previous = executable.with_extension("previous");
+    fs::rename(executable, &previous)?;
+    if let Err(e) = fs::write(executable, &binary) {
+        fs::rename(&previous, executable)?;
+        return Err(e.into());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(executable, fs::Permissions::from_mode(0o755))?;
+    }
+
+    fs::remove_file(updates_dir.join(STAGED_FILE))?;
+    let _ = fs::remove_file(&staged.path);
+
+    info!(
+        "Applied update {} to {}",
+        staged.version,
+        executable.display()
+    );
+    Ok(Some(staged.version))
+}
+
+fn save_status(kv: &Db, status: &UpdateStatus) -> Result<(), AppError> {
+    let json = serde_json::to_vec(status).map_err(|e| AppError::Storage(Box::new(e)))?;
+    kv.insert(UPDATE_STATUS_KEY, json)
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(())
+}
+
+/// Outcome of the last update check, if one has run
+pub fn load_status(kv: &Db) -> Result<Option<UpdateStatus>, AppError> {
+    kv.get(UPDATE_STATUS_KEY)
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .map(|bytes| serde_json::from_slice(&bytes).map_err(|e| AppError::Storage(Box::new(e))))
+        .transpose()
+}
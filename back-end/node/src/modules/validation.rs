@@ -0,0 +1,183 @@
+//! Rules for identifiers clients supply.
+//!
+//! Each identifier has a newtype that only deserializes from a valid value,
+//! so REST handlers taking one never see malformed or oversized input, and
+//! parsers further down don't have to cope with it.
+
+use std::fmt;
+
+use errors::AppError;
+use serde::{Deserialize, Serialize};
+
+/// Longest DID accepted; long did:peer:2 documents stay well under this
+pub const MAX_DID_LEN: usize = 4096;
+pub const MAX_KV_KEY_LEN: usize = 256;
+pub const MAX_PATH_LEN: usize = 1024;
+pub const MAX_PATH_SEGMENT_LEN: usize = 255;
+pub const MIN_USERNAME_LEN: usize = 3;
+pub const MAX_USERNAME_LEN: usize = 32;
+
+fn invalid(what: &str, reason: impl fmt::Display) -> AppError {
+    AppError::Validation(format!("Invalid {}: {}", what, reason))
+}
+
+/// `did:<method>:<method-specific-id>` per DID Core, at most [`MAX_DID_LEN`]
+pub fn validate_did(did: &str) -> Result<(), AppError> {
+    if did.len() > MAX_DID_LEN {
+        return Err(invalid("DID", format!("longer than {} bytes", MAX_DID_LEN)));
+    }
+    let mut parts = did.splitn(3, ':');
+    let (Some("did"), Some(method), Some(id)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("DID", "expected did:<method>:<id>"));
+    };
+    if method.is_empty()
+        || !method
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+    {
+        return Err(invalid("DID", format!("bad method name '{}'", method)));
+    }
+    let id_char = |b: u8| b.is_ascii_alphanumeric() || b".-_:%".contains(&b);
+    if id.is_empty() || id.ends_with(':') || !id.bytes().all(id_char) {
+        return Err(invalid("DID", "bad method-specific id"));
+    }
+    Ok(())
+}
+
+/// Space keys are the lowercase hex SHA-256 of the space's directory
+pub fn validate_space_key(key: &str) -> Result<(), AppError> {
+    if key.len() != 64 || !key.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(invalid("space key", "expected 64 lowercase hex digits"));
+    }
+    Ok(())
+}
+
+/// Non-empty, at most [`MAX_KV_KEY_LEN`] bytes, no control characters
+pub fn validate_kv_key(key: &str) -> Result<(), AppError> {
+    if key.is_empty() || key.len() > MAX_KV_KEY_LEN {
+        return Err(invalid(
+            "KV key",
+            format!("must be 1 to {} bytes", MAX_KV_KEY_LEN),
+        ));
+    }
+    if key.chars().any(char::is_control) {
+        return Err(invalid("KV key", "contains control characters"));
+    }
+    Ok(())
+}
+
+/// Relative file path of `/`-separated segments, none of them empty, `.`
+/// or `..`, and no backslashes or control characters
+pub fn validate_file_path(path: &str) -> Result<(), AppError> {
+    if path.is_empty() || path.len() > MAX_PATH_LEN {
+        return Err(invalid(
+            "file path",
+            format!("must be 1 to {} bytes", MAX_PATH_LEN),
+        ));
+    }
+    for segment in path.trim_start_matches('/').split('/') {
+        validate_path_segment(segment)?;
+    }
+    Ok(())
+}
+
+pub fn validate_path_segment(segment: &str) -> Result<(), AppError> {
+    if segment.is_empty() || segment == "." || segment == ".." {
+        return Err(invalid("path segment", format!("'{}'", segment)));
+    }
+    if segment.len() > MAX_PATH_SEGMENT_LEN {
+        return Err(invalid(
+            "path segment",
+            format!("longer than {} bytes", MAX_PATH_SEGMENT_LEN),
+        ));
+    }
+    if segment.chars().any(|c| c.is_control() || c == '\\') {
+        return Err(invalid(
+            "path segment",
+            "contains control characters or backslashes",
+        ));
+    }
+    Ok(())
+}
+
+/// Lowercase letters, digits, `-`, `_` and `.`, starting with a letter,
+/// [`MIN_USERNAME_LEN`] to [`MAX_USERNAME_LEN`] long
+pub fn validate_username(name: &str) -> Result<(), AppError> {
+    if !(MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&name.len()) {
+        return Err(invalid(
+            "username",
+            format!(
+                "must be {} to {} characters",
+                MIN_USERNAME_LEN, MAX_USERNAME_LEN
+            ),
+        ));
+    }
+    if !name.starts_with(|c: char| c.is_ascii_lowercase())
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"-_.".contains(&b))
+    {
+        return Err(invalid(
+            "username",
+            "use lowercase letters, digits, '-', '_' and '.', starting with a letter",
+        ));
+    }
+    Ok(())
+}
+
+/// Newtype over `String` that only deserializes from values `$validate` accepts
+macro_rules! validated {
+    ($(#[$doc:meta])* $name:ident, $validate:path) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(try_from = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_inner(self) -> String {
+                self.0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = AppError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                $validate(&value)?;
+                Ok(Self(value))
+            }
+        }
+
+        impl std::ops::Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    };
+}
+
+validated!(
+    /// A syntactically valid DID
+    Did,
+    validate_did
+);
+validated!(SpaceKey, validate_space_key);
+validated!(KvKey, validate_kv_key);
+validated!(
+    /// A file path within a space
+    FilePath,
+    validate_file_path
+);
+validated!(Username, validate_username);
@@ -0,0 +1,141 @@
+//! Optimistic concurrency for rows clients edit.
+//!
+//! Users, spaces and passkeys carry a `version`, bumped by every edit. A
+//! client sends the version it read along with its edit; if the row has moved
+//! on since, the edit is refused with [`AppError::Conflict`] (409) rather
+//! than silently overwriting the other one, and the client reads the row
+//! again. Edits sent without a version apply whatever the row is at.
+//!
+//! Bookkeeping the node does itself (sign-in counters, Merkle roots) leaves
+//! the version alone, so it never makes a client's copy stale.
+//!
+//! Edits to rows that keep a history are recorded there along the way (see
+//! [`crate::modules::history`]).
+
+use entity::{pass_key, space, user};
+use errors::AppError;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, TransactionTrait};
+use serde::Serialize;
+
+use crate::modules::history::{self, Change, Subject};
+
+/// An entity whose rows carry a `version`
+pub trait Versioned: EntityTrait {
+    /// What a row is called in errors
+    const NAME: &'static str;
+
+    fn id_column() -> Self::Column;
+
+    fn version_column() -> Self::Column;
+
+    /// Whose history edits to `model` are recorded in; none by default
+    fn subject(_model: &Self::Model) -> Option<Subject> {
+        None
+    }
+}
+
+impl Versioned for user::Entity {
+    const NAME: &'static str = "User";
+
+    fn id_column() -> Self::Column {
+        user::Column::Id
+    }
+
+    fn version_column() -> Self::Column {
+        user::Column::Version
+    }
+
+    fn subject(model: &Self::Model) -> Option<Subject> {
+        Some(model.into())
+    }
+}
+
+impl Versioned for space::Entity {
+    const NAME: &'static str = "Space";
+
+    fn id_column() -> Self::Column {
+        space::Column::Id
+    }
+
+    fn version_column() -> Self::Column {
+        space::Column::Version
+    }
+
+    fn subject(model: &Self::Model) -> Option<Subject> {
+        Some(model.into())
+    }
+}
+
+impl Versioned for pass_key::Entity {
+    const NAME: &'static str = "Passkey";
+
+    fn id_column() -> Self::Column {
+        pass_key::Column::Id
+    }
+
+    fn version_column() -> Self::Column {
+        pass_key::Column::Version
+    }
+}
+
+/// Write the columns set in `changes` to row `id` and bump its version, if
+/// the row is still at version `expected`; any version will do if that is
+/// `None`. The edit and its history entry commit together. `changes` should
+/// set only the columns edited, e.g.
+/// `ActiveModel { name: Set(..), ..Default::default() }`. Answers with the
+/// updated row.
+pub async fn update_versioned<E: Versioned>(
+    db: &(impl ConnectionTrait + TransactionTrait),
+    id: i32,
+    expected: Option<i32>,
+    changes: E::ActiveModel,
+) -> Result<E::Model, AppError>
+where
+    E::Model: Serialize,
+{
+    let txn = db
+        .begin()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    let before = find::<E>(&txn, id).await?;
+
+    let mut update = E::update_many()
+        .set(changes)
+        .col_expr(E::version_column(), Expr::col(E::version_column()).add(1))
+        .filter(E::id_column().eq(id));
+    if let Some(version) = expected {
+        update = update.filter(E::version_column().eq(version));
+    }
+    let updated = update
+        .exec(&txn)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .rows_affected;
+    if let (0, Some(version)) = (updated, expected) {
+        return Err(AppError::Conflict(format!(
+            "{} {} was changed since version {}; read it again",
+            E::NAME,
+            id,
+            version
+        )));
+    }
+
+    let after = find::<E>(&txn, id).await?;
+    if let Some(subject) = E::subject(&after) {
+        history::record(&txn, &subject, Change::Updated, Some(&before), Some(&after)).await?;
+    }
+    txn.commit()
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?;
+    Ok(after)
+}
+
+async fn find<E: Versioned>(conn: &impl ConnectionTrait, id: i32) -> Result<E::Model, AppError> {
+    E::find()
+        .filter(E::id_column().eq(id))
+        .one(conn)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .ok_or_else(|| AppError::NotFound(format!("{} not found: {}", E::NAME, id)))
+}
@@ -0,0 +1,305 @@
+//! Outgoing webhooks for node events.
+//!
+//! A registration names a URL and, optionally, the event types it wants, a
+//! space key and a path glob. Space events carry `space_key` and `path`
+//! (relative to the space root) properties; a registration with a glob only
+//! receives events whose path matches it, so automation watching e.g.
+//! `incoming/**/*.pdf` isn't woken for every file in the space. Space scans
+//! publish such events for each file they find created, modified or deleted.
+//!
+//! Registrations live in the KV store, and in memory with their globs
+//! compiled once they're read or registered. The [`WebhookDispatcher`] is
+//! subscribed to node events and posts matching events in the background;
+//! delivery is best effort and failures are only logged.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use errors::AppError;
+use event::source::EventListener;
+use event::types::{Event, EventType};
+use globset::{GlobBuilder, GlobMatcher};
+use log::{info, warn};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
+use sled::Db;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+pub const WEBHOOK_TREE: &str = "webhooks";
+
+/// A webhook registration request
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewWebhook {
+    pub url: String,
+    /// Event type names; empty means every event
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub space_key: Option<String>,
+    /// Glob over the event path, relative to the space root
+    pub path_glob: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub space_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_glob: Option<PathGlob>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A compiled glob over space paths; serialized as the glob itself
+#[derive(Debug, Clone)]
+pub struct PathGlob(GlobMatcher);
+
+impl PathGlob {
+    /// `*` and `?` stay within one path segment; `**` crosses them
+    pub fn new(glob: &str) -> Result<Self, AppError> {
+        GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .map(|glob| PathGlob(glob.compile_matcher()))
+            .map_err(|e| AppError::Validation(format!("Invalid path glob: {}", e)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.glob().glob()
+    }
+
+    /// Whether `path`, relative to the space root, matches
+    pub fn is_match(&self, path: &str) -> bool {
+        self.0.is_match(path.trim_start_matches('/'))
+    }
+}
+
+impl PartialEq for PathGlob {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for PathGlob {}
+
+impl Serialize for PathGlob {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for PathGlob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let glob = String::deserialize(deserializer)?;
+        PathGlob::new(&glob).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Webhook {
+    /// Whether this registration wants `event`
+    pub fn matches(&self, event: &Event) -> bool {
+        if !self.events.is_empty() && !self.events.iter().any(|e| e == event.event_type.as_str()) {
+            return false;
+        }
+
+        if let Some(space_key) = &self.space_key {
+            let event_space = event.properties.get("space_key").and_then(|v| v.as_str());
+            if event_space != Some(space_key.as_str()) {
+                return false;
+            }
+        }
+
+        match &self.path_glob {
+            None => true,
+            Some(glob) => event
+                .properties
+                .get("path")
+                .and_then(|v| v.as_str())
+                .is_some_and(|path| glob.is_match(path)),
+        }
+    }
+}
+
+/// Webhook registrations in the KV store
+#[derive(Clone)]
+pub struct WebhookStore {
+    kv: Db,
+    /// Registrations by id, once read; clones share it, and writes go
+    /// through it
+    cache: Arc<RwLock<Option<Vec<Webhook>>>>,
+}
+
+impl WebhookStore {
+    pub fn new(kv: Db) -> Self {
+        Self {
+            kv,
+            cache: Arc::default(),
+        }
+    }
+
+    pub fn register(&self, new: NewWebhook) -> Result<Webhook, AppError> {
+        let url = url::Url::parse(&new.url)
+            .map_err(|e| AppError::Validation(format!("Invalid webhook URL: {}", e)))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AppError::Validation(
+                "Webhook URL must be http or https".to_string(),
+            ));
+        }
+
+        for name in &new.events {
+            name.parse::<EventType>().map_err(AppError::Validation)?;
+        }
+
+        let path_glob = match &new.path_glob {
+            Some(_) if new.space_key.is_none() => {
+                return Err(AppError::Validation(
+                    "A path glob needs a space key".to_string(),
+                ));
+            }
+            Some(glob) => Some(PathGlob::new(glob)?),
+            None => None,
+        };
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let webhook = Webhook {
+            id: URL_SAFE_NO_PAD.encode(id),
+            url: url.to_string(),
+            events: new.events,
+            space_key: new.space_key,
+            path_glob,
+            created_at: Utc::now(),
+        };
+
+        let json = serde_json::to_vec(&webhook).map_err(|e| AppError::Storage(Box::new(e)))?;
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        self.tree()?
+            .insert(webhook.id.as_bytes(), json)
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if let Some(webhooks) = cache.as_mut() {
+            let at = webhooks.partition_point(|w| w.id < webhook.id);
+            webhooks.insert(at, webhook.clone());
+        }
+
+        info!("Registered webhook {} for {}", webhook.id, webhook.url);
+        Ok(webhook)
+    }
+
+    pub fn list(&self) -> Result<Vec<Webhook>, AppError> {
+        if let Some(webhooks) = self
+            .cache
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            return Ok(webhooks.clone());
+        }
+
+        // Loaded under the lock, so no write slips in between
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(webhooks) = cache.as_ref() {
+            return Ok(webhooks.clone());
+        }
+        let webhooks = self
+            .tree()?
+            .iter()
+            .values()
+            .map(|value| {
+                let value = value.map_err(|e| AppError::Storage(Box::new(e)))?;
+                serde_json::from_slice(&value).map_err(|e| AppError::Storage(Box::new(e)))
+            })
+            .collect::<Result<Vec<Webhook>, AppError>>()?;
+        *cache = Some(webhooks.clone());
+        Ok(webhooks)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), AppError> {
+        let mut cache = self.cache.write().unwrap_or_else(|e| e.into_inner());
+        let removed = self
+            .tree()?
+            .remove(id.as_bytes())
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        if removed.is_none() {
+            return Err(AppError::NotFound(format!("Webhook {} not found", id)));
+        }
+        if let Some(webhooks) = cache.as_mut() {
+            webhooks.retain(|w| w.id != id);
+        }
+
+        info!("Removed webhook {}", id);
+        Ok(())
+    }
+
+    /// Registrations that want `event`
+    pub fn matching(&self, event: &Event) -> Result<Vec<Webhook>, AppError> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|webhook| webhook.matches(event))
+            .collect())
+    }
+
+    fn tree(&self) -> Result<sled::Tree, AppError> {
+        self.kv
+            .open_tree(WEBHOOK_TREE)
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+}
+
+/// Posts node events to matching webhooks
+pub struct WebhookDispatcher {
+    store: WebhookStore,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: WebhookStore) -> Self {
+        Self {
+            store,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl EventListener for WebhookDispatcher {
+    fn handle(&self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let webhooks = self.store.matching(event)?;
+        if webhooks.is_empty() {
+            return Ok(());
+        }
+
+        // Events are published from sync code; delivery needs the runtime
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("No runtime to deliver {} webhooks on", event.event_type);
+            return Ok(());
+        };
+
+        for webhook in webhooks {
+            let body = json!({
+                "webhook": webhook.id,
+                "type": event.event_type.as_str(),
+                "properties": event.properties,
+                "deliveredAt": Utc::now(),
+            });
+            let client = self.client.clone();
+            runtime.spawn(async move {
+                let result = client
+                    .post(&webhook.url)
+                    .json(&body)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = result {
+                    warn!("Webhook {} delivery failed: {}", webhook.id, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use errors::AppError;
+
+use crate::api::node::Node;
+use crate::modules::manifest::SignedManifest;
+
+/// The subset of the node a plugin may use
+#[derive(Clone)]
+pub struct PluginContext {
+    plugin: String,
+    node: Node,
+}
+
+impl PluginContext {
+    pub(crate) fn new(plugin: &str, node: Node) -> Self {
+        Self {
+            plugin: plugin.to_string(),
+            node,
+        }
+    }
+
+    pub fn plugin_name(&self) -> &str {
+        &self.plugin
+    }
+
+    /// DID of the node the plugin runs in
+    pub fn node_did(&self) -> &str {
+        &self.node.node_data.id
+    }
+
+    /// KV tree private to this plugin
+    pub fn storage(&self) -> Result<sled::Tree, AppError> {
+        self.node
+            .kv
+            .open_tree(format!("plugin:{}", self.plugin))
+            .map_err(|e| AppError::Storage(Box::new(e)))
+    }
+
+    pub async fn create_space(&self, dir: &str) -> Result<(), AppError> {
+        self.node.create_space(dir).await
+    }
+
+    pub async fn space_manifest(&self, key: &str, path: &str) -> Result<SignedManifest, AppError> {
+        self.node.space_manifest(key, path).await
+    }
+}
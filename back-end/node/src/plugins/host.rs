@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use axum::routing::MethodRouter;
+use errors::AppError;
+use futures_util::future::BoxFuture;
+use log::{error, info};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+
+use super::{BUILTIN_WS_ACTIONS, FlowPlugin, PluginContext};
+use crate::api::node::Node;
+
+pub type WsActionHandler =
+    Arc<dyn Fn(PluginContext, Value) -> BoxFuture<'static, Result<Value, AppError>> + Send + Sync>;
+
+type JobFn = Arc<dyn Fn(PluginContext) -> BoxFuture<'static, Result<(), AppError>> + Send + Sync>;
+
+/// Periodic task registered by a plugin
+#[derive(Clone)]
+pub struct PluginJob {
+    pub name: String,
+    pub interval: Duration,
+    ctx: PluginContext,
+    run: JobFn,
+}
+
+/// Collects what a plugin registers during `init`
+pub struct PluginRegistrar {
+    ctx: PluginContext,
+    router: Router,
+    ws_actions: HashMap<String, (PluginContext, WsActionHandler)>,
+    jobs: Vec<PluginJob>,
+}
+
+impl PluginRegistrar {
+    /// Add a route, relative to `/api/v1/plugins/{name}`
+    pub fn route(&mut self, path: &str, method_router: MethodRouter) -> &mut Self {
+        self.router = std::mem::take(&mut self.router).route(path, method_router);
+        self
+    }
+
+    /// Handle WebSocket messages whose `action` is `name`. The handler's
+    /// result is sent back as the `result` field of the reply.
+    pub fn ws_action<F, Fut>(&mut self, name: &str, handler: F) -> &mut Self
+    where
+        F: Fn(PluginContext, Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, AppError>> + Send + 'static,
+    {
+        let handler: WsActionHandler =
+            Arc::new(move |ctx, payload| Box::pin(handler(ctx, payload)));
+        self.ws_actions
+            .insert(name.to_string(), (self.ctx.clone(), handler));
+        self
+    }
+
+    /// Run `job` every `interval` while the node is up
+    pub fn job<F, Fut>(&mut self, name: &str, interval: Duration, job: F) -> &mut Self
+    where
+        F: Fn(PluginContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+    {
+        self.jobs.push(PluginJob {
+            name: format!("{}/{}", self.ctx.plugin_name(), name),
+            interval,
+            ctx: self.ctx.clone(),
+            run: Arc::new(move |ctx| Box::pin(job(ctx))),
+        });
+        self
+    }
+}
+
+/// Plugins compiled into the binary, in load order
+#[derive(Default)]
+pub struct PluginLoader {
+    plugins: Vec<Box<dyn FlowPlugin>>,
+}
+
+impl PluginLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, plugin: impl FlowPlugin + 'static) -> Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Initialise every plugin against `node`
+    pub fn load(self, node: &Node) -> Result<PluginHost, AppError> {
+        let mut host = PluginHost::default();
+
+        for plugin in self.plugins {
+            let name = plugin.name().to_string();
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(AppError::Config(format!("Invalid plugin name: {:?}", name)));
+            }
+            if host.plugins.iter().any(|(n, _)| *n == name) {
+                return Err(AppError::Config(format!(
+                    "Plugin {} registered twice",
+                    name
+                )));
+            }
+
+            let ctx = PluginContext::new(&name, node.clone());
+            let mut registrar = PluginRegistrar {
+                ctx: ctx.clone(),
+                router: Router::new(),
+                ws_actions: HashMap::new(),
+                jobs: Vec::new(),
+            };
+            plugin.init(ctx, &mut registrar).map_err(|e| {
+                AppError::Config(format!("Plugin {} failed to initialise: {}", name, e))
+            })?;
+
+            for action in registrar.ws_actions.keys() {
+                if BUILTIN_WS_ACTIONS.contains(&action.as_str())
+                    || host.ws_actions.contains_key(action)
+                {
+                    return Err(AppError::Config(format!(
+                        "Plugin {} registers WebSocket action {} which is already taken",
+                        name, action
+                    )));
+                }
+            }
+
+            host.router = host
+                .router
+                .nest(&format!("/api/v1/plugins/{}", name), registrar.router);
+            host.ws_actions.extend(registrar.ws_actions);
+            host.jobs.extend(registrar.jobs);
+
+            info!("Loaded plugin {} {}", name, plugin.version());
+            host.plugins.push((name, plugin.version().to_string()));
+        }
+
+        Ok(host)
+    }
+}
+
+/// Everything the loaded plugins registered
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<(String, String)>,
+    router: Router,
+    ws_actions: HashMap<String, (PluginContext, WsActionHandler)>,
+    jobs: Vec<PluginJob>,
+}
+
+impl PluginHost {
+    /// Names and versions of the loaded plugins
+    pub fn plugins(&self) -> &[(String, String)] {
+        &self.plugins
+    }
+
+    /// Routes registered by plugins, already nested under their prefixes
+    pub fn router(&self) -> Router {
+        self.router.clone()
+    }
+
+    /// Run the handler registered for a WebSocket action, if any
+    pub async fn handle_ws_action(
+        &self,
+        action: &str,
+        payload: Value,
+    ) -> Option<Result<Value, AppError>> {
+        let (ctx, handler) = self.ws_actions.get(action)?;
+        Some(handler(ctx.clone(), payload).await)
+    }
+
+    pub fn jobs(&self) -> &[PluginJob] {
+        &self.jobs
+    }
+
+    /// Start every registered job on the runtime
+    pub fn spawn_jobs(&self) -> Vec<JoinHandle<()>> {
+        self.jobs
+            .iter()
+            .cloned()
+            .map(|job| {
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(job.interval);
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = (job.run)(job.ctx.clone()).await {
+                            error!("Plugin job {} failed: {}", job.name, e);
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+}
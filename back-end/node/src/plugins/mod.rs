@@ -0,0 +1,27 @@
+//! Statically-compiled plugins. A plugin receives a limited [`PluginContext`]
+//! and registers REST routes, WebSocket actions and background jobs through a
+//! [`PluginRegistrar`]. Embedders build a [`PluginLoader`] and hand it to
+//! [`crate::runner::run_with_plugins`].
+
+mod context;
+mod host;
+
+pub use context::PluginContext;
+pub use host::{PluginHost, PluginJob, PluginLoader, PluginRegistrar, WsActionHandler};
+
+use errors::AppError;
+
+/// WebSocket actions handled by the node itself; plugins may not shadow them
+pub const BUILTIN_WS_ACTIONS: &[&str] = &["create_space"];
+
+pub trait FlowPlugin: Send + Sync {
+    /// Unique name; plugin routes are mounted under `/api/v1/plugins/{name}`
+    fn name(&self) -> &str;
+
+    fn version(&self) -> &str {
+        "0.0.0"
+    }
+
+    /// Called once at startup, before the servers start
+    fn init(&self, ctx: PluginContext, registrar: &mut PluginRegistrar) -> Result<(), AppError>;
+}
@@ -1,38 +1,305 @@
 use crate::{
     api::{
         node::Node,
-        servers::{app_state::AppState, rest, websocket},
+        servers::{app_state::AppState, grpc, listen, rest, tls, websocket},
     },
-    bootstrap::{self, config::Config},
-    modules::ssi::webauthn::state::AuthState,
+    bootstrap::{
+        self,
+        config::{AcmeChallenge, Config, DbConfig, DidCacheBackend, DidCacheConfig, KvConfig},
+        daemon::InstanceLock,
+        layout::{self, DataLayout},
+        profile,
+    },
+    lifecycle,
+    modules::{
+        acme::AcmeManager,
+        clock::{self, SystemClock, TimeSource},
+        column_crypto::{self, ColumnKeys},
+        maintenance::Maintenance,
+        p2p, resources,
+        ssi::did::DidResolver,
+        ssi::did::resolvers::cache::{KvDidCache, MemoryDidCache},
+        ssi::did::resolvers::peer::store::KvPeerDidStore,
+        ssi::webauthn::state::AuthState,
+        timestamp::TimestampClient,
+        updater::{self, Updater},
+        webhook::WebhookDispatcher,
+    },
+    plugins::PluginLoader,
 };
 use errors::AppError;
-use log::info;
+use log::{info, warn};
 use migration::{Migrator, MigratorTrait};
 use sea_orm::{ConnectOptions, DatabaseConnection};
 use sled::Db;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+const IN_MEMORY_CONNECTION_LIFETIME: Duration = Duration::from_secs(60 * 60 * 24 * 365);
 
 pub async fn run() -> Result<(), AppError> {
-    let config = Config::from_env()?;
+    run_with(Config::from_env()?, PluginLoader::new()).await
+}
+
+/// Warn about settings that differ from the imported profile, if any
+fn report_profile_drift(config_dir: &Path) {
+    match profile::load_baseline(config_dir) {
+        Ok(Some(baseline)) => {
+            for drift in profile::drift(&baseline.profile, &profile::current_settings()) {
+                warn!(
+                    "{} differs from profile version {}: expected {}, found {}",
+                    drift.key,
+                    baseline.profile.version,
+                    drift.expected.as_deref().unwrap_or("<unset>"),
+                    drift.actual.as_deref().unwrap_or("<unset>")
+                );
+            }
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Could not check configuration profile: {}", e),
+    }
+}
 
+/// Run without persisting anything (`--ephemeral`)
+pub async fn run_ephemeral() -> Result<(), AppError> {
+    run_with(Config::ephemeral_from_env()?, PluginLoader::new()).await
+}
+
+/// Run with statically-compiled plugins, for binaries that embed the node
+pub async fn run_with_plugins(plugins: PluginLoader) -> Result<(), AppError> {
+    run_with(Config::from_env()?, plugins).await
+}
+
+async fn run_with(config: Config, plugins: PluginLoader) -> Result<(), AppError> {
     info!("Configuration loaded. Initializing node...");
+    config.server.validate()?;
+    if config.low_resource {
+        info!("Low-resource mode: smaller caches, connection pool and buffers");
+    }
 
     // Initialize foundational services like logging here (if any).
     // Bootstrap the node identity, file system, etc.
-    let node_data = bootstrap::init::initialize()?;
+    // Ephemeral nodes get a throwaway identity that is removed on exit.
+    let ephemeral_home = if config.ephemeral {
+        info!("Ephemeral mode: nothing will be persisted");
+        Some(TempDir::new()?)
+    } else {
+        None
+    };
+    let config_dir = PathBuf::from(bootstrap::init::get_flow_config_dir());
+
+    // Only one node may own a config directory at a time. Taken before the
+    // staged update is applied, so a running node's binary is never swapped.
+    let _instance_lock = match &ephemeral_home {
+        Some(_) => None,
+        None => Some(InstanceLock::acquire(&config_dir)?),
+    };
+    if ephemeral_home.is_none() {
+        apply_staged_update(&config_dir)?;
+        layout::check(&config_dir, &DataLayout::from_config(&config))?;
+        report_profile_drift(&config_dir);
+    }
+    let node_data = match &ephemeral_home {
+        Some(home) => bootstrap::init::initialize_config_dir(&home.path().to_string_lossy())?,
+        None => {
+            bootstrap::init::initialize_with(&config_dir.to_string_lossy(), config.key_storage)?
+        }
+    };
     info!("Node initialized successfully.");
 
     // Set up the database connection and run migrations.
-    let db_conn = setup_database(&config).await?;
+    let db_conn = setup_database(&config.db).await?;
     info!("Database setup and migrations complete.");
 
     // Set up KV Store
-    let kv = setup_kv_store(&config).await?;
+    let kv = setup_kv_store(&config.kv).await?;
+
+    if config.update.enabled {
+        let updater = Updater::new(config.update.clone(), &config_dir, kv.clone())?;
+        tokio::spawn(updater.run());
+        info!("Update checks enabled");
+    }
+
+    let auth_state = AuthState::from_env_serving(config.serves_https())?;
+
+    // Rotated column keys live next to the identity key
+    let node_home = match &ephemeral_home {
+        Some(home) => home.path().to_path_buf(),
+        None => config_dir.clone(),
+    };
+    let column_keys = ColumnKeys::load(
+        &node_home.join(bootstrap::init::KEYSTORE_DIR),
+        &node_data.private_key,
+    )?;
+
+    let mut node = Node::new(node_data, db_conn, kv, auth_state);
+    node.column_keys = column_keys;
+    node.home = Some(node_home.clone());
+    // Scratch files a crash left behind
+    match std::fs::remove_dir_all(node.scratch_dir()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            log::warn!("Failed to clear scratch files: {}", e)
+        }
+        _ => {}
+    }
+    node.export_dir = config.export_dir.clone();
+    node.multi_user = config.multi_user.clone();
+    if node.multi_user.enabled {
+        info!("Multi-user mode: spaces and KV data are isolated per user");
+    }
+    node.did_resolver = Arc::new(
+        did_resolver(&config.did_cache, &node.kv)?.with_parallelism(config.did_batch.parallelism),
+    );
+    node.did_batch = config.did_batch;
+    node.key_usage = config.key_usage.clone();
+    node.retention = config.retention.clone();
+    node.resources = config.resources.clone();
+    node.telemetry = config.telemetry.clone();
+    node.maintenance = Maintenance::new(&config.maintenance);
+    node.set_time(TimeSource::new(
+        Arc::new(SystemClock),
+        config.clock.max_skew,
+    ));
+    node.peer_replay_window = config.clock.replay_window;
+    if let Some(server) = config.clock.ntp_server.clone() {
+        let time = node.time.clone();
+        tokio::spawn(async move { clock::check_drift(&server, &time).await });
+    }
+    node.guest_mode = config.guest_mode;
+    if node.guest_mode {
+        info!("Guest mode: published resources are open to unauthenticated requests");
+    }
+    node.trusted_timestamps = config.timestamping.trusted_certificates.clone();
+    if let Some(url) = &config.timestamping.authority_url {
+        node.timestamps = Some(TimestampClient::new(&config.timestamping)?);
+        info!("Anchors are timestamped by {}", url);
+    }
+    column_crypto::reencrypt_pass_keys(&node.db, &node.column_keys).await?;
+    node.secrets().reseal()?;
+
+    node.subscribe(Box::new(WebhookDispatcher::new(node.webhooks.clone())));
+
+    #[cfg(feature = "scripting")]
+    {
+        let scripts = crate::scripting::ScriptHost::load_dir(
+            &config_dir.join(crate::scripting::SCRIPTS_DIR),
+            &node.kv,
+        )?;
+        if !scripts.is_empty() {
+            info!("Loaded {} event scripts", scripts.scripts().len());
+            node.subscribe(Box::new(scripts));
+        }
+    }
+
+    // Expired ceremony and session records are swept in the background
+    let sessions = node.sessions.clone();
+    node.tasks.spawn("sessions", async move {
+        let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match sessions.sweep_expired().await {
+                Ok(0) => {}
+                Ok(n) => info!("Swept {} expired sessions", n),
+                Err(e) => log::warn!("Session sweep failed: {}", e),
+            }
+        }
+    });
+
+    // Nonces of signed peer requests are dropped once out of the window
+    let replays = node.peer_requests();
+    let window = node.peer_replay_window.max(Duration::from_secs(1));
+    node.tasks.spawn("peer_requests", async move {
+        let mut interval = tokio::time::interval(window);
+        loop {
+            interval.tick().await;
+            if let Err(e) = replays.sweep() {
+                log::warn!("Replay cache sweep failed: {}", e);
+            }
+        }
+    });
+
+    // Records past their retention, accounts past their deletion grace and
+    // deleted records past their restore window are purged in the background
+    let purging = node.clone();
+    node.tasks.spawn("retention", async move {
+        let mut interval = tokio::time::interval(purging.retention.purge_interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = purging.purge_expired(false) {
+                log::warn!("Retention purge failed: {}", e);
+            }
+            if let Err(e) = purging.erase_deleted_accounts().await {
+                log::warn!("Erasing deleted accounts failed: {}", e);
+            }
+            if let Err(e) = purging.purge_deleted().await {
+                log::warn!("Purging deleted records failed: {}", e);
+            }
+            if let Err(e) = purging.purge_history().await {
+                log::warn!("Purging change history failed: {}", e);
+            }
+        }
+    });
+
+    node.tasks.spawn("scheduler", node.scheduler().run());
+
+    if node.telemetry.enabled {
+        info!(
+            "Telemetry: noised usage counts are reported to {}",
+            node.telemetry.endpoint.as_deref().unwrap_or_default()
+        );
+        node.tasks.spawn("telemetry", node.telemetry().run());
+    }
 
-    let auth_state = AuthState::from_env()?;
+    // Events committed with database changes are published from the outbox
+    node.tasks
+        .spawn("outbox", node.outbox.clone().run(node.clone()));
 
-    let node = Node::new(node_data, db_conn, kv, auth_state);
-    let app_state = AppState::new(node);
+    if node.resources.any_limit() {
+        node.tasks.spawn(
+            "resources",
+            resources::watch(
+                node.db.clone(),
+                node.kv.clone(),
+                node.tasks.clone(),
+                node.resources.clone(),
+            ),
+        );
+    }
+
+    let plugins = plugins.load(&node)?;
+    let _plugin_jobs = plugins.spawn_jobs();
+    let tasks = node.tasks.clone();
+    let peers = node.clone();
+    let mut app_state = AppState::with_plugins(node, plugins)
+        .with_server(config.server.clone())
+        .with_limits(config.limits)
+        .with_request_limits(config.request_limits)
+        .with_compression(config.compression)
+        .with_security_headers(config.security_headers)
+        .with_proxy(config.proxy.clone());
+
+    if config.acme.enabled() {
+        let acme = AcmeManager::new(config.acme.clone(), &node_home)?;
+        app_state = app_state.with_tls(acme.store().server_config()?);
+        if config.acme.challenge == AcmeChallenge::Http01 {
+            let addrs = config.server.listen_addrs(config.acme.http_port);
+            let listeners = listen::bind_all(&addrs)?;
+            let responses = acme.http01();
+            tokio::spawn(async move {
+                if let Err(e) = tls::serve_challenges(listeners, responses).await {
+                    warn!("ACME challenge server stopped: {}", e);
+                }
+            });
+        }
+        tasks.spawn("acme", acme.run());
+        info!("Serving HTTPS for {}", config.acme.domains.join(", "));
+    } else if config.tls.enabled() {
+        app_state =
+            app_state.with_tls(tls::server_config(&config.tls, &node_home, &config.server)?);
+    }
 
     info!("Starting servers...");
 
@@ -40,37 +307,98 @@ pub async fn run() -> Result<(), AppError> {
     // Start server, event loops, or other long-running
     // tasks, using the initialized objects.
 
-    tokio::select! {
-        result = rest::start(&app_state, &config) => {
-            result?;
-        }
-        result = websocket::start(&app_state, &config) => {
-            result?;
+    let shutdown = app_state.shutdown.clone();
+    let signalled = shutdown.clone();
+    tokio::spawn(async move {
+        let signal = lifecycle::signal().await;
+        signalled.trigger(&format!("{} received", signal));
+    });
+
+    // Serve until shutdown, or until a server fails
+    let serving = async {
+        tokio::select! {
+            result = futures_util::future::try_join(
+                rest::start(&app_state, &config),
+                websocket::start(&app_state, &config),
+            ) => result.map(|_| ()),
+            result = grpc::start(&app_state, &config) => result,
+            result = p2p::start(&peers, &config) => result,
         }
-        _ = tokio::signal::ctrl_c() => {
-            info!("Shutdown signal received");
+    };
+    let grace = async {
+        shutdown.triggered().await;
+        tokio::time::sleep(lifecycle::SHUTDOWN_GRACE).await;
+    };
+    let served = tokio::select! {
+        result = serving => result,
+        _ = grace => {
+            warn!("Requests still in flight after {:?}; stopping anyway", lifecycle::SHUTDOWN_GRACE);
+            Ok(())
         }
+    };
+    if let Err(e) = &served {
+        log::error!("Server failed: {}", e);
     }
+    shutdown.trigger("servers stopped");
+
+    lifecycle::close(&*app_state.node.read().await).await?;
+    info!("Shutdown complete");
+    served
+}
+
+/// Swap in an update staged by a previous run and restart into it
+fn apply_staged_update(config_dir: &Path) -> Result<(), AppError> {
+    let executable = std::env::current_exe()?;
+    let Some(version) = updater::apply_staged(&config_dir.join(updater::UPDATES_DIR), &executable)?
+    else {
+        return Ok(());
+    };
+
+    info!("Updated to version {}, restarting", version);
+    restart(&executable, &version)
+}
 
-    info!("Application running. Press Ctrl+C to exit.");
+/// The instance lock's file is opened close-on-exec, so the restarted
+/// node takes the lock over
+#[cfg(unix)]
+fn restart(executable: &Path, _version: &str) -> Result<(), AppError> {
+    use std::os::unix::process::CommandExt;
+    let err = std::process::Command::new(executable)
+        .args(std::env::args().skip(1))
+        .exec();
+    Err(AppError::IO(err))
+}
 
+#[cfg(not(unix))]
+fn restart(_executable: &Path, version: &str) -> Result<(), AppError> {
+    log::warn!("Restart the node to run version {}", version);
     Ok(())
 }
 
-async fn setup_database(config: &Config) -> Result<DatabaseConnection, AppError> {
+pub async fn setup_database(db_config: &DbConfig) -> Result<DatabaseConnection, AppError> {
     info!("Setting up Database");
 
-    let db_config = &config.db;
     let mut opt = ConnectOptions::new(&db_config.url);
 
-    opt.max_connections(db_config.max_connections)
-        .min_connections(db_config.min_connections)
-        .connect_timeout(db_config.connect_timeout)
-        .idle_timeout(db_config.idle_timeout)
-        .max_lifetime(db_config.max_lifetime)
+    opt.connect_timeout(db_config.connect_timeout)
         .sqlx_logging(db_config.logging_enabled)
         .sqlx_logging_level(log::LevelFilter::Info); // #TODO: hard-coded right now, remember to externalize into a config
 
+    if db_config.is_in_memory() {
+        // The in-memory database disappears once its last connection closes,
+        // so pin a single connection that is never reaped.
+        info!("Using in-memory database");
+        opt.max_connections(1)
+            .min_connections(1)
+            .idle_timeout(IN_MEMORY_CONNECTION_LIFETIME)
+            .max_lifetime(IN_MEMORY_CONNECTION_LIFETIME);
+    } else {
+        opt.max_connections(db_config.max_connections)
+            .min_connections(db_config.min_connections)
+            .idle_timeout(db_config.idle_timeout)
+            .max_lifetime(db_config.max_lifetime);
+    }
+
     let connection = sea_orm::Database::connect(opt)
         .await
         .map_err(|db_err| AppError::Storage(Box::new(db_err)))?;
@@ -83,7 +411,43 @@ async fn setup_database(config: &Config) -> Result<DatabaseConnection, AppError>
     Ok(connection)
 }
 
-async fn setup_kv_store(config: &Config) -> Result<Db, AppError> {
+pub async fn setup_kv_store(kv_config: &KvConfig) -> Result<Db, AppError> {
     info!("Setting up KVStore");
-    Ok(sled::open(config.kv.path.as_str()).unwrap())
+
+    let mut sled_config = sled::Config::new();
+    if let Some(bytes) = kv_config.cache_bytes {
+        sled_config = sled_config.cache_capacity(bytes);
+    }
+    if kv_config.low_space {
+        sled_config = sled_config.mode(sled::Mode::LowSpace);
+    }
+
+    if kv_config.in_memory {
+        info!("Using in-memory KVStore");
+        return sled_config
+            .temporary(true)
+            .open()
+            .map_err(|e| AppError::Storage(Box::new(e)));
+    }
+
+    sled_config
+        .path(kv_config.path.as_str())
+        .open()
+        .map_err(|e| AppError::Storage(Box::new(e)))
+}
+
+/// DID resolver caching where the config says, keeping did:peer short forms
+/// in the KV store
+pub fn did_resolver(config: &DidCacheConfig, kv: &Db) -> Result<DidResolver, AppError> {
+    let peer_store = KvPeerDidStore::open(kv).map_err(|e| AppError::Storage(Box::new(e)))?;
+    let resolver = DidResolver::new().with_peer_store(Arc::new(peer_store));
+    Ok(match config.backend {
+        DidCacheBackend::Memory => {
+            resolver.with_cache(Arc::new(MemoryDidCache::new(config.capacity)))
+        }
+        DidCacheBackend::Kv => resolver.with_cache(Arc::new(
+            KvDidCache::open(kv).map_err(|e| AppError::Storage(Box::new(e)))?,
+        )),
+        DidCacheBackend::Off => resolver.without_cache(),
+    })
 }
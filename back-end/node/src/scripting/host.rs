@@ -0,0 +1,364 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use errors::AppError;
+use event::source::EventListener;
+use event::types::{Event, EventType};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use wasmtime::{
+    Caller, Engine, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use super::{
+    FUEL_PER_EVENT, KV_ENTRY_TOO_LARGE, KV_QUOTA_EXCEEDED, MAX_KV_BYTES, MAX_KV_ENTRY_BYTES,
+    MAX_MEMORY_BYTES,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// `flow.log(ptr, len)` writes to the node log
+    Log,
+    /// `flow.kv_get` / `flow.kv_set` on a KV tree private to the script
+    Kv,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptManifest {
+    /// Event names (`file_created`, `credential_verified`, ...); empty means all
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default = "default_capabilities")]
+    pub capabilities: Vec<Capability>,
+}
+
+fn default_capabilities() -> Vec<Capability> {
+    vec![Capability::Log]
+}
+
+impl Default for ScriptManifest {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            capabilities: default_capabilities(),
+        }
+    }
+}
+
+/// A script's KV tree and the bytes of keys and values it holds
+#[derive(Clone)]
+struct ScriptKv {
+    tree: sled::Tree,
+    used: Arc<Mutex<u64>>,
+}
+
+impl ScriptKv {
+    fn open(kv: &sled::Db, script: &str) -> Result<Self, AppError> {
+        let tree = kv
+            .open_tree(format!("script:{}", script))
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        let mut used = 0;
+        for entry in tree.iter() {
+            let (key, value) = entry.map_err(|e| AppError::Storage(Box::new(e)))?;
+            used += (key.len() + value.len()) as u64;
+        }
+        Ok(Self {
+            tree,
+            used: Arc::new(Mutex::new(used)),
+        })
+    }
+
+    /// Store `value` under `key` unless that takes the tree over its quota,
+    /// returning the `kv_set` result for the guest
+    fn set(&self, key: Vec<u8>, value: Vec<u8>) -> wasmtime::Result<i32> {
+        let size = key.len() + value.len();
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let replaced = self
+            .tree
+            .get(&key)?
+            .map_or(0, |old| (key.len() + old.len()) as u64);
+        let after = *used - replaced + size as u64;
+        if after > MAX_KV_BYTES {
+            return Ok(KV_QUOTA_EXCEEDED);
+        }
+        self.tree.insert(key, value)?;
+        *used = after;
+        Ok(0)
+    }
+}
+
+struct HostState {
+    script: String,
+    kv: Option<ScriptKv>,
+    limits: StoreLimits,
+}
+
+pub struct Script {
+    pub name: String,
+    pub manifest: ScriptManifest,
+    pre: InstancePre<HostState>,
+    kv: Option<ScriptKv>,
+}
+
+impl Script {
+    fn subscribes_to(&self, event_type: EventType) -> bool {
+        self.manifest.events.is_empty()
+            || self
+                .manifest
+                .events
+                .iter()
+                .any(|e| e == event_type.as_str())
+    }
+}
+
+/// Loaded scripts, dispatched to as an [`EventListener`]
+pub struct ScriptHost {
+    engine: Engine,
+    scripts: Vec<Script>,
+}
+
+impl ScriptHost {
+    pub fn new() -> Result<Self, AppError> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)
+            .map_err(|e| AppError::Config(format!("Failed to start WASM engine: {}", e)))?;
+
+        Ok(Self {
+            engine,
+            scripts: Vec::new(),
+        })
+    }
+
+    /// Load every `.wasm`/`.wat` module in `dir`. A missing directory yields
+    /// an empty host.
+    pub fn load_dir(dir: &Path, kv: &sled::Db) -> Result<Self, AppError> {
+        let mut host = Self::new()?;
+        if !dir.is_dir() {
+            return Ok(host);
+        }
+
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| matches!(p.extension().and_then(|e| e.to_str()), Some("wasm" | "wat")))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let manifest = match fs::read(path.with_extension("json")) {
+                Ok(json) => serde_json::from_slice(&json).map_err(|e| {
+                    AppError::Config(format!("Invalid manifest for script {}: {}", name, e))
+                })?,
+                Err(_) => ScriptManifest::default(),
+            };
+            host.add(&name, &fs::read(&path)?, manifest, kv)?;
+        }
+
+        Ok(host)
+    }
+
+    /// Compile and link a script; fails if it imports an ungranted capability
+    pub fn add(
+        &mut self,
+        name: &str,
+        wasm: &[u8],
+        manifest: ScriptManifest,
+        kv: &sled::Db,
+    ) -> Result<(), AppError> {
+        let invalid =
+            |e: wasmtime::Error| AppError::Validation(format!("Script {}: {:#}", name, e));
+
+        for event in &manifest.events {
+            if event.parse::<EventType>().is_err() {
+                return Err(AppError::Validation(format!(
+                    "Script {} subscribes to unknown event {}",
+                    name, event
+                )));
+            }
+        }
+
+        let module = Module::new(&self.engine, wasm).map_err(invalid)?;
+        let capabilities: HashSet<_> = manifest.capabilities.iter().copied().collect();
+
+        let mut linker = Linker::new(&self.engine);
+        if capabilities.contains(&Capability::Log) {
+            link_log(&mut linker).map_err(invalid)?;
+        }
+        let kv = if capabilities.contains(&Capability::Kv) {
+            link_kv(&mut linker).map_err(invalid)?;
+            Some(ScriptKv::open(kv, name)?)
+        } else {
+            None
+        };
+        let pre = linker.instantiate_pre(&module).map_err(invalid)?;
+
+        info!("Loaded script {} ({:?})", name, manifest.capabilities);
+        self.scripts.push(Script {
+            name: name.to_string(),
+            manifest,
+            pre,
+            kv,
+        });
+        Ok(())
+    }
+
+    pub fn scripts(&self) -> &[Script] {
+        &self.scripts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// Run every subscribed script on `event`, returning each outcome
+    pub fn dispatch(&self, event: &Event) -> Vec<(String, Result<(), AppError>)> {
+        let payload = json!({
+            "type": event.event_type.as_str(),
+            "properties": event.properties,
+        })
+        .to_string();
+
+        self.scripts
+            .iter()
+            .filter(|script| script.subscribes_to(event.event_type))
+            .map(|script| {
+                let result = self.run(script, payload.as_bytes()).map_err(|e| {
+                    AppError::Validation(format!("Script {} failed: {:#}", script.name, e))
+                });
+                (script.name.clone(), result)
+            })
+            .collect()
+    }
+
+    fn run(&self, script: &Script, payload: &[u8]) -> wasmtime::Result<()> {
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                script: script.name.clone(),
+                kv: script.kv.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_BYTES)
+                    .instances(1)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_EVENT)?;
+
+        let instance = script.pre.instantiate(&mut store)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("script does not export memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let on_event = instance.get_typed_func::<(i32, i32), ()>(&mut store, "on_event")?;
+
+        let len = i32::try_from(payload.len())?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, ptr as usize, payload)?;
+        on_event.call(&mut store, (ptr, len))?;
+
+        debug!(
+            "Script {} used {} fuel",
+            script.name,
+            FUEL_PER_EVENT - store.get_fuel().unwrap_or(0)
+        );
+        Ok(())
+    }
+}
+
+impl EventListener for ScriptHost {
+    fn handle(&self, event: &Event) -> Result<(), Box<dyn Error>> {
+        for (name, result) in self.dispatch(event) {
+            if let Err(e) = result {
+                warn!("Script {} failed on {}: {}", name, event.event_type, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_guest(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("script does not export memory"))?;
+    let mut buf = vec![0u8; usize::try_from(len)?];
+    memory.read(&*caller, usize::try_from(ptr)?, &mut buf)?;
+    Ok(buf)
+}
+
+fn link_log(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+    linker.func_wrap(
+        "flow",
+        "log",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+            let message = read_guest(&mut caller, ptr, len)?;
+            info!(
+                "[script {}] {}",
+                caller.data().script,
+                String::from_utf8_lossy(&message)
+            );
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+fn link_kv(linker: &mut Linker<HostState>) -> wasmtime::Result<()> {
+    // Returns the value length (writing it only if it fits in `out_cap`), or
+    // -1 when the key is absent
+    linker.func_wrap(
+        "flow",
+        "kv_get",
+        |mut caller: Caller<'_, HostState>,
+         key_ptr: i32,
+         key_len: i32,
+         out_ptr: i32,
+         out_cap: i32|
+         -> wasmtime::Result<i32> {
+            let key = read_guest(&mut caller, key_ptr, key_len)?;
+            let kv = caller.data().kv.clone().expect("kv capability is linked");
+            let Some(value) = kv.tree.get(key)? else {
+                return Ok(-1);
+            };
+
+            if value.len() <= usize::try_from(out_cap)? {
+                let memory = caller
+                    .get_export("memory")
+                    .and_then(|e| e.into_memory())
+                    .ok_or_else(|| wasmtime::Error::msg("script does not export memory"))?;
+                memory.write(&mut caller, usize::try_from(out_ptr)?, &value)?;
+            }
+            Ok(i32::try_from(value.len())?)
+        },
+    )?;
+    linker.func_wrap(
+        "flow",
+        "kv_set",
+        |mut caller: Caller<'_, HostState>,
+         key_ptr: i32,
+         key_len: i32,
+         val_ptr: i32,
+         val_len: i32|
+         -> wasmtime::Result<i32> {
+            // Checked before copying anything out of the guest
+            if usize::try_from(key_len)? + usize::try_from(val_len)? > MAX_KV_ENTRY_BYTES {
+                return Ok(KV_ENTRY_TOO_LARGE);
+            }
+            let key = read_guest(&mut caller, key_ptr, key_len)?;
+            let value = read_guest(&mut caller, val_ptr, val_len)?;
+            let kv = caller.data().kv.clone().expect("kv capability is linked");
+            kv.set(key, value)
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! Sandboxed WASM scripts run on node events. Enabled with the `scripting`
+//! feature.
+//!
+//! Scripts live in `<config dir>/scripts` as `.wasm` (or `.wat`) modules. A
+//! script exports `memory`, `alloc(len) -> ptr` and `on_event(ptr, len)`; the
+//! event is passed as JSON `{"type": ..., "properties": {...}}`. An optional
+//! `<name>.json` next to the module lists the events it subscribes to and the
+//! capabilities it is granted:
+//!
+//! ```json
+//! { "events": ["credential_verified"], "capabilities": ["log", "kv"] }
+//! ```
+//!
+//! Only the host functions for granted capabilities are linked, so a script
+//! importing anything else fails to load. Each event runs in a fresh instance
+//! with bounded fuel and memory.
+//!
+//! `kv_set(key_ptr, key_len, val_ptr, val_len) -> i32` returns `0` once
+//! stored, [`KV_ENTRY_TOO_LARGE`] for an entry over [`MAX_KV_ENTRY_BYTES`]
+//! and [`KV_QUOTA_EXCEEDED`] when the script's tree would outgrow
+//! [`MAX_KV_BYTES`].
+
+mod host;
+
+pub use host::{Capability, Script, ScriptHost, ScriptManifest};
+
+pub const SCRIPTS_DIR: &str = "scripts";
+
+/// Fuel available to a script for handling a single event
+pub const FUEL_PER_EVENT: u64 = 10_000_000;
+
+/// Linear memory limit per script instance
+pub const MAX_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Largest key plus value a script may store
+pub const MAX_KV_ENTRY_BYTES: usize = 64 * 1024;
+
+/// Keys and values a script may keep in its KV tree
+pub const MAX_KV_BYTES: u64 = 4 * 1024 * 1024;
+
+/// `kv_set` result for an entry over [`MAX_KV_ENTRY_BYTES`]
+pub const KV_ENTRY_TOO_LARGE: i32 = -1;
+
+/// `kv_set` result when the entry would take the tree over [`MAX_KV_BYTES`]
+pub const KV_QUOTA_EXCEEDED: i32 = -2;
@@ -0,0 +1,362 @@
+//! `node seed --profile demo` - populate a node with sample data, so
+//! front-end work and testing start from something other than an empty node.
+//!
+//! Everything goes through the same code paths as real use: users register
+//! with a software passkey and get profiles, spaces are created over folders
+//! of files, and credentials are issued by the node. Contacts and messages
+//! have no store of their own, so each user's space holds them as files:
+//! `contacts.json` (the other demo users) and `messages/` (messages in the
+//! P2P wire format). The passkeys live only as long as the seed run; sign in
+//! by registering a passkey of your own.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use errors::AppError;
+use log::info;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use url::Url;
+use webauthn_authenticator_rs::AuthenticatorBackend;
+use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+
+use crate::api::node::Node;
+use crate::bootstrap::config::Config;
+use crate::bootstrap::daemon::InstanceLock;
+use crate::bootstrap::init::{self, get_flow_config_dir};
+use crate::bootstrap::layout;
+use crate::modules::column_crypto::ColumnKeys;
+use crate::modules::p2p::Message;
+use crate::modules::profile::ProfileUpdate;
+use crate::modules::ssi::vc::CredentialRequest;
+use crate::modules::ssi::webauthn::state::{AuthConfig, AuthState};
+use crate::modules::tenancy::Actor;
+use crate::runner::{setup_database, setup_kv_store};
+
+pub const USAGE: &str = "Usage: node seed --profile demo [--dir PATH]";
+
+/// Folder under the config dir the demo spaces are made in by default
+pub const DEFAULT_DIR: &str = "demo";
+
+const AUTHENTICATOR_TIMEOUT_MS: u32 = 60000;
+
+/// Username and display name of each demo user
+const DEMO_USERS: [(&str, &str); 3] = [
+    ("ada", "Ada Lovelace"),
+    ("grace", "Grace Hopper"),
+    ("alan", "Alan Turing"),
+];
+
+const DEMO_FILES: [(&str, &str); 4] = [
+    (
+        "Documents/welcome.md",
+        "# Welcome to Flow\n\nThis space was made by `node seed --profile demo`.\n",
+    ),
+    (
+        "Documents/notes.txt",
+        "Remember to back up the identity key.\n",
+    ),
+    (
+        "Projects/engine/README.md",
+        "# Analytical Engine\n\nPlans and punch cards.\n",
+    ),
+    (
+        "Projects/engine/cards.csv",
+        "card,operation\n1,load\n2,add\n3,store\n",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// A few users, each with a space of files, contacts, messages and a
+    /// membership credential
+    Demo,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Profile::Demo => f.write_str("demo"),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "demo" => Ok(Profile::Demo),
+            _ => Err(AppError::Config(format!("Unknown seed profile: {}", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedArgs {
+    pub profile: Profile,
+    /// Where the spaces' folders go; [`DEFAULT_DIR`] in the config dir if
+    /// not given
+    pub dir: Option<PathBuf>,
+}
+
+impl SeedArgs {
+    /// Parse the arguments following `seed`
+    pub fn parse<I, S>(args: I) -> Result<Self, AppError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut profile = None;
+        let mut dir = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| AppError::Config(format!("{} expects a value", arg.as_ref())))
+            };
+            match arg.as_ref() {
+                "--profile" | "-p" => profile = Some(value()?.as_ref().parse()?),
+                "--dir" => dir = Some(PathBuf::from(value()?.as_ref())),
+                other => {
+                    return Err(AppError::Config(format!("Unknown argument: {}", other)));
+                }
+            }
+        }
+
+        Ok(Self {
+            profile: profile.ok_or_else(|| AppError::Config(USAGE.to_string()))?,
+            dir,
+        })
+    }
+}
+
+/// A user the seed made
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeededUser {
+    pub id: i32,
+    pub did: String,
+    pub username: String,
+    pub space_key: String,
+    pub location: String,
+}
+
+/// What the seed made
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedReport {
+    pub profile: String,
+    pub users: Vec<SeededUser>,
+    /// Files written across all spaces
+    pub files: usize,
+    pub credentials: usize,
+}
+
+impl fmt::Display for SeedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Seeded {} users with {} files and {} credentials ({} profile)",
+            self.users.len(),
+            self.files,
+            self.credentials,
+            self.profile
+        )?;
+        for user in &self.users {
+            writeln!(
+                f,
+                "  {:<8} {}  space {} at {}",
+                user.username, user.did, user.space_key, user.location
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Entry point for `node seed ...`: seed the node of the config dir, which
+/// must not be running
+pub async fn run<I, S>(args: I) -> Result<SeedReport, AppError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let args = SeedArgs::parse(args)?;
+    let config = Config::from_env()?;
+    let config_dir = PathBuf::from(get_flow_config_dir());
+    let _lock = InstanceLock::acquire(&config_dir)?;
+
+    let node_data = init::initialize_with(&config_dir.to_string_lossy(), config.key_storage)?;
+    let db = setup_database(&config.db).await?;
+    let kv = setup_kv_store(&config.kv).await?;
+    let auth_config = AuthConfig::from_env_serving(config.serves_https())?;
+    let origin = Url::parse(&auth_config.rp_origin)
+        .map_err(|e| AppError::Config(format!("Invalid WebAuthn origin URL: {}", e)))?;
+
+    let column_keys =
+        ColumnKeys::load(&config_dir.join(init::KEYSTORE_DIR), &node_data.private_key)?;
+    let mut node = Node::new(node_data, db, kv, AuthState::new(auth_config)?);
+    node.column_keys = column_keys;
+    node.home = Some(config_dir.clone());
+    node.multi_user = config.multi_user.clone();
+
+    let dir = args.dir.unwrap_or_else(|| config_dir.join(DEFAULT_DIR));
+    seed(&node, args.profile, &origin, &dir).await
+}
+
+/// Seed `node` with `profile`, registering passkeys for `origin` and making
+/// the spaces' folders in `dir`, which must be empty or not exist yet. `dir`
+/// stands in for the users' spaces directory while seeding.
+pub async fn seed(
+    node: &Node,
+    profile: Profile,
+    origin: &Url,
+    dir: &Path,
+) -> Result<SeedReport, AppError> {
+    if dir.exists() && dir.read_dir()?.next().is_some() {
+        return Err(AppError::Conflict(format!(
+            "{} is not empty; seed data goes in a new folder",
+            dir.display()
+        )));
+    }
+    info!("Seeding {} data in {}", profile, dir.display());
+    let mut node = node.clone();
+    node.multi_user.spaces_root = Some(dir.to_path_buf());
+    let node = &node;
+
+    let mut users = Vec::with_capacity(DEMO_USERS.len());
+    for (username, display_name) in DEMO_USERS {
+        let (id, did) = register(node, origin).await?;
+        node.update_user_profile(
+            id,
+            ProfileUpdate {
+                username: Some(username.to_string()),
+                display_name: Some(display_name.to_string()),
+                ..Default::default()
+            },
+        )
+        .await?;
+        users.push((id, did, username, display_name));
+    }
+
+    let mut report = SeedReport {
+        profile: profile.to_string(),
+        users: Vec::with_capacity(users.len()),
+        files: 0,
+        credentials: 0,
+    };
+    for (id, did, username, display_name) in &users {
+        let space = layout::user_spaces(dir, *id).join(username);
+        for (path, content) in DEMO_FILES {
+            write(&space.join(path), content.as_bytes())?;
+        }
+
+        let contacts: Vec<Value> = users
+            .iter()
+            .filter(|(other, ..)| other != id)
+            .map(|(_, did, username, display_name)| {
+                json!({ "did": did, "username": username, "displayName": display_name })
+            })
+            .collect();
+        write(&space.join("contacts.json"), &to_json(&contacts)?)?;
+
+        let mut messages = 0;
+        for (_, from, sender, _) in users.iter().filter(|(other, ..)| other != id) {
+            let message = Message::new(
+                "chat.message",
+                json!({
+                    "from": from,
+                    "to": did,
+                    "text": format!("Hello {}, it's {}!", display_name, sender),
+                    "sentAt": chrono::Utc::now(),
+                }),
+            );
+            messages += 1;
+            write(
+                &space.join(format!("messages/{:03}-{}.json", messages, sender)),
+                &to_json(&message)?,
+            )?;
+        }
+
+        let mut claims = Map::new();
+        claims.insert("name".to_string(), json!(display_name));
+        claims.insert("membership".to_string(), json!("demo"));
+        let credential = node.issue_credential_with(CredentialRequest {
+            subject: did.clone(),
+            claims,
+            types: vec!["FlowMembershipCredential".to_string()],
+            valid_until: None,
+        })?;
+        write(
+            &space.join("credentials/membership.json"),
+            &to_json(&credential)?,
+        )?;
+
+        node.create_space_as(Actor::User(*id), username).await?;
+        let location = space.canonicalize()?.to_string_lossy().into_owned();
+        let space_key = entity::space::Entity::find()
+            .filter(entity::space::Column::Location.eq(location.as_str()))
+            .one(&node.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .map(|space| space.key)
+            .ok_or_else(|| AppError::NotFound(format!("Space not found: {}", location)))?;
+
+        report.files += DEMO_FILES.len() + 1 + messages + 1;
+        report.credentials += 1;
+        report.users.push(SeededUser {
+            id: *id,
+            did: did.clone(),
+            username: username.to_string(),
+            space_key,
+            location,
+        });
+    }
+
+    info!(
+        "Seeded {} users and {} files",
+        report.users.len(),
+        report.files
+    );
+    Ok(report)
+}
+
+/// Register a new user with a software passkey, as a browser would
+async fn register(node: &Node, origin: &Url) -> Result<(i32, String), AppError> {
+    let mut authenticator = SoftPasskey::new(true);
+    let (challenge, challenge_id) = node.start_webauthn_registration().await?;
+    let credential = authenticator
+        .perform_register(
+            origin.clone(),
+            challenge.public_key,
+            AUTHENTICATOR_TIMEOUT_MS,
+        )
+        .map_err(|e| AppError::Auth(format!("Authenticator registration failed: {:?}", e)))?;
+    let (did, _) = node
+        .finish_webauthn_registration(&challenge_id, credential)
+        .await?;
+
+    let user = entity::user::Entity::find()
+        .filter(entity::user::Column::Did.eq(did.as_str()))
+        .one(&node.db)
+        .await
+        .map_err(|e| AppError::Storage(Box::new(e)))?
+        .ok_or_else(|| AppError::NotFound(format!("User not found: {}", did)))?;
+    Ok((user.id, did))
+}
+
+fn write(path: &Path, content: &[u8]) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>, AppError> {
+    serde_json::to_vec_pretty(value)
+        .map_err(|e| AppError::Validation(format!("Failed to serialize seed data: {}", e)))
+}
@@ -0,0 +1,318 @@
+use crate::bootstrap::init::setup_test_server;
+use node::api::node::Node;
+use node::api::servers::app_state::AppState;
+use node::api::servers::grpc::{self, proto::*};
+use node::modules::pin::PinUnlock;
+use tempfile::TempDir;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+
+/// Serve gRPC for `node` on a random port and connect to it
+async fn connect(node: Node) -> Channel {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(grpc::serve(listener, AppState::new(node)));
+
+    Channel::from_shared(format!("http://{}", addr))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap()
+}
+
+/// Make a unary call to `flow.v1.Flow/<method>`
+async fn call<Req, Res>(
+    channel: &Channel,
+    method: &str,
+    request: Request<Req>,
+) -> Result<Res, Status>
+where
+    Req: prost::Message + Send + Sync + 'static,
+    Res: prost::Message + Default + Send + Sync + 'static,
+{
+    let mut client = tonic::client::Grpc::new(channel.clone());
+    client.ready().await.unwrap();
+    let path = format!("/{}/{}", grpc::SERVICE_NAME, method)
+        .parse()
+        .unwrap();
+    client
+        .unary(request, path, tonic_prost::ProstCodec::default())
+        .await
+        .map(|response| response.into_inner())
+}
+
+#[tokio::test]
+async fn test_grpc_health() {
+    let server = setup_test_server().await;
+    let channel = connect(server.node.clone()).await;
+
+    let health: HealthResponse = call(&channel, "Health", Request::new(HealthRequest {}))
+        .await
+        .unwrap();
+    assert_eq!(health.status, "healthy");
+    assert!(chrono::DateTime::parse_from_rfc3339(&health.timestamp).is_ok());
+
+    let unknown = call::<_, HealthResponse>(&channel, "Nope", Request::new(HealthRequest {})).await;
+    assert_eq!(unknown.unwrap_err().code(), Code::Unimplemented);
+}
+
+#[tokio::test]
+async fn test_grpc_spaces_need_an_unlock_token_to_list() {
+    let server = setup_test_server().await;
+    let channel = connect(server.node.clone()).await;
+    let dir = TempDir::new().unwrap();
+
+    let _: CreateSpaceResponse = call(
+        &channel,
+        "CreateSpace",
+        Request::new(CreateSpaceRequest {
+            dir: dir.path().to_str().unwrap().to_string(),
+            encrypted: false,
+            client_keys: false,
+        }),
+    )
+    .await
+    .unwrap();
+    let empty = call::<_, CreateSpaceResponse>(
+        &channel,
+        "CreateSpace",
+        Request::new(CreateSpaceRequest {
+            dir: String::new(),
+            encrypted: false,
+            client_keys: false,
+        }),
+    )
+    .await;
+    assert_eq!(empty.unwrap_err().code(), Code::InvalidArgument);
+
+    let locked =
+        call::<_, ListSpacesResponse>(&channel, "ListSpaces", Request::new(ListSpacesRequest {}))
+            .await;
+    assert_eq!(locked.unwrap_err().code(), Code::Unauthenticated);
+
+    server.node.local_pin().set("4821", None).await.unwrap();
+    let PinUnlock::Unlocked { token, .. } = server.node.local_pin().unlock("4821").await.unwrap()
+    else {
+        panic!("PIN should unlock");
+    };
+    let mut request = Request::new(ListSpacesRequest {});
+    request.metadata_mut().insert(
+        "authorization",
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    let listed: ListSpacesResponse = call(&channel, "ListSpaces", request).await.unwrap();
+    assert_eq!(listed.spaces.len(), 1);
+    assert!(
+        listed.spaces[0]
+            .location
+            .contains(dir.path().to_str().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn test_grpc_registration_ceremony() {
+    let server = setup_test_server().await;
+    let channel = connect(server.node.clone()).await;
+
+    let started: StartRegistrationResponse = call(
+        &channel,
+        "StartRegistration",
+        Request::new(StartRegistrationRequest::default()),
+    )
+    .await
+    .unwrap();
+    assert!(!started.challenge_id.is_empty());
+    let challenge: serde_json::Value = serde_json::from_str(&started.challenge_json).unwrap();
+    assert!(challenge["publicKey"]["challenge"].is_string());
+
+    let malformed = call::<_, FinishRegistrationResponse>(
+        &channel,
+        "FinishRegistration",
+        Request::new(FinishRegistrationRequest {
+            challenge_id: started.challenge_id,
+            credential_json: "{}".to_string(),
+        }),
+    )
+    .await;
+    assert_eq!(malformed.unwrap_err().code(), Code::InvalidArgument);
+
+    let overrides = call::<_, StartRegistrationResponse>(
+        &channel,
+        "StartRegistration",
+        Request::new(StartRegistrationRequest {
+            overrides_json: "not json".to_string(),
+        }),
+    )
+    .await;
+    assert_eq!(overrides.unwrap_err().code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_grpc_guest_mode_requires_a_session() {
+    let mut server = setup_test_server().await;
+    server.node.guest_mode = true;
+    let channel = connect(server.node.clone()).await;
+
+    let health =
+        call::<_, HealthResponse>(&channel, "Health", Request::new(HealthRequest {})).await;
+    assert!(health.is_ok());
+    // Signing in is open; this one only fails on its credential
+    let finished = call::<_, FinishAuthenticationResponse>(
+        &channel,
+        "FinishAuthentication",
+        Request::new(FinishAuthenticationRequest {
+            challenge_id: "unknown".to_string(),
+            credential_json: "{}".to_string(),
+        }),
+    )
+    .await;
+    assert_eq!(finished.unwrap_err().code(), Code::InvalidArgument);
+
+    let resolved = call::<_, ResolveDidResponse>(
+        &channel,
+        "ResolveDid",
+        Request::new(ResolveDidRequest {
+            did: "did:example:123".to_string(),
+            ..Default::default()
+        }),
+    )
+    .await;
+    assert_eq!(resolved.unwrap_err().code(), Code::Unauthenticated);
+}
+
+/// A message's fields as `(name, type, tag)`, the type as the .proto spells it
+type Fields = Vec<(String, String, u32)>;
+
+/// Messages as `(name, fields)` and methods as `(name, request, response)`
+type Api = (Vec<(String, Fields)>, Vec<(String, String, String)>);
+
+const PROTO: &str = include_str!("../../proto/flow/v1/flow.proto");
+const SERVER: &str = include_str!("../../src/api/servers/grpc.rs");
+
+/// `flow.proto`'s messages and rpcs
+fn proto_api() -> Api {
+    let source: String = PROTO
+        .lines()
+        .map(|line| line.split("//").next().unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let spaced = ["{", "}", "(", ")", ";", "="]
+        .iter()
+        .fold(source, |s, p| s.replace(p, &format!(" {} ", p)));
+    let mut tokens = spaced.split_whitespace();
+
+    let (mut messages, mut methods) = (Vec::new(), Vec::new());
+    while let Some(token) = tokens.next() {
+        match token {
+            "message" => {
+                let name = tokens.next().unwrap().to_string();
+                assert_eq!(tokens.next(), Some("{"));
+                let mut fields = Vec::new();
+                loop {
+                    let mut field: Vec<&str> = Vec::new();
+                    for token in tokens.by_ref() {
+                        if token == ";" || (token == "}" && field.is_empty()) {
+                            field.push(token);
+                            break;
+                        }
+                        field.push(token);
+                    }
+                    match field.as_slice() {
+                        ["}"] => break,
+                        [kind @ .., name, "=", tag, ";"] => {
+                            fields.push((name.to_string(), kind.join(" "), tag.parse().unwrap()))
+                        }
+                        other => panic!("Unexpected field in {}: {:?}", name, other),
+                    }
+                }
+                messages.push((name, fields));
+            }
+            "rpc" => {
+                let method: Vec<&str> = tokens.by_ref().take_while(|t| *t != ";").collect();
+                match method.as_slice() {
+                    [name, "(", request, ")", "returns", "(", response, ")"] => {
+                        methods.push((name.to_string(), request.to_string(), response.to_string()))
+                    }
+                    other => panic!("Unexpected rpc: {:?}", other),
+                }
+            }
+            _ => {}
+        }
+    }
+    (messages, methods)
+}
+
+/// The hand-written messages and routes of `grpc.rs`
+fn server_api() -> Api {
+    let start = SERVER.find("pub mod proto {").unwrap() + "pub mod proto {".len();
+    let end = start + SERVER[start..].find("\n}\n").unwrap();
+
+    let mut messages: Vec<(String, Fields)> = Vec::new();
+    let mut attribute: Option<(String, u32)> = None;
+    for line in SERVER[start..end].lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("pub struct ") {
+            messages.push((
+                name.trim_end_matches(['{', '}', ' ']).to_string(),
+                Vec::new(),
+            ));
+        } else if let Some(args) = line.strip_prefix("#[prost(") {
+            let args: Vec<&str> = args.trim_end_matches(")]").split(", ").collect();
+            let tag = args.last().unwrap().strip_prefix("tag = ").unwrap();
+            let label = if args.contains(&"repeated") {
+                "repeated "
+            } else {
+                ""
+            };
+            attribute = Some((
+                format!("{}{}", label, args[0]),
+                tag.trim_matches('"').parse().unwrap(),
+            ));
+        } else if let Some(field) = line.strip_prefix("pub ") {
+            let (name, rust_type) = field.trim_end_matches(',').split_once(": ").unwrap();
+            let (mut kind, tag) = attribute.take().unwrap();
+            if kind.ends_with("message") {
+                let inner = rust_type
+                    .trim_start_matches("Option<")
+                    .trim_start_matches("Vec<")
+                    .trim_end_matches('>');
+                kind = kind.replace("message", inner);
+            }
+            messages
+                .last_mut()
+                .unwrap()
+                .1
+                .push((name.to_string(), kind, tag));
+        }
+    }
+
+    let generic = |text: &str, wrapper: &str| {
+        let at = text.find(wrapper).unwrap() + wrapper.len();
+        text[at..].split('>').next().unwrap().to_string()
+    };
+    let methods = SERVER
+        .split("Some(\"")
+        .skip(1)
+        .map(|arm| {
+            let (name, rest) = arm.split_once("\") => route!(").unwrap();
+            let handler = rest.split(')').next().unwrap();
+            let at = SERVER.find(&format!("async fn {}(", handler)).unwrap();
+            let signature = SERVER[at..].split('{').next().unwrap();
+            (
+                name.to_string(),
+                generic(signature, "Request<"),
+                generic(signature, "Response<"),
+            )
+        })
+        .collect();
+    (messages, methods)
+}
+
+#[test]
+fn test_grpc_messages_match_the_proto() {
+    let (proto_messages, proto_methods) = proto_api();
+    let (server_messages, server_methods) = server_api();
+
+    assert!(!proto_messages.is_empty() && !proto_methods.is_empty());
+    assert_eq!(server_messages, proto_messages);
+    assert_eq!(server_methods, proto_methods);
+}
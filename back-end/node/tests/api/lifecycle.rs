@@ -0,0 +1,66 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::http::StatusCode;
+use futures_util::StreamExt;
+use node::api::servers::{app_state::AppState, rest, websocket};
+use node::lifecycle;
+use sea_orm::ConnectionTrait;
+use serde_json::json;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::{connect_async, tungstenite};
+
+#[tokio::test]
+async fn test_admin_shutdown_stops_servers_and_tasks() {
+    let (node, _temp) = setup_test_node().await;
+    let background = node.tasks.spawn("scheduler", std::future::pending::<()>());
+    let state = AppState::new(node);
+
+    // A WebSocket server stopping gracefully, with a client connected
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(
+        axum::serve(listener, websocket::build_router(state.clone()))
+            .with_graceful_shutdown(state.shutdown.triggered())
+            .into_future(),
+    );
+    let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+
+    let router = rest::build_router(state.clone());
+    let (status, body) = post_request(&router, "/api/v1/admin/shutdown", json!({})).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    assert!(state.shutdown.is_triggered());
+
+    let closed = timeout(Duration::from_secs(5), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let tungstenite::Message::Close(Some(frame)) = closed else {
+        panic!("Expected a close frame, got {:?}", closed);
+    };
+    assert_eq!(
+        frame.code,
+        tungstenite::protocol::frame::coding::CloseCode::Away
+    );
+
+    timeout(Duration::from_secs(5), server)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(background.await.unwrap(), None);
+    assert!(state.node.read().await.tasks.running().is_empty());
+}
+
+#[tokio::test]
+async fn test_close_flushes_and_disconnects() {
+    let (node, _temp) = setup_test_node().await;
+    node.kv.insert(b"kept", b"value").unwrap();
+
+    lifecycle::close(&node).await.unwrap();
+    assert!(node.db.execute_unprepared("SELECT 1").await.is_err());
+    assert_eq!(
+        node.kv.get(b"kept").unwrap().as_deref(),
+        Some(&b"value"[..])
+    );
+}
@@ -0,0 +1,14 @@
+use node::api::servers::listen;
+use std::net::SocketAddr;
+
+#[tokio::test]
+async fn test_bind_all_listens_on_every_address() {
+    let addrs: Vec<SocketAddr> = vec!["127.0.0.1:0".parse().unwrap()];
+    let listeners = listen::bind_all(&addrs).unwrap();
+    assert_eq!(listeners.len(), 1);
+    let bound = listeners[0].local_addr().unwrap();
+    assert!(bound.ip().is_loopback());
+
+    // The port is taken now
+    assert!(listen::bind_all(&[bound]).is_err());
+}
@@ -1,2 +1,5 @@
+pub mod grpc;
+pub mod lifecycle;
+pub mod listen;
 pub mod rest;
 pub mod websocket;
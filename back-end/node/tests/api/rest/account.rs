@@ -0,0 +1,53 @@
+use crate::util::users::signed_in_user;
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::http::StatusCode;
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::RetentionConfig;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_delete_account_offers_export_during_grace() {
+    let (node, _temp) = setup_test_node().await;
+    let (_, token) = signed_in_user(&node, "leaving").await;
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, _) = delete_request(&router, "/api/v1/users/me").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = delete_request_with_token(&router, "/api/v1/users/me", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "scheduled");
+    assert!(body["eraseAfter"].is_string());
+    assert_eq!(body["export"], "/api/v1/users/me/export");
+
+    let (status, export) = get_request_with_token(&router, "/api/v1/users/me/export", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", export);
+    assert_eq!(export["user"]["did"], "did:key:leaving");
+    assert_eq!(export["pendingDeletion"]["eraseAfter"], body["eraseAfter"]);
+
+    let uri = "/api/v1/users/me/cancel_deletion";
+    let (status, _) = post_request_with_token(&router, uri, json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_request_with_token(&router, uri, json!({}), &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_delete_account_without_grace_erases_at_once() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.retention = RetentionConfig {
+        account_deletion_grace: std::time::Duration::ZERO,
+        ..Default::default()
+    };
+    let (_, token) = signed_in_user(&node, "leaving").await;
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, body) = delete_request_with_token(&router, "/api/v1/users/me", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "erased");
+    assert_eq!(body["did"], "did:key:leaving");
+    assert_eq!(body["sessions"], 1);
+
+    let (status, _) = get_request_with_token(&router, "/api/v1/auth/session", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
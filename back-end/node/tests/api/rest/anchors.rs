@@ -0,0 +1,42 @@
+use crate::api::rest::helpers::*;
+use crate::bootstrap::init::{setup_test_node, with_did_key};
+use axum::http::StatusCode;
+use node::api::servers::app_state::AppState;
+use node::api::servers::rest;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_anchor_space() {
+    let (node, _temp) = setup_test_node().await;
+    let node = with_did_key(node, 7);
+    let space_dir = TempDir::new().unwrap();
+    std::fs::write(space_dir.path().join("a.txt"), b"hello").unwrap();
+    node.create_space(space_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    let router = rest::build_router(AppState::new(node));
+    let anchors_uri = format!("/api/v1/spaces/{}/anchors", key);
+
+    let (status, anchors) = get_request(&router, &anchors_uri).await;
+    assert_eq!(status, StatusCode::OK, "{}", anchors);
+    assert_eq!(anchors, serde_json::json!([]));
+
+    let (status, anchor) = post_request(&router, &anchors_uri, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", anchor);
+    assert_eq!(anchor["spaceKey"], key.as_str());
+    assert_eq!(
+        anchor["credential"]["credential"]["credentialSubject"]["space"]["merkleRoot"],
+        anchor["merkleRoot"]
+    );
+
+    let (_, merkle) = get_request(&router, &format!("/api/v1/spaces/{}/merkle", key)).await;
+    assert_eq!(merkle["hash"], anchor["merkleRoot"]);
+
+    let (_, anchors) = get_request(&router, &anchors_uri).await;
+    assert_eq!(anchors.as_array().unwrap().len(), 1);
+
+    let missing = format!("/api/v1/spaces/{}/anchors", "0".repeat(64));
+    let (status, _) = get_request(&router, &missing).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,139 @@
+use crate::bootstrap::init::setup_test_node;
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use node::api::servers::{app_state::AppState, rest};
+use node::modules::archive::SIZE_ESTIMATE_HEADER;
+use node::modules::space::Encryption;
+use node::modules::tenancy::Actor;
+use std::io::Read;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+async fn download(router: &Router, uri: &str) -> (StatusCode, axum::http::HeaderMap, Vec<u8>) {
+    let response = router
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, headers, bytes.to_vec())
+}
+
+/// Names and contents of the entries of a tar archive
+fn entries(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    tar::Archive::new(archive)
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).unwrap();
+            (name, contents)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_archive_streams_a_folder_as_tar() {
+    let (node, _temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    let long_name = format!("docs/{}.txt", "n".repeat(120));
+    std::fs::create_dir(space_dir.path().join("docs")).unwrap();
+    std::fs::write(space_dir.path().join("a.txt"), b"top").unwrap();
+    std::fs::write(space_dir.path().join("docs/b.txt"), vec![7u8; 1500]).unwrap();
+    std::fs::write(space_dir.path().join(&long_name), b"long").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("/etc/passwd", space_dir.path().join("docs/link")).unwrap();
+    node.create_space(space_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, headers, body) =
+        download(&router, &format!("/api/v1/spaces/{}/archive", key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/x-tar");
+    assert_eq!(
+        headers[header::CONTENT_DISPOSITION],
+        format!("attachment; filename=\"{}.tar\"", key)
+    );
+    assert_eq!(
+        headers[SIZE_ESTIMATE_HEADER].to_str().unwrap(),
+        body.len().to_string()
+    );
+    assert_eq!(
+        entries(&body),
+        vec![
+            ("a.txt".to_string(), b"top".to_vec()),
+            ("docs/b.txt".to_string(), vec![7u8; 1500]),
+            (long_name, b"long".to_vec()),
+        ]
+    );
+
+    let (status, headers, body) = download(
+        &router,
+        &format!("/api/v1/spaces/{}/archive?path=docs", key),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        headers[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"docs.tar\""
+    );
+    let names: Vec<_> = entries(&body).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names[0], "b.txt");
+    assert_eq!(names.len(), 2);
+}
+
+#[tokio::test]
+async fn test_encrypted_space_archives_plaintext() {
+    let (node, _temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    std::fs::write(space_dir.path().join("secret.txt"), b"plaintext").unwrap();
+    node.create_space_with(
+        Actor::Node,
+        space_dir.path().to_str().unwrap(),
+        Encryption::Node,
+    )
+    .await
+    .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, headers, body) =
+        download(&router, &format!("/api/v1/spaces/{}/archive", key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        headers[SIZE_ESTIMATE_HEADER].to_str().unwrap(),
+        body.len().to_string()
+    );
+    assert_eq!(
+        entries(&body),
+        vec![("secret.txt".to_string(), b"plaintext".to_vec())]
+    );
+}
+
+#[tokio::test]
+async fn test_archive_of_missing_or_escaping_folder() {
+    let (node, _temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    node.create_space(space_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    let router = rest::build_router(AppState::new(node));
+
+    let uri = format!("/api/v1/spaces/{}/archive?path=nope", key);
+    assert_eq!(download(&router, &uri).await.0, StatusCode::NOT_FOUND);
+    let uri = format!("/api/v1/spaces/{}/archive?path=../", key);
+    assert_eq!(download(&router, &uri).await.0, StatusCode::BAD_REQUEST);
+    let uri = format!("/api/v1/spaces/{}/archive", "ab".repeat(32));
+    assert_eq!(download(&router, &uri).await.0, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,47 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_server};
+use axum::http::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn test_session_and_logout() {
+    let server = setup_test_server().await;
+    let (token, session) = server.node.user_sessions().open(1).await.unwrap();
+
+    let (status, body) =
+        get_request_with_token(&server.router, "/api/v1/auth/session", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["id"], session.id);
+    assert_eq!(body["userId"], 1);
+    assert!(body["expiresAt"].is_string());
+
+    let (status, _) = get_request(&server.router, "/api/v1/auth/session").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) =
+        get_request_with_token(&server.router, "/api/v1/auth/session", "forged").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) =
+        post_request_with_token(&server.router, "/api/v1/auth/logout", json!({}), &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = get_request_with_token(&server.router, "/api/v1/auth/session", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) =
+        post_request_with_token(&server.router, "/api/v1/auth/logout", json!({}), &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_session_lists_spaces() {
+    let server = setup_test_server().await;
+    let (token, _) = server.node.user_sessions().open(1).await.unwrap();
+
+    let (status, _) = get_request(&server.router, "/api/v1/spaces").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = get_request_with_token(&server.router, "/api/v1/spaces", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    server.node.user_sessions().close(&token).await.unwrap();
+    let (status, _) = get_request_with_token(&server.router, "/api/v1/spaces", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,198 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use node::api::servers::{app_state::AppState, rest};
+use serde_json::json;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+/// Router over a node with one space with client-held keys, its key and a
+/// session token
+async fn setup() -> (Router, String, String, TempDir, TempDir) {
+    let (node, temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    let token = session_token(&node).await;
+    let router = rest::build_router(AppState::new(node.clone()));
+
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": space_dir.path().to_str().unwrap(), "client_keys": true}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let space = node.list_spaces().await.unwrap().remove(0);
+    assert!(space.client_keys);
+    assert!(!space.encrypted);
+    (router, space.key, token, space_dir, temp)
+}
+
+async fn send(router: &Router, method: &str, uri: &str, bytes: Vec<u8>) -> StatusCode {
+    router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(method)
+                .body(Body::from(bytes))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_blobs_are_stored_as_uploaded() {
+    let (router, key, token, space_dir, _temp) = setup().await;
+    let sealed = b"\x01opaque ciphertext\xff".to_vec();
+
+    let uri = format!("/api/v1/spaces/{}/files/chunk_0a1b-2c", key);
+    assert_eq!(
+        send(&router, "PUT", &uri, sealed.clone()).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        std::fs::read(space_dir.path().join("chunk_0a1b-2c")).unwrap(),
+        sealed
+    );
+    let response = router
+        .clone()
+        .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body.to_vec(), sealed);
+
+    // Names are the client's opaque blob ids, never paths
+    for name in ["docs/a.txt", "notes.txt", ".flow-index"] {
+        let uri = format!("/api/v1/spaces/{}/files/{}", key, name);
+        assert_eq!(
+            send(&router, "PUT", &uri, b"x".to_vec()).await,
+            StatusCode::BAD_REQUEST,
+            "{} should be refused",
+            name
+        );
+    }
+
+    let (_, listing) = get_request_with_token(&router, "/api/v1/spaces", &token).await;
+    assert_eq!(listing["spaces"][0]["client_keys"], true);
+}
+
+#[tokio::test]
+async fn test_index_round_trip() {
+    let (router, key, _, _space_dir, _temp) = setup().await;
+    let uri = format!("/api/v1/spaces/{}/index", key);
+
+    assert_eq!(
+        send(&router, "GET", &uri, Vec::new()).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        send(&router, "PUT", &uri, b"sealed index v1".to_vec()).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        send(&router, "PUT", &uri, b"sealed index v2".to_vec()).await,
+        StatusCode::OK
+    );
+
+    let response = router
+        .clone()
+        .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[header::ETAG].clone();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body.as_ref(), b"sealed index v2");
+
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(&uri)
+                .header(header::IF_NONE_MATCH, etag)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+async fn test_only_client_key_spaces_keep_an_index() {
+    let (node, _temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    node.create_space(space_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    let router = rest::build_router(AppState::new(node));
+
+    let uri = format!("/api/v1/spaces/{}/index", key);
+    assert_eq!(
+        send(&router, "PUT", &uri, b"index".to_vec()).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        send(&router, "GET", &uri, Vec::new()).await,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn test_client_keys_need_a_fresh_empty_space() {
+    let (node, _temp) = setup_test_node().await;
+    let token = session_token(&node).await;
+    let router = rest::build_router(AppState::new(node.clone()));
+
+    let both = TempDir::new().unwrap();
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": both.path().to_str().unwrap(), "encrypted": true, "client_keys": true}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let full = TempDir::new().unwrap();
+    std::fs::write(full.path().join("plain.txt"), b"readable").unwrap();
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": full.path().to_str().unwrap(), "client_keys": true}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // An existing space has been open to the node, even while empty
+    let plain = TempDir::new().unwrap();
+    node.create_space(plain.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": plain.path().to_str().unwrap(), "client_keys": true}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(
+        node.list_spaces()
+            .await
+            .unwrap()
+            .iter()
+            .all(|s| !s.client_keys)
+    );
+}
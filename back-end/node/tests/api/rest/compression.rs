@@ -0,0 +1,86 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use http_body_util::BodyExt;
+use node::api::servers::{app_state::AppState, compression, rest};
+use node::bootstrap::config::CompressionConfig;
+use tower::ServiceExt;
+
+async fn router(config: CompressionConfig) -> Router {
+    let (node, _temp) = setup_test_node().await;
+    rest::build_router(AppState::new(node).with_compression(config))
+}
+
+/// GET with `Accept-Encoding`, returning the response encoding and body size
+async fn get_encoded(router: &Router, uri: &str, accept: &str) -> (Option<String>, usize) {
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT_ENCODING, accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (encoding, body.len())
+}
+
+#[test]
+fn test_compressible_content_types() {
+    assert!(compression::compressible("application/json"));
+    assert!(compression::compressible(
+        "application/did+json; charset=utf-8"
+    ));
+    assert!(compression::compressible("text/html"));
+    assert!(!compression::compressible("image/png"));
+    assert!(!compression::compressible("application/gzip"));
+    assert!(!compression::compressible("application/octet-stream"));
+}
+
+#[tokio::test]
+async fn test_json_is_compressed_as_negotiated() {
+    let router = router(CompressionConfig {
+        enabled: true,
+        min_size_bytes: 256,
+    })
+    .await;
+
+    let (encoding, plain_size) = get_encoded(&router, "/api/v1/node", "identity").await;
+    assert_eq!(encoding, None);
+
+    for accept in ["gzip", "br", "zstd"] {
+        let (encoding, size) = get_encoded(&router, "/api/v1/node", accept).await;
+        assert_eq!(encoding.as_deref(), Some(accept));
+        assert!(size < plain_size);
+    }
+
+    let (_, body) = get_request(&router, "/api/v1/admin/compression").await;
+    assert_eq!(body["stats"]["responses"], 3);
+    assert_eq!(body["stats"]["originalBytes"], 3 * plain_size as u64);
+    assert!(body["stats"]["savedBytes"].as_u64().unwrap() > 0);
+
+    // Below the threshold
+    let (encoding, _) = get_encoded(&router, "/api/v1/health", "gzip").await;
+    assert_eq!(encoding, None);
+}
+
+#[tokio::test]
+async fn test_compression_can_be_disabled() {
+    let router = router(CompressionConfig {
+        enabled: false,
+        min_size_bytes: 0,
+    })
+    .await;
+
+    let (encoding, _) = get_encoded(&router, "/api/v1/node", "gzip, br").await;
+    assert_eq!(encoding, None);
+}
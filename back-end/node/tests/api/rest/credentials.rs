@@ -0,0 +1,48 @@
+use crate::api::rest::helpers::*;
+use crate::bootstrap::init::{setup_test_node, with_did_key};
+use axum::http::StatusCode;
+use node::api::servers::app_state::AppState;
+use node::api::servers::rest;
+use node::modules::ssi::vc::{self, VerifiableCredential};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_issue_credential() {
+    // Credentials name the node's did:key as their issuer
+    let (node, _temp) = setup_test_node().await;
+    let router = rest::build_router(AppState::new(with_did_key(node, 5)));
+
+    let subject = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+    let (status, issued) = post_request(
+        &router,
+        "/api/v1/credentials/issue",
+        json!({
+            "subject": subject,
+            "claims": { "name": "Alice" },
+            "type": ["NameCredential"]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", issued);
+
+    let credential: VerifiableCredential =
+        serde_json::from_value(issued["credential"].clone()).unwrap();
+    assert_eq!(credential.subject(), Some(subject));
+    assert_eq!(
+        issued["credential"]["proof"]["cryptosuite"],
+        "eddsa-jcs-2022"
+    );
+    vc::verify_credential(&credential).unwrap();
+
+    let jwt = issued["jwt"].as_str().unwrap();
+    assert_eq!(jwt.split('.').count(), 3);
+    assert_eq!(vc::verify_jwt(jwt).unwrap().id, credential.id);
+
+    let (status, _) = post_request(
+        &router,
+        "/api/v1/credentials/issue",
+        json!({ "subject": "not-a-did" }),
+    )
+    .await;
+    assert!(status.is_client_error());
+}
@@ -0,0 +1,223 @@
+use crate::api::rest::helpers::post_request;
+use crate::bootstrap::init::{setup_test_node, setup_test_server};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode, header};
+use http_body_util::BodyExt;
+use node::api::servers::{app_state::AppState, rest};
+use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+async fn get_document(
+    router: &Router,
+    did: &str,
+    if_none_match: Option<&str>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let mut request = Request::builder().uri(format!("/api/v1/dids/{}/document", did));
+    if let Some(etag) = if_none_match {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    let response = router
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_did_document_is_cached_by_content() {
+    let server = setup_test_server().await;
+    let did = PeerDidGenerator::from_ed25519_bytes(&[7u8; 32]).unwrap();
+
+    let (status, headers, body) = get_document(&server.router, &did, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/did+json");
+    // did:peer:0 documents are derived from the DID itself
+    assert!(
+        headers[header::CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .contains("immutable")
+    );
+    let document: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(document["id"], did.as_str());
+
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+    let (status, headers, body) = get_document(&server.router, &did, Some(&etag)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(headers[header::ETAG], etag.as_str());
+    assert!(body.is_empty());
+
+    let (status, _, _) = get_document(&server.router, &did, Some("\"stale\"")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Same content, same tag
+    let (_, headers, _) = get_document(&server.router, &did, None).await;
+    assert_eq!(headers[header::ETAG], etag.as_str());
+}
+
+#[tokio::test]
+async fn test_did_document_errors() {
+    let server = setup_test_server().await;
+
+    let (status, _, _) = get_document(&server.router, "did:peer:0zINVALID", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = get_document(&server.router, "did:peer:0z!!!INVALID!!!", None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+async fn resolve(router: &Router, uri: &str) -> (StatusCode, Value) {
+    let response = router
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_resolve_returns_full_result() {
+    let server = setup_test_server().await;
+    let did = PeerDidGenerator::from_ed25519_bytes(&[8u8; 32]).unwrap();
+
+    let (status, result) = resolve(&server.router, &format!("/api/v1/dids/resolve/{}", did)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["didDocument"]["id"], did.as_str());
+    let metadata = &result["didResolutionMetadata"];
+    assert_eq!(metadata["did_method"], "peer");
+    assert_eq!(metadata["from_cache"], false);
+    assert!(metadata["verifiable_data_registry"].is_object());
+    assert!(result["didDocumentMetadata"].is_object());
+
+    let (_, cached) = resolve(&server.router, &format!("/api/v1/dids/resolve/{}", did)).await;
+    assert_eq!(cached["didResolutionMetadata"]["from_cache"], true);
+
+    let (status, fresh) = resolve(
+        &server.router,
+        &format!("/api/v1/dids/resolve/{}?no_cache=true&timeout_ms=5000", did),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fresh["didResolutionMetadata"]["from_cache"], false);
+}
+
+#[tokio::test]
+async fn test_resolve_errors() {
+    let server = setup_test_server().await;
+
+    // Well-formed, but doesn't decode
+    let (status, _) = resolve(&server.router, "/api/v1/dids/resolve/did:peer:0zINVALID").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Rejected before reaching the resolver
+    let (status, _) = resolve(
+        &server.router,
+        "/api/v1/dids/resolve/did:peer:0z!!!INVALID!!!",
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = resolve(
+        &server.router,
+        &format!("/api/v1/dids/resolve/did:peer:0z{}", "a".repeat(5000)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let did = PeerDidGenerator::from_ed25519_bytes(&[8u8; 32]).unwrap();
+    let (status, _) = resolve(
+        &server.router,
+        &format!("/api/v1/dids/resolve/{}?accept=not-a-media-type", did),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+async fn resolve_batch(router: &Router, body: Value) -> (StatusCode, HeaderMap, Vec<Value>) {
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/dids/resolve_batch")
+                .method("POST")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let lines = String::from_utf8_lossy(&body)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    (status, headers, lines)
+}
+
+#[tokio::test]
+async fn test_resolve_batch_streams_a_line_per_did() {
+    let server = setup_test_server().await;
+    let first = PeerDidGenerator::from_ed25519_bytes(&[9u8; 32]).unwrap();
+    let second = PeerDidGenerator::from_ed25519_bytes(&[10u8; 32]).unwrap();
+    let dids = [first.as_str(), "did:peer:0zINVALID", second.as_str()];
+
+    let (status, headers, mut lines) =
+        resolve_batch(&server.router, json!({ "dids": dids, "noCache": true })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/x-ndjson");
+    assert_eq!(lines.len(), 3);
+
+    lines.sort_by_key(|line| line["index"].as_u64());
+    for (line, did) in lines.iter().zip(dids) {
+        assert_eq!(line["did"], did);
+    }
+    assert_eq!(lines[0]["result"]["didDocument"]["id"], first.as_str());
+    assert_eq!(lines[1]["error"]["status"], 400);
+    assert!(lines[1].get("result").is_none());
+    assert_eq!(lines[2]["result"]["didDocument"]["id"], second.as_str());
+}
+
+#[tokio::test]
+async fn test_resolve_batch_limits() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.did_batch.max_dids = 2;
+    let router = rest::build_router(AppState::new(node));
+    let did = PeerDidGenerator::from_ed25519_bytes(&[11u8; 32]).unwrap();
+
+    let (status, _) =
+        post_request(&router, "/api/v1/dids/resolve_batch", json!({ "dids": [] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post_request(
+        &router,
+        "/api/v1/dids/resolve_batch",
+        json!({ "dids": [did, did, did] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // One malformed DID rejects the whole batch before any is resolved
+    let (status, _) = post_request(
+        &router,
+        "/api/v1/dids/resolve_batch",
+        json!({ "dids": [did, "did:peer:0z!!!INVALID!!!"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, lines) = resolve_batch(&router, json!({ "dids": [did, did] })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(lines.len(), 2);
+}
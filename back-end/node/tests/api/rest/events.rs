@@ -0,0 +1,145 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_server};
+use axum::http::StatusCode;
+use event::schema::{
+    self, AuthenticationLocked, CredentialBackupChanged, CredentialVerified, FileChanged,
+    IdentityKeyRotated, KeyUsageAnomaly, MessageReceived, ScheduledTaskFailed, UserCreated,
+    VERSION_FIELD,
+};
+use event::types::{Event, EventType};
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+
+#[tokio::test]
+async fn test_event_schema() {
+    let server = setup_test_server().await;
+
+    let (status, body) = get_request(&server.router, "/api/v1/events/schema").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["versionField"], VERSION_FIELD);
+    assert_eq!(body["idField"], schema::ID_FIELD);
+    let types: Vec<&str> = body["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["type"].as_str().unwrap())
+        .collect();
+    let all: Vec<&str> = EventType::ALL.iter().map(EventType::as_str).collect();
+    assert_eq!(types, all);
+
+    let rotated = &body["events"][EventType::ALL.len() - 1];
+    assert_eq!(rotated["version"], 1);
+    assert_eq!(
+        rotated["fields"][0],
+        json!({
+            "name": "previous_did",
+            "type": "string",
+            "required": true,
+            "description": "DID before the rotation"
+        })
+    );
+}
+
+#[test]
+fn test_payloads_match_their_schemas() {
+    let events = [
+        Event::from_payload(
+            EventType::FileCreated,
+            &FileChanged {
+                space_key: "k".to_string(),
+                path: "a.txt".to_string(),
+                user_id: Some(1),
+            },
+        ),
+        Event::from_payload(
+            EventType::MessageReceived,
+            &MessageReceived {
+                peer: "did:key:z".to_string(),
+                kind: "sync.offer".to_string(),
+                body: json!({}),
+            },
+        ),
+        Event::from_payload(
+            EventType::UserCreated,
+            &UserCreated {
+                user_id: 1,
+                did: "did:key:z".to_string(),
+                username: "alice".to_string(),
+            },
+        ),
+        Event::from_payload(
+            EventType::CredentialVerified,
+            &CredentialVerified {
+                credential_id: "abc".to_string(),
+                counter: 3,
+            },
+        ),
+        Event::from_payload(
+            EventType::AuthenticationLocked,
+            &AuthenticationLocked {
+                key: "credential:abc".to_string(),
+                locked_until: "2026-01-01T00:00:00Z".to_string(),
+            },
+        ),
+        Event::from_payload(
+            EventType::CredentialBackupChanged,
+            &CredentialBackupChanged {
+                credential_id: "abc".to_string(),
+                backup_eligible: true,
+                backup_state: true,
+                previous_backup_state: false,
+            },
+        ),
+        Event::from_payload(
+            EventType::ScheduledTaskFailed,
+            &ScheduledTaskFailed {
+                task_id: "t".to_string(),
+                name: "backup".to_string(),
+                error: "disk full".to_string(),
+            },
+        ),
+        Event::from_payload(
+            EventType::KeyUsageAnomaly,
+            &KeyUsageAnomaly {
+                kind: "passkey".to_string(),
+                key_id: "abc".to_string(),
+                anomaly: json!({"type": "dormant"}),
+                user_id: Some(1),
+            },
+        ),
+        Event::from_payload(
+            EventType::IdentityKeyRotated,
+            &IdentityKeyRotated {
+                previous_did: "did:key:a".to_string(),
+                did: "did:key:b".to_string(),
+                version: 2,
+            },
+        ),
+    ];
+
+    for event in &events {
+        let schema = schema::schema(event.event_type);
+        assert_eq!(event.properties[VERSION_FIELD], json!(schema.version));
+
+        let mut properties: BTreeSet<&str> = event.properties.keys().map(String::as_str).collect();
+        properties.remove(VERSION_FIELD);
+        let fields: BTreeSet<&str> = schema.fields.iter().map(|field| field.name).collect();
+        assert_eq!(properties, fields, "{}", event.event_type);
+    }
+
+    // Payloads read back from their events, and only from theirs
+    let rotated = events.last().unwrap();
+    let payload = rotated.payload::<IdentityKeyRotated>().unwrap().unwrap();
+    assert_eq!(payload.version, 2);
+    assert!(rotated.payload::<FileChanged>().is_none());
+
+    // Optional fields are left out when unset
+    let event = Event::from_payload(
+        EventType::FileDeleted,
+        &FileChanged {
+            space_key: "k".to_string(),
+            path: "a.txt".to_string(),
+            user_id: None,
+        },
+    );
+    assert_eq!(event.properties.get("user_id"), None::<&Value>);
+}
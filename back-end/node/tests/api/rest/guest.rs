@@ -0,0 +1,101 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::Router;
+use axum::http::{Method, StatusCode};
+use node::api::node::Node;
+use node::api::servers::{access, app_state::AppState, rest};
+use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+use serde_json::json;
+use tempfile::TempDir;
+
+async fn guest_router() -> (Router, Node, TempDir) {
+    let (mut node, temp) = setup_test_node().await;
+    node.guest_mode = true;
+    (rest::build_router(AppState::new(node.clone())), node, temp)
+}
+
+#[test]
+fn test_published_paths() {
+    assert!(access::is_published("/api/v1/health"));
+    assert!(access::is_published("/api/v1/dids/did:key:z6Mk/document"));
+    assert!(access::is_published(
+        "/api/v1/dids/resolve/did:web:example.com"
+    ));
+    assert!(!access::is_published("/api/v1/dids//document"));
+    assert!(!access::is_published("/api/v1/dids/a/document/extra"));
+    assert!(!access::is_published("/api/v1/public/shares/abc"));
+    assert!(!access::is_published("/api/v1/node/attest"));
+    assert!(!access::is_published("/api/v1/healthz"));
+
+    assert!(access::guest_allowed(&Method::GET, "/api/v1/node"));
+    assert!(!access::guest_allowed(&Method::POST, "/api/v1/node"));
+    assert!(access::guest_allowed(&Method::POST, "/api/v1/pin/unlock"));
+    assert!(!access::guest_allowed(&Method::GET, "/api/v1/spaces"));
+}
+
+#[tokio::test]
+async fn test_guests_only_reach_published_resources() {
+    let (router, _node, _temp) = guest_router().await;
+
+    let (status, _) = get_request(&router, "/api/v1/health").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get_request(&router, "/api/v1/node").await;
+    assert_eq!(status, StatusCode::OK);
+    let did = PeerDidGenerator::from_ed25519_bytes(&[7u8; 32]).unwrap();
+    let (status, _) = get_request(&router, &format!("/api/v1/dids/{}/document", did)).await;
+    assert_eq!(status, StatusCode::OK);
+    // No passkeys yet, but the ceremony is reachable
+    let (status, _) =
+        post_request(&router, "/api/v1/webauthn/start_authentication", json!({})).await;
+    assert_ne!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = get_request(&router, "/api/v1/tasks").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = post_request(&router, "/api/v1/spaces", json!({"dir": "/tmp/guest"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = get_request(&router, "/api/v1/webauthn/start_registration").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = get_request_with_token(&router, "/api/v1/tasks", "forged").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_sessions_unlock_everything_else() {
+    let (router, node, _temp) = guest_router().await;
+    let (token, _) = node.user_sessions().open(1).await.unwrap();
+
+    let (status, _) = get_request_with_token(&router, "/api/v1/tasks", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Outside multi-user mode the session acts for the node
+    let space_dir = TempDir::new().unwrap();
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": space_dir.path().to_str().unwrap()}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(node.list_spaces().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_pin_grants_are_read_only() {
+    let (router, node, _temp) = guest_router().await;
+    node.local_pin().set("4321", None).await.unwrap();
+
+    let (status, body) = post_request(&router, "/api/v1/pin/unlock", json!({"pin": "4321"})).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = body["token"].as_str().unwrap();
+
+    let (status, _) = get_request_with_token(&router, "/api/v1/spaces", token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": "/tmp/guest"}),
+        token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
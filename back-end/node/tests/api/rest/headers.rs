@@ -0,0 +1,191 @@
+use crate::bootstrap::init::setup_test_node;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, Request, header};
+use axum::middleware;
+use axum::routing::get;
+use node::api::servers::app_state::AppState;
+use node::api::servers::headers::{self, API_CSP, PUBLISHED_CSP, SecurityHeaders};
+use node::api::servers::rest;
+use node::bootstrap::config::{ProxyConfig, SecurityHeadersConfig, ServerConfig};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// Response headers for GET `uri` from `peer`
+async fn headers_of(app: &Router, uri: &str, peer: &str, extra: &[(&str, &str)]) -> HeaderMap {
+    let mut request = Request::builder()
+        .uri(uri)
+        .header("host", "flow.example.com")
+        .extension(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+    for (name, value) in extra {
+        request = request.header(*name, *value);
+    }
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    response.headers().clone()
+}
+
+fn header(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).map(|v| v.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn test_api_responses_get_security_headers() {
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node));
+
+    let headers = headers_of(&app, "/api/v1/health", "127.0.0.1:5000", &[]).await;
+    assert_eq!(
+        header(&headers, header::X_CONTENT_TYPE_OPTIONS),
+        Some("nosniff")
+    );
+    assert_eq!(
+        header(&headers, header::REFERRER_POLICY),
+        Some(headers::REFERRER_POLICY)
+    );
+    assert_eq!(
+        header(&headers, header::CONTENT_SECURITY_POLICY),
+        Some(API_CSP)
+    );
+    // Plain HTTP
+    assert_eq!(header(&headers, header::STRICT_TRANSPORT_SECURITY), None);
+
+    // Errors too
+    let headers = headers_of(&app, "/api/v1/nowhere", "127.0.0.1:5000", &[]).await;
+    assert_eq!(
+        header(&headers, header::X_CONTENT_TYPE_OPTIONS),
+        Some("nosniff")
+    );
+}
+
+#[tokio::test]
+async fn test_hsts_only_over_https() {
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node).with_proxy(ProxyConfig {
+        trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        base_path: String::new(),
+        external_origin: None,
+        country_header: None,
+    }));
+    let forwarded = [("x-forwarded-proto", "https")];
+
+    let headers = headers_of(&app, "/api/v1/health", "10.0.0.2:5000", &forwarded).await;
+    assert_eq!(
+        header(&headers, header::STRICT_TRANSPORT_SECURITY),
+        Some("max-age=31536000; includeSubDomains")
+    );
+
+    // Only a trusted proxy is believed
+    let headers = headers_of(&app, "/api/v1/health", "203.0.113.7:5000", &forwarded).await;
+    assert_eq!(header(&headers, header::STRICT_TRANSPORT_SECURITY), None);
+}
+
+#[tokio::test]
+async fn test_published_content_policy_and_overrides() {
+    let security = Arc::new(SecurityHeaders {
+        config: SecurityHeadersConfig::default(),
+        tls: true,
+    });
+    let app = Router::new()
+        .route("/api/v1/public/sites/home", get(|| async { "<h1>Hi</h1>" }))
+        .route(
+            "/api/v1/public/sites/embed",
+            get(|| async { ([(header::CONTENT_SECURITY_POLICY, "default-src *")], "") }),
+        )
+        .layer(middleware::from_fn_with_state(security, headers::secure));
+
+    let headers = headers_of(&app, "/api/v1/public/sites/home", "127.0.0.1:5000", &[]).await;
+    assert_eq!(
+        header(&headers, header::CONTENT_SECURITY_POLICY),
+        Some(PUBLISHED_CSP)
+    );
+    assert!(header(&headers, header::STRICT_TRANSPORT_SECURITY).is_some());
+
+    let headers = headers_of(&app, "/api/v1/public/sites/embed", "127.0.0.1:5000", &[]).await;
+    assert_eq!(
+        header(&headers, header::CONTENT_SECURITY_POLICY),
+        Some("default-src *")
+    );
+}
+
+#[tokio::test]
+async fn test_security_headers_opt_out() {
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node).with_security_headers(
+        SecurityHeadersConfig {
+            csp: false,
+            ..SecurityHeadersConfig::default()
+        },
+    ));
+    let headers = headers_of(&app, "/api/v1/health", "127.0.0.1:5000", &[]).await;
+    assert_eq!(header(&headers, header::CONTENT_SECURITY_POLICY), None);
+    assert!(header(&headers, header::X_CONTENT_TYPE_OPTIONS).is_some());
+
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node).with_security_headers(
+        SecurityHeadersConfig {
+            enabled: false,
+            ..SecurityHeadersConfig::default()
+        },
+    ));
+    let headers = headers_of(&app, "/api/v1/health", "127.0.0.1:5000", &[]).await;
+    assert_eq!(header(&headers, header::X_CONTENT_TYPE_OPTIONS), None);
+    assert_eq!(header(&headers, header::REFERRER_POLICY), None);
+}
+
+#[tokio::test]
+async fn test_cors_allows_configured_origins() {
+    let allowed = |app: Router, origin: &'static str| async move {
+        let headers = headers_of(
+            &app,
+            "/api/v1/health",
+            "127.0.0.1:5000",
+            &[("origin", origin)],
+        )
+        .await;
+        header(&headers, header::ACCESS_CONTROL_ALLOW_ORIGIN).map(str::to_string)
+    };
+
+    // Without server settings, only the dev front-end
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node));
+    assert_eq!(
+        allowed(app.clone(), "http://localhost:3000")
+            .await
+            .as_deref(),
+        Some("http://localhost:3000")
+    );
+    assert_eq!(allowed(app, "https://evil.example").await, None);
+
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node).with_server(ServerConfig {
+        rest_port: 8080,
+        websocket_port: 8081,
+        grpc_port: None,
+        p2p_port: None,
+        host: "127.0.0.1".to_string(),
+        bind_addresses: vec!["127.0.0.1".parse().unwrap()],
+        advertised_addresses: Vec::new(),
+        allowed_origins: vec![
+            "https://app.example.com".to_string(),
+            "https://*.example.org".to_string(),
+        ],
+    }));
+    for origin in ["https://app.example.com", "https://a.b.example.org"] {
+        assert_eq!(allowed(app.clone(), origin).await.as_deref(), Some(origin));
+    }
+    for origin in [
+        "http://localhost:3000",
+        "https://example.org",
+        "http://a.example.org",
+        "https://evilexample.org",
+    ] {
+        assert_eq!(allowed(app.clone(), origin).await, None, "{}", origin);
+    }
+}
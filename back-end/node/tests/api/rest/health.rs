@@ -221,3 +221,43 @@ async fn test_health_endpoint_content_type() {
 
     info!("Content-Type: {}", content_type_value);
 }
+
+#[tokio::test]
+async fn test_liveness() {
+    let server = setup_test_server().await;
+
+    let (status, body) = get_request(&server.router, "/api/v1/health/live").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "alive");
+    assert!(body["timestamp"].is_string());
+}
+
+#[tokio::test]
+async fn test_readiness_reports_components() {
+    let server = setup_test_server().await;
+
+    let (status, body) = get_request(&server.router, "/api/v1/health/ready").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["shuttingDown"], false);
+    for component in ["database", "kv", "migrations", "keystore"] {
+        assert_eq!(body["components"][component]["status"], "up", "{}", body);
+        assert!(body["components"][component]["latencyMs"].is_u64());
+    }
+}
+
+#[tokio::test]
+async fn test_not_ready_while_shutting_down() {
+    let server = setup_test_server().await;
+    server.node.tasks.shutdown().trigger("test");
+
+    let (status, body) = get_request(&server.router, "/api/v1/health/ready").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["shuttingDown"], true);
+    assert_eq!(body["components"]["database"]["status"], "up");
+
+    // Still live, so it isn't restarted mid-drain
+    let (status, _) = get_request(&server.router, "/api/v1/health/live").await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -2,6 +2,7 @@ use axum::Router;
 use axum::body::Body;
 use axum::http::{Request, StatusCode};
 use http_body_util::BodyExt;
+use node::api::node::Node;
 use serde_json::Value;
 use tower::ServiceExt;
 
@@ -27,6 +28,34 @@ pub async fn get_request(app: &Router, uri: &str) -> (StatusCode, Value) {
     (status, json)
 }
 
+/// A session token for the node's owner, as signing in hands out
+pub async fn session_token(node: &Node) -> String {
+    node.user_sessions().open(1).await.unwrap().0
+}
+
+/// Helper to make GET request with a bearer token
+pub async fn get_request_with_token(app: &Router, uri: &str, token: &str) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("GET")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string().into());
+
+    (status, json)
+}
+
 /// Helper to make POST request
 pub async fn post_request(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
     let response = app
@@ -50,6 +79,158 @@ pub async fn post_request(app: &Router, uri: &str, body: Value) -> (StatusCode,
     (status, json)
 }
 
+/// Helper to make PUT request
+pub async fn put_request(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("PUT")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string().into());
+
+    (status, json)
+}
+
+pub async fn patch_request(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("PATCH")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string().into());
+
+    (status, json)
+}
+
+/// Helper to make POST request with a bearer token
+pub async fn post_request_with_token(
+    app: &Router,
+    uri: &str,
+    body: Value,
+    token: &str,
+) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("POST")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string().into());
+
+    (status, json)
+}
+
+/// Helper to make PATCH request with a bearer token
+pub async fn patch_request_with_token(
+    app: &Router,
+    uri: &str,
+    body: Value,
+    token: &str,
+) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("PATCH")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string().into());
+
+    (status, json)
+}
+
+/// Helper to make DELETE request
+pub async fn delete_request(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("DELETE")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string().into());
+
+    (status, json)
+}
+
+/// Helper to make DELETE request with a bearer token
+pub async fn delete_request_with_token(
+    app: &Router,
+    uri: &str,
+    token: &str,
+) -> (StatusCode, Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("DELETE")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&body).to_string().into());
+
+    (status, json)
+}
+
 /// Check for CORS headers
 pub fn assert_cors_headers(headers: &axum::http::HeaderMap) {
     assert!(
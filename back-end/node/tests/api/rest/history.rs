@@ -0,0 +1,84 @@
+use crate::util::users::signed_in_user;
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::http::StatusCode;
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::MultiUserConfig;
+use node::modules::tenancy::Actor;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_edits_are_recorded() {
+    let (node, _temp) = setup_test_node().await;
+    let (alice, token) = signed_in_user(&node, "alice").await;
+    let dir = TempDir::new().unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    node.delete_space_as(Actor::Node, &key, None).await.unwrap();
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, body) = patch_request_with_token(
+        &router,
+        "/api/v1/users/me",
+        json!({"displayName": "Alice A."}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let uri = format!("/api/v1/users/{}/history", alice);
+    let (status, body) = get_request(&router, &uri).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let changes = body["changes"].as_array().unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0]["change"], "updated");
+    assert_eq!(changes[0]["before"]["display_name"], "alice");
+    assert_eq!(changes[0]["after"]["display_name"], "Alice A.");
+    assert_eq!(changes[0]["after"]["version"], 2);
+
+    // Newest first, from the creation on
+    let uri = format!("/api/v1/spaces/{}/history", key);
+    let (status, body) = get_request(&router, &uri).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let changes = body["changes"].as_array().unwrap();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0]["change"], "updated");
+    assert_eq!(changes[0]["before"]["deleted_at"], json!(null));
+    assert!(changes[0]["after"]["deleted_at"].is_string());
+    assert_eq!(changes[1]["change"], "created");
+    assert_eq!(changes[1]["before"], json!(null));
+    assert_eq!(changes[1]["after"]["key"], key);
+
+    let (status, _) = get_request(&router, "/api/v1/widgets/1/history").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_users_only_see_their_own_history() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.multi_user = MultiUserConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let (alice, alice_token) = signed_in_user(&node, "alice").await;
+    let (_, bob_token) = signed_in_user(&node, "bob").await;
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, _) = patch_request_with_token(
+        &router,
+        "/api/v1/users/me",
+        json!({"displayName": "Alice A."}),
+        &alice_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/api/v1/users/{}/history", alice);
+    let (_, body) = get_request_with_token(&router, &uri, &alice_token).await;
+    assert_eq!(body["changes"].as_array().unwrap().len(), 1);
+    let (status, body) = get_request_with_token(&router, &uri, &bob_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["changes"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,131 @@
+use crate::bootstrap::init::setup_test_node;
+use axum::Router;
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode, header};
+use http_body_util::BodyExt;
+use node::api::servers::i18n::{self, CATALOG, LOCALES};
+use node::api::servers::{app_state::AppState, rest};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+async fn get(app: &Router, uri: &str, headers: &[(&str, &str)]) -> (StatusCode, HeaderMap, Value) {
+    let mut request = Request::builder().uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let (status, headers) = (response.status(), response.headers().clone());
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, serde_json::from_slice(&body).unwrap())
+}
+
+#[test]
+fn test_negotiate_locale() {
+    assert_eq!(i18n::negotiate(None), "en");
+    assert_eq!(i18n::negotiate(Some("fr-CH, fr;q=0.9, en;q=0.8")), "fr");
+    assert_eq!(i18n::negotiate(Some("pt-BR, de;q=0.5, es;q=0.7")), "es");
+    assert_eq!(i18n::negotiate(Some("DE-at")), "de");
+    assert_eq!(i18n::negotiate(Some("fr;q=0, de;q=0.1")), "de");
+    assert_eq!(i18n::negotiate(Some("ja, *;q=0.5")), "en");
+    assert_eq!(i18n::negotiate(Some("ja, ;q=x")), "en");
+}
+
+#[test]
+fn test_catalog_is_complete() {
+    for (code, texts) in CATALOG {
+        assert_eq!(texts.len(), LOCALES.len());
+        assert!(texts.iter().all(|text| !text.is_empty()), "{}", code);
+    }
+    assert_eq!(
+        i18n::message("not_found", "es"),
+        "Eso no existe o ya no está disponible."
+    );
+    assert_eq!(
+        i18n::message("no_such_code", "en"),
+        "Something went wrong on the node."
+    );
+    assert_eq!(
+        i18n::message("not_found", "ja"),
+        i18n::message("not_found", "en")
+    );
+}
+
+#[tokio::test]
+async fn test_errors_have_codes_and_localized_messages() {
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node));
+
+    let (status, headers, body) = get(&app, "/api/v1/tasks/nope", &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+    assert_eq!(
+        body["message"],
+        "That doesn't exist or is no longer available."
+    );
+    assert!(
+        body["detail"].as_str().unwrap().contains("nope"),
+        "{}",
+        body
+    );
+    assert_eq!(headers[header::CONTENT_LANGUAGE], "en");
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+
+    let (_, headers, body) = get(
+        &app,
+        "/api/v1/tasks/nope",
+        &[("accept-language", "fr-FR,fr;q=0.9")],
+    )
+    .await;
+    assert_eq!(body["code"], "not_found");
+    assert_eq!(
+        body["message"],
+        "Cela n'existe pas ou n'est plus disponible."
+    );
+    assert_eq!(headers[header::CONTENT_LANGUAGE], "fr");
+
+    // Unmatched routes too, with nothing more to say
+    let (status, _, body) = get(&app, "/api/v1/nowhere", &[("accept-language", "de")]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        body,
+        json!({
+            "code": "not_found",
+            "message": "Das gibt es nicht oder nicht mehr.",
+            "detail": "",
+        })
+    );
+
+    // Successful responses are left alone
+    let (status, headers, _) = get(&app, "/api/v1/health", &[("accept-language", "de")]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get(header::CONTENT_LANGUAGE).is_none());
+}
+
+#[tokio::test]
+async fn test_expired_session_has_its_own_code() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.guest_mode = true;
+    let app = rest::build_router(AppState::new(node));
+
+    let (status, _, body) = get(&app, "/api/v1/kv", &[]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+    assert_eq!(body["detail"], "Sign in required");
+
+    let (status, _, body) = get(
+        &app,
+        "/api/v1/kv",
+        &[("authorization", "Bearer stale"), ("accept-language", "es")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "session_expired");
+    assert_eq!(
+        body["message"],
+        "Tu sesión ha caducado; vuelve a iniciar sesión."
+    );
+}
@@ -0,0 +1,113 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::http::StatusCode;
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::{KeyUsageConfig, MultiUserConfig};
+use node::bootstrap::init::initialize_config_dir;
+use node::modules::column_crypto::ColumnKeys;
+use node::modules::key_rotation::SignedRotation;
+use node::modules::secrets::{Secret, WebhookSecret};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_key_usage_of_own_sessions() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.multi_user = MultiUserConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let (alice, alice_session) = node.user_sessions().open(7).await.unwrap();
+    let (bob, _) = node.user_sessions().open(8).await.unwrap();
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, _) = get_request_with_token(&router, "/api/v1/spaces", &alice).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, usage) = get_request_with_token(&router, "/api/v1/keys/usage", &alice).await;
+    assert_eq!(status, StatusCode::OK, "{}", usage);
+    let usage = usage.as_array().unwrap();
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0]["kind"], "session");
+    assert_eq!(usage[0]["keyId"], alice_session.id.as_str());
+    assert_eq!(usage[0]["uses"], 2);
+    assert_eq!(usage[0]["operations"]["read"], 2);
+
+    let uri = format!("/api/v1/keys/usage/session/{}", alice_session.id);
+    let (status, body) = get_request_with_token(&router, &uri, &alice).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["stats"]["keyId"], alice_session.id.as_str());
+    assert_eq!(body["history"].as_array().unwrap().len(), 3);
+
+    // Someone else's key is as good as unknown
+    let (status, _) = get_request_with_token(&router, &uri, &bob).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get_request_with_token(&router, "/api/v1/keys/usage/api_key/x", &bob).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_step_up_closes_anomalous_session() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.multi_user = MultiUserConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    // Every use after the first counts as a dormant key waking up
+    node.key_usage = KeyUsageConfig {
+        dormant_after: std::time::Duration::ZERO,
+        step_up: true,
+        ..Default::default()
+    };
+    let (token, _) = node.user_sessions().open(7).await.unwrap();
+    let router = rest::build_router(AppState::new(node.clone()));
+
+    let (status, _) = get_request_with_token(&router, "/api/v1/spaces", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get_request_with_token(&router, "/api/v1/spaces", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The session is gone, not just this request
+    assert!(node.user_session(&token).await.unwrap().is_none());
+    let (status, body) = get_request_with_token(&router, "/api/v1/spaces", &token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "session_expired");
+    assert_eq!(body["detail"], "Invalid or expired session");
+}
+
+#[tokio::test]
+async fn test_rotate_identity_key() {
+    let (mut node, temp) = setup_test_node().await;
+    let home = temp.path().join("flow-config");
+    node.node_data = initialize_config_dir(&home.to_string_lossy()).unwrap();
+    node.column_keys = ColumnKeys::from_identity(&node.node_data.private_key);
+    node.home = Some(home);
+    let previous = node.node_data.id.clone();
+    node.secrets()
+        .put("hook", &WebhookSecret(Secret::new("hush")))
+        .unwrap();
+    let app_state = AppState::new(node);
+    let router = rest::build_router(app_state.clone());
+
+    let (status, rotation) = post_request(&router, "/api/v1/identity/rotate", json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", rotation);
+    assert_eq!(rotation["type"], "flow-key-rotation/v1");
+    assert_eq!(rotation["previousDid"], previous.as_str());
+    let signed: SignedRotation = serde_json::from_value(rotation.clone()).unwrap();
+    assert!(signed.verify().unwrap());
+
+    let (_, info) = get_request(&router, "/api/v1/node").await;
+    assert_eq!(info["did"], rotation["nextDid"]);
+
+    // Secrets sealed under the old identity's column key still open
+    let node = app_state.node.read().await;
+    let secret: WebhookSecret = node.secrets().get("hook").unwrap().unwrap();
+    assert_eq!(secret.0.expose(), "hush");
+}
+
+#[tokio::test]
+async fn test_rotate_identity_key_needs_config_dir() {
+    let (node, _temp) = setup_test_node().await;
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, _) = post_request(&router, "/api/v1/identity/rotate", json!({})).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
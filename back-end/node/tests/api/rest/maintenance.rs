@@ -0,0 +1,102 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use node::api::servers::{app_state::AppState, rest};
+use serde_json::json;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_maintenance_refuses_writes_only() {
+    let (node, _temp) = setup_test_node().await;
+    let token = session_token(&node).await;
+    let router = rest::build_router(AppState::new(node));
+    let dir = TempDir::new().unwrap();
+
+    let (status, body) = get_request(&router, "/api/v1/admin/maintenance").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["enabled"], false);
+    assert_eq!(body["retryAfterSecs"], 60);
+
+    let (status, body) = put_request(
+        &router,
+        "/api/v1/admin/maintenance",
+        json!({"enabled": true, "reason": "nightly backup"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["enabled"], true);
+    assert_eq!(body["reason"], "nightly backup");
+    assert!(body["since"].is_string());
+
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/spaces")
+                .method("POST")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::from(
+                    json!({"dir": dir.path().to_str().unwrap()}).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "maintenance");
+    assert!(body["detail"].as_str().unwrap().contains("nightly backup"));
+
+    // Reads, health and POSTed reads carry on
+    let (status, _) = get_request(&router, "/api/v1/node").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get_request(&router, "/api/v1/health/ready").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_request(&router, "/api/v1/timestamps/verify", json!({})).await;
+    assert_ne!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let (status, body) = put_request(
+        &router,
+        "/api/v1/admin/maintenance",
+        json!({"enabled": false}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["enabled"], false);
+    assert_eq!(body["reason"], serde_json::Value::Null);
+
+    let (status, body) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": dir.path().to_str().unwrap()}),
+        &token,
+    )
+    .await;
+    assert!(status.is_success(), "{} {}", status, body);
+}
+
+#[tokio::test]
+async fn test_maintenance_from_config() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.maintenance =
+        node::modules::maintenance::Maintenance::new(&node::bootstrap::config::MaintenanceConfig {
+            enabled: true,
+            retry_after: std::time::Duration::from_secs(5),
+        });
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, body) = get_request(&router, "/api/v1/admin/maintenance").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["enabled"], true);
+    assert_eq!(body["reason"], "configured at startup");
+    assert_eq!(body["retryAfterSecs"], 5);
+
+    let (status, _) = put_request(&router, "/api/v1/kv/greeting", json!("hello")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
@@ -0,0 +1,271 @@
+use crate::{
+    api::rest::helpers::*,
+    bootstrap::init::{setup_test_node, setup_test_server, with_did_key},
+};
+use axum::http::StatusCode;
+use entity::space;
+use log::info;
+use node::modules::manifest::{self, SignedManifest};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+/// Create a space with a couple of files and return its key
+async fn create_space_with_files(
+    server: &crate::bootstrap::init::TestServer,
+    dir: &TempDir,
+) -> String {
+    let dir_path = dir.path().to_str().unwrap();
+    fs::write(dir.path().join("a.txt"), b"hello").unwrap();
+    fs::create_dir_all(dir.path().join("docs")).unwrap();
+    fs::write(dir.path().join("docs").join("b.txt"), b"world").unwrap();
+
+    let token = session_token(&server.node).await;
+    let (status, _) = post_request_with_token(
+        &server.router,
+        "/api/v1/spaces",
+        json!({ "dir": dir_path }),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    space::Entity::find()
+        .filter(space::Column::Location.contains(dir_path))
+        .one(&server.node.db)
+        .await
+        .unwrap()
+        .expect("Space should exist")
+        .key
+}
+
+#[tokio::test]
+async fn test_get_space_manifest_lists_files() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    let (status, body) =
+        get_request(&server.router, &format!("/api/v1/spaces/{}/manifest", key)).await;
+
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "Manifest should be returned: {}",
+        body
+    );
+    assert_eq!(body["schema"], "flow-manifest/v1");
+    assert_eq!(body["space_key"], key);
+    assert!(body["signature"].as_str().unwrap().starts_with('z'));
+
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["path"], "a.txt");
+    assert_eq!(entries[0]["size"], 5);
+    assert_eq!(
+        entries[0]["blake3"],
+        blake3::hash(b"hello").to_hex().to_string()
+    );
+    assert_eq!(entries[1]["path"], "docs/b.txt");
+
+    info!("Manifest: {}", body);
+}
+
+#[tokio::test]
+async fn test_get_space_manifest_for_subfolder() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    let (status, body) = get_request(
+        &server.router,
+        &format!("/api/v1/spaces/{}/manifest?path=docs", key),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["path"], "docs");
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["path"], "b.txt");
+}
+
+#[tokio::test]
+async fn test_get_space_manifest_rejects_path_traversal() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    let (status, _) = get_request(
+        &server.router,
+        &format!("/api/v1/spaces/{}/manifest?path=../", key),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_space_manifest_unknown_space() {
+    let server = setup_test_server().await;
+
+    let (status, _) = get_request(
+        &server.router,
+        &format!("/api/v1/spaces/{}/manifest", "de".repeat(32)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Not a space key at all
+    let (status, _) = get_request(&server.router, "/api/v1/spaces/deadbeef/manifest").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_verify_space_manifest_detects_changes() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    let (_, manifest) =
+        get_request(&server.router, &format!("/api/v1/spaces/{}/manifest", key)).await;
+
+    let verify_uri = format!("/api/v1/spaces/{}/manifest/verify", key);
+    let (status, body) = post_request(&server.router, &verify_uri, manifest.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["valid"], true,
+        "Untouched space should verify: {}",
+        body
+    );
+
+    // Modify, remove and add files
+    fs::write(temp_dir.path().join("a.txt"), b"changed").unwrap();
+    fs::remove_file(temp_dir.path().join("docs").join("b.txt")).unwrap();
+    fs::write(temp_dir.path().join("c.txt"), b"new").unwrap();
+
+    let (status, body) = post_request(&server.router, &verify_uri, manifest).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["valid"], false);
+    assert_eq!(body["signature_valid"], true);
+    assert_eq!(body["modified"], json!(["a.txt"]));
+    assert_eq!(body["missing"], json!(["docs/b.txt"]));
+    assert_eq!(body["unexpected"], json!(["c.txt"]));
+}
+
+#[tokio::test]
+async fn test_verify_space_manifest_detects_tampered_signature() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    let (_, mut manifest) =
+        get_request(&server.router, &format!("/api/v1/spaces/{}/manifest", key)).await;
+    manifest["entries"][0]["size"] = json!(6);
+
+    let (status, body) = post_request(
+        &server.router,
+        &format!("/api/v1/spaces/{}/manifest/verify", key),
+        manifest,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["signature_valid"], false);
+    assert_eq!(body["valid"], false);
+}
+
+#[tokio::test]
+async fn test_verify_space_manifest_rejects_other_signers() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    let (_, manifest) =
+        get_request(&server.router, &format!("/api/v1/spaces/{}/manifest", key)).await;
+    let mut signed: SignedManifest = serde_json::from_value(manifest).unwrap();
+    signed.manifest.entries[0].size = 6;
+
+    // Tampered, then re-signed with a valid signature of another key
+    let (other, _other_temp) = setup_test_node().await;
+    let other = with_did_key(other, 9);
+    let resigned = manifest::sign_manifest(signed.manifest, &other.node_data).unwrap();
+    let (status, body) = post_request(
+        &server.router,
+        &format!("/api/v1/spaces/{}/manifest/verify", key),
+        serde_json::to_value(&resigned).unwrap(),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["signature_valid"], false);
+    assert_eq!(body["valid"], false);
+}
+
+#[tokio::test]
+async fn test_get_space_manifest_waits_for_writes() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    // A write is half done: the file exists but isn't complete yet
+    let write = server.node.space_writes.begin(&key);
+    fs::write(temp_dir.path().join("c.txt"), b"par").unwrap();
+    let path = temp_dir.path().join("c.txt");
+    let writer = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        fs::write(path, b"partial no more").unwrap();
+        drop(write);
+    });
+
+    let (status, body) =
+        get_request(&server.router, &format!("/api/v1/spaces/{}/manifest", key)).await;
+    writer.await.unwrap();
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[1]["path"], "c.txt");
+    assert_eq!(
+        entries[1]["size"], 15,
+        "Manifest should see the finished write"
+    );
+}
+
+#[tokio::test]
+async fn test_manifest_summary_negotiation() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+    let key = create_space_with_files(&server, &temp_dir).await;
+
+    let (status, summary) = get_request(
+        &server.router,
+        &format!("/api/v1/spaces/{}/manifest/summary", key),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", summary);
+    assert_eq!(summary["schema"], "flow-manifest-summary/v1");
+    assert_eq!(summary["entries"], 2);
+    assert_eq!(summary["subtrees"].as_array().unwrap().len(), 2);
+
+    let compare_uri = format!("/api/v1/spaces/{}/manifest/compare", key);
+    let (status, body) = post_request(&server.router, &compare_uri, summary.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["in_sync"], true);
+
+    // The peer's copy is now behind in docs/
+    fs::write(temp_dir.path().join("docs").join("c.txt"), b"new").unwrap();
+
+    let (status, body) = post_request(&server.router, &compare_uri, summary.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["in_sync"], false);
+    assert_eq!(body["subtrees"], json!(["docs"]));
+    assert_eq!(body["missing_on_peer"], json!(["docs/c.txt"]));
+
+    // A summary of another space
+    let mut other = summary;
+    other["space_key"] = json!("de".repeat(32));
+    let (status, _) = post_request(&server.router, &compare_uri, other).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
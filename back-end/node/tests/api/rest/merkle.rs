@@ -0,0 +1,65 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_server};
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use node::modules::merkle::MerkleIndex;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_space_merkle_tracks_uploads() {
+    let server = setup_test_server().await;
+    let space_dir = TempDir::new().unwrap();
+    std::fs::write(space_dir.path().join("a.txt"), b"hello").unwrap();
+    server
+        .node
+        .create_space(space_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = server.node.list_spaces().await.unwrap()[0].key.clone();
+    let merkle_uri = format!("/api/v1/spaces/{}/merkle", key);
+
+    // Built on first use, and the root recorded on the space
+    let (status, root) = get_request(&server.router, &merkle_uri).await;
+    assert_eq!(status, StatusCode::OK, "{}", root);
+    assert_eq!(root["children"].as_array().unwrap().len(), 16);
+    let space = &server.node.list_spaces().await.unwrap()[0];
+    assert_eq!(space.merkle_root.as_deref(), root["hash"].as_str());
+
+    let status = server
+        .router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/v1/spaces/{}/files/docs/b.txt", key))
+                .method("PUT")
+                .body(Body::from("world"))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, updated) = get_request(&server.router, &merkle_uri).await;
+    assert_ne!(updated["hash"], root["hash"]);
+
+    // The incremental update matches a tree built from scratch
+    let (status, rebuilt) = post_request(
+        &server.router,
+        &format!("{}/rebuild", merkle_uri),
+        serde_json::json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rebuilt["root"], updated["hash"]);
+    assert_eq!(
+        MerkleIndex::open(&server.node.kv, &key)
+            .unwrap()
+            .root()
+            .unwrap(),
+        updated["hash"].as_str().unwrap()
+    );
+
+    let (status, _) = get_request(&server.router, &format!("{}?prefix=xyz", merkle_uri)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
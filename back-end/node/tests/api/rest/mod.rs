@@ -1,4 +1,35 @@
+pub mod account;
+pub mod anchors;
+pub mod archive;
+pub mod auth;
+pub mod client_keys;
+pub mod compression;
+pub mod credentials;
+pub mod did;
+pub mod events;
+pub mod guest;
+pub mod headers;
 pub mod health;
 pub mod helpers;
+pub mod history;
+pub mod i18n;
+pub mod keys;
+pub mod maintenance;
+pub mod manifest;
+pub mod merkle;
+pub mod multi_user;
+pub mod node;
+pub mod passkeys;
+pub mod pin;
+pub mod profile;
+pub mod proxy;
+pub mod resources;
+pub mod settings;
 pub mod space;
+pub mod tasks;
+pub mod telemetry;
+pub mod throttle;
+pub mod timestamps;
+pub mod update;
+pub mod upload;
 pub mod webauthn;
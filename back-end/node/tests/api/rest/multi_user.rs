@@ -0,0 +1,110 @@
+use crate::{
+    api::rest::helpers::*,
+    bootstrap::init::{setup_multi_user_server, setup_test_node},
+};
+use axum::http::StatusCode;
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::MultiUserConfig;
+use serde_json::json;
+use tempfile::TempDir;
+use webauthn_authenticator_rs::{AuthenticatorBackend, softpasskey::SoftPasskey};
+use webauthn_rs::prelude::Url;
+
+#[tokio::test]
+async fn test_authentication_opens_a_user_session() {
+    let (mut node, _temp) = setup_test_node().await;
+    let spaces_root = TempDir::new().unwrap();
+    node.multi_user = MultiUserConfig {
+        enabled: true,
+        spaces_root: Some(spaces_root.path().to_path_buf()),
+        ..Default::default()
+    };
+    let router = rest::build_router(AppState::new(node.clone()));
+    let origin = Url::parse("http://localhost:3000").unwrap();
+    let mut authenticator = SoftPasskey::new(true);
+
+    let (_, body) = get_request(&router, "/api/v1/webauthn/start_registration").await;
+    let credential = authenticator
+        .perform_register(
+            origin.clone(),
+            serde_json::from_value(body["challenge"]["publicKey"].clone()).unwrap(),
+            60000,
+        )
+        .unwrap();
+    let (status, _) = post_request(
+        &router,
+        "/api/v1/webauthn/finish_registration",
+        json!({"challenge_id": body["challenge_id"], "credential": credential}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = post_request(&router, "/api/v1/webauthn/start_authentication", json!({})).await;
+    let credential = authenticator
+        .perform_auth(
+            origin,
+            serde_json::from_value(body["challenge"]["publicKey"].clone()).unwrap(),
+            60000,
+        )
+        .unwrap();
+    let (status, body) = post_request(
+        &router,
+        "/api/v1/webauthn/finish_authentication",
+        json!({"challenge_id": body["challenge_id"], "credential": credential}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let token = body["session"]["token"].as_str().unwrap().to_string();
+
+    // A node-wide space isn't visible to the user
+    let shared = TempDir::new().unwrap();
+    node.create_space(shared.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let (status, _) = post_request(&router, "/api/v1/spaces", json!({"dir": "photos"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Only within the user's own spaces directory
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": shared.path().to_str().unwrap()}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) =
+        post_request_with_token(&router, "/api/v1/spaces", json!({"dir": "photos"}), &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = get_request_with_token(&router, "/api/v1/spaces", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let spaces = body["spaces"].as_array().unwrap();
+    assert_eq!(spaces.len(), 1);
+    assert!(spaces[0]["location"].as_str().unwrap().ends_with("/photos"));
+
+    let (status, body) = get_request_with_token(&router, "/api/v1/kv", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["keys"].as_array().unwrap().is_empty());
+
+    let (status, _) = get_request_with_token(&router, "/api/v1/spaces", "forged").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admin_reads_are_node_only() {
+    let (server, token) = setup_multi_user_server().await;
+
+    for uri in [
+        "/api/v1/admin/update",
+        "/api/v1/admin/compression",
+        "/api/v1/admin/stats",
+        "/api/v1/admin/metrics",
+        "/api/v1/admin/telemetry",
+        "/api/v1/admin/maintenance",
+    ] {
+        let (status, _) = get_request_with_token(&server.router, uri, &token).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+    }
+}
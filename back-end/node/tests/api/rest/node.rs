@@ -0,0 +1,141 @@
+use crate::bootstrap::init::{TestServer, setup_test_server};
+
+use super::helpers::*;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use http_body_util::BodyExt;
+use node::modules::peer_request::{self, Target};
+use node::modules::updater::CURRENT_VERSION;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_attest_signs_challenge() {
+    let server = setup_test_server().await;
+
+    let (status, body) = post_request(
+        &server.router,
+        "/api/v1/node/attest",
+        json!({"challenge": "0123456789abcdef0123", "audience": "https://peer.example"}),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["statement"]["challenge"], "0123456789abcdef0123");
+    assert_eq!(body["statement"]["audience"], "https://peer.example");
+    assert_eq!(body["statement"]["node"], server.node.node_data.id.as_str());
+    assert!(body["signature"].as_str().unwrap().starts_with('z'));
+}
+
+#[tokio::test]
+async fn test_attest_rejects_short_challenge() {
+    let server = setup_test_server().await;
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/node/attest",
+        json!({"challenge": "abc"}),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_node_info() {
+    let server = setup_test_server().await;
+
+    let (status, body) = get_request(&server.router, "/api/v1/node").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["did"], server.node.node_data.id.as_str());
+    assert_eq!(body["softwareVersion"], CURRENT_VERSION);
+    assert!(
+        body["didMethods"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("key"))
+    );
+    assert_eq!(body["subsystems"]["webauthn"], true);
+    assert_eq!(body["subsystems"]["sync"], false);
+    assert_eq!(body["protocols"]["api"], "v1");
+    assert_eq!(body["endpoints"]["attest"], "/api/v1/node/attest");
+    assert!(body["plugins"].as_array().unwrap().is_empty());
+}
+
+/// POST `body` to the peer message endpoint, signed as a peer of `server`
+async fn post_signed(server: &TestServer, body: &[u8], signature: &str) -> (StatusCode, Value) {
+    let response = server
+        .router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/peer/messages")
+                .method("POST")
+                .header("content-type", "application/json")
+                .header(peer_request::SIGNATURE_HEADER, signature)
+                .body(Body::from(body.to_vec()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_signed_peer_message_is_accepted_once() {
+    let server = setup_test_server().await;
+    let peer = SigningKey::from_bytes(&[7; 32]);
+    let mut multicodec_key = vec![0xed, 0x01];
+    multicodec_key.extend_from_slice(peer.verifying_key().as_bytes());
+    let peer_did = format!(
+        "did:key:{}",
+        multibase::encode(multibase::Base::Base58Btc, &multicodec_key)
+    );
+    let body = serde_json::to_vec(&json!({"kind": "sync.offer", "body": {"n": 1}})).unwrap();
+    let target = Target {
+        audience: &server.node.node_data.id,
+        method: "POST",
+        path: "/api/v1/peer/messages",
+        body: &body,
+    };
+    let signature = peer_request::sign_with(&peer, &peer_did, &target, Utc::now()).unwrap();
+
+    let (status, _) = post_signed(&server, &body, &signature).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, error) = post_signed(&server, &body, &signature).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(error["code"], "replayed");
+}
+
+#[tokio::test]
+async fn test_peer_message_needs_a_matching_signature() {
+    let server = setup_test_server().await;
+    let body = serde_json::to_vec(&json!({"kind": "sync.offer"})).unwrap();
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/peer/messages",
+        json!({"kind": "sync.offer"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Signed for another node
+    let target = Target {
+        audience: "did:web:other.example",
+        method: "POST",
+        path: "/api/v1/peer/messages",
+        body: &body,
+    };
+    let signature = server.node.sign_peer_request(&target).unwrap();
+    let (status, error) = post_signed(&server, &body, &signature).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["code"], "unauthorized");
+}
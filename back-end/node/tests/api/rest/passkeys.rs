@@ -0,0 +1,173 @@
+use crate::util::users::insert_user;
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::http::StatusCode;
+use chrono::Utc;
+use entity::pass_key;
+use node::api::node::Node;
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::MultiUserConfig;
+use sea_orm::{ActiveModelTrait, NotSet, Set};
+use serde_json::json;
+
+async fn user_with_passkeys(node: &Node, name: &str, passkeys: u8) -> (i32, Vec<i32>) {
+    let user = insert_user(&node.db, name).await;
+
+    let mut ids = Vec::new();
+    for n in 0..passkeys {
+        let passkey = pass_key::ActiveModel {
+            id: NotSet,
+            user_id: Set(user.id),
+            device_id: Set(format!("{}-device", name)),
+            credential_id: Set(format!("{}-{}", name, n).into_bytes()),
+            public_key: Set(vec![n]),
+            sign_count: Set(0),
+            authentication_count: Set(0),
+            last_authenticated: Set(Utc::now().into()),
+            name: Set(format!("{} key {}", name, n)),
+            attestation: Set("None".to_string()),
+            json_data: Set("{}".to_string()),
+            time_created: Set(Utc::now().into()),
+            user_verification: Set("preferred".to_string()),
+            authenticator_attachment: Set(None),
+            backup_eligible: Set(false),
+            backup_state: Set(false),
+            version: Set(1),
+            deleted_at: Set(None),
+        }
+        .insert(&node.db)
+        .await
+        .unwrap();
+        ids.push(passkey.id);
+    }
+    (user.id, ids)
+}
+
+#[tokio::test]
+async fn test_manage_passkeys() {
+    let (node, _temp) = setup_test_node().await;
+    let (alice, alice_keys) = user_with_passkeys(&node, "alice", 2).await;
+    user_with_passkeys(&node, "bob", 1).await;
+    let router = rest::build_router(AppState::new(node));
+
+    // A single-user node manages every user's passkeys
+    let (status, body) = get_request(&router, "/api/v1/webauthn/passkeys").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let passkeys = body["passkeys"].as_array().unwrap();
+    assert_eq!(passkeys.len(), 3);
+    assert_eq!(passkeys[0]["userId"], alice);
+    assert_eq!(passkeys[0]["name"], "alice key 0");
+    assert!(passkeys[0].get("publicKey").is_none());
+
+    let uri = format!("/api/v1/webauthn/passkeys/{}", alice_keys[0]);
+    let (status, body) = patch_request(&router, &uri, json!({"name": "  Laptop "})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["name"], "Laptop");
+    let (status, _) = patch_request(&router, &uri, json!({"name": " "})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let long = "x".repeat(65);
+    let (status, _) = patch_request(&router, &uri, json!({"name": long})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // The last passkey of a user stays
+    let (status, body) = delete_request(&router, &uri).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["name"], "Laptop");
+    let last = format!("/api/v1/webauthn/passkeys/{}", alice_keys[1]);
+    let (status, _) = delete_request(&router, &last).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = delete_request(&router, &uri).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = get_request(&router, "/api/v1/webauthn/passkeys").await;
+    assert_eq!(body["passkeys"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_users_manage_only_their_passkeys() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.multi_user = MultiUserConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let (alice, _) = user_with_passkeys(&node, "alice", 2).await;
+    let (_, bob_keys) = user_with_passkeys(&node, "bob", 2).await;
+    let (token, _) = node.user_sessions().open(alice).await.unwrap();
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, _) = get_request(&router, "/api/v1/webauthn/passkeys").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = get_request_with_token(&router, "/api/v1/webauthn/passkeys", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let passkeys = body["passkeys"].as_array().unwrap();
+    assert_eq!(passkeys.len(), 2);
+    assert!(passkeys.iter().all(|passkey| passkey["userId"] == alice));
+
+    // Other users' passkeys read as missing
+    let uri = format!("/api/v1/webauthn/passkeys/{}", bob_keys[0]);
+    let (status, _) = delete_request_with_token(&router, &uri, &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_stale_rename_is_refused() {
+    let (node, _temp) = setup_test_node().await;
+    let (_, keys) = user_with_passkeys(&node, "alice", 1).await;
+    let router = rest::build_router(AppState::new(node));
+    let uri = format!("/api/v1/webauthn/passkeys/{}", keys[0]);
+
+    // Two clients read version 1; the first rename wins
+    let (status, body) =
+        patch_request(&router, &uri, json!({"name": "Laptop", "version": 1})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["version"], 2);
+    let (status, _) = patch_request(&router, &uri, json!({"name": "Phone", "version": 1})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Reading again gets the second one through; no version, no check
+    let (_, body) = get_request(&router, "/api/v1/webauthn/passkeys").await;
+    assert_eq!(body["passkeys"][0]["name"], "Laptop");
+    let version = body["passkeys"][0]["version"].clone();
+    let (status, body) =
+        patch_request(&router, &uri, json!({"name": "Phone", "version": version})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = patch_request(&router, &uri, json!({"name": "Tablet"})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["version"], 4);
+}
+
+#[tokio::test]
+async fn test_restore_deleted_passkey() {
+    let (mut node, _temp) = setup_test_node().await;
+    let (_, keys) = user_with_passkeys(&node, "alice", 2).await;
+    node.retention.restore_window = std::time::Duration::from_secs(60);
+    let router = rest::build_router(AppState::new(node.clone()));
+    let uri = format!("/api/v1/webauthn/passkeys/{}", keys[0]);
+    let restore = format!("{}/restore", uri);
+
+    let (status, _) = post_request(&router, &restore, json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = delete_request(&router, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = get_request(&router, "/api/v1/webauthn/passkeys").await;
+    assert_eq!(body["passkeys"].as_array().unwrap().len(), 1);
+
+    // A deleted passkey doesn't count towards keeping the last one
+    let other = format!("/api/v1/webauthn/passkeys/{}", keys[1]);
+    let (status, _) = delete_request(&router, &other).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, body) = post_request(&router, &restore, json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["name"], "alice key 0");
+    let (_, body) = get_request(&router, "/api/v1/webauthn/passkeys").await;
+    assert_eq!(body["passkeys"].as_array().unwrap().len(), 2);
+
+    // Past the restore window, the purge removes it for good
+    let (status, _) = delete_request(&router, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(node.purge_deleted().await.unwrap().passkeys, 0);
+    node.retention.restore_window = std::time::Duration::ZERO;
+    assert_eq!(node.purge_deleted().await.unwrap().passkeys, 1);
+    let (status, _) = post_request(&router, &restore, json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,73 @@
+use crate::{
+    api::rest::helpers::*,
+    bootstrap::init::{setup_multi_user_server, setup_test_server},
+};
+use axum::http::StatusCode;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_pin_unlock_allows_listing_spaces() {
+    let server = setup_test_server().await;
+    let space_dir = TempDir::new().unwrap();
+    server
+        .node
+        .create_space(space_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+
+    let (status, _) = get_request(&server.router, "/api/v1/spaces").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = post_request(&server.router, "/api/v1/pin", json!({"pin": "4321"})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) =
+        post_request(&server.router, "/api/v1/pin/unlock", json!({"pin": "0000"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) =
+        post_request(&server.router, "/api/v1/pin/unlock", json!({"pin": "4321"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["scope"], "read_only");
+    let token = body["token"].as_str().unwrap();
+
+    let (status, body) = get_request_with_token(&server.router, "/api/v1/spaces", token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["spaces"].as_array().unwrap().len(), 1);
+
+    let (status, _) = get_request_with_token(&server.router, "/api/v1/spaces", "bogus").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_changing_pin_requires_current_pin() {
+    let server = setup_test_server().await;
+
+    let (status, _) = post_request(&server.router, "/api/v1/pin", json!({"pin": "12"})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    post_request(&server.router, "/api/v1/pin", json!({"pin": "4321"})).await;
+
+    let (status, _) = post_request(&server.router, "/api/v1/pin", json!({"pin": "9999"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/pin",
+        json!({"pin": "9999", "current_pin": "4321"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_pin_is_node_only() {
+    let (server, token) = setup_multi_user_server().await;
+
+    for uri in ["/api/v1/pin", "/api/v1/pin/unlock"] {
+        let (status, _) =
+            post_request_with_token(&server.router, uri, json!({"pin": "4321"}), &token).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::util::users::signed_in_user;
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::http::StatusCode;
+use node::api::servers::{app_state::AppState, rest};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_read_and_edit_profile() {
+    let (node, _temp) = setup_test_node().await;
+    let (_, token) = signed_in_user(&node, "alice").await;
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, _) = get_request(&router, "/api/v1/users/me").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = get_request_with_token(&router, "/api/v1/users/me", &token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["username"], "alice");
+    assert_eq!(body["did"], "did:key:alice");
+    assert_eq!(body["avatarUrl"], json!(null));
+    assert_eq!(body["version"], 1);
+
+    // Fields left out stay as they are
+    let (status, body) = patch_request_with_token(
+        &router,
+        "/api/v1/users/me",
+        json!({"displayName": " Alice A. ", "avatarUrl": "https://example.com/a.png", "version": 1}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["displayName"], "Alice A.");
+    assert_eq!(body["avatarUrl"], "https://example.com/a.png");
+    assert_eq!(body["username"], "alice");
+    assert_eq!(body["version"], 2);
+
+    // An empty avatar URL removes it
+    let (status, body) = patch_request_with_token(
+        &router,
+        "/api/v1/users/me",
+        json!({"avatarUrl": ""}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["avatarUrl"], json!(null));
+    let (_, body) = get_request_with_token(&router, "/api/v1/users/me", &token).await;
+    assert_eq!(body["displayName"], "Alice A.");
+}
+
+#[tokio::test]
+async fn test_profile_edits_are_checked() {
+    let (node, _temp) = setup_test_node().await;
+    let (_, token) = signed_in_user(&node, "alice").await;
+    signed_in_user(&node, "bob").await;
+    let router = rest::build_router(AppState::new(node));
+    let patch = |body| patch_request_with_token(&router, "/api/v1/users/me", body, &token);
+
+    for body in [
+        json!({"username": "Alice"}),
+        json!({"username": "al"}),
+        json!({"displayName": "  "}),
+        json!({"displayName": "x".repeat(65)}),
+        json!({"avatarUrl": "javascript:alert(1)"}),
+        json!({"avatarUrl": "not a url"}),
+    ] {
+        let (status, _) = patch(body.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    let (status, _) = patch(json!({"username": "bob"})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, body) = patch(json!({"username": "alice.a", "version": 1})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // An edit from a stale copy is refused
+    let (status, _) = patch(json!({"displayName": "Old copy", "version": 1})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, body) = get_request_with_token(&router, "/api/v1/users/me", &token).await;
+    assert_eq!(body["username"], "alice.a");
+    assert_eq!(body["displayName"], "alice");
+}
@@ -0,0 +1,173 @@
+use crate::bootstrap::init::setup_test_node;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Extension};
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::middleware;
+use axum::routing::get;
+use http_body_util::BodyExt;
+use node::api::servers::app_state::AppState;
+use node::api::servers::proxy::{self, ClientCountry, Forwarding, RequestOrigin};
+use node::api::servers::rest;
+use node::bootstrap::config::ProxyConfig;
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn proxy_config() -> ProxyConfig {
+    ProxyConfig {
+        trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        base_path: "/flow".to_string(),
+        external_origin: None,
+        country_header: None,
+    }
+}
+
+/// GET `uri` from `peer` with the given headers
+async fn get_from(
+    app: &Router,
+    uri: &str,
+    peer: &str,
+    headers: &[(&str, &str)],
+) -> (StatusCode, Value) {
+    let mut request = Request::builder()
+        .uri(uri)
+        .header("host", "flow.example.com")
+        .extension(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn echo_router(proxy: ProxyConfig) -> Router {
+    let forwarding = Arc::new(Forwarding { proxy, tls: false });
+    Router::new()
+        .route(
+            "/client",
+            get(
+                |ConnectInfo(addr): ConnectInfo<SocketAddr>,
+                 origin: Option<Extension<RequestOrigin>>,
+                 country: Option<Extension<ClientCountry>>| async move {
+                    axum::Json(json!({
+                        "ip": addr.ip().to_string(),
+                        "origin": origin.map(|Extension(RequestOrigin(o))| o),
+                        "country": country.map(|Extension(ClientCountry(c))| c),
+                    }))
+                },
+            ),
+        )
+        .layer(middleware::from_fn_with_state(forwarding, proxy::forwarded))
+}
+
+#[test]
+fn test_forwarded_client_skips_trusted_hops() {
+    let proxy = proxy_config();
+    let headers = |value: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    };
+
+    // The nearest untrusted hop, not whatever the client put first
+    assert_eq!(
+        proxy::forwarded_client(&proxy, &headers("1.1.1.1, 203.0.113.7, 10.0.0.2")),
+        Some("203.0.113.7".parse().unwrap())
+    );
+    assert_eq!(
+        proxy::forwarded_client(&proxy, &headers("10.0.0.3, 10.0.0.2")),
+        Some("10.0.0.3".parse().unwrap())
+    );
+    assert_eq!(
+        proxy::forwarded_client(&proxy, &headers("[2001:db8::1]:4711")),
+        Some("2001:db8::1".parse().unwrap())
+    );
+    assert_eq!(
+        proxy::forwarded_client(&proxy, &headers("203.0.113.7, unknown")),
+        None
+    );
+    assert_eq!(proxy::forwarded_client(&proxy, &HeaderMap::new()), None);
+}
+
+#[tokio::test]
+async fn test_forwarded_headers_only_trusted_from_proxies() {
+    let app = echo_router(proxy_config());
+    let forwarded = [
+        ("x-forwarded-for", "203.0.113.7"),
+        ("x-forwarded-proto", "https"),
+    ];
+
+    let (status, body) = get_from(&app, "/client", "10.0.0.2:5000", &forwarded).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ip"], "203.0.113.7");
+    assert_eq!(body["origin"], "https://flow.example.com");
+
+    // A client can't claim to be someone else
+    let (_, body) = get_from(&app, "/client", "198.51.100.9:5000", &forwarded).await;
+    assert_eq!(body["ip"], "198.51.100.9");
+    assert_eq!(body["origin"], "http://flow.example.com");
+
+    // A configured origin wins over the request's
+    let app = echo_router(ProxyConfig {
+        external_origin: Some("https://node.example.org".to_string()),
+        ..proxy_config()
+    });
+    let (_, body) = get_from(&app, "/client", "10.0.0.2:5000", &forwarded).await;
+    assert_eq!(body["origin"], "https://node.example.org");
+}
+
+#[tokio::test]
+async fn test_client_country_only_from_proxies() {
+    let app = echo_router(ProxyConfig {
+        country_header: Some("CF-IPCountry".to_string()),
+        ..proxy_config()
+    });
+
+    let (_, body) = get_from(&app, "/client", "10.1.2.3:443", &[("cf-ipcountry", "nl")]).await;
+    assert_eq!(body["country"], "NL");
+    // Unknown, or not a country code at all
+    for value in ["XX", "Netherlands", "1"] {
+        let (_, body) = get_from(&app, "/client", "10.1.2.3:443", &[("cf-ipcountry", value)]).await;
+        assert_eq!(body["country"], Value::Null, "{}", value);
+    }
+    let (_, body) = get_from(
+        &app,
+        "/client",
+        "203.0.113.9:443",
+        &[("cf-ipcountry", "NL")],
+    )
+    .await;
+    assert_eq!(body["country"], Value::Null);
+
+    // Not configured: the header means nothing
+    let app = echo_router(proxy_config());
+    let (_, body) = get_from(&app, "/client", "10.1.2.3:443", &[("cf-ipcountry", "NL")]).await;
+    assert_eq!(body["country"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_routes_and_links_under_base_path() {
+    let (node, _temp) = setup_test_node().await;
+    let app = rest::build_router(AppState::new(node).with_proxy(proxy_config()));
+    let forwarded = [("x-forwarded-proto", "https")];
+
+    let (status, body) = get_from(&app, "/flow/api/v1/node", "10.0.0.2:5000", &forwarded).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["endpoints"]["rest"], "/flow/api/v1");
+    assert_eq!(body["endpoints"]["attest"], "/flow/api/v1/node/attest");
+    assert_eq!(body["endpoints"]["websocket"], "/flow/ws");
+    assert_eq!(body["endpoints"]["url"], "https://flow.example.com/flow");
+
+    let (status, _) = get_from(&app, "/api/v1/node", "10.0.0.2:5000", &forwarded).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,57 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use http_body_util::BodyExt;
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::ResourceConfig;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_admin_stats_report_usage_over_limits() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.resources = ResourceConfig {
+        db_bytes: Some(1),
+        ..Default::default()
+    };
+    let router = rest::build_router(AppState::new(node));
+
+    let (status, body) = get_request(&router, "/api/v1/admin/stats").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["usage"]["dbBytes"].as_u64().unwrap() > 1);
+    assert!(body["usage"]["kvTrees"].is_object());
+    assert_eq!(body["exceeded"][0]["resource"], "dbBytes");
+    assert_eq!(body["exceeded"][0]["limit"], 1);
+}
+
+#[tokio::test]
+async fn test_metrics_are_prometheus_text() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.resources = ResourceConfig {
+        tasks: Some(8),
+        ..Default::default()
+    };
+    let router = rest::build_router(AppState::new(node));
+
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/admin/metrics")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain")
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("# TYPE flow_db_bytes gauge\n"));
+    assert!(text.contains("flow_soft_limit{resource=\"tasks\"} 8\n"));
+    assert!(text.contains("# TYPE flow_kv_flush_microseconds gauge\n"));
+    assert!(text.contains("# TYPE flow_db_pages gauge\n"));
+}
@@ -0,0 +1,61 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_server};
+use axum::http::StatusCode;
+use node::modules::pin::{MAX_FAILED_ATTEMPTS, MAX_FAILED_ATTEMPTS_LIMIT, PinSettings, PinUnlock};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_settings_read_through() {
+    let server = setup_test_server().await;
+
+    let (status, body) = get_request(&server.router, "/api/v1/settings/pin").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["maxFailedAttempts"], MAX_FAILED_ATTEMPTS);
+
+    let (status, body) = put_request(
+        &server.router,
+        "/api/v1/settings/pin",
+        json!({"maxFailedAttempts": 2}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["maxFailedAttempts"], 2);
+    // Left out, so the default
+    assert_eq!(body["lockoutSecs"], PinSettings::default().lockout_secs);
+
+    // The PIN sees the change at once
+    let pin = server.node.local_pin();
+    pin.set("1234", None).await.unwrap();
+    assert_eq!(
+        pin.unlock("0000").await.unwrap(),
+        PinUnlock::Rejected {
+            remaining_attempts: 1
+        }
+    );
+    assert!(matches!(
+        pin.unlock("0000").await.unwrap(),
+        PinUnlock::LockedOut { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_settings_are_validated() {
+    let server = setup_test_server().await;
+
+    for invalid in [
+        json!({"maxFailedAttempts": 0}),
+        json!({"maxFailedAttempts": MAX_FAILED_ATTEMPTS_LIMIT + 1}),
+        json!({"lockoutSecs": 0}),
+        json!({"maxFailedAttempt": 3}),
+        json!({"maxFailedAttempts": "three"}),
+    ] {
+        let (status, body) = put_request(&server.router, "/api/v1/settings/pin", invalid).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+    let (_, body) = get_request(&server.router, "/api/v1/settings/pin").await;
+    assert_eq!(body["maxFailedAttempts"], MAX_FAILED_ATTEMPTS);
+
+    let (status, _) = get_request(&server.router, "/api/v1/settings/nope").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = put_request(&server.router, "/api/v1/settings/nope", json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -10,6 +10,7 @@ use tempfile::TempDir;
 async fn test_create_space_valid_directory() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
     let dir_path = temp_dir.path().to_str().unwrap();
 
@@ -18,7 +19,8 @@ async fn test_create_space_valid_directory() {
         "dir": dir_path
     });
 
-    let (status, body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert
     assert_eq!(status, StatusCode::OK, "Should return 200 OK");
@@ -59,10 +61,23 @@ async fn test_create_space_valid_directory() {
     );
 }
 
+#[tokio::test]
+async fn test_create_space_requires_session() {
+    let server = setup_test_server().await;
+    let temp_dir = TempDir::new().unwrap();
+
+    let payload = json!({ "dir": temp_dir.path().to_str().unwrap() });
+    let (status, _) = post_request(&server.router, "/api/v1/spaces", payload).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(server.node.list_spaces().await.unwrap().is_empty());
+}
+
 #[tokio::test]
 async fn test_create_space_creates_directory_if_not_exists() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
     let new_dir = temp_dir.path().join("new_space_dir");
     let dir_path = new_dir.to_str().unwrap();
@@ -70,7 +85,8 @@ async fn test_create_space_creates_directory_if_not_exists() {
     assert!(!new_dir.exists(), "Directory should not exist initially");
 
     let payload = json!({ "dir": dir_path });
-    let (status, body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     assert_eq!(status, StatusCode::OK);
     assert_eq!(body["status"], "success");
@@ -94,6 +110,7 @@ async fn test_create_space_creates_directory_if_not_exists() {
 async fn test_create_space_with_nested_directory() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
     let nested_dir = temp_dir
         .path()
@@ -107,7 +124,8 @@ async fn test_create_space_with_nested_directory() {
         "dir": dir_path
     });
 
-    let (status, body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert
     assert_eq!(status, StatusCode::OK);
@@ -123,6 +141,7 @@ async fn test_create_space_with_nested_directory() {
 async fn test_create_space_invalid_directory() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
 
     // Use a path that's definitely invalid on Unix-like systems
     // Note: This might behave differently on Windows
@@ -133,7 +152,8 @@ async fn test_create_space_invalid_directory() {
         "dir": invalid_path
     });
 
-    let (status, body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert
     assert_eq!(
@@ -143,8 +163,9 @@ async fn test_create_space_invalid_directory() {
     );
 
     // Error message should be present
+    assert_eq!(body["code"], "internal");
     assert!(
-        body.is_string(),
+        body["detail"].is_string(),
         "Should return error message as string, got: {:?}",
         body
     );
@@ -156,13 +177,15 @@ async fn test_create_space_invalid_directory() {
 async fn test_create_space_duplicate() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
     let dir_path = temp_dir.path().to_str().unwrap();
 
     let payload = json!({ "dir": dir_path });
 
     // First creation
-    let (status1, body1) = post_request(&server.router, "/api/v1/spaces", payload.clone()).await;
+    let (status1, body1) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload.clone(), &token).await;
     assert_eq!(status1, StatusCode::OK);
     assert_eq!(body1["status"], "success");
 
@@ -179,7 +202,8 @@ async fn test_create_space_duplicate() {
     );
 
     // Second creation (duplicate)
-    let (status2, body2) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status2, body2) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
     assert_eq!(status2, StatusCode::OK, "Duplicate should be idempotent");
     assert_eq!(body2["status"], "success");
 
@@ -202,11 +226,13 @@ async fn test_create_space_duplicate() {
 async fn test_create_space_no_directory_provided() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
 
     // Execute - Don't provide dir field (should default to /tmp/space)
     let payload = json!({});
 
-    let (status, body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert - Should use default directory /tmp/space
     assert_eq!(
@@ -223,13 +249,15 @@ async fn test_create_space_no_directory_provided() {
 async fn test_create_space_empty_directory_string() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
 
     // Execute - Provide empty string as directory
     let payload = json!({
         "dir": ""
     });
 
-    let (status, _body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, _body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert - Should fail because empty string is not a valid path
     assert_eq!(
@@ -245,13 +273,15 @@ async fn test_create_space_empty_directory_string() {
 async fn test_create_space_relative_path() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
 
     // Execute - Use relative path
     let payload = json!({
         "dir": "./test_space_relative"
     });
 
-    let (status, body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert - Should succeed (relative paths are canonicalized)
     assert_eq!(status, StatusCode::OK, "Relative path should be accepted");
@@ -267,6 +297,7 @@ async fn test_create_space_relative_path() {
 async fn test_create_space_with_special_characters() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
 
     // Create directory with special characters (but valid on most filesystems)
@@ -278,7 +309,8 @@ async fn test_create_space_with_special_characters() {
         "dir": dir_path
     });
 
-    let (status, body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert
     assert_eq!(status, StatusCode::OK, "Should handle special characters");
@@ -295,12 +327,14 @@ async fn test_create_space_with_special_characters() {
 async fn test_create_space_generates_deterministic_key() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
     let dir_path = temp_dir.path().to_str().unwrap();
 
     let payload = json!({ "dir": dir_path });
 
-    let (status1, _) = post_request(&server.router, "/api/v1/spaces", payload.clone()).await;
+    let (status1, _) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload.clone(), &token).await;
     assert_eq!(status1, StatusCode::OK);
 
     // Get the space key from first creation
@@ -315,7 +349,8 @@ async fn test_create_space_generates_deterministic_key() {
     let id1 = space1.id;
 
     // Second creation
-    let (status2, _) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status2, _) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
     assert_eq!(status2, StatusCode::OK);
 
     // Verify the key is still the same (deterministic)
@@ -336,6 +371,7 @@ async fn test_create_space_generates_deterministic_key() {
 async fn test_create_space_different_directories_succeed() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
 
     let dir1 = temp_dir.path().join("space1");
@@ -344,8 +380,10 @@ async fn test_create_space_different_directories_succeed() {
     let payload1 = json!({ "dir": dir1.to_str().unwrap() });
     let payload2 = json!({ "dir": dir2.to_str().unwrap() });
 
-    let (status1, _) = post_request(&server.router, "/api/v1/spaces", payload1).await;
-    let (status2, _) = post_request(&server.router, "/api/v1/spaces", payload2).await;
+    let (status1, _) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload1, &token).await;
+    let (status2, _) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload2, &token).await;
 
     assert_eq!(status1, StatusCode::OK);
     assert_eq!(status2, StatusCode::OK);
@@ -382,11 +420,13 @@ async fn test_create_space_different_directories_succeed() {
 async fn test_create_space_malformed_json() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
 
     // Execute - Send non-object JSON (array instead)
     let payload = json!([1, 2, 3]);
 
-    let (status, _body) = post_request(&server.router, "/api/v1/spaces", payload).await;
+    let (status, _body) =
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await;
 
     // Assert - Should handle gracefully (defaults to /tmp/space)
     // or return error depending on implementation
@@ -403,6 +443,7 @@ async fn test_create_space_malformed_json() {
 async fn test_create_space_concurrent_requests() {
     // Setup
     let server = setup_test_server().await;
+    let token = session_token(&server.node).await;
     let temp_dir = TempDir::new().unwrap();
     let dir_path = temp_dir.path().to_str().unwrap().to_string();
 
@@ -411,14 +452,15 @@ async fn test_create_space_concurrent_requests() {
 
     let app_clone = server.router.clone();
     let payload_clone = payload.clone();
+    let token_clone = token.clone();
 
-    let handle1 =
-        tokio::spawn(async move { post_request(&server.router, "/api/v1/spaces", payload).await });
+    let handle1 = tokio::spawn(async move {
+        post_request_with_token(&server.router, "/api/v1/spaces", payload, &token).await
+    });
 
-    let handle2 =
-        tokio::spawn(
-            async move { post_request(&app_clone, "/api/v1/spaces", payload_clone).await },
-        );
+    let handle2 = tokio::spawn(async move {
+        post_request_with_token(&app_clone, "/api/v1/spaces", payload_clone, &token_clone).await
+    });
 
     let (result1, result2) = tokio::join!(handle1, handle2);
 
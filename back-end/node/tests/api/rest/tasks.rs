@@ -0,0 +1,113 @@
+use crate::{
+    api::rest::helpers::*,
+    bootstrap::init::{setup_multi_user_server, setup_test_server},
+};
+use axum::http::StatusCode;
+use serde_json::json;
+use std::fs;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_task_lifecycle() {
+    let server = setup_test_server().await;
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), b"hello").unwrap();
+    post_request_with_token(
+        &server.router,
+        "/api/v1/spaces",
+        json!({ "dir": dir.path().to_str().unwrap() }),
+        &session_token(&server.node).await,
+    )
+    .await;
+    let key = server.node.list_spaces().await.unwrap()[0].key.clone();
+
+    let (status, body) = post_request(
+        &server.router,
+        "/api/v1/tasks",
+        json!({
+            "name": "scan",
+            "schedule": "0 3 * * *",
+            "action": {"type": "scan_space", "spaceKey": key}
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let id = body["task"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["task"]["enabled"], true);
+    assert!(body["task"]["nextRun"].is_string());
+
+    let (status, body) = post_request(
+        &server.router,
+        &format!("/api/v1/tasks/{}/run", id),
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["run"]["status"], "succeeded");
+    assert_eq!(body["run"]["output"]["files"], 1);
+
+    let (status, body) = get_request(&server.router, &format!("/api/v1/tasks/{}/runs", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["runs"].as_array().unwrap().len(), 1);
+
+    let (status, _) = delete_request(&server.router, &format!("/api/v1/tasks/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = get_request(&server.router, &format!("/api/v1/tasks/{}", id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_create_task_rejects_invalid_schedule() {
+    let server = setup_test_server().await;
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/tasks",
+        json!({
+            "name": "backup",
+            "schedule": "whenever",
+            "action": {"type": "run_backup", "destination": "backups"}
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = get_request(&server.router, "/api/v1/tasks").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["tasks"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_tasks_and_webhooks_are_node_only() {
+    let (server, token) = setup_multi_user_server().await;
+
+    for uri in ["/api/v1/tasks", "/api/v1/admin/webhooks"] {
+        let (status, _) = get_request_with_token(&server.router, uri, &token).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+    }
+
+    let (status, _) = post_request_with_token(
+        &server.router,
+        "/api/v1/tasks",
+        json!({
+            "name": "purge",
+            "schedule": "@daily",
+            "action": {"type": "purge_data"}
+        }),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = post_request_with_token(
+        &server.router,
+        "/api/v1/admin/webhooks",
+        json!({"url": "https://ci.example/hook"}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(server.node.scheduler().list().unwrap().is_empty());
+    assert!(server.node.webhooks.list().unwrap().is_empty());
+}
@@ -0,0 +1,52 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::http::StatusCode;
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::TelemetryConfig;
+
+#[tokio::test]
+async fn test_telemetry_is_off_by_default() {
+    let (node, _temp) = setup_test_node().await;
+    let router = rest::build_router(AppState::new(node));
+
+    get_request(&router, "/api/v1/node").await;
+    let (status, body) = get_request(&router, "/api/v1/admin/telemetry").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["enabled"], false);
+    assert!(body["report"]["features"].as_object().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_telemetry_counts_routes_and_error_codes() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.telemetry = TelemetryConfig {
+        enabled: true,
+        endpoint: Some("https://telemetry.example.com/flow".to_string()),
+        // Next to no noise, so the counts are exact
+        epsilon: 1000.0,
+        ..TelemetryConfig::default()
+    };
+    let router = rest::build_router(AppState::new(node));
+
+    get_request(&router, "/api/v1/node").await;
+    get_request(&router, "/api/v1/node").await;
+    let (status, _) = get_request(&router, "/api/v1/timestamps/0000").await;
+    assert!(status.is_client_error());
+
+    let (status, body) = get_request(&router, "/api/v1/admin/telemetry").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["enabled"], true);
+    assert_eq!(body["endpoint"], "https://telemetry.example.com/flow");
+    let report = &body["report"];
+    assert_eq!(report["features"]["GET /api/v1/node"], 2);
+    // By route, never by the path as requested
+    assert_eq!(report["features"]["GET /api/v1/timestamps/{digest}"], 1);
+    assert!(
+        report["features"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .all(|feature| !feature.contains("0000"))
+    );
+    let errors = report["errors"].as_object().unwrap();
+    assert_eq!(errors.values().map(|n| n.as_u64().unwrap()).sum::<u64>(), 1);
+}
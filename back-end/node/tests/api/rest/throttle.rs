@@ -0,0 +1,112 @@
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use axum::middleware;
+use axum::routing::get;
+use node::api::servers::throttle::{self, RequestLimits, RouteClass};
+use node::bootstrap::config::RequestLimitsConfig;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tower::ServiceExt;
+
+async fn status_of(app: &Router, uri: &str) -> (StatusCode, bool) {
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let retry = response.headers().contains_key(header::RETRY_AFTER);
+    (response.status(), retry)
+}
+
+/// A cheap route, an expensive one held until `release`, and one that
+/// never answers
+fn app(config: RequestLimitsConfig, release: Arc<Notify>) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/webauthn/start_authentication",
+            get(|| async { "ok" }),
+        )
+        .route(
+            "/api/v1/dids/resolve/{did}",
+            get(move || async move {
+                release.notified().await;
+                "resolved"
+            }),
+        )
+        .route(
+            "/api/v1/kv",
+            get(|| async { std::future::pending::<&str>().await }),
+        )
+        .layer(middleware::from_fn_with_state(
+            RequestLimits::new(&config),
+            throttle::limit,
+        ))
+}
+
+#[test]
+fn test_route_classes() {
+    for path in [
+        "/api/v1/users/me/export",
+        "/api/v1/spaces/docs/files/a/b.txt",
+        "/api/v1/spaces/docs/manifest/verify",
+        "/api/v1/dids/resolve/did:web:example.com",
+        "/api/v1/admin/metrics",
+        "/api/v1/tasks/nightly/run",
+    ] {
+        assert_eq!(RouteClass::of(path), RouteClass::Expensive, "{}", path);
+    }
+    for path in [
+        "/api/v1/webauthn/finish_authentication",
+        "/api/v1/spaces",
+        "/api/v1/spaces//files/a",
+        "/api/v1/didsx",
+        "/api/v1/admin/lockouts",
+        "/api/v1/tasks/nightly/runs",
+    ] {
+        assert_eq!(RouteClass::of(path), RouteClass::Cheap, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_expensive_routes_dont_starve_cheap_ones() {
+    let release = Arc::new(Notify::new());
+    let app = app(
+        RequestLimitsConfig {
+            max_concurrent_expensive: 1,
+            ..RequestLimitsConfig::default()
+        },
+        release.clone(),
+    );
+
+    let slow = tokio::spawn({
+        let app = app.clone();
+        async move { status_of(&app, "/api/v1/dids/resolve/did:web:a").await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // The only expensive slot is taken; sign-ins still go through
+    let (status, retry) = status_of(&app, "/api/v1/dids/resolve/did:web:b").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(retry);
+    let (status, _) = status_of(&app, "/api/v1/webauthn/start_authentication").await;
+    assert_eq!(status, StatusCode::OK);
+
+    release.notify_one();
+    assert_eq!(slow.await.unwrap().0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_slow_handlers_time_out() {
+    let app = app(
+        RequestLimitsConfig {
+            timeout: Duration::from_millis(50),
+            ..RequestLimitsConfig::default()
+        },
+        Arc::new(Notify::new()),
+    );
+
+    let (status, _) = status_of(&app, "/api/v1/kv").await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+}
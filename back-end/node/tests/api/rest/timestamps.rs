@@ -0,0 +1,61 @@
+use crate::modules::timestamp::{CERT_SHA256, REPLY};
+use crate::{
+    api::rest::helpers::*,
+    bootstrap::init::{setup_test_node, setup_test_server},
+};
+use axum::http::StatusCode;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use node::api::servers::{app_state::AppState, rest};
+use node::modules::timestamp;
+use serde_json::json;
+
+/// What the fixture reply in `tests/modules/timestamp.rs` timestamps
+const DIGEST: &str = "1c5dc5fb10d05c72bfb91d7e5e19e3a0f9e7fb82c64812ec0500f4f7c6713d90";
+
+#[tokio::test]
+async fn test_timestamps_need_an_authority() {
+    let server = setup_test_server().await;
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/timestamps",
+        json!({ "digest": DIGEST }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+
+    let (status, _) = get_request(&server.router, &format!("/api/v1/timestamps/{}", DIGEST)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_verify_timestamp() {
+    let token = timestamp::parse_reply(&STANDARD.decode(REPLY).unwrap()).unwrap();
+    let proof = json!({
+        "authorityUrl": "http://tsa.invalid",
+        "digest": DIGEST,
+        "token": STANDARD.encode(&token),
+        "genTime": "2026-10-16T13:23:18Z",
+    });
+
+    // Nothing is trusted until a certificate is pinned
+    let server = setup_test_server().await;
+    let (status, _) =
+        post_request(&server.router, "/api/v1/timestamps/verify", proof.clone()).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+
+    let (mut node, _temp) = setup_test_node().await;
+    node.trusted_timestamps = vec![CERT_SHA256];
+    let router = rest::build_router(AppState::new(node));
+    let (status, verification) =
+        post_request(&router, "/api/v1/timestamps/verify", proof.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", verification);
+    assert_eq!(verification["authority"], "CN=Flow Test TSA");
+    assert_eq!(verification["genTime"], "2026-10-16T13:23:18Z");
+
+    let mut other = proof;
+    other["digest"] = json!("00".repeat(32));
+    let (status, _) = post_request(&router, "/api/v1/timestamps/verify", other).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,62 @@
+use crate::bootstrap::init::{setup_multi_user_server, setup_test_server};
+
+use super::helpers::*;
+use axum::http::StatusCode;
+use node::modules::updater::CURRENT_VERSION;
+
+#[tokio::test]
+async fn test_update_status_before_first_check() {
+    let server = setup_test_server().await;
+
+    let (status, body) = get_request(&server.router, "/api/v1/admin/update").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["state"], "unchecked");
+    assert_eq!(body["currentVersion"], CURRENT_VERSION);
+}
+
+#[tokio::test]
+async fn test_lockouts_listed_and_cleared() {
+    use node::modules::ssi::webauthn::lockout::AttemptKey;
+
+    let server = setup_test_server().await;
+    let key = AttemptKey::Network("10.1.2.0/24".to_string());
+    server
+        .node
+        .auth_guard()
+        .record_failure(std::slice::from_ref(&key))
+        .await
+        .unwrap();
+
+    let (status, body) = get_request(&server.router, "/api/v1/admin/lockouts").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["lockouts"][0]["key"], "network:10.1.2.0/24");
+    assert_eq!(body["lockouts"][0]["failures"], 1);
+
+    let (status, _) = delete_request(
+        &server.router,
+        "/api/v1/admin/lockouts?key=network:10.1.2.0%2F24",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = get_request(&server.router, "/api/v1/admin/lockouts").await;
+    assert!(body["lockouts"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_lockouts_are_node_only() {
+    let (server, token) = setup_multi_user_server().await;
+
+    let (status, _) =
+        get_request_with_token(&server.router, "/api/v1/admin/lockouts", &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = delete_request_with_token(
+        &server.router,
+        "/api/v1/admin/lockouts?key=network:10.1.2.0%2F24",
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,301 @@
+use crate::{api::rest::helpers::*, bootstrap::init::setup_test_node};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use node::api::servers::{app_state::AppState, rest};
+use node::bootstrap::config::BodyLimits;
+use node::modules::space::Encryption;
+use node::modules::tenancy::Actor;
+use serde_json::json;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+const LIMITS: BodyLimits = BodyLimits {
+    max_body_bytes: 256,
+    max_upload_bytes: 4096,
+    spill_threshold_bytes: 1024,
+};
+
+/// Router with small limits over a node with one space; the node's config
+/// dir is the last temp dir
+async fn setup() -> (Router, String, TempDir, TempDir) {
+    let (mut node, temp) = setup_test_node().await;
+    node.home = Some(temp.path().to_path_buf());
+    let space_dir = TempDir::new().unwrap();
+    node.create_space(space_dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    let router = rest::build_router(AppState::new(node).with_limits(LIMITS));
+    (router, key, space_dir, temp)
+}
+
+async fn upload(router: &Router, uri: &str, bytes: Vec<u8>) -> StatusCode {
+    router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("PUT")
+                .body(Body::from(bytes))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_json_body_limit() {
+    let (node, _temp) = setup_test_node().await;
+    let token = session_token(&node).await;
+    let router = rest::build_router(AppState::new(node).with_limits(LIMITS));
+
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": "x".repeat(512)}),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_upload_small_and_spilled_files() {
+    let (router, key, space_dir, temp) = setup().await;
+
+    let status = upload(
+        &router,
+        &format!("/api/v1/spaces/{}/files/notes.txt", key),
+        b"hello".to_vec(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        std::fs::read(space_dir.path().join("notes.txt")).unwrap(),
+        b"hello"
+    );
+
+    // Past the spill threshold, and larger than the JSON limit
+    let large: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+    let status = upload(
+        &router,
+        &format!("/api/v1/spaces/{}/files/photos/a.bin", key),
+        large.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        std::fs::read(space_dir.path().join("photos/a.bin")).unwrap(),
+        large
+    );
+    // Spilled into the node's scratch dir, not the space, and moved out
+    assert_eq!(
+        std::fs::read_dir(space_dir.path().join("photos"))
+            .unwrap()
+            .count(),
+        1
+    );
+    assert_eq!(
+        std::fs::read_dir(temp.path().join("tmp")).unwrap().count(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_upload_limits_and_paths() {
+    let (router, key, space_dir, _temp) = setup().await;
+
+    let status = upload(
+        &router,
+        &format!("/api/v1/spaces/{}/files/big.bin", key),
+        vec![0; 5000],
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(!space_dir.path().join("big.bin").exists());
+
+    let status = upload(
+        &router,
+        &format!("/api/v1/spaces/{}/files/a/../../escape.txt", key),
+        b"x".to_vec(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let missing = "ab".repeat(32);
+    let status = upload(
+        &router,
+        &format!("/api/v1/spaces/{}/files/a.txt", missing),
+        b"x".to_vec(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let status = upload(&router, "/api/v1/spaces/missing/files/a.txt", b"x".to_vec()).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_upload_doesnt_follow_symlinks_out_of_the_space() {
+    let (router, key, space_dir, _temp) = setup().await;
+    let outside = TempDir::new().unwrap();
+    std::os::unix::fs::symlink(outside.path(), space_dir.path().join("link")).unwrap();
+    std::os::unix::fs::symlink(
+        outside.path().join("escape.txt"),
+        space_dir.path().join("dangling.txt"),
+    )
+    .unwrap();
+
+    for path in ["link/escape.txt", "dangling.txt"] {
+        let status = upload(
+            &router,
+            &format!("/api/v1/spaces/{}/files/{}", key, path),
+            b"x".to_vec(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+    }
+    assert!(!outside.path().join("escape.txt").exists());
+}
+
+async fn download(router: &Router, uri: &str) -> (StatusCode, Vec<u8>) {
+    let response = router
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, bytes.to_vec())
+}
+
+#[tokio::test]
+async fn test_encrypted_space_round_trip() {
+    let (node, _temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    let router = rest::build_router(AppState::new(node.clone()).with_limits(LIMITS));
+
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": space_dir.path().to_str().unwrap(), "encrypted": true}),
+        &session_token(&node).await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let space = node.list_spaces().await.unwrap().remove(0);
+    assert!(space.encrypted);
+
+    let large: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+    for (path, contents) in [("notes.txt", b"hello".to_vec()), ("photos/a.bin", large)] {
+        let uri = format!("/api/v1/spaces/{}/files/{}", space.key, path);
+        assert_eq!(
+            upload(&router, &uri, contents.clone()).await,
+            StatusCode::OK
+        );
+
+        let stored = std::fs::read(space_dir.path().join(path)).unwrap();
+        assert!(stored.starts_with(b"FLOWENC1"));
+        assert!(!stored.windows(contents.len()).any(|w| w == contents));
+        assert_eq!(download(&router, &uri).await, (StatusCode::OK, contents));
+    }
+    // Neither the spilled plaintext nor the ciphertext temp file is left over
+    assert_eq!(
+        std::fs::read_dir(space_dir.path().join("photos"))
+            .unwrap()
+            .count(),
+        1
+    );
+
+    let (status, _) = download(
+        &router,
+        &format!("/api/v1/spaces/{}/files/missing.txt", space.key),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_turning_encryption_on_encrypts_existing_files() {
+    let (node, _temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    std::fs::create_dir(space_dir.path().join("docs")).unwrap();
+    std::fs::write(space_dir.path().join("docs/old.txt"), b"from before").unwrap();
+    let dir = space_dir.path().to_str().unwrap();
+    node.create_space(dir).await.unwrap();
+    let router = rest::build_router(AppState::new(node.clone()).with_limits(LIMITS));
+
+    let (status, _) = post_request_with_token(
+        &router,
+        "/api/v1/spaces",
+        json!({"dir": dir, "encrypted": true}),
+        &session_token(&node).await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let key = node.list_spaces().await.unwrap().remove(0).key;
+
+    let stored = std::fs::read(space_dir.path().join("docs/old.txt")).unwrap();
+    assert!(stored.starts_with(b"FLOWENC1"));
+    let uri = format!("/api/v1/spaces/{}/files/docs/old.txt", key);
+    assert_eq!(
+        download(&router, &uri).await,
+        (StatusCode::OK, b"from before".to_vec())
+    );
+
+    // A plaintext file swapped in behind the node's back is refused
+    std::fs::write(space_dir.path().join("docs/old.txt"), b"swapped").unwrap();
+    let (status, body) = download(&router, &uri).await;
+    assert_ne!(status, StatusCode::OK);
+    assert_ne!(body, b"swapped");
+}
+
+#[tokio::test]
+async fn test_encrypted_space_manifest_hashes_plaintext() {
+    let (node, _temp) = setup_test_node().await;
+    let space_dir = TempDir::new().unwrap();
+    node.create_space_with(
+        Actor::Node,
+        space_dir.path().to_str().unwrap(),
+        Encryption::Node,
+    )
+    .await
+    .unwrap();
+    let key = node.list_spaces().await.unwrap().remove(0).key;
+    let router = rest::build_router(AppState::new(node.clone()).with_limits(LIMITS));
+
+    let uri = format!("/api/v1/spaces/{}/files/docs/a.txt", key);
+    assert_eq!(
+        upload(&router, &uri, b"plain".to_vec()).await,
+        StatusCode::OK
+    );
+
+    for sub_path in ["", "docs"] {
+        let manifest = node.space_manifest(&key, sub_path).await.unwrap();
+        let entry = &manifest.manifest.entries[0];
+        assert_eq!(entry.blake3, blake3::hash(b"plain").to_hex().to_string());
+        assert_eq!(entry.size, 5);
+        assert!(
+            node.verify_space_manifest(&key, manifest)
+                .await
+                .unwrap()
+                .is_valid()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_plain_space_downloads_as_stored() {
+    let (router, key, space_dir, _temp) = setup().await;
+    std::fs::write(space_dir.path().join("plain.txt"), b"as is").unwrap();
+
+    let (status, body) =
+        download(&router, &format!("/api/v1/spaces/{}/files/plain.txt", key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"as is");
+}
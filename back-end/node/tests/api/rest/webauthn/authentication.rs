@@ -523,3 +523,31 @@ async fn test_finish_authentication_returns_counter() {
         passkey.sign_count, passkey.authentication_count
     );
 }
+
+#[tokio::test]
+async fn test_start_conditional_authentication() {
+    let server = setup_test_server().await;
+
+    let (status, body) = post_request(
+        &server.router,
+        "/api/v1/webauthn/start_authentication?mediation=conditional",
+        json!({}),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["challenge"]["mediation"], "conditional");
+    assert_eq!(
+        body["challenge"]["publicKey"]["allowCredentials"],
+        json!([])
+    );
+    assert!(body["challenge_id"].is_string());
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/webauthn/start_authentication?mediation=silent",
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
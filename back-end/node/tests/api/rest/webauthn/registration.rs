@@ -642,3 +642,41 @@ async fn test_finish_registration_challenge_expires() {
         "Challenge expiry test completed (implement actual timeout test with tokio::time::sleep if needed)"
     );
 }
+
+#[tokio::test]
+async fn test_start_registration_with_options() {
+    let server = setup_test_server().await;
+
+    let (status, body) = post_request(
+        &server.router,
+        "/api/v1/webauthn/start_registration",
+        json!({"timeout": 120000, "hints": ["client-device"]}),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["options"]["timeout"], 120000);
+    assert_eq!(body["options"]["attestation"], "none");
+    assert_eq!(body["options"]["hints"], json!(["client-device"]));
+    assert_eq!(body["challenge"]["publicKey"]["timeout"], 120000);
+    assert_eq!(
+        body["challenge"]["publicKey"]["hints"],
+        json!(["client-device"])
+    );
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/webauthn/start_registration",
+        json!({"timeout": 3600000}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post_request(
+        &server.router,
+        "/api/v1/webauthn/start_registration",
+        json!({"attestation": "direct"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -1,3 +1,5 @@
+mod protocol;
+
 use crate::bootstrap::init::setup_test_server;
 use axum::Router;
 use futures_util::{SinkExt, StreamExt};
@@ -161,6 +163,30 @@ async fn test_websocket_connection_rejected_invalid_upgrade() {
     info!("✓ Invalid WebSocket upgrade properly rejected");
 }
 
+#[tokio::test]
+async fn test_websocket_connection_checks_origin() {
+    use tungstenite::client::IntoClientRequest;
+
+    let (ws_url, server_handle) = setup_websocket_test_server().await;
+    let connect_from = |origin: &'static str| {
+        let mut request = ws_url.as_str().into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("Origin", origin.parse().unwrap());
+        connect_async(request)
+    };
+
+    match connect_from("https://evil.example").await {
+        Err(tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), 403)
+        }
+        other => panic!("Expected 403, got {:?}", other.map(|(_, r)| r.status())),
+    }
+    assert!(connect_from("http://localhost:3000").await.is_ok());
+
+    server_handle.abort();
+}
+
 #[tokio::test]
 async fn test_websocket_handles_multiple_concurrent_connections() {
     // Setup: Start WebSocket server
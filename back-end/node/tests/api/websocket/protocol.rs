@@ -0,0 +1,389 @@
+use crate::bootstrap::init::setup_test_node;
+use crate::util::users::signed_in_user;
+use event::types::{Event, EventType};
+use futures_util::{SinkExt, StreamExt};
+use node::api::node::Node;
+use node::api::servers::{app_state::AppState, websocket};
+use node::bootstrap::config::MultiUserConfig;
+use serde_json::{Value, json};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Serve WebSocket for `node` on a random port and connect to it
+async fn connect(node: Node) -> Socket {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = websocket::build_router(AppState::new(node));
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    let (socket, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    socket
+}
+
+async fn send(socket: &mut Socket, message: Value) {
+    socket
+        .send(tungstenite::Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+}
+
+async fn receive(socket: &mut Socket) -> Value {
+    let message = timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("Should receive a message")
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+async fn request(socket: &mut Socket, message: Value) -> Value {
+    send(socket, message).await;
+    receive(socket).await
+}
+
+#[tokio::test]
+async fn test_versioned_requests_echo_their_id() {
+    let (node, _temp) = setup_test_node().await;
+    let mut socket = connect(node).await;
+    let dir = TempDir::new().unwrap();
+
+    let hello = request(&mut socket, json!({"id": 1, "action": "protocol.hello"})).await;
+    assert_eq!(hello["v"], 1);
+    assert_eq!(hello["id"], 1);
+    assert_eq!(hello["status"], "ok");
+    assert_eq!(hello["action"], "protocol.hello");
+    assert_eq!(hello["payload"]["versions"], json!([1]));
+    let actions = hello["payload"]["actions"].as_array().unwrap();
+    assert!(actions.contains(&json!("space.create")));
+    assert!(!actions.contains(&json!("spaces.create")));
+    assert!(actions.contains(&json!("webauthn.finish_authentication")));
+
+    let created = request(
+        &mut socket,
+        json!({
+            "v": 1,
+            "id": "create-1",
+            "action": "space.create",
+            "payload": {"dir": dir.path().to_str().unwrap()}
+        }),
+    )
+    .await;
+    assert_eq!(created["id"], "create-1");
+    assert_eq!(created["status"], "ok", "{}", created);
+
+    let missing = request(
+        &mut socket,
+        json!({"id": "create-2", "action": "space.create", "payload": {}}),
+    )
+    .await;
+    assert_eq!(missing["status"], "error");
+    assert_eq!(missing["payload"]["code"], "invalid");
+
+    let unknown = request(&mut socket, json!({"id": 2, "action": "nope"})).await;
+    assert_eq!(unknown["action"], "nope");
+    assert_eq!(unknown["payload"]["code"], "unknown_action");
+    assert_eq!(
+        unknown["payload"]["message"],
+        "That action isn't supported."
+    );
+    assert_eq!(unknown["payload"]["detail"], "Unknown action 'nope'");
+
+    let future = request(
+        &mut socket,
+        json!({"v": 2, "id": 3, "action": "protocol.hello"}),
+    )
+    .await;
+    assert_eq!(future["id"], 3);
+    assert_eq!(future["payload"]["code"], "unsupported_version");
+
+    // Messages without an id keep the original format
+    let legacy = request(&mut socket, json!({"action": "nope"})).await;
+    assert_eq!(legacy["action"], "error");
+    assert_eq!(legacy["status"], "error");
+    assert!(legacy.get("id").is_none());
+}
+
+#[tokio::test]
+async fn test_error_messages_follow_accept_language() {
+    let (node, _temp) = setup_test_node().await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = websocket::build_router(AppState::new(node));
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    let mut upgrade = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    upgrade
+        .headers_mut()
+        .insert("accept-language", "pt-BR, es;q=0.8".parse().unwrap());
+    let (mut socket, _) = connect_async(upgrade).await.unwrap();
+
+    let unknown = request(&mut socket, json!({"id": 1, "action": "nope"})).await;
+    assert_eq!(unknown["payload"]["code"], "unknown_action");
+    assert_eq!(
+        unknown["payload"]["message"],
+        "Esa acción no es compatible."
+    );
+    assert_eq!(unknown["payload"]["detail"], "Unknown action 'nope'");
+}
+
+#[tokio::test]
+async fn test_subscribers_get_the_events_they_asked_for() {
+    let (node, _temp) = setup_test_node().await;
+    let mut socket = connect(node.clone()).await;
+
+    let invalid = request(
+        &mut socket,
+        json!({"id": 1, "action": "events.subscribe", "payload": {"types": ["nope"]}}),
+    )
+    .await;
+    assert_eq!(invalid["payload"]["code"], "invalid");
+
+    let subscribed = request(
+        &mut socket,
+        json!({
+            "id": 2,
+            "action": "events.subscribe",
+            "payload": {"types": ["key_usage_anomaly"]}
+        }),
+    )
+    .await;
+    assert_eq!(subscribed["status"], "ok", "{}", subscribed);
+
+    node.publish(&Event::new(EventType::FileCreated).with("path", "/a"));
+    node.publish(&Event::new(EventType::KeyUsageAnomaly).with("key_id", "k1"));
+    let event = receive(&mut socket).await;
+    assert_eq!(event["status"], "event");
+    assert_eq!(event["id"], Value::Null);
+    assert_eq!(event["action"], "key_usage_anomaly");
+    assert_eq!(event["payload"]["key_id"], "k1");
+
+    let unsubscribed = request(
+        &mut socket,
+        json!({"id": 3, "action": "events.unsubscribe"}),
+    )
+    .await;
+    assert_eq!(unsubscribed["payload"]["subscribed"], true);
+    node.publish(&Event::new(EventType::KeyUsageAnomaly).with("key_id", "k2"));
+    let hello = request(&mut socket, json!({"id": 4, "action": "protocol.hello"})).await;
+    assert_eq!(hello["id"], 4);
+}
+
+#[tokio::test]
+async fn test_users_only_see_their_own_events() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.multi_user = MultiUserConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let (alice, token) = signed_in_user(&node, "alice").await;
+    let (bob, _) = signed_in_user(&node, "bob").await;
+    let mut socket = connect(node.clone()).await;
+
+    let anonymous = request(&mut socket, json!({"id": 1, "action": "events.subscribe"})).await;
+    assert_eq!(anonymous["payload"]["code"], "unauthorized");
+    let subscribed = request(
+        &mut socket,
+        json!({"id": 2, "action": "events.subscribe", "token": token}),
+    )
+    .await;
+    assert_eq!(subscribed["status"], "ok", "{}", subscribed);
+
+    node.publish(&Event::new(EventType::KeyUsageAnomaly).with("user_id", bob));
+    node.publish(&Event::new(EventType::ScheduledTaskFailed).with("task_id", "t1"));
+    node.publish(&Event::new(EventType::KeyUsageAnomaly).with("user_id", alice));
+    let event = receive(&mut socket).await;
+    assert_eq!(event["action"], "key_usage_anomaly");
+    assert_eq!(event["payload"]["user_id"], alice);
+}
+
+#[tokio::test]
+async fn test_guest_mode_opens_only_sign_in() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.guest_mode = true;
+    let (_, token) = signed_in_user(&node, "guest").await;
+    let mut socket = connect(node).await;
+    let dir = TempDir::new().unwrap();
+    let create = |id: u32, token: Option<&str>| {
+        json!({
+            "id": id,
+            "action": "space.create",
+            "payload": {"dir": dir.path().to_str().unwrap()},
+            "token": token
+        })
+    };
+
+    let hello = request(&mut socket, json!({"id": 1, "action": "protocol.hello"})).await;
+    assert_eq!(hello["status"], "ok");
+    // Signing in is open; this one only fails on its credential
+    let finished = request(
+        &mut socket,
+        json!({
+            "id": 2,
+            "action": "webauthn.finish_authentication",
+            "payload": {"challenge_id": "unknown", "credential": {}}
+        }),
+    )
+    .await;
+    assert_eq!(finished["payload"]["code"], "invalid");
+
+    let refused = request(&mut socket, create(3, None)).await;
+    assert_eq!(refused["payload"]["code"], "unauthorized");
+    let created = request(&mut socket, create(4, Some(&token))).await;
+    assert_eq!(created["status"], "ok", "{}", created);
+}
+
+#[tokio::test]
+async fn test_space_actions() {
+    let (node, _temp) = setup_test_node().await;
+    let mut socket = connect(node).await;
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.txt"), b"one").unwrap();
+
+    let subscribed = request(
+        &mut socket,
+        json!({
+            "id": 1,
+            "action": "events.subscribe",
+            "payload": {"types": ["file_created", "file_modified", "file_deleted"]}
+        }),
+    )
+    .await;
+    assert_eq!(subscribed["status"], "ok", "{}", subscribed);
+    let created = request(
+        &mut socket,
+        json!({
+            "id": 2,
+            "action": "space.create",
+            "payload": {"dir": dir.path().to_str().unwrap()}
+        }),
+    )
+    .await;
+    assert_eq!(created["status"], "ok", "{}", created);
+
+    let listed = request(&mut socket, json!({"id": 3, "action": "space.list"})).await;
+    let spaces = listed["payload"]["spaces"].as_array().unwrap();
+    assert_eq!(spaces.len(), 1);
+    let key = spaces[0]["key"].clone();
+    let version = spaces[0]["version"].as_i64().unwrap();
+
+    // A space never indexed has every file found created
+    let scanned = request(
+        &mut socket,
+        json!({"id": 4, "action": "space.scan", "payload": {"key": key}}),
+    )
+    .await;
+    assert_eq!(
+        scanned["payload"]["created"],
+        json!(["a.txt"]),
+        "{}",
+        scanned
+    );
+    let event = receive(&mut socket).await;
+    assert_eq!(event["action"], "file_created");
+    assert_eq!(event["payload"]["space_key"], key);
+    assert_eq!(event["payload"]["path"], "a.txt");
+
+    // Changes made outside the node show up on the next scan
+    std::fs::write(dir.path().join("a.txt"), b"two").unwrap();
+    std::fs::write(dir.path().join("b.txt"), b"new").unwrap();
+    let scanned = request(
+        &mut socket,
+        json!({"id": 5, "action": "space.scan", "payload": {"key": key}}),
+    )
+    .await;
+    assert_eq!(scanned["payload"]["created"], json!(["b.txt"]));
+    assert_eq!(scanned["payload"]["modified"], json!(["a.txt"]));
+    let mut events = [receive(&mut socket).await, receive(&mut socket).await];
+    events.sort_by_key(|event| event["action"].to_string());
+    assert_eq!(events[0]["action"], "file_created");
+    assert_eq!(events[1]["action"], "file_modified");
+
+    std::fs::remove_file(dir.path().join("a.txt")).unwrap();
+    let scanned = request(
+        &mut socket,
+        json!({"id": 6, "action": "space.scan", "payload": {"key": key}}),
+    )
+    .await;
+    assert_eq!(scanned["payload"]["deleted"], json!(["a.txt"]));
+    assert_eq!(receive(&mut socket).await["action"], "file_deleted");
+
+    // Deleting forgets the space but leaves its files. Scans don't move its
+    // version on; an edit from a stale read is refused.
+    let stale = request(
+        &mut socket,
+        json!({"id": 7, "action": "space.delete", "payload": {"key": key, "version": version - 1}}),
+    )
+    .await;
+    assert_eq!(stale["payload"]["code"], "conflict", "{}", stale);
+    let deleted = request(
+        &mut socket,
+        json!({"id": 7, "action": "space.delete", "payload": {"key": key, "version": version}}),
+    )
+    .await;
+    assert_eq!(deleted["status"], "ok", "{}", deleted);
+    assert_eq!(deleted["payload"]["version"], version + 1);
+    assert!(dir.path().join("b.txt").exists());
+    let listed = request(&mut socket, json!({"id": 8, "action": "space.list"})).await;
+    assert_eq!(listed["payload"]["spaces"], json!([]));
+    let gone = request(
+        &mut socket,
+        json!({"id": 9, "action": "space.scan", "payload": {"key": key}}),
+    )
+    .await;
+    assert_eq!(gone["payload"]["code"], "not_found");
+}
+
+#[tokio::test]
+async fn test_maintenance_refuses_writes_but_delivers_events() {
+    let (node, _temp) = setup_test_node().await;
+    let mut socket = connect(node.clone()).await;
+    let dir = TempDir::new().unwrap();
+    node.maintenance.enable("backup");
+
+    let subscribed = request(
+        &mut socket,
+        json!({
+            "id": 1,
+            "action": "events.subscribe",
+            "payload": {"types": ["key_usage_anomaly"]}
+        }),
+    )
+    .await;
+    assert_eq!(subscribed["status"], "ok", "{}", subscribed);
+
+    let refused = request(
+        &mut socket,
+        json!({
+            "id": 2,
+            "action": "space.create",
+            "payload": {"dir": dir.path().to_str().unwrap()}
+        }),
+    )
+    .await;
+    assert_eq!(refused["status"], "error");
+    assert_eq!(refused["payload"]["code"], "maintenance", "{}", refused);
+    let listed = request(&mut socket, json!({"id": 3, "action": "space.list"})).await;
+    assert_eq!(listed["status"], "ok");
+
+    node.publish(&Event::new(EventType::KeyUsageAnomaly).with("key_id", "k1"));
+    let event = receive(&mut socket).await;
+    assert_eq!(event["action"], "key_usage_anomaly");
+
+    node.maintenance.disable();
+    let created = request(
+        &mut socket,
+        json!({
+            "id": 4,
+            "action": "space.create",
+            "payload": {"dir": dir.path().to_str().unwrap()}
+        }),
+    )
+    .await;
+    assert_eq!(created["status"], "ok", "{}", created);
+}
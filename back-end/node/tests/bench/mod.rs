@@ -0,0 +1,68 @@
+use node::bench::{BenchArgs, BenchNode, BenchReport, Scenario, run_scenario};
+use std::time::Duration;
+
+#[test]
+fn test_parse_bench_args() {
+    let args = BenchArgs::parse(["spaces", "--concurrency", "8", "-n", "50"]).unwrap();
+    assert_eq!(args.scenarios, vec![Scenario::Spaces]);
+    assert_eq!(args.concurrency, 8);
+    assert_eq!(args.iterations, 50);
+
+    let args = BenchArgs::parse(["all"]).unwrap();
+    assert_eq!(args.scenarios, Scenario::ALL.to_vec());
+
+    assert!(BenchArgs::parse(Vec::<String>::new()).is_err());
+    assert!(BenchArgs::parse(["nope"]).is_err());
+    assert_eq!(
+        BenchArgs::parse(["broadcast"]).unwrap().scenarios,
+        vec![Scenario::Broadcast]
+    );
+    assert!(BenchArgs::parse(["ws", "--concurrency", "0"]).is_err());
+    assert!(BenchArgs::parse(["ws", "--iterations"]).is_err());
+}
+
+#[test]
+fn test_report_percentiles() {
+    let latencies = (1..=100).map(Duration::from_millis).collect();
+    let report = BenchReport::new("test".to_string(), 1, latencies, 0, Duration::from_secs(2));
+
+    assert_eq!(report.percentile(50.0), Duration::from_millis(50));
+    assert_eq!(report.percentile(99.0), Duration::from_millis(99));
+    assert_eq!(report.percentile(100.0), Duration::from_millis(100));
+    assert_eq!(report.throughput(), 50.0);
+}
+
+#[tokio::test]
+async fn test_bench_scenarios_complete_without_errors() {
+    let bench = BenchNode::start().await.unwrap();
+
+    for scenario in [Scenario::WebAuthn, Scenario::Spaces, Scenario::WebSocket] {
+        let report = run_scenario(&bench, scenario, 2, 4).await.unwrap();
+        assert_eq!(report.errors, 0, "{} should not fail: {}", scenario, report);
+        assert_eq!(report.latencies.len(), 4);
+    }
+}
+
+#[tokio::test]
+async fn test_bench_broadcast_reaches_every_subscriber() {
+    let bench = BenchNode::start().await.unwrap();
+
+    let report = run_scenario(&bench, Scenario::Broadcast, 3, 5)
+        .await
+        .unwrap();
+    assert_eq!(report.errors, 0, "Broadcast should not fail: {}", report);
+    assert_eq!(report.latencies.len(), 5);
+    assert_eq!(report.concurrency, 3);
+}
+
+#[tokio::test]
+async fn test_bench_did_resolution() {
+    let bench = BenchNode::start().await.unwrap();
+
+    let report = run_scenario(&bench, Scenario::Did, 2, 4).await.unwrap();
+    assert_eq!(
+        report.errors, 0,
+        "DID resolution should not fail: {}",
+        report
+    );
+}
@@ -1,5 +1,10 @@
-use node::bootstrap::config::Config;
+use node::bootstrap::config::{
+    AcmeChallenge, Config, KeyStorageBackend, LETS_ENCRYPT_DIRECTORY, ServerConfig, low_resource,
+};
 use serial_test::serial;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::util::temp_env::TempEnv;
 
@@ -63,6 +68,601 @@ fn test_config_applies_defaults() -> Result<(), Box<dyn std::error::Error>> {
     );
     assert_eq!(config.kv.path, "/tmp/flow-kv", "Should use default KV path");
     assert_eq!(config.server.host, "0.0.0.0", "Should use default host");
+    assert_eq!(config.key_storage, KeyStorageBackend::File);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_ephemeral_uses_in_memory_stores() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    // DATABASE_URL is not required in ephemeral mode
+    env.remove("DATABASE_URL");
+    env.remove("FLOW_EPHEMERAL");
+
+    let config = Config::ephemeral_from_env()?;
+
+    assert!(config.ephemeral);
+    assert!(config.db.is_in_memory());
+    assert!(config.kv.in_memory);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_kv_in_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.set("KV_STORE_PATH", ":memory:");
+    env.remove("FLOW_EPHEMERAL");
+
+    let config = Config::from_env()?;
+
+    assert!(!config.ephemeral);
+    assert!(config.db.is_in_memory());
+    assert!(config.kv.in_memory);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_low_resource_shrinks_defaults() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite://test.db");
+    for key in [
+        "DB_MAX_CONNECTIONS",
+        "DB_MIN_CONNECTIONS",
+        "KV_CACHE_MB",
+        "FLOW_DID_CACHE_CAPACITY",
+        "FLOW_BODY_SPILL_BYTES",
+        "FLOW_SOFT_LIMIT_MEMORY_MB",
+        "FLOW_MAX_CONCURRENT_EXPENSIVE",
+    ] {
+        env.remove(key);
+    }
+    env.remove("FLOW_LOW_RESOURCE");
+
+    let config = Config::from_env()?;
+    assert!(!config.low_resource);
+    assert_eq!(config.kv.cache_bytes, None);
+    assert!(!config.kv.low_space);
+    assert_eq!(config.resources.memory_bytes, None);
+
+    env.set("FLOW_LOW_RESOURCE", "true");
+    env.set("DB_MIN_CONNECTIONS", "2");
+    let config = Config::from_env()?;
+    assert!(config.low_resource);
+    assert_eq!(
+        config.db.max_connections as u64,
+        low_resource::DB_MAX_CONNECTIONS
+    );
+    // Explicit settings still win
+    assert_eq!(config.db.min_connections, 2);
+    assert_eq!(
+        config.kv.cache_bytes,
+        Some(low_resource::KV_CACHE_MB * 1024 * 1024)
+    );
+    assert!(config.kv.low_space);
+    assert_eq!(
+        config.did_cache.capacity as u64,
+        low_resource::DID_CACHE_CAPACITY
+    );
+    assert_eq!(
+        config.limits.spill_threshold_bytes as u64,
+        low_resource::BODY_SPILL_BYTES
+    );
+    assert_eq!(
+        config.request_limits.max_concurrent_expensive as u64,
+        low_resource::MAX_CONCURRENT_EXPENSIVE
+    );
+    assert!(config.resources.memory_bytes.is_some());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_data_dir_defaults() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.remove("DATABASE_URL");
+    env.remove("KV_STORE_PATH");
+    env.remove("FLOW_EPHEMERAL");
+    env.remove("KV_IN_MEMORY");
+    env.remove("FLOW_SPACES_DIR");
+    env.set("FLOW_DATA_DIR", "/srv/flow");
+
+    let config = Config::from_env()?;
+    assert_eq!(config.db.url, "sqlite:///srv/flow/db/flow.sqlite?mode=rwc");
+    assert_eq!(config.kv.path, "/srv/flow/kv");
+    assert_eq!(
+        config.multi_user.spaces_root,
+        Some(PathBuf::from("/srv/flow/spaces"))
+    );
+
+    // Explicit locations still win
+    env.set("KV_STORE_PATH", "/var/lib/flow-kv");
+    env.set("FLOW_SPACES_DIR", "/home/flow");
+    let config = Config::from_env()?;
+    assert_eq!(config.kv.path, "/var/lib/flow-kv");
+    assert_eq!(
+        config.multi_user.spaces_root,
+        Some(PathBuf::from("/home/flow"))
+    );
+
+    env.remove("FLOW_DATA_DIR");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_bind_addresses() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.set("HOST", "127.0.0.1");
+    env.remove("FLOW_BIND_ADDRESSES");
+    env.remove("FLOW_ADVERTISED_ADDRESSES");
+
+    // HOST alone
+    let config = Config::from_env()?;
+    assert_eq!(
+        config.server.bind_addresses,
+        vec![IpAddr::from([127, 0, 0, 1])]
+    );
+    assert!(config.server.advertised_addresses.is_empty());
+
+    env.set("FLOW_BIND_ADDRESSES", "0.0.0.0, [::]");
+    env.set(
+        "FLOW_ADVERTISED_ADDRESSES",
+        "flow.example.com:8080,[2001:db8::1]:8080",
+    );
+    let config = Config::from_env()?;
+    assert_eq!(
+        config.server.bind_addresses,
+        vec![
+            IpAddr::from([0, 0, 0, 0]),
+            IpAddr::from([0u16, 0, 0, 0, 0, 0, 0, 0])
+        ]
+    );
+    assert_eq!(config.server.advertised_addresses.len(), 2);
+    config.server.validate()?;
+
+    env.set("FLOW_BIND_ADDRESSES", "eth0");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_cors_origins() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("CORS_ORIGINS");
+    let config = Config::from_env()?;
+    assert_eq!(config.server.allowed_origins, vec!["http://localhost:3000"]);
+
+    env.set(
+        "CORS_ORIGINS",
+        " https://App.example.com/ ,https://*.example.org:8443,",
+    );
+    let config = Config::from_env()?;
+    assert_eq!(
+        config.server.allowed_origins,
+        vec!["https://app.example.com", "https://*.example.org:8443"]
+    );
+    config.server.validate()?;
+
+    for invalid in [
+        "ftp://example.com",
+        "https://example.com/app",
+        "example.com",
+    ] {
+        env.set("CORS_ORIGINS", invalid);
+        assert!(
+            Config::from_env()?.server.validate().is_err(),
+            "{}",
+            invalid
+        );
+    }
+    env.set("CORS_ORIGINS", "*");
+    Config::from_env()?.server.validate()?;
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_clock() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("FLOW_CLOCK_SKEW_SECS");
+    env.remove("FLOW_NTP_SERVER");
+    env.remove("FLOW_PEER_REPLAY_WINDOW_SECS");
+    let config = Config::from_env()?;
+    assert_eq!(config.clock.max_skew, Duration::from_secs(60));
+    assert_eq!(config.clock.ntp_server.as_deref(), Some("pool.ntp.org:123"));
+    assert_eq!(config.clock.replay_window, Duration::from_secs(300));
+
+    env.set("FLOW_CLOCK_SKEW_SECS", "5");
+    env.set("FLOW_NTP_SERVER", " ");
+    env.set("FLOW_PEER_REPLAY_WINDOW_SECS", "30");
+    let config = Config::from_env()?;
+    assert_eq!(config.clock.max_skew, Duration::from_secs(5));
+    assert_eq!(config.clock.ntp_server, None);
+    assert_eq!(config.clock.replay_window, Duration::from_secs(30));
+
+    env.set("FLOW_CLOCK_SKEW_SECS", "a minute");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_maintenance() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("FLOW_MAINTENANCE");
+    env.remove("FLOW_MAINTENANCE_RETRY_AFTER");
+    let config = Config::from_env()?;
+    assert!(!config.maintenance.enabled);
+    assert_eq!(config.maintenance.retry_after, Duration::from_secs(60));
+
+    env.set("FLOW_MAINTENANCE", "true");
+    env.set("FLOW_MAINTENANCE_RETRY_AFTER", "300");
+    let config = Config::from_env()?;
+    assert!(config.maintenance.enabled);
+    assert_eq!(config.maintenance.retry_after, Duration::from_secs(300));
+
+    env.set("FLOW_MAINTENANCE_RETRY_AFTER", "soon");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_export_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("FLOW_EXPORT_DIR");
+    assert_eq!(Config::from_env()?.export_dir, PathBuf::from("exports"));
+
+    env.set("FLOW_EXPORT_DIR", "backups/nightly");
+    assert_eq!(
+        Config::from_env()?.export_dir,
+        PathBuf::from("backups/nightly")
+    );
+
+    for outside in ["", "/var/backups", "../backups", "exports/../../etc"] {
+        env.set("FLOW_EXPORT_DIR", outside);
+        assert!(Config::from_env().is_err(), "{}", outside);
+    }
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_timestamping_pins_certificates() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("FLOW_TSA_CERT_SHA256");
+    env.set("FLOW_TSA_URL", "https://tsa.example.com");
+    assert!(Config::from_env().is_err());
+
+    let fingerprint = "4D:67:27:A0:6A:25:9B:A2:7E:98:FA:A9:A5:36:59:86:\
+                       76:EA:81:BE:62:FF:50:E7:6C:45:6D:C1:F4:44:67:8F";
+    env.set(
+        "FLOW_TSA_CERT_SHA256",
+        &format!("{}, {}", fingerprint, "00".repeat(32)),
+    );
+    let config = Config::from_env()?;
+    assert_eq!(config.timestamping.trusted_certificates.len(), 2);
+    assert_eq!(config.timestamping.trusted_certificates[0][0], 0x4d);
+
+    env.set("FLOW_TSA_CERT_SHA256", "not-a-fingerprint");
+    assert!(Config::from_env().is_err());
+
+    env.remove("FLOW_TSA_URL");
+    env.remove("FLOW_TSA_CERT_SHA256");
+    assert!(
+        Config::from_env()?
+            .timestamping
+            .trusted_certificates
+            .is_empty()
+    );
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_did_batch() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("FLOW_DID_BATCH_MAX");
+    env.remove("FLOW_DID_BATCH_PARALLELISM");
+    let config = Config::from_env()?;
+    assert_eq!(config.did_batch.max_dids, 100);
+    assert_eq!(config.did_batch.parallelism, 8);
+
+    env.set("FLOW_DID_BATCH_MAX", "20");
+    env.set("FLOW_DID_BATCH_PARALLELISM", "4");
+    let config = Config::from_env()?;
+    assert_eq!(config.did_batch.max_dids, 20);
+    assert_eq!(config.did_batch.parallelism, 4);
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_telemetry() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    for key in [
+        "FLOW_TELEMETRY_ENABLED",
+        "FLOW_TELEMETRY_URL",
+        "FLOW_TELEMETRY_INTERVAL",
+        "FLOW_TELEMETRY_EPSILON",
+    ] {
+        env.remove(key);
+    }
+    let config = Config::from_env()?;
+    assert!(!config.telemetry.enabled);
+    assert_eq!(config.telemetry.epsilon, 1.0);
+
+    env.set("FLOW_TELEMETRY_ENABLED", "true");
+    assert!(Config::from_env().is_err());
+
+    env.set("FLOW_TELEMETRY_URL", "https://telemetry.example.com/flow");
+    env.set("FLOW_TELEMETRY_INTERVAL", "3600");
+    env.set("FLOW_TELEMETRY_EPSILON", "0.5");
+    let config = Config::from_env()?;
+    assert!(config.telemetry.enabled);
+    assert_eq!(config.telemetry.submit_interval, Duration::from_secs(3600));
+    assert_eq!(config.telemetry.epsilon, 0.5);
+
+    env.set("FLOW_TELEMETRY_EPSILON", "0");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_server_config_rejects_conflicts() {
+    let server = |addresses: &[&str], advertised: &[&str], websocket_port: u16| ServerConfig {
+        rest_port: 8080,
+        websocket_port,
+        grpc_port: None,
+        p2p_port: None,
+        host: "0.0.0.0".to_string(),
+        bind_addresses: addresses.iter().map(|a| a.parse().unwrap()).collect(),
+        advertised_addresses: advertised.iter().map(|a| a.to_string()).collect(),
+        allowed_origins: vec!["http://localhost:3000".to_string()],
+    };
+
+    assert!(
+        server(&["127.0.0.1", "::1"], &["peer.example:9000"], 8081)
+            .validate()
+            .is_ok()
+    );
+    assert!(server(&[], &[], 8081).validate().is_err());
+    assert!(server(&["127.0.0.1"], &[], 8080).validate().is_err());
+    assert!(server(&["::1", "::1"], &[], 8081).validate().is_err());
+    // The wildcard already covers the specific address
+    assert!(
+        server(&["0.0.0.0", "192.168.1.5"], &[], 8081)
+            .validate()
+            .is_err()
+    );
+    assert!(
+        server(&["127.0.0.1"], &["peer.example"], 8081)
+            .validate()
+            .is_err()
+    );
+}
+
+#[test]
+#[serial]
+fn test_config_acme() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("FLOW_ACME_DOMAINS");
+    env.remove("FLOW_ACME_CHALLENGE");
+    env.remove("FLOW_ACME_HTTP_PORT");
+
+    let config = Config::from_env()?;
+    assert!(!config.acme.enabled());
+    assert_eq!(config.acme.directory_url, LETS_ENCRYPT_DIRECTORY);
+
+    env.set(
+        "FLOW_ACME_DOMAINS",
+        "node.example.com, www.node.example.com",
+    );
+    env.set("FLOW_ACME_CHALLENGE", "tls-alpn-01");
+    env.set("FLOW_ACME_HTTP_PORT", "8088");
+    let config = Config::from_env()?;
+    assert!(config.acme.enabled());
+    assert_eq!(config.acme.domains.len(), 2);
+    assert_eq!(config.acme.challenge, AcmeChallenge::TlsAlpn01);
+    assert_eq!(config.acme.http_port, 8088);
+
+    env.set("FLOW_ACME_CHALLENGE", "dns-01");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_proxy() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    env.remove("FLOW_TRUSTED_PROXIES");
+    env.remove("FLOW_BASE_PATH");
+    env.remove("FLOW_EXTERNAL_ORIGIN");
+    env.remove("FLOW_COUNTRY_HEADER");
+
+    let config = Config::from_env()?;
+    assert!(config.proxy.trusted_proxies.is_empty());
+    assert_eq!(config.proxy.base_path, "");
+    assert_eq!(config.proxy.external_origin, None);
+    assert_eq!(config.proxy.country_header, None);
+
+    env.set("FLOW_TRUSTED_PROXIES", "127.0.0.1, 10.0.0.0/8,::1");
+    env.set("FLOW_BASE_PATH", "flow/api/");
+    env.set("FLOW_EXTERNAL_ORIGIN", "https://Flow.Example.com:443/");
+    env.set("FLOW_COUNTRY_HEADER", "CF-IPCountry");
+    let config = Config::from_env()?;
+    assert_eq!(config.proxy.country_header.as_deref(), Some("CF-IPCountry"));
+    assert!(config.proxy.trusts("10.1.2.3".parse()?));
+    assert!(config.proxy.trusts("::1".parse()?));
+    assert!(!config.proxy.trusts("192.168.0.1".parse()?));
+    assert_eq!(config.proxy.base_path, "/flow/api");
+    assert_eq!(config.proxy.public_path("/ws"), "/flow/api/ws");
+    assert_eq!(
+        config.proxy.external_origin.as_deref(),
+        Some("https://flow.example.com")
+    );
+
+    env.set("FLOW_TRUSTED_PROXIES", "proxy.local");
+    assert!(Config::from_env().is_err());
+    env.remove("FLOW_TRUSTED_PROXIES");
+
+    env.set("FLOW_BASE_PATH", "/flow/../admin");
+    assert!(Config::from_env().is_err());
+    env.remove("FLOW_BASE_PATH");
+
+    env.set("FLOW_EXTERNAL_ORIGIN", "https://flow.example.com/app");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_webauthn_follows_external_origin() -> Result<(), Box<dyn std::error::Error>> {
+    use node::modules::ssi::webauthn::state::AuthConfig;
+
+    let mut env = TempEnv::new();
+
+    env.remove("WEBAUTHN_RP_ID");
+    env.remove("WEBAUTHN_RP_ORIGIN");
+    env.set("FLOW_EXTERNAL_ORIGIN", "https://flow.example.com");
+    let auth = AuthConfig::from_env()?;
+    assert_eq!(auth.rp_id, "flow.example.com");
+    assert_eq!(auth.rp_origin, "https://flow.example.com");
+
+    // Explicit WebAuthn settings still win
+    env.set("WEBAUTHN_RP_ID", "example.com");
+    env.set("WEBAUTHN_RP_ORIGIN", "https://app.example.com");
+    let auth = AuthConfig::from_env()?;
+    assert_eq!(auth.rp_id, "example.com");
+    assert_eq!(auth.rp_origin, "https://app.example.com");
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_webauthn_origin_follows_https() -> Result<(), Box<dyn std::error::Error>> {
+    use node::modules::ssi::webauthn::state::AuthConfig;
+
+    let mut env = TempEnv::new();
+
+    env.remove("WEBAUTHN_RP_ORIGIN");
+    env.remove("FLOW_EXTERNAL_ORIGIN");
+    assert_eq!(AuthConfig::from_env()?.rp_origin, "http://localhost:8080");
+    assert_eq!(
+        AuthConfig::from_env_serving(true)?.rp_origin,
+        "https://localhost:8080"
+    );
+
+    // Browsers on an HTTPS node never report an http:// origin
+    env.set("WEBAUTHN_RP_ORIGIN", "http://localhost:8080");
+    assert!(AuthConfig::from_env_serving(true).is_err());
+    env.set("WEBAUTHN_RP_ORIGIN", "https://flow.example.com");
+    assert_eq!(
+        AuthConfig::from_env_serving(true)?.rp_origin,
+        "https://flow.example.com"
+    );
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_tls() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+
+    env.set("DATABASE_URL", "sqlite::memory:");
+    for key in [
+        "FLOW_TLS_CERT",
+        "FLOW_TLS_KEY",
+        "FLOW_TLS_SELF_SIGNED",
+        "FLOW_ACME_DOMAINS",
+    ] {
+        env.remove(key);
+    }
+    let config = Config::from_env()?;
+    assert!(!config.tls.enabled());
+    assert!(!config.serves_https());
+
+    env.set("FLOW_TLS_CERT", "/etc/flow/cert.pem");
+    assert!(Config::from_env().is_err());
+    env.set("FLOW_TLS_KEY", "/etc/flow/key.pem");
+    let config = Config::from_env()?;
+    assert_eq!(
+        config.tls.key_path.as_deref(),
+        Some(std::path::Path::new("/etc/flow/key.pem"))
+    );
+    assert!(config.serves_https());
+
+    env.set("FLOW_TLS_SELF_SIGNED", "true");
+    assert!(Config::from_env().is_err());
+    env.remove("FLOW_TLS_CERT");
+    env.remove("FLOW_TLS_KEY");
+    assert!(Config::from_env()?.tls.self_signed);
+
+    // One source of certificates at a time
+    env.set("FLOW_ACME_DOMAINS", "flow.example.com");
+    assert!(Config::from_env().is_err());
+
+    Ok(())
+}
+
+#[test]
+#[serial]
+fn test_config_key_storage() -> Result<(), Box<dyn std::error::Error>> {
+    let mut env = TempEnv::new();
+    env.set("DATABASE_URL", "sqlite://test.db");
+
+    env.set("FLOW_KEY_STORAGE", "Keychain");
+    assert_eq!(Config::from_env()?.key_storage, KeyStorageBackend::Keychain);
+
+    env.set("FLOW_KEY_STORAGE", "tpm");
+    assert!(Config::from_env().is_err());
 
     Ok(())
 }
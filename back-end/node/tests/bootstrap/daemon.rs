@@ -0,0 +1,95 @@
+use node::bootstrap::daemon::{self, InstanceLock};
+use node::bootstrap::service::{self, ServiceManager, ServiceSpec};
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+#[test]
+fn test_instance_lock_is_exclusive() {
+    let temp = TempDir::new().unwrap();
+
+    let lock = InstanceLock::acquire(temp.path()).expect("first lock should succeed");
+    assert!(daemon::is_running(temp.path()).unwrap());
+
+    let err = InstanceLock::acquire(temp.path())
+        .err()
+        .expect("second lock should fail while the first is held");
+    assert!(err.to_string().contains(&std::process::id().to_string()));
+
+    drop(lock);
+    assert!(!daemon::is_running(temp.path()).unwrap());
+    assert!(InstanceLock::acquire(temp.path()).is_ok());
+}
+
+#[test]
+fn test_pid_file_lifecycle() {
+    let temp = TempDir::new().unwrap();
+    assert_eq!(daemon::read_pid(temp.path()), None);
+
+    let lock = InstanceLock::acquire(temp.path()).unwrap();
+    assert_eq!(daemon::read_pid(temp.path()), Some(std::process::id()));
+
+    drop(lock);
+    assert_eq!(daemon::read_pid(temp.path()), None);
+}
+
+fn spec() -> ServiceSpec {
+    ServiceSpec {
+        executable: PathBuf::from("/opt/flow/bin/node"),
+        config_dir: PathBuf::from("/home/flow/.config/flow"),
+    }
+}
+
+#[test]
+fn test_render_systemd_unit() {
+    let unit = service::render(ServiceManager::Systemd, &spec()).unwrap();
+
+    assert!(unit.contains("ExecStart=\"/opt/flow/bin/node\"\n"));
+    assert!(unit.contains("WorkingDirectory=/home/flow/.config/flow\n"));
+    assert!(unit.contains("Environment=\"FLOW_CONFIG_HOME=/home/flow/.config/flow\""));
+    assert!(unit.contains("WantedBy=default.target"));
+}
+
+#[test]
+fn test_render_systemd_unit_escapes_paths() {
+    let mut spec = spec();
+    spec.executable = PathBuf::from("/opt/my \"flow\"/100%/node");
+    spec.config_dir = PathBuf::from("/home/flow/my config");
+    let unit = service::render(ServiceManager::Systemd, &spec).unwrap();
+
+    assert!(unit.contains(r#"ExecStart="/opt/my \"flow\"/100%%/node""#));
+    assert!(unit.contains("WorkingDirectory=/home/flow/my config\n"));
+    assert!(unit.contains(r#"Environment="FLOW_CONFIG_HOME=/home/flow/my config""#));
+
+    spec.config_dir = PathBuf::from("/home/flow\nExecStartPre=/bin/evil");
+    assert!(service::render(ServiceManager::Systemd, &spec).is_err());
+}
+
+#[test]
+fn test_render_launchd_plist() {
+    let mut spec = spec();
+    spec.config_dir = PathBuf::from("/opt/flow & <co>");
+    let plist = service::render(ServiceManager::Launchd, &spec).unwrap();
+
+    assert!(plist.contains(&format!("<string>{}</string>", service::LAUNCHD_LABEL)));
+    assert!(plist.contains("<string>/opt/flow/bin/node</string>\n    </array>"));
+    assert!(
+        plist.contains(
+            "<key>WorkingDirectory</key>\n    <string>/opt/flow &amp; &lt;co&gt;</string>"
+        )
+    );
+    assert!(plist.contains("<string>/opt/flow &amp; &lt;co&gt;/logs/node.log</string>"));
+}
+
+#[test]
+fn test_install_writes_unit_file() {
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("nested/flow-node.service");
+
+    service::install(ServiceManager::Systemd, &spec(), &path).unwrap();
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        written,
+        service::render(ServiceManager::Systemd, &spec()).unwrap()
+    );
+}
@@ -2,7 +2,13 @@ use axum::Router;
 use chrono::{DateTime, Utc};
 use node::api::servers::app_state::AppState;
 use node::api::servers::rest;
-use node::bootstrap::init::{AuthMetadata, initialize_config_dir};
+use node::bootstrap::config::{DbConfig, KeyStorageBackend, KvConfig};
+use node::bootstrap::init::{
+    AuthMetadata, IDENTITY_KEY, PENDING_KEY, initialize_config_dir, initialize_with,
+    rotate_identity_key,
+};
+use node::bootstrap::keystore::{FileKeyStorage, KeyStorage};
+use node::runner::{setup_database, setup_kv_store};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -34,6 +40,19 @@ pub async fn setup_test_server() -> TestServer {
     }
 }
 
+/// A multi-user test server and the session token of a signed-in user
+pub async fn setup_multi_user_server() -> (TestServer, String) {
+    let (mut node, temp) = setup_test_node().await;
+    node.multi_user = node::bootstrap::config::MultiUserConfig {
+        enabled: true,
+        ..Default::default()
+    };
+    let (token, _) = node.user_sessions().open(7).await.unwrap();
+    let router = rest::build_router(AppState::new(node.clone()));
+
+    (TestServer { router, node, temp }, token)
+}
+
 // Helper to create test Node
 pub async fn setup_test_node() -> (Node, TempDir) {
     setup_test_node_with_device_id("test-node--").await
@@ -43,28 +62,24 @@ pub async fn setup_test_node() -> (Node, TempDir) {
 pub async fn setup_test_node_with_device_id(device_id: &str) -> (Node, TempDir) {
     let temp_dir = TempDir::new().unwrap();
 
-    // Setup database
-    let db_path = temp_dir.path().join("test.db");
-    let db_url = format!("sqlite://{}?mode=rwc", db_path.display());
-    let db = Database::connect(&db_url).await.unwrap();
-    Migrator::up(&db, None).await.unwrap();
-
-    // Setup KV store
-    let kv_path = temp_dir.path().join("kv");
-    let kv = sled::open(&kv_path).unwrap();
+    // In-memory database and KV store, migrated on connect
+    let db = setup_database(&DbConfig::in_memory()).await.unwrap();
+    let kv = setup_kv_store(&KvConfig::in_memory()).await.unwrap();
 
     // Setup auth state
     let auth_config = node::modules::ssi::webauthn::state::AuthConfig {
         rp_id: "localhost".to_string(),
         rp_origin: "http://localhost:3000".to_string(),
         rp_name: "Test Flow".to_string(),
+        policy: Default::default(),
+        limits: Default::default(),
     };
     let auth_state = AuthState::new(auth_config).unwrap();
 
     // Create node data with custom device_id
     let node_data = NodeData {
         id: device_id.to_string(),
-        private_key: vec![0u8; 32],
+        private_key: vec![0u8; 32].into(),
         public_key: vec![0u8; 32],
     };
 
@@ -73,6 +88,18 @@ pub async fn setup_test_node_with_device_id(device_id: &str) -> (Node, TempDir)
     (node, temp_dir)
 }
 
+/// Give a test node a real identity: an Ed25519 key from `seed` and its
+/// did:key, as bootstrap would, for anything the node signs as its DID
+pub fn with_did_key(mut node: Node, seed: u8) -> Node {
+    let key = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+    node.node_data = NodeData {
+        id: compute_did_from_pubkey(key.verifying_key().as_bytes()),
+        private_key: key.to_bytes().to_vec().into(),
+        public_key: key.verifying_key().to_bytes().to_vec(),
+    };
+    node
+}
+
 /// Setup just a test database (no node) - useful for testing storage functions
 pub async fn setup_test_db() -> (DatabaseConnection, TempDir) {
     let temp_dir = TempDir::new().unwrap();
@@ -98,12 +125,14 @@ pub fn create_test_node_with_db(device_id: &str, db: DatabaseConnection, kv_path
         rp_id: "localhost".to_string(),
         rp_origin: "http://localhost:3000".to_string(),
         rp_name: "Test Flow".to_string(),
+        policy: Default::default(),
+        limits: Default::default(),
     };
     let auth_state = AuthState::new(auth_config).unwrap();
 
     let node_data = NodeData {
         id: device_id.to_string(),
-        private_key: vec![0u8; 32],
+        private_key: vec![0u8; 32].into(),
         public_key: vec![0u8; 32],
     };
 
@@ -422,3 +451,137 @@ async fn test_bootstrap_concurrent_initialization() -> Result<(), Box<dyn std::e
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_in_memory_stores_keep_state() {
+    use entity::space;
+    use sea_orm::{EntityTrait, PaginatorTrait};
+
+    let (node, temp) = setup_test_node().await;
+    let dir = temp.path().join("space");
+    node.create_space(&dir.to_string_lossy()).await.unwrap();
+
+    // The pool must keep the same in-memory database across queries
+    for _ in 0..3 {
+        assert_eq!(space::Entity::find().count(&node.db).await.unwrap(), 1);
+    }
+
+    node.kv.insert(b"key", b"value").unwrap();
+    assert_eq!(node.kv.get(b"key").unwrap().unwrap().as_ref(), b"value");
+}
+
+#[test]
+fn test_rotate_identity_key() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::TempDir::new()?;
+    let config_dir = tmp.path().join("flow-config");
+    let dir = config_dir.to_string_lossy();
+    let first = initialize_config_dir(&dir)?;
+
+    let (second, rotation) = rotate_identity_key(&dir)?;
+    assert_ne!(second.id, first.id);
+    assert_eq!(rotation.statement.previous_did, first.id);
+    assert_eq!(rotation.statement.next_did, second.id);
+    assert_eq!(rotation.statement.version, 2);
+    assert!(rotation.verify()?);
+    let (third, _) = rotate_identity_key(&dir)?;
+
+    // The current key is what bootstrap loads; the old ones are archived
+    assert_eq!(initialize_config_dir(&dir)?.id, third.id);
+    let keystore = config_dir.join("keystore");
+    assert_eq!(
+        fs::read(keystore.join("ed25519.v1.priv"))?,
+        first.private_key.to_vec()
+    );
+    assert_eq!(fs::read(keystore.join("ed25519.v1.pub"))?, first.public_key);
+    assert_eq!(
+        fs::read(keystore.join("ed25519.v2.pub"))?,
+        second.public_key
+    );
+
+    let meta: AuthMetadata = serde_json::from_slice(&fs::read(config_dir.join("auth.json"))?)?;
+    assert_eq!(meta.did, third.id);
+    let history: Vec<_> = meta
+        .key_history
+        .iter()
+        .map(|key| (key.version, key.did.as_str()))
+        .collect();
+    assert_eq!(history, [(1, first.id.as_str()), (2, second.id.as_str())]);
+    assert_eq!(meta.key_history[1].rotation.statement.next_did, third.id);
+
+    // Statements don't survive tampering
+    let mut forged = rotation.clone();
+    forged.statement.next_did = third.id.clone();
+    assert!(!forged.verify()?);
+
+    Ok(())
+}
+
+#[test]
+fn test_interrupted_rotation_leaves_a_consistent_identity() -> Result<(), Box<dyn std::error::Error>>
+{
+    let tmp = tempfile::TempDir::new()?;
+    let config_dir = tmp.path().join("flow-config");
+    let dir = config_dir.to_string_lossy();
+    let first = initialize_config_dir(&dir)?;
+    let storage = FileKeyStorage::new(config_dir.join("keystore"));
+    let auth_file = config_dir.join("auth.json");
+
+    // Stopped before auth.json named the new key: it is dropped
+    storage.store(PENDING_KEY, &[9u8; 32])?;
+    assert_eq!(initialize_config_dir(&dir)?.id, first.id);
+    assert!(storage.load(PENDING_KEY)?.is_none());
+
+    // Stopped after: the new key is put in place
+    let key = ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]).verifying_key();
+    let mut multicodec_key = vec![0xed, 0x01];
+    multicodec_key.extend_from_slice(key.as_bytes());
+    let did = format!(
+        "did:key:{}",
+        multibase::encode(multibase::Base::Base58Btc, &multicodec_key)
+    );
+    let mut meta: AuthMetadata = serde_json::from_slice(&fs::read(&auth_file)?)?;
+    meta.did = did.clone();
+    fs::write(&auth_file, serde_json::to_vec(&meta)?)?;
+    storage.store(PENDING_KEY, &[9u8; 32])?;
+
+    let second = initialize_config_dir(&dir)?;
+    assert_eq!(second.id, did);
+    assert_eq!(second.private_key.as_slice(), &[9u8; 32]);
+    assert_eq!(second.public_key, key.as_bytes());
+    assert!(storage.load(PENDING_KEY)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_key_storage() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::TempDir::new()?;
+    let config_dir = tmp.path().join("flow-config");
+    let dir = config_dir.to_string_lossy();
+    let node_data = initialize_with(&dir, KeyStorageBackend::File)?;
+
+    // New nodes record where their keys went
+    let meta: AuthMetadata = serde_json::from_slice(&fs::read(config_dir.join("auth.json"))?)?;
+    assert_eq!(meta.key_storage, KeyStorageBackend::File);
+
+    let storage = FileKeyStorage::new(config_dir.join("keystore"));
+    assert_eq!(
+        storage.load(IDENTITY_KEY)?.as_deref(),
+        Some(&*node_data.private_key)
+    );
+    storage.store("spare", b"secret")?;
+    assert_eq!(storage.load("spare")?.as_deref(), Some(&b"secret".to_vec()));
+    storage.delete("spare")?;
+    storage.delete("spare")?;
+    assert!(storage.load("spare")?.is_none());
+
+    // Without the keychain feature, asking for it fails and moves nothing
+    #[cfg(not(feature = "keychain"))]
+    {
+        assert!(initialize_with(&dir, KeyStorageBackend::Keychain).is_err());
+        assert_eq!(initialize_config_dir(&dir)?.id, node_data.id);
+        assert!(storage.load(IDENTITY_KEY)?.is_some());
+    }
+
+    Ok(())
+}
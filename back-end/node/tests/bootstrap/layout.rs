@@ -0,0 +1,110 @@
+use node::bootstrap::layout::{self, DataLayout};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn seed(layout: &DataLayout) {
+    let db = layout.db.as_ref().unwrap();
+    fs::create_dir_all(db.parent().unwrap()).unwrap();
+    fs::write(db, b"sqlite bytes").unwrap();
+
+    let kv = layout.kv.as_ref().unwrap();
+    fs::create_dir_all(kv.join("blobs")).unwrap();
+    fs::write(kv.join("conf"), b"sled conf").unwrap();
+    fs::write(kv.join("blobs/1"), b"blob").unwrap();
+}
+
+#[test]
+fn test_sqlite_path_parsing() {
+    assert_eq!(
+        layout::sqlite_path("sqlite:///db/data.sqlite?mode=rwc"),
+        Some(PathBuf::from("/db/data.sqlite"))
+    );
+    assert_eq!(
+        layout::sqlite_path("sqlite://test.db"),
+        Some(PathBuf::from("test.db"))
+    );
+    assert_eq!(layout::sqlite_path("sqlite::memory:"), None);
+    assert_eq!(layout::sqlite_path("sqlite://file?mode=memory"), None);
+    assert_eq!(layout::sqlite_path("postgres://localhost/flow"), None);
+}
+
+#[test]
+fn test_check_records_layout_on_first_start() {
+    let temp = TempDir::new().unwrap();
+    let config_dir = temp.path().join("config");
+    let data = DataLayout::under(&temp.path().join("data"));
+
+    layout::check(&config_dir, &data).unwrap();
+    assert_eq!(
+        layout::load_recorded(&config_dir).unwrap(),
+        Some(data.clone())
+    );
+
+    // Unchanged layout keeps passing
+    layout::check(&config_dir, &data).unwrap();
+}
+
+#[test]
+fn test_check_detects_mismatched_layout() {
+    let temp = TempDir::new().unwrap();
+    let config_dir = temp.path().join("config");
+    let old = DataLayout::under(&temp.path().join("old"));
+    seed(&old);
+    layout::check(&config_dir, &old).unwrap();
+
+    let new = DataLayout::under(&temp.path().join("new"));
+    let err = layout::check(&config_dir, &new).unwrap_err();
+    assert!(err.to_string().contains("Data layout mismatch"));
+    assert_eq!(layout::load_recorded(&config_dir).unwrap(), Some(old));
+}
+
+#[test]
+fn test_relocate_moves_and_records_data() {
+    let temp = TempDir::new().unwrap();
+    let config_dir = temp.path().join("config");
+    let old = DataLayout::under(&temp.path().join("old"));
+    seed(&old);
+    fs::write(
+        format!("{}-wal", old.db.as_ref().unwrap().display()),
+        b"wal",
+    )
+    .unwrap();
+    layout::check(&config_dir, &old).unwrap();
+
+    let new_root = temp.path().join("new");
+    let new = layout::relocate(&config_dir, &old, &new_root).unwrap();
+
+    assert_eq!(new, DataLayout::under(&new_root));
+    assert_eq!(
+        layout::load_recorded(&config_dir).unwrap(),
+        Some(new.clone())
+    );
+
+    let db = new.db.as_ref().unwrap();
+    assert_eq!(fs::read(db).unwrap(), b"sqlite bytes");
+    assert_eq!(fs::read(format!("{}-wal", db.display())).unwrap(), b"wal");
+    assert_eq!(
+        fs::read(new.kv.as_ref().unwrap().join("blobs/1")).unwrap(),
+        b"blob"
+    );
+
+    assert!(!old.db.as_ref().unwrap().exists());
+    assert!(!old.kv.as_ref().unwrap().exists());
+
+    // Starting against the new layout passes; the stale one is refused
+    layout::check(&config_dir, &new).unwrap();
+    assert!(layout::check(&config_dir, &DataLayout::under(Path::new("/nonexistent"))).is_err());
+}
+
+#[test]
+fn test_relocate_refuses_to_overwrite() {
+    let temp = TempDir::new().unwrap();
+    let old = DataLayout::under(&temp.path().join("old"));
+    seed(&old);
+    let new_root = temp.path().join("new");
+    seed(&DataLayout::under(&new_root));
+
+    assert!(layout::relocate(&temp.path().join("config"), &old, &new_root).is_err());
+    assert!(old.db.as_ref().unwrap().exists());
+}
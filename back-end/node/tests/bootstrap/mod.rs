@@ -1,2 +1,5 @@
 pub mod config;
+pub mod daemon;
 pub mod init;
+pub mod layout;
+pub mod profile;
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use node::bootstrap::init::initialize_config_dir;
+use node::bootstrap::profile::{self, Drift};
+use tempfile::TempDir;
+
+fn settings(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_export_signs_and_verifies() {
+    let home = TempDir::new().unwrap();
+    let node_data = initialize_config_dir(home.path().to_str().unwrap()).unwrap();
+
+    let signed = profile::export(
+        &node_data,
+        settings(&[("REST_PORT", "9000"), ("WEBAUTHN_RP_ID", "flow.example")]),
+        3,
+    )
+    .unwrap();
+    assert_eq!(signed.profile.version, 3);
+    assert_eq!(signed.profile.issuer, node_data.id);
+    profile::verify(&signed, None).unwrap();
+    profile::verify(&signed, Some(&signed.profile.public_key_multibase)).unwrap();
+
+    // Another issuer's key isn't trusted
+    let other = TempDir::new().unwrap();
+    let other = initialize_config_dir(other.path().to_str().unwrap()).unwrap();
+    let other_key = other.id.trim_start_matches("did:key:");
+    assert!(profile::verify(&signed, Some(other_key)).is_err());
+
+    let mut tampered = signed.clone();
+    tampered
+        .profile
+        .settings
+        .insert("REST_PORT".to_string(), "80".to_string());
+    assert!(profile::verify(&tampered, None).is_err());
+}
+
+#[test]
+fn test_secrets_are_never_exported() {
+    let home = TempDir::new().unwrap();
+    let node_data = initialize_config_dir(home.path().to_str().unwrap()).unwrap();
+
+    for rejected in [
+        settings(&[("DATABASE_URL", "postgres://user:secret@db/flow")]),
+        settings(&[("REST_PORT", "9000\nDATABASE_URL=sqlite://evil.db")]),
+    ] {
+        assert!(profile::export(&node_data, rejected, 1).is_err());
+    }
+}
+
+#[test]
+fn test_import_merges_env_file_and_records_baseline() {
+    let home = TempDir::new().unwrap();
+    let node_data = initialize_config_dir(home.path().to_str().unwrap()).unwrap();
+    let target = TempDir::new().unwrap();
+    let env_file = target.path().join(".env");
+    fs::write(
+        &env_file,
+        "# local settings\nDATABASE_URL=sqlite://flow.db\nREST_PORT=8080\n",
+    )
+    .unwrap();
+
+    let v2 = profile::export(
+        &node_data,
+        settings(&[("REST_PORT", "9000"), ("HOST", "127.0.0.1")]),
+        2,
+    )
+    .unwrap();
+    profile::import(&v2, None, &env_file, target.path()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&env_file).unwrap(),
+        "# local settings\nDATABASE_URL=sqlite://flow.db\nREST_PORT=9000\nHOST=127.0.0.1\n"
+    );
+    let baseline = profile::load_baseline(target.path()).unwrap().unwrap();
+    assert_eq!(baseline.profile, v2.profile);
+
+    // Older revisions from the same issuer are refused
+    let v1 = profile::export(&node_data, settings(&[("REST_PORT", "7000")]), 1).unwrap();
+    assert!(profile::import(&v1, None, &env_file, target.path()).is_err());
+    assert!(
+        fs::read_to_string(&env_file)
+            .unwrap()
+            .contains("REST_PORT=9000")
+    );
+}
+
+#[test]
+fn test_drift_against_baseline() {
+    let home = TempDir::new().unwrap();
+    let node_data = initialize_config_dir(home.path().to_str().unwrap()).unwrap();
+    let baseline = profile::export(
+        &node_data,
+        settings(&[("REST_PORT", "9000"), ("HOST", "127.0.0.1")]),
+        1,
+    )
+    .unwrap()
+    .profile;
+
+    assert!(
+        profile::drift(
+            &baseline,
+            &settings(&[("REST_PORT", "9000"), ("HOST", "127.0.0.1")])
+        )
+        .is_empty()
+    );
+
+    assert_eq!(
+        profile::drift(
+            &baseline,
+            &settings(&[("REST_PORT", "9001"), ("DB_LOGGING_ENABLED", "true")])
+        ),
+        vec![
+            Drift {
+                key: "HOST".to_string(),
+                expected: Some("127.0.0.1".to_string()),
+                actual: None,
+            },
+            Drift {
+                key: "REST_PORT".to_string(),
+                expected: Some("9000".to_string()),
+                actual: Some("9001".to_string()),
+            },
+            Drift {
+                key: "DB_LOGGING_ENABLED".to_string(),
+                expected: None,
+                actual: Some("true".to_string()),
+            },
+        ]
+    );
+}
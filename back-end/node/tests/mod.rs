@@ -1,6 +1,12 @@
 pub mod api;
+pub mod bench;
 pub mod bootstrap;
 pub mod modules;
+pub mod plugins;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "seed")]
+pub mod seed;
 pub mod util;
 
 #[cfg(test)]
@@ -0,0 +1,177 @@
+use crate::bootstrap::init::setup_test_node;
+use crate::modules::ssi::fixtures::load_es256_passkey;
+use crate::util::users::insert_user;
+use chrono::{Duration, Utc};
+use entity::{pass_key, user};
+use node::api::node::Node;
+use node::modules::account::{self, Deletion};
+use node::modules::key_usage::{KeyKind, KeyUse, Operation};
+use node::modules::ssi::webauthn::auth::store_passkey;
+use node::modules::ssi::webauthn::policy::CeremonyPolicy;
+use node::modules::tenancy::Actor;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use tempfile::TempDir;
+
+/// A node whose users' spaces go in `temp`
+async fn setup() -> (Node, TempDir) {
+    let (mut node, temp) = setup_test_node().await;
+    node.multi_user.spaces_root = Some(temp.path().join("spaces"));
+    (node, temp)
+}
+
+/// A user with a session, a space, a KV key and a key use
+async fn user_with_data(node: &Node, name: &str) -> (i32, String) {
+    let user = insert_user(&node.db, name).await;
+
+    let (token, session) = node.user_sessions().open(user.id).await.unwrap();
+    let actor = Actor::User(user.id);
+    node.create_space_as(actor, "home").await.unwrap();
+    node.kv_for(actor).unwrap().put("note", b"hello").unwrap();
+    node.key_usage()
+        .record(&KeyUse {
+            kind: KeyKind::Session,
+            key_id: session.id,
+            user_id: Some(user.id),
+            at: Utc::now(),
+            ip: None,
+            country: None,
+            operation: Operation::Read,
+        })
+        .unwrap();
+    (user.id, token)
+}
+
+#[tokio::test]
+async fn test_erase_removes_only_the_users_data() {
+    let (node, temp) = setup().await;
+    let (alice, alice_token) = user_with_data(&node, "alice").await;
+    let (bob, bob_token) = user_with_data(&node, "bob").await;
+    let (passkey, _) = load_es256_passkey();
+    store_passkey(
+        &node.db,
+        &node.column_keys,
+        alice,
+        &node.node_data.id,
+        &passkey,
+        &CeremonyPolicy::default(),
+    )
+    .await
+    .unwrap();
+
+    let export = node.export_account(alice).await.unwrap();
+    assert_eq!(export.user.did, "did:key:alice");
+    assert_eq!(export.passkeys.len(), 1);
+    assert_eq!(export.spaces.len(), 1);
+    assert_eq!(export.kv["note"], "aGVsbG8");
+    assert_eq!(export.key_usage.len(), 1);
+
+    let erasure = account::erase(&node, alice).await.unwrap();
+    assert_eq!(erasure.did.as_deref(), Some("did:key:alice"));
+    assert_eq!(
+        (
+            erasure.passkeys,
+            erasure.sessions,
+            erasure.spaces,
+            erasure.kv_keys
+        ),
+        (1, 1, 1, 1)
+    );
+    assert_eq!(erasure.key_usage, 2);
+    // Space files aren't the node's to delete
+    assert!(temp.path().join(format!("spaces/{}/home", alice)).exists());
+
+    assert!(
+        user::Entity::find_by_id(alice)
+            .one(&node.db)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(
+        pass_key::Entity::find()
+            .filter(pass_key::Column::UserId.eq(alice))
+            .count(&node.db)
+            .await
+            .unwrap(),
+        0
+    );
+    assert!(node.user_session(&alice_token).await.unwrap().is_none());
+    assert!(
+        node.list_spaces_as(Actor::User(alice))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        node.kv_for(Actor::User(alice))
+            .unwrap()
+            .keys()
+            .unwrap()
+            .is_empty()
+    );
+    assert!(node.key_usage().stats(Some(alice)).unwrap().is_empty());
+    assert!(node.export_account(alice).await.is_err());
+
+    // Bob keeps everything
+    assert!(node.user_session(&bob_token).await.unwrap().is_some());
+    assert_eq!(
+        node.list_spaces_as(Actor::User(bob)).await.unwrap().len(),
+        1
+    );
+    assert_eq!(
+        node.kv_for(Actor::User(bob)).unwrap().keys().unwrap(),
+        vec!["note"]
+    );
+    assert_eq!(node.key_usage().stats(Some(bob)).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_deletion_waits_out_its_grace() {
+    let (node, _temp) = setup().await;
+    let (alice, token) = user_with_data(&node, "alice").await;
+    let now = Utc::now();
+
+    let Deletion::Scheduled(pending) = account::request_deletion(&node, alice, now).await.unwrap()
+    else {
+        panic!("Deletion should wait out the grace");
+    };
+    assert_eq!(pending.erase_after, now + Duration::days(30));
+    // Asking again keeps the first request
+    let Deletion::Scheduled(again) =
+        account::request_deletion(&node, alice, now + Duration::days(1))
+            .await
+            .unwrap()
+    else {
+        panic!("Deletion should still be pending");
+    };
+    assert_eq!(again, pending);
+    assert!(account::erase_due(&node, now).await.unwrap().is_empty());
+    assert!(node.user_session(&token).await.unwrap().is_some());
+
+    let erased = account::erase_due(&node, now + Duration::days(31))
+        .await
+        .unwrap();
+    assert_eq!(erased.len(), 1);
+    assert_eq!(erased[0].user_id, alice);
+    assert!(node.user_session(&token).await.unwrap().is_none());
+    assert!(node.account_deletions().pending(alice).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_cancelled_deletion_is_not_erased() {
+    let (node, _temp) = setup().await;
+    let (alice, _) = user_with_data(&node, "alice").await;
+    let now = Utc::now();
+
+    account::request_deletion(&node, alice, now).await.unwrap();
+    assert!(node.account_deletions().cancel(alice).unwrap());
+    assert!(!node.account_deletions().cancel(alice).unwrap());
+
+    assert!(
+        account::erase_due(&node, now + Duration::days(31))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(node.export_account(alice).await.is_ok());
+}
@@ -0,0 +1,492 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use node::api::servers::tls;
+use node::bootstrap::config::{AcmeConfig, ServerConfig, TlsConfig};
+use node::modules::acme::client::AccountKey;
+use node::modules::acme::{AcmeManager, CertStore};
+use rcgen::{CertificateParams, Issuer, KeyPair, PublicKeyData, SignatureAlgorithm};
+use ring::signature::{ECDSA_P256_SHA256_FIXED, UnparsedPublicKey};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde_json::{Value, json};
+use tempfile::TempDir;
+use tokio_rustls::TlsConnector;
+use x509_parser::prelude::FromDer;
+
+const DOMAIN: &str = "node.example";
+
+fn self_signed(domain: &str) -> (String, String) {
+    let key_pair = KeyPair::generate().unwrap();
+    let cert = CertificateParams::new(vec![domain.to_string()])
+        .unwrap()
+        .self_signed(&key_pair)
+        .unwrap();
+    (cert.pem(), key_pair.serialize_pem())
+}
+
+/// Certificate `store` presents to `client`
+async fn presented_certificate(
+    store: &Arc<CertStore>,
+    client: ClientConfig,
+) -> CertificateDer<'static> {
+    let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+    let acceptor = tokio_rustls::TlsAcceptor::from(store.server_config().unwrap());
+    tokio::spawn(async move {
+        let _ = acceptor.accept(server_io).await;
+    });
+
+    let stream = TlsConnector::from(Arc::new(client))
+        .connect(ServerName::try_from(DOMAIN).unwrap(), client_io)
+        .await
+        .unwrap();
+    stream.get_ref().1.peer_certificates().unwrap()[0].clone()
+}
+
+fn trusting(pems: &[&str]) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    for pem in pems {
+        let der = rustls::pki_types::pem::PemObject::from_pem_slice(pem.as_bytes()).unwrap();
+        roots.add(der).unwrap();
+    }
+    ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
+#[test]
+fn test_account_key_signs_requests() {
+    let key = AccountKey::generate().unwrap();
+    let protected = json!({ "alg": "ES256", "nonce": "n1", "url": "https://ca.example/acct" });
+    let jws = key
+        .sign(&protected, Some(&json!({ "termsOfServiceAgreed": true })))
+        .unwrap();
+
+    let jwk = key.jwk();
+    let mut point = vec![0x04];
+    point.extend(URL_SAFE_NO_PAD.decode(jwk["x"].as_str().unwrap()).unwrap());
+    point.extend(URL_SAFE_NO_PAD.decode(jwk["y"].as_str().unwrap()).unwrap());
+    let signing_input = format!(
+        "{}.{}",
+        jws["protected"].as_str().unwrap(),
+        jws["payload"].as_str().unwrap()
+    );
+    let signature = URL_SAFE_NO_PAD
+        .decode(jws["signature"].as_str().unwrap())
+        .unwrap();
+    assert!(
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, &point)
+            .verify(signing_input.as_bytes(), &signature)
+            .is_ok()
+    );
+
+    // POST-as-GET has an empty payload
+    let jws = key.sign(&protected, None).unwrap();
+    assert_eq!(jws["payload"], "");
+
+    // SHA-256 digest, base64url without padding
+    assert_eq!(key.thumbprint().len(), 43);
+    assert_eq!(
+        key.key_authorization("token"),
+        format!("token.{}", key.thumbprint())
+    );
+    let restored = AccountKey::from_pkcs8(key.pkcs8()).unwrap();
+    assert_eq!(restored.thumbprint(), key.thumbprint());
+}
+
+#[tokio::test]
+async fn test_cert_store_swaps_certificates_in_place() {
+    let (first_cert, first_key) = self_signed(DOMAIN);
+    let (second_cert, second_key) = self_signed(DOMAIN);
+    let client = || trusting(&[&first_cert, &second_cert]);
+
+    let store = Arc::new(CertStore::default());
+    assert!(store.not_after().is_none());
+    store.install(&first_cert, &first_key).unwrap();
+    assert!(store.not_after().unwrap() > Utc::now());
+
+    let presented = presented_certificate(&store, client()).await;
+    let first_der: CertificateDer =
+        rustls::pki_types::pem::PemObject::from_pem_slice(first_cert.as_bytes()).unwrap();
+    assert_eq!(presented, first_der);
+
+    store.install(&second_cert, &second_key).unwrap();
+    let presented = presented_certificate(&store, client()).await;
+    assert_ne!(presented, first_der);
+
+    assert!(store.install("not a certificate", &second_key).is_err());
+}
+
+#[tokio::test]
+async fn test_self_signed_certificate_is_made_once() {
+    let home = TempDir::new().unwrap();
+    let server = ServerConfig {
+        rest_port: 8443,
+        websocket_port: 8444,
+        grpc_port: None,
+        p2p_port: None,
+        host: "0.0.0.0".to_string(),
+        bind_addresses: vec!["0.0.0.0".parse().unwrap()],
+        advertised_addresses: vec!["node.example:8443".to_string()],
+        allowed_origins: vec!["https://localhost:3000".to_string()],
+    };
+    let config = TlsConfig {
+        self_signed: true,
+        ..Default::default()
+    };
+    let server_config = tls::server_config(&config, home.path(), &server).unwrap();
+
+    let dir = home.path().join(tls::SELF_SIGNED_DIR);
+    let (cert_pem, _) = tls::self_signed(&dir, &[]).unwrap();
+    let der: CertificateDer =
+        rustls::pki_types::pem::PemObject::from_pem_slice(cert_pem.as_bytes()).unwrap();
+    let (_, cert) = x509_parser::parse_x509_certificate(&der).unwrap();
+    let names = cert
+        .subject_alternative_name()
+        .unwrap()
+        .unwrap()
+        .value
+        .general_names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    assert!(
+        names.contains(&"DNSName(localhost)".to_string()),
+        "{:?}",
+        names
+    );
+    assert!(
+        names.contains(&"DNSName(node.example)".to_string()),
+        "{:?}",
+        names
+    );
+    assert!(
+        !names.iter().any(|name| name.contains("0.0.0.0")),
+        "{:?}",
+        names
+    );
+
+    // The saved certificate is the one served, then and after a restart
+    let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+    let acceptor = tokio_rustls::TlsAcceptor::from(server_config);
+    tokio::spawn(async move {
+        let _ = acceptor.accept(server_io).await;
+    });
+    let stream = TlsConnector::from(Arc::new(trusting(&[&cert_pem])))
+        .connect(ServerName::try_from("node.example").unwrap(), client_io)
+        .await
+        .unwrap();
+    assert_eq!(stream.get_ref().1.peer_certificates().unwrap()[0], der);
+    tls::server_config(&config, home.path(), &server).unwrap();
+    assert_eq!(tls::self_signed(&dir, &[]).unwrap().0, cert_pem);
+}
+
+/// Accepts any server certificate; the test inspects it afterwards
+#[derive(Debug)]
+struct AcceptAny;
+
+impl ServerCertVerifier for AcceptAny {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[tokio::test]
+async fn test_tls_alpn_challenge_certificate() {
+    let store = Arc::new(CertStore::default());
+    store.add_challenge(DOMAIN, "token.thumbprint").unwrap();
+
+    let mut client =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAny))
+            .with_no_client_auth();
+    client.alpn_protocols = vec![b"acme-tls/1".to_vec()];
+
+    let presented = presented_certificate(&store, client).await;
+    let (_, cert) = x509_parser::parse_x509_certificate(&presented).unwrap();
+    // id-pe-acmeIdentifier holding the SHA-256 of the key authorization
+    let extension = cert
+        .extensions()
+        .iter()
+        .find(|e| e.oid.to_id_string() == "1.3.6.1.5.5.7.1.31")
+        .unwrap();
+    assert!(extension.critical);
+    assert!(
+        extension
+            .value
+            .ends_with(ring::digest::digest(&ring::digest::SHA256, b"token.thumbprint").as_ref())
+    );
+}
+
+/// Just enough of an ACME CA to issue one certificate over HTTP-01
+struct MockCa {
+    base: String,
+    challenge_url: String,
+    nonces: AtomicU64,
+    validated: AtomicBool,
+    issuer: Issuer<'static, KeyPair>,
+    issued: Mutex<Option<String>>,
+}
+
+impl MockCa {
+    fn reply(&self, status: StatusCode, location: Option<&str>, body: Value) -> Response {
+        let mut headers = HeaderMap::new();
+        let nonce = self.nonces.fetch_add(1, Ordering::SeqCst);
+        headers.insert(
+            "replay-nonce",
+            HeaderValue::from_str(&format!("n{}", nonce)).unwrap(),
+        );
+        if let Some(location) = location {
+            headers.insert(header::LOCATION, HeaderValue::from_str(location).unwrap());
+        }
+        (status, headers, axum::Json(body)).into_response()
+    }
+
+    fn order(&self) -> Value {
+        let issued = self.issued.lock().unwrap().is_some();
+        let status = match (issued, self.validated.load(Ordering::SeqCst)) {
+            (true, _) => "valid",
+            (false, true) => "ready",
+            _ => "pending",
+        };
+        json!({
+            "status": status,
+            "authorizations": [format!("{}/authz/1", self.base)],
+            "finalize": format!("{}/finalize", self.base),
+            "certificate": issued.then(|| format!("{}/cert", self.base)),
+        })
+    }
+}
+
+fn jws_payload(body: &str) -> Value {
+    let jws: Value = serde_json::from_str(body).unwrap();
+    let payload = URL_SAFE_NO_PAD
+        .decode(jws["payload"].as_str().unwrap())
+        .unwrap();
+    serde_json::from_slice(&payload).unwrap_or(Value::Null)
+}
+
+struct CsrKey(Vec<u8>);
+
+impl PublicKeyData for CsrKey {
+    fn der_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn algorithm(&self) -> &'static SignatureAlgorithm {
+        &rcgen::PKCS_ECDSA_P256_SHA256
+    }
+}
+
+fn mock_ca_router(ca: Arc<MockCa>) -> Router {
+    Router::new()
+        .route(
+            "/directory",
+            get(|State(ca): State<Arc<MockCa>>| async move {
+                axum::Json(json!({
+                    "newNonce": format!("{}/nonce", ca.base),
+                    "newAccount": format!("{}/account", ca.base),
+                    "newOrder": format!("{}/order", ca.base),
+                }))
+            }),
+        )
+        .route(
+            "/nonce",
+            get(|State(ca): State<Arc<MockCa>>| async move {
+                ca.reply(StatusCode::OK, None, Value::Null)
+            }),
+        )
+        .route(
+            "/account",
+            post(|State(ca): State<Arc<MockCa>>| async move {
+                let location = format!("{}/account/1", ca.base);
+                ca.reply(
+                    StatusCode::CREATED,
+                    Some(&location),
+                    json!({ "status": "valid" }),
+                )
+            }),
+        )
+        .route(
+            "/order",
+            post(|State(ca): State<Arc<MockCa>>| async move {
+                let location = format!("{}/order/1", ca.base);
+                ca.reply(StatusCode::CREATED, Some(&location), ca.order())
+            }),
+        )
+        .route(
+            "/order/1",
+            post(|State(ca): State<Arc<MockCa>>| async move {
+                ca.reply(StatusCode::OK, None, ca.order())
+            }),
+        )
+        .route(
+            "/authz/1",
+            post(|State(ca): State<Arc<MockCa>>| async move {
+                let status = if ca.validated.load(Ordering::SeqCst) {
+                    "valid"
+                } else {
+                    "pending"
+                };
+                let body = json!({
+                    "status": status,
+                    "identifier": { "type": "dns", "value": DOMAIN },
+                    "challenges": [{
+                        "type": "http-01",
+                        "url": format!("{}/chall/1", ca.base),
+                        "token": "tok",
+                        "status": status,
+                    }],
+                });
+                ca.reply(StatusCode::OK, None, body)
+            }),
+        )
+        .route(
+            "/chall/1",
+            post(|State(ca): State<Arc<MockCa>>| async move {
+                // Validate the way a CA would: fetch the key authorization
+                let answer = reqwest::get(&ca.challenge_url)
+                    .await
+                    .unwrap()
+                    .text()
+                    .await
+                    .unwrap();
+                ca.validated.store(
+                    answer.starts_with("tok.") && answer.len() == 47,
+                    Ordering::SeqCst,
+                );
+                ca.reply(StatusCode::OK, None, json!({ "status": "processing" }))
+            }),
+        )
+        .route(
+            "/finalize",
+            post(|State(ca): State<Arc<MockCa>>, body: String| async move {
+                let csr = URL_SAFE_NO_PAD
+                    .decode(jws_payload(&body)["csr"].as_str().unwrap())
+                    .unwrap();
+                let (_, request) =
+                    x509_parser::certification_request::X509CertificationRequest::from_der(&csr)
+                        .unwrap();
+                let key = CsrKey(
+                    request
+                        .certification_request_info
+                        .subject_pki
+                        .subject_public_key
+                        .data
+                        .to_vec(),
+                );
+                let cert = CertificateParams::new(vec![DOMAIN.to_string()])
+                    .unwrap()
+                    .signed_by(&key, &ca.issuer)
+                    .unwrap();
+                *ca.issued.lock().unwrap() = Some(cert.pem());
+                ca.reply(StatusCode::OK, None, ca.order())
+            }),
+        )
+        .route(
+            "/cert",
+            post(|State(ca): State<Arc<MockCa>>| async move {
+                ca.issued.lock().unwrap().clone().unwrap_or_default()
+            }),
+        )
+        .with_state(ca)
+}
+
+#[tokio::test]
+async fn test_issue_against_acme_server() {
+    let config_dir = TempDir::new().unwrap();
+
+    let challenge_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let challenge_addr = challenge_listener.local_addr().unwrap();
+    let ca_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", ca_listener.local_addr().unwrap());
+
+    let ca_key = KeyPair::generate().unwrap();
+    let ca_params = CertificateParams::new(vec!["mock-ca.example".to_string()]).unwrap();
+    let ca = Arc::new(MockCa {
+        base: base.clone(),
+        challenge_url: format!("http://{}/.well-known/acme-challenge/tok", challenge_addr),
+        nonces: AtomicU64::new(0),
+        validated: AtomicBool::new(false),
+        issuer: Issuer::new(ca_params, ca_key),
+        issued: Mutex::new(None),
+    });
+    tokio::spawn(axum::serve(ca_listener, mock_ca_router(ca.clone())).into_future());
+
+    let config = AcmeConfig {
+        domains: vec![DOMAIN.to_string()],
+        contact: Some("ops@node.example".to_string()),
+        directory_url: format!("{}/directory", base),
+        ..AcmeConfig::default()
+    };
+    let manager = AcmeManager::new(config.clone(), config_dir.path()).unwrap();
+    assert!(manager.renewal_due(Utc::now()));
+    tokio::spawn(tls::serve_challenges(
+        vec![challenge_listener],
+        manager.http01(),
+    ));
+
+    tokio::time::timeout(Duration::from_secs(30), manager.issue())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(ca.validated.load(Ordering::SeqCst));
+    assert!(!manager.renewal_due(Utc::now()));
+    // The answered challenge is withdrawn
+    assert!(manager.http01().get("tok").is_none());
+
+    let acme_dir = config_dir.path().join("acme");
+    assert!(acme_dir.join("cert.pem").exists());
+    assert!(acme_dir.join("key.pem").exists());
+    assert!(acme_dir.join("account.pk8").exists());
+
+    // A restart serves the saved certificate without asking again
+    let restarted = AcmeManager::new(config, config_dir.path()).unwrap();
+    assert_eq!(restarted.store().not_after(), manager.store().not_after());
+}
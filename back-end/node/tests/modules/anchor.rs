@@ -0,0 +1,132 @@
+use crate::bootstrap::init::{setup_test_node, with_did_key};
+use node::bootstrap::config::TimestampConfig;
+use node::modules::anchor::{ANCHOR_TYPE, AnchorLog};
+use node::modules::scheduler::{NewTask, RunStatus, TaskAction};
+use node::modules::ssi::vc;
+use node::modules::timestamp::TimestampClient;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_anchor_space_attests_merkle_root() {
+    let (node, _temp) = setup_test_node().await;
+    let node = with_did_key(node, 9);
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+
+    let first = node.anchor_space(&key).await.unwrap();
+    let space = &node.list_spaces().await.unwrap()[0];
+    assert_eq!(space.merkle_root.as_ref(), Some(&first.merkle_root));
+
+    // Self-issued, naming the space and its root
+    let credential = &first.credential.credential;
+    assert_eq!(credential.issuer, node.node_data.id);
+    assert_eq!(credential.subject(), Some(node.node_data.id.as_str()));
+    assert!(credential.types.iter().any(|t| t == ANCHOR_TYPE));
+    assert_eq!(credential.credential_subject["space"]["key"], key.as_str());
+    assert_eq!(
+        credential.credential_subject["space"]["merkleRoot"],
+        first.merkle_root.as_str()
+    );
+    vc::verify_credential(credential).unwrap();
+    vc::verify_jwt(&first.credential.jwt).unwrap();
+
+    std::fs::write(dir.path().join("b.txt"), "world").unwrap();
+    let second = node.anchor_space(&key).await.unwrap();
+    assert_ne!(second.merkle_root, first.merkle_root);
+
+    let anchors = node.space_anchors(&key).await.unwrap();
+    let roots: Vec<_> = anchors.iter().map(|a| a.merkle_root.as_str()).collect();
+    assert_eq!(roots, vec![&second.merkle_root, &first.merkle_root]);
+    assert_eq!(
+        AnchorLog::open(&node.kv, &key)
+            .unwrap()
+            .latest()
+            .unwrap()
+            .unwrap()
+            .merkle_root,
+        second.merkle_root
+    );
+}
+
+#[tokio::test]
+async fn test_scheduled_anchor() {
+    let (node, _temp) = setup_test_node().await;
+    let node = with_did_key(node, 9);
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    let scheduler = node.scheduler();
+
+    let task = scheduler
+        .create(NewTask {
+            name: "anchor".to_string(),
+            schedule: "@daily".to_string(),
+            action: TaskAction::AnchorSpace {
+                space_key: key.clone(),
+            },
+            enabled: true,
+        })
+        .await
+        .unwrap();
+    let run = scheduler.run_now(&task.id).await.unwrap();
+    assert_eq!(run.status, RunStatus::Succeeded, "{:?}", run.error);
+
+    let anchors = node.space_anchors(&key).await.unwrap();
+    assert_eq!(anchors.len(), 1);
+    let output = run.output.unwrap();
+    assert_eq!(output["merkleRoot"], anchors[0].merkle_root.as_str());
+    assert_eq!(
+        output["credential"],
+        anchors[0].credential.credential.id.as_str()
+    );
+
+    assert!(
+        scheduler
+            .create(NewTask {
+                name: "anchor".to_string(),
+                schedule: "@daily".to_string(),
+                action: TaskAction::AnchorSpace {
+                    space_key: "missing".to_string(),
+                },
+                enabled: true,
+            })
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_anchor_survives_unreachable_timestamp_authority() {
+    let (node, _temp) = setup_test_node().await;
+    let mut node = with_did_key(node, 9);
+    // Nothing listens on the port once the listener is dropped
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/tsa", listener.local_addr().unwrap());
+    drop(listener);
+    node.timestamps = Some(
+        TimestampClient::new(&TimestampConfig {
+            authority_url: Some(url),
+            trusted_certificates: vec![[0; 32]],
+            ..TimestampConfig::default()
+        })
+        .unwrap(),
+    );
+
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+
+    let anchor = node.anchor_space(&key).await.unwrap();
+    assert!(anchor.timestamp.is_none());
+    assert!(node.timestamp(&[0; 32]).await.is_err());
+}
@@ -0,0 +1,81 @@
+use errors::AppError;
+use node::bootstrap::init::initialize_config_dir;
+use node::modules::attestation::{NodeAttestation, attest, verify_attestation};
+use std::time::Duration;
+use tempfile::TempDir;
+
+const CHALLENGE: &str = "b1f0c7a2-5d3e-4e8a-9c61-2f7d4a0e9b13";
+const MAX_AGE: Duration = Duration::from_secs(60);
+
+fn attested(audience: Option<&str>) -> (NodeAttestation, String, TempDir) {
+    let temp = TempDir::new().unwrap();
+    let node_data = initialize_config_dir(&temp.path().to_string_lossy()).unwrap();
+    let attestation = attest(&node_data, CHALLENGE, audience).unwrap();
+    (attestation, node_data.id, temp)
+}
+
+#[test]
+fn test_attestation_verifies() {
+    let (attestation, did, _temp) = attested(Some("https://peer.example"));
+
+    let verified = verify_attestation(
+        &attestation,
+        CHALLENGE,
+        Some("https://peer.example"),
+        MAX_AGE,
+    )
+    .unwrap();
+    assert_eq!(verified, did);
+}
+
+#[test]
+fn test_attestation_bound_to_challenge_and_audience() {
+    let (attestation, _, _temp) = attested(Some("https://peer.example"));
+
+    assert!(matches!(
+        verify_attestation(
+            &attestation,
+            "another-challenge-entirely",
+            Some("https://peer.example"),
+            MAX_AGE
+        ),
+        Err(AppError::Auth(_))
+    ));
+    assert!(matches!(
+        verify_attestation(
+            &attestation,
+            CHALLENGE,
+            Some("https://other.example"),
+            MAX_AGE
+        ),
+        Err(AppError::Auth(_))
+    ));
+}
+
+#[test]
+fn test_tampered_attestation_is_rejected() {
+    let (mut attestation, _, _temp) = attested(None);
+    attestation.statement.software_version = "99.0.0".to_string();
+
+    assert!(matches!(
+        verify_attestation(&attestation, CHALLENGE, None, MAX_AGE),
+        Err(AppError::Auth(_))
+    ));
+
+    // A key that isn't the DID's can't vouch for it
+    let (other, _, _other_temp) = attested(None);
+    let (mut attestation, _, _temp) = attested(None);
+    attestation.statement.public_key_multibase = other.statement.public_key_multibase;
+    assert!(verify_attestation(&attestation, CHALLENGE, None, MAX_AGE).is_err());
+}
+
+#[test]
+fn test_short_challenge_is_rejected() {
+    let temp = TempDir::new().unwrap();
+    let node_data = initialize_config_dir(&temp.path().to_string_lossy()).unwrap();
+
+    assert!(matches!(
+        attest(&node_data, "short", None),
+        Err(AppError::Validation(_))
+    ));
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use node::modules::clock::{self, Clock, TestClock, TimeSource};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const NTP_EPOCH_OFFSET: i64 = 2_208_988_800;
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap()
+}
+
+#[test]
+fn test_time_source_allows_skew() {
+    let clock = TestClock::at(at(1_000));
+    let time = TimeSource::new(Arc::new(clock.clone()), Duration::from_secs(30));
+
+    assert!(!time.has_passed(at(1_000)));
+    assert!(!time.has_passed(at(971)));
+    assert!(time.has_passed(at(970)));
+    assert!(!time.is_ahead(at(1_030)));
+    assert!(time.is_ahead(at(1_031)));
+
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(time.now(), at(1_060));
+    assert!(time.has_passed(at(1_000)));
+
+    // Without skew, times are taken as they are
+    let exact = TimeSource::new(Arc::new(clock), Duration::ZERO);
+    assert!(exact.has_passed(at(1_060)));
+    assert!(exact.is_ahead(at(1_061)));
+}
+
+#[tokio::test]
+async fn test_ntp_offset() {
+    // A server whose clock is 90s ahead of the node's
+    let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = server.local_addr().unwrap().to_string();
+    let node_clock = TestClock::at(at(1_700_000_000));
+    let server_time = node_clock.now().timestamp() + 90 + NTP_EPOCH_OFFSET;
+    tokio::spawn(async move {
+        let mut packet = [0u8; 48];
+        let (_, client) = server.recv_from(&mut packet).await.unwrap();
+        packet[0] = 0b00_011_100;
+        for at in [32, 40] {
+            packet[at..at + 4].copy_from_slice(&(server_time as u32).to_be_bytes());
+        }
+        server.send_to(&packet, client).await.unwrap();
+    });
+
+    let offset = clock::ntp_offset(&address, &node_clock, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(offset.num_seconds(), 90);
+}
+
+#[tokio::test]
+async fn test_ntp_offset_times_out() {
+    let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = silent.local_addr().unwrap().to_string();
+
+    let result =
+        clock::ntp_offset(&address, &TestClock::at(at(0)), Duration::from_millis(50)).await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,129 @@
+use crate::bootstrap::init::setup_test_db;
+use crate::modules::ssi::fixtures::load_es256_passkey;
+use crate::util::users::insert_user;
+use entity::cipher::ColumnCipher;
+use entity::pass_key;
+use node::modules::column_crypto::{self, ColumnKeys, KEYS_FILE, key_id_of};
+use node::modules::ssi::webauthn::auth::{get_passkeys_for_device, store_passkey};
+use node::modules::ssi::webauthn::policy::CeremonyPolicy;
+use sea_orm::EntityTrait;
+use tempfile::TempDir;
+
+const COLUMN: &str = pass_key::JSON_DATA_COLUMN;
+
+#[test]
+fn test_round_trip() {
+    let keys = ColumnKeys::from_identity(&[1u8; 32]);
+
+    let sealed = keys.encrypt(COLUMN, 1, "secret").unwrap();
+    assert!(sealed.starts_with(column_crypto::ENCRYPTED_PREFIX));
+    assert!(!sealed.contains("secret"));
+    assert_eq!(key_id_of(&sealed), Some("k0"));
+
+    assert_eq!(keys.decrypt(COLUMN, 1, &sealed).unwrap(), "secret");
+    // Fresh nonce every time
+    assert_ne!(sealed, keys.encrypt(COLUMN, 1, "secret").unwrap());
+}
+
+#[test]
+fn test_values_are_bound_to_owner_and_column() {
+    let keys = ColumnKeys::from_identity(&[1u8; 32]);
+    let sealed = keys.encrypt(COLUMN, 1, "secret").unwrap();
+
+    assert!(keys.decrypt(COLUMN, 2, &sealed).is_err());
+    assert!(keys.decrypt("other.column", 1, &sealed).is_err());
+    // A different node identity can't read it either
+    assert!(
+        ColumnKeys::from_identity(&[2u8; 32])
+            .decrypt(COLUMN, 1, &sealed)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_plaintext_passes_through_and_tampering_fails() {
+    let keys = ColumnKeys::from_identity(&[1u8; 32]);
+    assert_eq!(
+        keys.decrypt(COLUMN, 1, "{\"legacy\":1}").unwrap(),
+        "{\"legacy\":1}"
+    );
+    assert_eq!(key_id_of("{\"legacy\":1}"), None);
+
+    let sealed = keys.encrypt(COLUMN, 1, "secret").unwrap();
+    let mut tampered = sealed.into_bytes();
+    let last = tampered.len() - 1;
+    tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+    let tampered = String::from_utf8(tampered).unwrap();
+    assert!(keys.decrypt(COLUMN, 1, &tampered).is_err());
+
+    assert!(keys.decrypt(COLUMN, 1, "enc:v1:k9:AAAA").is_err());
+}
+
+#[test]
+fn test_rotation_persists_and_keeps_old_keys() {
+    let keystore = TempDir::new().unwrap();
+    let identity = [1u8; 32];
+
+    let keys = ColumnKeys::load(keystore.path(), &identity).unwrap();
+    assert_eq!(keys.active_key_id(), "k0");
+    let old = keys.encrypt(COLUMN, 1, "before").unwrap();
+
+    let rotated = keys.rotate(keystore.path()).unwrap();
+    assert_eq!(rotated.active_key_id(), "k1");
+    assert!(keystore.path().join(KEYS_FILE).exists());
+    assert!(!rotated.is_current(&old));
+
+    // Reloading sees the rotated key and can still read old values
+    let reloaded = ColumnKeys::load(keystore.path(), &identity).unwrap();
+    assert_eq!(reloaded.active_key_id(), "k1");
+    assert_eq!(reloaded.decrypt(COLUMN, 1, &old).unwrap(), "before");
+
+    let new = rotated.encrypt(COLUMN, 1, "after").unwrap();
+    assert_eq!(reloaded.decrypt(COLUMN, 1, &new).unwrap(), "after");
+}
+
+#[tokio::test]
+async fn test_reencrypt_pass_keys() {
+    let (db, _temp) = setup_test_db().await;
+    let keystore = TempDir::new().unwrap();
+    let keys = ColumnKeys::load(keystore.path(), &[1u8; 32]).unwrap();
+
+    let user = insert_user(&db, "crypto").await;
+
+    let (passkey, _) = load_es256_passkey();
+    store_passkey(
+        &db,
+        &keys,
+        user.id,
+        "device-crypto-1",
+        &passkey,
+        &CeremonyPolicy::default(),
+    )
+    .await
+    .unwrap();
+
+    // Nothing to do while every row is on the active key
+    assert_eq!(
+        column_crypto::reencrypt_pass_keys(&db, &keys)
+            .await
+            .unwrap(),
+        0
+    );
+
+    let rotated = keys.rotate(keystore.path()).unwrap();
+    assert_eq!(
+        column_crypto::reencrypt_pass_keys(&db, &rotated)
+            .await
+            .unwrap(),
+        1
+    );
+
+    let row = pass_key::Entity::find().one(&db).await.unwrap().unwrap();
+    assert_eq!(key_id_of(&row.json_data), Some("k1"));
+
+    let passkeys = get_passkeys_for_device(&db, &rotated, "device-crypto-1")
+        .await
+        .unwrap();
+    assert_eq!(passkeys.len(), 1);
+    assert_eq!(passkeys[0].cred_id(), passkey.cred_id());
+}
@@ -0,0 +1,77 @@
+use crate::bootstrap::init::setup_test_node;
+use node::bootstrap::config::KeyStorageBackend;
+use node::bootstrap::init::{IDENTITY_KEY, initialize_config_dir};
+use node::bootstrap::keystore;
+use node::modules::health::{self, ComponentStatus};
+use sea_orm::Database;
+
+#[tokio::test]
+async fn test_ready_when_everything_answers() {
+    let (node, _temp) = setup_test_node().await;
+    let readiness = health::readiness(&node).await;
+
+    assert!(readiness.ready, "{:?}", readiness);
+    assert!(!readiness.shutting_down);
+    assert_eq!(
+        readiness.components.keys().copied().collect::<Vec<_>>(),
+        ["database", "keystore", "kv", "migrations"]
+    );
+    assert!(
+        readiness
+            .components
+            .values()
+            .all(|c| c.status == ComponentStatus::Up)
+    );
+    assert_eq!(
+        readiness.components["keystore"].detail.as_deref(),
+        Some("Identity key held in memory")
+    );
+}
+
+#[tokio::test]
+async fn test_pending_migrations_hold_readiness() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.db = Database::connect("sqlite::memory:").await.unwrap();
+
+    let readiness = health::readiness(&node).await;
+    assert!(!readiness.ready);
+    assert_eq!(readiness.components["database"].status, ComponentStatus::Up);
+    let migrations = &readiness.components["migrations"];
+    assert_eq!(migrations.status, ComponentStatus::Down);
+    assert!(
+        migrations
+            .detail
+            .as_deref()
+            .unwrap()
+            .contains("Pending migrations: m20250811_140008_create_space"),
+        "{:?}",
+        migrations
+    );
+}
+
+#[tokio::test]
+async fn test_missing_identity_key_holds_readiness() {
+    let (mut node, temp) = setup_test_node().await;
+    let home = temp.path().join("flow-config");
+    node.node_data = initialize_config_dir(&home.to_string_lossy()).unwrap();
+    node.home = Some(home.clone());
+    assert!(health::readiness(&node).await.ready);
+
+    keystore::open(KeyStorageBackend::File, &home)
+        .unwrap()
+        .delete(IDENTITY_KEY)
+        .unwrap();
+    let readiness = health::readiness(&node).await;
+    assert!(!readiness.ready);
+    let keystore = &readiness.components["keystore"];
+    assert_eq!(keystore.status, ComponentStatus::Down);
+    assert!(
+        keystore
+            .detail
+            .as_deref()
+            .unwrap()
+            .contains("Private key missing"),
+        "{:?}",
+        keystore
+    );
+}
@@ -0,0 +1,141 @@
+use crate::bootstrap::init::setup_test_node;
+use chrono::{DateTime, Duration, Utc};
+use node::bootstrap::config::KeyUsageConfig;
+use node::modules::key_usage::{Anomaly, KeyKind, KeyUse, MAX_USES_PER_KEY, Operation};
+
+fn key_use(at: DateTime<Utc>, country: Option<&str>) -> KeyUse {
+    KeyUse {
+        kind: KeyKind::Session,
+        key_id: "session-1".to_string(),
+        user_id: Some(7),
+        at,
+        ip: Some("203.0.113.7".parse().unwrap()),
+        country: country.map(str::to_string),
+        operation: Operation::Read,
+    }
+}
+
+#[tokio::test]
+async fn test_record_keeps_stats_and_history() {
+    let (node, _temp) = setup_test_node().await;
+    let usage = node.key_usage();
+    let start = Utc::now() - Duration::days(1);
+
+    assert!(
+        usage
+            .record(&key_use(start, Some("NL")))
+            .unwrap()
+            .is_empty()
+    );
+    let mut write = key_use(start + Duration::hours(1), Some("NL"));
+    write.operation = Operation::Write;
+    usage.record(&write).unwrap();
+
+    let stats = usage
+        .key_stats(KeyKind::Session, "session-1")
+        .unwrap()
+        .unwrap();
+    assert_eq!(stats.uses, 2);
+    assert_eq!(stats.user_id, Some(7));
+    assert_eq!(stats.first_used, start);
+    assert_eq!(stats.last_used, write.at);
+    assert_eq!(stats.countries, vec!["NL".to_string()]);
+    assert_eq!(stats.operations[&Operation::Read], 1);
+    assert_eq!(stats.operations[&Operation::Write], 1);
+
+    assert_eq!(usage.stats(Some(7)).unwrap().len(), 1);
+    assert!(usage.stats(Some(8)).unwrap().is_empty());
+    assert!(
+        usage
+            .key_stats(KeyKind::Passkey, "session-1")
+            .unwrap()
+            .is_none()
+    );
+
+    // History is trimmed, newest first; the stats keep counting
+    for i in 0..MAX_USES_PER_KEY as i64 {
+        usage
+            .record(&key_use(start + Duration::minutes(90 + i), Some("NL")))
+            .unwrap();
+    }
+    let history = usage.history(KeyKind::Session, "session-1").unwrap();
+    assert_eq!(history.len(), MAX_USES_PER_KEY);
+    assert!(history[0].at > history[1].at);
+    assert_eq!(
+        usage
+            .key_stats(KeyKind::Session, "session-1")
+            .unwrap()
+            .unwrap()
+            .uses,
+        MAX_USES_PER_KEY as u64 + 2
+    );
+}
+
+#[tokio::test]
+async fn test_anomalies() {
+    let (node, _temp) = setup_test_node().await;
+    let usage = node.key_usage();
+    let start = Utc::now() - Duration::days(200);
+
+    usage.record(&key_use(start, Some("NL"))).unwrap();
+    // Unknown countries are neither new nor travel
+    assert!(
+        usage
+            .record(&key_use(start + Duration::minutes(5), None))
+            .unwrap()
+            .is_empty()
+    );
+
+    let anomalies = usage
+        .record(&key_use(start + Duration::minutes(30), Some("JP")))
+        .unwrap();
+    assert_eq!(
+        anomalies,
+        vec![
+            Anomaly::NewCountry {
+                country: "JP".to_string()
+            },
+            Anomaly::ImpossibleTravel {
+                from: "NL".to_string(),
+                to: "JP".to_string(),
+                minutes: 25
+            },
+        ]
+    );
+
+    // Back home long after: dormant, but no longer a new country or travel
+    let anomalies = usage
+        .record(&key_use(start + Duration::days(100), Some("NL")))
+        .unwrap();
+    assert!(matches!(
+        anomalies.as_slice(),
+        [Anomaly::DormantKeyActive { idle_days: 99 }]
+    ));
+}
+
+#[tokio::test]
+async fn test_anomaly_thresholds_are_configurable() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.key_usage = KeyUsageConfig {
+        dormant_after: std::time::Duration::from_secs(3600),
+        travel_window: std::time::Duration::ZERO,
+        step_up: false,
+    };
+    let usage = node.key_usage();
+    let start = Utc::now() - Duration::days(1);
+
+    usage.record(&key_use(start, Some("NL"))).unwrap();
+    let anomalies = usage
+        .record(&key_use(start + Duration::hours(2), Some("NL")))
+        .unwrap();
+    assert_eq!(anomalies, vec![Anomaly::DormantKeyActive { idle_days: 0 }]);
+    let anomalies = usage
+        .record(&key_use(start + Duration::hours(2), Some("BE")))
+        .unwrap();
+    assert_eq!(
+        anomalies,
+        vec![Anomaly::NewCountry {
+            country: "BE".to_string()
+        }]
+    );
+}
@@ -0,0 +1,37 @@
+use errors::AppError;
+use node::bootstrap::config::MaintenanceConfig;
+use node::modules::maintenance::Maintenance;
+
+#[test]
+fn test_toggle_is_shared_by_clones() {
+    let maintenance = Maintenance::new(&MaintenanceConfig::default());
+    let clone = maintenance.clone();
+    assert!(maintenance.check().is_ok());
+
+    maintenance.enable("restore");
+    maintenance.enable("second reason");
+    let window = clone.window().unwrap();
+    assert_eq!(window.reason, "restore");
+    assert!(matches!(clone.check(), Err(AppError::Maintenance(_))));
+
+    assert!(clone.disable());
+    assert!(!maintenance.is_active());
+    assert!(!maintenance.disable());
+}
+
+#[test]
+fn test_guard_holds_maintenance_for_one_operation() {
+    let maintenance = Maintenance::new(&MaintenanceConfig::default());
+
+    let guard = maintenance.begin("migration").unwrap();
+    assert!(maintenance.is_active());
+    // A second operation doesn't end the first one's window
+    assert!(matches!(
+        maintenance.begin("backup"),
+        Err(AppError::Maintenance(_))
+    ));
+    assert!(maintenance.is_active());
+
+    drop(guard);
+    assert!(maintenance.check().is_ok());
+}
@@ -0,0 +1,125 @@
+use node::modules::manifest::{MANIFEST_SCHEMA, Manifest, ManifestEntry};
+use node::modules::manifest_summary::{self, BloomFilter};
+
+fn entry(path: &str, contents: &[u8]) -> ManifestEntry {
+    ManifestEntry {
+        path: path.to_string(),
+        blake3: blake3::hash(contents).to_hex().to_string(),
+        size: contents.len() as u64,
+    }
+}
+
+fn manifest(mut entries: Vec<ManifestEntry>) -> Manifest {
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Manifest {
+        schema: MANIFEST_SCHEMA.to_string(),
+        space_key: "ab".repeat(32),
+        path: String::new(),
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        signer: "did:peer:0test".to_string(),
+        public_key_multibase: String::new(),
+        entries,
+    }
+}
+
+#[test]
+fn test_bloom_filter_has_no_false_negatives() {
+    let mut filter = BloomFilter::with_capacity(1000);
+    for i in 0..1000 {
+        filter.insert(format!("item-{}", i).as_bytes());
+    }
+
+    assert!((0..1000).all(|i| filter.contains(format!("item-{}", i).as_bytes())));
+
+    let false_positives = (0..1000)
+        .filter(|i| filter.contains(format!("other-{}", i).as_bytes()))
+        .count();
+    assert!(
+        false_positives < 50,
+        "Too many false positives: {}",
+        false_positives
+    );
+}
+
+#[test]
+fn test_bloom_filter_round_trips_as_json() {
+    let mut filter = BloomFilter::with_capacity(10);
+    filter.insert(b"hello");
+
+    let json = serde_json::to_value(&filter).unwrap();
+    assert!(json["data"].as_str().unwrap().starts_with('u'));
+
+    let decoded: BloomFilter = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(decoded, filter);
+
+    let mut oversized = json.clone();
+    oversized["bits"] = serde_json::json!(u64::MAX);
+    assert!(serde_json::from_value::<BloomFilter>(oversized).is_err());
+
+    let mut truncated = json;
+    truncated["bits"] = serde_json::json!(4096);
+    assert!(serde_json::from_value::<BloomFilter>(truncated).is_err());
+}
+
+#[test]
+fn test_summary_groups_top_level_subtrees() {
+    let summary = manifest_summary::summarize(&manifest(vec![
+        entry("a.txt", b"a"),
+        entry("docs/b.txt", b"b"),
+        entry("docs/deep/c.txt", b"c"),
+    ]));
+
+    assert_eq!(summary.entries, 3);
+    let subtrees: Vec<_> = summary
+        .subtrees
+        .iter()
+        .map(|s| (s.path.as_str(), s.entries))
+        .collect();
+    assert_eq!(subtrees, vec![("a.txt", 1), ("docs", 2)]);
+}
+
+#[test]
+fn test_compare_identical_manifests() {
+    let local = manifest(vec![entry("a.txt", b"a"), entry("docs/b.txt", b"b")]);
+    let remote = manifest_summary::summarize(&local);
+
+    let comparison = manifest_summary::compare(&local, &remote).unwrap();
+
+    assert!(comparison.in_sync);
+    assert!(comparison.subtrees.is_empty());
+    assert!(comparison.missing_on_peer.is_empty());
+}
+
+#[test]
+fn test_compare_finds_differing_subtrees() {
+    let local = manifest(vec![
+        entry("a.txt", b"a"),
+        entry("docs/b.txt", b"b"),
+        entry("docs/new.txt", b"new"),
+        entry("photos/p.jpg", b"p"),
+    ]);
+    let remote = manifest_summary::summarize(&manifest(vec![
+        entry("a.txt", b"a"),
+        entry("docs/b.txt", b"b"),
+        entry("photos/p.jpg", b"changed"),
+        entry("music/m.mp3", b"m"),
+    ]));
+
+    let comparison = manifest_summary::compare(&local, &remote).unwrap();
+
+    assert!(!comparison.in_sync);
+    assert_eq!(comparison.subtrees, vec!["docs", "music", "photos"]);
+    assert_eq!(
+        comparison.missing_on_peer,
+        vec!["docs/new.txt", "photos/p.jpg"]
+    );
+}
+
+#[test]
+fn test_compare_rejects_other_folder() {
+    let local = manifest(vec![entry("a.txt", b"a")]);
+    let mut remote = manifest_summary::summarize(&local);
+    remote.path = "docs".to_string();
+
+    assert!(manifest_summary::compare(&local, &remote).is_err());
+}
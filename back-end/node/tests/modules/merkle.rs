@@ -0,0 +1,108 @@
+use errors::AppError;
+use node::modules::manifest::ManifestEntry;
+use node::modules::merkle::{self, DEPTH, MerkleIndex};
+
+fn entry(path: &str, contents: &[u8]) -> ManifestEntry {
+    ManifestEntry {
+        path: path.to_string(),
+        blake3: blake3::hash(contents).to_hex().to_string(),
+        size: contents.len() as u64,
+    }
+}
+
+fn index(name: &str) -> (sled::Db, MerkleIndex) {
+    let kv = sled::Config::new().temporary(true).open().unwrap();
+    let index = MerkleIndex::open(&kv, name).unwrap();
+    (kv, index)
+}
+
+fn files(count: usize) -> Vec<ManifestEntry> {
+    (0..count)
+        .map(|i| entry(&format!("dir{}/file{}.txt", i % 7, i), &i.to_le_bytes()))
+        .collect()
+}
+
+#[test]
+fn test_incremental_updates_match_rebuild() {
+    let (_kv, built) = index("built");
+    let (_kv2, incremental) = index("incremental");
+    let entries = files(200);
+
+    let empty = incremental.root().unwrap();
+    assert_eq!(empty, "0".repeat(64));
+
+    let mut root = String::new();
+    for entry in &entries {
+        root = incremental.upsert(entry).unwrap();
+    }
+    assert_eq!(root, built.rebuild(&entries).unwrap());
+
+    // Changing a file moves the root, changing it back restores it
+    let changed = incremental
+        .upsert(&entry("dir0/file0.txt", b"edited"))
+        .unwrap();
+    assert_ne!(changed, root);
+    assert_eq!(incremental.upsert(&entries[0]).unwrap(), root);
+
+    for entry in &entries {
+        root = incremental.remove(&entry.path).unwrap();
+    }
+    assert_eq!(root, empty);
+    assert_eq!(built.rebuild(&[]).unwrap(), empty);
+}
+
+#[test]
+fn test_nodes_down_to_buckets() {
+    let (_kv, index) = index("space");
+    let root = index.rebuild(&files(50)).unwrap();
+
+    let node = index.node("").unwrap();
+    assert_eq!(node.hash, root);
+    assert_eq!(node.children.len(), 16);
+    assert!(node.leaves.is_empty());
+
+    // Follow a non-empty child down to its bucket
+    let mut prefix = String::new();
+    while prefix.len() < DEPTH {
+        let node = index.node(&prefix).unwrap();
+        let digit = node
+            .children
+            .iter()
+            .position(|hash| *hash != "0".repeat(64))
+            .unwrap();
+        prefix.push_str(&format!("{:x}", digit));
+    }
+    let bucket = index.node(&prefix).unwrap();
+    assert!(bucket.children.is_empty());
+    assert!(!bucket.leaves.is_empty());
+
+    assert!(matches!(index.node("xyz"), Err(AppError::Validation(_))));
+    assert!(matches!(index.node("00000"), Err(AppError::Validation(_))));
+}
+
+#[test]
+fn test_diff_descends_only_into_changes() {
+    let (_kv, ours) = index("ours");
+    let (_kv2, theirs) = index("theirs");
+    let entries = files(500);
+    ours.rebuild(&entries).unwrap();
+    let mut changed = entries.clone();
+    changed[123] = entry(&entries[123].path, b"changed");
+    theirs.rebuild(&changed).unwrap();
+
+    let mut prefixes = vec![String::new()];
+    let mut visited = 0;
+    let mut leaves = Vec::new();
+    while let Some(prefix) = prefixes.pop() {
+        visited += 1;
+        let (a, b) = (ours.node(&prefix).unwrap(), theirs.node(&prefix).unwrap());
+        if prefix.len() == DEPTH {
+            leaves.extend(b.leaves.into_iter().filter(|leaf| !a.leaves.contains(leaf)));
+        } else {
+            prefixes.extend(merkle::differing_children(&a, &b));
+        }
+    }
+
+    assert_eq!(visited, DEPTH + 1, "Only one path should differ");
+    assert_eq!(leaves, vec![changed[123].clone()]);
+}
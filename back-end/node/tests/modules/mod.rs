@@ -1 +1,34 @@
+pub mod account;
+pub mod acme;
+pub mod anchor;
+pub mod attestation;
+pub mod clock;
+pub mod column_crypto;
+pub mod health;
+pub mod key_usage;
+pub mod maintenance;
+pub mod manifest_summary;
+pub mod merkle;
+pub mod mtls;
+pub mod outbox;
+pub mod p2p;
+pub mod peer_request;
+pub mod pin;
+pub mod resources;
+pub mod retention;
+pub mod scheduler;
+pub mod secrets;
+pub mod session;
+pub mod session_token;
+pub mod settings;
+pub mod soft_delete;
+pub mod space;
+pub mod space_crypto;
 pub mod ssi;
+pub mod storage_stats;
+pub mod telemetry;
+pub mod tenancy;
+pub mod timestamp;
+pub mod updater;
+pub mod validation;
+pub mod webhook;
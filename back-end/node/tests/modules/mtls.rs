@@ -0,0 +1,107 @@
+use ed25519_dalek::SigningKey;
+use node::modules::mtls::{self, DEFAULT_VALIDITY, NodeCertificate};
+use node::modules::ssi::did::DidResolver;
+use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn did_key(signing_key: &SigningKey) -> String {
+    let mut multicodec_key = vec![0xed, 0x01];
+    multicodec_key.extend_from_slice(signing_key.verifying_key().as_bytes());
+    format!(
+        "did:key:{}",
+        multibase::encode(multibase::Base::Base58Btc, &multicodec_key)
+    )
+}
+
+fn node_certificate(seed: u8) -> NodeCertificate {
+    let signing_key = SigningKey::from_bytes(&[seed; 32]);
+    NodeCertificate::issue_for(&signing_key, &did_key(&signing_key), DEFAULT_VALIDITY).unwrap()
+}
+
+#[tokio::test]
+async fn test_certificate_names_did_and_key() {
+    let signing_key = SigningKey::from_bytes(&[1u8; 32]);
+    let did = did_key(&signing_key);
+    let cert = NodeCertificate::issue_for(&signing_key, &did, DEFAULT_VALIDITY).unwrap();
+    assert!(cert.cert_pem().starts_with("-----BEGIN CERTIFICATE-----"));
+
+    let identity = mtls::peer_identity(cert.cert_der()).unwrap();
+    assert_eq!(identity.did, did);
+    assert_eq!(identity.public_key, signing_key.verifying_key());
+    let resolver = DidResolver::new();
+    assert!(mtls::verify_binding(&resolver, &identity).await.is_ok());
+
+    // Someone else's DID on our key
+    let other = did_key(&SigningKey::from_bytes(&[2u8; 32]));
+    let forged = NodeCertificate::issue_for(&signing_key, &other, DEFAULT_VALIDITY).unwrap();
+    let identity = mtls::peer_identity(forged.cert_der()).unwrap();
+    assert!(mtls::verify_binding(&resolver, &identity).await.is_err());
+}
+
+#[tokio::test]
+async fn test_binding_resolves_other_methods() {
+    let signing_key = SigningKey::from_bytes(&[3u8; 32]);
+    let did = PeerDidGenerator::from_ed25519_bytes(signing_key.verifying_key().as_bytes()).unwrap();
+    let resolver = DidResolver::new();
+
+    let cert = NodeCertificate::issue_for(&signing_key, &did, DEFAULT_VALIDITY).unwrap();
+    let identity = mtls::peer_identity(cert.cert_der()).unwrap();
+    assert!(mtls::verify_binding(&resolver, &identity).await.is_ok());
+
+    let other = PeerDidGenerator::from_ed25519_bytes(&[4u8; 32]).unwrap();
+    let cert = NodeCertificate::issue_for(&signing_key, &other, DEFAULT_VALIDITY).unwrap();
+    let identity = mtls::peer_identity(cert.cert_der()).unwrap();
+    assert!(mtls::verify_binding(&resolver, &identity).await.is_err());
+}
+
+#[tokio::test]
+async fn test_mutual_handshake_identifies_both_nodes() {
+    let server = node_certificate(5);
+    let client = node_certificate(6);
+    let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+
+    let acceptor = server.acceptor().unwrap();
+    let accepted = tokio::spawn(async move {
+        let mut stream = acceptor.accept(server_io).await.unwrap();
+        let peer =
+            mtls::authenticate_peer(&DidResolver::new(), stream.get_ref().1.peer_certificates())
+                .await
+                .unwrap();
+        let mut request = [0u8; 4];
+        stream.read_exact(&mut request).await.unwrap();
+        stream.write_all(b"pong").await.unwrap();
+        stream.flush().await.unwrap();
+        peer.did
+    });
+
+    let connector = client.connector(server.did()).unwrap();
+    let name = rustls::pki_types::ServerName::try_from("peer.flow").unwrap();
+    let mut stream = connector.connect(name, client_io).await.unwrap();
+    let peer = mtls::authenticate_peer(&DidResolver::new(), stream.get_ref().1.peer_certificates())
+        .await
+        .unwrap();
+    assert_eq!(peer.did, server.did());
+
+    stream.write_all(b"ping").await.unwrap();
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"pong");
+    assert_eq!(accepted.await.unwrap(), client.did());
+}
+
+#[tokio::test]
+async fn test_client_rejects_unexpected_server() {
+    let server = node_certificate(7);
+    let client = node_certificate(8);
+    let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+
+    let acceptor = server.acceptor().unwrap();
+    tokio::spawn(async move {
+        let _ = acceptor.accept(server_io).await;
+    });
+
+    let impostor_for = did_key(&SigningKey::from_bytes(&[9u8; 32]));
+    let connector = client.connector(&impostor_for).unwrap();
+    let name = rustls::pki_types::ServerName::try_from("peer.flow").unwrap();
+    assert!(connector.connect(name, client_io).await.is_err());
+}
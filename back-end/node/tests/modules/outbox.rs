@@ -0,0 +1,105 @@
+use std::sync::{Arc, Mutex};
+
+use crate::bootstrap::init::setup_test_node;
+use event::schema::{FileChanged, ID_FIELD};
+use event::source::EventListener;
+use event::types::{Event, EventType};
+use node::modules::outbox::{self, Outbox};
+use node::modules::tenancy::Actor;
+use sea_orm::TransactionTrait;
+use serde_json::json;
+use tempfile::TempDir;
+
+struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+impl EventListener for Recorder {
+    fn handle(&self, event: &Event) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+fn file_created(path: &str) -> Event {
+    Event::from_payload(
+        EventType::FileCreated,
+        &FileChanged {
+            space_key: "k".to_string(),
+            path: path.to_string(),
+            user_id: None,
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_only_committed_events_are_dispatched() {
+    let (node, _temp) = setup_test_node().await;
+    let outbox = Outbox::new(node.db.clone());
+
+    let txn = node.db.begin().await.unwrap();
+    outbox::enqueue(&txn, &file_created("lost.txt"))
+        .await
+        .unwrap();
+    txn.rollback().await.unwrap();
+    assert_eq!(outbox.pending().await.unwrap(), 0);
+
+    let txn = node.db.begin().await.unwrap();
+    outbox::enqueue(&txn, &file_created("a.txt")).await.unwrap();
+    outbox::enqueue(&txn, &file_created("b.txt")).await.unwrap();
+    txn.commit().await.unwrap();
+    assert_eq!(outbox.pending().await.unwrap(), 2);
+
+    // Published oldest first, each with its outbox ID, then gone
+    let published = Mutex::new(Vec::new());
+    let count = outbox
+        .dispatch(|event| published.lock().unwrap().push(event.clone()))
+        .await
+        .unwrap();
+    assert_eq!(count, 2);
+    let published = published.into_inner().unwrap();
+    assert_eq!(published[0].properties["path"], json!("a.txt"));
+    assert_eq!(published[1].properties["path"], json!("b.txt"));
+    assert!(
+        published[0].properties[ID_FIELD].as_i64() < published[1].properties[ID_FIELD].as_i64()
+    );
+    assert_eq!(outbox.pending().await.unwrap(), 0);
+    assert_eq!(
+        outbox
+            .dispatch(|_| panic!("published twice"))
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_scan_publishes_through_the_outbox() {
+    let (node, _temp) = setup_test_node().await;
+    let events = Arc::new(Mutex::new(Vec::new()));
+    node.subscribe(Box::new(Recorder(events.clone())));
+
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+
+    node.scan_space_as(Actor::Node, &key).await.unwrap();
+    {
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, EventType::FileCreated);
+        assert!(events[0].properties.contains_key(ID_FIELD));
+    }
+    assert_eq!(node.outbox.pending().await.unwrap(), 0);
+
+    // Events a crash left behind go out on the next dispatch
+    outbox::enqueue(&node.db, &file_created("left.txt"))
+        .await
+        .unwrap();
+    assert_eq!(node.dispatch_outbox().await.unwrap(), 1);
+    assert_eq!(
+        events.lock().unwrap()[1].properties["path"],
+        json!("left.txt")
+    );
+}
@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use ed25519_dalek::SigningKey;
+use errors::AppError;
+use node::modules::mtls::{DEFAULT_VALIDITY, NodeCertificate, PeerIdentity};
+use node::modules::p2p::{Host, Message, MessageHandler, Multiaddr, P2p, wire};
+use node::modules::ssi::did::DidResolver;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+fn node(seed: u8) -> P2p {
+    let signing_key = SigningKey::from_bytes(&[seed; 32]);
+    let mut multicodec_key = vec![0xed, 0x01];
+    multicodec_key.extend_from_slice(signing_key.verifying_key().as_bytes());
+    let did = format!(
+        "did:key:{}",
+        multibase::encode(multibase::Base::Base58Btc, &multicodec_key)
+    );
+    let certificate = NodeCertificate::issue_for(&signing_key, &did, DEFAULT_VALIDITY).unwrap();
+    P2p::new(certificate, Arc::new(DidResolver::new()))
+}
+
+/// Answers every message with who sent it
+struct Echo;
+
+#[async_trait]
+impl MessageHandler for Echo {
+    async fn handle(
+        &self,
+        peer: &PeerIdentity,
+        message: Message,
+    ) -> Result<Option<Message>, AppError> {
+        Ok(Some(Message::new(
+            "echo",
+            json!({"from": peer.did, "kind": message.kind, "body": message.body}),
+        )))
+    }
+}
+
+/// Serve `p2p` on a random local port, returning its address
+async fn listen(p2p: &P2p) -> Multiaddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let serving = p2p.clone();
+    tokio::spawn(async move { serving.serve(listener, Arc::new(Echo)).await });
+    Multiaddr::new(Host::Ip("127.0.0.1".parse().unwrap()), port).with_peer(p2p.did())
+}
+
+#[test]
+fn test_multiaddr_round_trips() {
+    let did = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+    let addr: Multiaddr = format!("/ip4/192.0.2.7/tcp/4001/p2p/{}", did)
+        .parse()
+        .unwrap();
+    assert_eq!(addr.host, Host::Ip("192.0.2.7".parse().unwrap()));
+    assert_eq!(addr.port, 4001);
+    assert_eq!(addr.peer.as_deref(), Some(did));
+    assert_eq!(
+        addr.to_string(),
+        format!("/ip4/192.0.2.7/tcp/4001/p2p/{}", did)
+    );
+
+    let addr: Multiaddr = "/ip6/::1/tcp/4001".parse().unwrap();
+    assert_eq!(addr.socket_addr(), "[::1]:4001");
+    let addr: Multiaddr = "/dns4/peer.example/tcp/4001".parse().unwrap();
+    assert_eq!(addr.to_string(), "/dns/peer.example/tcp/4001");
+
+    for invalid in [
+        "ip4/192.0.2.7/tcp/4001",
+        "/ip4/192.0.2.700/tcp/4001",
+        "/ip4/192.0.2.7/udp/4001",
+        "/ip4/192.0.2.7/tcp/99999",
+        "/ip4/192.0.2.7/tcp/4001/p2p/12D3KooW",
+        "/ip4/192.0.2.7/tcp/4001/p2p/did:key:z6Mk/extra",
+    ] {
+        assert!(invalid.parse::<Multiaddr>().is_err(), "{}", invalid);
+    }
+}
+
+#[tokio::test]
+async fn test_nodes_exchange_authenticated_messages() {
+    let server = node(7);
+    let client = node(8);
+    let addr = listen(&server).await;
+
+    let mut connection = client.dial(&addr).await.unwrap();
+    assert_eq!(connection.peer().did, server.did());
+
+    let answer = connection
+        .request(&Message::new("sync.offer", json!({"space": "s1"})))
+        .await
+        .unwrap();
+    assert_eq!(answer.kind, "echo");
+    assert_eq!(answer.body["from"], client.did());
+    assert_eq!(answer.body["kind"], "sync.offer");
+    assert_eq!(answer.body["body"]["space"], "s1");
+}
+
+#[tokio::test]
+async fn test_dial_is_pinned_to_the_named_did() {
+    let server = node(9);
+    let client = node(10);
+    let mut addr = listen(&server).await;
+
+    addr.peer = Some(node(11).did().to_string());
+    assert!(client.dial(&addr).await.is_err());
+
+    addr.peer = None;
+    assert!(matches!(
+        client.dial(&addr).await,
+        Err(AppError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_oversized_messages_are_refused() {
+    let message = Message::new("blob", json!("x".repeat(64)));
+    let mut buffer = Vec::new();
+    assert!(matches!(
+        wire::write(&mut buffer, &message, 16).await,
+        Err(AppError::PayloadTooLarge(_))
+    ));
+    assert!(buffer.is_empty());
+
+    wire::write(&mut buffer, &message, 1024).await.unwrap();
+    assert!(matches!(
+        wire::read(&mut buffer.as_slice(), 16).await,
+        Err(AppError::PayloadTooLarge(_))
+    ));
+    let read = wire::read(&mut buffer.as_slice(), 1024).await.unwrap();
+    assert_eq!(read, Some(message));
+    assert_eq!(wire::read(&mut &[][..], 1024).await.unwrap(), None);
+}
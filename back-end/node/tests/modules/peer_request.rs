@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
+use errors::AppError;
+use node::modules::clock::{Clock, TestClock, TimeSource};
+use node::modules::peer_request::{self, ReplayCache, Target};
+use node::modules::ssi::did::DidResolver;
+use std::sync::Arc;
+use std::time::Duration;
+
+const AUDIENCE: &str = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+const BODY: &[u8] = br#"{"kind":"sync.offer","body":{}}"#;
+
+fn peer(seed: u8) -> (SigningKey, String) {
+    let signing_key = SigningKey::from_bytes(&[seed; 32]);
+    let mut multicodec_key = vec![0xed, 0x01];
+    multicodec_key.extend_from_slice(signing_key.verifying_key().as_bytes());
+    let did = format!(
+        "did:key:{}",
+        multibase::encode(multibase::Base::Base58Btc, &multicodec_key)
+    );
+    (signing_key, did)
+}
+
+fn target(body: &[u8]) -> Target<'_> {
+    Target {
+        audience: AUDIENCE,
+        method: "POST",
+        path: "/api/v1/peer/messages",
+        body,
+    }
+}
+
+fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap()
+}
+
+/// A replay cache going by `clock`, with a five minute window and a minute
+/// of skew
+fn cache(clock: &TestClock) -> ReplayCache {
+    let kv = sled::Config::new().temporary(true).open().unwrap();
+    ReplayCache::new(
+        kv,
+        Duration::from_secs(300),
+        TimeSource::new(Arc::new(clock.clone()), Duration::from_secs(60)),
+    )
+}
+
+#[tokio::test]
+async fn test_signed_request_verifies_once() {
+    let clock = TestClock::at(at(1_700_000_000));
+    let replays = cache(&clock);
+    let resolver = DidResolver::new();
+    let (key, did) = peer(1);
+
+    let signature = peer_request::sign_with(&key, &did, &target(BODY), clock.now()).unwrap();
+    let verified = peer_request::verify(&signature, &target(BODY), &resolver, &replays)
+        .await
+        .unwrap();
+    assert_eq!(verified.did, did);
+    assert_eq!(verified.public_key, key.verifying_key());
+
+    assert!(matches!(
+        peer_request::verify(&signature, &target(BODY), &resolver, &replays).await,
+        Err(AppError::Replayed(_))
+    ));
+
+    // A fresh signature carries a fresh nonce
+    let again = peer_request::sign_with(&key, &did, &target(BODY), clock.now()).unwrap();
+    assert!(
+        peer_request::verify(&again, &target(BODY), &resolver, &replays)
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn test_signature_covers_the_request() {
+    let clock = TestClock::at(at(1_700_000_000));
+    let replays = cache(&clock);
+    let resolver = DidResolver::new();
+    let (key, did) = peer(1);
+    let signature = peer_request::sign_with(&key, &did, &target(BODY), clock.now()).unwrap();
+
+    let others = [
+        target(b"{}"),
+        Target {
+            path: "/api/v1/peer/messages?to=elsewhere",
+            ..target(BODY)
+        },
+        Target {
+            method: "PUT",
+            ..target(BODY)
+        },
+        Target {
+            audience: "did:web:other.example",
+            ..target(BODY)
+        },
+    ];
+    for other in others {
+        assert!(matches!(
+            peer_request::verify(&signature, &other, &resolver, &replays).await,
+            Err(AppError::Auth(_))
+        ));
+    }
+
+    // Claims of one signature under the signature bytes of another
+    let other = peer_request::sign_with(&key, &did, &target(b"{}"), clock.now()).unwrap();
+    let (claims, _) = signature.rsplit_once('.').unwrap();
+    let tampered = format!("{}.{}", claims, other.rsplit_once('.').unwrap().1);
+    assert!(matches!(
+        peer_request::verify(&tampered, &target(BODY), &resolver, &replays).await,
+        Err(AppError::Auth(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_key_must_belong_to_did() {
+    let clock = TestClock::at(at(1_700_000_000));
+    let replays = cache(&clock);
+    let (_, did) = peer(1);
+    let (impostor, _) = peer(2);
+
+    let signature = peer_request::sign_with(&impostor, &did, &target(BODY), clock.now()).unwrap();
+    assert!(matches!(
+        peer_request::verify(&signature, &target(BODY), &DidResolver::new(), &replays).await,
+        Err(AppError::Auth(_))
+    ));
+    assert_eq!(replays.sweep().unwrap(), 0);
+}
+
+#[test]
+fn test_replay_window_is_bounded() {
+    let clock = TestClock::at(at(1_700_000_000));
+    let replays = cache(&clock);
+    let did = peer(1).1;
+    let nonce = |n: u8| format!("nonce-{:0>16}", n);
+
+    // Inside the window, give or take the skew
+    replays
+        .check(&did, &nonce(1), at(1_700_000_000 - 350))
+        .unwrap();
+    replays
+        .check(&did, &nonce(2), at(1_700_000_000 + 50))
+        .unwrap();
+    assert!(matches!(
+        replays.check(&did, &nonce(3), at(1_700_000_000 - 400)),
+        Err(AppError::Auth(_))
+    ));
+    assert!(matches!(
+        replays.check(&did, &nonce(4), at(1_700_000_000 + 120)),
+        Err(AppError::Auth(_))
+    ));
+    assert!(matches!(
+        replays.check(&did, "short", at(1_700_000_000)),
+        Err(AppError::Validation(_))
+    ));
+
+    // Nonces are per peer
+    assert!(matches!(
+        replays.check(&did, &nonce(1), at(1_700_000_000)),
+        Err(AppError::Replayed(_))
+    ));
+    replays
+        .check(&peer(2).1, &nonce(1), at(1_700_000_000))
+        .unwrap();
+
+    // Swept once out of the window, and by then too old to be sent again
+    assert_eq!(replays.sweep().unwrap(), 0);
+    clock.advance(Duration::from_secs(20));
+    assert_eq!(replays.sweep().unwrap(), 1);
+    assert!(matches!(
+        replays.check(&did, &nonce(1), at(1_700_000_000 - 350)),
+        Err(AppError::Auth(_))
+    ));
+    clock.advance(Duration::from_secs(600));
+    assert_eq!(replays.sweep().unwrap(), 2);
+}
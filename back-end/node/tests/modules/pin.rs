@@ -0,0 +1,113 @@
+use crate::bootstrap::init::setup_test_node;
+use node::modules::pin::{MAX_FAILED_ATTEMPTS, PinScope, PinUnlock};
+
+#[tokio::test]
+async fn test_unlock_issues_read_only_grant() {
+    let (node, _temp) = setup_test_node().await;
+    let pin = node.local_pin();
+
+    assert!(!pin.is_set().unwrap());
+    assert!(pin.unlock("1234").await.is_err());
+
+    pin.set("1234", None).await.unwrap();
+    assert!(pin.is_set().unwrap());
+
+    let PinUnlock::Unlocked { token, grant } = pin.unlock("1234").await.unwrap() else {
+        panic!("correct PIN should unlock");
+    };
+    assert_eq!(grant.scope, PinScope::ReadOnly);
+    assert_eq!(pin.grant(&token).await.unwrap(), Some(grant));
+    assert_eq!(pin.grant("unknown").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_pin_is_hashed_with_argon2id() {
+    let (node, _temp) = setup_test_node().await;
+    node.local_pin().set("1234", None).await.unwrap();
+
+    let hash = node
+        .kv
+        .open_tree(node::modules::pin::PIN_TREE)
+        .unwrap()
+        .get(b"hash")
+        .unwrap()
+        .unwrap();
+    let hash = String::from_utf8(hash.to_vec()).unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert!(!hash.contains("1234"));
+}
+
+#[tokio::test]
+async fn test_lockout_after_repeated_failures() {
+    let (node, _temp) = setup_test_node().await;
+    let pin = node.local_pin();
+    pin.set("1234", None).await.unwrap();
+
+    for attempt in 1..MAX_FAILED_ATTEMPTS {
+        assert_eq!(
+            pin.unlock("0000").await.unwrap(),
+            PinUnlock::Rejected {
+                remaining_attempts: MAX_FAILED_ATTEMPTS - attempt
+            }
+        );
+    }
+    assert!(matches!(
+        pin.unlock("0000").await.unwrap(),
+        PinUnlock::LockedOut { .. }
+    ));
+
+    // The right PIN doesn't help while locked, nor does changing it
+    assert!(matches!(
+        pin.unlock("1234").await.unwrap(),
+        PinUnlock::LockedOut { .. }
+    ));
+    assert!(pin.set("5678", Some("1234")).await.is_err());
+}
+
+#[tokio::test]
+async fn test_concurrent_failures_are_all_counted() {
+    let (node, _temp) = setup_test_node().await;
+    let pin = node.local_pin();
+    pin.set("1234", None).await.unwrap();
+
+    let results =
+        futures_util::future::join_all((0..MAX_FAILED_ATTEMPTS).map(|_| pin.unlock("0000"))).await;
+
+    let mut remaining: Vec<u32> = results
+        .iter()
+        .filter_map(|result| match result.as_ref().unwrap() {
+            PinUnlock::Rejected { remaining_attempts } => Some(*remaining_attempts),
+            _ => None,
+        })
+        .collect();
+    remaining.sort();
+    assert_eq!(remaining, (1..MAX_FAILED_ATTEMPTS).collect::<Vec<_>>());
+    assert!(matches!(
+        pin.unlock("1234").await.unwrap(),
+        PinUnlock::LockedOut { .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_success_resets_failures() {
+    let (node, _temp) = setup_test_node().await;
+    let pin = node.local_pin();
+    pin.set("1234", None).await.unwrap();
+
+    for _ in 1..MAX_FAILED_ATTEMPTS {
+        pin.unlock("0000").await.unwrap();
+    }
+    assert!(matches!(
+        pin.unlock("1234").await.unwrap(),
+        PinUnlock::Unlocked { .. }
+    ));
+    assert_eq!(
+        pin.unlock("0000").await.unwrap(),
+        PinUnlock::Rejected {
+            remaining_attempts: MAX_FAILED_ATTEMPTS - 1
+        }
+    );
+
+    pin.clear("1234").await.unwrap();
+    assert!(!pin.is_set().unwrap());
+}
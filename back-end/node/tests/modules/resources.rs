@@ -0,0 +1,72 @@
+use crate::bootstrap::init::setup_test_node;
+use node::bootstrap::config::ResourceConfig;
+use node::modules::resources::{self, LimitExceeded, Tasks};
+
+#[tokio::test]
+async fn test_tasks_are_counted_until_they_finish() {
+    let tasks = Tasks::new();
+    let guard = tasks.track("websocket");
+    let (release, released) = tokio::sync::oneshot::channel::<()>();
+    let handle = tasks.spawn("scheduler", async move {
+        released.await.ok();
+    });
+
+    let running = tasks.running();
+    assert_eq!(running["websocket"], 1);
+    assert_eq!(running["scheduler"], 1);
+
+    drop(guard);
+    release.send(()).unwrap();
+    handle.await.unwrap();
+    assert!(tasks.running().is_empty());
+}
+
+#[tokio::test]
+async fn test_usage_sizes_kv_trees_and_the_database() {
+    let (node, _temp) = setup_test_node().await;
+    node.kv
+        .open_tree("sizing")
+        .unwrap()
+        .insert(b"key", &[0u8; 100][..])
+        .unwrap();
+    let _open = node.tasks.track("websocket");
+
+    let usage = node.resource_usage().await.unwrap();
+    assert_eq!(usage.kv_trees["sizing"], 103);
+    assert!(usage.db_bytes.unwrap() > 0);
+    assert_eq!(usage.task_count(), 1);
+    if cfg!(target_os = "linux") {
+        assert!(usage.memory_bytes.unwrap() > 0);
+        assert!(usage.open_files.unwrap() > 0);
+    }
+
+    // Polling skips the per-tree scan
+    let totals = resources::totals(&node.db, &node.kv, &node.tasks)
+        .await
+        .unwrap();
+    assert!(totals.kv_trees.is_empty());
+}
+
+#[tokio::test]
+async fn test_only_soft_limits_gone_over_are_reported() {
+    let (node, _temp) = setup_test_node().await;
+    let _open = node.tasks.track("websocket");
+    let _also = node.tasks.track("websocket");
+    let usage = node.resource_usage().await.unwrap();
+
+    assert!(usage.exceeded(&ResourceConfig::default()).is_empty());
+    let limits = ResourceConfig {
+        tasks: Some(1),
+        db_bytes: Some(u64::MAX),
+        ..Default::default()
+    };
+    assert!(limits.any_limit());
+    assert_eq!(
+        usage.exceeded(&limits),
+        vec![LimitExceeded {
+            resource: "tasks",
+            used: 2,
+            limit: 1,
+        }]
+    );
+}
@@ -0,0 +1,119 @@
+use crate::bootstrap::init::setup_test_node;
+use chrono::{Duration, Utc};
+use node::bootstrap::config::RetentionConfig;
+use node::modules::key_usage::{KeyKind, KeyUse, Operation};
+use node::modules::scheduler::{NewTask, RUN_TREE, TaskAction};
+use tempfile::TempDir;
+
+fn key_use(key_id: &str, days_ago: i64) -> KeyUse {
+    KeyUse {
+        kind: KeyKind::Session,
+        key_id: key_id.to_string(),
+        user_id: Some(1),
+        at: Utc::now() - Duration::days(days_ago),
+        ip: None,
+        country: None,
+        operation: Operation::Read,
+    }
+}
+
+#[tokio::test]
+async fn test_purge_removes_records_past_retention() {
+    let (node, _temp) = setup_test_node().await;
+    let dir = TempDir::new().unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+
+    // One run now, one made to look 100 days old
+    let task = node
+        .scheduler()
+        .create(NewTask {
+            name: "scan".to_string(),
+            schedule: "@daily".to_string(),
+            action: TaskAction::ScanSpace { space_key: key },
+            enabled: true,
+        })
+        .await
+        .unwrap();
+    node.scheduler().run_now(&task.id).await.unwrap();
+    let runs = node.kv.open_tree(RUN_TREE).unwrap();
+    let (run_key, run) = runs.first().unwrap().unwrap();
+    let mut old: serde_json::Value = serde_json::from_slice(&run).unwrap();
+    old["finishedAt"] = serde_json::json!(Utc::now() - Duration::days(100));
+    let mut old_key = run_key.to_vec();
+    old_key.push(b'0');
+    runs.insert(old_key, serde_json::to_vec(&old).unwrap())
+        .unwrap();
+
+    // An old key with an old use, and a recent key with an old and a new use
+    let usage = node.key_usage();
+    usage.record(&key_use("stale", 400)).unwrap();
+    usage.record(&key_use("active", 400)).unwrap();
+    usage.record(&key_use("active", 1)).unwrap();
+
+    let report = node.purge_expired(true).unwrap();
+    assert!(report.dry_run);
+    assert_eq!((report.task_runs, report.key_usage), (1, 3));
+    assert_eq!(node.scheduler().history(&task.id).unwrap().len(), 2);
+
+    let report = node.purge_expired(false).unwrap();
+    assert_eq!((report.task_runs, report.key_usage), (1, 3));
+    assert_eq!(node.scheduler().history(&task.id).unwrap().len(), 1);
+    assert!(
+        usage
+            .key_stats(KeyKind::Session, "stale")
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(usage.history(KeyKind::Session, "active").unwrap().len(), 1);
+    assert_eq!(node.purge_expired(false).unwrap().total(), 0);
+}
+
+#[tokio::test]
+async fn test_purge_keeps_records_without_retention() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.retention = RetentionConfig {
+        key_usage: None,
+        ..Default::default()
+    };
+    node.key_usage().record(&key_use("old", 1000)).unwrap();
+
+    assert_eq!(node.purge_expired(false).unwrap().key_usage, 0);
+    assert_eq!(
+        node.key_usage()
+            .history(KeyKind::Session, "old")
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_purge_data_task_reports() {
+    let (node, _temp) = setup_test_node().await;
+    node.key_usage().record(&key_use("old", 1000)).unwrap();
+
+    let task = node
+        .scheduler()
+        .create(NewTask {
+            name: "retention report".to_string(),
+            schedule: "@weekly".to_string(),
+            action: TaskAction::PurgeData { dry_run: true },
+            enabled: true,
+        })
+        .await
+        .unwrap();
+    let run = node.scheduler().run_now(&task.id).await.unwrap();
+    let output = run.output.unwrap();
+    assert_eq!(output["dryRun"], true);
+    assert_eq!(output["keyUsage"], 2);
+    assert_eq!(
+        node.key_usage()
+            .history(KeyKind::Session, "old")
+            .unwrap()
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,250 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::bootstrap::init::setup_test_node;
+use chrono::{Duration, TimeZone, Utc};
+use event::source::EventListener;
+use event::types::{Event, EventType};
+use node::api::node::Node;
+use node::modules::scheduler::{MAX_RUN_HISTORY, NewTask, RunStatus, TaskAction, parse_schedule};
+use tempfile::TempDir;
+
+async fn space_with_files(node: &Node) -> (String, TempDir) {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/b.txt"), "world!").unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    (key, dir)
+}
+
+/// A test node whose config dir is the returned temp dir, so it can export
+async fn node_with_home() -> (Node, TempDir) {
+    let (mut node, temp) = setup_test_node().await;
+    node.home = Some(temp.path().to_path_buf());
+    (node, temp)
+}
+
+fn task(schedule: &str, action: TaskAction) -> NewTask {
+    NewTask {
+        name: "nightly".to_string(),
+        schedule: schedule.to_string(),
+        action,
+        enabled: true,
+    }
+}
+
+struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+impl EventListener for Recorder {
+    fn handle(&self, event: &Event) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_schedule_accepts_five_and_six_fields() {
+    let after = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 30).unwrap();
+
+    let five = parse_schedule("*/15 * * * *").unwrap();
+    assert_eq!(
+        five.after(&after).next().unwrap(),
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 15, 0).unwrap()
+    );
+
+    let six = parse_schedule("45 * * * * *").unwrap();
+    assert_eq!(
+        six.after(&after).next().unwrap(),
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 45).unwrap()
+    );
+
+    assert!(parse_schedule("@daily").is_ok());
+    assert!(parse_schedule("every day").is_err());
+    assert!(parse_schedule("61 * * * *").is_err());
+}
+
+#[tokio::test]
+async fn test_create_validates_task() {
+    let (node, _temp) = setup_test_node().await;
+    let scheduler = node.scheduler();
+
+    for invalid in [
+        task("not a schedule", TaskAction::PurgeData { dry_run: true }),
+        task(
+            "@daily",
+            TaskAction::ScanSpace {
+                space_key: "missing".to_string(),
+            },
+        ),
+        task(
+            "@daily",
+            TaskAction::RunBackup {
+                destination: "/var/backups".into(),
+            },
+        ),
+        task(
+            "@daily",
+            TaskAction::RunBackup {
+                destination: "backups/../../keystore".into(),
+            },
+        ),
+    ] {
+        assert!(scheduler.create(invalid).await.is_err());
+    }
+    assert!(scheduler.list().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_run_now_records_history() {
+    let (node, _temp) = setup_test_node().await;
+    let (key, _space) = space_with_files(&node).await;
+    let scheduler = node.scheduler();
+
+    let created = scheduler
+        .create(task("@daily", TaskAction::ScanSpace { space_key: key }))
+        .await
+        .unwrap();
+    assert!(created.next_run.unwrap() > Utc::now());
+    assert_eq!(scheduler.list().unwrap(), vec![created.clone()]);
+
+    let run = scheduler.run_now(&created.id).await.unwrap();
+    assert_eq!(run.status, RunStatus::Succeeded);
+    let output = run.output.clone().unwrap();
+    assert_eq!(output["files"], 2);
+    assert_eq!(output["bytes"], 11);
+
+    assert_eq!(scheduler.history(&created.id).unwrap(), vec![run]);
+
+    scheduler.remove(&created.id).unwrap();
+    assert!(scheduler.get(&created.id).is_err());
+    assert!(scheduler.history(&created.id).is_err());
+}
+
+#[tokio::test]
+async fn test_run_due_runs_and_reschedules() {
+    let (node, temp) = node_with_home().await;
+    let (key, _space) = space_with_files(&node).await;
+    let scheduler = node.scheduler();
+
+    let export = scheduler
+        .create(task(
+            "0 * * * *",
+            TaskAction::ExportArchive {
+                space_key: key.clone(),
+                destination: "archives".into(),
+            },
+        ))
+        .await
+        .unwrap();
+    let mut disabled = task("0 * * * *", TaskAction::ScanSpace { space_key: key });
+    disabled.enabled = false;
+    let disabled = scheduler.create(disabled).await.unwrap();
+
+    // Nothing is due yet
+    assert!(scheduler.run_due(Utc::now()).await.unwrap().is_empty());
+
+    let due = export.next_run.unwrap();
+    let runs = scheduler.run_due(due).await.unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].task_id, export.id);
+    assert_eq!(runs[0].status, RunStatus::Succeeded);
+
+    let archive = runs[0].output.as_ref().unwrap()["archive"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(archive.starts_with(temp.path().join("exports/archives").to_str().unwrap()));
+    let mut entries = tar::Archive::new(std::fs::File::open(archive).unwrap())
+        .entries()
+        .unwrap()
+        .map(|e| e.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .filter(|path| path.ends_with(".txt"))
+        .collect::<Vec<_>>();
+    entries.sort();
+    assert_eq!(entries, vec!["a.txt", "sub/b.txt"]);
+
+    assert_eq!(
+        scheduler.get(&export.id).unwrap().next_run.unwrap(),
+        due + Duration::hours(1)
+    );
+    assert!(scheduler.history(&disabled.id).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_run_is_recorded_and_published() {
+    let (node, temp) = node_with_home().await;
+    let events = Arc::new(Mutex::new(Vec::new()));
+    node.subscribe(Box::new(Recorder(events.clone())));
+
+    // A file where the backup directory should be
+    std::fs::create_dir(temp.path().join("exports")).unwrap();
+    std::fs::write(temp.path().join("exports/backups"), "not a directory").unwrap();
+
+    let scheduler = node.scheduler();
+    let backup = scheduler
+        .create(task(
+            "@daily",
+            TaskAction::RunBackup {
+                destination: "backups".into(),
+            },
+        ))
+        .await
+        .unwrap();
+
+    for _ in 0..MAX_RUN_HISTORY + 2 {
+        let run = scheduler.run_now(&backup.id).await.unwrap();
+        assert_eq!(run.status, RunStatus::Failed);
+        assert!(run.error.is_some());
+    }
+    assert_eq!(
+        scheduler.history(&backup.id).unwrap().len(),
+        MAX_RUN_HISTORY
+    );
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), MAX_RUN_HISTORY + 2);
+    assert_eq!(events[0].event_type, EventType::ScheduledTaskFailed);
+    assert_eq!(events[0].properties["task_id"], backup.id.as_str());
+}
+
+#[tokio::test]
+async fn test_backup_writes_database_copy() {
+    let (node, temp) = node_with_home().await;
+    let scheduler = node.scheduler();
+
+    let backup = scheduler
+        .create(task(
+            "@daily",
+            TaskAction::RunBackup {
+                destination: PathBuf::new(),
+            },
+        ))
+        .await
+        .unwrap();
+    let run = scheduler.run_now(&backup.id).await.unwrap();
+    assert_eq!(run.status, RunStatus::Succeeded, "{:?}", run.error);
+
+    let path = run.output.unwrap()["backup"].as_str().unwrap().to_string();
+    assert!(path.starts_with(temp.path().join("exports").to_str().unwrap()));
+    assert!(std::fs::metadata(path).unwrap().len() > 0);
+}
+
+#[tokio::test]
+async fn test_exports_need_a_config_dir() {
+    let (node, _temp) = setup_test_node().await;
+
+    let created = node
+        .scheduler()
+        .create(task(
+            "@daily",
+            TaskAction::RunBackup {
+                destination: PathBuf::new(),
+            },
+        ))
+        .await;
+    assert!(matches!(created, Err(errors::AppError::Config(_))));
+}
@@ -0,0 +1,179 @@
+use crate::bootstrap::init::setup_test_node;
+use chrono::DateTime;
+use node::modules::secrets::{
+    OAuthToken, REDACTED, SECRETS_TREE, Secret, SecretKind, TurnSecret, WebhookSecret,
+};
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn oauth_token() -> OAuthToken {
+    OAuthToken {
+        access_token: Secret::new("access-123"),
+        refresh_token: Some(Secret::new("refresh-456")),
+        expires_at: Some(DateTime::from_timestamp(1_800_000_000, 0).unwrap()),
+        scope: Some("files.read".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_secrets_are_sealed_and_typed() {
+    let (node, _temp) = setup_test_node().await;
+    let vault = node.secrets();
+
+    let info = vault.put("github", &oauth_token()).unwrap();
+    assert_eq!(info.kind, SecretKind::OauthToken);
+    assert_eq!(info.version, 1);
+    vault
+        .put("relay", &TurnSecret(Secret::new("turn-789")))
+        .unwrap();
+
+    assert_eq!(
+        vault.get::<OAuthToken>("github").unwrap(),
+        Some(oauth_token())
+    );
+    // Kinds are separate namespaces
+    assert!(vault.get::<WebhookSecret>("github").unwrap().is_none());
+    assert_eq!(
+        vault
+            .get::<TurnSecret>("relay")
+            .unwrap()
+            .unwrap()
+            .0
+            .expose(),
+        "turn-789"
+    );
+
+    // Nothing is stored in plaintext
+    for item in node.kv.open_tree(SECRETS_TREE).unwrap().iter() {
+        let (_, value) = item.unwrap();
+        let stored = String::from_utf8_lossy(&value);
+        for plaintext in ["access-123", "refresh-456", "turn-789"] {
+            assert!(!stored.contains(plaintext), "{}", stored);
+        }
+    }
+
+    let list = vault.list().unwrap();
+    let names: Vec<_> = list.iter().map(|info| info.name.as_str()).collect();
+    assert_eq!(names, vec!["github", "relay"]);
+
+    assert!(vault.delete(SecretKind::TurnSecret, "relay").unwrap());
+    assert!(!vault.delete(SecretKind::TurnSecret, "relay").unwrap());
+    assert!(vault.put("../escape", &oauth_token()).is_err());
+    assert!(vault.put("", &oauth_token()).is_err());
+}
+
+#[test]
+fn test_secret_values_are_redacted() {
+    let token = oauth_token();
+    let debug = format!("{:?}", token);
+    assert!(!debug.contains("access-123"), "{}", debug);
+    assert!(debug.contains(REDACTED));
+
+    let json = serde_json::to_value(&token).unwrap();
+    assert_eq!(json["accessToken"], REDACTED);
+    assert_eq!(json["refreshToken"], REDACTED);
+    assert_eq!(json["scope"], "files.read");
+    assert_eq!(Secret::new("x").to_string(), REDACTED);
+}
+
+#[tokio::test]
+async fn test_rotation_keeps_previous_for_grace() {
+    let (node, _temp) = setup_test_node().await;
+    let vault = node.secrets();
+    let secret = |value: &str| WebhookSecret(Secret::new(value));
+
+    assert!(
+        vault
+            .rotate("hooks", &secret("new"), Duration::from_secs(60))
+            .is_err()
+    );
+    vault.put("hooks", &secret("old")).unwrap();
+    let info = vault
+        .rotate("hooks", &secret("new"), Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(info.version, 2);
+    assert!(info.previous_until.is_some());
+    assert_eq!(vault.get("hooks").unwrap(), Some(secret("new")));
+    assert_eq!(vault.previous("hooks").unwrap(), Some(secret("old")));
+
+    // A grace period that is already over, or a plain replacement, drops it
+    vault
+        .rotate("hooks", &secret("newer"), Duration::ZERO)
+        .unwrap();
+    assert_eq!(vault.previous::<WebhookSecret>("hooks").unwrap(), None);
+    let info = vault.put("hooks", &secret("newest")).unwrap();
+    assert_eq!(info.version, 4);
+    assert_eq!(info.previous_until, None);
+    assert_eq!(vault.previous::<WebhookSecret>("hooks").unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_reseal_after_column_key_rotation() {
+    let (mut node, _temp) = setup_test_node().await;
+    let keystore = TempDir::new().unwrap();
+    node.secrets()
+        .put("hooks", &WebhookSecret(Secret::new("old")))
+        .unwrap();
+    node.secrets()
+        .rotate(
+            "hooks",
+            &WebhookSecret(Secret::new("new")),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+    assert_eq!(node.secrets().reseal().unwrap(), 0);
+
+    node.column_keys = node.column_keys.rotate(keystore.path()).unwrap();
+    assert_eq!(node.secrets().reseal().unwrap(), 1);
+    assert_eq!(node.secrets().reseal().unwrap(), 0);
+
+    let tree = node.kv.open_tree(SECRETS_TREE).unwrap();
+    let (_, stored) = tree.first().unwrap().unwrap();
+    let stored: serde_json::Value = serde_json::from_slice(&stored).unwrap();
+    let active = node.column_keys.active_key_id();
+    assert!(stored["sealed"].as_str().unwrap().contains(active));
+    assert!(stored["previous"].as_str().unwrap().contains(active));
+
+    let vault = node.secrets();
+    assert_eq!(
+        vault
+            .get::<WebhookSecret>("hooks")
+            .unwrap()
+            .unwrap()
+            .0
+            .expose(),
+        "new"
+    );
+    assert_eq!(
+        vault
+            .previous::<WebhookSecret>("hooks")
+            .unwrap()
+            .unwrap()
+            .0
+            .expose(),
+        "old"
+    );
+}
+
+#[tokio::test]
+async fn test_sealed_value_is_bound_to_its_entry() {
+    let (node, _temp) = setup_test_node().await;
+    let vault = node.secrets();
+    vault
+        .put("a", &WebhookSecret(Secret::new("secret-a")))
+        .unwrap();
+    vault
+        .put("b", &WebhookSecret(Secret::new("secret-b")))
+        .unwrap();
+
+    let tree = node.kv.open_tree(SECRETS_TREE).unwrap();
+    let a: serde_json::Value =
+        serde_json::from_slice(&tree.get("webhook_secret/a").unwrap().unwrap()).unwrap();
+    let mut b: serde_json::Value =
+        serde_json::from_slice(&tree.get("webhook_secret/b").unwrap().unwrap()).unwrap();
+    b["sealed"] = a["sealed"].clone();
+    tree.insert("webhook_secret/b", serde_json::to_vec(&b).unwrap())
+        .unwrap();
+
+    assert!(vault.get::<WebhookSecret>("b").is_err());
+}
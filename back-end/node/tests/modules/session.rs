@@ -0,0 +1,220 @@
+use crate::bootstrap::init::{create_test_node_with_db, setup_test_db, setup_test_node};
+use chrono::Utc;
+use entity::session;
+use node::modules::auth_crypto;
+use node::modules::clock::{TestClock, TimeSource};
+use node::modules::session::{SessionKind, SessionStore, Taken};
+use sea_orm::EntityTrait;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TTL: Duration = Duration::from_secs(60);
+
+#[tokio::test]
+async fn test_put_get_take() {
+    let (db, _temp) = setup_test_db().await;
+    let store = SessionStore::new(db);
+
+    store
+        .put(SessionKind::Registration, "abc", &json!({"n": 1}), TTL)
+        .await
+        .unwrap();
+
+    let value: Option<Value> = store.get(SessionKind::Registration, "abc").await.unwrap();
+    assert_eq!(value, Some(json!({"n": 1})));
+
+    // Kinds are separate namespaces
+    let other: Option<Value> = store.get(SessionKind::Authentication, "abc").await.unwrap();
+    assert_eq!(other, None);
+
+    let taken: Taken<Value> = store.take(SessionKind::Registration, "abc").await.unwrap();
+    assert_eq!(taken, Taken::Found(json!({"n": 1})));
+
+    // Records are single use
+    let again: Taken<Value> = store.take(SessionKind::Registration, "abc").await.unwrap();
+    assert_eq!(again, Taken::Missing);
+}
+
+#[tokio::test]
+async fn test_concurrent_updates_are_not_lost() {
+    let (db, _temp) = setup_test_db().await;
+    let store = SessionStore::new(db);
+
+    let counts = futures_util::future::join_all((0..20).map(|_| {
+        store.update(
+            SessionKind::AuthFailures,
+            "counter",
+            |count: Option<u32>| (count.unwrap_or(0) + 1, TTL),
+        )
+    }))
+    .await;
+
+    let mut counts: Vec<u32> = counts.into_iter().map(Result::unwrap).collect();
+    counts.sort();
+    assert_eq!(counts, (1..=20).collect::<Vec<_>>());
+    let stored: Option<u32> = store
+        .get(SessionKind::AuthFailures, "counter")
+        .await
+        .unwrap();
+    assert_eq!(stored, Some(20));
+}
+
+#[tokio::test]
+async fn test_secret_keys_are_stored_as_digests() {
+    let (db, _temp) = setup_test_db().await;
+    let store = SessionStore::new(db.clone());
+
+    store
+        .put(SessionKind::PinUnlock, "bearer-token", &1, TTL)
+        .await
+        .unwrap();
+    store
+        .put(SessionKind::PinAttempts, "attempts", &2, TTL)
+        .await
+        .unwrap();
+
+    let mut keys: Vec<String> = session::Entity::find()
+        .all(&db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|record| record.key)
+        .collect();
+    let mut expected = vec![
+        "attempts".to_string(),
+        auth_crypto::lookup_key("bearer-token"),
+    ];
+    keys.sort();
+    expected.sort();
+    assert_eq!(keys, expected);
+
+    let grant: Option<i32> = store
+        .get(SessionKind::PinUnlock, "bearer-token")
+        .await
+        .unwrap();
+    assert_eq!(grant, Some(1));
+    store
+        .delete(SessionKind::PinUnlock, "bearer-token")
+        .await
+        .unwrap();
+    let grant: Option<i32> = store
+        .get(SessionKind::PinUnlock, "bearer-token")
+        .await
+        .unwrap();
+    assert_eq!(grant, None);
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(auth_crypto::eq("token", "token"));
+    assert!(!auth_crypto::eq("token", "tokex"));
+    assert!(!auth_crypto::eq("token", "token2"));
+    assert!(auth_crypto::eq(b"", b""));
+}
+
+#[tokio::test]
+async fn test_put_replaces_existing_record() {
+    let (db, _temp) = setup_test_db().await;
+    let store = SessionStore::new(db);
+
+    store
+        .put(SessionKind::Authentication, "k", &"first", TTL)
+        .await
+        .unwrap();
+    store
+        .put(SessionKind::Authentication, "k", &"second", TTL)
+        .await
+        .unwrap();
+
+    let value: Option<String> = store.get(SessionKind::Authentication, "k").await.unwrap();
+    assert_eq!(value.as_deref(), Some("second"));
+}
+
+#[tokio::test]
+async fn test_expired_records() {
+    let (db, _temp) = setup_test_db().await;
+    let store = SessionStore::new(db);
+
+    store
+        .put(SessionKind::Registration, "old", &1, Duration::ZERO)
+        .await
+        .unwrap();
+    store
+        .put(SessionKind::Registration, "stale", &2, Duration::ZERO)
+        .await
+        .unwrap();
+    store
+        .put(SessionKind::Registration, "fresh", &3, TTL)
+        .await
+        .unwrap();
+
+    let value: Option<i32> = store.get(SessionKind::Registration, "old").await.unwrap();
+    assert_eq!(value, None);
+    let taken: Taken<i32> = store.take(SessionKind::Registration, "old").await.unwrap();
+    assert_eq!(taken, Taken::Expired);
+
+    assert_eq!(store.sweep_expired().await.unwrap(), 1);
+    let fresh: Option<i32> = store.get(SessionKind::Registration, "fresh").await.unwrap();
+    assert_eq!(fresh, Some(3));
+}
+
+#[tokio::test]
+async fn test_ceremonies_and_sessions_allow_clock_skew() {
+    let (db, _temp) = setup_test_db().await;
+    let clock = TestClock::at(Utc::now());
+    let store = SessionStore::with_time(
+        db,
+        TimeSource::new(Arc::new(clock.clone()), Duration::from_secs(30)),
+    );
+
+    for kind in [SessionKind::Authentication, SessionKind::PinUnlock] {
+        store.put(kind, "key", &1, TTL).await.unwrap();
+    }
+    clock.advance(TTL + Duration::from_secs(10));
+
+    // Ceremonies outlive their TTL by the skew allowed; other records don't
+    let ceremony: Option<i32> = store.get(SessionKind::Authentication, "key").await.unwrap();
+    assert_eq!(ceremony, Some(1));
+    let unlock: Option<i32> = store.get(SessionKind::PinUnlock, "key").await.unwrap();
+    assert_eq!(unlock, None);
+    assert_eq!(store.sweep_expired().await.unwrap(), 0);
+
+    clock.advance(Duration::from_secs(20));
+    let taken: Taken<i32> = store
+        .take(SessionKind::Authentication, "key")
+        .await
+        .unwrap();
+    assert_eq!(taken, Taken::Expired);
+    assert_eq!(store.sweep_expired().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_registration_ceremony_is_stored_in_session_store() {
+    let (node, _temp) = setup_test_node().await;
+
+    let (_, challenge_id) = node.start_webauthn_registration().await.unwrap();
+
+    let stored: Option<Value> = node
+        .sessions
+        .get(SessionKind::Registration, &challenge_id)
+        .await
+        .unwrap();
+    assert!(stored.is_some(), "pending registration should be persisted");
+}
+
+#[tokio::test]
+async fn test_pending_ceremony_is_shared_through_database() {
+    let (db, temp) = setup_test_db().await;
+    let first = create_test_node_with_db("shared-node-1", db.clone(), &temp.path().join("kv1"));
+    let (_, challenge_id) = first.start_webauthn_registration().await.unwrap();
+
+    // A node restarted against the same database still sees the ceremony
+    let second = create_test_node_with_db("shared-node-1", db, &temp.path().join("kv2"));
+    let stored: Option<Value> = second
+        .sessions
+        .get(SessionKind::Registration, &challenge_id)
+        .await
+        .unwrap();
+    assert!(stored.is_some());
+}
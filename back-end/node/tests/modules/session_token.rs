@@ -0,0 +1,64 @@
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use node::modules::clock::{TestClock, TimeSource};
+use node::modules::session_token::{self, SessionClaims};
+use std::sync::Arc;
+use std::time::Duration;
+
+const ISSUER: &str = "did:peer:0issuer";
+
+fn claims(expires_in: i64) -> SessionClaims {
+    let now = Utc::now().timestamp();
+    SessionClaims {
+        iss: ISSUER.to_string(),
+        sub: "7".to_string(),
+        sid: "session".to_string(),
+        iat: now,
+        exp: now + expires_in,
+    }
+}
+
+#[test]
+fn test_session_token_round_trip() {
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let claims = claims(60);
+    let token = session_token::sign(&claims, &key).unwrap();
+
+    let verified = session_token::verify(&token, &key.verifying_key(), ISSUER).unwrap();
+    assert_eq!(verified, claims);
+    assert_eq!(verified.user_id(), Some(7));
+    assert!(verified.expires_at() > verified.issued_at());
+}
+
+#[test]
+fn test_session_token_rejects_bad_tokens() {
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let other = SigningKey::from_bytes(&[2; 32]);
+    let token = session_token::sign(&claims(60), &key).unwrap();
+
+    assert!(session_token::verify(&token, &other.verifying_key(), ISSUER).is_none());
+    assert!(session_token::verify(&token, &key.verifying_key(), "did:peer:0other").is_none());
+
+    let expired = session_token::sign(&claims(-1), &key).unwrap();
+    assert!(session_token::verify(&expired, &key.verifying_key(), ISSUER).is_none());
+
+    // Unsigned tokens aren't accepted whatever their header says
+    let (unsigned, _) = token.rsplit_once('.').unwrap();
+    assert!(
+        session_token::verify(&format!("{}.", unsigned), &key.verifying_key(), ISSUER).is_none()
+    );
+    assert!(session_token::verify("not-a-token", &key.verifying_key(), ISSUER).is_none());
+}
+
+#[test]
+fn test_session_token_expiry_allows_clock_skew() {
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let token = session_token::sign(&claims(-30), &key).unwrap();
+    let clock = Arc::new(TestClock::at(Utc::now()));
+
+    let lenient = TimeSource::new(clock.clone(), Duration::from_secs(60));
+    assert!(session_token::verify_at(&token, &key.verifying_key(), ISSUER, &lenient).is_some());
+
+    let strict = TimeSource::new(clock, Duration::from_secs(10));
+    assert!(session_token::verify_at(&token, &key.verifying_key(), ISSUER, &strict).is_none());
+}
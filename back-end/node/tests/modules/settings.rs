@@ -0,0 +1,38 @@
+use crate::bootstrap::init::setup_test_node;
+use node::modules::scheduler::{MAX_RUN_HISTORY, SchedulerSettings};
+
+#[tokio::test]
+async fn test_watchers_see_writes() {
+    let (node, _temp) = setup_test_node().await;
+    let settings = node.settings.clone();
+
+    let mut watcher = settings.watch::<SchedulerSettings>().unwrap();
+    assert_eq!(watcher.borrow().max_run_history, MAX_RUN_HISTORY);
+
+    // Written through a clone: the node's own store
+    node.settings
+        .put(&SchedulerSettings { max_run_history: 7 })
+        .unwrap();
+    watcher.changed().await.unwrap();
+    assert_eq!(watcher.borrow_and_update().max_run_history, 7);
+    assert_eq!(
+        settings.get::<SchedulerSettings>().unwrap().max_run_history,
+        7
+    );
+
+    // Rejected writes change nothing
+    assert!(
+        settings
+            .put(&SchedulerSettings { max_run_history: 0 })
+            .is_err()
+    );
+    assert!(!watcher.has_changed().unwrap());
+    assert_eq!(
+        settings
+            .watch::<SchedulerSettings>()
+            .unwrap()
+            .borrow()
+            .max_run_history,
+        7
+    );
+}
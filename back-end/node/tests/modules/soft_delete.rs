@@ -0,0 +1,69 @@
+use crate::bootstrap::init::setup_test_node;
+use node::modules::tenancy::Actor;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_deleted_space_can_be_restored_until_purged() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.retention.restore_window = Duration::from_secs(60);
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+    node.rebuild_space_merkle(&key).await.unwrap();
+
+    // A deleted space reads as gone
+    let deleted = node.delete_space_as(Actor::Node, &key, None).await.unwrap();
+    assert!(deleted.deleted_at.is_some());
+    assert!(node.list_spaces().await.unwrap().is_empty());
+    assert!(node.find_space_as(Actor::Node, &key).await.is_err());
+    assert!(
+        node.restore_space_as(Actor::User(7), &key, None)
+            .await
+            .is_err()
+    );
+
+    let restored = node
+        .restore_space_as(Actor::Node, &key, None)
+        .await
+        .unwrap();
+    assert_eq!(restored.deleted_at, None);
+    assert_eq!(node.list_spaces().await.unwrap().len(), 1);
+    assert!(
+        node.restore_space_as(Actor::Node, &key, None)
+            .await
+            .is_err()
+    );
+
+    // Creating it again at the same directory restores it too
+    node.delete_space_as(Actor::Node, &key, None).await.unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(node.list_spaces().await.unwrap()[0].key, key);
+
+    // The purge leaves spaces within the window, then drops them and their
+    // Merkle trees; the files stay
+    node.delete_space_as(Actor::Node, &key, None).await.unwrap();
+    assert_eq!(node.purge_deleted().await.unwrap().spaces, 0);
+    node.retention.restore_window = Duration::ZERO;
+    let purged = node.purge_deleted().await.unwrap();
+    assert_eq!(purged.spaces, 1);
+    assert_eq!(purged.space_locations.len(), 1);
+    assert!(
+        node.merkle_index(&key)
+            .unwrap()
+            .entries()
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        node.restore_space_as(Actor::Node, &key, None)
+            .await
+            .is_err()
+    );
+    assert!(dir.path().join("a.txt").exists());
+}
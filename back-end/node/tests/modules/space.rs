@@ -0,0 +1,71 @@
+use errors::AppError;
+use node::modules::space::SpaceWrites;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_read_consistent_without_writes() {
+    let writes = SpaceWrites::new();
+    let reads = AtomicU32::new(0);
+
+    let value = writes
+        .read_consistent("space", || Ok(reads.fetch_add(1, Ordering::SeqCst)))
+        .unwrap();
+
+    assert_eq!(value, 0);
+    assert_eq!(reads.load(Ordering::SeqCst), 1, "One read should be enough");
+    assert_eq!(writes.generation("space"), 0);
+}
+
+#[test]
+fn test_read_consistent_waits_for_write() {
+    let writes = SpaceWrites::new();
+    let write = writes.begin("space");
+
+    let writer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(60));
+        drop(write);
+    });
+
+    let generation = writes
+        .read_consistent("space", || Ok(writes.generation("space")))
+        .unwrap();
+    writer.join().unwrap();
+
+    assert_eq!(generation, 1, "Read should only run once the write ended");
+}
+
+#[test]
+fn test_read_consistent_retries_read_overlapping_write() {
+    let writes = SpaceWrites::new();
+    let reads = AtomicU32::new(0);
+
+    let value = writes
+        .read_consistent("space", || {
+            // The first read races a write that finishes during it
+            if reads.fetch_add(1, Ordering::SeqCst) == 0 {
+                drop(writes.begin("space"));
+            }
+            Ok("snapshot")
+        })
+        .unwrap();
+
+    assert_eq!(value, "snapshot");
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_read_consistent_gives_up_on_busy_space() {
+    let writes = SpaceWrites::new();
+    let _other = writes.begin("other");
+
+    let result = writes.read_consistent("space", || {
+        drop(writes.begin("space"));
+        Ok(())
+    });
+
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+    // Writes to other spaces don't get in the way
+    assert!(writes.read_consistent("space", || Ok(())).is_ok());
+}
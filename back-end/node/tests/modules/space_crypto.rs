@@ -0,0 +1,122 @@
+use node::modules::space_crypto::{self, CHUNK_BYTES, SpaceCipher};
+use std::io::Read;
+use tempfile::TempDir;
+
+fn encrypt(cipher: &SpaceCipher, path: &str, plaintext: &[u8]) -> Vec<u8> {
+    let mut sealed = Vec::new();
+    cipher.encrypt(path, plaintext, &mut sealed).unwrap();
+    sealed
+}
+
+fn decrypt(cipher: &SpaceCipher, path: &str, sealed: &[u8]) -> Option<Vec<u8>> {
+    let mut opened = Vec::new();
+    cipher.decrypt(path, sealed, &mut opened).ok()?;
+    Some(opened)
+}
+
+#[test]
+fn test_round_trip_across_chunk_boundaries() {
+    let cipher = SpaceCipher::new("space-a", &SpaceCipher::generate_key());
+    for len in [0, 1, CHUNK_BYTES - 1, CHUNK_BYTES, 2 * CHUNK_BYTES + 7] {
+        let plaintext: Vec<u8> = (0..len).map(|i| (i % 253) as u8).collect();
+        let sealed = encrypt(&cipher, "a/b.bin", &plaintext);
+        assert!(sealed.starts_with(space_crypto::MAGIC));
+        assert_eq!(
+            space_crypto::plaintext_len(sealed.len() as u64),
+            Some(len as u64)
+        );
+        assert_eq!(decrypt(&cipher, "a/b.bin", &sealed), Some(plaintext));
+    }
+}
+
+#[test]
+fn test_decrypting_reads_in_small_pieces() {
+    let cipher = SpaceCipher::new("space-a", &SpaceCipher::generate_key());
+    let plaintext: Vec<u8> = (0..CHUNK_BYTES * 2 + 100)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let sealed = encrypt(&cipher, "a.bin", &plaintext);
+
+    let mut reader = cipher.decrypting("a.bin", &sealed[..]).unwrap();
+    let mut opened = Vec::new();
+    let mut buf = [0u8; 1000];
+    loop {
+        let len = reader.read(&mut buf).unwrap();
+        if len == 0 {
+            break;
+        }
+        opened.extend_from_slice(&buf[..len]);
+    }
+    assert_eq!(opened, plaintext);
+
+    let mut tampered = sealed.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    let mut reader = cipher.decrypting("a.bin", &tampered[..]).unwrap();
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_files_are_bound_to_space_path_and_length() {
+    let key = SpaceCipher::generate_key();
+    let cipher = SpaceCipher::new("space-a", &key);
+    let plaintext = vec![7u8; CHUNK_BYTES + 10];
+    let sealed = encrypt(&cipher, "a.bin", &plaintext);
+
+    assert_eq!(decrypt(&cipher, "b.bin", &sealed), None);
+    assert_eq!(
+        decrypt(&SpaceCipher::new("space-b", &key), "a.bin", &sealed),
+        None
+    );
+    let other_key = SpaceCipher::new("space-a", &SpaceCipher::generate_key());
+    assert_eq!(decrypt(&other_key, "a.bin", &sealed), None);
+
+    // Dropping the last chunk, or flipping a bit, is caught
+    let header = space_crypto::MAGIC.len() + 19;
+    assert_eq!(
+        decrypt(&cipher, "a.bin", &sealed[..header + CHUNK_BYTES + 16]),
+        None
+    );
+    let mut tampered = sealed.clone();
+    tampered[header + 3] ^= 1;
+    assert_eq!(decrypt(&cipher, "a.bin", &tampered), None);
+}
+
+#[test]
+fn test_read_refuses_plain_files() {
+    let dir = TempDir::new().unwrap();
+    let cipher = SpaceCipher::new("space-a", &SpaceCipher::generate_key());
+
+    let plain = dir.path().join("plain.txt");
+    std::fs::write(&plain, b"swapped in").unwrap();
+    assert!(!space_crypto::is_encrypted(&plain).unwrap());
+    assert!(cipher.read("plain.txt", &plain).is_err());
+
+    let sealed = dir.path().join("sealed.txt");
+    cipher
+        .encrypt_to("sealed.txt", &b"secret"[..], &sealed)
+        .unwrap();
+    assert!(space_crypto::is_encrypted(&sealed).unwrap());
+    assert_eq!(cipher.read("sealed.txt", &sealed).unwrap(), b"secret");
+}
+
+#[test]
+fn test_encrypt_existing_encrypts_each_file_once() {
+    let dir = TempDir::new().unwrap();
+    let cipher = SpaceCipher::new("space-a", &SpaceCipher::generate_key());
+    std::fs::create_dir(dir.path().join("docs")).unwrap();
+    std::fs::write(dir.path().join("a.txt"), b"top").unwrap();
+    std::fs::write(dir.path().join("docs/b.txt"), b"nested").unwrap();
+
+    assert_eq!(cipher.encrypt_existing(dir.path()).unwrap(), 2);
+    assert_eq!(cipher.encrypt_existing(dir.path()).unwrap(), 0);
+
+    let nested = dir.path().join("docs/b.txt");
+    assert!(space_crypto::is_encrypted(&nested).unwrap());
+    assert_eq!(cipher.read("docs/b.txt", &nested).unwrap(), b"nested");
+    assert_eq!(
+        cipher.read("a.txt", &dir.path().join("a.txt")).unwrap(),
+        b"top"
+    );
+}
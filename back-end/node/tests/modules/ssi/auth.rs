@@ -153,12 +153,14 @@ async fn test_start_registration_with_multiple_devices() {
         rp_id: "localhost".to_string(),
         rp_origin: "http://localhost:3000".to_string(),
         rp_name: "Test Flow".to_string(),
+        policy: Default::default(),
+        limits: Default::default(),
     };
 
     let node1 = Node::new(
         NodeData {
             id: "device-1".to_string(),
-            private_key: vec![0u8; 32],
+            private_key: vec![0u8; 32].into(),
             public_key: vec![0u8; 32],
         },
         db.clone(),
@@ -169,7 +171,7 @@ async fn test_start_registration_with_multiple_devices() {
     let node2 = Node::new(
         NodeData {
             id: "device-2".to_string(),
-            private_key: vec![1u8; 32],
+            private_key: vec![1u8; 32].into(),
             public_key: vec![1u8; 32],
         },
         db.clone(),
@@ -209,12 +211,14 @@ async fn test_end_to_end_registration_and_authentication() {
         rp_id: "localhost".to_string(),
         rp_origin: "http://localhost:3000".to_string(),
         rp_name: "Test Flow".to_string(),
+        policy: Default::default(),
+        limits: Default::default(),
     };
 
     let node = Node::new(
         NodeData {
             id: "test-device-e2e".to_string(),
-            private_key: vec![0u8; 32],
+            private_key: vec![0u8; 32].into(),
             public_key: vec![0u8; 32],
         },
         db.clone(),
@@ -423,8 +427,11 @@ async fn test_finish_registration_creates_user_and_did() {
 
     // 9. Verify passkey JSON data can be deserialized
     use webauthn_rs::prelude::Passkey;
-    let _passkey: Passkey = serde_json::from_str(&stored_passkey.json_data)
-        .expect("Should be able to deserialize passkey JSON");
+    let passkey_json = stored_passkey
+        .passkey_json(&node.column_keys)
+        .expect("Should be able to decrypt passkey JSON");
+    let _passkey: Passkey =
+        serde_json::from_str(&passkey_json).expect("Should be able to deserialize passkey JSON");
 
     // ===== VERIFY DID DOCUMENT STRUCTURE =====
 
@@ -452,7 +459,9 @@ async fn test_finish_registration_creates_user_and_did() {
 #[tokio::test]
 async fn test_store_passkey_success() {
     use entity::user;
+    use node::modules::column_crypto::ColumnKeys;
     use node::modules::ssi::webauthn::auth::store_passkey;
+    use node::modules::ssi::webauthn::policy::CeremonyPolicy;
     use sea_orm::{ActiveModelTrait, NotSet, Set};
     use webauthn_rs::prelude::Passkey;
 
@@ -464,10 +473,13 @@ async fn test_store_passkey_success() {
         did: Set("did:key:test123".to_string()),
         username: Set("test_user".to_string()),
         display_name: Set("Test User".to_string()),
+        avatar_url: Set(None),
         device_ids: Set(r#"["test-device-123"]"#.to_string()),
         public_key_jwk: Set("{}".to_string()),
         time_created: Set(chrono::Utc::now().into()),
         last_login: Set(chrono::Utc::now().into()),
+        version: Set(1),
+        deleted_at: Set(None),
     };
 
     let user_model = test_user.insert(&db).await.unwrap();
@@ -478,7 +490,16 @@ async fn test_store_passkey_success() {
     let device_id = "test-device-store-123";
 
     // Call the actual store_passkey function from auth.rs
-    let result = store_passkey(&db, user_model.id, device_id, &passkey).await;
+    let keys = ColumnKeys::from_identity(&[7u8; 32]);
+    let result = store_passkey(
+        &db,
+        &keys,
+        user_model.id,
+        device_id,
+        &passkey,
+        &CeremonyPolicy::default(),
+    )
+    .await;
 
     assert!(
         result.is_ok(),
@@ -509,13 +530,17 @@ async fn test_store_passkey_success() {
     assert_eq!(stored.device_id, device_id);
     assert_eq!(stored.sign_count, 0);
     assert_eq!(stored.authentication_count, 0);
+    assert_eq!(stored.user_verification, "required");
+    assert_eq!(stored.authenticator_attachment, None);
 
     // Verify the credential_id matches
     let expected_cred_id: Vec<u8> = passkey.cred_id().as_ref().to_vec();
     assert_eq!(stored.credential_id, expected_cred_id);
 
-    // Verify the JSON data can be deserialized back to a Passkey
-    let deserialized_passkey: Passkey = serde_json::from_str(&stored.json_data).unwrap();
+    // Verify the JSON data is encrypted at rest and decrypts back to a Passkey
+    assert!(stored.json_data.starts_with("enc:v1:"));
+    let deserialized_passkey: Passkey =
+        serde_json::from_str(&stored.passkey_json(&keys).unwrap()).unwrap();
     assert_eq!(
         deserialized_passkey.cred_id(),
         passkey.cred_id(),
@@ -528,6 +553,7 @@ async fn test_store_passkey_success() {
 #[tokio::test]
 async fn test_get_passkeys_for_device() {
     use entity::user;
+    use node::modules::column_crypto::ColumnKeys;
     use node::modules::ssi::webauthn::auth::get_passkeys_for_device;
     use sea_orm::{ActiveModelTrait, NotSet, Set};
     use webauthn_rs::prelude::Passkey; // Import the actual function
@@ -545,10 +571,13 @@ async fn test_get_passkeys_for_device() {
         did: Set("did:key:test456".to_string()),
         username: Set("test_user".to_string()),
         display_name: Set("Test User".to_string()),
+        avatar_url: Set(None),
         device_ids: Set(r#"["device-A", "device-B"]"#.to_string()),
         public_key_jwk: Set("{}".to_string()),
         time_created: Set(chrono::Utc::now().into()),
         last_login: Set(chrono::Utc::now().into()),
+        version: Set(1),
+        deleted_at: Set(None),
     };
 
     let user_model = test_user.insert(&db).await.unwrap();
@@ -615,6 +644,12 @@ async fn test_get_passkeys_for_device() {
             json_data: Set(passkey_json),
             time_created: Set(chrono::Utc::now().into()),
             last_authenticated: Set(chrono::Utc::now().into()),
+            user_verification: Set("required".to_string()),
+            authenticator_attachment: Set(None),
+            backup_eligible: Set(false),
+            backup_state: Set(false),
+            version: Set(1),
+            deleted_at: Set(None),
         };
 
         new_passkey.insert(&db).await.unwrap();
@@ -639,14 +674,23 @@ async fn test_get_passkeys_for_device() {
         json_data: Set(passkey_json_b),
         time_created: Set(chrono::Utc::now().into()),
         last_authenticated: Set(chrono::Utc::now().into()),
+        user_verification: Set("required".to_string()),
+        authenticator_attachment: Set(None),
+        backup_eligible: Set(false),
+        backup_state: Set(false),
+        version: Set(1),
+        deleted_at: Set(None),
     };
 
     new_passkey_b.insert(&db).await.unwrap();
 
     info!("Stored 2 passkeys for device-A and 1 for device-B");
 
+    // Rows were written as plaintext, as before column encryption existed
+    let keys = ColumnKeys::from_identity(&[7u8; 32]);
+
     // Test: Get passkeys for device-A using the actual get_passkeys_for_device function
-    let passkeys_a = get_passkeys_for_device(&db, "device-A")
+    let passkeys_a = get_passkeys_for_device(&db, &keys, "device-A")
         .await
         .expect("Should successfully retrieve passkeys for device-A");
 
@@ -657,7 +701,7 @@ async fn test_get_passkeys_for_device() {
     );
 
     // Test: Get passkeys for device-B
-    let passkeys_b = get_passkeys_for_device(&db, "device-B")
+    let passkeys_b = get_passkeys_for_device(&db, &keys, "device-B")
         .await
         .expect("Should successfully retrieve passkeys for device-B");
 
@@ -668,7 +712,7 @@ async fn test_get_passkeys_for_device() {
     );
 
     // Test: Get passkeys for non-existent device
-    let passkeys_none = get_passkeys_for_device(&db, "device-C")
+    let passkeys_none = get_passkeys_for_device(&db, &keys, "device-C")
         .await
         .expect("Should return empty vec for non-existent device");
 
@@ -976,3 +1020,62 @@ async fn test_authentication_with_tampered_credential_fails_at_server() {
     info!("✓ Server correctly rejected replay attack");
     info!("  This validates server-side challenge verification");
 }
+
+// ========== Credential Bookkeeping Tests ==========
+
+#[tokio::test]
+async fn test_authentication_updates_the_credential_used() {
+    use entity::pass_key;
+    use sea_orm::{EntityTrait, QueryOrder};
+
+    let (node, _temp) = setup_test_node_with_device_id("test-device-two-keys").await;
+    let origin = Url::parse("http://localhost:3000").unwrap();
+
+    // Register two authenticators on the same device
+    let mut authenticators = [SoftPasskey::new(true), SoftPasskey::new(true)];
+    for authenticator in authenticators.iter_mut() {
+        let (ccr, challenge_id) = node.start_webauthn_registration().await.unwrap();
+        let credential = authenticator
+            .perform_register(origin.clone(), ccr.public_key, 60000)
+            .unwrap();
+        node.finish_webauthn_registration(&challenge_id, credential)
+            .await
+            .unwrap();
+    }
+
+    let before = pass_key::Entity::find()
+        .order_by_asc(pass_key::Column::Id)
+        .all(&node.db)
+        .await
+        .unwrap();
+    assert_eq!(before.len(), 2);
+
+    // Authenticate twice with the second one
+    for _ in 0..2 {
+        let (rcr, challenge_id) = node.start_webauthn_authentication().await.unwrap();
+        let assertion = authenticators[1]
+            .perform_auth(origin.clone(), rcr.public_key, 60000)
+            .unwrap();
+        node.finish_webauthn_authentication(&challenge_id, assertion)
+            .await
+            .unwrap();
+    }
+
+    let after = pass_key::Entity::find()
+        .order_by_asc(pass_key::Column::Id)
+        .all(&node.db)
+        .await
+        .unwrap();
+
+    // The first credential is untouched
+    assert_eq!(after[0], before[0]);
+
+    assert_eq!(after[1].sign_count, 2);
+    assert_eq!(after[1].authentication_count, 2);
+    assert!(after[1].last_authenticated > before[1].last_authenticated);
+
+    // The stored passkey carries the new counter, so a replayed counter is caught
+    let json: serde_json::Value =
+        serde_json::from_str(&after[1].passkey_json(&node.column_keys).unwrap()).unwrap();
+    assert_eq!(json["cred"]["counter"], 2);
+}
@@ -0,0 +1,124 @@
+use crate::bootstrap::init::setup_test_node_with_device_id;
+use entity::pass_key;
+use event::source::EventListener;
+use event::types::{Event, EventType};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, EntityTrait};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use webauthn_authenticator_rs::{AuthenticatorBackend, softpasskey::SoftPasskey};
+use webauthn_rs::prelude::Url;
+
+const ORIGIN: &str = "http://localhost:3000";
+
+struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+impl EventListener for Recorder {
+    fn handle(&self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_backup_state_transition_is_persisted_and_announced() {
+    let (node, _temp) = setup_test_node_with_device_id("backup-state-01").await;
+    let events = Arc::new(Mutex::new(Vec::new()));
+    node.subscribe(Box::new(Recorder(events.clone())));
+    let mut authenticator = SoftPasskey::new(true);
+
+    let (ccr, challenge_id) = node.start_webauthn_registration().await.unwrap();
+    let credential = authenticator
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_registration(&challenge_id, credential)
+        .await
+        .unwrap();
+
+    // The soft authenticator never backs up its keys
+    let stored = pass_key::Entity::find()
+        .one(&node.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!stored.backup_eligible);
+    assert!(!stored.backup_state);
+
+    // Pretend the credential was backed up when last seen
+    let mut json: serde_json::Value =
+        serde_json::from_str(&stored.passkey_json(&node.column_keys).unwrap()).unwrap();
+    json["cred"]["backup_state"] = true.into();
+    let mut model: pass_key::ActiveModel = stored.clone().into();
+    model.json_data =
+        Set(
+            pass_key::encrypt_json_data(&node.column_keys, stored.user_id, &json.to_string())
+                .unwrap(),
+        );
+    model.backup_state = Set(true);
+    model.update(&node.db).await.unwrap();
+
+    let (rcr, challenge_id) = node.start_webauthn_authentication().await.unwrap();
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), rcr.public_key, 60000)
+        .unwrap();
+    let result = node
+        .finish_webauthn_authentication(&challenge_id, assertion)
+        .await
+        .unwrap();
+    assert!(!result.backup_state());
+
+    let updated = pass_key::Entity::find()
+        .one(&node.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!updated.backup_eligible);
+    assert!(!updated.backup_state);
+    let json: serde_json::Value =
+        serde_json::from_str(&updated.passkey_json(&node.column_keys).unwrap()).unwrap();
+    assert_eq!(json["cred"]["backup_state"], false);
+
+    let events = events.lock().unwrap();
+    let changed: Vec<_> = events
+        .iter()
+        .filter(|e| e.event_type == EventType::CredentialBackupChanged)
+        .collect();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].properties["previous_backup_state"], true);
+    assert_eq!(changed[0].properties["backup_state"], false);
+}
+
+#[tokio::test]
+async fn test_unchanged_backup_state_is_not_announced() {
+    let (node, _temp) = setup_test_node_with_device_id("backup-state-02").await;
+    let events = Arc::new(Mutex::new(Vec::new()));
+    node.subscribe(Box::new(Recorder(events.clone())));
+    let mut authenticator = SoftPasskey::new(true);
+
+    let (ccr, challenge_id) = node.start_webauthn_registration().await.unwrap();
+    let credential = authenticator
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_registration(&challenge_id, credential)
+        .await
+        .unwrap();
+
+    let (rcr, challenge_id) = node.start_webauthn_authentication().await.unwrap();
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), rcr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_authentication(&challenge_id, assertion)
+        .await
+        .unwrap();
+
+    let events = events.lock().unwrap();
+    assert!(
+        events
+            .iter()
+            .all(|e| e.event_type != EventType::CredentialBackupChanged)
+    );
+    assert!(
+        events
+            .iter()
+            .any(|e| e.event_type == EventType::CredentialVerified)
+    );
+}
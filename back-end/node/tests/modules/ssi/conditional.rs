@@ -0,0 +1,98 @@
+use crate::bootstrap::init::setup_test_node_with_device_id;
+use webauthn_authenticator_rs::{AuthenticatorBackend, softpasskey::SoftPasskey};
+use webauthn_rs::prelude::Url;
+use webauthn_rs_proto::{Mediation, PublicKeyCredentialHints};
+
+const ORIGIN: &str = "http://localhost:3000";
+
+#[tokio::test]
+async fn test_conditional_options_suit_autofill() {
+    let (node, _temp) = setup_test_node_with_device_id("conditional-opts-01").await;
+
+    let (rcr, challenge_id) = node
+        .start_webauthn_conditional_authentication()
+        .await
+        .expect("conditional authentication starts without registered passkeys");
+
+    assert!(!challenge_id.is_empty());
+    assert!(matches!(rcr.mediation, Some(Mediation::Conditional)));
+    assert!(rcr.public_key.allow_credentials.is_empty());
+    assert_eq!(
+        rcr.public_key.hints,
+        Some(vec![
+            PublicKeyCredentialHints::ClientDevice,
+            PublicKeyCredentialHints::Hybrid
+        ])
+    );
+
+    let json = serde_json::to_value(&rcr).unwrap();
+    assert_eq!(json["mediation"], "conditional");
+}
+
+#[tokio::test]
+async fn test_conditional_authentication_with_discovered_credential() {
+    let (node, _temp) = setup_test_node_with_device_id("conditional-flow-01").await;
+    let mut authenticator = SoftPasskey::new(true);
+
+    let (ccr, challenge_id) = node.start_webauthn_registration().await.unwrap();
+    let credential = authenticator
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_registration(&challenge_id, credential)
+        .await
+        .unwrap();
+
+    let (rcr, challenge_id) = node
+        .start_webauthn_conditional_authentication()
+        .await
+        .unwrap();
+
+    // The soft authenticator can't discover credentials, so hand it the one
+    // a browser would have picked from its autofill list
+    let (listed, _) = node.start_webauthn_authentication().await.unwrap();
+    let mut options = rcr.public_key;
+    options.allow_credentials = listed.public_key.allow_credentials;
+
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), options, 60000)
+        .unwrap();
+    let result = node
+        .finish_webauthn_authentication(&challenge_id, assertion)
+        .await
+        .expect("discovered credential should authenticate");
+    assert!(result.user_verified());
+}
+
+#[tokio::test]
+async fn test_conditional_authentication_rejects_unknown_credential() {
+    let (node, _temp) = setup_test_node_with_device_id("conditional-unknown-01").await;
+    let (other, _other_temp) = setup_test_node_with_device_id("conditional-other-01").await;
+    let mut authenticator = SoftPasskey::new(true);
+
+    // Registered with a different node, so unknown here
+    let (ccr, challenge_id) = other.start_webauthn_registration().await.unwrap();
+    let credential = authenticator
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    other
+        .finish_webauthn_registration(&challenge_id, credential)
+        .await
+        .unwrap();
+    let (listed, _) = other.start_webauthn_authentication().await.unwrap();
+
+    let (rcr, challenge_id) = node
+        .start_webauthn_conditional_authentication()
+        .await
+        .unwrap();
+    let mut options = rcr.public_key;
+    options.allow_credentials = listed.public_key.allow_credentials;
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), options, 60000)
+        .unwrap();
+
+    assert!(
+        node.finish_webauthn_authentication(&challenge_id, assertion)
+            .await
+            .is_err()
+    );
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use node::modules::ssi::did::DidResolver;
+use node::modules::ssi::did::resolvers::cache::{
+    CachedResolution, DidCache, KvDidCache, MemoryDidCache, cache_key,
+};
+use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+use node::modules::ssi::did::types::ResolutionOptions;
+
+fn peer_did(seed: u8) -> String {
+    PeerDidGenerator::from_ed25519_bytes(&[seed; 32]).unwrap()
+}
+
+async fn cached_entry(did: &str) -> CachedResolution {
+    let result = DidResolver::new()
+        .without_cache()
+        .resolve_did(did, &ResolutionOptions::new())
+        .await
+        .unwrap();
+    CachedResolution::from_result(&result, Utc::now()).unwrap()
+}
+
+#[tokio::test]
+async fn test_repeat_resolution_is_served_from_cache() {
+    let resolver = DidResolver::new();
+    let did = peer_did(1);
+
+    let first = resolver
+        .resolve_did(&did, &ResolutionOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(first.did_resolution_metadata.from_cache, Some(false));
+
+    let second = resolver
+        .resolve_did(&did, &ResolutionOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(second.did_resolution_metadata.from_cache, Some(true));
+    assert_eq!(second.did_document, first.did_document);
+
+    let bypassed = resolver
+        .resolve_did(&did, &ResolutionOptions::new().no_cache())
+        .await
+        .unwrap();
+    assert_eq!(bypassed.did_resolution_metadata.from_cache, Some(false));
+}
+
+#[tokio::test]
+async fn test_expired_entries_are_resolved_again() {
+    let cache = Arc::new(MemoryDidCache::default());
+    let resolver = DidResolver::new().with_cache(cache.clone());
+    let did = peer_did(2);
+
+    let mut stale = cached_entry(&did).await;
+    stale.expires_at = Some(Utc::now() - Duration::seconds(1));
+    cache.put(&did, stale);
+
+    let result = resolver
+        .resolve_did(&did, &ResolutionOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(result.did_resolution_metadata.from_cache, Some(false));
+    // Replaced by a fresh, non-expiring entry
+    assert!(cache.get(&did).unwrap().is_fresh(Utc::now()));
+}
+
+#[tokio::test]
+async fn test_memory_cache_evicts_least_recently_used() {
+    let cache = MemoryDidCache::new(2);
+    let entry = cached_entry(&peer_did(3)).await;
+
+    cache.put("a", entry.clone());
+    cache.put("b", entry.clone());
+    cache.get("a");
+    cache.put("c", entry);
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("a").is_some());
+    assert!(cache.get("b").is_none());
+    assert!(cache.get("c").is_some());
+}
+
+#[tokio::test]
+async fn test_kv_cache_outlives_the_resolver() {
+    let kv = sled::Config::new().temporary(true).open().unwrap();
+    let did = peer_did(4);
+
+    let first = DidResolver::new().with_cache(Arc::new(KvDidCache::open(&kv).unwrap()));
+    first
+        .resolve_did(&did, &ResolutionOptions::new())
+        .await
+        .unwrap();
+
+    let second = DidResolver::new().with_cache(Arc::new(KvDidCache::open(&kv).unwrap()));
+    let result = second
+        .resolve_did(&did, &ResolutionOptions::new())
+        .await
+        .unwrap();
+    assert_eq!(result.did_resolution_metadata.from_cache, Some(true));
+}
+
+#[tokio::test]
+async fn test_kv_cache_purge() {
+    let kv = sled::Config::new().temporary(true).open().unwrap();
+    let cache = KvDidCache::open(&kv).unwrap();
+    let now = Utc::now();
+
+    let fresh = cached_entry(&peer_did(6)).await;
+    cache.put("fresh", fresh.clone());
+    let mut old = fresh.clone();
+    old.cached_at = Some(now - Duration::days(40));
+    cache.put("old", old);
+    let mut expired = fresh.clone();
+    expired.expires_at = Some(now - Duration::seconds(1));
+    cache.put("expired", expired);
+    let mut unknown_age = fresh;
+    unknown_age.cached_at = None;
+    cache.put("unknown-age", unknown_age);
+
+    let before = now - Duration::days(30);
+    assert_eq!(cache.purge(before, now, true).unwrap(), 3);
+    assert!(cache.get("old").is_some());
+    assert_eq!(cache.purge(before, now, false).unwrap(), 3);
+    assert!(cache.get("fresh").is_some());
+    for key in ["old", "expired", "unknown-age"] {
+        assert!(cache.get(key).is_none(), "{}", key);
+    }
+}
+
+#[test]
+fn test_cache_key_includes_representation() {
+    let did = peer_did(5);
+    let json = ResolutionOptions::new()
+        .with_accept("application/did+json")
+        .unwrap();
+    let json_ld = ResolutionOptions::new()
+        .with_accept("application/did+ld+json")
+        .unwrap();
+
+    assert_eq!(cache_key(&did, &ResolutionOptions::new()), did);
+    assert_ne!(cache_key(&did, &json), cache_key(&did, &json_ld));
+}
@@ -601,6 +601,61 @@ mod resolver_config_tests {
     }
 }
 
+// ============================================================================
+// BATCH RESOLUTION
+// ============================================================================
+
+mod batch_tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_resolve_many_keeps_order_and_failures() {
+        let resolver = DidResolver::new().with_parallelism(2);
+        assert_eq!(resolver.parallelism(), 2);
+        let did = PeerDidGenerator::from_ed25519_bytes(&[1u8; 32]).unwrap();
+        let dids = ["did:peer:0zINVALID", &did, "did:unknown:123", &did];
+
+        let results = resolver
+            .resolve_many(&dids, &ResolutionOptions::default())
+            .await;
+        assert_eq!(results.len(), dids.len());
+        for (index, (resolved, did)) in results.iter().zip(dids).enumerate() {
+            assert_eq!(resolved.index, index);
+            assert_eq!(resolved.did, did);
+        }
+        assert!(results[0].result.is_err());
+        assert!(matches!(
+            results[2].result,
+            Err(ResolutionError::MethodNotSupported(_))
+        ));
+        for resolved in [&results[1], &results[3]] {
+            let document = resolved.result.as_ref().unwrap().did_document.as_ref();
+            assert_eq!(document.unwrap().id.as_str(), did);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_stream_yields_every_did() {
+        let resolver = Arc::new(DidResolver::new().with_parallelism(0));
+        assert_eq!(resolver.parallelism(), 1);
+
+        let did = PeerDidGenerator::from_ed25519_bytes(&[2u8; 32]).unwrap();
+        let dids = vec![did, "did:peer:0zINVALID".to_string()];
+        let mut results: Vec<_> = resolver
+            .resolve_stream(dids, ResolutionOptions::default())
+            .collect()
+            .await;
+        results.sort_by_key(|resolved| resolved.index);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].result.is_ok());
+        assert_eq!(results[1].did, "did:peer:0zINVALID");
+        assert!(results[1].result.is_err());
+    }
+}
+
 // ============================================================================
 // PERFORMANCE TESTS
 // ============================================================================
@@ -0,0 +1,115 @@
+use crate::bootstrap::init::setup_test_node_with_device_id;
+use errors::AppError;
+use node::modules::ssi::webauthn::fingerprint::{self, ClientFingerprint};
+use std::net::IpAddr;
+use webauthn_authenticator_rs::{AuthenticatorBackend, softpasskey::SoftPasskey};
+use webauthn_rs::prelude::Url;
+
+const ORIGIN: &str = "http://localhost:3000";
+
+fn client(ip: &str, user_agent: &str) -> ClientFingerprint {
+    ClientFingerprint::new(Some(ip.parse::<IpAddr>().unwrap()), Some(user_agent))
+}
+
+#[test]
+fn test_fingerprint_uses_network_prefix() {
+    let a = client("192.168.1.20", "Firefox");
+    assert_eq!(a.network.as_deref(), Some("192.168.1.0/24"));
+    assert!(!a.user_agent.as_deref().unwrap().contains("Firefox"));
+
+    // Same /24 and user agent
+    assert_eq!(a, client("192.168.1.99", "Firefox"));
+    assert_eq!(a, client("::ffff:192.168.1.7", "Firefox"));
+    assert_ne!(a, client("192.168.2.20", "Firefox"));
+    assert_ne!(a, client("192.168.1.20", "Chrome"));
+
+    assert_eq!(
+        client("2001:db8:1:2:3:4:5:6", "Firefox").network.as_deref(),
+        Some("2001:db8:1:2::/64")
+    );
+}
+
+#[test]
+fn test_unbound_ceremonies_match_any_client() {
+    let a = client("10.0.0.1", "Firefox");
+
+    assert!(fingerprint::matches(None, Some(&a)));
+    assert!(fingerprint::matches(None, None));
+    assert!(fingerprint::matches(Some(&a), Some(&a)));
+    assert!(!fingerprint::matches(Some(&a), None));
+}
+
+#[tokio::test]
+async fn test_registration_rejected_from_other_client() {
+    let (node, _temp) = setup_test_node_with_device_id("fingerprint-reg-01").await;
+    let issued = client("10.0.0.1", "Firefox");
+
+    let (ccr, challenge_id) = node
+        .start_webauthn_registration_for(Some(&issued))
+        .await
+        .unwrap();
+    let credential = SoftPasskey::new(true)
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+
+    let result = node
+        .finish_webauthn_registration_for(
+            &challenge_id,
+            credential,
+            Some(&client("172.16.5.1", "Firefox")),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::Forbidden(_))));
+}
+
+#[tokio::test]
+async fn test_authentication_bound_to_client() {
+    let (node, _temp) = setup_test_node_with_device_id("fingerprint-auth-01").await;
+    let issued = client("10.0.0.1", "Firefox");
+    let mut authenticator = SoftPasskey::new(true);
+
+    let (ccr, challenge_id) = node
+        .start_webauthn_registration_for(Some(&issued))
+        .await
+        .unwrap();
+    let credential = authenticator
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_registration_for(&challenge_id, credential, Some(&issued))
+        .await
+        .unwrap();
+
+    // A relayed challenge fails, and the challenge is spent
+    let (rcr, challenge_id) = node
+        .start_webauthn_authentication_for(Some(&issued))
+        .await
+        .unwrap();
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), rcr.public_key, 60000)
+        .unwrap();
+    let relayed = node
+        .finish_webauthn_authentication_for(
+            &challenge_id,
+            assertion.clone(),
+            Some(&client("10.0.0.1", "Chrome")),
+        )
+        .await;
+    assert!(matches!(relayed, Err(AppError::Forbidden(_))));
+    assert!(
+        node.finish_webauthn_authentication_for(&challenge_id, assertion, Some(&issued))
+            .await
+            .is_err()
+    );
+
+    // The original client can finish a fresh one
+    let (rcr, challenge_id) = node
+        .start_webauthn_authentication_for(Some(&issued))
+        .await
+        .unwrap();
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), rcr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_authentication_for(&challenge_id, assertion, Some(&issued))
+        .await
+        .unwrap();
+}
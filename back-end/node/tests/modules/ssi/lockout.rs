@@ -0,0 +1,140 @@
+use crate::bootstrap::init::{setup_test_db, setup_test_node_with_device_id};
+use errors::AppError;
+use node::modules::session::SessionStore;
+use node::modules::ssi::webauthn::fingerprint::ClientFingerprint;
+use node::modules::ssi::webauthn::lockout::{
+    AttemptKey, AuthGuard, CREDENTIAL_LOCKOUT_THRESHOLD, FREE_ATTEMPTS, attempt_keys,
+};
+use webauthn_authenticator_rs::{AuthenticatorBackend, softpasskey::SoftPasskey};
+use webauthn_rs::prelude::Url;
+
+const ORIGIN: &str = "http://localhost:3000";
+
+#[test]
+fn test_attempt_keys() {
+    let client = ClientFingerprint::new(Some("10.1.2.3".parse().unwrap()), None);
+    assert_eq!(
+        attempt_keys("cred", Some(&client)),
+        vec![
+            AttemptKey::Credential("cred".to_string()),
+            AttemptKey::Network("10.1.2.0/24".to_string())
+        ]
+    );
+    assert_eq!(attempt_keys("cred", None).len(), 1);
+
+    let key: AttemptKey = "network:10.1.2.0/24".parse().unwrap();
+    assert_eq!(key.to_string(), "network:10.1.2.0/24");
+    assert!("nonsense".parse::<AttemptKey>().is_err());
+}
+
+#[tokio::test]
+async fn test_delays_start_after_free_attempts() {
+    let (db, _temp) = setup_test_db().await;
+    let guard = AuthGuard::new(SessionStore::new(db));
+    let keys = [AttemptKey::Credential("cred".to_string())];
+
+    for _ in 0..FREE_ATTEMPTS {
+        guard.record_failure(&keys).await.unwrap();
+        guard.check(&keys).await.unwrap();
+    }
+
+    guard.record_failure(&keys).await.unwrap();
+    assert!(matches!(
+        guard.check(&keys).await,
+        Err(AppError::RateLimited(_))
+    ));
+
+    // Other keys are unaffected
+    guard
+        .check(&[AttemptKey::Credential("other".to_string())])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_lockout_escalates_and_can_be_cleared() {
+    let (db, _temp) = setup_test_db().await;
+    let guard = AuthGuard::new(SessionStore::new(db));
+    let key = AttemptKey::Credential("cred".to_string());
+
+    let mut lockouts = Vec::new();
+    for _ in 0..2 {
+        for attempt in 1..=CREDENTIAL_LOCKOUT_THRESHOLD {
+            let locked = guard
+                .record_failure(std::slice::from_ref(&key))
+                .await
+                .unwrap();
+            if attempt < CREDENTIAL_LOCKOUT_THRESHOLD {
+                assert!(locked.is_empty());
+            } else {
+                assert_eq!(locked.len(), 1);
+                lockouts.push(locked[0].1);
+            }
+        }
+    }
+    // The second lockout lasts longer than the first
+    assert!(lockouts[1] - lockouts[0] > chrono::Duration::minutes(10));
+
+    let records = guard.records().await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].0, key);
+    assert_eq!(records[0].1.lockouts, 2);
+    assert!(guard.check(std::slice::from_ref(&key)).await.is_err());
+
+    guard.clear(&key).await.unwrap();
+    guard.check(&[key]).await.unwrap();
+    assert!(guard.records().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_concurrent_failures_all_count() {
+    let (db, _temp) = setup_test_db().await;
+    let guard = AuthGuard::new(SessionStore::new(db));
+    let key = AttemptKey::Credential("cred".to_string());
+
+    let results = futures_util::future::join_all(
+        (0..CREDENTIAL_LOCKOUT_THRESHOLD).map(|_| guard.record_failure(std::slice::from_ref(&key))),
+    )
+    .await;
+
+    // Exactly the last failure crosses the threshold
+    let locked: Vec<_> = results.into_iter().flat_map(Result::unwrap).collect();
+    assert_eq!(locked.len(), 1);
+    assert_eq!(guard.records().await.unwrap()[0].1.lockouts, 1);
+}
+
+#[tokio::test]
+async fn test_failed_finishes_are_throttled() {
+    let (node, _temp) = setup_test_node_with_device_id("lockout-node-01").await;
+    let mut authenticator = SoftPasskey::new(true);
+
+    let (ccr, challenge_id) = node.start_webauthn_registration().await.unwrap();
+    let credential = authenticator
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_registration(&challenge_id, credential)
+        .await
+        .unwrap();
+
+    let (rcr, _) = node.start_webauthn_authentication().await.unwrap();
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), rcr.public_key, 60000)
+        .unwrap();
+
+    // Replaying against unknown challenges counts against the credential
+    for _ in 0..=FREE_ATTEMPTS {
+        let result = node
+            .finish_webauthn_authentication("bogus-challenge", assertion.clone())
+            .await;
+        assert!(matches!(result, Err(AppError::Auth(_))));
+    }
+    let result = node
+        .finish_webauthn_authentication("bogus-challenge", assertion.clone())
+        .await;
+    assert!(matches!(result, Err(AppError::RateLimited(_))));
+
+    let records = node.auth_guard().records().await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].0, AttemptKey::Credential(assertion.id.clone()));
+    assert_eq!(records[0].1.failures, FREE_ATTEMPTS + 1);
+}
@@ -1,5 +1,13 @@
 pub mod auth;
+pub mod backup_state;
+pub mod conditional;
 pub mod did;
+pub mod did_cache;
 pub mod did_resolver;
+pub mod fingerprint;
 pub mod fixtures;
+pub mod lockout;
+pub mod options;
+pub mod policy;
 pub mod resolvers;
+pub mod vc;
@@ -0,0 +1,81 @@
+use crate::bootstrap::init::setup_test_node_with_device_id;
+use errors::AppError;
+use node::modules::ssi::webauthn::options::{
+    Attestation, CeremonyLimits, CeremonyOverrides, DEFAULT_TIMEOUT, Hint,
+};
+use webauthn_rs_proto::{AttestationConveyancePreference, PublicKeyCredentialHints};
+
+#[test]
+fn test_defaults_without_overrides() {
+    let limits = CeremonyLimits::default();
+
+    let options = limits.registration(&CeremonyOverrides::default()).unwrap();
+    assert_eq!(options.timeout(), DEFAULT_TIMEOUT);
+    assert_eq!(options.attestation, Some(Attestation::None));
+    assert!(options.hints.is_empty());
+
+    let options = limits
+        .authentication(&CeremonyOverrides::default())
+        .unwrap();
+    assert_eq!(options.attestation, None);
+}
+
+#[test]
+fn test_overrides_outside_limits_are_rejected() {
+    let limits = CeremonyLimits::default();
+
+    let too_short = CeremonyOverrides {
+        timeout: Some(1000),
+        ..Default::default()
+    };
+    assert!(matches!(
+        limits.authentication(&too_short),
+        Err(AppError::Validation(_))
+    ));
+
+    let direct = CeremonyOverrides {
+        attestation: Some(Attestation::Direct),
+        ..Default::default()
+    };
+    assert!(matches!(
+        limits.registration(&direct),
+        Err(AppError::Validation(_))
+    ));
+
+    let permissive = CeremonyLimits {
+        attestation: Attestation::parse_list("none, direct").unwrap(),
+        ..Default::default()
+    };
+    assert_eq!(
+        permissive.registration(&direct).unwrap().attestation,
+        Some(Attestation::Direct)
+    );
+    assert!(Attestation::parse_list("none,sometimes").is_err());
+}
+
+#[tokio::test]
+async fn test_overrides_are_applied_to_ceremony_options() {
+    let (mut node, _temp) = setup_test_node_with_device_id("options-applied-01").await;
+    node.auth_state.limits.attestation = vec![Attestation::None, Attestation::Indirect];
+
+    let overrides = CeremonyOverrides {
+        timeout: Some(60_000),
+        attestation: Some(Attestation::Indirect),
+        hints: Some(vec![Hint::SecurityKey]),
+    };
+    let (ccr, _, options) = node
+        .start_webauthn_registration_with(None, &overrides)
+        .await
+        .unwrap();
+
+    assert_eq!(options.timeout, 60_000);
+    assert_eq!(ccr.public_key.timeout, Some(60_000));
+    assert!(matches!(
+        ccr.public_key.attestation,
+        Some(AttestationConveyancePreference::Indirect)
+    ));
+    assert_eq!(
+        ccr.public_key.hints,
+        Some(vec![PublicKeyCredentialHints::SecurityKey])
+    );
+}
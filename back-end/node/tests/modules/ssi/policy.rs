@@ -0,0 +1,155 @@
+use crate::bootstrap::init::setup_test_node_with_device_id;
+use entity::pass_key;
+use node::modules::ssi::webauthn::policy::{
+    Attachment, CeremonyPolicy, ResidentKey, UserVerification,
+};
+use sea_orm::EntityTrait;
+use webauthn_authenticator_rs::{AuthenticatorBackend, softpasskey::SoftPasskey};
+use webauthn_rs::prelude::{AuthenticatorAttachment, Url};
+use webauthn_rs_proto::{ResidentKeyRequirement, UserVerificationPolicy};
+
+const ORIGIN: &str = "http://localhost:3000";
+
+#[test]
+fn test_parse_policy_values() {
+    assert_eq!(
+        "Preferred".parse::<UserVerification>().unwrap(),
+        UserVerification::Preferred
+    );
+    assert!("sometimes".parse::<UserVerification>().is_err());
+
+    assert_eq!(Attachment::parse("any").unwrap(), None);
+    assert_eq!(
+        Attachment::parse("cross-platform").unwrap(),
+        Some(Attachment::CrossPlatform)
+    );
+    assert!(Attachment::parse("usb").is_err());
+
+    assert_eq!(
+        "Required".parse::<ResidentKey>().unwrap(),
+        ResidentKey::Required
+    );
+    assert!("always".parse::<ResidentKey>().is_err());
+
+    assert_eq!(
+        UserVerification::Discouraged.strictest(UserVerification::Required),
+        UserVerification::Required
+    );
+}
+
+#[tokio::test]
+async fn test_default_policy_rejects_authenticator_without_verification() {
+    let (node, _temp) = setup_test_node_with_device_id("policy-required-01").await;
+
+    let (ccr, _) = node.start_webauthn_registration().await.unwrap();
+    let selection = ccr.public_key.authenticator_selection.clone().unwrap();
+    assert_eq!(
+        selection.user_verification,
+        UserVerificationPolicy::Required
+    );
+    assert_eq!(selection.authenticator_attachment, None);
+
+    // The soft authenticator can't verify users, so it refuses the ceremony
+    assert!(
+        SoftPasskey::new(false)
+            .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_preferred_policy_is_applied_and_stored() {
+    let (mut node, _temp) = setup_test_node_with_device_id("policy-preferred-01").await;
+    node.auth_state.policy = CeremonyPolicy {
+        registration: UserVerification::Preferred,
+        authentication: UserVerification::Preferred,
+        attachment: Some(Attachment::CrossPlatform),
+        resident_key: ResidentKey::Discouraged,
+    };
+
+    let (ccr, challenge_id) = node.start_webauthn_registration().await.unwrap();
+    let selection = ccr.public_key.authenticator_selection.clone().unwrap();
+    assert_eq!(
+        selection.user_verification,
+        UserVerificationPolicy::Preferred
+    );
+    assert_eq!(
+        selection.authenticator_attachment,
+        Some(AuthenticatorAttachment::CrossPlatform)
+    );
+
+    let mut authenticator = SoftPasskey::new(false);
+    let credential = authenticator
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_registration(&challenge_id, credential)
+        .await
+        .expect("preferred policy should accept an unverified authenticator");
+
+    let stored = pass_key::Entity::find()
+        .one(&node.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.user_verification, "preferred");
+    assert_eq!(
+        stored.authenticator_attachment.as_deref(),
+        Some("cross-platform")
+    );
+
+    let (rcr, challenge_id) = node.start_webauthn_authentication().await.unwrap();
+    assert_eq!(
+        rcr.public_key.user_verification,
+        UserVerificationPolicy::Preferred
+    );
+    let assertion = authenticator
+        .perform_auth(Url::parse(ORIGIN).unwrap(), rcr.public_key, 60000)
+        .unwrap();
+    let result = node
+        .finish_webauthn_authentication(&challenge_id, assertion)
+        .await
+        .unwrap();
+    assert!(!result.user_verified());
+}
+
+#[tokio::test]
+async fn test_stricter_registered_policy_wins_at_authentication() {
+    let (mut node, _temp) = setup_test_node_with_device_id("policy-stricter-01").await;
+
+    let (ccr, challenge_id) = node.start_webauthn_registration().await.unwrap();
+    let credential = SoftPasskey::new(true)
+        .perform_register(Url::parse(ORIGIN).unwrap(), ccr.public_key, 60000)
+        .unwrap();
+    node.finish_webauthn_registration(&challenge_id, credential)
+        .await
+        .unwrap();
+
+    node.auth_state.policy.authentication = UserVerification::Discouraged;
+    let (rcr, _) = node.start_webauthn_authentication().await.unwrap();
+    assert_eq!(
+        rcr.public_key.user_verification,
+        UserVerificationPolicy::Required
+    );
+}
+
+#[tokio::test]
+async fn test_resident_key_requirement_is_applied() {
+    let (mut node, _temp) = setup_test_node_with_device_id("policy-resident-01").await;
+
+    let (ccr, _) = node.start_webauthn_registration().await.unwrap();
+    let selection = ccr.public_key.authenticator_selection.clone().unwrap();
+    assert_eq!(
+        selection.resident_key,
+        Some(ResidentKeyRequirement::Discouraged)
+    );
+    assert!(!selection.require_resident_key);
+
+    node.auth_state.policy.resident_key = ResidentKey::Required;
+    let (ccr, _) = node.start_webauthn_registration().await.unwrap();
+    let selection = ccr.public_key.authenticator_selection.clone().unwrap();
+    assert_eq!(
+        selection.resident_key,
+        Some(ResidentKeyRequirement::Required)
+    );
+    assert!(selection.require_resident_key);
+}
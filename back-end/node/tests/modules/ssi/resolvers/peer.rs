@@ -293,8 +293,8 @@ async fn test_parse_peer_did_unsupported_numalgo() {
     use node::modules::ssi::did::resolvers::peer::resolve_peer_did;
     use node::modules::ssi::did::types::ResolutionOptions;
 
-    // Numalgo 3 is not currently supported
-    let result = resolve_peer_did("did:peer:3abc123", &ResolutionOptions::default()).await;
+    // Numalgo 4 is not currently supported
+    let result = resolve_peer_did("did:peer:4abc123", &ResolutionOptions::default()).await;
     assert!(result.is_err(), "Should reject unsupported numalgo");
 
     if let Err(e) = result {
@@ -1171,6 +1171,104 @@ async fn test_generate_numalgo2_with_encryption_key() {
     println!("✓ Successfully generated numalgo:2 with encryption keys");
 }
 
+#[test]
+fn test_generate_numalgo3_short_form() {
+    use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+
+    let did = PeerDidGenerator::generate_numalgo2(vec![vec![0xAAu8; 32]], vec![vec![0xBBu8; 32]])
+        .expect("Should generate numalgo:2 DID");
+
+    let short = PeerDidGenerator::to_numalgo3(&did).expect("Should derive numalgo:3 DID");
+
+    // sha2-256 multihash in base58btc always starts with "zQm"
+    assert!(short.starts_with("did:peer:3zQm"), "got {}", short);
+    assert_eq!(short, PeerDidGenerator::to_numalgo3(&did).unwrap());
+
+    // Only did:peer:2 has a short form
+    let did0 = PeerDidGenerator::from_ed25519_bytes(&[0x11u8; 32]).unwrap();
+    assert!(PeerDidGenerator::to_numalgo3(&did0).is_err());
+    assert!(PeerDidGenerator::to_numalgo3("did:peer:2").is_err());
+}
+
+#[tokio::test]
+async fn test_resolve_numalgo3_after_numalgo2() {
+    use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+    use node::modules::ssi::did::resolvers::peer::resolve_peer_did_with;
+    use node::modules::ssi::did::resolvers::peer::store::{MemoryPeerDidStore, PeerDidStore};
+    use node::modules::ssi::did::resolvers::types::ResolutionError;
+    use node::modules::ssi::did::types::ResolutionOptions;
+
+    let store = MemoryPeerDidStore::default();
+    let options = ResolutionOptions::default();
+    let did = PeerDidGenerator::generate_numalgo2(vec![vec![0xAAu8; 32]], vec![vec![0xBBu8; 32]])
+        .unwrap();
+    let short = PeerDidGenerator::to_numalgo3(&did).unwrap();
+
+    // Unknown until the long form has been seen
+    let result = resolve_peer_did_with(&short, &options, Some(&store)).await;
+    assert!(matches!(result, Err(ResolutionError::NotFound)));
+
+    resolve_peer_did_with(&did, &options, Some(&store))
+        .await
+        .expect("Should resolve numalgo:2");
+    assert_eq!(store.get(&short), Some(did.clone()));
+
+    let result = resolve_peer_did_with(&short, &options, Some(&store))
+        .await
+        .expect("Should resolve numalgo:3");
+    let doc = result.did_document.expect("Should have DID document");
+    assert_eq!(doc.id.to_string(), short);
+    assert_eq!(doc.verification_method.len(), 2);
+    assert!(
+        doc.verification_method
+            .iter()
+            .all(|vm| vm.id.to_string().starts_with(&short))
+    );
+    assert_eq!(
+        result.did_document_metadata.equivalent_id,
+        Some(vec![did.clone()])
+    );
+    assert_eq!(
+        result
+            .did_resolution_metadata
+            .verifiable_data_registry
+            .and_then(|vdr| vdr.registry_version),
+        Some("did:peer:3".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_resolver_remembers_numalgo2_in_kv() {
+    use node::modules::ssi::did::DidResolver;
+    use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
+    use node::modules::ssi::did::resolvers::peer::store::KvPeerDidStore;
+    use node::modules::ssi::did::types::ResolutionOptions;
+    use std::sync::Arc;
+
+    let kv = sled::Config::new().temporary(true).open().unwrap();
+    let options = ResolutionOptions::default();
+    let did = PeerDidGenerator::generate_numalgo2(vec![vec![0xCCu8; 32]], vec![]).unwrap();
+    let short = PeerDidGenerator::to_numalgo3(&did).unwrap();
+
+    let resolver = DidResolver::new().with_peer_store(Arc::new(KvPeerDidStore::open(&kv).unwrap()));
+    resolver.resolve_did(&did, &options).await.unwrap();
+
+    // A resolver opened later on the same KV store knows the short form
+    let resolver = DidResolver::new()
+        .without_cache()
+        .with_peer_store(Arc::new(KvPeerDidStore::open(&kv).unwrap()));
+    let result = resolver.resolve_did(&short, &options).await.unwrap();
+    assert_eq!(result.did_document.unwrap().id.to_string(), short);
+
+    // Not shared with a resolver keeping short forms in memory
+    assert!(
+        DidResolver::new()
+            .resolve_did(&short, &options)
+            .await
+            .is_err()
+    );
+}
+
 #[test]
 fn test_from_passkey_deterministic() {
     use node::modules::ssi::did::resolvers::peer::generator::PeerDidGenerator;
@@ -0,0 +1,138 @@
+use chrono::{Duration, Utc};
+use ed25519_dalek::SigningKey;
+use errors::AppError;
+use node::bootstrap::init::NodeData;
+use node::modules::clock::{TestClock, TimeSource};
+use node::modules::ssi::vc::{self, CredentialRequest};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+const SUBJECT: &str = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+
+fn issuer() -> NodeData {
+    let key = SigningKey::from_bytes(&[3; 32]);
+    let mut multicodec_key = vec![0xed, 0x01];
+    multicodec_key.extend_from_slice(key.verifying_key().as_bytes());
+    NodeData {
+        id: format!(
+            "did:key:{}",
+            multibase::encode(multibase::Base::Base58Btc, &multicodec_key)
+        ),
+        private_key: key.to_bytes().to_vec().into(),
+        public_key: key.verifying_key().to_bytes().to_vec(),
+    }
+}
+
+fn request() -> CredentialRequest {
+    CredentialRequest {
+        subject: SUBJECT.to_string(),
+        claims: json!({ "role": "editor" }).as_object().unwrap().clone(),
+        types: vec!["SpaceMemberCredential".to_string()],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_issued_credential_verifies_both_ways() {
+    let node_data = issuer();
+    let issued = vc::issue(&node_data, request()).unwrap();
+
+    let credential = &issued.credential;
+    assert_eq!(credential.issuer, node_data.id);
+    assert_eq!(credential.subject(), Some(SUBJECT));
+    assert_eq!(credential.credential_subject["role"], "editor");
+    assert_eq!(
+        credential.types,
+        vec!["VerifiableCredential", "SpaceMemberCredential"]
+    );
+    assert!(credential.id.starts_with("urn:uuid:"));
+    vc::verify_credential(credential).unwrap();
+
+    let from_jwt = vc::verify_jwt(&issued.jwt).unwrap();
+    assert_eq!(from_jwt.proof, None);
+    assert_eq!(from_jwt.credential_subject, credential.credential_subject);
+}
+
+#[test]
+fn test_tampered_credential_fails_verification() {
+    let issued = vc::issue(&issuer(), request()).unwrap();
+
+    let mut tampered = issued.credential.clone();
+    tampered
+        .credential_subject
+        .insert("role".to_string(), json!("owner"));
+    assert!(matches!(
+        vc::verify_credential(&tampered),
+        Err(AppError::Auth(_))
+    ));
+
+    let (header, _) = issued.jwt.rsplit_once('.').unwrap();
+    let forged = format!("{}.{}", header, "A".repeat(86));
+    assert!(vc::verify_jwt(&forged).is_err());
+}
+
+#[test]
+fn test_issue_rejects_invalid_requests() {
+    let node_data = issuer();
+
+    let bad_subject = CredentialRequest {
+        subject: "not-a-did".to_string(),
+        ..request()
+    };
+    assert!(matches!(
+        vc::issue(&node_data, bad_subject),
+        Err(AppError::Validation(_))
+    ));
+
+    let mut overriding = request();
+    overriding.claims.insert("id".to_string(), json!(SUBJECT));
+    assert!(matches!(
+        vc::issue(&node_data, overriding),
+        Err(AppError::Validation(_))
+    ));
+
+    let expired = CredentialRequest {
+        valid_until: Some(Utc::now() - Duration::minutes(1)),
+        ..request()
+    };
+    assert!(matches!(
+        vc::issue(&node_data, expired),
+        Err(AppError::Validation(_))
+    ));
+}
+
+#[test]
+fn test_validity_period_allows_clock_skew() {
+    let valid_until = Utc::now() + Duration::minutes(10);
+    let issued = vc::issue(
+        &issuer(),
+        CredentialRequest {
+            valid_until: Some(valid_until),
+            ..request()
+        },
+    )
+    .unwrap();
+    let clock = TestClock::at(valid_until + Duration::seconds(30));
+    let time = TimeSource::new(Arc::new(clock.clone()), StdDuration::from_secs(60));
+
+    // Expired by this clock, but within the skew allowed
+    vc::verify_credential_at(&issued.credential, &time).unwrap();
+    vc::verify_jwt_at(&issued.jwt, &time).unwrap();
+    assert!(vc::verify_credential(&issued.credential).is_ok());
+
+    clock.advance(StdDuration::from_secs(60));
+    assert!(matches!(
+        vc::verify_jwt_at(&issued.jwt, &time),
+        Err(AppError::Auth(_))
+    ));
+
+    // Issued by a clock ahead of this one
+    clock.set(Utc::now() - Duration::seconds(30));
+    vc::verify_credential_at(&issued.credential, &time).unwrap();
+    clock.set(Utc::now() - Duration::minutes(5));
+    assert!(matches!(
+        vc::verify_credential_at(&issued.credential, &time),
+        Err(AppError::Auth(_))
+    ));
+}
@@ -0,0 +1,111 @@
+use crate::bootstrap::init::setup_test_node;
+use node::modules::storage_stats::{self, DbStats, KvStats, StorageStats, TreeStats};
+use sea_orm::{ConnectionTrait, Database};
+use std::collections::BTreeMap;
+
+fn stats(flush_micros: u64, db: Option<DbStats>) -> StorageStats {
+    StorageStats {
+        kv: KvStats {
+            disk_bytes: 1024,
+            trees: BTreeMap::from([(
+                "sessions".to_string(),
+                TreeStats {
+                    entries: 3,
+                    bytes: 300,
+                },
+            )]),
+            flush_micros,
+        },
+        db,
+    }
+}
+
+fn sqlite(pages: u64, free_pages: u64, wal_bytes: Option<u64>) -> DbStats {
+    DbStats {
+        page_size: 4096,
+        pages,
+        free_pages,
+        cache_bytes: 2 * 1024 * 1024,
+        journal_mode: "wal".to_string(),
+        wal_bytes,
+        pool_connections: 2,
+        pool_idle: 1,
+        pool_max: 4,
+    }
+}
+
+#[tokio::test]
+async fn test_collect_counts_kv_trees_and_reads_sqlite() {
+    let (node, _temp) = setup_test_node().await;
+    let tree = node.kv.open_tree("stats").unwrap();
+    tree.insert(b"a", &[0u8; 10][..]).unwrap();
+    tree.insert(b"b", &[0u8; 20][..]).unwrap();
+
+    let stats = node.storage_stats().await.unwrap();
+    assert_eq!(
+        stats.kv.trees["stats"],
+        TreeStats {
+            entries: 2,
+            bytes: 32
+        }
+    );
+    assert!(stats.kv.disk_bytes > 0);
+
+    let db = stats.db.as_ref().unwrap();
+    assert!(db.page_size > 0);
+    assert!(db.pages > 0);
+    assert!(db.cache_bytes > 0);
+    // In memory, there is no log file to measure
+    assert_eq!(db.wal_bytes, None);
+    assert!(stats.warnings().is_empty(), "{:?}", stats.warnings());
+}
+
+#[tokio::test]
+async fn test_collect_measures_the_wal_of_a_file_database() {
+    let (node, temp) = setup_test_node().await;
+    let path = temp.path().join("stats.sqlite");
+    let db = Database::connect(format!("sqlite://{}?mode=rwc", path.display()))
+        .await
+        .unwrap();
+    db.execute_unprepared("PRAGMA journal_mode = WAL; CREATE TABLE t (v BLOB); INSERT INTO t VALUES (zeroblob(10000))")
+        .await
+        .unwrap();
+
+    let stats = storage_stats::collect(&db, &node.kv).await.unwrap();
+    let db = stats.db.unwrap();
+    assert_eq!(db.journal_mode, "wal");
+    assert!(db.wal_bytes.unwrap() > 10000);
+}
+
+#[test]
+fn test_warnings_point_at_capacity_issues() {
+    assert!(
+        stats(500, Some(sqlite(1000, 10, Some(1024))))
+            .warnings()
+            .is_empty()
+    );
+
+    let warnings = stats(2_000_000, Some(sqlite(1000, 500, Some(100 * 1024 * 1024)))).warnings();
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings[0].contains("flush"));
+    assert!(warnings[1].contains("write-ahead log"));
+    assert!(warnings[2].contains("VACUUM"));
+
+    let mut bloated = stats(500, None);
+    bloated.kv.disk_bytes = 512 * 1024 * 1024;
+    assert_eq!(bloated.warnings().len(), 1);
+}
+
+#[test]
+fn test_prometheus_text() {
+    let text = storage_stats::prometheus(&stats(1500, Some(sqlite(1000, 10, Some(8192)))));
+    assert!(text.contains("flow_kv_tree_entries{tree=\"sessions\"} 3\n"));
+    assert!(text.contains("flow_kv_flush_microseconds 1500\n"));
+    assert!(text.contains("flow_db_pages 1000\n"));
+    assert!(text.contains("flow_db_wal_bytes 8192\n"));
+    assert!(text.contains("flow_db_connections{state=\"busy\"} 1\n"));
+    assert!(text.contains("flow_db_connections_max 4\n"));
+
+    let text = storage_stats::prometheus(&stats(1500, None));
+    assert!(!text.contains("flow_db_"));
+}
@@ -0,0 +1,122 @@
+use crate::bootstrap::init::setup_test_node;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::Utc;
+use node::bootstrap::config::TelemetryConfig;
+use node::modules::telemetry::{Counter, TelemetryReport};
+use std::sync::{Arc, Mutex};
+
+/// Telemetry on, sending to `endpoint`, with next to no noise
+fn enabled(endpoint: &str) -> TelemetryConfig {
+    TelemetryConfig {
+        enabled: true,
+        endpoint: Some(endpoint.to_string()),
+        epsilon: 1000.0,
+        ..TelemetryConfig::default()
+    }
+}
+
+/// Collect what is POSTed to a local endpoint, answering with `status`
+async fn collector(status: StatusCode) -> (String, Arc<Mutex<Vec<TelemetryReport>>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/reports", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let collected = received.clone();
+    let app = Router::new().route(
+        "/reports",
+        post(move |Json(report): Json<TelemetryReport>| async move {
+            collected.lock().unwrap().push(report);
+            status
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (url, received)
+}
+
+#[tokio::test]
+async fn test_nothing_is_counted_while_off() {
+    let (node, _temp) = setup_test_node().await;
+    let telemetry = node.telemetry();
+    assert!(!telemetry.enabled());
+
+    telemetry
+        .count(Counter::Feature, "GET /api/v1/node")
+        .unwrap();
+    let report = telemetry.report().unwrap();
+    assert!(report.features.is_empty());
+    assert_eq!(report.since, None);
+}
+
+#[tokio::test]
+async fn test_report_is_noised_and_stable() {
+    let (mut node, _temp) = setup_test_node().await;
+    node.telemetry = TelemetryConfig {
+        epsilon: 1.0,
+        ..enabled("http://127.0.0.1:9/reports")
+    };
+    let telemetry = node.telemetry();
+    for _ in 0..1000 {
+        telemetry
+            .count(Counter::Feature, "GET /api/v1/spaces")
+            .unwrap();
+    }
+    telemetry.count(Counter::Error, "not_found").unwrap();
+
+    let report = telemetry.report().unwrap();
+    assert_eq!(report.schema, "flow-telemetry/v1");
+    assert_eq!(report.since, Some(Utc::now().date_naive()));
+    // Laplace noise of scale 1 is over 30 about once in 10^13
+    let spaces = report.features["GET /api/v1/spaces"];
+    assert!((970..=1030).contains(&spaces), "{}", spaces);
+    assert!(report.errors.keys().all(|code| code == "not_found"));
+
+    // The preview is what would be sent
+    assert_eq!(telemetry.report().unwrap(), report);
+}
+
+#[tokio::test]
+async fn test_submit_sends_the_report_and_drops_its_counts() {
+    let (url, received) = collector(StatusCode::ACCEPTED).await;
+    let (mut node, _temp) = setup_test_node().await;
+    node.telemetry = enabled(&url);
+    let telemetry = node.telemetry();
+    let client = reqwest::Client::new();
+
+    assert_eq!(telemetry.submit(&client).await.unwrap(), None);
+
+    for _ in 0..3 {
+        telemetry
+            .count(Counter::Feature, "GET /api/v1/node")
+            .unwrap();
+    }
+    telemetry.count(Counter::Error, "forbidden").unwrap();
+    let preview = telemetry.report().unwrap();
+    assert_eq!(preview.features["GET /api/v1/node"], 3);
+    assert_eq!(preview.errors["forbidden"], 1);
+
+    let sent = telemetry.submit(&client).await.unwrap();
+    assert_eq!(sent.as_ref(), Some(&preview));
+    assert_eq!(*received.lock().unwrap(), vec![preview]);
+
+    let report = telemetry.report().unwrap();
+    assert!(report.features.is_empty() && report.errors.is_empty());
+    assert_eq!(telemetry.submit(&client).await.unwrap(), None);
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_failed_submission_keeps_counts() {
+    let (url, received) = collector(StatusCode::SERVICE_UNAVAILABLE).await;
+    let (mut node, _temp) = setup_test_node().await;
+    node.telemetry = enabled(&url);
+    let telemetry = node.telemetry();
+    telemetry
+        .count(Counter::Feature, "GET /api/v1/node")
+        .unwrap();
+    let preview = telemetry.report().unwrap();
+
+    assert!(telemetry.submit(&reqwest::Client::new()).await.is_err());
+    assert_eq!(received.lock().unwrap().len(), 1);
+    assert_eq!(telemetry.report().unwrap(), preview);
+}
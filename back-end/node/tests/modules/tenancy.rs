@@ -0,0 +1,184 @@
+use crate::bootstrap::init::setup_test_node;
+use errors::AppError;
+use node::bootstrap::config::MultiUserConfig;
+use node::modules::tenancy::Actor;
+use std::path::Path;
+use tempfile::TempDir;
+
+const ALICE: Actor = Actor::User(1);
+const BOB: Actor = Actor::User(2);
+
+/// Multi-user settings with users' spaces under `spaces_root`
+fn multi_user(
+    max_spaces: Option<u64>,
+    kv_quota_bytes: Option<u64>,
+    spaces_root: &Path,
+) -> MultiUserConfig {
+    MultiUserConfig {
+        enabled: true,
+        max_spaces,
+        kv_quota_bytes,
+        spaces_root: Some(spaces_root.to_path_buf()),
+    }
+}
+
+#[tokio::test]
+async fn test_spaces_are_isolated_per_user() {
+    let (mut node, temp) = setup_test_node().await;
+    node.multi_user = multi_user(None, None, &temp.path().join("spaces"));
+
+    node.create_space_as(ALICE, "docs").await.unwrap();
+    node.create_space_as(BOB, "docs").await.unwrap();
+
+    let alice_spaces = node.list_spaces_as(ALICE).await.unwrap();
+    assert_eq!(alice_spaces.len(), 1);
+    assert_eq!(alice_spaces[0].user_id, Some(1));
+    let bob_spaces = node.list_spaces_as(BOB).await.unwrap();
+    assert_eq!(bob_spaces.len(), 1);
+    // The node sees everything
+    assert_eq!(node.list_spaces_as(Actor::Node).await.unwrap().len(), 2);
+
+    assert!(
+        node.find_space_as(ALICE, &alice_spaces[0].key)
+            .await
+            .is_ok()
+    );
+    assert!(matches!(
+        node.find_space_as(BOB, &alice_spaces[0].key).await,
+        Err(AppError::NotFound(_))
+    ));
+    assert!(matches!(
+        node.create_space_as(BOB, &alice_spaces[0].location).await,
+        Err(AppError::Forbidden(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_users_make_spaces_only_in_their_directory() {
+    let (mut node, temp) = setup_test_node().await;
+    let outside = TempDir::new().unwrap();
+    assert!(matches!(
+        node.create_space_as(ALICE, "docs").await,
+        Err(AppError::Forbidden(_))
+    ));
+
+    let spaces_root = temp.path().join("spaces");
+    node.multi_user = multi_user(None, None, &spaces_root);
+    let alice_root = spaces_root.join("1");
+    let home = temp.path().to_str().unwrap();
+    for dir in [home, outside.path().to_str().unwrap(), "../2/docs", "", "/"] {
+        assert!(
+            matches!(
+                node.create_space_as(ALICE, dir).await,
+                Err(AppError::Forbidden(_))
+            ),
+            "{}",
+            dir
+        );
+    }
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(outside.path(), alice_root.join("link")).unwrap();
+        assert!(matches!(
+            node.create_space_as(ALICE, "link/docs").await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(!outside.path().join("docs").exists());
+    }
+
+    // Relative to the directory, or absolute within it
+    node.create_space_as(ALICE, "docs").await.unwrap();
+    let photos = alice_root.join("photos");
+    node.create_space_as(ALICE, photos.to_str().unwrap())
+        .await
+        .unwrap();
+    let spaces = node.list_spaces_as(ALICE).await.unwrap();
+    assert_eq!(spaces.len(), 2);
+    assert!(spaces[1].location.ends_with("1/photos"));
+    // The node itself is not held to it
+    node.create_space(outside.path().to_str().unwrap())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_space_quota_applies_to_users_only() {
+    let (mut node, temp) = setup_test_node().await;
+    node.multi_user = multi_user(Some(1), None, &temp.path().join("spaces"));
+    let dir = TempDir::new().unwrap();
+
+    node.create_space_as(ALICE, "a").await.unwrap();
+    assert!(matches!(
+        node.create_space_as(ALICE, "b").await,
+        Err(AppError::Forbidden(_))
+    ));
+    node.create_space_as(BOB, "a").await.unwrap();
+    node.create_space_as(Actor::Node, dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_kv_namespaces_and_quota() {
+    let (mut node, temp) = setup_test_node().await;
+    node.multi_user = multi_user(None, Some(16), temp.path());
+
+    let alice = node.kv_for(ALICE).unwrap();
+    let bob = node.kv_for(BOB).unwrap();
+    alice.put("theme", b"dark").unwrap();
+    assert_eq!(alice.get("theme").unwrap(), Some(b"dark".to_vec()));
+    assert_eq!(bob.get("theme").unwrap(), None);
+    assert_eq!(alice.usage().unwrap(), 9);
+
+    // Replacing a value only counts the difference
+    alice.put("theme", b"light").unwrap();
+    assert!(matches!(
+        alice.put("wallpaper", b"mountains"),
+        Err(AppError::Forbidden(_))
+    ));
+    assert_eq!(alice.keys().unwrap(), vec!["theme".to_string()]);
+
+    alice.remove("theme").unwrap();
+    alice.put("wallpaper", b"hills").unwrap();
+
+    // The node's namespace has no quota
+    let node_kv = node.kv_for(Actor::Node).unwrap();
+    node_kv.put("large", &[0u8; 64]).unwrap();
+    assert_eq!(node_kv.quota_bytes(), None);
+}
+
+#[tokio::test]
+async fn test_user_sessions() {
+    let (node, _temp) = setup_test_node().await;
+    let sessions = node.user_sessions();
+
+    let (token, session) = sessions.open(7).await.unwrap();
+    assert_eq!(session.user_id, 7);
+    assert_eq!(token.split('.').count(), 3, "Tokens should be JWTs");
+    assert_eq!(sessions.actor(&token).await.unwrap(), Some(Actor::User(7)));
+    assert_eq!(sessions.find(&token).await.unwrap(), Some(session));
+    assert_eq!(sessions.actor("unknown").await.unwrap(), None);
+
+    // Logging out revokes the token before it expires
+    assert!(sessions.close(&token).await.unwrap());
+    assert_eq!(sessions.actor(&token).await.unwrap(), None);
+    assert!(!sessions.close(&token).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_user_sessions_reject_foreign_tokens() {
+    let (node, _temp) = setup_test_node().await;
+    let (other, _other_temp) = setup_test_node().await;
+
+    // Signed by another node
+    let (token, _) = other.user_sessions().open(7).await.unwrap();
+    assert_eq!(node.user_sessions().actor(&token).await.unwrap(), None);
+
+    // Claims swapped for another user's, keeping the signature
+    let (alice, _) = node.user_sessions().open(7).await.unwrap();
+    let (bob, _) = node.user_sessions().open(8).await.unwrap();
+    let alice: Vec<&str> = alice.split('.').collect();
+    let bob: Vec<&str> = bob.split('.').collect();
+    let forged = format!("{}.{}.{}", alice[0], bob[1], alice[2]);
+    assert_eq!(node.user_sessions().actor(&forged).await.unwrap(), None);
+}
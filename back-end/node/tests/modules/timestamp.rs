@@ -0,0 +1,158 @@
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::{Router, routing::post};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{TimeZone, Utc};
+use errors::AppError;
+use node::bootstrap::config::TimestampConfig;
+use node::modules::timestamp::{self, QUERY_CONTENT_TYPE, TimestampClient, TimestampProof};
+use sha2::{Digest, Sha256};
+
+/// `openssl ts -reply` from a throwaway P-256 TSA ("CN=Flow Test TSA") to
+/// [`QUERY`]
+pub const REPLY: &str = "\
+    MIIDazADAgEAMIIDYgYJKoZIhvcNAQcCoIIDUzCCA08CAQMxDzANBglghkgBZQMEAgEFADByBgsq\
+    hkiG9w0BCRABBKBjBGEwXwIBAQYEKgMEATAxMA0GCWCGSAFlAwQCAQUABCAcXcX7ENBccr+5HX5e\
+    GeOg+ef7gsZIEuwFAPT3xnE9kAIBAhgPMjAyNjEwMTYxMzIzMThaMAMCAQECCAEjRWeJq83voIIB\
+    ezCCAXcwggEeoAMCAQICFBD2gYEVU7eq4UdnzFxL7SiNwrhZMAoGCCqGSM49BAMCMBgxFjAUBgNV\
+    BAMMDUZsb3cgVGVzdCBUU0EwIBcNMjYxMDE2MTMyMzE3WhgPMjEyNjA5MjIxMzIzMTdaMBgxFjAU\
+    BgNVBAMMDUZsb3cgVGVzdCBUU0EwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATEk2hEzBR3QE36\
+    8bCfkW3RiEOtX3X34E4kaumyaPDwZre5b9Ib+QkiN2os9k3HoFPNo2veMy3TxKXsy8PxfsAuo0Qw\
+    QjAWBgNVHSUBAf8EDDAKBggrBgEFBQcDCDAJBgNVHRMEAjAAMB0GA1UdDgQWBBTVBy1fICHw0gD0\
+    ngCpCC/EGKpINjAKBggqhkjOPQQDAgNHADBEAiBkAuMzzoc/n3chWNk0OXKotBcvtG6XiqRNlUcV\
+    0MDVbQIgStkk2dl8H86t/+LVkoMAINLfCP8b7AwUSvpRAzAZ3PExggFEMIIBQAIBATAwMBgxFjAU\
+    BgNVBAMMDUZsb3cgVGVzdCBUU0ECFBD2gYEVU7eq4UdnzFxL7SiNwrhZMA0GCWCGSAFlAwQCAQUA\
+    oIGkMBoGCSqGSIb3DQEJAzENBgsqhkiG9w0BCRABBDAcBgkqhkiG9w0BCQUxDxcNMjYxMDE2MTMy\
+    MzE4WjAvBgkqhkiG9w0BCQQxIgQg1NoG44u0zGsnXq4ERO8QtYEkzmfE9z/m4CTrXQzbhwkwNwYL\
+    KoZIhvcNAQkQAi8xKDAmMCQwIgQgTWcnoGolm6J+mPqppTZZhnbqgb5i/1DnbEVtwfREZ48wCgYI\
+    KoZIzj0EAwIERzBFAiEAsCh4LfrMGn6fMMs04L6OYelA68HF55MlzykqDb52KwQCIDwMxulCQZDJ\
+    DwBx4Am9yR+j/L1ZIQwxcDbXzWrTiXfJ";
+
+/// SHA-256 of the certificate in [`REPLY`]
+pub const CERT_SHA256: [u8; 32] = [
+    0x4d, 0x67, 0x27, 0xa0, 0x6a, 0x25, 0x9b, 0xa2, 0x7e, 0x98, 0xfa, 0xa9, 0xa5, 0x36, 0x59, 0x86,
+    0x76, 0xea, 0x81, 0xbe, 0x62, 0xff, 0x50, 0xe7, 0x6c, 0x45, 0x6d, 0xc1, 0xf4, 0x44, 0x67, 0x8f,
+];
+
+/// `request(fixture_digest(), NONCE)`, as the reply answers it
+const QUERY: &str =
+    "MEMCAQEwMTANBglghkgBZQMEAgEFAAQgHF3F+xDQXHK/uR1+XhnjoPnn+4LGSBLsBQD098ZxPZACCAEjRWeJq83vAQH/";
+const NONCE: u64 = 0x0123_4567_89ab_cdef;
+
+fn fixture_digest() -> [u8; 32] {
+    Sha256::digest(b"flow timestamp fixture").into()
+}
+
+fn token() -> Vec<u8> {
+    timestamp::parse_reply(&STANDARD.decode(REPLY).unwrap()).unwrap()
+}
+
+#[test]
+fn test_request_encoding() {
+    assert_eq!(
+        STANDARD.encode(timestamp::request(&fixture_digest(), NONCE)),
+        QUERY
+    );
+}
+
+#[test]
+fn test_verify_token() {
+    let verification = timestamp::verify(&token(), &fixture_digest(), &[CERT_SHA256]).unwrap();
+    assert_eq!(
+        verification.gen_time,
+        Utc.with_ymd_and_hms(2026, 10, 16, 13, 23, 18).unwrap()
+    );
+    assert_eq!(verification.serial_number, "02");
+    assert_eq!(verification.policy, "1.2.3.4.1");
+    assert_eq!(verification.authority, "CN=Flow Test TSA");
+    assert_eq!(verification.certificate_sha256, hex(&CERT_SHA256));
+
+    let proof = TimestampProof {
+        authority_url: "http://tsa.invalid".to_string(),
+        digest: hex(&fixture_digest()),
+        token: STANDARD.encode(token()),
+        gen_time: verification.gen_time,
+    };
+    assert_eq!(
+        timestamp::verify_proof(&proof, &[CERT_SHA256]).unwrap(),
+        verification
+    );
+}
+
+#[test]
+fn test_verify_needs_a_trusted_certificate() {
+    let error = timestamp::verify(&token(), &fixture_digest(), &[[0; 32]]).unwrap_err();
+    assert!(error.to_string().contains("untrusted"), "{}", error);
+    assert!(timestamp::verify(&token(), &fixture_digest(), &[]).is_err());
+
+    // The signer names the certificate by issuer and serial; the same serial
+    // from another issuer is another certificate
+    let mut token = token();
+    let name = b"Flow Test TSA";
+    let at = token
+        .windows(name.len())
+        .rposition(|window| window == name)
+        .unwrap();
+    token[at] = b'G';
+    let error = timestamp::verify(&token, &fixture_digest(), &[CERT_SHA256]).unwrap_err();
+    assert!(error.to_string().contains("does not include"), "{}", error);
+}
+
+#[test]
+fn test_verify_rejects_other_data_and_bad_signatures() {
+    let other: [u8; 32] = Sha256::digest(b"something else").into();
+    assert!(matches!(
+        timestamp::verify(&token(), &other, &[CERT_SHA256]),
+        Err(AppError::Validation(_))
+    ));
+
+    // The signature is the token's last field
+    let mut forged = token();
+    let last = forged.len() - 1;
+    forged[last] ^= 0x01;
+    assert!(timestamp::verify(&forged, &fixture_digest(), &[CERT_SHA256]).is_err());
+
+    assert!(timestamp::verify(b"not a token", &fixture_digest(), &[CERT_SHA256]).is_err());
+}
+
+#[test]
+fn test_parse_reply_reports_rejection() {
+    // status 2 (rejection), statusString "bad digest"
+    let rejection = [
+        0x30, 0x13, 0x30, 0x11, 0x02, 0x01, 0x02, 0x30, 0x0c, 0x0c, 0x0a, b'b', b'a', b'd', b' ',
+        b'd', b'i', b'g', b'e', b's', b't',
+    ];
+    let error = timestamp::parse_reply(&rejection).unwrap_err();
+    assert!(error.to_string().contains("bad digest"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_client_rejects_replies_to_other_requests() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/tsa", listener.local_addr().unwrap());
+    let app = Router::new().route(
+        "/tsa",
+        post(|headers: HeaderMap| async move {
+            if headers.get(header::CONTENT_TYPE).unwrap() != QUERY_CONTENT_TYPE {
+                return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            }
+            Ok(STANDARD.decode(REPLY).unwrap())
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let client = TimestampClient::new(&TimestampConfig {
+        authority_url: Some(url),
+        trusted_certificates: vec![CERT_SHA256],
+        ..TimestampConfig::default()
+    })
+    .unwrap();
+
+    // A valid token, but for another request's nonce
+    let error = client.timestamp(&fixture_digest()).await.unwrap_err();
+    assert!(error.to_string().contains("does not answer"), "{}", error);
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
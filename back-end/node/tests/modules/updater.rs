@@ -0,0 +1,167 @@
+use axum::{Json, Router, routing::get};
+use chrono::Utc;
+use ed25519_dalek::{Signer, SigningKey};
+use multibase::Base;
+use node::bootstrap::config::UpdateConfig;
+use node::modules::updater::{
+    self, ReleaseManifest, SignedRelease, UpdateState, Updater, decode_publisher_key,
+    verify_release,
+};
+use tempfile::TempDir;
+
+const BINARY: &[u8] = b"#!/bin/sh\necho flow\n";
+
+fn publisher() -> SigningKey {
+    SigningKey::from_bytes(&[9u8; 32])
+}
+
+fn publisher_multibase(key: &SigningKey) -> String {
+    let mut bytes = vec![0xed, 0x01];
+    bytes.extend_from_slice(key.verifying_key().as_bytes());
+    multibase::encode(Base::Base58Btc, bytes)
+}
+
+fn release(version: &str, url: &str) -> ReleaseManifest {
+    ReleaseManifest {
+        version: version.to_string(),
+        url: url.to_string(),
+        blake3: blake3::hash(BINARY).to_hex().to_string(),
+        size: BINARY.len() as u64,
+        published_at: Utc::now(),
+        notes: None,
+    }
+}
+
+fn sign(release: ReleaseManifest, key: &SigningKey) -> SignedRelease {
+    let signature = key.sign(&serde_json::to_vec(&release).unwrap());
+    SignedRelease {
+        release,
+        signature: multibase::encode(Base::Base58Btc, signature.to_bytes()),
+    }
+}
+
+#[test]
+fn test_verify_release_signature() {
+    let key = publisher();
+    let pinned = decode_publisher_key(&publisher_multibase(&key)).unwrap();
+    let signed = sign(release("9.0.0", "http://example.invalid/node"), &key);
+
+    assert!(verify_release(&signed, &pinned).is_ok());
+
+    let mut tampered = signed.clone();
+    tampered.release.url = "http://evil.invalid/node".to_string();
+    assert!(verify_release(&tampered, &pinned).is_err());
+
+    let other = sign(
+        release("9.0.0", "http://example.invalid/node"),
+        &SigningKey::from_bytes(&[1u8; 32]),
+    );
+    assert!(verify_release(&other, &pinned).is_err());
+}
+
+#[test]
+fn test_is_newer() {
+    assert!(updater::is_newer("0.2.0", "0.1.0").unwrap());
+    assert!(updater::is_newer("v1.0.0", "0.9.9").unwrap());
+    assert!(!updater::is_newer("0.1.0", "0.1.0").unwrap());
+    assert!(!updater::is_newer("0.1.0-rc.1", "0.1.0").unwrap());
+    assert!(updater::is_newer("latest", "0.1.0").is_err());
+}
+
+#[test]
+fn test_stage_rejects_digest_mismatch() {
+    let temp = TempDir::new().unwrap();
+    let mut manifest = release("9.0.0", "http://example.invalid/node");
+    manifest.blake3 = blake3::hash(b"something else").to_hex().to_string();
+
+    assert!(updater::stage(temp.path(), &manifest, BINARY).is_err());
+    assert_eq!(updater::staged_version(temp.path()), None);
+}
+
+#[test]
+fn test_apply_staged_replaces_executable() {
+    let temp = TempDir::new().unwrap();
+    let updates = temp.path().join("updates");
+    let executable = temp.path().join("node");
+    std::fs::write(&executable, b"old binary").unwrap();
+
+    assert_eq!(updater::apply_staged(&updates, &executable).unwrap(), None);
+
+    updater::stage(&updates, &release("9.0.0", "unused"), BINARY).unwrap();
+    assert_eq!(updater::staged_version(&updates).as_deref(), Some("9.0.0"));
+
+    let applied = updater::apply_staged(&updates, &executable).unwrap();
+    assert_eq!(applied.as_deref(), Some("9.0.0"));
+    assert_eq!(std::fs::read(&executable).unwrap(), BINARY);
+    assert_eq!(
+        std::fs::read(executable.with_extension("previous")).unwrap(),
+        b"old binary"
+    );
+    assert_eq!(updater::staged_version(&updates), None);
+}
+
+#[tokio::test]
+async fn test_check_downloads_and_stages_release() {
+    let key = publisher();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+
+    let signed = sign(release("9.0.0", &format!("{}/node", base)), &key);
+    let app = Router::new()
+        .route("/latest.json", get(move || async move { Json(signed) }))
+        .route("/node", get(|| async { BINARY }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let temp = TempDir::new().unwrap();
+    let kv = sled::Config::new().temporary(true).open().unwrap();
+    let config = UpdateConfig {
+        enabled: true,
+        endpoint: Some(format!("{}/latest.json", base)),
+        publisher_key: Some(publisher_multibase(&key)),
+        ..UpdateConfig::default()
+    };
+    let updater = Updater::new(config, temp.path(), kv.clone()).unwrap();
+
+    let status = updater.check().await;
+    assert_eq!(
+        status.state,
+        UpdateState::Staged {
+            version: "9.0.0".to_string()
+        }
+    );
+    assert_eq!(status.latest_version.as_deref(), Some("9.0.0"));
+    assert_eq!(updater::load_status(&kv).unwrap(), Some(status));
+    assert_eq!(
+        updater::staged_version(&temp.path().join(updater::UPDATES_DIR)).as_deref(),
+        Some("9.0.0")
+    );
+}
+
+#[tokio::test]
+async fn test_check_rejects_unsigned_release() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+
+    let forged = sign(
+        release("9.0.0", &format!("{}/node", base)),
+        &SigningKey::from_bytes(&[1u8; 32]),
+    );
+    let app = Router::new().route("/latest.json", get(move || async move { Json(forged) }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let temp = TempDir::new().unwrap();
+    let kv = sled::Config::new().temporary(true).open().unwrap();
+    let config = UpdateConfig {
+        enabled: true,
+        endpoint: Some(format!("{}/latest.json", base)),
+        publisher_key: Some(publisher_multibase(&publisher())),
+        ..UpdateConfig::default()
+    };
+
+    let status = Updater::new(config, temp.path(), kv).unwrap().check().await;
+    assert!(matches!(status.state, UpdateState::Failed { .. }));
+    assert_eq!(
+        updater::staged_version(&temp.path().join(updater::UPDATES_DIR)),
+        None
+    );
+}
@@ -0,0 +1,79 @@
+use node::modules::validation::{
+    Did, FilePath, KvKey, MAX_DID_LEN, SpaceKey, Username, validate_did, validate_file_path,
+    validate_kv_key, validate_space_key, validate_username,
+};
+
+#[test]
+fn test_did_syntax() {
+    assert!(validate_did("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK").is_ok());
+    assert!(validate_did("did:web:example.com%3A8443:users:alice").is_ok());
+
+    for did in [
+        "",
+        "did:",
+        "did:key",
+        "did::abc",
+        "did:Key:abc",
+        "did:key:",
+        "did:web:example.com:",
+        "did:key:abc def",
+        "did:key:abc/../x",
+        "urn:key:abc",
+    ] {
+        assert!(validate_did(did).is_err(), "accepted {:?}", did);
+    }
+
+    let long = format!("did:key:z{}", "a".repeat(MAX_DID_LEN));
+    assert!(validate_did(&long).is_err());
+}
+
+#[test]
+fn test_space_key_is_hex_sha256() {
+    assert!(validate_space_key(&"0a".repeat(32)).is_ok());
+    assert!(validate_space_key(&"0A".repeat(32)).is_err());
+    assert!(validate_space_key(&"0a".repeat(31)).is_err());
+    assert!(validate_space_key(&"zz".repeat(32)).is_err());
+}
+
+#[test]
+fn test_kv_key_limits() {
+    assert!(validate_kv_key("settings/theme").is_ok());
+    assert!(validate_kv_key("").is_err());
+    assert!(validate_kv_key(&"k".repeat(257)).is_err());
+    assert!(validate_kv_key("line\nbreak").is_err());
+}
+
+#[test]
+fn test_file_path_segments() {
+    assert!(validate_file_path("notes.txt").is_ok());
+    assert!(validate_file_path("photos/2024/a b.jpg").is_ok());
+
+    for path in ["", "a//b", "a/./b", "a/../b", "..", "dir/", "a\\b", "a\0b"] {
+        assert!(validate_file_path(path).is_err(), "accepted {:?}", path);
+    }
+    assert!(validate_file_path(&"x".repeat(256)).is_err());
+}
+
+#[test]
+fn test_username_rules() {
+    assert!(validate_username("alice").is_ok());
+    assert!(validate_username("bob.smith-2_x").is_ok());
+
+    for name in ["al", "Alice", "1alice", "_alice", "alice smith", "ålice"] {
+        assert!(validate_username(name).is_err(), "accepted {:?}", name);
+    }
+    assert!(validate_username(&"a".repeat(33)).is_err());
+}
+
+#[test]
+fn test_newtypes_only_deserialize_valid_values() {
+    let did: Did = serde_json::from_str("\"did:key:z6Mk\"").unwrap();
+    assert_eq!(did.as_str(), "did:key:z6Mk");
+    assert_eq!(serde_json::to_string(&did).unwrap(), "\"did:key:z6Mk\"");
+
+    assert!(serde_json::from_str::<Did>("\"not-a-did\"").is_err());
+    assert!(serde_json::from_str::<SpaceKey>("\"deadbeef\"").is_err());
+    assert!(serde_json::from_str::<KvKey>("\"\"").is_err());
+    assert!(serde_json::from_str::<FilePath>("\"../etc/passwd\"").is_err());
+    assert!(serde_json::from_str::<Username>("\"Root\"").is_err());
+}
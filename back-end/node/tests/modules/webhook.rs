@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use crate::bootstrap::init::setup_test_node;
+use axum::{Json, Router, extract::State, routing::post};
+use event::types::{Event, EventType};
+use node::modules::tenancy::Actor;
+use node::modules::webhook::{NewWebhook, WebhookDispatcher};
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+
+fn file_created(space_key: &str, path: &str) -> Event {
+    Event::new(EventType::FileCreated)
+        .with("space_key", space_key)
+        .with("path", path)
+}
+
+#[tokio::test]
+async fn test_register_validates_registration() {
+    let (node, _temp) = setup_test_node().await;
+    let webhooks = &node.webhooks;
+
+    for invalid in [
+        NewWebhook {
+            url: "ftp://ci.example/hook".to_string(),
+            ..Default::default()
+        },
+        NewWebhook {
+            url: "https://ci.example/hook".to_string(),
+            events: vec!["file_renamed".to_string()],
+            ..Default::default()
+        },
+        NewWebhook {
+            url: "https://ci.example/hook".to_string(),
+            path_glob: Some("*.pdf".to_string()),
+            ..Default::default()
+        },
+        NewWebhook {
+            url: "https://ci.example/hook".to_string(),
+            space_key: Some("space".to_string()),
+            path_glob: Some("incoming/[".to_string()),
+            ..Default::default()
+        },
+    ] {
+        assert!(matches!(
+            webhooks.register(invalid),
+            Err(errors::AppError::Validation(_))
+        ));
+    }
+    assert!(webhooks.list().unwrap().is_empty());
+
+    let webhook = webhooks
+        .register(NewWebhook {
+            url: "https://ci.example/hook".to_string(),
+            events: vec!["file_created".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(webhooks.list().unwrap(), vec![webhook.clone()]);
+
+    webhooks.remove(&webhook.id).unwrap();
+    assert!(webhooks.list().unwrap().is_empty());
+    assert!(matches!(
+        webhooks.remove(&webhook.id),
+        Err(errors::AppError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_path_glob_filters_space_events() {
+    let (node, _temp) = setup_test_node().await;
+    let webhook = node
+        .webhooks
+        .register(NewWebhook {
+            url: "https://ci.example/hook".to_string(),
+            events: vec!["file_created".to_string(), "file_modified".to_string()],
+            space_key: Some("space-a".to_string()),
+            path_glob: Some("incoming/**/*.pdf".to_string()),
+        })
+        .unwrap();
+
+    assert!(webhook.matches(&file_created("space-a", "incoming/report.pdf")));
+    assert!(webhook.matches(&file_created("space-a", "/incoming/2025/q3/report.pdf")));
+    assert!(!webhook.matches(&file_created("space-a", "incoming/report.txt")));
+    assert!(!webhook.matches(&file_created("space-a", "archive/report.pdf")));
+    assert!(!webhook.matches(&file_created("space-b", "incoming/report.pdf")));
+    assert!(
+        !webhook.matches(
+            &Event::new(EventType::FileDeleted)
+                .with("space_key", "space-a")
+                .with("path", "incoming/report.pdf")
+        )
+    );
+    // Events without a path never match a glob
+    assert!(!webhook.matches(&Event::new(EventType::FileCreated).with("space_key", "space-a")));
+
+    let shallow = node
+        .webhooks
+        .register(NewWebhook {
+            url: "https://ci.example/hook".to_string(),
+            space_key: Some("space-a".to_string()),
+            path_glob: Some("incoming/*.pdf".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(shallow.matches(&file_created("space-a", "incoming/report.pdf")));
+    assert!(!shallow.matches(&file_created("space-a", "incoming/2025/report.pdf")));
+}
+
+/// A receiver for webhook deliveries, and the URL to post them to
+async fn webhook_receiver() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let (tx, rx) = mpsc::unbounded_channel::<Value>();
+    let receiver = Router::new()
+        .route(
+            "/hook",
+            post(
+                |State(tx): State<mpsc::UnboundedSender<Value>>, Json(body): Json<Value>| async move {
+                    tx.send(body).unwrap();
+                },
+            ),
+        )
+        .with_state(tx);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+    (format!("http://{}/hook", addr), rx)
+}
+
+#[tokio::test]
+async fn test_dispatcher_delivers_matching_events() {
+    let (node, _temp) = setup_test_node().await;
+    let (url, mut rx) = webhook_receiver().await;
+
+    let webhook = node
+        .webhooks
+        .register(NewWebhook {
+            url,
+            space_key: Some("space-a".to_string()),
+            path_glob: Some("drop/*".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+    node.subscribe(Box::new(WebhookDispatcher::new(node.webhooks.clone())));
+
+    node.publish(&file_created("space-a", "other/ignored.txt"));
+    node.publish(&file_created("space-a", "drop/build.zip"));
+
+    let body = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("webhook was not delivered")
+        .unwrap();
+    assert_eq!(body["webhook"], webhook.id.as_str());
+    assert_eq!(body["type"], "file_created");
+    assert_eq!(body["properties"]["path"], "drop/build.zip");
+
+    // The non-matching event was never sent
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_space_scan_events_reach_filtered_webhooks() {
+    let (node, _temp) = setup_test_node().await;
+    let (url, mut rx) = webhook_receiver().await;
+    // Subscribed first, so the registration below reaches its cache
+    node.subscribe(Box::new(WebhookDispatcher::new(node.webhooks.clone())));
+
+    let dir = TempDir::new().unwrap();
+    std::fs::create_dir_all(dir.path().join("drop")).unwrap();
+    std::fs::create_dir_all(dir.path().join("other")).unwrap();
+    std::fs::write(dir.path().join("drop/build.zip"), "zip").unwrap();
+    std::fs::write(dir.path().join("other/notes.txt"), "notes").unwrap();
+    node.create_space(dir.path().to_str().unwrap())
+        .await
+        .unwrap();
+    let key = node.list_spaces().await.unwrap()[0].key.clone();
+
+    let webhook = node
+        .webhooks
+        .register(NewWebhook {
+            url,
+            events: vec!["file_created".to_string()],
+            space_key: Some(key.clone()),
+            path_glob: Some("drop/*".to_string()),
+        })
+        .unwrap();
+    node.scan_space_as(Actor::Node, &key).await.unwrap();
+
+    let body = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("webhook was not delivered")
+        .unwrap();
+    assert_eq!(body["webhook"], webhook.id.as_str());
+    assert_eq!(body["properties"]["space_key"], key.as_str());
+    assert_eq!(body["properties"]["path"], "drop/build.zip");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(rx.try_recv().is_err());
+}
@@ -0,0 +1,164 @@
+use crate::api::rest::helpers::get_request;
+use crate::bootstrap::init::setup_test_node;
+use axum::http::StatusCode;
+use axum::routing::get;
+use errors::AppError;
+use futures_util::{SinkExt, StreamExt};
+use node::api::servers::app_state::AppState;
+use node::api::servers::{rest, websocket};
+use node::plugins::{FlowPlugin, PluginContext, PluginLoader, PluginRegistrar};
+use serde_json::{Value, json};
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// Plugin exercising every extension point
+struct EchoPlugin;
+
+impl FlowPlugin for EchoPlugin {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn version(&self) -> &str {
+        "1.2.3"
+    }
+
+    fn init(&self, ctx: PluginContext, registrar: &mut PluginRegistrar) -> Result<(), AppError> {
+        let did = ctx.node_did().to_string();
+        registrar
+            .route(
+                "/whoami",
+                get(move || {
+                    let did = did.clone();
+                    async move { axum::Json(json!({ "did": did })) }
+                }),
+            )
+            .ws_action("echo", |ctx, payload| async move {
+                let count = ctx
+                    .storage()?
+                    .update_and_fetch("count", |old| {
+                        let n = old.map(|b| b[0]).unwrap_or(0);
+                        Some(vec![n + 1])
+                    })
+                    .map_err(|e| AppError::Storage(Box::new(e)))?
+                    .map(|b| b[0])
+                    .unwrap_or(0);
+                Ok(json!({ "echo": payload["message"], "count": count }))
+            })
+            .ws_action("fail", |_, _| async {
+                Err::<Value, _>(AppError::Validation("nope".to_string()))
+            })
+            .job("tick", Duration::from_millis(10), |ctx| async move {
+                ctx.storage()?
+                    .insert("ticked", b"yes".to_vec())
+                    .map_err(|e| AppError::Storage(Box::new(e)))?;
+                Ok(())
+            });
+        Ok(())
+    }
+}
+
+/// Plugin trying to take over a built-in action
+struct HijackPlugin;
+
+impl FlowPlugin for HijackPlugin {
+    fn name(&self) -> &str {
+        "hijack"
+    }
+
+    fn init(&self, _: PluginContext, registrar: &mut PluginRegistrar) -> Result<(), AppError> {
+        registrar.ws_action("create_space", |_, _| async { Ok(Value::Null) });
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_plugin_routes_are_mounted_under_prefix() {
+    let (node, _temp) = setup_test_node().await;
+    let host = PluginLoader::new().with(EchoPlugin).load(&node).unwrap();
+    assert_eq!(host.plugins(), &[("echo".to_string(), "1.2.3".to_string())]);
+
+    let did = node.node_data.id.clone();
+    let router = rest::build_router(AppState::with_plugins(node, host));
+
+    let (status, body) = get_request(&router, "/api/v1/plugins/echo/whoami").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["did"], did);
+
+    let (status, body) = get_request(&router, "/api/v1/health").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "healthy");
+}
+
+#[tokio::test]
+async fn test_plugin_websocket_actions() {
+    let (node, _temp) = setup_test_node().await;
+    let host = PluginLoader::new().with(EchoPlugin).load(&node).unwrap();
+    let app = websocket::build_router(AppState::with_plugins(node, host));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    let mut call = async |request: Value| -> Value {
+        socket
+            .send(Message::Text(request.to_string().into()))
+            .await
+            .unwrap();
+        let reply = socket.next().await.unwrap().unwrap();
+        serde_json::from_str(reply.to_text().unwrap()).unwrap()
+    };
+
+    let reply = call(json!({ "action": "echo", "message": "hi" })).await;
+    assert_eq!(reply["status"], "success");
+    assert_eq!(reply["action"], "echo");
+    assert_eq!(reply["result"], json!({ "echo": "hi", "count": 1 }));
+
+    let reply = call(json!({ "action": "echo", "message": "again" })).await;
+    assert_eq!(reply["result"]["count"], 2);
+
+    let reply = call(json!({ "action": "fail" })).await;
+    assert_eq!(reply["status"], "error");
+    assert!(reply["message"].as_str().unwrap().contains("nope"));
+
+    let reply = call(json!({ "action": "missing" })).await;
+    assert_eq!(reply["message"], "Unknown action");
+}
+
+#[tokio::test]
+async fn test_plugin_jobs_run_with_private_storage() {
+    let (node, _temp) = setup_test_node().await;
+    let kv = node.kv.clone();
+    let host = PluginLoader::new().with(EchoPlugin).load(&node).unwrap();
+    assert_eq!(host.jobs().len(), 1);
+    assert_eq!(host.jobs()[0].name, "echo/tick");
+
+    let handles = host.spawn_jobs();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    handles.iter().for_each(|h| h.abort());
+
+    let tree = kv.open_tree("plugin:echo").unwrap();
+    assert_eq!(tree.get("ticked").unwrap().as_deref(), Some(&b"yes"[..]));
+    assert!(kv.get("ticked").unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_plugin_conflicts_are_rejected() {
+    let (node, _temp) = setup_test_node().await;
+
+    let err = PluginLoader::new()
+        .with(HijackPlugin)
+        .load(&node)
+        .err()
+        .expect("built-in actions cannot be shadowed");
+    assert!(err.to_string().contains("create_space"));
+
+    let err = PluginLoader::new()
+        .with(EchoPlugin)
+        .with(EchoPlugin)
+        .load(&node)
+        .err()
+        .expect("plugin names are unique");
+    assert!(err.to_string().contains("registered twice"));
+}
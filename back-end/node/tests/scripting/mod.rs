@@ -0,0 +1,217 @@
+use crate::bootstrap::init::setup_test_node;
+use event::types::{Event, EventType};
+use node::scripting::{Capability, MAX_KV_BYTES, ScriptHost, ScriptManifest};
+use tempfile::TempDir;
+
+/// Logs each event and counts invocations in its KV tree
+const COUNTER: &str = r#"
+(module
+  (import "flow" "log" (func $log (param i32 i32)))
+  (import "flow" "kv_get" (func $kv_get (param i32 i32 i32 i32) (result i32)))
+  (import "flow" "kv_set" (func $kv_set (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "count")
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "on_event") (param $ptr i32) (param $len i32)
+    (call $log (local.get $ptr) (local.get $len))
+    (if (i32.eq (call $kv_get (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 4)) (i32.const -1))
+      (then (i32.store (i32.const 16) (i32.const 0))))
+    (i32.store (i32.const 16) (i32.add (i32.load (i32.const 16)) (i32.const 1)))
+    (drop (call $kv_set (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 4)))))
+"#;
+
+/// Checks an oversized entry is refused, then stores 60000 byte values under
+/// counting keys until its quota runs out; traps on any other result
+const HOARDER: &str = r#"
+(module
+  (import "flow" "kv_set" (func $kv_set (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "on_event") (param i32 i32)
+    (local $i i32) (local $code i32)
+    (if (i32.ne (call $kv_set (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 65537)) (i32.const -1))
+      (then unreachable))
+    (block $full
+      (loop $fill
+        (i32.store (i32.const 0) (local.get $i))
+        (local.set $code (call $kv_set (i32.const 0) (i32.const 4) (i32.const 2048) (i32.const 60000)))
+        (br_if $full (i32.eq (local.get $code) (i32.const -2)))
+        (if (i32.ne (local.get $code) (i32.const 0)) (then unreachable))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br_if $fill (i32.lt_u (local.get $i) (i32.const 100)))
+        unreachable))))
+"#;
+
+const SPIN: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 0))
+  (func (export "on_event") (param i32 i32) (loop $l (br $l))))
+"#;
+
+fn manifest(events: &[&str], capabilities: &[Capability]) -> ScriptManifest {
+    ScriptManifest {
+        events: events.iter().map(|e| e.to_string()).collect(),
+        capabilities: capabilities.to_vec(),
+    }
+}
+
+fn count(kv: &sled::Db, script: &str) -> Option<i32> {
+    kv.open_tree(format!("script:{}", script))
+        .unwrap()
+        .get("count")
+        .unwrap()
+        .map(|v| i32::from_le_bytes(v.as_ref().try_into().unwrap()))
+}
+
+fn temp_kv() -> sled::Db {
+    sled::Config::new().temporary(true).open().unwrap()
+}
+
+#[test]
+fn test_script_runs_with_granted_capabilities() {
+    let kv = temp_kv();
+    let mut host = ScriptHost::new().unwrap();
+    host.add(
+        "counter",
+        COUNTER.as_bytes(),
+        manifest(&[], &[Capability::Log, Capability::Kv]),
+        &kv,
+    )
+    .unwrap();
+
+    for _ in 0..3 {
+        let results = host.dispatch(&Event::new(EventType::FileCreated).with("path", "a.txt"));
+        assert_eq!(results.len(), 1);
+        assert!(results[0].1.is_ok(), "{:?}", results[0].1);
+    }
+    assert_eq!(count(&kv, "counter"), Some(3));
+}
+
+#[test]
+fn test_script_kv_is_limited() {
+    let kv = temp_kv();
+    let stored = || {
+        let tree = kv.open_tree("script:hoarder").unwrap();
+        let bytes: u64 = tree
+            .iter()
+            .map(|entry| entry.map(|(k, v)| (k.len() + v.len()) as u64).unwrap())
+            .sum();
+        (tree.len() as u64, bytes)
+    };
+
+    for _ in 0..2 {
+        // A fresh host counts what the tree already holds
+        let mut host = ScriptHost::new().unwrap();
+        host.add(
+            "hoarder",
+            HOARDER.as_bytes(),
+            manifest(&[], &[Capability::Kv]),
+            &kv,
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            let results = host.dispatch(&Event::new(EventType::FileCreated));
+            assert!(results[0].1.is_ok(), "{:?}", results[0].1);
+        }
+        assert_eq!(stored().0, MAX_KV_BYTES / 60_004);
+        assert!(stored().1 <= MAX_KV_BYTES);
+    }
+}
+
+#[test]
+fn test_script_without_capability_is_rejected() {
+    let mut host = ScriptHost::new().unwrap();
+    let err = host
+        .add(
+            "counter",
+            COUNTER.as_bytes(),
+            manifest(&[], &[Capability::Log]),
+            &temp_kv(),
+        )
+        .expect_err("kv imports must not link without the kv capability");
+    assert!(err.to_string().contains("kv_get"));
+}
+
+#[test]
+fn test_script_only_receives_subscribed_events() {
+    let kv = temp_kv();
+    let mut host = ScriptHost::new().unwrap();
+    host.add(
+        "counter",
+        COUNTER.as_bytes(),
+        manifest(&["credential_verified"], &[Capability::Log, Capability::Kv]),
+        &kv,
+    )
+    .unwrap();
+
+    assert!(
+        host.dispatch(&Event::new(EventType::FileCreated))
+            .is_empty()
+    );
+    assert_eq!(
+        host.dispatch(&Event::new(EventType::CredentialVerified))
+            .len(),
+        1
+    );
+    assert_eq!(count(&kv, "counter"), Some(1));
+
+    assert!(
+        host.add("bad", COUNTER.as_bytes(), manifest(&["reboot"], &[]), &kv)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_runaway_script_runs_out_of_fuel() {
+    let mut host = ScriptHost::new().unwrap();
+    host.add(
+        "spin",
+        SPIN.as_bytes(),
+        ScriptManifest::default(),
+        &temp_kv(),
+    )
+    .unwrap();
+
+    let results = host.dispatch(&Event::new(EventType::MessageReceived));
+    let err = results[0].1.as_ref().unwrap_err();
+    assert!(err.to_string().contains("fuel"), "{}", err);
+}
+
+#[test]
+fn test_load_dir_reads_sidecar_manifests() {
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("counter.wat"), COUNTER).unwrap();
+    std::fs::write(
+        temp.path().join("counter.json"),
+        r#"{"events": ["file_created"], "capabilities": ["log", "kv"]}"#,
+    )
+    .unwrap();
+    std::fs::write(temp.path().join("notes.txt"), "ignored").unwrap();
+
+    let host = ScriptHost::load_dir(temp.path(), &temp_kv()).unwrap();
+    assert_eq!(host.scripts().len(), 1);
+    assert_eq!(host.scripts()[0].name, "counter");
+    assert_eq!(host.scripts()[0].manifest.events, vec!["file_created"]);
+
+    let missing = ScriptHost::load_dir(&temp.path().join("missing"), &temp_kv()).unwrap();
+    assert!(missing.is_empty());
+}
+
+#[tokio::test]
+async fn test_node_events_reach_scripts() {
+    let (node, _temp) = setup_test_node().await;
+    let mut host = ScriptHost::new().unwrap();
+    host.add(
+        "counter",
+        COUNTER.as_bytes(),
+        manifest(&[], &[Capability::Log, Capability::Kv]),
+        &node.kv,
+    )
+    .unwrap();
+    node.subscribe(Box::new(host));
+
+    node.publish(&Event::new(EventType::MessageReceived).with("from", "did:key:z6Mk"));
+    assert_eq!(count(&node.kv, "counter"), Some(1));
+}
@@ -0,0 +1,82 @@
+use crate::bootstrap::init::{setup_test_node, with_did_key};
+use errors::AppError;
+use node::modules::p2p::Message;
+use node::modules::ssi::vc::IssuedCredential;
+use node::modules::tenancy::Actor;
+use node::seed::{self, Profile, SeedArgs};
+use std::path::PathBuf;
+use url::Url;
+
+#[test]
+fn test_parse_seed_args() {
+    let args = SeedArgs::parse(["--profile", "demo", "--dir", "/tmp/demo"]).unwrap();
+    assert_eq!(args.profile, Profile::Demo);
+    assert_eq!(args.dir, Some(PathBuf::from("/tmp/demo")));
+
+    assert_eq!(SeedArgs::parse(["-p", "demo"]).unwrap().dir, None);
+    assert!(SeedArgs::parse(Vec::<String>::new()).is_err());
+    assert!(SeedArgs::parse(["--profile", "huge"]).is_err());
+    assert!(SeedArgs::parse(["--profile"]).is_err());
+    assert!(SeedArgs::parse(["--profile", "demo", "--force"]).is_err());
+}
+
+#[tokio::test]
+async fn test_demo_profile_populates_the_node() {
+    let (node, temp) = setup_test_node().await;
+    let node = with_did_key(node, 7);
+    let origin = Url::parse("http://localhost:3000").unwrap();
+    let dir = temp.path().join("demo");
+
+    let report = seed::seed(&node, Profile::Demo, &origin, &dir)
+        .await
+        .unwrap();
+    assert_eq!(report.users.len(), 3);
+    assert_eq!(report.credentials, 3);
+
+    for user in &report.users {
+        let profile = node.user_profile(user.id).await.unwrap();
+        assert_eq!(profile.username, user.username);
+        assert!(
+            !node
+                .list_passkeys_as(Actor::User(user.id))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let spaces = node.list_spaces_as(Actor::User(user.id)).await.unwrap();
+        assert_eq!(spaces.len(), 1);
+        assert_eq!(spaces[0].key, user.space_key);
+
+        let space = PathBuf::from(&user.location);
+        assert!(space.join("Documents/welcome.md").is_file());
+        let contacts: Vec<serde_json::Value> =
+            serde_json::from_slice(&std::fs::read(space.join("contacts.json")).unwrap()).unwrap();
+        assert_eq!(contacts.len(), 2);
+        assert!(contacts.iter().all(|c| c["did"] != user.did.as_str()));
+
+        let messages: Vec<Message> = std::fs::read_dir(space.join("messages"))
+            .unwrap()
+            .map(|entry| {
+                serde_json::from_slice(&std::fs::read(entry.unwrap().path()).unwrap()).unwrap()
+            })
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| m.body["to"] == user.did.as_str()));
+
+        let issued: IssuedCredential = serde_json::from_slice(
+            &std::fs::read(space.join("credentials/membership.json")).unwrap(),
+        )
+        .unwrap();
+        let credential = node.verify_credential_jwt(&issued.jwt).unwrap();
+        assert_eq!(credential.id, issued.credential.id);
+        assert_eq!(credential.credential_subject["id"], user.did.as_str());
+        node.verify_credential(&issued.credential).unwrap();
+    }
+
+    // Seeding twice into the same folder is refused
+    assert!(matches!(
+        seed::seed(&node, Profile::Demo, &origin, &dir).await,
+        Err(AppError::Conflict(_))
+    ));
+}
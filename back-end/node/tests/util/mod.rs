@@ -1 +1,2 @@
 pub mod temp_env;
+pub mod users;
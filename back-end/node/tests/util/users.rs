@@ -0,0 +1,31 @@
+use chrono::Utc;
+use entity::user;
+use node::api::node::Node;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, NotSet, Set};
+
+/// Insert a user named `name`, with DID `did:key:<name>`
+pub async fn insert_user(db: &DatabaseConnection, name: &str) -> user::Model {
+    user::ActiveModel {
+        id: NotSet,
+        did: Set(format!("did:key:{}", name)),
+        username: Set(name.to_string()),
+        display_name: Set(name.to_string()),
+        avatar_url: Set(None),
+        device_ids: Set("[]".to_string()),
+        public_key_jwk: Set("{}".to_string()),
+        time_created: Set(Utc::now().into()),
+        last_login: Set(Utc::now().into()),
+        version: Set(1),
+        deleted_at: Set(None),
+    }
+    .insert(db)
+    .await
+    .unwrap()
+}
+
+/// A user named `name` and the token of a session opened for them
+pub async fn signed_in_user(node: &Node, name: &str) -> (i32, String) {
+    let user = insert_user(&node.db, name).await;
+    let (token, _) = node.user_sessions().open(user.id).await.unwrap();
+    (user.id, token)
+}
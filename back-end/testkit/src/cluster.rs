@@ -0,0 +1,86 @@
+use std::future::Future;
+use std::time::Duration;
+
+use errors::AppError;
+use node::modules::p2p::Multiaddr;
+use tokio::time::{Instant, sleep};
+
+use crate::node::TestNode;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A group of independent in-process nodes
+pub struct TestCluster {
+    pub nodes: Vec<TestNode>,
+}
+
+impl TestCluster {
+    /// Start `count` nodes named `node-0`, `node-1`, ...
+    pub async fn start(count: usize) -> Result<Self, AppError> {
+        let mut nodes = Vec::with_capacity(count);
+        for i in 0..count {
+            nodes.push(TestNode::start(&format!("node-{}", i)).await?);
+        }
+
+        Ok(Self { nodes })
+    }
+
+    pub fn node(&self, index: usize) -> &TestNode {
+        &self.nodes[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Loopback REST base URLs of every node, in order
+    pub fn peer_urls(&self) -> Vec<String> {
+        self.nodes.iter().map(|n| n.url("")).collect()
+    }
+
+    /// Loopback P2P addresses of every node, in order
+    pub fn peer_addrs(&self) -> Vec<Multiaddr> {
+        self.nodes.iter().map(|n| n.p2p_addr.clone()).collect()
+    }
+
+    /// Poll `check` against every node until it holds for all of them or
+    /// `timeout` elapses
+    pub async fn await_convergence<F, Fut>(
+        &self,
+        timeout: Duration,
+        mut check: F,
+    ) -> Result<(), AppError>
+    where
+        F: FnMut(&TestNode) -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let mut converged = true;
+            for node in &self.nodes {
+                if !check(node).await {
+                    converged = false;
+                    break;
+                }
+            }
+
+            if converged {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(AppError::Validation(format!(
+                    "Cluster did not converge within {:?}",
+                    timeout
+                )));
+            }
+
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
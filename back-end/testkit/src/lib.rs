@@ -0,0 +1,13 @@
+//! In-process multi-node harness for Flow integration tests.
+//!
+//! Each [`TestNode`] gets its own temp directory (identity, database, KV store
+//! and spaces) and serves its REST, WebSocket and P2P APIs on loopback ports,
+//! so tests can talk to several nodes the same way clients and peers would.
+
+pub mod cluster;
+pub mod node;
+pub mod passkey;
+
+pub use cluster::TestCluster;
+pub use node::TestNode;
+pub use passkey::RegisteredPasskey;
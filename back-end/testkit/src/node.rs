@@ -0,0 +1,171 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::Router;
+use entity::space;
+use errors::AppError;
+use log::info;
+use migration::{Migrator, MigratorTrait};
+use node::api::node::Node;
+use node::api::servers::app_state::AppState;
+use node::api::servers::{rest, websocket};
+use node::bootstrap::init::initialize_config_dir;
+use node::modules::mtls::{self, NodeCertificate};
+use node::modules::p2p::{Host, Multiaddr, P2p, PeerConnection, PublishMessages};
+use node::modules::ssi::webauthn::state::{AuthConfig, AuthState};
+use sea_orm::{ColumnTrait, Database, EntityTrait, QueryFilter};
+use tempfile::TempDir;
+use tokio::task::JoinHandle;
+
+use crate::passkey::RegisteredPasskey;
+
+/// Relying party origin used by every test node
+pub const TEST_ORIGIN: &str = "http://localhost:3000";
+
+/// A single node running in-process with isolated storage
+pub struct TestNode {
+    pub name: String,
+    pub node: Node,
+    pub router: Router,
+    /// Loopback address the REST API is served on
+    pub addr: SocketAddr,
+    /// Loopback address the WebSocket API is served on
+    pub ws_addr: SocketAddr,
+    /// This node's end of the peer-to-peer transport
+    pub p2p: P2p,
+    /// Loopback address peers dial this node at, naming its DID
+    pub p2p_addr: Multiaddr,
+    pub temp: TempDir,
+    servers: Vec<JoinHandle<()>>,
+}
+
+impl TestNode {
+    /// Bootstrap a fresh node identity, database and KV store in a new temp
+    /// directory and start serving its REST, WebSocket and P2P APIs on
+    /// `127.0.0.1`.
+    pub async fn start(name: &str) -> Result<Self, AppError> {
+        let temp = TempDir::new()?;
+
+        let config_dir = temp.path().join("config");
+        let node_data = initialize_config_dir(&config_dir.to_string_lossy())?;
+
+        let db_url = format!(
+            "sqlite://{}?mode=rwc",
+            temp.path().join("flow.db").display()
+        );
+        let db = Database::connect(&db_url)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?;
+        Migrator::up(&db, None)
+            .await
+            .map_err(|e| AppError::Migration(Box::new(e)))?;
+
+        let kv = sled::open(temp.path().join("kv")).map_err(|e| AppError::Storage(Box::new(e)))?;
+
+        let auth_state = AuthState::new(AuthConfig {
+            rp_id: "localhost".to_string(),
+            rp_origin: TEST_ORIGIN.to_string(),
+            rp_name: format!("Flow Test {}", name),
+            policy: Default::default(),
+            limits: Default::default(),
+        })?;
+
+        let node = Node::new(node_data, db, kv, auth_state);
+        let app_state = AppState::new(node.clone());
+        let router = rest::build_router(app_state.clone());
+
+        let (addr, rest_server) = serve(router.clone()).await?;
+        let (ws_addr, ws_server) = serve(websocket::build_router(app_state)).await?;
+
+        let certificate = NodeCertificate::issue(&node.node_data, mtls::DEFAULT_VALIDITY)?;
+        let p2p = P2p::new(certificate, node.did_resolver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let local = listener.local_addr()?;
+        let p2p_addr = Multiaddr::new(Host::Ip(local.ip()), local.port()).with_peer(p2p.did());
+        let serving = p2p.clone();
+        let handler = Arc::new(PublishMessages::new(node.clone()));
+        let p2p_server = tokio::spawn(async move {
+            let _ = serving.serve(listener, handler).await;
+        });
+
+        info!(
+            "Test node '{}' ({}) listening on {}",
+            name, node.node_data.id, addr
+        );
+
+        Ok(Self {
+            name: name.to_string(),
+            node,
+            router,
+            addr,
+            ws_addr,
+            p2p,
+            p2p_addr,
+            temp,
+            servers: vec![rest_server, ws_server, p2p_server],
+        })
+    }
+
+    /// The node's DID
+    pub fn did(&self) -> &str {
+        &self.node.node_data.id
+    }
+
+    /// Absolute URL for a path on this node's REST API
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// URL of this node's WebSocket endpoint
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/ws", self.ws_addr)
+    }
+
+    /// Create a space in a fresh folder under the node's temp directory and
+    /// return its key and location.
+    pub async fn create_space(&self, folder: &str) -> Result<(String, PathBuf), AppError> {
+        let dir = self.temp.path().join("spaces").join(folder);
+        std::fs::create_dir_all(&dir)?;
+        let dir = dir.canonicalize()?;
+        let location = dir.to_string_lossy().into_owned();
+
+        self.node.create_space(&location).await?;
+
+        let space = space::Entity::find()
+            .filter(space::Column::Location.eq(location.as_str()))
+            .one(&self.node.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .ok_or_else(|| AppError::NotFound(format!("Space not found: {}", location)))?;
+
+        Ok((space.key, dir))
+    }
+
+    /// Connect to `peer` over the P2P transport
+    pub async fn dial(&self, peer: &TestNode) -> Result<PeerConnection, AppError> {
+        self.p2p.dial(&peer.p2p_addr).await
+    }
+
+    /// Register a new software passkey with this node
+    pub async fn register_passkey(&self) -> Result<RegisteredPasskey, AppError> {
+        RegisteredPasskey::register(&self.node).await
+    }
+}
+
+impl Drop for TestNode {
+    fn drop(&mut self) {
+        for server in &self.servers {
+            server.abort();
+        }
+    }
+}
+
+async fn serve(app: Router) -> Result<(SocketAddr, JoinHandle<()>), AppError> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    Ok((addr, server))
+}
@@ -0,0 +1,69 @@
+use entity::user;
+use errors::AppError;
+use node::api::node::Node;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use url::Url;
+use webauthn_authenticator_rs::AuthenticatorBackend;
+use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+
+use crate::node::TEST_ORIGIN;
+
+const AUTHENTICATOR_TIMEOUT_MS: u32 = 60000;
+
+/// A software passkey that has completed registration with a node
+pub struct RegisteredPasskey {
+    pub did: String,
+    /// Id of the user the registration created
+    pub user_id: i32,
+    pub authenticator: SoftPasskey,
+}
+
+impl RegisteredPasskey {
+    /// Run the full registration ceremony against `node` with a new
+    /// user-verifying software authenticator
+    pub async fn register(node: &Node) -> Result<Self, AppError> {
+        let (challenge, challenge_id) = node.start_webauthn_registration().await?;
+
+        let mut authenticator = SoftPasskey::new(true);
+        let credential = authenticator
+            .perform_register(origin(), challenge.public_key, AUTHENTICATOR_TIMEOUT_MS)
+            .map_err(|e| AppError::Auth(format!("Authenticator registration failed: {:?}", e)))?;
+
+        let (did, _document) = node
+            .finish_webauthn_registration(&challenge_id, credential)
+            .await?;
+        let user_id = user::Entity::find()
+            .filter(user::Column::Did.eq(did.as_str()))
+            .one(&node.db)
+            .await
+            .map_err(|e| AppError::Storage(Box::new(e)))?
+            .ok_or_else(|| AppError::NotFound(format!("No user for {}", did)))?
+            .id;
+
+        Ok(Self {
+            did,
+            user_id,
+            authenticator,
+        })
+    }
+
+    /// Authenticate against `node` and return the new signature counter
+    pub async fn authenticate(&mut self, node: &Node) -> Result<u32, AppError> {
+        let (challenge, challenge_id) = node.start_webauthn_authentication().await?;
+
+        let credential = self
+            .authenticator
+            .perform_auth(origin(), challenge.public_key, AUTHENTICATOR_TIMEOUT_MS)
+            .map_err(|e| AppError::Auth(format!("Authenticator assertion failed: {:?}", e)))?;
+
+        let result = node
+            .finish_webauthn_authentication(&challenge_id, credential)
+            .await?;
+
+        Ok(result.counter())
+    }
+}
+
+fn origin() -> Url {
+    Url::parse(TEST_ORIGIN).expect("Test origin is a valid URL")
+}
@@ -0,0 +1,126 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use event::source::EventListener;
+use event::types::{Event, EventType};
+use flow_testkit::TestCluster;
+use node::modules::p2p::Message;
+use serde_json::json;
+
+struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+impl EventListener for Recorder {
+    fn handle(&self, event: &Event) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_cluster_nodes_are_isolated() {
+    let cluster = TestCluster::start(3).await.unwrap();
+    assert_eq!(cluster.len(), 3);
+
+    let mut dids: Vec<&str> = cluster.nodes.iter().map(|n| n.did()).collect();
+    dids.sort();
+    dids.dedup();
+    assert_eq!(dids.len(), 3, "Each node should have its own identity");
+
+    let (key, dir) = cluster.node(0).create_space("docs").await.unwrap();
+    assert!(dir.starts_with(cluster.node(0).temp.path().canonicalize().unwrap()));
+
+    assert!(cluster.node(0).node.space_manifest(&key, "").await.is_ok());
+    assert!(cluster.node(1).node.space_manifest(&key, "").await.is_err());
+}
+
+#[tokio::test]
+async fn test_cluster_serves_rest_over_loopback() {
+    let cluster = TestCluster::start(2).await.unwrap();
+    let client = reqwest::Client::new();
+
+    for url in cluster.peer_urls() {
+        let response = client
+            .get(format!("{}/api/v1/health", url))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+}
+
+#[tokio::test]
+async fn test_cluster_nodes_dial_each_other_over_loopback() {
+    let cluster = TestCluster::start(2).await.unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    cluster
+        .node(1)
+        .node
+        .subscribe(Box::new(Recorder(received.clone())));
+    assert_eq!(cluster.peer_addrs()[1], cluster.node(1).p2p_addr);
+
+    let mut connection = cluster.node(0).dial(cluster.node(1)).await.unwrap();
+    assert_eq!(connection.peer().did, cluster.node(1).did());
+    connection
+        .send(&Message::new("hello", json!({"n": 1})))
+        .await
+        .unwrap();
+
+    let sender = cluster.node(0).did().to_string();
+    cluster
+        .await_convergence(Duration::from_secs(5), |_| {
+            let received = received.lock().unwrap().clone();
+            let sender = sender.clone();
+            async move {
+                received.iter().any(|event| {
+                    event.event_type == EventType::MessageReceived
+                        && event.properties["peer"] == sender
+                })
+            }
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_register_and_authenticate_passkey() {
+    let cluster = TestCluster::start(1).await.unwrap();
+    let node = cluster.node(0);
+
+    let mut passkey = node.register_passkey().await.unwrap();
+    assert!(passkey.did.starts_with("did:key:"));
+    use sea_orm::EntityTrait;
+    let user = entity::user::Entity::find_by_id(passkey.user_id)
+        .one(&node.node.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.did, passkey.did);
+
+    let counter = passkey.authenticate(&node.node).await.unwrap();
+    assert_eq!(counter, 1);
+}
+
+#[tokio::test]
+async fn test_await_convergence() {
+    let cluster = TestCluster::start(2).await.unwrap();
+
+    for (i, node) in cluster.nodes.iter().enumerate() {
+        node.create_space(&format!("space-{}", i)).await.unwrap();
+    }
+
+    cluster
+        .await_convergence(Duration::from_secs(1), |node| {
+            let db = node.node.db.clone();
+            async move {
+                use sea_orm::{EntityTrait, PaginatorTrait};
+                entity::space::Entity::find().count(&db).await.unwrap() == 1
+            }
+        })
+        .await
+        .unwrap();
+
+    let timed_out = cluster
+        .await_convergence(Duration::from_millis(100), |_| async { false })
+        .await;
+    assert!(timed_out.is_err());
+}
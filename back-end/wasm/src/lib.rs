@@ -0,0 +1,141 @@
+//! wasm-bindgen exports of the SSI helpers the node uses, so web front-ends
+//! can run the same logic. Build with
+//! `cargo build -p wasm --target wasm32-unknown-unknown`.
+//!
+//! Depends only on the portable `identity` crate, never on the node.
+//! Structured values cross the boundary as JSON strings. Each export is a thin
+//! wrapper over a plain Rust function that can be tested natively.
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use identity::jwk;
+use identity::manifest::{self, SignedManifest};
+use identity::peer::{self, PeerDidGenerator};
+use ssi::dids::DIDKey;
+use ssi::jwk::JWK;
+use wasm_bindgen::prelude::*;
+
+/// Convert a CBOR encoded COSE key, as in a WebAuthn attestation, to a JWK
+pub fn cose_to_jwk(cose_key: &[u8]) -> Result<String, String> {
+    let jwk = jwk::cose_to_jwk(cose_key).map_err(|e| e.to_string())?;
+    serde_json::to_string(&jwk).map_err(|e| e.to_string())
+}
+
+/// Generate a did:key from a JWK
+pub fn did_key_from_jwk(jwk_json: &str) -> Result<String, String> {
+    let jwk: JWK = serde_json::from_str(jwk_json).map_err(|e| format!("Invalid JWK: {}", e))?;
+    DIDKey::generate(&jwk)
+        .map(|did| did.into_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Generate a did:peer:0 from raw Ed25519 public key bytes
+pub fn did_peer_from_ed25519(public_key: &[u8]) -> Result<String, String> {
+    PeerDidGenerator::from_ed25519_bytes(public_key).map_err(|e| e.to_string())
+}
+
+/// Generate a did:peer:0 from raw X25519 public key bytes
+pub fn did_peer_from_x25519(public_key: &[u8]) -> Result<String, String> {
+    PeerDidGenerator::from_x25519_bytes(public_key).map_err(|e| e.to_string())
+}
+
+/// Parse a did:peer and return its DID document
+pub fn parse_peer_did(did: &str) -> Result<String, String> {
+    let document = peer::peer_did_document(did).map_err(|e| e.to_string())?;
+    serde_json::to_string(&document).map_err(|e| e.to_string())
+}
+
+/// Verify an Ed25519 signature over `message`
+pub fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<bool, String> {
+    let public_key: [u8; 32] = public_key
+        .try_into()
+        .map_err(|_| "Ed25519 public key must be 32 bytes".to_string())?;
+    let verifying_key = VerifyingKey::from_bytes(&public_key).map_err(|e| e.to_string())?;
+    let signature = Signature::from_slice(signature).map_err(|e| e.to_string())?;
+
+    Ok(verifying_key.verify(message, &signature).is_ok())
+}
+
+/// Verify that a signed space manifest was signed by `signer_did`, a
+/// `did:key`
+pub fn verify_manifest_signature(manifest_json: &str, signer_did: &str) -> Result<bool, String> {
+    let signed: SignedManifest =
+        serde_json::from_str(manifest_json).map_err(|e| format!("Invalid manifest: {}", e))?;
+    let trusted_key = manifest::did_key_public_key(signer_did).map_err(|e| e.to_string())?;
+    manifest::verify_signature(&signed, trusted_key).map_err(|e| e.to_string())
+}
+
+#[wasm_bindgen(js_name = coseToJwk)]
+pub fn wasm_cose_to_jwk(cose_key: &[u8]) -> Result<String, JsError> {
+    cose_to_jwk(cose_key).map_err(|e| JsError::new(&e))
+}
+
+#[wasm_bindgen(js_name = didKeyFromJwk)]
+pub fn wasm_did_key_from_jwk(jwk_json: &str) -> Result<String, JsError> {
+    did_key_from_jwk(jwk_json).map_err(|e| JsError::new(&e))
+}
+
+#[wasm_bindgen(js_name = didPeerFromEd25519)]
+pub fn wasm_did_peer_from_ed25519(public_key: &[u8]) -> Result<String, JsError> {
+    did_peer_from_ed25519(public_key).map_err(|e| JsError::new(&e))
+}
+
+#[wasm_bindgen(js_name = didPeerFromX25519)]
+pub fn wasm_did_peer_from_x25519(public_key: &[u8]) -> Result<String, JsError> {
+    did_peer_from_x25519(public_key).map_err(|e| JsError::new(&e))
+}
+
+#[wasm_bindgen(js_name = parsePeerDid)]
+pub fn wasm_parse_peer_did(did: &str) -> Result<String, JsError> {
+    parse_peer_did(did).map_err(|e| JsError::new(&e))
+}
+
+#[wasm_bindgen(js_name = verifyEd25519)]
+pub fn wasm_verify_ed25519(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, JsError> {
+    verify_ed25519(public_key, message, signature).map_err(|e| JsError::new(&e))
+}
+
+#[wasm_bindgen(js_name = verifyManifestSignature)]
+pub fn wasm_verify_manifest_signature(
+    manifest_json: &str,
+    signer_did: &str,
+) -> Result<bool, JsError> {
+    verify_manifest_signature(manifest_json, signer_did).map_err(|e| JsError::new(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_did_peer_round_trip() {
+        let did = did_peer_from_ed25519(&[7u8; 32]).unwrap();
+        let document: serde_json::Value =
+            serde_json::from_str(&parse_peer_did(&did).unwrap()).unwrap();
+
+        assert_eq!(document["id"], did);
+        assert!(did_peer_from_ed25519(&[7u8; 16]).is_err());
+        assert!(parse_peer_did("did:peer:9abc").is_err());
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        let signing_key = SigningKey::from_bytes(&[1u8; 32]);
+        let public_key = signing_key.verifying_key().to_bytes();
+        let signature = signing_key.sign(b"hello").to_bytes();
+
+        assert!(verify_ed25519(&public_key, b"hello", &signature).unwrap());
+        assert!(!verify_ed25519(&public_key, b"tampered", &signature).unwrap());
+        assert!(verify_ed25519(&public_key[..16], b"hello", &signature).is_err());
+    }
+
+    #[test]
+    fn test_cose_to_jwk_rejects_garbage() {
+        assert!(cose_to_jwk(b"{}").is_err());
+        assert!(did_key_from_jwk("not json").is_err());
+    }
+}